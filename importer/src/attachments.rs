@@ -0,0 +1,145 @@
+//! Copies files embedded in notes (`![[photo.png]]`) next to the output
+//! snapshot so imported blocks keep working references.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use sightline_lib::vault::is_markdown;
+use walkdir::WalkDir;
+
+use crate::markup;
+
+/// Name of the directory, beside the output snapshot, that receives copies.
+pub const ASSETS_DIR: &str = "assets";
+
+pub struct AttachmentCopier {
+    /// Canonical vault root; embeds may only resolve to files beneath it.
+    vault: PathBuf,
+    assets_dir: PathBuf,
+    /// Non-note files in the vault keyed by file name, for Obsidian-style
+    /// shortest-path embeds.
+    by_name: HashMap<String, Vec<PathBuf>>,
+    copied: HashMap<PathBuf, String>,
+    used_names: HashSet<String>,
+    pub missing: Vec<String>,
+}
+
+impl AttachmentCopier {
+    pub fn new(vault: &Path, assets_dir: PathBuf) -> Result<Self> {
+        let mut by_name: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        let walker = WalkDir::new(vault)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !is_hidden(entry.file_name()));
+        for entry in walker {
+            let entry =
+                entry.with_context(|| format!("failed to walk vault '{}'", vault.display()))?;
+            if !entry.file_type().is_file() || is_markdown(entry.path()) {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            by_name.entry(name).or_default().push(entry.into_path());
+        }
+
+        let vault = vault
+            .canonicalize()
+            .with_context(|| format!("failed to resolve vault '{}'", vault.display()))?;
+        Ok(Self {
+            vault,
+            assets_dir,
+            by_name,
+            copied: HashMap::new(),
+            used_names: HashSet::new(),
+            missing: Vec::new(),
+        })
+    }
+
+    pub fn copied_count(&self) -> usize {
+        self.copied.len()
+    }
+
+    /// Copies every attachment embedded in `text` and rewrites the embeds as
+    /// markdown images pointing into the assets directory. Embeds that can't
+    /// be resolved are left as they are and recorded in `missing`.
+    pub fn rewrite(&mut self, text: &str) -> Result<String> {
+        markup::replace_embeds(text, |target| {
+            let Some(source) = self.resolve(target) else {
+                if !self.missing.iter().any(|missing| missing == target) {
+                    self.missing.push(target.to_string());
+                }
+                return Ok(None);
+            };
+
+            let asset = self.copy(&source)?;
+            Ok(Some(format!("![{target}](<{ASSETS_DIR}/{asset}>)")))
+        })
+    }
+
+    /// Finds the file an embed names, either as a path from the vault root
+    /// or by file name alone. `../` and absolute targets that lead out of
+    /// the vault are never followed.
+    fn resolve(&self, target: &str) -> Option<PathBuf> {
+        let direct = self.vault.join(target).canonicalize().ok();
+        if let Some(direct) = direct.filter(|direct| direct.starts_with(&self.vault)) {
+            if direct.is_file() && !is_markdown(&direct) {
+                return Some(direct);
+            }
+        }
+
+        let name = Path::new(target).file_name()?.to_string_lossy();
+        self.by_name.get(name.as_ref())?.first().cloned()
+    }
+
+    fn copy(&mut self, source: &Path) -> Result<String> {
+        if let Some(asset) = self.copied.get(source) {
+            return Ok(asset.clone());
+        }
+
+        let asset = self.unique_name(source);
+        fs::create_dir_all(&self.assets_dir).with_context(|| {
+            format!(
+                "failed to create assets directory '{}'",
+                self.assets_dir.display()
+            )
+        })?;
+        fs::copy(source, self.assets_dir.join(&asset))
+            .with_context(|| format!("failed to copy attachment '{}'", source.display()))?;
+
+        self.used_names.insert(asset.clone());
+        self.copied.insert(source.to_path_buf(), asset.clone());
+        Ok(asset)
+    }
+
+    /// Different attachments can share a file name in separate folders;
+    /// later ones get a numeric suffix, e.g. `photo-1.png`.
+    fn unique_name(&self, source: &Path) -> String {
+        let name = source
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !self.used_names.contains(&name) {
+            return name;
+        }
+
+        let stem = source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = source
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        (1..)
+            .map(|n| format!("{stem}-{n}{extension}"))
+            .find(|candidate| !self.used_names.contains(candidate))
+            .expect("unbounded suffixes")
+    }
+}
+
+fn is_hidden(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
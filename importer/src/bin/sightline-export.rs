@@ -0,0 +1,120 @@
+//! Writes a timeline snapshot out as a markdown vault that the importer
+//! reads back (day files under `journal/`, project files under
+//! `projects/`), as JSON Lines for jq, duckdb and backups, or as an
+//! iCalendar file to overlay on a calendar app.
+
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use sightline_lib::markdown::TagRendering;
+use sightline_lib::timeline::{SensitiveContent, Timeline};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "sightline-export",
+    author,
+    version,
+    about = "Export a Sightline timeline snapshot to markdown, JSON Lines or iCalendar",
+    long_about = None
+)]
+struct Cli {
+    /// Timeline snapshot to export
+    #[arg(long, value_name = "SNAPSHOT")]
+    timeline: PathBuf,
+
+    /// Directory to write markdown into, replacing files of the same name;
+    /// otherwise the file to write, or `-` for JSON Lines on stdout
+    #[arg(long, value_name = "PATH")]
+    out: PathBuf,
+
+    /// What to write
+    #[arg(long, value_enum, default_value_t = Format::Markdown)]
+    format: Format,
+
+    /// How block tags are written in markdown
+    #[arg(long, value_enum, default_value_t = Tags::Frontmatter)]
+    tags: Tags,
+
+    /// Write blocks with sensitive tags instead of leaving them out
+    #[arg(long)]
+    include_sensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Day and project files the importer reads back
+    Markdown,
+    /// A tag registry header line, then one block per line
+    Jsonl,
+    /// An event per day and a to-do per due-dated task
+    Ical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Tags {
+    /// A `tags:` list in each file's frontmatter
+    Frontmatter,
+    /// A line of `#tags` after each tagged block
+    Inline,
+    /// No tags
+    Omit,
+}
+
+impl From<Tags> for TagRendering {
+    fn from(tags: Tags) -> Self {
+        match tags {
+            Tags::Frontmatter => Self::Frontmatter,
+            Tags::Inline => Self::Inline,
+            Tags::Omit => Self::Omit,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let timeline = Timeline::load_from_path(&cli.timeline)
+        .with_context(|| format!("failed to load '{}'", cli.timeline.display()))?;
+    let sensitive = if cli.include_sensitive {
+        SensitiveContent::Included
+    } else {
+        SensitiveContent::Masked
+    };
+    if cli.format == Format::Ical {
+        let summary = timeline
+            .export_ical(&cli.out, sensitive)
+            .with_context(|| format!("failed to export to '{}'", cli.out.display()))?;
+        println!(
+            "Wrote {} events and {} to-dos to {}",
+            summary.events,
+            summary.todos,
+            cli.out.display()
+        );
+        return Ok(());
+    }
+
+    if cli.format == Format::Jsonl {
+        // Progress goes to stderr so stdout can be piped.
+        let blocks = if cli.out == Path::new("-") {
+            timeline.write_jsonl(BufWriter::new(io::stdout().lock()), sensitive)
+        } else {
+            timeline.export_jsonl(&cli.out, sensitive)
+        }
+        .with_context(|| format!("failed to export to '{}'", cli.out.display()))?;
+        eprintln!("Wrote {blocks} blocks to {}", cli.out.display());
+        return Ok(());
+    }
+
+    let summary = timeline
+        .export_markdown(&cli.out, cli.tags.into(), sensitive)
+        .with_context(|| format!("failed to export to '{}'", cli.out.display()))?;
+    println!(
+        "Wrote {} blocks to {} files ({} in projects/) under {}",
+        summary.blocks,
+        summary.files,
+        summary.projects,
+        cli.out.display()
+    );
+    Ok(())
+}
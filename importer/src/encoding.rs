@@ -0,0 +1,14 @@
+//! Reading notes that older tools saved as UTF-16 or Windows-1252 instead
+//! of UTF-8. Decoding lives in the app crate so the in-app vault import
+//! reads notes the same way.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+pub use sightline_lib::encoding::{Decoded, Encoding};
+
+pub fn read_text(path: &Path) -> Result<Decoded> {
+    sightline_lib::encoding::read_text(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))
+}
@@ -0,0 +1,97 @@
+//! `--include` / `--exclude` glob filtering for vault traversal.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Patterns are matched against a note's path relative to the vault (e.g.
+/// `projects/archive/old.md`) and relative to its section directory (e.g.
+/// `archive/old.md`), so `archive/**` works without naming the section.
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: build_set(include)?,
+            exclude: build_set(exclude)?,
+        })
+    }
+
+    /// Whether a note at `relative` inside `section` should be imported.
+    pub fn allows_file(&self, section: &str, relative: &Path) -> bool {
+        let in_vault = Path::new(section).join(relative);
+        let matches = |set: &GlobSet| set.is_match(relative) || set.is_match(&in_vault);
+
+        if self.exclude.as_ref().is_some_and(matches) {
+            return false;
+        }
+        self.include.as_ref().is_none_or(matches)
+    }
+
+    /// Whether the traversal can skip the directory at `relative` entirely.
+    pub fn excludes_dir(&self, section: &str, relative: &Path) -> bool {
+        let in_vault = Path::new(section).join(relative);
+        self.exclude
+            .as_ref()
+            .is_some_and(|set| set.is_match(relative) || set.is_match(&in_vault))
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).with_context(|| format!("invalid glob '{pattern}'"))?;
+        builder.add(glob);
+        // `archive/**` should also prune the `archive` directory itself.
+        if let Some(dir) = pattern.strip_suffix("/**") {
+            builder.add(Glob::new(dir).with_context(|| format!("invalid glob '{pattern}'"))?);
+        }
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclude_matches_section_or_vault_relative_paths() {
+        let filter = PathFilter::new(
+            &[],
+            &[
+                "templates/**".to_string(),
+                "projects/archive/**".to_string(),
+            ],
+        )
+        .expect("filter");
+
+        assert!(!filter.allows_file("journal", Path::new("templates/daily.md")));
+        assert!(!filter.allows_file("projects", Path::new("archive/2019/old.md")));
+        assert!(filter.allows_file("journal", Path::new("archive/2019-01-01.md")));
+        assert!(filter.excludes_dir("projects", Path::new("archive")));
+        assert!(!filter.excludes_dir("projects", Path::new("active")));
+    }
+
+    #[test]
+    fn include_restricts_to_matching_files() {
+        let filter = PathFilter::new(&["**/Work/**".to_string()], &[]).expect("filter");
+
+        assert!(filter.allows_file("projects", Path::new("Work/plan.md")));
+        assert!(!filter.allows_file("projects", Path::new("Home/plan.md")));
+        assert!(PathFilter::default().allows_file("projects", Path::new("Home/plan.md")));
+    }
+
+    #[test]
+    fn invalid_glob_is_an_error() {
+        assert!(PathFilter::new(&[], &["[".to_string()]).is_err());
+    }
+}
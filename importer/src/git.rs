@@ -0,0 +1,66 @@
+//! Reads note dates from git history, which survives a fresh clone where
+//! filesystem modification times do not.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+
+/// Fails when `git` isn't installed or `dir` isn't inside a work tree.
+pub fn ensure_repository(dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output()
+        .context("failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{}' is not inside a git repository: {}",
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The committer date of the last commit touching `path`, or `None` when the
+/// file has never been committed.
+pub fn last_commit_date(path: &Path) -> Result<Option<NaiveDate>> {
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(anyhow!("'{}' is not a file path", path.display()));
+    };
+
+    // Run from the file's own directory so the pathspec is relative and
+    // unaffected by symlinks between the caller's path and the work tree.
+    // It's taken literally, so a name like `[a]b.md` matches only itself.
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["log", "-1", "--format=%cs", "--"])
+        .arg(file_name)
+        .env("GIT_LITERAL_PATHSPECS", "1")
+        .output()
+        .context("failed to run git log")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git log failed for '{}': {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let date = stdout.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(Some)
+        .with_context(|| format!("git printed an unexpected commit date '{date}'"))
+}
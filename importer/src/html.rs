@@ -0,0 +1,297 @@
+//! Exported HTML notes (e.g. from Apple Notes exporters): the title, any
+//! embedded creation date, and the body flattened to markdown-ish text.
+
+use chrono::{DateTime, NaiveDate};
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Selector};
+use sightline_lib::html::first_text;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HtmlNote {
+    pub title: Option<String>,
+    /// From a `<meta name="created">`-style tag or the first `<time datetime>`.
+    pub created: Option<NaiveDate>,
+    pub text: String,
+}
+
+/// `<meta name=...>` values exporters use for a note's creation date.
+const DATE_META_NAMES: [&str; 5] = [
+    "created",
+    "creation-date",
+    "date",
+    "dcterms.created",
+    "article:published_time",
+];
+
+/// Elements whose contents aren't part of the note's text.
+const SKIPPED_ELEMENTS: [&str; 4] = ["head", "script", "style", "template"];
+
+pub fn parse(html: &str) -> HtmlNote {
+    let document = Html::parse_document(html);
+    let mut out = Converter::default();
+    out.children(document.root_element());
+    HtmlNote {
+        title: first_text(&document, "title"),
+        created: created(&document),
+        text: out.finish(),
+    }
+}
+
+fn created(document: &Html) -> Option<NaiveDate> {
+    let meta = Selector::parse("meta").expect("valid meta selector");
+    let time = Selector::parse("time[datetime]").expect("valid time selector");
+    document
+        .select(&meta)
+        .find(|meta| {
+            let element = meta.value();
+            element
+                .attr("name")
+                .or_else(|| element.attr("property"))
+                .is_some_and(|name| {
+                    DATE_META_NAMES
+                        .iter()
+                        .any(|meta| name.eq_ignore_ascii_case(meta))
+                })
+        })
+        .and_then(|meta| meta.value().attr("content"))
+        .or_else(|| {
+            let time = document.select(&time).next()?;
+            time.value().attr("datetime")
+        })
+        .and_then(parse_date)
+}
+
+/// Accepts RFC 3339, RFC 2822, or anything starting with `YYYY-MM-DD`.
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .map(|datetime| datetime.date_naive())
+        .ok()
+        .or_else(|| {
+            value
+                .get(..10)
+                .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+        })
+}
+
+/// Builds the text output, collapsing whitespace the way a browser would
+/// outside `<pre>`.
+#[derive(Default)]
+struct Converter {
+    out: String,
+    links: Vec<Option<String>>,
+    lists: Vec<Option<usize>>,
+    pre_depth: usize,
+}
+
+impl Converter {
+    fn children(&mut self, parent: ElementRef) {
+        for child in parent.children() {
+            if let Some(text) = child.value().as_text() {
+                self.text(text);
+            } else if let Some(element) = ElementRef::wrap(child) {
+                if SKIPPED_ELEMENTS.contains(&element.value().name()) {
+                    continue;
+                }
+                self.tag(element.value(), false);
+                self.children(element);
+                self.tag(element.value(), true);
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = text.replace('\u{a0}', " ");
+        if self.pre_depth > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+        for (index, word) in text.split_whitespace().enumerate() {
+            let starts_with_space = index > 0 || text.starts_with(char::is_whitespace);
+            if starts_with_space && !self.out.is_empty() && !self.out.ends_with(['\n', ' ']) {
+                self.out.push(' ');
+            }
+            self.out.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace)
+            && !text.trim().is_empty()
+            && !self.out.ends_with('\n')
+        {
+            self.out.push(' ');
+        }
+    }
+
+    fn tag(&mut self, tag: &Element, closing: bool) {
+        match (tag.name(), closing) {
+            ("br", false) => self.out.push('\n'),
+            ("p" | "div" | "tr" | "blockquote" | "table", _) => self.block_break(),
+            ("hr", false) => {
+                self.block_break();
+                self.out.push_str("---\n");
+            }
+            (heading @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6"), closing) => {
+                self.block_break();
+                if !closing {
+                    let level = heading[1..].parse().unwrap_or(1);
+                    self.out.push_str(&"#".repeat(level));
+                    self.out.push(' ');
+                }
+            }
+            ("ul", false) => {
+                self.block_break();
+                self.lists.push(None);
+            }
+            ("ol", false) => {
+                self.block_break();
+                self.lists.push(Some(0));
+            }
+            ("ul" | "ol", true) => {
+                self.lists.pop();
+                self.block_break();
+            }
+            ("li", false) => {
+                self.line_break();
+                let depth = self.lists.len().saturating_sub(1);
+                self.out.push_str(&"  ".repeat(depth));
+                match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        self.out.push_str(&format!("{number}. "));
+                    }
+                    _ => self.out.push_str("- "),
+                }
+                if tag.classes().any(|class| class == "checked") {
+                    self.out.push_str("[x] ");
+                } else if tag.classes().any(|class| class == "unchecked") {
+                    self.out.push_str("[ ] ");
+                }
+            }
+            ("li", true) => self.line_break(),
+            ("b" | "strong", _) => self.out.push_str("**"),
+            ("i" | "em", _) => self.out.push('_'),
+            ("pre", false) => {
+                self.block_break();
+                self.out.push_str("```\n");
+                self.pre_depth += 1;
+            }
+            ("pre", true) => {
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+                self.line_break();
+                self.out.push_str("```\n");
+            }
+            ("a", false) => {
+                let href = tag
+                    .attr("href")
+                    .filter(|href| !href.starts_with('#'))
+                    .map(str::to_string);
+                if href.is_some() {
+                    self.out.push('[');
+                }
+                self.links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = self.links.pop() {
+                    self.out.push_str(&format!("]({href})"));
+                }
+            }
+            // Inline `data:` images would bury the note in base64, so only
+            // linked files are kept.
+            ("img", false) => {
+                if let Some(src) = tag.attr("src").filter(|src| !src.starts_with("data:")) {
+                    let alt = tag.attr("alt").unwrap_or_default();
+                    self.out.push_str(&format!("![{alt}]({src})"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn line_break(&mut self) {
+        trim_trailing_spaces(&mut self.out);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn block_break(&mut self) {
+        self.line_break();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") && self.lists.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut text = String::new();
+        let mut blank_run = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank_run += 1;
+                if blank_run > 1 {
+                    continue;
+                }
+            } else {
+                blank_run = 0;
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        let trimmed = text.trim_matches('\n');
+        if trimmed.is_empty() {
+            String::new()
+        } else {
+            format!("{trimmed}\n")
+        }
+    }
+}
+
+fn trim_trailing_spaces(text: &mut String) {
+    let trimmed = text.trim_end_matches(' ').len();
+    text.truncate(trimmed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_title_date_and_flattens_body() {
+        let html = r#"<!DOCTYPE html><html><head>
+            <title>Trip &amp; plans</title>
+            <meta name="created" content="2024-03-04T10:15:00Z">
+            <style>body { color: red }</style>
+        </head><body>
+            <h1>Trip &amp; plans</h1>
+            <div>Pack <b>boots</b> and
+              <a href="https://example.com/map">the map</a></div>
+            <!-- exporter comment -->
+            <ul class="checklist"><li class="checked">Tickets</li><li class="unchecked">Hotel</li></ul>
+            <ol><li>Drive</li><li>Hike</li></ol>
+            <div><img src="data:image/png;base64,AAAA"><img src="Attachments/trail.jpg" alt="trail"></div>
+        </body></html>"#;
+
+        let note = parse(html);
+        assert_eq!(note.title.as_deref(), Some("Trip & plans"));
+        assert_eq!(note.created, NaiveDate::from_ymd_opt(2024, 3, 4));
+        assert_eq!(
+            note.text,
+            "# Trip & plans\n\n\
+             Pack **boots** and [the map](https://example.com/map)\n\n\
+             - [x] Tickets\n- [ ] Hotel\n\n\
+             1. Drive\n2. Hike\n\n\
+             ![trail](Attachments/trail.jpg)\n"
+        );
+    }
+
+    #[test]
+    fn parse_falls_back_to_time_element_and_decodes_entities() {
+        let note = parse(
+            "<p>Met <time datetime=\"Tue, 1 Jul 2003 10:52:37 +0200\">then</time> &lt;3&#x21;</p>",
+        );
+        assert_eq!(note.title, None);
+        assert_eq!(note.created, NaiveDate::from_ymd_opt(2003, 7, 1));
+        assert_eq!(note.text, "Met then <3!\n");
+        assert_eq!(parse("&bogus; & more").text, "&bogus; & more\n");
+    }
+}
@@ -0,0 +1,139 @@
+//! Google Keep notes from a Takeout archive: one `.json` file per note, with
+//! an `.html` rendering beside it (older archives have only the HTML).
+
+use chrono::{DateTime, NaiveDate};
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct KeepNote {
+    pub title: String,
+    pub text_content: String,
+    pub list_content: Vec<ListItem>,
+    pub labels: Vec<Label>,
+    pub attachments: Vec<Attachment>,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    pub is_trashed: bool,
+    pub created_timestamp_usec: Option<i64>,
+    pub user_edited_timestamp_usec: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ListItem {
+    pub text: String,
+    pub is_checked: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Attachment {
+    pub file_path: String,
+    pub mimetype: String,
+}
+
+impl KeepNote {
+    /// When the note was created, falling back to its last edit. Takeout
+    /// timestamps are microseconds since the epoch, read as UTC.
+    pub fn date(&self) -> Option<NaiveDate> {
+        self.created_timestamp_usec
+            .filter(|&usec| usec > 0)
+            .or(self.user_edited_timestamp_usec)
+            .and_then(DateTime::from_timestamp_micros)
+            .map(|created| created.date_naive())
+    }
+
+    /// The note as block text: the title as a heading, then the body, with
+    /// checklists written as `- [x]` / `- [ ]` lines and image attachments
+    /// as `![[file]]` embeds.
+    pub fn to_text(&self) -> String {
+        let mut sections = Vec::new();
+        let title = self.title.trim();
+        if !title.is_empty() {
+            sections.push(format!("# {title}"));
+        }
+
+        let body = self.text_content.trim_end();
+        if !body.is_empty() {
+            sections.push(body.to_string());
+        }
+        if !self.list_content.is_empty() {
+            let items: Vec<String> = self
+                .list_content
+                .iter()
+                .map(|item| {
+                    let mark = if item.is_checked { 'x' } else { ' ' };
+                    format!("- [{mark}] {}", item.text.trim())
+                })
+                .collect();
+            sections.push(items.join("\n"));
+        }
+
+        let images: Vec<String> = self
+            .attachments
+            .iter()
+            .filter(|attachment| attachment.mimetype.starts_with("image/"))
+            .map(|attachment| format!("![[{}]]", attachment.file_path))
+            .collect();
+        if !images.is_empty() {
+            sections.push(images.join("\n"));
+        }
+
+        let mut text = sections.join("\n\n");
+        text.push('\n');
+        text
+    }
+}
+
+pub fn parse(json: &str) -> serde_json::Result<KeepNote> {
+    serde_json::from_str(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_keep_titles_checklists_and_dates() {
+        let note = parse(
+            r#"{
+                "title": "Groceries",
+                "textContent": "",
+                "listContent": [
+                    {"text": "Milk", "isChecked": true},
+                    {"text": "Eggs ", "isChecked": false}
+                ],
+                "labels": [{"name": "Home"}],
+                "attachments": [
+                    {"filePath": "1a2b.jpg", "mimetype": "image/jpeg"},
+                    {"filePath": "memo.3gp", "mimetype": "audio/3gpp"}
+                ],
+                "isPinned": true,
+                "createdTimestampUsec": 1709547300000000,
+                "userEditedTimestampUsec": 1709900000000000
+            }"#,
+        )
+        .expect("parse note");
+
+        assert_eq!(note.date(), NaiveDate::from_ymd_opt(2024, 3, 4));
+        assert_eq!(
+            note.to_text(),
+            "# Groceries\n\n- [x] Milk\n- [ ] Eggs\n\n![[1a2b.jpg]]\n"
+        );
+        assert!(note.is_pinned && !note.is_archived);
+        assert_eq!(note.labels[0].name, "Home");
+
+        let untitled =
+            parse(r#"{"textContent": "Call Sam\n", "userEditedTimestampUsec": 1709547300000000}"#)
+                .expect("parse note");
+        assert_eq!(untitled.to_text(), "Call Sam\n");
+        assert_eq!(untitled.date(), NaiveDate::from_ymd_opt(2024, 3, 4));
+    }
+}
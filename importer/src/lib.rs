@@ -1,15 +1,54 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
 use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow};
 use chrono::{DateTime, NaiveDate, Utc};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use rayon::prelude::*;
 use serde::Serialize;
-use sightline_lib::timeline::{Tag, TagRegistry, TaggedBlock};
+use sightline_lib::clock::SharedClock;
+use sightline_lib::markdown;
+use sightline_lib::obsidian::DailyNotesSettings;
+use sightline_lib::timeline::{
+    self, BlockSource, SensitiveContent, SnapshotEncoding, Tag, TagRegistry, TaggedBlock,
+};
+pub use sightline_lib::vault::DEFAULT_DATE_KEYS;
+use sightline_lib::vault::{
+    self, JournalPeriod, infer_date_from_path, is_markdown, normalize_tag_segment,
+    parse_date_value, parse_journal_date, parse_journal_period,
+};
+use sightline_lib::wal;
 use tracing::info;
 use walkdir::WalkDir;
 
+mod attachments;
+mod encoding;
+mod filter;
+mod git;
+mod html;
+mod keep;
+mod markup;
+mod notion;
+mod progress;
+mod split;
+mod table;
+mod tag_rules;
+
+use attachments::AttachmentCopier;
+use filter::PathFilter;
+use progress::{Progress, ProgressMode};
+use split::DaySection;
+use tag_rules::TagRules;
+
+pub use encoding::Encoding;
+pub use table::ColumnMap;
+
 #[derive(Debug, Parser, Clone)]
 #[command(
     name = "sightline-importer",
@@ -19,383 +58,3684 @@ use walkdir::WalkDir;
     long_about = None
 )]
 pub struct Cli {
-    /// Path to the source vault (e.g., an Obsidian directory)
-    #[arg(long, value_name = "SOURCE_DIR")]
+    /// Path to the source vault (e.g., an Obsidian directory), or a CSV/TSV file, HTML notes folder, or timeline snapshot with `--format`
+    #[arg(long, value_name = "SOURCE")]
     pub source: PathBuf,
 
+    /// What kind of source to read
+    #[arg(long, value_enum, default_value_t = SourceFormat::Vault)]
+    pub format: SourceFormat,
+
+    /// Another timeline snapshot to merge into a `--format sightline` source
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub merge_with: Option<PathBuf>,
+
+    /// Columns to read from a CSV/TSV source, e.g. `date=Day,text=col3,tags=Labels`
+    #[arg(long, value_name = "FIELD=COLUMN,...")]
+    pub map: Option<ColumnMap>,
+
+    /// Separator between tags in the mapped tags column
+    #[arg(long, value_name = "DELIMITER", default_value = ",")]
+    pub tag_delimiter: String,
+
     /// Destination file for the generated timeline snapshot
     #[arg(long, value_name = "OUTPUT_FILE")]
     pub output: PathBuf,
-}
 
-#[derive(Debug, Serialize)]
-struct ImportSnapshot {
-    version: u64,
-    blocks: Vec<TaggedBlock>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    tag_registry: Vec<Tag>,
+    /// Encoding of the snapshot; `cbor`, `jsonl` and `sqlite` are more compact for very large imports
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub output_format: OutputFormat,
+
+    /// How to handle notes whose content appears more than once in the vault
+    #[arg(long, value_enum, default_value_t = DedupPolicy::KeepBoth)]
+    pub dedup: DedupPolicy,
+
+    /// How to carry `[[wikilinks]]` from notes into the timeline
+    #[arg(long, value_enum, default_value_t = WikilinkMode::Ignore)]
+    pub wikilinks: WikilinkMode,
+
+    /// Warn when the vault has no `.obsidian/daily-notes.json`. Its folder
+    /// and date format are used whenever it is present.
+    #[arg(long)]
+    pub obsidian_daily_notes: bool,
+
+    /// Only import notes matching this glob (repeatable), e.g. `**/Work/**`
+    #[arg(long = "include", value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip notes matching this glob (repeatable), e.g. `templates/**`
+    #[arg(long = "exclude", value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Tag notes using regex → tag mappings from a TOML file (see `[[rule]]` tables)
+    #[arg(long, value_name = "RULES_FILE")]
+    pub tag_rules: Option<PathBuf>,
+
+    /// Frontmatter keys that date a note, tried in order before the file name
+    #[arg(
+        long,
+        value_name = "KEY,...",
+        value_delimiter = ',',
+        default_values = DEFAULT_DATE_KEYS
+    )]
+    pub date_keys: Vec<String>,
+
+    /// Date project notes by their last git commit instead of file modification time
+    #[arg(long)]
+    pub dates_from_git: bool,
+
+    /// Root tag for project notes, under which their folders become tags, e.g. `area` or `work:projects`
+    #[arg(long, value_name = "TAG", default_value = DEFAULT_PROJECT_TAG)]
+    pub project_tag: String,
+
+    /// Tag at most this many folder levels below the project tag; deeper notes get the deepest tag
+    #[arg(long, value_name = "DEPTH")]
+    pub folder_tag_depth: Option<usize>,
+
+    /// Give folders with this name no tag of their own (repeatable), e.g. `attachments`
+    #[arg(long = "skip-folder", value_name = "NAME")]
+    pub skip_folders: Vec<String>,
+
+    /// Split vault notes with date headings (e.g. `## 2024-03-10`) into one journal block per day
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub split_by: Option<SplitBy>,
+
+    /// What to do with journal notes and table rows whose date can't be read
+    #[arg(long, value_enum, default_value_t = UndatedPolicy::Skip)]
+    pub undated: UndatedPolicy,
+
+    /// Show a progress bar on stderr while importing
+    #[arg(long, conflicts_with = "progress_json")]
+    pub progress: bool,
+
+    /// Stream newline-delimited JSON progress events to stdout
+    #[arg(long)]
+    pub progress_json: bool,
+
+    /// Write machine-readable import metrics (counts, phase timings, warnings) as JSON
+    #[arg(long, value_name = "METRICS_FILE")]
+    pub metrics_out: Option<PathBuf>,
+
+    /// Write a JSON report of skipped files, undated notes, tags, and blocks per directory
+    #[arg(long, value_name = "REPORT_FILE")]
+    pub report: Option<PathBuf>,
 }
 
-pub fn run(cli: Cli) -> Result<()> {
-    let source_root = ensure_directory(&cli.source)
-        .with_context(|| format!("source directory '{}' is invalid", cli.source.display()))?;
-
-    let journal_dir = source_root.join("journal");
-    ensure_directory(&journal_dir)
-        .with_context(|| format!("journal directory '{}' is missing", journal_dir.display()))?;
+/// Root tag of project notes unless `--project-tag` says otherwise.
+pub const DEFAULT_PROJECT_TAG: &str = "project";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SourceFormat {
+    /// A directory with `journal/` and `projects/` notes
+    #[default]
+    Vault,
+    /// A comma-separated file with a header row; one block per row
+    Csv,
+    /// A tab-separated file with a header row; one block per row
+    Tsv,
+    /// A folder of exported HTML notes (e.g. from Apple Notes); one block per note
+    Html,
+    /// An unzipped Notion "Markdown & CSV" export; one block per page
+    Notion,
+    /// The `Keep` folder of a Google Takeout archive; one block per note
+    Keep,
+    /// A Sightline timeline snapshot, merged with `--merge-with` if given
+    Sightline,
+}
 
-    let projects_dir = source_root.join("projects");
-    ensure_directory(&projects_dir)
-        .with_context(|| format!("projects directory '{}' is missing", projects_dir.display()))?;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty-printed JSON, like the app's own timeline file
+    #[default]
+    Json,
+    /// Binary CBOR
+    Cbor,
+    /// A header line followed by one JSON block per line
+    Jsonl,
+    /// An SQLite database with a row per block
+    Sqlite,
+}
 
-    if let Some(parent) = cli.output.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent).with_context(|| {
-                format!(
-                    "failed to create output parent directory '{}'",
-                    parent.display()
-                )
-            })?;
+impl From<OutputFormat> for SnapshotEncoding {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Json => SnapshotEncoding::Json,
+            OutputFormat::Cbor => SnapshotEncoding::Cbor,
+            OutputFormat::Jsonl => SnapshotEncoding::Jsonl,
+            OutputFormat::Sqlite => SnapshotEncoding::Sqlite,
         }
     }
+}
 
-    let mut registry = TagRegistry::new();
-    let mut blocks = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SplitBy {
+    /// Headings whose title is a date; each starts a new day
+    DateHeading,
+}
 
-    collect_journal_entries(&journal_dir, &mut registry, &mut blocks)?;
-    collect_project_entries(&projects_dir, &mut registry, &mut blocks)?;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum UndatedPolicy {
+    /// Leave them out and list them in the report
+    #[default]
+    Skip,
+    /// Date them on the day of the import
+    Today,
+    /// Date them by the file's modification time
+    Mtime,
+    /// Stop the import at the first one
+    Error,
+}
 
-    blocks.sort_by(|a, b| a.date.cmp(&b.date));
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DedupPolicy {
+    /// Keep the first block and drop later blocks with identical content
+    Skip,
+    /// Keep the first block and fold the tags of later duplicates into it
+    MergeTags,
+    /// Keep every block, even when content repeats
+    #[default]
+    KeepBoth,
+}
 
-    let mut tags: Vec<Tag> = registry.iter().cloned().collect();
-    tags.sort_by(|a, b| a.id.cmp(&b.id));
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WikilinkMode {
+    /// Leave wikilinks as plain text
+    #[default]
+    Ignore,
+    /// Tag blocks with `#link:<target>` for every wikilink
+    Tags,
+    /// Record wikilink targets in each block's `links` list
+    Links,
+}
 
-    let snapshot = ImportSnapshot {
-        version: 0,
-        blocks,
-        tag_registry: tags,
-    };
+#[derive(Debug, Default, Serialize)]
+pub struct ImportMetrics {
+    pub files_processed: usize,
+    pub blocks_created: usize,
+    pub tags_created: usize,
+    pub attachments_copied: usize,
+    pub duplicates: usize,
+    pub phases: Vec<PhaseTiming>,
+    pub warnings: Vec<String>,
+}
 
-    let json = serde_json::to_vec_pretty(&snapshot)?;
-    fs::write(&cli.output, json)
-        .with_context(|| format!("failed to write snapshot to '{}'", cli.output.display()))?;
+#[derive(Debug, Serialize)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub duration_ms: f64,
+}
 
-    info!(
-        target: "sightline::importer",
-        source = %source_root.display(),
-        output = %cli.output.display(),
-        blocks = snapshot.blocks.len(),
-        tags = snapshot.tag_registry.len(),
-        "importer completed"
-    );
+/// What the importer did with each part of the vault. Paths are relative to
+/// the source directory.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub skipped: Vec<SkippedFile>,
+    /// Journal notes skipped because no date could be read from their path,
+    /// with `--undated skip`. Table rows appear as `file.csv:line`.
+    pub unparseable_dates: Vec<PathBuf>,
+    /// Project notes without a frontmatter date that git has no history
+    /// for. Only filled with `--dates-from-git`.
+    pub uncommitted_notes: Vec<PathBuf>,
+    /// Full `a:b` names of every interned tag, sorted.
+    pub tags: Vec<String>,
+    /// Where each imported journal and project note got its date.
+    pub date_sources: BTreeMap<PathBuf, DateSource>,
+    /// Files that weren't UTF-8 and were decoded with a detected encoding.
+    /// Worth a look for garbled characters.
+    pub decoded_files: Vec<DecodedFile>,
+    /// Blocks read from each directory, or from each table file, before
+    /// deduplication.
+    pub blocks_per_directory: BTreeMap<PathBuf, usize>,
+}
 
-    Ok(())
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum DateSource {
+    /// The first of `--date-keys` present in the note's frontmatter.
+    Frontmatter {
+        key: String,
+    },
+    FileName,
+    /// A dated folder, e.g. `2024/03/04.md`.
+    Path,
+    Git,
+    ModifiedTime,
+    /// No date could be read; `--undated today` dated it on the import day.
+    Today,
+    /// Split from a longer note by `--split-by date-heading`; each block
+    /// has its own heading's date.
+    Heading,
 }
 
-fn collect_journal_entries(
-    journal_dir: &Path,
-    registry: &mut TagRegistry,
-    blocks: &mut Vec<TaggedBlock>,
-) -> Result<()> {
-    let journal_tag = registry
-        .intern_path(["type", "journal"])
-        .ok_or_else(|| anyhow!("failed to intern #type:journal"))?;
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DecodedFile {
+    pub path: PathBuf,
+    pub encoding: Encoding,
+}
 
-    let mut files: Vec<PathBuf> = fs::read_dir(journal_dir)
-        .with_context(|| {
-            format!(
-                "failed to read journal directory '{}'",
-                journal_dir.display()
-            )
-        })?
-        .filter_map(|entry| entry.ok().map(|e| e.path()))
-        .filter(|path| is_markdown(path))
-        .collect();
-    files.sort();
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
 
-    for path in files {
-        let file_stem = path
-            .file_stem()
-            .and_then(OsStr::to_str)
-            .ok_or_else(|| anyhow!("journal entry '{}' has an invalid name", path.display()))?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Rejected by `--include` or `--exclude`. For a directory, nothing
+    /// below it was read.
+    Excluded,
+    NotMarkdown,
+    NotHtml,
+    /// Neither a Keep note's `.json` nor its `.html`.
+    NotKeepNote,
+    /// A Keep note that was in the trash.
+    Trashed,
+}
 
-        let date = parse_journal_date(file_stem)
-            .with_context(|| format!("failed to parse date from journal entry '{file_stem}'.md"))?;
+/// Which files in a walked directory count as notes.
+#[derive(Debug, Clone, Copy)]
+enum NoteKind {
+    Markdown,
+    Html,
+    /// Markdown pages and CSV databases.
+    Notion,
+    /// Keep notes as JSON, or HTML in older archives.
+    Keep,
+}
 
-        let text = fs::read_to_string(&path)
-            .with_context(|| format!("failed to read journal entry '{}'", path.display()))?;
+impl NoteKind {
+    fn accepts(self, path: &Path) -> bool {
+        match self {
+            Self::Markdown => is_markdown(path),
+            Self::Html => path.extension().and_then(OsStr::to_str).is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm")
+            }),
+            Self::Notion => is_markdown(path) || is_csv(path),
+            Self::Keep => has_extension(path, "json") || Self::Html.accepts(path),
+        }
+    }
 
-        blocks.push(TaggedBlock {
-            date,
-            text,
-            tags: vec![journal_tag],
-        });
+    fn rejection(self) -> SkipReason {
+        match self {
+            Self::Markdown | Self::Notion => SkipReason::NotMarkdown,
+            Self::Html => SkipReason::NotHtml,
+            Self::Keep => SkipReason::NotKeepNote,
+        }
     }
+}
 
-    Ok(())
+/// Vault conventions from the command line: which notes to read, how they
+/// are dated, and which extra tags they get.
+#[derive(Debug, Default)]
+struct NoteRules {
+    filter: PathFilter,
+    date_keys: Vec<String>,
+    tags: TagRules,
+    split_by: Option<SplitBy>,
+    undated: UndatedPolicy,
+    /// The date `--undated today` gives, read once per import.
+    today: NaiveDate,
+    project_tag: String,
+    project_folders: FolderTags,
 }
 
-fn collect_project_entries(
-    projects_dir: &Path,
-    registry: &mut TagRegistry,
-    blocks: &mut Vec<TaggedBlock>,
-) -> Result<()> {
-    let project_root_tag = registry.intern_segment(None, "project");
-    let project_note_tag = registry
-        .intern_path(["type", "project-note"])
-        .ok_or_else(|| anyhow!("failed to intern #type:project-note"))?;
+/// Which folders above a note become nested tags under its root tag.
+#[derive(Debug, Default)]
+struct FolderTags {
+    /// Folder levels tagged below the root; notes deeper down share the
+    /// deepest tag.
+    max_depth: Option<usize>,
+    /// Folder names, matched case-insensitively, that get no tag. Notes in
+    /// them keep their parent folder's tag.
+    skip: Vec<String>,
+}
 
-    let mut entries = Vec::new();
-    for entry in WalkDir::new(projects_dir) {
-        let entry = entry.with_context(|| {
-            format!(
-                "failed to walk projects directory '{}'",
-                projects_dir.display()
-            )
-        })?;
+impl NoteRules {
+    /// The note's days, when splitting is on and it has date headings.
+    fn split(&self, text: &str, date_formats: &[String]) -> Option<Vec<DaySection>> {
+        match self.split_by? {
+            SplitBy::DateHeading => split::split_by_date_heading(text, date_formats),
+        }
+    }
 
-        if !entry.file_type().is_file() {
-            continue;
+    /// The date `--undated` gives a note or row whose date couldn't be
+    /// read, or `None` to skip it. `path` is the file on disk; `location`
+    /// names it in errors.
+    fn undated_date(
+        &self,
+        path: &Path,
+        location: &Path,
+    ) -> Result<Option<(NaiveDate, DateSource)>> {
+        match self.undated {
+            UndatedPolicy::Skip => Ok(None),
+            UndatedPolicy::Today => Ok(Some((self.today, DateSource::Today))),
+            UndatedPolicy::Mtime => Ok(Some((file_modified_date(path)?, DateSource::ModifiedTime))),
+            UndatedPolicy::Error => anyhow::bail!(
+                "no date could be read for '{}'; use --undated to skip or date such notes",
+                location.display()
+            ),
         }
+    }
+}
 
-        let path = entry.into_path();
-        if !is_markdown(&path) {
-            continue;
+impl ImportReport {
+    fn count_block(&mut self, path: &Path) {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        *self.blocks_per_directory.entry(dir).or_default() += 1;
+    }
+
+    fn record_encoding(&mut self, path: &Path, encoding: Encoding) {
+        if encoding != Encoding::Utf8 {
+            self.decoded_files.push(DecodedFile {
+                path: path.to_path_buf(),
+                encoding,
+            });
         }
+    }
+}
 
-        entries.push(path);
+impl ImportMetrics {
+    fn time_phase<T>(&mut self, phase: &'static str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let started = Instant::now();
+        let result = f();
+        self.record_phase(phase, started.elapsed());
+        result
     }
 
-    entries.sort();
+    fn record_phase(&mut self, phase: &'static str, duration: Duration) {
+        self.phases.push(PhaseTiming {
+            phase,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+        });
+    }
+}
 
-    for path in entries {
-        let relative = path.strip_prefix(projects_dir).with_context(|| {
-            format!("failed to strip projects prefix from '{}'", path.display())
-        })?;
+pub fn run(cli: Cli) -> Result<ImportReport> {
+    let mode = if cli.progress_json {
+        ProgressMode::Json
+    } else if cli.progress {
+        ProgressMode::Bar
+    } else {
+        ProgressMode::Off
+    };
+    let mut progress = Progress::stdio(mode);
 
-        let text = fs::read_to_string(&path)
-            .with_context(|| format!("failed to read project note '{}'", path.display()))?;
-        let date = file_modified_date(&path).with_context(|| {
-            format!("failed to read modification date for '{}'", path.display())
-        })?;
+    let result = import(&cli, &mut progress);
+    if let Err(err) = &result {
+        progress.error(err);
+    }
+    result
+}
 
-        let mut tags = vec![project_root_tag, project_note_tag];
-        let mut parent_tag = Some(project_root_tag);
+/// Imports without a [`Cli`], keeping the snapshot in memory:
+///
+/// ```no_run
+/// let imported = importer::Importer::builder()
+///     .source("notes")
+///     .journal_dir("daily")
+///     .run()?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Importer {
+    options: ImportOptions,
+}
 
-        if let Some(dir_path) = relative.parent() {
-            for component in dir_path.components() {
-                if let Component::Normal(name) = component {
-                    let segment = match normalize_tag_segment(&name.to_string_lossy()) {
-                        Some(segment) => segment,
-                        None => continue,
-                    };
+#[derive(Debug, Clone)]
+pub struct ImporterBuilder {
+    options: ImportOptions,
+}
 
-                    let tag_id = registry.intern_segment(parent_tag, &segment);
-                    tags.push(tag_id);
-                    parent_tag = Some(tag_id);
-                }
-            }
+/// Everything an import reads, shared by [`Cli`] and [`Importer`].
+#[derive(Debug, Clone)]
+struct ImportOptions {
+    source: PathBuf,
+    format: SourceFormat,
+    merge_with: Option<PathBuf>,
+    map: Option<ColumnMap>,
+    tag_delimiter: String,
+    /// Relative to `source` unless absolute. Without one, the daily-notes
+    /// folder from the vault's Obsidian settings, then `journal`.
+    journal_dir: Option<PathBuf>,
+    projects_dir: PathBuf,
+    dedup: DedupPolicy,
+    wikilinks: WikilinkMode,
+    obsidian_daily_notes: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    tag_rules: Option<PathBuf>,
+    date_keys: Vec<String>,
+    dates_from_git: bool,
+    split_by: Option<SplitBy>,
+    undated: UndatedPolicy,
+    project_tag: String,
+    folder_tag_depth: Option<usize>,
+    skip_folders: Vec<String>,
+    /// Where embedded attachments are copied; without one, embeds are left
+    /// pointing into the source.
+    assets_dir: Option<PathBuf>,
+    clock: SharedClock,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            source: PathBuf::new(),
+            format: SourceFormat::default(),
+            merge_with: None,
+            map: None,
+            tag_delimiter: ",".to_string(),
+            journal_dir: None,
+            projects_dir: PathBuf::from("projects"),
+            dedup: DedupPolicy::default(),
+            wikilinks: WikilinkMode::default(),
+            obsidian_daily_notes: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            tag_rules: None,
+            date_keys: DEFAULT_DATE_KEYS.map(String::from).to_vec(),
+            dates_from_git: false,
+            split_by: None,
+            undated: UndatedPolicy::default(),
+            project_tag: DEFAULT_PROJECT_TAG.to_string(),
+            folder_tag_depth: None,
+            skip_folders: Vec::new(),
+            assets_dir: None,
+            clock: SharedClock::default(),
         }
+    }
+}
 
-        tags.sort_unstable();
-        tags.dedup();
+impl Cli {
+    fn options(&self) -> ImportOptions {
+        let output_dir = self.output.parent().unwrap_or(Path::new(""));
+        ImportOptions {
+            source: self.source.clone(),
+            format: self.format,
+            merge_with: self.merge_with.clone(),
+            map: self.map.clone(),
+            tag_delimiter: self.tag_delimiter.clone(),
+            dedup: self.dedup,
+            wikilinks: self.wikilinks,
+            obsidian_daily_notes: self.obsidian_daily_notes,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            tag_rules: self.tag_rules.clone(),
+            date_keys: self.date_keys.clone(),
+            dates_from_git: self.dates_from_git,
+            split_by: self.split_by,
+            undated: self.undated,
+            project_tag: self.project_tag.clone(),
+            folder_tag_depth: self.folder_tag_depth,
+            skip_folders: self.skip_folders.clone(),
+            assets_dir: Some(output_dir.join(attachments::ASSETS_DIR)),
+            ..ImportOptions::default()
+        }
+    }
+}
 
-        blocks.push(TaggedBlock { date, text, tags });
+impl Importer {
+    pub fn builder() -> ImporterBuilder {
+        ImporterBuilder {
+            options: ImportOptions::default(),
+        }
     }
 
-    Ok(())
+    pub fn run(&self) -> Result<ImportedSnapshot> {
+        build(
+            &self.options,
+            &mut Progress::new(ProgressMode::Off, Box::new(io::sink())),
+        )
+    }
 }
 
-fn ensure_directory(path: &Path) -> Result<&Path> {
-    let metadata = fs::metadata(path)
-        .with_context(|| format!("failed to read metadata for '{}'", path.display()))?;
-
-    if !metadata.is_dir() {
-        anyhow::bail!("'{}' is not a directory", path.display());
+impl ImporterBuilder {
+    pub fn source(mut self, source: impl Into<PathBuf>) -> Self {
+        self.options.source = source.into();
+        self
     }
 
-    Ok(path)
-}
+    pub fn format(mut self, format: SourceFormat) -> Self {
+        self.options.format = format;
+        self
+    }
 
-fn is_markdown(path: &Path) -> bool {
-    path.extension()
-        .and_then(OsStr::to_str)
-        .map(|ext| ext.eq_ignore_ascii_case("md"))
-        .unwrap_or(false)
-}
+    /// A second snapshot merged into a [`SourceFormat::Sightline`] source.
+    pub fn merge_with(mut self, snapshot: impl Into<PathBuf>) -> Self {
+        self.options.merge_with = Some(snapshot.into());
+        self
+    }
 
-fn parse_journal_date(name: &str) -> Result<NaiveDate> {
-    let trimmed = name.trim();
-    let normalized = trimmed.trim_matches('.');
+    /// Columns to read from a CSV/TSV source.
+    pub fn map(mut self, map: ColumnMap) -> Self {
+        self.options.map = Some(map);
+        self
+    }
 
-    let candidates = [normalized, &normalized.replace("Sept", "Sep")];
-    let formats = ["%B %d, %Y", "%b %d, %Y", "%Y-%m-%d"];
+    pub fn tag_delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.options.tag_delimiter = delimiter.into();
+        self
+    }
 
-    for candidate in candidates.iter() {
-        for format in formats.iter() {
-            if let Ok(date) = NaiveDate::parse_from_str(candidate, format) {
-                return Ok(date);
-            }
-        }
+    /// Journal folder of a vault source; the Obsidian daily-notes folder,
+    /// or `journal`, by default.
+    pub fn journal_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.journal_dir = Some(dir.into());
+        self
     }
 
-    Err(anyhow!("unable to parse journal date from '{name}'"))
-}
+    /// Projects folder of a vault source; `projects` by default.
+    pub fn projects_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.projects_dir = dir.into();
+        self
+    }
 
-fn file_modified_date(path: &Path) -> Result<NaiveDate> {
-    let metadata = fs::metadata(path)
-        .with_context(|| format!("failed to read metadata for '{}'", path.display()))?;
-    let modified = metadata
-        .modified()
-        .with_context(|| format!("failed to read modification time for '{}'", path.display()))?;
+    pub fn dedup(mut self, policy: DedupPolicy) -> Self {
+        self.options.dedup = policy;
+        self
+    }
 
-    let datetime: DateTime<Utc> = DateTime::<Utc>::from(modified);
-    Ok(datetime.date_naive())
-}
+    pub fn wikilinks(mut self, mode: WikilinkMode) -> Self {
+        self.options.wikilinks = mode;
+        self
+    }
 
-fn normalize_tag_segment(segment: &str) -> Option<String> {
-    let trimmed = segment.trim();
-    if trimmed.is_empty() {
-        return None;
+    pub fn obsidian_daily_notes(mut self, enabled: bool) -> Self {
+        self.options.obsidian_daily_notes = enabled;
+        self
     }
 
-    let mut result = String::with_capacity(trimmed.len());
-    let mut last_dash = false;
-    for ch in trimmed.chars() {
-        let lower = ch.to_ascii_lowercase();
-        if lower.is_ascii_alphanumeric() {
-            result.push(lower);
-            last_dash = false;
-        } else if !last_dash {
-            result.push('-');
-            last_dash = true;
-        }
+    pub fn include(mut self, glob: impl Into<String>) -> Self {
+        self.options.include.push(glob.into());
+        self
     }
 
-    while result.ends_with('-') {
-        result.pop();
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.options.exclude.push(glob.into());
+        self
     }
 
-    while result.starts_with('-') {
-        result.remove(0);
+    pub fn tag_rules(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.tag_rules = Some(path.into());
+        self
     }
 
-    if result.is_empty() {
-        None
-    } else {
-        Some(result)
+    /// Frontmatter keys that date a note, in order of preference.
+    pub fn date_keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.options.date_keys = keys.into_iter().map(Into::into).collect();
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
-    use std::fs;
-    use std::time::SystemTime;
+    pub fn dates_from_git(mut self, enabled: bool) -> Self {
+        self.options.dates_from_git = enabled;
+        self
+    }
 
-    use assert_fs::prelude::*;
-    use chrono::{NaiveDateTime, NaiveTime};
-    use clap::CommandFactory;
-    use filetime::FileTime;
+    /// Splits vault notes into one journal block per day.
+    pub fn split_by(mut self, split_by: SplitBy) -> Self {
+        self.options.split_by = Some(split_by);
+        self
+    }
 
-    #[derive(Debug, serde::Deserialize)]
-    struct Snapshot {
-        version: u64,
-        blocks: Vec<TaggedBlock>,
-        #[serde(default)]
-        tag_registry: Vec<Tag>,
+    /// How journal notes and table rows without a readable date are
+    /// handled; skipped by default.
+    pub fn undated(mut self, policy: UndatedPolicy) -> Self {
+        self.options.undated = policy;
+        self
     }
 
-    #[test]
-    fn cli_definition_is_valid() {
-        Cli::command().debug_assert();
+    /// Where "today" comes from for undated notes; the system clock by
+    /// default.
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.options.clock = clock;
+        self
     }
 
-    #[test]
-    fn run_errors_when_source_missing() {
-        let temp = assert_fs::TempDir::new().expect("temp dir");
-        let output = temp.child("timeline.json");
+    /// Root tag of project notes, `project` by default. May be nested, e.g.
+    /// `work:projects`.
+    pub fn project_tag(mut self, tag: impl Into<String>) -> Self {
+        self.options.project_tag = tag.into();
+        self
+    }
 
-        let cli = Cli {
-            source: temp.child("missing").path().to_path_buf(),
-            output: output.path().to_path_buf(),
-        };
+    pub fn folder_tag_depth(mut self, depth: usize) -> Self {
+        self.options.folder_tag_depth = Some(depth);
+        self
+    }
 
-        let result = run(cli);
-        assert!(result.is_err(), "expected missing directory error");
+    pub fn skip_folder(mut self, name: impl Into<String>) -> Self {
+        self.options.skip_folders.push(name.into());
+        self
     }
 
-    #[test]
-    fn run_imports_journal_and_project_notes() {
-        let temp = assert_fs::TempDir::new().expect("temp dir");
-        let vault = temp.child("vault");
-        let journal = vault.child("journal");
-        journal.create_dir_all().expect("create journal");
-        let projects = vault.child("projects");
-        projects.create_dir_all().expect("create projects");
+    /// Copies attachments embedded in vault notes here and links them as
+    /// `assets/<name>`.
+    pub fn assets_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.assets_dir = Some(dir.into());
+        self
+    }
 
-        let journal_entry = journal.child("Sept 14, 2025.md");
-        journal_entry
-            .write_str("Morning reflection")
-            .expect("write journal");
+    pub fn build(self) -> Importer {
+        Importer {
+            options: self.options,
+        }
+    }
 
-        let project_dir = projects.child("Sightline");
-        project_dir.create_dir_all().expect("create project dir");
-        let project_note = project_dir.child("Plan.md");
-        project_note
-            .write_str("Project plan notes")
-            .expect("write project note");
+    pub fn run(self) -> Result<ImportedSnapshot> {
+        self.build().run()
+    }
+}
 
-        let project_date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
-        let project_time =
-            NaiveDateTime::new(project_date, NaiveTime::from_hms_opt(12, 0, 0).unwrap());
-        let system_time: SystemTime =
-            DateTime::<Utc>::from_naive_utc_and_offset(project_time, Utc).into();
-        filetime::set_file_mtime(project_note.path(), FileTime::from_system_time(system_time))
-            .expect("set mtime");
+/// An import held in memory, sorted by date and deduplicated.
+#[derive(Debug)]
+pub struct ImportedSnapshot {
+    pub blocks: Vec<TaggedBlock>,
+    /// Sorted by id.
+    pub tags: Vec<Tag>,
+    pub report: ImportReport,
+    pub metrics: ImportMetrics,
+}
 
-        let output = temp.child("out/timeline.json");
-        let cli = Cli {
-            source: vault.path().to_path_buf(),
-            output: output.path().to_path_buf(),
-        };
+impl ImportedSnapshot {
+    /// Writes the snapshot in a form [`timeline::Timeline`] loads.
+    pub fn write<W: Write>(&self, writer: W, encoding: SnapshotEncoding) -> Result<()> {
+        timeline::write_snapshot(writer, encoding, 0, &self.blocks, &self.tags, |_| true)?;
+        Ok(())
+    }
 
-        run(cli).expect("run importer");
+    /// Writes the snapshot to `path`, replacing any file there, in any
+    /// encoding [`timeline::Timeline::load_from_path`] loads.
+    pub fn write_file(&self, path: &Path, encoding: SnapshotEncoding) -> Result<()> {
+        timeline::write_snapshot_file(path, encoding, 0, &self.blocks, &self.tags, |_| true)?;
+        Ok(())
+    }
+}
 
-        let snapshot: Snapshot =
-            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
-                .expect("parse snapshot");
+fn import(cli: &Cli, progress: &mut Progress) -> Result<ImportReport> {
+    let mut imported = build(&cli.options(), progress)?;
 
-        assert_eq!(snapshot.version, 0);
-        assert_eq!(snapshot.blocks.len(), 2);
-        assert!(
-            snapshot
-                .tag_registry
-                .iter()
-                .all(|tag| tag.color.as_ref().is_some())
-        );
+    if let Some(parent) = cli.output.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "failed to create output parent directory '{}'",
+                    parent.display()
+                )
+            })?;
+        }
+    }
 
-        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+    let started = Instant::now();
+    // Edits logged against the timeline being replaced would be replayed
+    // over the import on the next launch.
+    wal::clear(&wal::log_path(&cli.output)).with_context(|| {
+        format!(
+            "failed to remove the edit log beside '{}'",
+            cli.output.display()
+        )
+    })?;
+    imported
+        .write_file(&cli.output, cli.output_format.into())
+        .with_context(|| format!("failed to write snapshot to '{}'", cli.output.display()))?;
+    imported.metrics.record_phase("write", started.elapsed());
+    progress.finished(imported.metrics.blocks_created);
+
+    let ImportedSnapshot {
+        report, metrics, ..
+    } = imported;
+    if let Some(metrics_path) = &cli.metrics_out {
+        let json = serde_json::to_vec_pretty(&metrics)?;
+        fs::write(metrics_path, json)
+            .with_context(|| format!("failed to write metrics to '{}'", metrics_path.display()))?;
+    }
 
-        let journal_date = NaiveDate::from_ymd_opt(2025, 9, 14).unwrap();
-        let journal_block = snapshot
-            .blocks
-            .iter()
-            .find(|block| block.date == journal_date)
-            .expect("journal block");
-        let journal_tags = tags_as_names(journal_block, &tag_names);
-        assert!(journal_tags.contains(&"type:journal".to_string()));
+    if let Some(report_path) = &cli.report {
+        let json = serde_json::to_vec_pretty(&report)?;
+        fs::write(report_path, json)
+            .with_context(|| format!("failed to write report to '{}'", report_path.display()))?;
+    }
 
-        let project_block = snapshot
-            .blocks
+    info!(
+        target: "sightline::importer",
+        source = %cli.source.display(),
+        output = %cli.output.display(),
+        files = metrics.files_processed,
+        blocks = metrics.blocks_created,
+        tags = metrics.tags_created,
+        duplicates = metrics.duplicates,
+        "importer completed"
+    );
+
+    Ok(report)
+}
+
+fn build(options: &ImportOptions, progress: &mut Progress) -> Result<ImportedSnapshot> {
+    let mut registry = TagRegistry::new();
+    let mut blocks = Vec::new();
+    let mut metrics = ImportMetrics::default();
+    let mut report = ImportReport::default();
+
+    let rules = NoteRules {
+        filter: PathFilter::new(&options.include, &options.exclude)?,
+        date_keys: options.date_keys.clone(),
+        tags: match &options.tag_rules {
+            Some(path) => TagRules::load(path)?,
+            None => TagRules::default(),
+        },
+        split_by: options.split_by,
+        undated: options.undated,
+        today: options.clock.today(),
+        project_tag: options.project_tag.clone(),
+        project_folders: FolderTags {
+            max_depth: options.folder_tag_depth,
+            skip: options.skip_folders.clone(),
+        },
+    };
+
+    if options.merge_with.is_some() && options.format != SourceFormat::Sightline {
+        anyhow::bail!("--merge-with only applies to --format sightline");
+    }
+
+    match options.format {
+        SourceFormat::Vault => import_vault(
+            options,
+            &rules,
+            &mut registry,
+            &mut blocks,
+            &mut metrics,
+            &mut report,
+            progress,
+        )?,
+        SourceFormat::Csv | SourceFormat::Tsv => {
+            metrics.files_processed += 1;
+            metrics.time_phase("rows", || {
+                import_table(
+                    options,
+                    &rules,
+                    &mut registry,
+                    &mut blocks,
+                    &mut report,
+                    progress,
+                )
+            })?;
+            if !report.unparseable_dates.is_empty() {
+                metrics.warnings.push(format!(
+                    "skipped {} row(s) with no parseable date",
+                    report.unparseable_dates.len()
+                ));
+            }
+        }
+        SourceFormat::Html => {
+            let notes_dir = ensure_directory(&options.source).with_context(|| {
+                format!("notes directory '{}' is invalid", options.source.display())
+            })?;
+            metrics.files_processed += metrics.time_phase("notes", || {
+                collect_html_notes(
+                    notes_dir,
+                    &rules,
+                    &mut registry,
+                    &mut blocks,
+                    &mut report,
+                    progress,
+                )
+            })?;
+        }
+        SourceFormat::Notion => {
+            let export_dir = ensure_directory(&options.source).with_context(|| {
+                format!("Notion export '{}' is invalid", options.source.display())
+            })?;
+            metrics.files_processed += metrics.time_phase("pages", || {
+                collect_notion_pages(
+                    export_dir,
+                    &rules,
+                    &mut registry,
+                    &mut blocks,
+                    &mut report,
+                    progress,
+                )
+            })?;
+            copy_attachments(export_dir, options, &mut blocks, &mut metrics)?;
+        }
+        SourceFormat::Keep => {
+            let keep_dir = ensure_directory(&options.source).with_context(|| {
+                format!("Keep folder '{}' is invalid", options.source.display())
+            })?;
+            metrics.files_processed += metrics.time_phase("notes", || {
+                collect_keep_notes(
+                    keep_dir,
+                    &rules,
+                    &mut registry,
+                    &mut blocks,
+                    &mut report,
+                    progress,
+                )
+            })?;
+            copy_attachments(keep_dir, options, &mut blocks, &mut metrics)?;
+        }
+        SourceFormat::Sightline => {
+            let snapshots: Vec<&Path> = [
+                Some(options.source.as_path()),
+                options.merge_with.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            let (identical, dropped) = metrics.time_phase("snapshots", || {
+                collect_snapshots(
+                    &snapshots,
+                    &mut registry,
+                    &mut blocks,
+                    &mut report,
+                    progress,
+                )
+            })?;
+            metrics.files_processed += snapshots.len();
+            if identical > 0 {
+                metrics.warnings.push(format!(
+                    "merged {identical} block(s) that appeared in more than one snapshot"
+                ));
+            }
+            metrics.warnings.extend(dropped);
+        }
+    }
+
+    if !report.decoded_files.is_empty() {
+        metrics.warnings.push(format!(
+            "decoded {} file(s) that were not UTF-8; see decoded_files in the report",
+            report.decoded_files.len()
+        ));
+    }
+
+    if options.wikilinks != WikilinkMode::Ignore {
+        metrics.time_phase("wikilinks", || {
+            apply_wikilinks(&mut blocks, options.wikilinks, &mut registry);
+            Ok(())
+        })?;
+    }
+
+    let started = Instant::now();
+    let (mut blocks, duplicates) = dedup_blocks(blocks, options.dedup);
+    blocks.sort_by(|a, b| a.date.cmp(&b.date));
+    metrics.record_phase("dedup", started.elapsed());
+
+    metrics.duplicates = duplicates;
+    if duplicates > 0 && options.dedup != DedupPolicy::KeepBoth {
+        metrics.warnings.push(format!(
+            "collapsed {duplicates} duplicate note(s) using dedup policy {:?}",
+            options.dedup
+        ));
+    }
+
+    let mut tags: Vec<Tag> = registry.iter().cloned().collect();
+    tags.sort_by(|a, b| a.id.cmp(&b.id));
+
+    report.tags = tags
+        .iter()
+        .filter_map(|tag| registry.full_name(tag.id))
+        .collect();
+    report.tags.sort();
+
+    metrics.blocks_created = blocks.len();
+    metrics.tags_created = tags.len();
+    Ok(ImportedSnapshot {
+        blocks,
+        tags,
+        report,
+        metrics,
+    })
+}
+
+/// Blocks of existing timeline snapshots. Tags are merged by name into
+/// `registry`, remapping ids that differ, and a block with the same date,
+/// text and source as one read from an earlier snapshot only adds its tags
+/// to it. Repeats within one snapshot are kept, each matching its own
+/// counterpart in earlier snapshots. Returns how many blocks were folded,
+/// and a warning for each snapshot whose settings are not carried over.
+fn collect_snapshots(
+    snapshots: &[&Path],
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<(usize, Vec<String>)> {
+    progress.phase("snapshots", snapshots.len());
+    type Key = (NaiveDate, String, Option<timeline::BlockSource>);
+    let mut seen: HashMap<Key, Vec<usize>> = HashMap::new();
+    let mut identical = 0;
+    let mut dropped = Vec::new();
+    for &path in snapshots {
+        let snapshot = timeline::Timeline::load_from_path(path)
+            .with_context(|| format!("failed to read snapshot '{}'", path.display()))?;
+        let settings = snapshot.customized_settings();
+        if !settings.is_empty() {
+            dropped.push(format!(
+                "settings in snapshot '{}' were not carried over: {}",
+                path.display(),
+                settings.join(", ")
+            ));
+        }
+        let ids = registry.merge(snapshot.tag_registry());
+        let every_block =
+            snapshot.blocks_in_range(&timeline::DateRange::default(), SensitiveContent::Included);
+        let count = every_block.len();
+        // How many of each key this snapshot has matched so far, and the
+        // blocks it added, which only later snapshots may match.
+        let mut matched: HashMap<Key, usize> = HashMap::new();
+        let mut added: Vec<(Key, usize)> = Vec::new();
+        for block in every_block {
+            let mut tags: Vec<u32> = block
+                .tags
+                .iter()
+                .filter_map(|id| ids.get(id).copied())
+                .collect();
+            tags.sort_unstable();
+            tags.dedup();
+            let key = (block.date, block.text.clone(), block.source.clone());
+            let nth = matched.entry(key.clone()).or_default();
+            match seen.get(&key).and_then(|earlier| earlier.get(*nth)) {
+                Some(&index) => {
+                    *nth += 1;
+                    identical += 1;
+                    let existing = &mut blocks[index];
+                    existing.tags.extend(tags);
+                    existing.tags.sort_unstable();
+                    existing.tags.dedup();
+                }
+                None => {
+                    added.push((key, blocks.len()));
+                    blocks.push(TaggedBlock {
+                        tags,
+                        ..block.clone()
+                    });
+                }
+            }
+        }
+        for (key, index) in added {
+            seen.entry(key).or_default().push(index);
+        }
+        let file_name = PathBuf::from(path.file_name().unwrap_or_default());
+        *report
+            .blocks_per_directory
+            .entry(file_name.clone())
+            .or_default() += count;
+        progress.file(&file_name, count);
+    }
+    Ok((identical, dropped))
+}
+
+/// Journal and project notes from a vault directory, with embedded
+/// attachments copied to the assets directory.
+fn import_vault(
+    options: &ImportOptions,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    metrics: &mut ImportMetrics,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<()> {
+    let source_root = ensure_directory(&options.source)
+        .with_context(|| format!("source directory '{}' is invalid", options.source.display()))?;
+
+    let daily_notes = DailyNotesSettings::load(source_root)
+        .context("failed to read the vault's daily-notes settings")?;
+    let mut date_formats = Vec::new();
+    match &daily_notes {
+        Some(settings) => match settings.file_name_format() {
+            Some(format) => date_formats.push(format),
+            None => metrics.warnings.push(format!(
+                "unsupported daily-notes format '{}'; using built-in date formats",
+                settings.format()
+            )),
+        },
+        None if options.obsidian_daily_notes => metrics
+            .warnings
+            .push("no .obsidian/daily-notes.json found; using built-in date formats".to_string()),
+        None => {}
+    }
+
+    // The app's vault import finds the journal the same way, so blocks
+    // from either carry the same sources and are recognized on re-import.
+    let journal_dir = match &options.journal_dir {
+        Some(dir) => source_root.join(dir),
+        None => source_root.join(
+            vault::journal_layout(source_root)
+                .context("failed to read the vault's daily-notes settings")?
+                .0,
+        ),
+    };
+    ensure_directory(&journal_dir)
+        .with_context(|| format!("journal directory '{}' is missing", journal_dir.display()))?;
+
+    let projects_dir = source_root.join(&options.projects_dir);
+    ensure_directory(&projects_dir)
+        .with_context(|| format!("projects directory '{}' is missing", projects_dir.display()))?;
+
+    if options.dates_from_git {
+        git::ensure_repository(source_root)
+            .context("--dates-from-git requires the source to be a git checkout")?;
+    }
+
+    metrics.files_processed += metrics.time_phase("journal", || {
+        collect_journal_entries(
+            &journal_dir,
+            journal_dir
+                .strip_prefix(source_root)
+                .unwrap_or(&journal_dir),
+            &date_formats,
+            rules,
+            registry,
+            blocks,
+            report,
+            progress,
+        )
+    })?;
+    if !report.unparseable_dates.is_empty() {
+        metrics.warnings.push(format!(
+            "skipped {} journal note(s) with no parseable date",
+            report.unparseable_dates.len()
+        ));
+    }
+    metrics.files_processed += metrics.time_phase("projects", || {
+        collect_project_entries(
+            &projects_dir,
+            rules,
+            options.dates_from_git,
+            registry,
+            blocks,
+            report,
+            progress,
+        )
+    })?;
+    if !report.uncommitted_notes.is_empty() {
+        metrics.warnings.push(format!(
+            "{} project note(s) have no git history; used file name or modification time",
+            report.uncommitted_notes.len()
+        ));
+    }
+
+    copy_attachments(source_root, options, blocks, metrics)
+}
+
+/// Copies files embedded in `blocks` into the assets directory, if there is
+/// one, and points the embeds at the copies.
+fn copy_attachments(
+    source_root: &Path,
+    options: &ImportOptions,
+    blocks: &mut [TaggedBlock],
+    metrics: &mut ImportMetrics,
+) -> Result<()> {
+    let Some(assets_dir) = &options.assets_dir else {
+        return Ok(());
+    };
+    let mut copier = AttachmentCopier::new(source_root, assets_dir.clone())?;
+    metrics.time_phase("attachments", || {
+        for block in blocks.iter_mut() {
+            block.text = copier.rewrite(&block.text)?;
+        }
+        Ok(())
+    })?;
+    metrics.attachments_copied = copier.copied_count();
+    for missing in &copier.missing {
+        metrics.warnings.push(format!(
+            "attachment '{missing}' embedded in notes was not found"
+        ));
+    }
+
+    Ok(())
+}
+
+/// One block per row of a CSV/TSV source.
+fn import_table(
+    options: &ImportOptions,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<()> {
+    let map = options
+        .map
+        .as_ref()
+        .ok_or_else(|| anyhow!("--format {:?} requires --map", options.format))?;
+    let delimiter = if options.format == SourceFormat::Tsv {
+        '\t'
+    } else {
+        ','
+    };
+    let file_name = PathBuf::from(options.source.file_name().unwrap_or_default());
+    let decoded = encoding::read_text(&options.source)
+        .with_context(|| format!("failed to read table '{}'", options.source.display()))?;
+    report.record_encoding(&file_name, decoded.encoding);
+    let rows = table::read_rows(
+        &options.source,
+        &decoded.text,
+        delimiter,
+        map,
+        &options.tag_delimiter,
+    )?;
+    progress.phase("rows", rows.len());
+
+    for row in rows {
+        let location = PathBuf::from(format!("{}:{}", file_name.display(), row.line));
+        let dated = match parse_date_value(&row.date) {
+            Some(date) => Some(date),
+            None => rules
+                .undated_date(&options.source, &location)?
+                .map(|(date, _)| date),
+        };
+        let Some(date) = dated else {
+            progress.file(&location, 0);
+            report.unparseable_dates.push(location);
+            continue;
+        };
+
+        let mut tags = intern_tag_names(registry, &row.tags);
+        tags.extend(rules.tags.apply(&file_name, &row.text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        // Like every other source, a block ends with its last line's newline.
+        let mut text = row.text;
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource {
+                path: file_name.clone(),
+                start: row.span.start,
+                end: row.span.end,
+            }),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        *report
+            .blocks_per_directory
+            .entry(file_name.clone())
+            .or_default() += 1;
+        progress.file(&location, 1);
+    }
+
+    Ok(())
+}
+
+/// Interns tags written as `#a:b` or `a:b`, skipping names with no usable
+/// segment.
+fn intern_tag_names(registry: &mut TagRegistry, names: &[String]) -> Vec<u32> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let segments: Vec<String> = name
+                .trim_start_matches('#')
+                .split(':')
+                .filter_map(normalize_tag_segment)
+                .collect();
+            registry.intern_path(segments.iter().map(String::as_str))
+        })
+        .collect()
+}
+
+/// One block per part of a file Sightline's markdown export wrote, tagged
+/// as its marker says plus whatever the tag rules find. None of the tags
+/// the importer gives notes by their kind or folder are added: the export
+/// wrote every tag the block had.
+fn push_exported(
+    parts: Vec<markdown::ExportedBlock>,
+    date: NaiveDate,
+    vault_relative: &Path,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+) {
+    for part in parts {
+        let mut tags = intern_tag_names(registry, &part.tags);
+        tags.extend(rules.tags.apply(vault_relative, &part.text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource {
+                path: vault_relative.to_path_buf(),
+                start: part.span.start,
+                end: part.span.end,
+            }),
+            ..TaggedBlock::new(date, part.text, tags)
+        });
+        report.count_block(vault_relative);
+    }
+}
+
+/// Lists markdown notes under `dir`, sorted, skipping anything `filter`
+/// rejects. Excluded directories are pruned rather than walked. Skipped
+/// files and directories are recorded in `report`.
+fn walk_notes(
+    dir: &Path,
+    section: &str,
+    kind: NoteKind,
+    filter: &PathFilter,
+    report: &mut ImportReport,
+) -> Result<Vec<PathBuf>> {
+    let relative = |path: &Path| {
+        path.strip_prefix(dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    };
+
+    let mut excluded_dirs = Vec::new();
+    let walker = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+        let excluded = entry.depth() > 0
+            && entry.file_type().is_dir()
+            && filter.excludes_dir(section, &relative(entry.path()));
+        if excluded {
+            excluded_dirs.push((relative(entry.path()), SkipReason::Excluded));
+        }
+        !excluded
+    });
+
+    let mut notes = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in walker {
+        let entry = entry
+            .with_context(|| format!("failed to walk {section} directory '{}'", dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = relative(entry.path());
+        if !kind.accepts(entry.path()) {
+            skipped.push((path, kind.rejection()));
+        } else if !filter.allows_file(section, &path) {
+            skipped.push((path, SkipReason::Excluded));
+        } else {
+            notes.push(entry.into_path());
+        }
+    }
+
+    skipped.append(&mut excluded_dirs);
+    skipped.sort();
+    report
+        .skipped
+        .extend(skipped.into_iter().map(|(path, reason)| SkippedFile {
+            path: Path::new(section).join(path),
+            reason,
+        }));
+
+    notes.sort();
+    Ok(notes)
+}
+
+/// Project notes are read in parallel batches of this size so progress can
+/// be reported between batches.
+const PROJECT_READ_CHUNK: usize = 256;
+
+/// Reads the notes under `journal_dir`, which is `journal` relative to the
+/// vault; block sources are given relative to the vault.
+fn collect_journal_entries(
+    journal_dir: &Path,
+    journal: &Path,
+    date_formats: &[String],
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let journal_tag = registry
+        .intern_path(["type", "journal"])
+        .ok_or_else(|| anyhow!("failed to intern #type:journal"))?;
+
+    let files = walk_notes(
+        journal_dir,
+        "journal",
+        NoteKind::Markdown,
+        &rules.filter,
+        report,
+    )?;
+    let file_count = files.len();
+    progress.phase("journal", file_count);
+
+    for path in files {
+        let file_stem = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| anyhow!("journal entry '{}' has an invalid name", path.display()))?;
+
+        let relative = path
+            .strip_prefix(journal_dir)
+            .with_context(|| format!("failed to strip journal prefix from '{}'", path.display()))?;
+
+        let vault_relative = journal.join(relative);
+        let decoded = encoding::read_text(&path)
+            .with_context(|| format!("failed to read journal entry '{}'", path.display()))?;
+        report.record_encoding(&vault_relative, decoded.encoding);
+        let text = decoded.text;
+        let exported = markdown::parse_export(&text);
+        if let Some(days) = exported
+            .is_none()
+            .then(|| rules.split(&text, date_formats))
+            .flatten()
+        {
+            progress.file(relative, days.len());
+            push_days(
+                days,
+                &vault_relative,
+                &[journal_tag],
+                rules,
+                registry,
+                blocks,
+                report,
+            );
+            continue;
+        }
+        let named = parse_journal_period(file_stem, date_formats);
+        let dated = frontmatter_date(&text, &rules.date_keys)
+            .or_else(|| named.map(|(date, _)| (date, DateSource::FileName)))
+            .or_else(|| infer_date_from_path(relative).map(|date| (date, DateSource::Path)));
+        let dated = match dated {
+            Some(dated) => Some(dated),
+            None => rules.undated_date(&path, &vault_relative)?,
+        };
+        let Some((date, source)) = dated else {
+            report.unparseable_dates.push(vault_relative);
+            progress.file(relative, 0);
+            continue;
+        };
+        report.date_sources.insert(vault_relative.clone(), source);
+        if let Some(parts) = exported {
+            progress.file(relative, parts.len());
+            push_exported(
+                parts,
+                date,
+                &vault_relative,
+                rules,
+                registry,
+                blocks,
+                report,
+            );
+            continue;
+        }
+        // Weekly and monthly notes are typed by their name, even when
+        // frontmatter gives the date.
+        let period_tag = match named.map(|(_, period)| period) {
+            Some(period @ (JournalPeriod::Week | JournalPeriod::Month)) => registry
+                .intern_path(["type", period.type_tag()])
+                .ok_or_else(|| anyhow!("failed to intern #type:{}", period.type_tag()))?,
+            _ => journal_tag,
+        };
+        let mut tags = vec![period_tag];
+        tags.extend(rules.tags.apply(&vault_relative, &text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource::file(&vault_relative, &text)),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        report.count_block(&vault_relative);
+        progress.file(relative, 1);
+    }
+
+    Ok(file_count)
+}
+
+fn collect_project_entries(
+    projects_dir: &Path,
+    rules: &NoteRules,
+    dates_from_git: bool,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let project_root_tag = registry
+        .intern_colon_path(&rules.project_tag)
+        .ok_or_else(|| anyhow!("project tag '{}' is empty", rules.project_tag))?;
+    let project_note_tag = registry
+        .intern_path(["type", "project-note"])
+        .ok_or_else(|| anyhow!("failed to intern #type:project-note"))?;
+
+    let entries = walk_notes(
+        projects_dir,
+        "projects",
+        NoteKind::Markdown,
+        &rules.filter,
+        report,
+    )?;
+    let file_count = entries.len();
+    progress.phase("projects", file_count);
+
+    // Reading and dating notes is I/O bound and independent per file, so it
+    // runs in parallel. `collect` keeps the sorted order, and tag interning
+    // below stays single-threaded so tag ids are deterministic. Chunking lets
+    // progress advance while a large vault is read.
+    for chunk in entries.chunks(PROJECT_READ_CHUNK) {
+        let notes = chunk
+            .par_iter()
+            .map(|path| read_project_note(path, &rules.date_keys, dates_from_git))
+            .collect::<Result<Vec<_>>>()?;
+
+        for ProjectNote {
+            path,
+            text,
+            encoding,
+            date,
+            source,
+            uncommitted,
+        } in notes
+        {
+            let relative = path.strip_prefix(projects_dir).with_context(|| {
+                format!("failed to strip projects prefix from '{}'", path.display())
+            })?;
+            let vault_relative = Path::new("projects").join(relative);
+            report.record_encoding(&vault_relative, encoding);
+            if let Some(parts) = markdown::parse_export(&text) {
+                report.date_sources.insert(vault_relative.clone(), source);
+                progress.file(relative, parts.len());
+                push_exported(
+                    parts,
+                    date,
+                    &vault_relative,
+                    rules,
+                    registry,
+                    blocks,
+                    report,
+                );
+                continue;
+            }
+
+            let mut tags = vec![project_root_tag];
+            tags.extend(intern_folder_tags(
+                registry,
+                project_root_tag,
+                relative,
+                &rules.project_folders,
+            ));
+            if let Some(days) = rules.split(&text, &[]) {
+                let journal_tag = registry
+                    .intern_path(["type", "journal"])
+                    .ok_or_else(|| anyhow!("failed to intern #type:journal"))?;
+                tags.push(journal_tag);
+                progress.file(relative, days.len());
+                push_days(
+                    days,
+                    &vault_relative,
+                    &tags,
+                    rules,
+                    registry,
+                    blocks,
+                    report,
+                );
+                continue;
+            }
+
+            if uncommitted {
+                report.uncommitted_notes.push(vault_relative.clone());
+            }
+            report.date_sources.insert(vault_relative.clone(), source);
+
+            tags.push(project_note_tag);
+            tags.extend(rules.tags.apply(&vault_relative, &text, registry));
+            tags.sort_unstable();
+            tags.dedup();
+
+            blocks.push(TaggedBlock {
+                source: Some(BlockSource::file(&vault_relative, &text)),
+                ..TaggedBlock::new(date, text, tags)
+            });
+            report.count_block(&vault_relative);
+            progress.file(relative, 1);
+        }
+    }
+
+    Ok(file_count)
+}
+
+/// One block per day split from the note at `vault_relative`, each with
+/// `tags` plus whatever the tag rules find in that day's text.
+fn push_days(
+    days: Vec<DaySection>,
+    vault_relative: &Path,
+    tags: &[u32],
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+) {
+    report
+        .date_sources
+        .insert(vault_relative.to_path_buf(), DateSource::Heading);
+    for DaySection { date, text, span } in days {
+        let mut block_tags = tags.to_vec();
+        block_tags.extend(rules.tags.apply(vault_relative, &text, registry));
+        block_tags.sort_unstable();
+        block_tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource {
+                path: vault_relative.to_path_buf(),
+                start: span.start,
+                end: span.end,
+            }),
+            ..TaggedBlock::new(date, text, block_tags)
+        });
+        report.count_block(vault_relative);
+    }
+}
+
+/// Nested tags under `root` for each folder above `relative`, e.g.
+/// `project:work:q3` for `Work/Q3/plan.md`, limited by `folders`.
+fn intern_folder_tags(
+    registry: &mut TagRegistry,
+    root: u32,
+    relative: &Path,
+    folders: &FolderTags,
+) -> Vec<u32> {
+    let mut tags = Vec::new();
+    let mut parent_tag = Some(root);
+
+    if let Some(dir_path) = relative.parent() {
+        for component in dir_path.components() {
+            if folders.max_depth.is_some_and(|depth| tags.len() >= depth) {
+                break;
+            }
+            if let Component::Normal(name) = component {
+                let name = name.to_string_lossy();
+                if folders
+                    .skip
+                    .iter()
+                    .any(|skip| skip.eq_ignore_ascii_case(&name))
+                {
+                    continue;
+                }
+                let segment = match normalize_tag_segment(&name) {
+                    Some(segment) => segment,
+                    None => continue,
+                };
+
+                let tag_id = registry.intern_segment(parent_tag, &segment);
+                tags.push(tag_id);
+                parent_tag = Some(tag_id);
+            }
+        }
+    }
+
+    tags
+}
+
+/// One block per exported HTML note, tagged `#type:note` and by folder under
+/// `#notes`. Notes are dated by an embedded creation date, then a date in the
+/// file name, then modification time.
+fn collect_html_notes(
+    notes_dir: &Path,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let notes_root_tag = registry.intern_segment(None, "notes");
+    let note_tag = registry
+        .intern_path(["type", "note"])
+        .ok_or_else(|| anyhow!("failed to intern #type:note"))?;
+
+    let files = walk_notes(notes_dir, "", NoteKind::Html, &rules.filter, report)?;
+    let file_count = files.len();
+    progress.phase("notes", file_count);
+
+    for path in files {
+        let relative = path
+            .strip_prefix(notes_dir)
+            .with_context(|| format!("failed to strip notes prefix from '{}'", path.display()))?;
+        let decoded = encoding::read_text(&path)
+            .with_context(|| format!("failed to read note '{}'", path.display()))?;
+        report.record_encoding(relative, decoded.encoding);
+        let note = html::parse(&decoded.text);
+
+        let date = match note.created.or_else(|| {
+            let stem = relative
+                .file_stem()
+                .and_then(OsStr::to_str)
+                .unwrap_or_default();
+            parse_journal_date(stem, &[]).or_else(|| infer_date_from_path(relative))
+        }) {
+            Some(date) => date,
+            None => file_modified_date(&path).with_context(|| {
+                format!("failed to read modification date for '{}'", path.display())
+            })?,
+        };
+
+        let text =
+            match &note.title {
+                Some(title)
+                    if !note.text.lines().next().is_some_and(|line| {
+                        line.trim_start_matches('#').trim() == title.as_str()
+                    }) =>
+                {
+                    format!("# {title}\n\n{}", note.text)
+                }
+                _ => note.text,
+            };
+
+        let mut tags = vec![notes_root_tag, note_tag];
+        tags.extend(intern_folder_tags(
+            registry,
+            notes_root_tag,
+            relative,
+            &FolderTags::default(),
+        ));
+        tags.extend(rules.tags.apply(relative, &text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource::file(relative, &decoded.text)),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        report.count_block(relative);
+        progress.file(relative, 1);
+    }
+
+    Ok(file_count)
+}
+
+/// One block per page of a Notion export, tagged `#type:notion-page` and by
+/// page hierarchy under `#notion`. Database rows are dated by the row's date
+/// property, other pages by a date property line, then modification time.
+/// Rows without a page of their own become `#type:notion-row` blocks.
+fn collect_notion_pages(
+    export_dir: &Path,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let notion_root_tag = registry.intern_segment(None, "notion");
+    let page_tag = registry
+        .intern_path(["type", "notion-page"])
+        .ok_or_else(|| anyhow!("failed to intern #type:notion-page"))?;
+    let row_tag = registry
+        .intern_path(["type", "notion-row"])
+        .ok_or_else(|| anyhow!("failed to intern #type:notion-row"))?;
+
+    let files = walk_notes(export_dir, "", NoteKind::Notion, &rules.filter, report)?;
+    let file_count = files.len();
+    progress.phase("pages", file_count);
+
+    // Rows keyed by the database's row folder and the row title.
+    let mut rows: BTreeMap<(PathBuf, String), (PathBuf, notion::DatabaseRow)> = BTreeMap::new();
+    let (databases, pages): (Vec<PathBuf>, Vec<PathBuf>) =
+        files.into_iter().partition(|path| is_csv(path));
+    for path in &databases {
+        let relative = path
+            .strip_prefix(export_dir)
+            .with_context(|| format!("failed to strip export prefix from '{}'", path.display()))?;
+        let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+        let row_dir = relative.with_file_name(stem.strip_suffix("_all").unwrap_or(&stem));
+        let decoded = encoding::read_text(path)
+            .with_context(|| format!("failed to read database '{}'", path.display()))?;
+        report.record_encoding(relative, decoded.encoding);
+        for row in notion::read_database(path, &decoded.text)? {
+            rows.entry((row_dir.clone(), row.title.clone()))
+                .or_insert_with(|| (relative.to_path_buf(), row));
+        }
+    }
+
+    for path in pages {
+        let relative = path
+            .strip_prefix(export_dir)
+            .with_context(|| format!("failed to strip export prefix from '{}'", path.display()))?;
+        let page_dir = relative.parent().unwrap_or(Path::new(""));
+        let decoded = encoding::read_text(&path)
+            .with_context(|| format!("failed to read page '{}'", path.display()))?;
+        report.record_encoding(relative, decoded.encoding);
+        let text = decoded.text;
+        let title = notion::strip_id(&relative.file_stem().unwrap_or_default().to_string_lossy())
+            .to_string();
+        let row = rows
+            .remove(&(page_dir.to_path_buf(), title))
+            .map(|(_, row)| row);
+
+        let date = match row
+            .as_ref()
+            .and_then(|row| row.date)
+            .or_else(|| notion::property_date(&text))
+        {
+            Some(date) => date,
+            None => file_modified_date(&path).with_context(|| {
+                format!("failed to read modification date for '{}'", path.display())
+            })?,
+        };
+
+        let source = BlockSource::file(relative, &text);
+        let text = notion::embed_images(&text, export_dir, page_dir);
+        let mut tags = vec![notion_root_tag, page_tag];
+        tags.extend(intern_folder_tags(
+            registry,
+            notion_root_tag,
+            &notion::clean_path(relative),
+            &FolderTags::default(),
+        ));
+        if let Some(row) = &row {
+            tags.extend(intern_tag_names(registry, &row.tags));
+        }
+        tags.extend(rules.tags.apply(relative, &text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(source),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        report.count_block(relative);
+        progress.file(relative, 1);
+    }
+
+    // Inline databases often have rows with no page; flatten those.
+    for ((row_dir, _), (database, row)) in rows {
+        let date = match row.date {
+            Some(date) => date,
+            None => file_modified_date(&export_dir.join(&database)).with_context(|| {
+                format!(
+                    "failed to read modification date for '{}'",
+                    database.display()
+                )
+            })?,
+        };
+        let text = row.to_text();
+        let mut tags = vec![notion_root_tag, row_tag];
+        tags.extend(intern_folder_tags(
+            registry,
+            notion_root_tag,
+            &notion::clean_path(&row_dir.join(&row.title)),
+            &FolderTags::default(),
+        ));
+        tags.extend(intern_tag_names(registry, &row.tags));
+        tags.extend(rules.tags.apply(&database, &text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource {
+                path: database.clone(),
+                start: row.span.start,
+                end: row.span.end,
+            }),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        report.count_block(&database);
+    }
+    for database in &databases {
+        if let Ok(relative) = database.strip_prefix(export_dir) {
+            progress.file(relative, 0);
+        }
+    }
+
+    Ok(file_count)
+}
+
+/// One block per Google Keep note, tagged `#type:keep-note`, with labels
+/// as tags and `#keep:pinned` / `#keep:archived` for the note's status.
+/// Notes are dated by creation time; trashed notes are skipped. The `.html`
+/// copy is only read when a note has no `.json`.
+fn collect_keep_notes(
+    keep_dir: &Path,
+    rules: &NoteRules,
+    registry: &mut TagRegistry,
+    blocks: &mut Vec<TaggedBlock>,
+    report: &mut ImportReport,
+    progress: &mut Progress,
+) -> Result<usize> {
+    let keep_root_tag = registry.intern_segment(None, "keep");
+    let note_tag = registry
+        .intern_path(["type", "keep-note"])
+        .ok_or_else(|| anyhow!("failed to intern #type:keep-note"))?;
+
+    let files = walk_notes(keep_dir, "", NoteKind::Keep, &rules.filter, report)?;
+    let json_notes: HashSet<PathBuf> = files
+        .iter()
+        .filter(|path| has_extension(path, "json"))
+        .map(|path| path.with_extension(""))
+        .collect();
+    let files: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|path| {
+            has_extension(path, "json") || !json_notes.contains(&path.with_extension(""))
+        })
+        .collect();
+    let file_count = files.len();
+    progress.phase("notes", file_count);
+
+    for path in files {
+        let relative = path
+            .strip_prefix(keep_dir)
+            .with_context(|| format!("failed to strip Keep prefix from '{}'", path.display()))?;
+        let decoded = encoding::read_text(&path)
+            .with_context(|| format!("failed to read note '{}'", path.display()))?;
+        report.record_encoding(relative, decoded.encoding);
+        let contents = decoded.text;
+
+        let mut tags = vec![keep_root_tag, note_tag];
+        let (date, text) = if has_extension(&path, "json") {
+            let note = keep::parse(&contents)
+                .with_context(|| format!("failed to parse Keep note '{}'", path.display()))?;
+            if note.is_trashed {
+                report.skipped.push(SkippedFile {
+                    path: relative.to_path_buf(),
+                    reason: SkipReason::Trashed,
+                });
+                progress.file(relative, 0);
+                continue;
+            }
+
+            let labels: Vec<String> = note.labels.iter().map(|label| label.name.clone()).collect();
+            tags.extend(intern_tag_names(registry, &labels));
+            for (flag, status) in [(note.is_pinned, "pinned"), (note.is_archived, "archived")] {
+                if flag {
+                    tags.push(registry.intern_segment(Some(keep_root_tag), status));
+                }
+            }
+            (note.date(), note.to_text())
+        } else {
+            let note = html::parse(&contents);
+            let text = match note.title {
+                Some(title) if !title.is_empty() => format!("# {title}\n\n{}", note.text),
+                _ => note.text,
+            };
+            (note.created, text)
+        };
+        let date = match date {
+            Some(date) => date,
+            None => file_modified_date(&path).with_context(|| {
+                format!("failed to read modification date for '{}'", path.display())
+            })?,
+        };
+
+        tags.extend(rules.tags.apply(relative, &text, registry));
+        tags.sort_unstable();
+        tags.dedup();
+
+        blocks.push(TaggedBlock {
+            source: Some(BlockSource::file(relative, &contents)),
+            ..TaggedBlock::new(date, text, tags)
+        });
+        report.count_block(relative);
+        progress.file(relative, 1);
+    }
+
+    Ok(file_count)
+}
+
+struct ProjectNote {
+    path: PathBuf,
+    text: String,
+    encoding: Encoding,
+    date: NaiveDate,
+    source: DateSource,
+    /// Whether git was asked for a date and had no history for the note.
+    uncommitted: bool,
+}
+
+/// Project notes are dated by frontmatter, then git history when asked
+/// for, then a date file name, then modification time.
+fn read_project_note(
+    path: &Path,
+    date_keys: &[String],
+    dates_from_git: bool,
+) -> Result<ProjectNote> {
+    let decoded = encoding::read_text(path)
+        .with_context(|| format!("failed to read project note '{}'", path.display()))?;
+    let text = decoded.text;
+
+    let mut uncommitted = false;
+    let mut dated = frontmatter_date(&text, date_keys);
+    if dated.is_none() && dates_from_git {
+        dated = git::last_commit_date(path)?.map(|date| (date, DateSource::Git));
+        uncommitted = dated.is_none();
+    }
+    let dated = dated.or_else(|| {
+        let stem = path.file_stem().and_then(OsStr::to_str)?;
+        parse_journal_date(stem, &[]).map(|date| (date, DateSource::FileName))
+    });
+    let (date, source) = match dated {
+        Some(dated) => dated,
+        None => (
+            file_modified_date(path).with_context(|| {
+                format!("failed to read modification date for '{}'", path.display())
+            })?,
+            DateSource::ModifiedTime,
+        ),
+    };
+
+    Ok(ProjectNote {
+        path: path.to_path_buf(),
+        text,
+        encoding: decoded.encoding,
+        date,
+        source,
+        uncommitted,
+    })
+}
+
+/// The date under the first of `keys` that the note's frontmatter holds
+/// and that parses as a date.
+fn frontmatter_date(text: &str, keys: &[String]) -> Option<(NaiveDate, DateSource)> {
+    let (date, key) = vault::frontmatter_date(text, keys)?;
+    Some((
+        date,
+        DateSource::Frontmatter {
+            key: key.to_string(),
+        },
+    ))
+}
+
+fn apply_wikilinks(blocks: &mut [TaggedBlock], mode: WikilinkMode, registry: &mut TagRegistry) {
+    for block in blocks.iter_mut() {
+        let targets = markup::wikilinks(&block.text);
+        if targets.is_empty() {
+            continue;
+        }
+
+        match mode {
+            WikilinkMode::Ignore => {}
+            WikilinkMode::Tags => {
+                let link_root = registry.intern_segment(None, "link");
+                for target in &targets {
+                    if let Some(segment) = normalize_tag_segment(target) {
+                        block
+                            .tags
+                            .push(registry.intern_segment(Some(link_root), &segment));
+                    }
+                }
+                block.tags.sort_unstable();
+                block.tags.dedup();
+            }
+            WikilinkMode::Links => block.links = targets,
+        }
+    }
+}
+
+/// Collapses blocks with identical text according to `policy`, keeping the
+/// first occurrence. Returns the remaining blocks and the number of
+/// duplicates that were found.
+fn dedup_blocks(blocks: Vec<TaggedBlock>, policy: DedupPolicy) -> (Vec<TaggedBlock>, usize) {
+    let mut kept: Vec<TaggedBlock> = Vec::with_capacity(blocks.len());
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut duplicates = 0;
+
+    for block in blocks {
+        let hash = content_hash(&block.text);
+        let candidates = by_hash.entry(hash).or_default();
+        let existing = candidates
+            .iter()
+            .copied()
+            .find(|&index| kept[index].text == block.text);
+
+        let Some(index) = existing else {
+            candidates.push(kept.len());
+            kept.push(block);
+            continue;
+        };
+
+        duplicates += 1;
+        match policy {
+            DedupPolicy::Skip => {}
+            DedupPolicy::MergeTags => {
+                let original = &mut kept[index];
+                original.tags.extend(block.tags);
+                original.tags.sort_unstable();
+                original.tags.dedup();
+            }
+            DedupPolicy::KeepBoth => kept.push(block),
+        }
+    }
+
+    (kept, duplicates)
+}
+
+fn content_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn is_csv(path: &Path) -> bool {
+    has_extension(path, "csv")
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn ensure_directory(path: &Path) -> Result<&Path> {
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("failed to read metadata for '{}'", path.display()))?;
+
+    if !metadata.is_dir() {
+        anyhow::bail!("'{}' is not a directory", path.display());
+    }
+
+    Ok(path)
+}
+
+fn file_modified_date(path: &Path) -> Result<NaiveDate> {
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("failed to read metadata for '{}'", path.display()))?;
+    let modified = metadata
+        .modified()
+        .with_context(|| format!("failed to read modification time for '{}'", path.display()))?;
+
+    let datetime: DateTime<Utc> = DateTime::<Utc>::from(modified);
+    Ok(datetime.date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::SystemTime;
+
+    use assert_fs::prelude::*;
+    use chrono::{NaiveDateTime, NaiveTime};
+    use clap::CommandFactory;
+    use filetime::FileTime;
+    use sightline_lib::clock::ManualClock;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Snapshot {
+        version: u64,
+        blocks: Vec<TaggedBlock>,
+        #[serde(default)]
+        tag_registry: Vec<Tag>,
+    }
+
+    fn cli(source: &Path, output: &Path) -> Cli {
+        Cli::parse_from([
+            OsStr::new("sightline-importer"),
+            OsStr::new("--source"),
+            source.as_os_str(),
+            OsStr::new("--output"),
+            output.as_os_str(),
+        ])
+    }
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn run_errors_when_source_missing() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let output = temp.child("timeline.json");
+
+        let cli = cli(temp.child("missing").path(), output.path());
+
+        let result = run(cli);
+        assert!(result.is_err(), "expected missing directory error");
+    }
+
+    #[test]
+    fn run_imports_journal_and_project_notes() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        let journal = vault.child("journal");
+        journal.create_dir_all().expect("create journal");
+        let projects = vault.child("projects");
+        projects.create_dir_all().expect("create projects");
+
+        let journal_entry = journal.child("Sept 14, 2025.md");
+        journal_entry
+            .write_str("Morning reflection")
+            .expect("write journal");
+
+        let project_dir = projects.child("Sightline");
+        project_dir.create_dir_all().expect("create project dir");
+        let project_note = project_dir.child("Plan.md");
+        project_note
+            .write_str("Project plan notes")
+            .expect("write project note");
+
+        let project_date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let project_time =
+            NaiveDateTime::new(project_date, NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        let system_time: SystemTime =
+            DateTime::<Utc>::from_naive_utc_and_offset(project_time, Utc).into();
+        filetime::set_file_mtime(project_note.path(), FileTime::from_system_time(system_time))
+            .expect("set mtime");
+
+        let output = temp.child("out/timeline.json");
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+
+        assert_eq!(snapshot.version, 0);
+        assert_eq!(snapshot.blocks.len(), 2);
+        assert!(
+            snapshot
+                .tag_registry
+                .iter()
+                .all(|tag| tag.color.as_ref().is_some())
+        );
+
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+
+        let journal_date = NaiveDate::from_ymd_opt(2025, 9, 14).unwrap();
+        let journal_block = snapshot
+            .blocks
+            .iter()
+            .find(|block| block.date == journal_date)
+            .expect("journal block");
+        let journal_tags = tags_as_names(journal_block, &tag_names);
+        assert!(journal_tags.contains(&"type:journal".to_string()));
+
+        let project_block = snapshot
+            .blocks
+            .iter()
+            .find(|block| block.date == project_date)
+            .expect("project block");
+        let project_tags = tags_as_names(project_block, &tag_names);
+        assert!(project_tags.contains(&"type:project-note".to_string()));
+        assert!(project_tags.contains(&"project".to_string()));
+        assert!(project_tags.contains(&"project:sightline".to_string()));
+    }
+
+    #[test]
+    fn builder_imports_in_memory_from_custom_folders() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("daily/2025-01-02.md")
+            .write_str("Standup ![[chart.png]]")
+            .expect("write journal");
+        vault
+            .child("work/Plan.md")
+            .write_str("Roadmap")
+            .expect("write project note");
+        vault
+            .child("chart.png")
+            .write_str("png")
+            .expect("write attachment");
+
+        let imported = Importer::builder()
+            .source(vault.path())
+            .journal_dir("daily")
+            .projects_dir("work")
+            .run()
+            .expect("run importer");
+
+        let texts: Vec<&str> = imported
+            .blocks
+            .iter()
+            .map(|block| block.text.as_str())
+            .collect();
+        assert!(texts.contains(&"Standup ![[chart.png]]"));
+        assert!(texts.contains(&"Roadmap"));
+        assert!(imported.report.tags.contains(&"type:journal".to_string()));
+        assert_eq!(imported.metrics.blocks_created, 2);
+        assert_eq!(imported.metrics.attachments_copied, 0);
+        assert_eq!(
+            fs::read_dir(temp.path()).expect("list temp").count(),
+            1,
+            "nothing but the vault is written"
+        );
+
+        let mut bytes = Vec::new();
+        imported
+            .write(&mut bytes, SnapshotEncoding::Jsonl)
+            .expect("write snapshot");
+        let timeline = timeline::Timeline::from_snapshot_bytes(&bytes).expect("load snapshot");
+        assert_eq!(timeline.entry_count(), 2);
+    }
+
+    #[test]
+    fn output_formats_load_as_timelines() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        let journal = vault.child("journal");
+        journal
+            .child("2025-01-02.md")
+            .write_str("First day")
+            .expect("write journal");
+        journal
+            .child("2025-01-03.md")
+            .write_str("Second day")
+            .expect("write journal");
+
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::Cbor,
+            OutputFormat::Jsonl,
+            OutputFormat::Sqlite,
+        ] {
+            let output = temp.child(format!("timeline-{format:?}"));
+            let mut cli = cli(vault.path(), output.path());
+            cli.output_format = format;
+            run(cli).expect("run importer");
+
+            let timeline =
+                timeline::Timeline::load_from_path(output.path()).expect("load snapshot");
+            assert_eq!(timeline.entry_count(), 2, "{format:?}");
+            let content = timeline.content();
+            assert!(content.contains("First day") && content.contains("Second day"));
+            assert!(
+                timeline
+                    .tag_registry()
+                    .find_colon_path("type:journal")
+                    .is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn importing_over_a_workspace_discards_its_edit_log() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        vault
+            .child("journal/2025-01-02.md")
+            .write_str("Imported day")
+            .expect("write journal");
+        let output = temp.child("timeline.json");
+        output.write_str(r#"{"version": 7, "blocks": []}"#).unwrap();
+        let log = wal::log_path(output.path());
+        wal::append(
+            &log,
+            &wal::LogEntry {
+                version: 8,
+                date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                at: None,
+                ops: vec![sightline_lib::api::TextOperation::Insert {
+                    position: 0,
+                    text: "Stale edit".to_string(),
+                }],
+            },
+        )
+        .expect("append log");
+
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        assert!(!log.exists());
+        let timeline = timeline::Timeline::load_from_path(output.path()).expect("load snapshot");
+        assert_eq!(timeline.content(), "Imported day");
+    }
+
+    #[test]
+    fn dedup_policy_controls_duplicate_notes() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        let journal = vault.child("journal");
+        journal.create_dir_all().expect("create journal");
+        let projects = vault.child("projects");
+        projects.create_dir_all().expect("create projects");
+
+        journal
+            .child("2025-01-02.md")
+            .write_str("Shared note")
+            .expect("write journal");
+        projects
+            .child("Shared.md")
+            .write_str("Shared note")
+            .expect("write project note");
+
+        let import_with = |policy: DedupPolicy| -> Snapshot {
+            let output = temp.child(format!("{policy:?}.json"));
+            let mut cli = cli(vault.path(), output.path());
+            cli.dedup = policy;
+            run(cli).expect("run importer");
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot")
+        };
+
+        assert_eq!(import_with(DedupPolicy::KeepBoth).blocks.len(), 2);
+
+        let skipped = import_with(DedupPolicy::Skip);
+        assert_eq!(skipped.blocks.len(), 1);
+        let tag_names = build_tag_name_map(&skipped.tag_registry);
+        let names = tags_as_names(&skipped.blocks[0], &tag_names);
+        assert_eq!(names, vec!["type:journal".to_string()]);
+
+        let merged = import_with(DedupPolicy::MergeTags);
+        assert_eq!(merged.blocks.len(), 1);
+        let tag_names = build_tag_name_map(&merged.tag_registry);
+        let names = tags_as_names(&merged.blocks[0], &tag_names);
+        assert!(names.contains(&"type:journal".to_string()));
+        assert!(names.contains(&"type:project-note".to_string()));
+        assert_eq!(
+            merged.blocks[0].date,
+            NaiveDate::from_ymd_opt(2025, 1, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn non_utf8_notes_are_decoded_and_reported() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        let journal = vault.child("journal");
+        journal
+            .child("2025-01-01.md")
+            .write_binary(b"Caf\xe9 au lait")
+            .expect("write latin-1 note");
+        journal
+            .child("2025-01-02.md")
+            .write_str("Plain")
+            .expect("write utf-8 note");
+
+        let output = temp.child("timeline.json");
+        let report = run(cli(vault.path(), output.path())).expect("run importer");
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(snapshot.blocks[0].text, "Café au lait");
+        assert_eq!(
+            report.decoded_files,
+            vec![DecodedFile {
+                path: PathBuf::from("journal/2025-01-01.md"),
+                encoding: Encoding::Windows1252,
+            }]
+        );
+    }
+
+    #[test]
+    fn sightline_snapshots_merge_with_remapped_tags() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let laptop = temp.child("laptop.json");
+        laptop
+            .write_str(
+                &serde_json::json!({
+                    "version": 3,
+                    "blocks": [
+                        {"date": "2024-01-01", "text": "Same\n", "tags": [1]},
+                        {"date": "2024-01-02", "text": "Twice\n", "tags": []},
+                        {"date": "2024-01-02", "text": "Twice\n", "tags": []}
+                    ],
+                    "tag_registry": [{"id": 1, "name": "work", "parent_id": null, "color": "red"}],
+                    "snippets": [{"trigger": ";sig", "expansion": "Best"}]
+                })
+                .to_string(),
+            )
+            .expect("write snapshot");
+        let desktop = temp.child("desktop.json");
+        desktop
+            .write_str(
+                &serde_json::json!({
+                    "version": 7,
+                    "blocks": [
+                        {"date": "2024-01-01", "text": "Same\n", "tags": [1]},
+                        {"date": "2023-12-31", "text": "Only here\n", "tags": [2]},
+                        {"date": "2024-01-02", "text": "Twice\n", "tags": [1]}
+                    ],
+                    "tag_registry": [
+                        {"id": 1, "name": "home", "parent_id": null},
+                        {"id": 2, "name": "work", "parent_id": null}
+                    ]
+                })
+                .to_string(),
+            )
+            .expect("write snapshot");
+
+        let imported = Importer::builder()
+            .source(laptop.path())
+            .format(SourceFormat::Sightline)
+            .merge_with(desktop.path())
+            .run()
+            .expect("merge snapshots");
+        let tag_names = build_tag_name_map(&imported.tags);
+        let blocks: Vec<(&str, Vec<String>)> = imported
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut names = tags_as_names(block, &tag_names);
+                names.sort();
+                (block.text.as_str(), names)
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                ("Only here\n", vec!["work".to_string()]),
+                ("Same\n", vec!["home".to_string(), "work".to_string()]),
+                ("Twice\n", vec!["home".to_string()]),
+                ("Twice\n", Vec::new()),
+            ]
+        );
+        let work = imported.tags.iter().find(|tag| tag.name == "work").unwrap();
+        assert_eq!(work.color.as_deref(), Some("red"));
+        assert_eq!(imported.metrics.files_processed, 2);
+        assert!(imported.metrics.warnings[0].contains("merged 2 block"));
+        assert!(
+            imported.metrics.warnings[1].contains("were not carried over: snippets"),
+            "{:?}",
+            imported.metrics.warnings
+        );
+
+        assert!(
+            Importer::builder()
+                .source(laptop.path())
+                .merge_with(desktop.path())
+                .run()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn undated_policy_skips_dates_or_rejects_undated_notes() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal/ideas.md")
+            .write_str("No date here")
+            .expect("write journal");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let import = |policy| {
+            Importer::builder()
+                .source(vault.path())
+                .undated(policy)
+                .clock(SharedClock::new(ManualClock::new(now)))
+                .run()
+        };
+
+        let skipped = import(UndatedPolicy::Skip).expect("import");
+        assert!(skipped.blocks.is_empty());
+        assert_eq!(
+            skipped.report.unparseable_dates,
+            vec![PathBuf::from("journal/ideas.md")]
+        );
+
+        let path = PathBuf::from("journal/ideas.md");
+        let today = import(UndatedPolicy::Today).expect("import");
+        assert_eq!(today.blocks[0].date, now.date_naive());
+        assert_eq!(today.report.date_sources[&path], DateSource::Today);
+        assert!(today.report.unparseable_dates.is_empty());
+
+        let mtime = import(UndatedPolicy::Mtime).expect("import");
+        assert_eq!(
+            mtime.blocks[0].date,
+            file_modified_date(&vault.path().join(&path)).unwrap()
+        );
+        assert_eq!(mtime.report.date_sources[&path], DateSource::ModifiedTime);
+
+        let error = import(UndatedPolicy::Error).expect_err("undated note");
+        assert!(error.to_string().contains("journal/ideas.md"));
+    }
+
+    #[test]
+    fn split_by_date_heading_makes_a_journal_block_per_day() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal/2024.md")
+            .write_str("# 2024\n\n## 2024-03-10\nRan.\n\n## 2024-03-11\nRested.\n")
+            .expect("write diary");
+        vault
+            .child("projects/Trip/Log.md")
+            .write_str("## March 12, 2024\nPacked.\n")
+            .expect("write log");
+
+        let unsplit = Importer::builder()
+            .source(vault.path())
+            .run()
+            .expect("import");
+        assert_eq!(
+            unsplit.report.unparseable_dates,
+            vec![PathBuf::from("journal/2024.md")]
+        );
+
+        let imported = Importer::builder()
+            .source(vault.path())
+            .split_by(SplitBy::DateHeading)
+            .run()
+            .expect("import");
+        let tag_names = build_tag_name_map(&imported.tags);
+        let days: Vec<(String, &str, Vec<String>)> = imported
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut names = tags_as_names(block, &tag_names);
+                names.sort();
+                (block.date.to_string(), block.text.as_str(), names)
+            })
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                (
+                    "2024-03-10".to_string(),
+                    "Ran.\n",
+                    vec!["type:journal".to_string()]
+                ),
+                (
+                    "2024-03-11".to_string(),
+                    "Rested.\n",
+                    vec!["type:journal".to_string()]
+                ),
+                (
+                    "2024-03-12".to_string(),
+                    "Packed.\n",
+                    vec![
+                        "project".to_string(),
+                        "project:trip".to_string(),
+                        "type:journal".to_string()
+                    ]
+                ),
+            ]
+        );
+        assert_eq!(
+            imported.report.date_sources[Path::new("journal/2024.md")],
+            DateSource::Heading
+        );
+        let diary = "# 2024\n\n## 2024-03-10\nRan.\n\n## 2024-03-11\nRested.\n";
+        let sources: Vec<(&Path, &str)> = imported
+            .blocks
+            .iter()
+            .map(|block| {
+                let source = block.source.as_ref().expect("block source");
+                let text = if source.path.starts_with("journal") {
+                    &diary[source.start..source.end]
+                } else {
+                    ""
+                };
+                (source.path.as_path(), text)
+            })
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                (Path::new("journal/2024.md"), "## 2024-03-10\nRan.\n\n"),
+                (Path::new("journal/2024.md"), "## 2024-03-11\nRested.\n"),
+                (Path::new("projects/Trip/Log.md"), ""),
+            ]
+        );
+        assert!(imported.report.unparseable_dates.is_empty());
+    }
+
+    #[test]
+    fn project_folder_tags_follow_root_depth_and_skip_options() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal")
+            .create_dir_all()
+            .expect("create journal");
+        for (path, text) in [
+            ("projects/Client/Site/Launch/plan.md", "Plan"),
+            ("projects/attachments/spec.md", "Spec"),
+            ("projects/Attachments/Mobile/todo.md", "Todo"),
+        ] {
+            vault.child(path).write_str(text).expect("write note");
+        }
+
+        let imported = Importer::builder()
+            .source(vault.path())
+            .project_tag("work:projects")
+            .folder_tag_depth(1)
+            .skip_folder("attachments")
+            .run()
+            .expect("import");
+        let tag_names = build_tag_name_map(&imported.tags);
+        let tags_of = |needle: &str| {
+            let block = imported
+                .blocks
+                .iter()
+                .find(|block| block.text == needle)
+                .expect("block");
+            let mut names = tags_as_names(block, &tag_names);
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            tags_of("Plan"),
+            vec!["type:project-note", "work:projects", "work:projects:client"]
+        );
+        assert_eq!(tags_of("Spec"), vec!["type:project-note", "work:projects"]);
+        assert_eq!(
+            tags_of("Todo"),
+            vec!["type:project-note", "work:projects", "work:projects:mobile"]
+        );
+        assert!(!imported.report.tags.contains(&"project".to_string()));
+    }
+
+    #[test]
+    fn frontmatter_date_keys_take_precedence_in_order() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        for (path, text) in [
+            (
+                "journal/2025-01-01.md",
+                "---\ncreated: 2024-12-30T09:00\ndate: not a date\n---\nLate write-up",
+            ),
+            ("journal/2025-01-02.md", "Plain day"),
+            (
+                "journal/someday.md",
+                "---\nday: 2025-01-05\n---\nNamed freely",
+            ),
+            (
+                "projects/Work/Plan.md",
+                "---\njournal-date: 2024-06-01\n---\nPlan",
+            ),
+            ("projects/Work/2024-07-01.md", "Dated by name"),
+            ("projects/Work/Notes.md", "Notes"),
+        ] {
+            vault.child(path).write_str(text).expect("write note");
+        }
+
+        let output = temp.child("timeline.json");
+        let report = run(cli(vault.path(), output.path())).expect("run importer");
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let date_of = |needle: &str| {
+            snapshot
+                .blocks
+                .iter()
+                .find(|block| block.text.contains(needle))
+                .map(|block| block.date)
+                .expect("block")
+        };
+        assert_eq!(
+            date_of("Late write-up"),
+            NaiveDate::from_ymd_opt(2024, 12, 30).unwrap()
+        );
+        assert_eq!(
+            date_of("Named freely"),
+            NaiveDate::from_ymd_opt(2025, 1, 5).unwrap()
+        );
+        assert_eq!(
+            date_of("Plan"),
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()
+        );
+        assert_eq!(
+            date_of("Dated by name"),
+            NaiveDate::from_ymd_opt(2024, 7, 1).unwrap()
+        );
+        assert!(report.unparseable_dates.is_empty());
+
+        let frontmatter = |key: &str| DateSource::Frontmatter {
+            key: key.to_string(),
+        };
+        assert_eq!(
+            report.date_sources,
+            BTreeMap::from([
+                (
+                    PathBuf::from("journal/2025-01-01.md"),
+                    frontmatter("created")
+                ),
+                (PathBuf::from("journal/2025-01-02.md"), DateSource::FileName),
+                (PathBuf::from("journal/someday.md"), frontmatter("day")),
+                (
+                    PathBuf::from("projects/Work/2024-07-01.md"),
+                    DateSource::FileName
+                ),
+                (
+                    PathBuf::from("projects/Work/Notes.md"),
+                    DateSource::ModifiedTime
+                ),
+                (
+                    PathBuf::from("projects/Work/Plan.md"),
+                    frontmatter("journal-date")
+                ),
+            ])
+        );
+
+        let mut cli = cli(vault.path(), output.path());
+        cli.date_keys = vec!["date".to_string()];
+        let report = run(cli).expect("run importer");
+        assert_eq!(
+            report.unparseable_dates,
+            vec![PathBuf::from("journal/someday.md")]
+        );
+        assert_eq!(
+            report.date_sources[Path::new("journal/2025-01-01.md")],
+            DateSource::FileName
+        );
+    }
+
+    #[test]
+    fn dates_from_git_uses_last_commit_date() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal")
+            .create_dir_all()
+            .expect("create journal");
+        vault
+            .child("projects/Alpha/Committed.md")
+            .write_str("Committed")
+            .expect("write committed note");
+
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(vault.path())
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", "2023-03-04T12:00:00Z")
+                .env("GIT_COMMITTER_DATE", "2023-03-04T12:00:00Z")
+                .status()
+                .expect("run git");
+            assert!(status.success(), "git {args:?} failed");
+        };
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "notes"]);
+
+        // Read as a glob, this name would match the committed note.
+        vault
+            .child("projects/Alpha/[C]ommitted.md")
+            .write_str("Draft")
+            .expect("write uncommitted note");
+
+        let output = temp.child("timeline.json");
+        let metrics_out = temp.child("metrics.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.dates_from_git = true;
+        cli.metrics_out = Some(metrics_out.path().to_path_buf());
+        run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let committed = snapshot
+            .blocks
+            .iter()
+            .find(|block| block.text == "Committed")
+            .expect("committed block");
+        assert_eq!(committed.date, NaiveDate::from_ymd_opt(2023, 3, 4).unwrap());
+
+        let metrics: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(metrics_out.path()).expect("read metrics"))
+                .expect("parse metrics");
+        assert_eq!(
+            metrics["warnings"],
+            serde_json::json!([
+                "1 project note(s) have no git history; used file name or modification time"
+            ])
+        );
+    }
+
+    #[test]
+    fn dates_from_git_errors_outside_repository() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal")
+            .create_dir_all()
+            .expect("create journal");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+
+        let mut cli = cli(vault.path(), temp.child("timeline.json").path());
+        cli.dates_from_git = true;
+        assert!(run(cli).is_err(), "expected missing repository error");
+    }
+
+    #[test]
+    fn nested_journal_dates_come_from_directories() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        vault
+            .child("journal/2024/05/2024-05-01.md")
+            .write_str("Full name")
+            .expect("write journal");
+        vault
+            .child("journal/2024/05/02.md")
+            .write_str("Day only")
+            .expect("write journal");
+        vault
+            .child("journal/archive/2023/March/14.md")
+            .write_str("Month name")
+            .expect("write journal");
+
+        let output = temp.child("timeline.json");
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let dates: Vec<(NaiveDate, &str)> = snapshot
+            .blocks
+            .iter()
+            .map(|block| (block.date, block.text.as_str()))
+            .collect();
+        assert_eq!(
+            dates,
+            vec![
+                (NaiveDate::from_ymd_opt(2023, 3, 14).unwrap(), "Month name"),
+                (NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), "Full name"),
+                (NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), "Day only"),
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_and_monthly_notes_are_dated_and_typed() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        for (path, text) in [
+            ("journal/2024-05-21.md", "Day"),
+            ("journal/2024-W21.md", "Week"),
+            ("journal/2024-05.md", "Month"),
+        ] {
+            vault.child(path).write_str(text).expect("write journal");
+        }
+
+        let output = temp.child("timeline.json");
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+        let blocks: Vec<(NaiveDate, &str, Vec<String>)> = snapshot
+            .blocks
             .iter()
-            .find(|block| block.date == project_date)
-            .expect("project block");
-        let project_tags = tags_as_names(project_block, &tag_names);
-        assert!(project_tags.contains(&"type:project-note".to_string()));
-        assert!(project_tags.contains(&"project".to_string()));
-        assert!(project_tags.contains(&"project:sightline".to_string()));
+            .map(|block| {
+                let tags = tags_as_names(block, &tag_names);
+                (block.date, block.text.as_str(), tags)
+            })
+            .collect();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                (date(1), "Month", vec!["type:monthly-note".to_string()]),
+                (date(20), "Week", vec!["type:weekly-note".to_string()]),
+                (date(21), "Day", vec!["type:journal".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn embedded_attachments_are_copied_and_rewritten() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal/2025-03-01.md")
+            .write_str("Trip ![[beach photo.png|400]] and ![[gone.pdf]]")
+            .expect("write journal");
+        vault
+            .child("projects/Alpha/Plan.md")
+            .write_str("Diagram ![[Alpha/img/diagram.png]]")
+            .expect("write project note");
+        vault
+            .child("attachments/beach photo.png")
+            .write_binary(b"beach")
+            .expect("write attachment");
+        vault
+            .child("Alpha/img/diagram.png")
+            .write_binary(b"diagram")
+            .expect("write attachment");
+
+        let output = temp.child("out/timeline.json");
+        let metrics_out = temp.child("metrics.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.metrics_out = Some(metrics_out.path().to_path_buf());
+        run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let texts: Vec<&str> = snapshot
+            .blocks
+            .iter()
+            .map(|block| block.text.as_str())
+            .collect();
+        assert!(
+            texts.contains(&"Trip ![beach photo.png](<assets/beach photo.png>) and ![[gone.pdf]]")
+        );
+        assert!(texts.contains(&"Diagram ![Alpha/img/diagram.png](<assets/diagram.png>)"));
+
+        let assets = temp.child("out/assets");
+        assert_eq!(
+            fs::read(assets.child("beach photo.png").path()).expect("read copy"),
+            b"beach"
+        );
+        assert_eq!(
+            fs::read(assets.child("diagram.png").path()).expect("read copy"),
+            b"diagram"
+        );
+
+        let metrics: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(metrics_out.path()).expect("read metrics"))
+                .expect("parse metrics");
+        assert_eq!(metrics["attachments_copied"], 2);
+        assert_eq!(
+            metrics["warnings"],
+            serde_json::json!(["attachment 'gone.pdf' embedded in notes was not found"])
+        );
+    }
+
+    #[test]
+    fn embeds_outside_the_vault_are_not_copied() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        temp.child("secret.png")
+            .write_binary(b"secret")
+            .expect("write outside file");
+        let outside = temp.child("secret.png").path().display().to_string();
+        vault
+            .child("journal/2025-03-01.md")
+            .write_str(&format!("![[../secret.png]] ![[{outside}]]"))
+            .expect("write journal");
+        vault.child("projects").create_dir_all().expect("projects");
+
+        let output = temp.child("out/timeline.json");
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(
+            snapshot.blocks[0].text,
+            format!("![[../secret.png]] ![[{outside}]]")
+        );
+        assert!(!temp.child("out/assets").exists());
+    }
+
+    #[test]
+    fn parallel_project_import_is_deterministic() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal")
+            .create_dir_all()
+            .expect("create journal");
+        for index in 0..64 {
+            vault
+                .child(format!("projects/Area {}/Topic {index}/Note.md", index % 5))
+                .write_str(&format!("Note {index}"))
+                .expect("write project note");
+        }
+
+        let import = |name: &str| {
+            let output = temp.child(name);
+            run(cli(vault.path(), output.path())).expect("run importer");
+            fs::read_to_string(output.path()).expect("read snapshot")
+        };
+
+        assert_eq!(import("first.json"), import("second.json"));
+    }
+
+    #[test]
+    fn include_and_exclude_globs_filter_notes() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        for (path, text) in [
+            ("journal/2025-01-01.md", "Day"),
+            ("journal/templates/daily.md", "Template"),
+            ("projects/Work/Plan.md", "Work plan"),
+            ("projects/Work/archive/Old.md", "Old work"),
+            ("projects/Home/List.md", "Home list"),
+        ] {
+            vault.child(path).write_str(text).expect("write note");
+        }
+
+        let output = temp.child("timeline.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.include = vec!["journal/**".to_string(), "Work/**".to_string()];
+        cli.exclude = vec!["templates/**".to_string(), "**/archive/**".to_string()];
+        run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let mut texts: Vec<&str> = snapshot
+            .blocks
+            .iter()
+            .map(|block| block.text.as_str())
+            .collect();
+        texts.sort_unstable();
+        assert_eq!(texts, vec!["Day", "Work plan"]);
+    }
+
+    #[test]
+    fn csv_rows_become_blocks_with_mapped_columns() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let source = temp.child("habits.csv");
+        source
+            .write_str(
+                "Day,Mood,Note,Labels\n\
+                 2025-03-01,good,\"Ran 5km, felt great\",health; #habit:run\n\
+                 someday,meh,No date,\n\
+                 2025-03-02T08:30:00,ok,Read,\n",
+            )
+            .expect("write csv");
+
+        let output = temp.child("timeline.json");
+        let mut cli = cli(source.path(), output.path());
+        cli.format = SourceFormat::Csv;
+        cli.map = Some("date=Day,text=col3,tags=Labels".parse().expect("map"));
+        cli.tag_delimiter = ";".to_string();
+        let report = run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(snapshot.blocks.len(), 2);
+        assert_eq!(snapshot.blocks[0].text, "Ran 5km, felt great\n");
+        let csv = fs::read_to_string(source.path()).expect("read csv");
+        let row = snapshot.blocks[0].source.as_ref().expect("row source");
+        assert_eq!(row.path, PathBuf::from("habits.csv"));
+        assert_eq!(
+            &csv[row.start..row.end],
+            "2025-03-01,good,\"Ran 5km, felt great\",health; #habit:run"
+        );
+        assert_eq!(
+            snapshot.blocks[1].date,
+            NaiveDate::from_ymd_opt(2025, 3, 2).unwrap()
+        );
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+        let mut names = tags_as_names(&snapshot.blocks[0], &tag_names);
+        names.sort();
+        assert_eq!(names, vec!["habit:run", "health"]);
+
+        assert_eq!(
+            report.unparseable_dates,
+            vec![PathBuf::from("habits.csv:3")]
+        );
+        assert_eq!(
+            report.blocks_per_directory,
+            BTreeMap::from([(PathBuf::from("habits.csv"), 2)])
+        );
+    }
+
+    #[test]
+    fn notion_export_pages_rows_and_images_become_blocks() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let export = temp.child("export");
+        let home = "Home 0123456789abcdef0123456789abcdef";
+        let tasks = "Tasks 00000000000000000000000000000001";
+        export
+            .child(format!("{home}.md"))
+            .write_str(
+                "# Home\n\nWelcome ![map](Home%200123456789abcdef0123456789abcdef/map.png)\n",
+            )
+            .expect("write page");
+        export
+            .child(format!("{home}/map.png"))
+            .write_str("png")
+            .expect("write image");
+        export
+            .child(format!("{home}/{tasks}.csv"))
+            .write_str("Name,Due date,Tags\nShip,\"March 4, 2024\",Work\nLoose,2024/03/05,\n")
+            .expect("write database");
+        export
+            .child(format!(
+                "{home}/{tasks}/Ship 00000000000000000000000000000002.md"
+            ))
+            .write_str("# Ship\n\nDue date: March 4, 2024\nTags: Work\n\nDetails\n")
+            .expect("write row page");
+
+        let output = temp.child("out/timeline.json");
+        let mut cli = cli(export.path(), output.path());
+        cli.format = SourceFormat::Notion;
+        run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+        let block = |title: &str| {
+            snapshot
+                .blocks
+                .iter()
+                .find(|block| block.text.starts_with(&format!("# {title}")))
+                .unwrap_or_else(|| panic!("no block for {title}"))
+        };
+
+        let home_block = block("Home");
+        assert!(
+            home_block
+                .text
+                .contains("![Home 0123456789abcdef0123456789abcdef/map.png](<assets/map.png>)")
+        );
+        assert!(temp.child("out/assets/map.png").path().is_file());
+
+        let ship = block("Ship");
+        assert_eq!(ship.date, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+        let mut names = tags_as_names(ship, &tag_names);
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "notion",
+                "notion:home",
+                "notion:home:tasks",
+                "type:notion-page",
+                "work"
+            ]
+        );
+
+        let loose = block("Loose");
+        assert_eq!(loose.text, "# Loose\n\nDue date: 2024/03/05\n");
+        assert_eq!(loose.date, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap());
+        assert!(tags_as_names(loose, &tag_names).contains(&"type:notion-row".to_string()));
+        assert_eq!(snapshot.blocks.len(), 3);
+    }
+
+    #[test]
+    fn keep_takeout_notes_become_tagged_blocks() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let keep = temp.child("Takeout/Keep");
+        keep.child("Trip.json")
+            .write_str(
+                r#"{"title": "Trip", "textContent": "Pack boots ![[x]]",
+                    "labels": [{"name": "Travel Plans"}], "isPinned": true,
+                    "attachments": [{"filePath": "map.png", "mimetype": "image/png"}],
+                    "createdTimestampUsec": 1709547300000000}"#,
+            )
+            .expect("write json note");
+        keep.child("Trip.html")
+            .write_str("<html><body>duplicate rendering</body></html>")
+            .expect("write html rendering");
+        keep.child("map.png").write_str("png").expect("write image");
+        keep.child("Old.json")
+            .write_str(r#"{"title": "Old", "isTrashed": true}"#)
+            .expect("write trashed note");
+        keep.child("Legacy.html")
+            .write_str(
+                "<html><head><title>Legacy</title></head><body><p>From 2015</p></body></html>",
+            )
+            .expect("write html-only note");
+
+        let output = temp.child("out/timeline.json");
+        let mut cli = cli(keep.path(), output.path());
+        cli.format = SourceFormat::Keep;
+        let report = run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(snapshot.blocks.len(), 2);
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+
+        let trip = snapshot
+            .blocks
+            .iter()
+            .find(|block| block.text.starts_with("# Trip"))
+            .expect("trip block");
+        assert_eq!(trip.date, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+        assert!(trip.text.ends_with("![map.png](<assets/map.png>)\n"));
+        let mut names = tags_as_names(trip, &tag_names);
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["keep", "keep:pinned", "travel-plans", "type:keep-note"]
+        );
+
+        assert!(
+            snapshot
+                .blocks
+                .iter()
+                .any(|block| block.text == "# Legacy\n\nFrom 2015\n")
+        );
+        assert!(report.skipped.contains(&SkippedFile {
+            path: PathBuf::from("Old.json"),
+            reason: SkipReason::Trashed,
+        }));
+    }
+
+    #[test]
+    fn html_notes_become_blocks_with_embedded_or_file_dates() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let notes = temp.child("notes");
+        notes
+            .child("Travel/Trip.html")
+            .write_str(
+                "<html><head><title>Trip</title>\
+                 <meta name=\"created\" content=\"2024-03-04 10:15:00\"></head>\
+                 <body><div>Pack <b>boots</b></div></body></html>",
+            )
+            .expect("write dated note");
+        notes
+            .child("2024-05-06.htm")
+            .write_str("<p>Untitled &amp; undated</p>")
+            .expect("write named note");
+        notes
+            .child("Travel/photo.jpg")
+            .write_str("jpg")
+            .expect("write attachment");
+
+        let output = temp.child("timeline.json");
+        let mut cli = cli(notes.path(), output.path());
+        cli.format = SourceFormat::Html;
+        let report = run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(snapshot.blocks.len(), 2);
+        assert_eq!(snapshot.blocks[0].text, "# Trip\n\nPack **boots**\n");
+        assert_eq!(
+            snapshot.blocks[0].date,
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+        );
+        assert_eq!(snapshot.blocks[1].text, "Untitled & undated\n");
+        assert_eq!(
+            snapshot.blocks[1].date,
+            NaiveDate::from_ymd_opt(2024, 5, 6).unwrap()
+        );
+
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+        let mut names = tags_as_names(&snapshot.blocks[0], &tag_names);
+        names.sort();
+        assert_eq!(names, vec!["notes", "notes:travel", "type:note"]);
+        assert_eq!(
+            report.skipped,
+            vec![SkippedFile {
+                path: PathBuf::from("Travel/photo.jpg"),
+                reason: SkipReason::NotHtml,
+            }]
+        );
+    }
+
+    #[test]
+    fn table_format_requires_column_map() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let source = temp.child("habits.tsv");
+        source.write_str("Day\tNote\n").expect("write tsv");
+
+        let mut cli = cli(source.path(), temp.child("timeline.json").path());
+        cli.format = SourceFormat::Tsv;
+        assert!(run(cli).is_err(), "expected missing --map error");
+    }
+
+    #[test]
+    fn tag_rules_file_adds_tags_during_import() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        for (path, text) in [
+            ("journal/2025-01-01.md", "Daily standup notes"),
+            ("journal/2025-01-02.md", "Quiet day"),
+            ("projects/Work/Standup agenda.md", "Agenda"),
+        ] {
+            vault.child(path).write_str(text).expect("write note");
+        }
+        let rules = temp.child("rules.toml");
+        rules
+            .write_str(
+                "[[rule]]\npattern = \"(?i)standup\"\ntag = \"#type:standup\"\n\n\
+                 [[rule]]\npattern = \"standup\"\ntag = \"meeting\"\nmatch = \"content\"\n",
+            )
+            .expect("write rules");
+
+        let output = temp.child("timeline.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.tag_rules = Some(rules.path().to_path_buf());
+        run(cli).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        let tag_names = build_tag_name_map(&snapshot.tag_registry);
+        let tags_for = |text: &str| {
+            let block = snapshot
+                .blocks
+                .iter()
+                .find(|block| block.text == text)
+                .expect("block");
+            let mut names = tags_as_names(block, &tag_names);
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            tags_for("Daily standup notes"),
+            vec!["meeting", "type:journal"]
+        );
+        assert_eq!(tags_for("Quiet day"), vec!["type:journal"]);
+        assert!(tags_for("Agenda").contains(&"type:standup".to_string()));
+    }
+
+    #[test]
+    fn markdown_exports_keep_their_tags_on_import() {
+        use sightline_lib::markdown::{self, TagRendering};
+
+        let mut exported = timeline::Timeline::default();
+        for (day, text, tag) in [
+            (1, "Fixed the fence\n", "project:home"),
+            (2, "Standup\n", "work"),
+        ] {
+            let date = NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+            exported
+                .insert_on_date(exported.version(), date, text)
+                .expect("insert");
+            exported
+                .assign_block_tags(day as usize - 1, &[tag.to_string()])
+                .expect("tag block");
+        }
+
+        for rendering in [TagRendering::Frontmatter, TagRendering::Inline] {
+            let temp = assert_fs::TempDir::new().expect("temp dir");
+            markdown::export(
+                &exported,
+                temp.path(),
+                rendering,
+                timeline::SensitiveContent::Included,
+            )
+            .expect("export markdown");
+            temp.child("projects")
+                .create_dir_all()
+                .expect("projects dir");
+
+            let imported = Importer::builder()
+                .source(temp.path())
+                .run()
+                .expect("run importer");
+            let tag_names = build_tag_name_map(&imported.tags);
+            let tags_on = |date: NaiveDate| {
+                let block = imported
+                    .blocks
+                    .iter()
+                    .find(|block| block.date == date)
+                    .expect("block");
+                let mut names = tags_as_names(block, &tag_names);
+                names.sort();
+                names
+            };
+
+            assert_eq!(
+                tags_on(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()),
+                vec!["project:home"],
+                "{rendering:?}"
+            );
+            assert_eq!(
+                tags_on(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap()),
+                vec!["work"],
+                "{rendering:?}"
+            );
+            let texts: Vec<&str> = imported
+                .blocks
+                .iter()
+                .map(|block| block.text.as_str())
+                .collect();
+            assert_eq!(texts, ["Fixed the fence\n", "Standup\n"], "{rendering:?}");
+        }
+    }
+
+    #[test]
+    fn report_lists_skipped_files_tags_and_directory_counts() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        for (path, text) in [
+            ("journal/2025-01-01.md", "Day"),
+            ("journal/someday.md", "Undated"),
+            ("journal/sketch.png", "png"),
+            ("projects/Work/Plan.md", "Work plan"),
+            ("projects/Work/Notes.md", "Work notes"),
+            ("projects/drafts/Idea.md", "Idea"),
+        ] {
+            vault.child(path).write_str(text).expect("write note");
+        }
+
+        let output = temp.child("timeline.json");
+        let report_path = temp.child("report.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.exclude = vec!["drafts/**".to_string()];
+        cli.report = Some(report_path.path().to_path_buf());
+        let report = run(cli).expect("run importer");
+
+        assert_eq!(
+            report.skipped,
+            vec![
+                SkippedFile {
+                    path: PathBuf::from("journal/sketch.png"),
+                    reason: SkipReason::NotMarkdown,
+                },
+                SkippedFile {
+                    path: PathBuf::from("projects/drafts"),
+                    reason: SkipReason::Excluded,
+                },
+            ]
+        );
+        assert_eq!(
+            report.unparseable_dates,
+            vec![PathBuf::from("journal/someday.md")]
+        );
+        assert_eq!(
+            report.tags,
+            vec![
+                "project",
+                "project:work",
+                "type",
+                "type:journal",
+                "type:project-note"
+            ]
+        );
+        assert_eq!(
+            report.blocks_per_directory,
+            BTreeMap::from([
+                (PathBuf::from("journal"), 1),
+                (PathBuf::from("projects/Work"), 2),
+            ])
+        );
+
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(report_path.path()).expect("read report"))
+                .expect("parse report");
+        assert_eq!(written["skipped"][0]["reason"], "not_markdown");
+        assert_eq!(written["blocks_per_directory"]["projects/Work"], 2);
+    }
+
+    #[test]
+    fn metrics_out_reports_counts_and_phases() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("journal")
+            .create_dir_all()
+            .expect("create journal");
+        vault
+            .child("journal/2025-02-01.md")
+            .write_str("Journal")
+            .expect("write journal");
+        vault
+            .child("projects/Alpha/Notes.md")
+            .write_str("Alpha notes")
+            .expect("write project note");
+
+        let output = temp.child("timeline.json");
+        let metrics_out = temp.child("metrics.json");
+        let mut cli = cli(vault.path(), output.path());
+        cli.metrics_out = Some(metrics_out.path().to_path_buf());
+
+        run(cli).expect("run importer");
+
+        let metrics: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(metrics_out.path()).expect("read metrics"))
+                .expect("parse metrics");
+
+        assert_eq!(metrics["files_processed"], 2);
+        assert_eq!(metrics["blocks_created"], 2);
+        assert_eq!(metrics["duplicates"], 0);
+        assert!(metrics["tags_created"].as_u64().unwrap() >= 4);
+        let phases: Vec<&str> = metrics["phases"]
+            .as_array()
+            .expect("phases")
+            .iter()
+            .filter_map(|phase| phase["phase"].as_str())
+            .collect();
+        assert_eq!(
+            phases,
+            vec!["journal", "projects", "attachments", "dedup", "write"]
+        );
+        assert!(metrics["warnings"].as_array().expect("warnings").is_empty());
+    }
+
+    #[test]
+    fn imports_a_generated_messy_vault() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        let spec = xtask::fixture_vault::VaultSpec {
+            files: 300,
+            depth: 5,
+            seed: 42,
+        };
+        let stats = xtask::fixture_vault::generate(vault.path(), &spec).expect("generate vault");
+
+        let output = temp.child("timeline.json");
+        let report = run(cli(vault.path(), output.path())).expect("run importer");
+        assert_eq!(report.unparseable_dates.len(), stats.undated_journal_notes);
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(
+            snapshot.blocks.len(),
+            stats.dated_journal_notes + stats.project_notes
+        );
+        assert!(
+            snapshot
+                .blocks
+                .iter()
+                .any(|block| block.text.contains("東京"))
+        );
+    }
+
+    #[test]
+    fn obsidian_daily_notes_settings_pick_journal_folder_and_dates() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        vault
+            .child("Daily/03.04.2025.md")
+            .write_str("Custom format")
+            .expect("write journal");
+
+        let output = temp.child("timeline.json");
+        let without_settings = run(cli(vault.path(), output.path()))
+            .expect_err("no journal/ without daily-notes settings");
+        assert!(format!("{without_settings:#}").contains("journal directory"));
+
+        vault
+            .child(".obsidian/daily-notes.json")
+            .write_str(r#"{"format": "DD.MM.YYYY", "folder": "Daily/"}"#)
+            .expect("write daily-notes settings");
+        run(cli(vault.path(), output.path())).expect("run importer");
+
+        let snapshot: Snapshot =
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot");
+        assert_eq!(snapshot.blocks.len(), 1);
+        assert_eq!(
+            snapshot.blocks[0].date,
+            NaiveDate::from_ymd_opt(2025, 4, 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn app_recognizes_cli_imported_notes_from_a_configured_journal() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        vault
+            .child(".obsidian/daily-notes.json")
+            .write_str(r#"{"folder": "Daily"}"#)
+            .expect("write daily-notes settings");
+        vault
+            .child("Daily/2025-04-03.md")
+            .write_str("Standup\n")
+            .expect("write journal");
+
+        let imported = Importer::builder()
+            .source(vault.path())
+            .run()
+            .expect("run importer");
+        assert_eq!(
+            imported.blocks[0]
+                .source
+                .as_ref()
+                .map(|source| &source.path),
+            Some(&PathBuf::from("Daily/2025-04-03.md"))
+        );
+        let mut bytes = Vec::new();
+        imported
+            .write(&mut bytes, SnapshotEncoding::Jsonl)
+            .expect("write snapshot");
+        let mut timeline = timeline::Timeline::from_snapshot_bytes(&bytes).expect("load snapshot");
+
+        let notes = vault::read_vault(vault.path(), &mut TagRegistry::new()).expect("read vault");
+        let plan = timeline.plan_merge(notes.blocks, timeline::MergeStrategy::default());
+        let summary = timeline.apply_merge(plan);
+        assert_eq!(summary.added, 0);
+        assert_eq!(summary.reimported, 1);
+    }
+
+    #[test]
+    fn wikilinks_become_tags_or_links() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let vault = temp.child("vault");
+        vault
+            .child("projects")
+            .create_dir_all()
+            .expect("create projects");
+        vault
+            .child("journal/2025-03-01.md")
+            .write_str("Worked on [[Project X]] with [[Ana|Ana B]]")
+            .expect("write journal");
+
+        let import_with = |mode: WikilinkMode| -> Snapshot {
+            let output = temp.child(format!("{mode:?}.json"));
+            let mut cli = cli(vault.path(), output.path());
+            cli.wikilinks = mode;
+            run(cli).expect("run importer");
+            serde_json::from_str(&fs::read_to_string(output.path()).expect("read snapshot"))
+                .expect("parse snapshot")
+        };
+
+        let tagged = import_with(WikilinkMode::Tags);
+        let tag_names = build_tag_name_map(&tagged.tag_registry);
+        let names = tags_as_names(&tagged.blocks[0], &tag_names);
+        assert!(names.contains(&"link:project-x".to_string()));
+        assert!(names.contains(&"link:ana".to_string()));
+        assert!(tagged.blocks[0].links.is_empty());
+
+        let linked = import_with(WikilinkMode::Links);
+        assert_eq!(linked.blocks[0].links, vec!["Project X", "Ana"]);
+        let tag_names = build_tag_name_map(&linked.tag_registry);
+        assert!(!tag_names.values().any(|name| name.starts_with("link")));
     }
 
     fn build_tag_name_map(tags: &[Tag]) -> HashMap<u32, String> {
@@ -3,5 +3,6 @@ use importer::{Cli, run};
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    run(cli)
+    run(cli)?;
+    Ok(())
 }
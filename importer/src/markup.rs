@@ -0,0 +1,94 @@
+//! Parsers for the Obsidian-flavoured markdown constructs found in note text.
+
+/// Returns the targets of `[[wikilinks]]` in `text`, in order of appearance
+/// and without duplicates. Aliases (`[[target|alias]]`) and heading anchors
+/// (`[[target#heading]]`) are stripped; embeds (`![[image.png]]`) are skipped.
+pub fn wikilinks(text: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("[[") {
+        let is_embed = rest[..start].ends_with('!');
+        let after_open = &rest[start + 2..];
+        let Some(end) = after_open.find("]]") else {
+            break;
+        };
+
+        let inner = &after_open[..end];
+        if !is_embed {
+            let target = inner.split(['|', '#']).next().unwrap_or_default().trim();
+            if !target.is_empty() && !targets.iter().any(|existing| existing == target) {
+                targets.push(target.to_string());
+            }
+        }
+
+        rest = &after_open[end + 2..];
+    }
+
+    targets
+}
+
+/// Replaces each `![[embed]]` in `text` with the string returned by
+/// `replace`, which receives the embed target with any `|size` suffix
+/// stripped. Embeds for which `replace` returns `None` are left untouched.
+pub fn replace_embeds<E>(
+    text: &str,
+    mut replace: impl FnMut(&str) -> Result<Option<String>, E>,
+) -> Result<String, E> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("![[") {
+        let after_open = &rest[start + 3..];
+        let Some(end) = after_open.find("]]") else {
+            break;
+        };
+
+        output.push_str(&rest[..start]);
+        let embed = &rest[start..start + 3 + end + 2];
+        let target = after_open[..end]
+            .split('|')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        match replace(target)? {
+            Some(replacement) if !target.is_empty() => output.push_str(&replacement),
+            _ => output.push_str(embed),
+        }
+
+        rest = &after_open[end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikilinks_extracts_targets() {
+        let text = "Met about [[Project X]] and [[People/Ana|Ana]], see [[Project X#Plan]].";
+        assert_eq!(wikilinks(text), vec!["Project X", "People/Ana"]);
+    }
+
+    #[test]
+    fn wikilinks_skips_embeds_and_unclosed_links() {
+        let text = "![[photo.png]] then [[Real]] and [[dangling";
+        assert_eq!(wikilinks(text), vec!["Real"]);
+    }
+
+    #[test]
+    fn replace_embeds_rewrites_resolved_targets() {
+        let text = "Look ![[photo.png|300]] and ![[missing.pdf]], not [[link]] ![[open";
+        let rewritten = replace_embeds(text, |target| {
+            Ok::<_, ()>((target == "photo.png").then(|| format!("![{target}](assets/{target})")))
+        })
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            "Look ![photo.png](assets/photo.png) and ![[missing.pdf]], not [[link]] ![[open"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Notion's "Markdown & CSV" export: every page is `Title <id>.md`, with its
+//! subpages and attachments in a `Title <id>/` folder beside it. A database
+//! is `Name <id>.csv` (or `Name <id>_all.csv`), and its rows are pages in the
+//! `Name <id>/` folder.
+
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+
+use crate::table::{self, Record};
+
+/// Length of the hex page id Notion appends to exported names.
+const ID_LEN: usize = 32;
+
+/// Date formats Notion offers for date properties. Times and the end of a
+/// range follow the date and are ignored.
+const DATE_FORMATS: [&str; 5] = ["%B %d, %Y", "%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d/%m/%Y"];
+
+/// One row of a database CSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseRow {
+    /// The first column, which is the row page's title.
+    pub title: String,
+    pub date: Option<NaiveDate>,
+    /// Values of a `Tags` property.
+    pub tags: Vec<String>,
+    /// Every other non-empty property, in column order.
+    pub properties: Vec<(String, String)>,
+    /// Byte range of the row in the database's text.
+    pub span: Range<usize>,
+}
+
+impl DatabaseRow {
+    /// The row as a block of its own, for rows that have no page.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("# {}\n", self.title);
+        if !self.properties.is_empty() {
+            text.push('\n');
+            for (name, value) in &self.properties {
+                text.push_str(&format!("{name}: {value}\n"));
+            }
+        }
+        text
+    }
+}
+
+/// `name` without the trailing ` <32 hex digits>` page id, if it has one.
+pub fn strip_id(name: &str) -> &str {
+    let name = name.strip_suffix("_all").unwrap_or(name);
+    match name.len().checked_sub(ID_LEN + 1) {
+        Some(split)
+            if name.is_char_boundary(split)
+                && name[split..].starts_with(' ')
+                && name[split + 1..].chars().all(|ch| ch.is_ascii_hexdigit()) =>
+        {
+            &name[..split]
+        }
+        _ => name,
+    }
+}
+
+/// `relative` with the page id stripped from every folder and file stem, so
+/// the page hierarchy reads as titles.
+pub fn clean_path(relative: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        let path = Path::new(name);
+        if components.peek().is_none() {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut file = strip_id(&stem).to_string();
+            if let Some(extension) = path.extension() {
+                file.push('.');
+                file.push_str(&extension.to_string_lossy());
+            }
+            cleaned.push(file);
+        } else {
+            cleaned.push(strip_id(&name.to_string_lossy()));
+        }
+    }
+    cleaned
+}
+
+/// Reads a Notion date property, e.g. `March 4, 2024 10:15 AM` or
+/// `2024/03/04 → 2024/03/06`.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    let start = value.split('→').next().unwrap_or_default().trim();
+    DATE_FORMATS.iter().find_map(|format| {
+        NaiveDate::parse_and_remainder(start, format)
+            .ok()
+            .filter(|(_, rest)| rest.is_empty() || rest.starts_with(' '))
+            .map(|(date, _)| date)
+    })
+}
+
+/// The first date among the `Name: value` property lines Notion writes
+/// under a row page's title.
+pub fn property_date(text: &str) -> Option<NaiveDate> {
+    text.lines()
+        .skip_while(|line| line.trim().is_empty() || line.starts_with("# "))
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(": "))
+        .find_map(|(_, value)| parse_date(value))
+}
+
+/// Rows of the database exported to `path`, whose contents are `text`. The
+/// row date comes from the first column holding a date, preferring columns
+/// named like a date.
+pub fn read_database(path: &Path, text: &str) -> Result<Vec<DatabaseRow>> {
+    let mut records = table::parse_records(text, ',')
+        .with_context(|| format!("failed to parse database '{}'", path.display()))?
+        .into_iter();
+    let Some(Record { fields: header, .. }) = records.next() else {
+        return Ok(Vec::new());
+    };
+
+    let is_date_like = |name: &str| {
+        let name = name.to_lowercase();
+        name.contains("date") || name.contains("created")
+    };
+    let mut date_columns: Vec<usize> = (1..header.len()).collect();
+    date_columns.sort_by_key(|&index| !is_date_like(&header[index]));
+
+    Ok(records
+        .filter_map(|Record { span, fields, .. }| {
+            let title = fields.first()?.trim().to_string();
+            let date = date_columns
+                .iter()
+                .find_map(|&index| parse_date(fields.get(index)?));
+            let mut tags = Vec::new();
+            let mut properties = Vec::new();
+            for (name, value) in header.iter().zip(&fields).skip(1) {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                if name.trim().eq_ignore_ascii_case("tags") {
+                    tags.extend(value.split(',').map(|tag| tag.trim().to_string()));
+                } else {
+                    properties.push((name.trim().to_string(), value.to_string()));
+                }
+            }
+            Some(DatabaseRow {
+                title,
+                date,
+                tags,
+                properties,
+                span,
+            })
+        })
+        .collect())
+}
+
+/// Rewrites `![alt](Page%20abc/image.png)` images whose file exists in the
+/// export as `![[Page abc/image.png]]` embeds, with the path made relative
+/// to `export_dir`, so attachment copying picks them up.
+pub fn embed_images(text: &str, export_dir: &Path, page_dir: &Path) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("![") {
+        let after_alt = rest[start + 2..].find("](").map(|end| start + 2 + end + 2);
+        let close = after_alt.and_then(|open| rest[open..].find(')').map(|end| (open, open + end)));
+        let Some((open, close)) = close else {
+            break;
+        };
+
+        let target = page_dir.join(percent_decode(&rest[open..close]));
+        let is_local =
+            !rest[open..close].contains("://") && !rest[open..close].starts_with("data:");
+        output.push_str(&rest[..start]);
+        if is_local && export_dir.join(&target).is_file() {
+            output.push_str(&format!(
+                "![[{}]]",
+                target.to_string_lossy().replace('\\', "/")
+            ));
+        } else {
+            output.push_str(&rest[start..=close]);
+        }
+        rest = &rest[close + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = value
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use std::fs;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn names_and_dates_follow_notion_conventions() {
+        assert_eq!(strip_id(&format!("Trip {ID}")), "Trip");
+        assert_eq!(strip_id(&format!("Tasks {ID}_all")), "Tasks");
+        assert_eq!(strip_id("Plain name"), "Plain name");
+        assert_eq!(
+            clean_path(Path::new(&format!("Home {ID}/Trip {ID}.md"))),
+            PathBuf::from("Home/Trip.md")
+        );
+
+        let march_4 = NaiveDate::from_ymd_opt(2024, 3, 4);
+        assert_eq!(parse_date("March 4, 2024 10:15 AM"), march_4);
+        assert_eq!(parse_date("2024/03/04 → 2024/03/06"), march_4);
+        assert_eq!(parse_date("Not a date"), None);
+        assert_eq!(
+            property_date("# Standup\n\nStatus: Done\nDate: March 4, 2024\n\nNotes"),
+            march_4
+        );
+    }
+
+    #[test]
+    fn databases_and_images_are_read_from_the_export() {
+        let temp = assert_fs::TempDir::new().expect("temp dir");
+        let csv = temp.child("Tasks.csv");
+        csv.write_str(
+            "\u{feff}Name,Status,Tags,Due date\nShip,Done,\"work, launch\",\"March 4, 2024\"\nIdle,,,\n",
+        )
+        .expect("write database");
+        let text = fs::read_to_string(csv.path()).expect("read database");
+        let rows =
+            read_database(csv.path(), text.trim_start_matches('\u{feff}')).expect("read database");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, NaiveDate::from_ymd_opt(2024, 3, 4));
+        assert_eq!(rows[0].tags, vec!["work", "launch"]);
+        assert_eq!(
+            rows[0].to_text(),
+            "# Ship\n\nStatus: Done\nDue date: March 4, 2024\n"
+        );
+        assert_eq!(rows[1].to_text(), "# Idle\n");
+
+        temp.child(format!("Trip {ID}/map.png"))
+            .write_str("png")
+            .expect("write image");
+        let text = format!(
+            "![map](Trip%20{ID}/map.png) ![gone](missing.png) ![web](https://x.test/a.png)"
+        );
+        assert_eq!(
+            embed_images(&text, temp.path(), Path::new("")),
+            format!("![[Trip {ID}/map.png]] ![gone](missing.png) ![web](https://x.test/a.png)")
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Progress reporting for long imports, either as a terminal progress bar
+//! or as newline-delimited JSON events for other programs to consume.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+const BAR_WIDTH: usize = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProgressMode {
+    #[default]
+    Off,
+    Bar,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ProgressEvent<'a> {
+    Phase {
+        phase: &'static str,
+        total: usize,
+    },
+    File {
+        phase: &'static str,
+        path: &'a str,
+        files_scanned: usize,
+        total: usize,
+        blocks_produced: usize,
+    },
+    Error {
+        message: String,
+    },
+    Finished {
+        files_scanned: usize,
+        blocks_produced: usize,
+    },
+}
+
+pub struct Progress {
+    mode: ProgressMode,
+    out: Box<dyn Write>,
+    phase: &'static str,
+    phase_done: usize,
+    phase_total: usize,
+    files_scanned: usize,
+    blocks_produced: usize,
+}
+
+impl Progress {
+    pub fn new(mode: ProgressMode, out: Box<dyn Write>) -> Self {
+        Self {
+            mode,
+            out,
+            phase: "",
+            phase_done: 0,
+            phase_total: 0,
+            files_scanned: 0,
+            blocks_produced: 0,
+        }
+    }
+
+    /// JSON events go to stdout so they can be piped; the bar draws on
+    /// stderr to stay out of the way of any redirected output.
+    pub fn stdio(mode: ProgressMode) -> Self {
+        let out: Box<dyn Write> = match mode {
+            ProgressMode::Json => Box::new(io::stdout()),
+            ProgressMode::Off | ProgressMode::Bar => Box::new(io::stderr()),
+        };
+        Self::new(mode, out)
+    }
+
+    pub fn phase(&mut self, phase: &'static str, total: usize) {
+        self.finish_bar();
+        self.phase = phase;
+        self.phase_done = 0;
+        self.phase_total = total;
+        self.emit(&ProgressEvent::Phase { phase, total });
+        self.draw_bar();
+    }
+
+    /// Records that `path` was read and produced `blocks` timeline blocks.
+    pub fn file(&mut self, path: &Path, blocks: usize) {
+        self.phase_done += 1;
+        self.files_scanned += 1;
+        self.blocks_produced += blocks;
+
+        let path = path.to_string_lossy();
+        self.emit(&ProgressEvent::File {
+            phase: self.phase,
+            path: &path,
+            files_scanned: self.files_scanned,
+            total: self.phase_total,
+            blocks_produced: self.blocks_produced,
+        });
+        self.draw_bar();
+    }
+
+    pub fn error(&mut self, err: &anyhow::Error) {
+        self.finish_bar();
+        self.emit(&ProgressEvent::Error {
+            message: format!("{err:#}"),
+        });
+    }
+
+    /// Reports totals after the snapshot is written. `blocks` is the final
+    /// block count, after deduplication.
+    pub fn finished(&mut self, blocks: usize) {
+        self.finish_bar();
+        self.blocks_produced = blocks;
+        self.emit(&ProgressEvent::Finished {
+            files_scanned: self.files_scanned,
+            blocks_produced: blocks,
+        });
+    }
+
+    // Progress output is best effort: a closed pipe shouldn't fail the import.
+    fn emit(&mut self, event: &ProgressEvent<'_>) {
+        if self.mode != ProgressMode::Json {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(self.out, "{line}");
+            let _ = self.out.flush();
+        }
+    }
+
+    fn draw_bar(&mut self) {
+        if self.mode != ProgressMode::Bar {
+            return;
+        }
+        // More files than the phase announced fill the bar rather than
+        // overflowing it.
+        let filled = self
+            .phase_done
+            .saturating_mul(BAR_WIDTH)
+            .checked_div(self.phase_total)
+            .unwrap_or(BAR_WIDTH)
+            .min(BAR_WIDTH);
+        let _ = write!(
+            self.out,
+            "\r{:<8} [{}{}] {}/{}",
+            self.phase,
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH.saturating_sub(filled)),
+            self.phase_done,
+            self.phase_total
+        );
+        let _ = self.out.flush();
+    }
+
+    fn finish_bar(&mut self) {
+        if self.mode == ProgressMode::Bar && !self.phase.is_empty() {
+            let _ = writeln!(self.out);
+            self.phase = "";
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn bar_draws_counts_per_phase() {
+        let buffer = SharedBuffer::default();
+        let mut progress = Progress::new(ProgressMode::Bar, Box::new(buffer.clone()));
+        progress.phase("journal", 2);
+        progress.file(Path::new("a.md"), 1);
+        progress.file(Path::new("b.md"), 1);
+        progress.finished(2);
+
+        let output = buffer.contents();
+        assert!(output.ends_with(&format!("\rjournal  [{}] 2/2\n", "#".repeat(BAR_WIDTH))));
+    }
+
+    #[test]
+    fn bar_stays_full_past_the_announced_total() {
+        let buffer = SharedBuffer::default();
+        let mut progress = Progress::new(ProgressMode::Bar, Box::new(buffer.clone()));
+        progress.phase("journal", 1);
+        progress.file(Path::new("a.md"), 1);
+        progress.file(Path::new("b.md"), 1);
+
+        let output = buffer.contents();
+        assert!(output.ends_with(&format!("\rjournal  [{}] 2/1", "#".repeat(BAR_WIDTH))));
+    }
+
+    #[test]
+    fn json_mode_streams_one_event_per_line() {
+        let buffer = SharedBuffer::default();
+        let mut progress = Progress::new(ProgressMode::Json, Box::new(buffer.clone()));
+        progress.phase("projects", 1);
+        progress.file(Path::new("projects/a.md"), 1);
+        progress.error(&anyhow::anyhow!("boom"));
+        progress.finished(1);
+
+        let events: Vec<serde_json::Value> = buffer
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid json line"))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                serde_json::json!({"event": "phase", "phase": "projects", "total": 1}),
+                serde_json::json!({
+                    "event": "file",
+                    "phase": "projects",
+                    "path": "projects/a.md",
+                    "files_scanned": 1,
+                    "total": 1,
+                    "blocks_produced": 1
+                }),
+                serde_json::json!({"event": "error", "message": "boom"}),
+                serde_json::json!({"event": "finished", "files_scanned": 1, "blocks_produced": 1}),
+            ]
+        );
+    }
+
+    #[test]
+    fn off_mode_writes_nothing() {
+        let buffer = SharedBuffer::default();
+        let mut progress = Progress::new(ProgressMode::Off, Box::new(buffer.clone()));
+        progress.phase("journal", 1);
+        progress.file(Path::new("a.md"), 1);
+        progress.finished(1);
+        assert!(buffer.contents().is_empty());
+    }
+}
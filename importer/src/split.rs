@@ -0,0 +1,166 @@
+//! `--split-by date-heading`: monolithic diary files where each day is a
+//! section under a heading like `## 2024-03-10`.
+
+use std::ops::Range;
+
+use chrono::NaiveDate;
+use sightline_lib::vault::parse_journal_date;
+
+/// One day's section of a diary file, without its heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaySection {
+    pub date: NaiveDate,
+    pub text: String,
+    /// Byte range of the section in the file's text, from its heading (or
+    /// the start of the kept text, for the first day) to the next day.
+    pub span: Range<usize>,
+}
+
+/// Splits `text` at headings whose title is a date, or starts with one
+/// (`## 2024-03-10 Sunday`). Only headings at the level of the first date
+/// heading split the file, so `###` subheadings stay inside their day.
+/// Frontmatter is dropped; other text above the first date heading, apart
+/// from headings, is kept with the first day. Returns `None` when the file
+/// has no date headings, so it can be imported whole.
+pub fn split_by_date_heading(text: &str, date_formats: &[String]) -> Option<Vec<DaySection>> {
+    let mut level = None;
+    let mut preamble = Vec::new();
+    let mut days: Vec<(NaiveDate, usize, Vec<&str>)> = Vec::new();
+    let mut in_fence = false;
+
+    let body = skip_frontmatter(text);
+    let mut offset = text.len() - body.len();
+    let body_start = offset;
+    for line in body.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches('\n').trim_end_matches('\r');
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if let Some((depth, title)) = (!in_fence).then(|| heading(line)).flatten() {
+            if level.is_none_or(|level| level == depth) {
+                if let Some(date) = heading_date(title, date_formats) {
+                    level = Some(depth);
+                    days.push((date, line_start, Vec::new()));
+                    continue;
+                }
+            }
+            if days.is_empty() {
+                continue;
+            }
+        }
+        match days.last_mut() {
+            Some((_, _, lines)) => lines.push(line),
+            None => preamble.push(line),
+        }
+    }
+
+    let trimmed = |lines: &[&str]| lines.join("\n").trim_matches('\n').trim_end().to_string();
+    let preamble = trimmed(&preamble);
+    let ends: Vec<usize> = days
+        .iter()
+        .skip(1)
+        .map(|(_, start, _)| *start)
+        .chain([text.len()])
+        .collect();
+    let sections: Vec<DaySection> = days
+        .into_iter()
+        .zip(ends)
+        .enumerate()
+        .filter_map(|(index, ((date, mut start, lines), end))| {
+            let mut day = trimmed(&lines);
+            if index == 0 && !preamble.is_empty() {
+                day = format!("{preamble}\n\n{day}").trim_end().to_string();
+                start = body_start;
+            }
+            (!day.is_empty()).then(|| DaySection {
+                date,
+                text: format!("{day}\n"),
+                span: start..end,
+            })
+        })
+        .collect();
+    level.is_some().then_some(sections)
+}
+
+/// The level and title of an ATX heading line.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let depth = line.bytes().take_while(|&byte| byte == b'#').count();
+    let title = line[depth..].strip_prefix([' ', '\t'])?;
+    (1..=6)
+        .contains(&depth)
+        .then(|| (depth, title.trim().trim_end_matches('#').trim_end()))
+}
+
+fn heading_date(title: &str, date_formats: &[String]) -> Option<NaiveDate> {
+    parse_journal_date(title, date_formats).or_else(|| {
+        let first_word = title.split_whitespace().next()?;
+        parse_journal_date(first_word.trim_end_matches([',', ':']), date_formats)
+    })
+}
+
+fn skip_frontmatter(text: &str) -> &str {
+    let text = text.trim_start_matches('\u{feff}');
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return text;
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return &rest[offset..];
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    #[test]
+    fn days_split_at_date_headings_of_one_level() {
+        let text = "---\ntitle: Diary\n---\n# 2024\n\nKept with the first day.\n\n\
+                    ## 2024-03-10\n\nMorning run.\n\n### Evening\n\nRead.\n\n\
+                    ## March 11, 2024\n\n```\n## 2024-03-12\n```\n\
+                    ## 2024-03-13 Wednesday\n\n## 2024-03-14\nLast.\n";
+        let sections = split_by_date_heading(text, &[]).expect("date headings");
+        let at = |heading: &str| text.find(heading).unwrap();
+        assert_eq!(
+            sections,
+            vec![
+                DaySection {
+                    date: date(10),
+                    text: "Kept with the first day.\n\nMorning run.\n\n### Evening\n\nRead.\n"
+                        .to_string(),
+                    span: at("# 2024\n")..at("## March"),
+                },
+                DaySection {
+                    date: date(11),
+                    text: "```\n## 2024-03-12\n```\n".to_string(),
+                    span: at("## March")..at("## 2024-03-13"),
+                },
+                DaySection {
+                    date: date(14),
+                    text: "Last.\n".to_string(),
+                    span: at("## 2024-03-14")..text.len(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            split_by_date_heading("# Plans\n\nNo days here.\n", &[]),
+            None
+        );
+        let custom = ["%d.%m.%Y".to_string()];
+        assert_eq!(
+            split_by_date_heading("# 10.03.2024\nHi\n", &custom).map(|days| days[0].date),
+            Some(date(10))
+        );
+    }
+}
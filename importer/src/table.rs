@@ -0,0 +1,221 @@
+//! CSV/TSV sources: every row becomes one block, with `--map` naming the
+//! columns that hold its date, text, and tags.
+
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+/// Which column feeds each block field, e.g. `date=Day,text=col3,tags=Labels`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMap {
+    date: ColumnRef,
+    text: ColumnRef,
+    tags: Option<ColumnRef>,
+}
+
+/// A header name, or `colN` for the Nth column (1-based) when no header has
+/// that name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ColumnRef(String);
+
+impl FromStr for ColumnMap {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (mut date, mut text, mut tags) = (None, None, None);
+        for pair in value.split(',') {
+            let (field, column) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected FIELD=COLUMN, got '{pair}'"))?;
+            let column = Some(ColumnRef(column.trim().to_string()));
+            match field.trim() {
+                "date" => date = column,
+                "text" => text = column,
+                "tags" => tags = column,
+                other => return Err(format!("unknown field '{other}'; use date, text, or tags")),
+            }
+        }
+
+        Ok(Self {
+            date: date.ok_or("missing date=COLUMN")?,
+            text: text.ok_or("missing text=COLUMN")?,
+            tags,
+        })
+    }
+}
+
+impl ColumnRef {
+    fn resolve(&self, header: &[String]) -> Result<usize> {
+        if let Some(index) = header
+            .iter()
+            .position(|name| name.trim().eq_ignore_ascii_case(&self.0))
+        {
+            return Ok(index);
+        }
+
+        self.0
+            .strip_prefix("col")
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+            .filter(|&index| index < header.len())
+            .ok_or_else(|| anyhow!("no column named '{}'", self.0))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TableRow {
+    /// 1-based line where the row starts, for error messages.
+    pub line: usize,
+    /// Byte range of the row in the file's text.
+    pub span: Range<usize>,
+    pub date: String,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+/// Rows of `text`, read from `path`, treating the first record as the
+/// header row.
+pub fn read_rows(
+    path: &Path,
+    text: &str,
+    delimiter: char,
+    map: &ColumnMap,
+    tag_delimiter: &str,
+) -> Result<Vec<TableRow>> {
+    let mut records = parse_records(text, delimiter)
+        .with_context(|| format!("failed to parse table '{}'", path.display()))?
+        .into_iter();
+
+    let Some(Record { fields: header, .. }) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let date = map.date.resolve(&header)?;
+    let text = map.text.resolve(&header)?;
+    let tags = map
+        .tags
+        .as_ref()
+        .map(|column| column.resolve(&header))
+        .transpose()?;
+
+    let field = |fields: &[String], index: usize| fields.get(index).cloned().unwrap_or_default();
+    Ok(records
+        .map(|Record { line, span, fields }| TableRow {
+            line,
+            span,
+            date: field(&fields, date),
+            text: field(&fields, text),
+            tags: tags
+                .map(|index| field(&fields, index))
+                .unwrap_or_default()
+                .split(tag_delimiter)
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+        .collect())
+}
+
+/// One record of a table, with where it sits in the text.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Record {
+    /// 1-based line the record starts on.
+    pub line: usize,
+    /// Byte range of the record, without its line ending.
+    pub span: Range<usize>,
+    pub fields: Vec<String>,
+}
+
+/// Splits RFC 4180 text into records with the `csv` crate. Quoted fields
+/// may contain delimiters, newlines, and `""` escapes. Blank lines are
+/// skipped.
+pub fn parse_records(text: &str, delimiter: char) -> Result<Vec<Record>> {
+    let delimiter = u8::try_from(delimiter)
+        .ok()
+        .filter(u8::is_ascii)
+        .ok_or_else(|| anyhow!("delimiter '{delimiter}' is not an ASCII character"))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+
+    let mut records = Vec::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        // A record's position is where the reader stopped after the one
+        // before, so it may still point at that record's line ending.
+        let position = record.position().expect("records read have a position");
+        let after = &text[position.byte() as usize..];
+        let start = text.len() - after.trim_start_matches(['\r', '\n']).len();
+        let line =
+            position.line() as usize + text[position.byte() as usize..start].matches('\n').count();
+        let end = start
+            + text[start..reader.position().byte() as usize]
+                .trim_end_matches(['\r', '\n'])
+                .len();
+        // The reader takes an unclosed quote to run to the end of the text.
+        if end == text.len() && text[start..end].matches('"').count() % 2 == 1 {
+            bail!("unterminated quoted field starting on line {line}");
+        }
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        records.push(Record {
+            line,
+            span: start..end,
+            fields: record.iter().map(str::to_string).collect(),
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_records_handles_quotes_and_blank_lines() {
+        let text = "a,b\r\n\"x, y\",\"say \"\"hi\"\"\"\n\n\"multi\nline\",z";
+        let records = parse_records(text, ',').unwrap();
+        let summary: Vec<(usize, &str, Vec<String>)> = records
+            .into_iter()
+            .map(|record| (record.line, &text[record.span], record.fields))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "a,b", vec!["a".to_string(), "b".to_string()]),
+                (
+                    2,
+                    "\"x, y\",\"say \"\"hi\"\"\"",
+                    vec!["x, y".to_string(), "say \"hi\"".to_string()]
+                ),
+                (
+                    4,
+                    "\"multi\nline\",z",
+                    vec!["multi\nline".to_string(), "z".to_string()]
+                ),
+            ]
+        );
+        assert!(parse_records("\"open", ',').is_err());
+    }
+
+    #[test]
+    fn column_map_parses_and_resolves_names_or_positions() {
+        let map: ColumnMap = "date=Day, text=col3,tags=labels".parse().unwrap();
+        let header: Vec<String> = ["Day", "Mood", "Note", "Labels"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(map.date.resolve(&header).unwrap(), 0);
+        assert_eq!(map.text.resolve(&header).unwrap(), 2);
+        assert_eq!(map.tags.unwrap().resolve(&header).unwrap(), 3);
+
+        assert!("text=col1".parse::<ColumnMap>().is_err());
+        assert!("date=a,text=b,mood=c".parse::<ColumnMap>().is_err());
+        assert!(ColumnRef("col9".into()).resolve(&header).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! `--tag-rules` files: regex → tag mappings applied to every imported note.
+//!
+//! ```toml
+//! [[rule]]
+//! pattern = "(?i)standup"
+//! tag = "#type:standup"
+//!
+//! [[rule]]
+//! pattern = "(?m)^- \\[ \\]"
+//! tag = "todo"
+//! match = "content"
+//! ```
+//!
+//! Patterns match the note's vault-relative path (e.g.
+//! `journal/2025-03-01 standup.md`) unless `match = "content"`.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::Deserialize;
+use sightline_lib::timeline::TagRegistry;
+use sightline_lib::vault::normalize_tag_segment;
+
+#[derive(Debug, Default)]
+pub struct TagRules {
+    rules: Vec<TagRule>,
+}
+
+#[derive(Debug)]
+struct TagRule {
+    pattern: Regex,
+    /// Normalized tag segments, e.g. `["type", "standup"]`.
+    tag: Vec<String>,
+    target: MatchTarget,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MatchTarget {
+    #[default]
+    Path,
+    Content,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    pattern: String,
+    tag: String,
+    #[serde(default, rename = "match")]
+    target: MatchTarget,
+}
+
+impl TagRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read tag rules '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid tag rules '{}'", path.display()))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let file: RulesFile = toml::from_str(text)?;
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|spec| {
+                let pattern = Regex::new(&spec.pattern)
+                    .with_context(|| format!("invalid pattern '{}'", spec.pattern))?;
+                let tag = spec
+                    .tag
+                    .trim()
+                    .trim_start_matches('#')
+                    .split(':')
+                    .map(|segment| {
+                        normalize_tag_segment(segment)
+                            .ok_or_else(|| anyhow!("tag '{}' has an empty segment", spec.tag))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(TagRule {
+                    pattern,
+                    tag,
+                    target: spec.target,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Interns and returns the tags of every rule matching the note at the
+    /// vault-relative `path` with contents `text`.
+    pub fn apply(&self, path: &Path, text: &str, registry: &mut TagRegistry) -> Vec<u32> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .filter(|rule| match rule.target {
+                MatchTarget::Path => rule.pattern.is_match(&path),
+                MatchTarget::Content => rule.pattern.is_match(text),
+            })
+            .filter_map(|rule| registry.intern_path(rule.tag.iter().map(String::as_str)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_match_paths_or_content() {
+        let rules = TagRules::parse(
+            r##"
+            [[rule]]
+            pattern = "(?i)standup"
+            tag = "#type:Standup"
+
+            [[rule]]
+            pattern = "(?m)^- \\[ \\]"
+            tag = "todo"
+            match = "content"
+            "##,
+        )
+        .expect("parse rules");
+        let mut registry = TagRegistry::new();
+
+        let tags = rules.apply(
+            Path::new("journal/Standup 2025-03-01.md"),
+            "Notes\n- [ ] follow up",
+            &mut registry,
+        );
+        let names: Vec<String> = tags
+            .iter()
+            .filter_map(|id| registry.full_name(*id))
+            .collect();
+        assert_eq!(names, vec!["type:standup", "todo"]);
+
+        assert!(
+            rules
+                .apply(Path::new("journal/2025-03-01.md"), "Notes", &mut registry)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(TagRules::parse("[[rule]]\npattern = \"(\"\ntag = \"x\"").is_err());
+        assert!(TagRules::parse("[[rule]]\npattern = \"x\"\ntag = \"a::b\"").is_err());
+        assert!(
+            TagRules::parse("[[rule]]\npattern = \"x\"\ntag = \"a\"\nmatch = \"name\"").is_err()
+        );
+    }
+}
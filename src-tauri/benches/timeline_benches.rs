@@ -5,8 +5,12 @@ use proptest::prelude::*;
 use proptest::strategy::{Strategy, ValueTree};
 use proptest::test_runner::TestRunner;
 use sightline_lib::api::TextOperation;
-use sightline_lib::timeline::{TaggedBlock, Timeline};
+use sightline_lib::timeline::{
+    ArchivedContent, DateRange, MergeStrategy, TagRegistry, TaggedBlock, Timeline,
+};
+use sightline_lib::vault;
 use sum_tree::{SumTree, TREE_BASE};
+use xtask::fixture_vault::{self, VaultSpec};
 
 fn arb_op(doc_len: usize) -> BoxedStrategy<TextOperation> {
     let insert_strategy =
@@ -100,26 +104,12 @@ fn node_split_benchmark(c: &mut Criterion) {
             || {
                 let mut tree = SumTree::<TaggedBlock>::new(());
                 for _ in 0..NODE_PRE_SPLIT_CAPACITY {
-                    tree.push(
-                        TaggedBlock {
-                            date,
-                            text: "a".to_string(),
-                            tags: Vec::new(),
-                        },
-                        (),
-                    );
+                    tree.push(TaggedBlock::new(date, "a", Vec::new()), ());
                 }
                 tree
             },
             |mut tree| {
-                tree.push(
-                    TaggedBlock {
-                        date,
-                        text: "a".to_string(),
-                        tags: Vec::new(),
-                    },
-                    (),
-                );
+                tree.push(TaggedBlock::new(date, "a", Vec::new()), ());
             },
         );
     });
@@ -208,12 +198,128 @@ fn delete_only_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn log_for_date_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("timeline_log_for_date");
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid benchmark date");
+
+    for block_count in [1_000usize, 10_000, 100_000] {
+        let blocks = (0..block_count).map(|index| {
+            TaggedBlock::new(
+                start + chrono::Days::new(index as u64 / 4),
+                format!("entry {index}\n"),
+                Vec::new(),
+            )
+        });
+        let mut timeline = Timeline::default();
+        timeline.merge_blocks(blocks.collect(), MergeStrategy::KeepBoth);
+        let date = start + chrono::Days::new(block_count as u64 / 8);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(block_count),
+            &timeline,
+            |b, tl| {
+                b.iter(|| tl.log_for_date(date).expect("day has entries"));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Tag search through the timeline's bloom-filtered cursor, against a
+/// plain scan of every block, for a tag on one block in a thousand.
+fn tag_search_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("timeline_tag_search");
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid benchmark date");
+
+    for block_count in [10_000usize, 100_000] {
+        let mut timeline = Timeline::default();
+        let rare = timeline.intern_tag("#rare").expect("intern tag").id;
+        let topics: Vec<u32> = (0..50)
+            .map(|topic| {
+                timeline
+                    .intern_tag(&format!("#topic{topic}"))
+                    .expect("intern tag")
+                    .id
+            })
+            .collect();
+        let blocks: Vec<TaggedBlock> = (0..block_count)
+            .map(|index| {
+                TaggedBlock::new(
+                    start + chrono::Days::new(index as u64 / 4),
+                    format!("entry {index}\n"),
+                    if index % 1_000 == 500 {
+                        vec![rare]
+                    } else {
+                        vec![topics[index % topics.len()]]
+                    },
+                )
+            })
+            .collect();
+        let tree = SumTree::from_iter(blocks.clone(), ());
+        timeline.merge_blocks(blocks, MergeStrategy::KeepBoth);
+
+        let linear = |tree: &SumTree<TaggedBlock>| -> Vec<u32> {
+            tree.iter()
+                .enumerate()
+                .filter(|(_, block)| block.tags.contains(&rare))
+                .map(|(index, _)| index as u32)
+                .collect()
+        };
+        let range = DateRange::default();
+        assert_eq!(
+            timeline.search_prefix("#rare", &range, ArchivedContent::Excluded),
+            linear(&tree)
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("bloom", block_count),
+            &timeline,
+            |b, tl| {
+                b.iter(|| tl.search_prefix("#rare", &range, ArchivedContent::Excluded));
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("linear", block_count), &tree, |b, tree| {
+            b.iter(|| linear(tree));
+        });
+    }
+
+    group.finish();
+}
+
+fn read_vault_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vault_read");
+    group.sample_size(10);
+
+    for files in [100usize, 1_000] {
+        let dir = tempfile::tempdir().expect("create vault dir");
+        let spec = VaultSpec {
+            files,
+            depth: 4,
+            ..VaultSpec::default()
+        };
+        fixture_vault::generate(dir.path(), &spec).expect("generate vault");
+
+        group.bench_with_input(BenchmarkId::from_parameter(files), dir.path(), |b, path| {
+            b.iter(|| {
+                let mut registry = TagRegistry::default();
+                vault::read_vault(path, &mut registry).expect("read vault")
+            });
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     apply_ops_benchmark,
     clone_benchmark,
     node_split_benchmark,
     append_only_benchmark,
-    delete_only_benchmark
+    delete_only_benchmark,
+    log_for_date_benchmark,
+    tag_search_benchmark,
+    read_vault_benchmark
 );
 criterion_main!(benches);
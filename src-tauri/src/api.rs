@@ -1,5 +1,8 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
+use crate::timeline::{BlockMetadata, TagDescriptor};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TextOperation {
@@ -13,17 +16,105 @@ pub enum TextOperation {
     },
 }
 
+/// The coordinate space of an edit's offsets. The document is indexed by
+/// character; other units are converted before the edit is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetUnit {
+    /// Unicode scalar values, as Rust's `chars()` counts them.
+    #[default]
+    Chars,
+    /// UTF-8 bytes.
+    Bytes,
+    /// UTF-16 code units, as JavaScript string indexes count them.
+    Utf16,
+}
+
+impl OffsetUnit {
+    pub fn is_chars(&self) -> bool {
+        *self == Self::Chars
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EditPayload {
     pub base_version: u64,
     pub ops: Vec<TextOperation>,
+    /// Insert text exactly as typed, skipping snippet expansion.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub literal: bool,
+    /// Token of an edit lock the sender holds; edits inside its range are
+    /// let through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_token: Option<String>,
+    /// The unit of the ops' offsets. Offsets in the response are always
+    /// characters.
+    #[serde(default, skip_serializing_if = "OffsetUnit::is_chars")]
+    pub offsets: OffsetUnit,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum EditResponse {
-    Ok { new_version: u64 },
-    Conflict { server_version: u64 },
+    Ok {
+        new_version: u64,
+        /// Set when the backend rewrote the edit (e.g. expanded a snippet);
+        /// the client should refetch the document.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        rewritten: bool,
+    },
+    Conflict {
+        server_version: u64,
+    },
+    /// The edit touched an entry inside a frozen date range; the offsets
+    /// identify the protected entry.
+    Frozen {
+        server_version: u64,
+        start_position: usize,
+        end_position: usize,
+    },
+    /// Another writer holds an edit lock on the range the edit touched;
+    /// retry once it's released or after `retry_after_ms`.
+    Locked {
+        server_version: u64,
+        owner: String,
+        start_position: usize,
+        end_position: usize,
+        retry_after_ms: u64,
+    },
+}
+
+/// Where dropped files are inserted: at a document offset, or as a block on
+/// a given day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropTarget {
+    Offset(usize),
+    Date(NaiveDate),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredAttachment {
+    /// Path relative to the assets directory, as used in `](<assets/...>)`.
+    pub id: String,
+    pub file_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestResponse {
+    pub new_version: u64,
+    /// The block holding the attachment links.
+    pub block: BlockMetadata,
+    pub attachments: Vec<StoredAttachment>,
+}
+
+/// Outcome of interning one tag of a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InternTagResult {
+    Ok { tag: TagDescriptor },
+    Error { tag: String, message: String },
 }
 
 #[cfg(test)]
@@ -32,8 +123,22 @@ mod tests {
 
     #[test]
     fn edit_response_serializes_to_expected_json() {
-        let response = EditResponse::Ok { new_version: 42 };
+        let response = EditResponse::Ok {
+            new_version: 42,
+            rewritten: false,
+        };
         let json = serde_json::to_string(&response).expect("serialize response");
         assert_eq!(json, r#"{"status":"ok","new_version":42}"#);
     }
+
+    #[test]
+    fn drop_target_accepts_offset_or_date() {
+        let offset: DropTarget = serde_json::from_str(r#"{"offset":12}"#).unwrap();
+        assert_eq!(offset, DropTarget::Offset(12));
+        let date: DropTarget = serde_json::from_str(r#"{"date":"2025-03-02"}"#).unwrap();
+        assert_eq!(
+            date,
+            DropTarget::Date(NaiveDate::from_ymd_opt(2025, 3, 2).unwrap())
+        );
+    }
 }
@@ -0,0 +1,269 @@
+//! Attachment files kept in `assets/` beside the timeline, and the markdown
+//! links in blocks that point at them.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+/// Attachments directory, relative to the directory holding `timeline.json`.
+pub const ASSETS_DIR: &str = "assets";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AttachmentReport {
+    /// Files linked from at least one block.
+    pub referenced: usize,
+    /// Files in the assets directory that no block links to.
+    pub orphaned: Vec<String>,
+    /// Links whose file is not in the assets directory.
+    pub missing: Vec<String>,
+    /// Whether the orphaned files were deleted.
+    pub pruned: bool,
+}
+
+/// Asset paths linked from `text`, relative to the assets directory. Both
+/// `](assets/a.png)` and the importer's `](<assets/a b.png>)` forms count.
+pub fn references(text: &str) -> impl Iterator<Item = &str> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| {
+        Regex::new(&format!(
+            r"\]\((?:<{ASSETS_DIR}/([^>\n]+)>|{ASSETS_DIR}/([^)\s]+))\)"
+        ))
+        .expect("valid attachment link pattern")
+    });
+
+    link.captures_iter(text)
+        .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|path| path.as_str())
+}
+
+/// Markdown linking attachment `id` as `name`; images are embedded.
+pub fn link(name: &str, id: &str) -> String {
+    const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "svg"];
+    let is_image = id.rsplit_once('.').is_some_and(|(_, ext)| {
+        IMAGE_EXTENSIONS
+            .iter()
+            .any(|image| ext.eq_ignore_ascii_case(image))
+    });
+    let name = name.replace(['[', ']'], "");
+    let bang = if is_image { "!" } else { "" };
+    format!("{bang}[{name}](<{ASSETS_DIR}/{id}>)")
+}
+
+/// Whether `id` is a `/`-separated path that stays inside the directory it
+/// is resolved against.
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.contains('\\')
+        && id
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != ".." && !part.contains(':'))
+}
+
+/// Every file under `assets_dir` as a `/`-separated relative path, sorted.
+/// A missing directory has no files.
+pub fn list(assets_dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    if assets_dir.is_dir() {
+        collect(assets_dir, "", &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn collect(dir: &Path, prefix: &str, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = format!("{prefix}{}", name.to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect(&entry.path(), &format!("{name}/"), files)?;
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Copies `source` into `assets_dir` under its own file name, adding a
+/// numeric suffix (`photo-1.png`) when that name is taken, and returns the
+/// new attachment id.
+pub fn store_file(assets_dir: &Path, source: &Path) -> io::Result<String> {
+    let name = source
+        .file_name()
+        .filter(|_| source.is_file())
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| is_valid_id(name))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not a file", source.display()),
+            )
+        })?;
+
+    let mut reader = File::open(source)?;
+    let (id, mut file) = create_unique(assets_dir, &name)?;
+    io::copy(&mut reader, &mut file)?;
+    Ok(id)
+}
+
+/// Writes `bytes` into `assets_dir` as `name`, suffixed like
+/// [`store_file`] when taken, and returns the new attachment id.
+pub fn store_bytes(assets_dir: &Path, name: &str, bytes: &[u8]) -> io::Result<String> {
+    if !is_valid_id(name) || name.contains('/') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{name}' is not a valid attachment name"),
+        ));
+    }
+    let (id, mut file) = create_unique(assets_dir, name)?;
+    file.write_all(bytes)?;
+    Ok(id)
+}
+
+/// File extension for a clipboard image MIME type, or `None` for types that
+/// are not images the editor can display.
+pub fn image_extension(mime: &str) -> Option<&'static str> {
+    match mime.trim().to_ascii_lowercase().as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/svg+xml" => Some("svg"),
+        _ => None,
+    }
+}
+
+/// Creates a new file in `assets_dir` named `name`, or the first free
+/// `stem-N.ext` variant of it.
+fn create_unique(assets_dir: &Path, name: &str) -> io::Result<(String, File)> {
+    fs::create_dir_all(assets_dir)?;
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+
+    let candidates =
+        std::iter::once(name.to_string()).chain((1..).map(|n| format!("{stem}-{n}{extension}")));
+    for candidate in candidates {
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(assets_dir.join(&candidate))
+        {
+            Ok(file) => return Ok((candidate, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("unbounded suffixes")
+}
+
+/// Cross-references the files in `assets_dir` against the links in `text`.
+/// Unless `dry_run` is set, orphaned files are deleted.
+pub fn collect_garbage(
+    text: &str,
+    assets_dir: &Path,
+    dry_run: bool,
+) -> io::Result<AttachmentReport> {
+    let linked: BTreeSet<&str> = references(text).collect();
+    let files = list(assets_dir)?;
+    let stored: BTreeSet<&str> = files.iter().map(String::as_str).collect();
+
+    let report = AttachmentReport {
+        referenced: linked.intersection(&stored).count(),
+        orphaned: stored
+            .difference(&linked)
+            .map(|path| path.to_string())
+            .collect(),
+        missing: linked
+            .difference(&stored)
+            .map(|path| path.to_string())
+            .collect(),
+        pruned: !dry_run,
+    };
+
+    if !dry_run {
+        for orphan in &report.orphaned {
+            fs::remove_file(assets_dir.join(orphan))?;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn references_accept_bare_and_angle_bracket_links() {
+        let text = "![a](assets/a.png) ![b](<assets/photos/b c.jpg>) [doc](notes/x.md)";
+        assert_eq!(
+            references(text).collect::<Vec<_>>(),
+            vec!["a.png", "photos/b c.jpg"]
+        );
+        assert_eq!(link("b c.jpg", "b c.jpg"), "![b c.jpg](<assets/b c.jpg>)");
+        assert_eq!(
+            link("notes.pdf", "notes-1.pdf"),
+            "[notes.pdf](<assets/notes-1.pdf>)"
+        );
+        assert_eq!(
+            references(&link("x", "x.PNG")).collect::<Vec<_>>(),
+            vec!["x.PNG"]
+        );
+    }
+
+    #[test]
+    fn store_suffixes_taken_names() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join(ASSETS_DIR);
+        let source = dir.path().join("photo.png");
+        fs::write(&source, b"png").unwrap();
+
+        assert_eq!(store_file(&assets, &source).unwrap(), "photo.png");
+        assert_eq!(store_file(&assets, &source).unwrap(), "photo-1.png");
+        assert_eq!(fs::read(assets.join("photo-1.png")).unwrap(), b"png");
+        assert!(store_file(&assets, dir.path()).is_err());
+
+        assert_eq!(
+            store_bytes(&assets, "photo.png", b"new").unwrap(),
+            "photo-2.png"
+        );
+        assert!(store_bytes(&assets, "../escape.png", b"x").is_err());
+        assert_eq!(image_extension("image/JPEG"), Some("jpg"));
+        assert_eq!(image_extension("text/plain"), None);
+    }
+
+    #[test]
+    fn collect_garbage_reports_then_prunes_orphans() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join(ASSETS_DIR);
+        fs::create_dir_all(assets.join("2025")).unwrap();
+        fs::write(assets.join("kept.png"), b"k").unwrap();
+        fs::write(assets.join("2025/orphan.png"), b"o").unwrap();
+        let text = "![kept](assets/kept.png)\n![gone](<assets/gone.png>)\n";
+
+        let report = collect_garbage(text, &assets, true).unwrap();
+        assert_eq!(
+            report,
+            AttachmentReport {
+                referenced: 1,
+                orphaned: vec!["2025/orphan.png".to_string()],
+                missing: vec!["gone.png".to_string()],
+                pruned: false,
+            }
+        );
+        assert!(assets.join("2025/orphan.png").exists());
+
+        let report = collect_garbage(text, &assets, false).unwrap();
+        assert!(report.pruned);
+        assert!(!assets.join("2025/orphan.png").exists());
+        assert!(assets.join("kept.png").exists());
+    }
+}
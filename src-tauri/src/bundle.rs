@@ -0,0 +1,366 @@
+//! `.sightline` bundles: a single zip file carrying a timeline, its
+//! attachments, and settings, for moving a workspace to another machine
+//! without going through sync.
+//!
+//! A bundle holds:
+//! - `manifest.json`: format name, schema version, and export details
+//! - `timeline.json`: the snapshot, minus the settings below
+//! - `settings.json`: snippets, snippet expansion, metric patterns, and
+//!   the daily note template
+//! - `assets/...`: files from the attachments directory beside the timeline
+//!
+//! Entries are stored uncompressed, since attachments are usually images
+//! that are already compressed. Archives and entries past 4 GiB are written
+//! as zip64.
+
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::attachments::{self, ASSETS_DIR};
+use crate::timeline::{Timeline, TimelinePersistenceError};
+
+pub const BUNDLE_EXTENSION: &str = "sightline";
+pub const SCHEMA_VERSION: u32 = 1;
+
+const FORMAT: &str = "sightline-bundle";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const TIMELINE_ENTRY: &str = "timeline.json";
+const SETTINGS_ENTRY: &str = "settings.json";
+
+/// Snapshot keys that are preferences rather than content.
+const SETTINGS_KEYS: [&str; 8] = [
+    "snippets",
+    "snippet_expansion",
+    "fetch_link_titles",
+    "offline_mode",
+    "metric_patterns",
+    "now_page",
+    "daily_note",
+    "export_schedule",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub timeline_version: u64,
+    pub attachments: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    Timeline(#[from] TimelinePersistenceError),
+    #[error(transparent)]
+    Zip(#[from] ZipError),
+    #[error("invalid bundle: {0}")]
+    Invalid(String),
+    #[error("bundle schema version {0} is newer than this version of Sightline supports")]
+    UnsupportedSchema(u32),
+}
+
+/// A bundle read back from disk. Nothing is written until the caller
+/// installs the timeline and calls [`Bundle::write_attachments`], which
+/// copies attachments out of the archive one at a time.
+pub struct Bundle {
+    pub manifest: BundleManifest,
+    pub timeline: Timeline,
+    path: PathBuf,
+    /// Path under the assets directory, and the entry holding it.
+    attachments: Vec<(PathBuf, String)>,
+}
+
+impl Bundle {
+    pub fn write_attachments(&self, assets_dir: &Path) -> Result<usize, BundleError> {
+        let mut archive = open_archive(fs::File::open(&self.path)?)?;
+        for (relative, name) in &self.attachments {
+            let path = assets_dir.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut archive.by_name(name)?, &mut fs::File::create(path)?)?;
+        }
+        Ok(self.attachments.len())
+    }
+}
+
+pub fn export(
+    timeline: &Timeline,
+    assets_dir: &Path,
+    path: &Path,
+) -> Result<BundleManifest, BundleError> {
+    let mut snapshot: Map<String, Value> = serde_json::from_slice(&timeline.to_snapshot_json()?)?;
+    let settings: Map<String, Value> = SETTINGS_KEYS
+        .iter()
+        .filter_map(|key| snapshot.remove(*key).map(|value| (key.to_string(), value)))
+        .collect();
+
+    let attachments: Vec<(String, Contents)> = attachments::list(assets_dir)?
+        .into_iter()
+        .map(|name| {
+            let path = assets_dir.join(&name);
+            (format!("{ASSETS_DIR}/{name}"), Contents::File(path))
+        })
+        .collect();
+
+    let manifest = BundleManifest {
+        format: FORMAT.to_string(),
+        schema_version: SCHEMA_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: timeline.clock().now(),
+        timeline_version: timeline.version(),
+        attachments: attachments.len(),
+    };
+
+    let mut entries = vec![
+        (
+            MANIFEST_ENTRY.to_string(),
+            Contents::Bytes(serde_json::to_vec_pretty(&manifest)?),
+        ),
+        (
+            TIMELINE_ENTRY.to_string(),
+            Contents::Bytes(serde_json::to_vec_pretty(&snapshot)?),
+        ),
+        (
+            SETTINGS_ENTRY.to_string(),
+            Contents::Bytes(serde_json::to_vec_pretty(&settings)?),
+        ),
+    ];
+    entries.extend(attachments);
+
+    // Written beside `path` and renamed over it, so a failed export leaves
+    // an earlier bundle in place.
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let written = fs::File::create(&temp)
+        .map_err(BundleError::from)
+        .and_then(|file| write_archive(io::BufWriter::new(file), entries, manifest.exported_at))
+        .and_then(|mut writer| Ok(writer.flush()?));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    fs::rename(&temp, path)?;
+    Ok(manifest)
+}
+
+pub fn read(path: &Path) -> Result<Bundle, BundleError> {
+    let mut archive = open_archive(fs::File::open(path)?)?;
+    let mut take = |name: &str| read_entry(&mut archive, name);
+
+    let manifest: BundleManifest = serde_json::from_slice(&take(MANIFEST_ENTRY)?)?;
+    if manifest.format != FORMAT {
+        return Err(BundleError::Invalid(format!(
+            "unknown format '{}'",
+            manifest.format
+        )));
+    }
+    if manifest.schema_version > SCHEMA_VERSION {
+        return Err(BundleError::UnsupportedSchema(manifest.schema_version));
+    }
+
+    let mut snapshot: Map<String, Value> = serde_json::from_slice(&take(TIMELINE_ENTRY)?)?;
+    let settings: Map<String, Value> = serde_json::from_slice(&take(SETTINGS_ENTRY)?)?;
+    snapshot.extend(
+        settings
+            .into_iter()
+            .filter(|(key, _)| SETTINGS_KEYS.contains(&key.as_str())),
+    );
+    let timeline = Timeline::from_snapshot_json(&serde_json::to_vec(&snapshot)?)?;
+
+    // Entries this version doesn't know about are ignored so newer minor
+    // additions don't block an import.
+    let prefix = format!("{ASSETS_DIR}/");
+    let attachments = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .filter_map(|name| {
+            name.strip_prefix(&prefix)
+                .map(|relative| (PathBuf::from(relative), name.to_string()))
+        })
+        .collect();
+
+    Ok(Bundle {
+        manifest,
+        timeline,
+        path: path.to_path_buf(),
+        attachments,
+    })
+}
+
+/// What an archive entry holds: bytes in memory, or a file streamed in as
+/// the entry is written.
+enum Contents {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+fn write_archive<W: Write + Seek>(
+    out: W,
+    entries: Vec<(String, Contents)>,
+    at: DateTime<Utc>,
+) -> Result<W, BundleError> {
+    // Zip times can't go before 1980.
+    let modified = zip::DateTime::try_from(at.naive_utc()).unwrap_or_default();
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(modified);
+    let large = |len: u64| len >= u64::from(u32::MAX);
+
+    let mut archive = ZipWriter::new(out);
+    for (name, contents) in entries {
+        match contents {
+            Contents::Bytes(data) => {
+                archive.start_file(name, options.large_file(large(data.len() as u64)))?;
+                archive.write_all(&data)?;
+            }
+            Contents::File(path) => {
+                let mut file = fs::File::open(path)?;
+                let len = file.metadata()?.len();
+                archive.start_file(name, options.large_file(large(len)))?;
+                io::copy(&mut file, &mut archive)?;
+            }
+        }
+    }
+    Ok(archive.finish()?)
+}
+
+/// Opens a bundle's archive, refusing entry names that could land outside
+/// the assets directory.
+fn open_archive<R: Read + Seek>(reader: R) -> Result<ZipArchive<R>, BundleError> {
+    let archive = ZipArchive::new(reader)?;
+    if let Some(name) = archive
+        .file_names()
+        .find(|name| !name.ends_with('/') && !attachments::is_valid_id(name))
+    {
+        return Err(BundleError::Invalid(format!("unsafe entry name '{name}'")));
+    }
+    Ok(archive)
+}
+
+/// The contents of entry `name`, checked against its CRC.
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, BundleError> {
+    let mut entry = archive.by_name(name).map_err(|err| match err {
+        ZipError::FileNotFound => BundleError::Invalid(format!("missing {name}")),
+        err => err.into(),
+    })?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::TextOperation;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let entries = entries
+            .iter()
+            .map(|(name, data)| (name.to_string(), Contents::Bytes(data.to_vec())))
+            .collect();
+        write_archive(Cursor::new(Vec::new()), entries, Utc::now())
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn archive_round_trips_entries() {
+        let bytes = archive(&[
+            ("a.txt", b"alpha"),
+            ("assets/nested/b.bin", &[0, 1, 2, 255]),
+        ]);
+        let mut archive = open_archive(Cursor::new(bytes)).unwrap();
+        assert_eq!(read_entry(&mut archive, "a.txt").unwrap(), b"alpha");
+        assert_eq!(
+            read_entry(&mut archive, "assets/nested/b.bin").unwrap(),
+            [0, 1, 2, 255]
+        );
+        assert!(matches!(
+            read_entry(&mut archive, "c.txt"),
+            Err(BundleError::Invalid(reason)) if reason == "missing c.txt"
+        ));
+    }
+
+    #[test]
+    fn archive_rejects_unsafe_names_and_bad_checksums() {
+        assert!(matches!(
+            open_archive(Cursor::new(archive(&[("../escape.txt", b"x")]))),
+            Err(BundleError::Invalid(reason)) if reason.contains("unsafe")
+        ));
+
+        let mut bytes = archive(&[("a.txt", b"alpha")]);
+        let data_start = open_archive(Cursor::new(bytes.clone()))
+            .unwrap()
+            .by_name("a.txt")
+            .unwrap()
+            .data_start();
+        bytes[data_start as usize] = b'A';
+        let mut archive = open_archive(Cursor::new(bytes)).unwrap();
+        assert!(read_entry(&mut archive, "a.txt").is_err());
+    }
+
+    #[test]
+    fn export_and_read_preserve_timeline_settings_and_attachments() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join(ASSETS_DIR);
+        fs::create_dir_all(assets.join("2025")).unwrap();
+        fs::write(assets.join("2025/photo.png"), b"png").unwrap();
+
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "Day one\n".to_string(),
+                }],
+            )
+            .unwrap();
+        timeline.save_snippet(":mtg", "Meeting").unwrap();
+        timeline.set_snippet_expansion(true);
+
+        let path = dir.path().join("export.sightline");
+        let manifest = export(&timeline, &assets, &path).unwrap();
+        assert_eq!(manifest.attachments, 1);
+
+        let mut archive = open_archive(fs::File::open(&path).unwrap()).unwrap();
+        let stored: Value =
+            serde_json::from_slice(&read_entry(&mut archive, TIMELINE_ENTRY).unwrap()).unwrap();
+        assert!(stored.get("snippets").is_none());
+
+        let bundle = read(&path).unwrap();
+        assert_eq!(bundle.manifest, manifest);
+        assert_eq!(bundle.timeline.content(), timeline.content());
+        assert_eq!(bundle.timeline.snippets(), timeline.snippets());
+        assert!(bundle.timeline.snippet_expansion());
+
+        let target = dir.path().join("restored");
+        assert_eq!(bundle.write_attachments(&target).unwrap(), 1);
+        assert_eq!(fs::read(target.join("2025/photo.png")).unwrap(), b"png");
+    }
+}
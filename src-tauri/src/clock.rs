@@ -0,0 +1,113 @@
+//! Where the app reads the current time. Everything date-dependent (the
+//! date given to typed text, job schedules, export stamps) asks a
+//! [`SharedClock`] rather than the system, so tests can pin or move time
+//! with a [`ManualClock`].
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().expect("clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("clock lock poisoned") += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().expect("clock lock poisoned")
+    }
+}
+
+/// A clock handed to the timeline and app state. Defaults to the system
+/// clock.
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.0.now()
+    }
+
+    /// The current UTC date, which typed text is dated with.
+    pub fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+
+    /// The current time in the user's time zone, for names and stamps
+    /// they read.
+    pub fn local_now(&self) -> DateTime<Local> {
+        self.now().with_timezone(&Local)
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl From<Arc<ManualClock>> for SharedClock {
+    fn from(clock: Arc<ManualClock>) -> Self {
+        Self(clock)
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedClock").field(&self.now()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clocks_move_only_when_told() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let manual = Arc::new(ManualClock::new(start));
+        let clock = SharedClock::from(Arc::clone(&manual));
+        assert_eq!(clock.now(), start);
+
+        manual.advance(Duration::days(1));
+        assert_eq!(clock.today(), (start + Duration::days(1)).date_naive());
+        manual.set(start);
+        assert_eq!(clock.clone().now(), start);
+    }
+}
@@ -0,0 +1,271 @@
+//! Per-day index of the timeline: which dates have blocks and their word
+//! count. A day's blocks and the span of text they cover are looked up in
+//! the tree, by date summaries, when read, so the index holds nothing an
+//! edit on another day moves. Text edits update only the days they touch;
+//! anything that rebuilds the tree wholesale rebuilds the index with it.
+//! Archived blocks are left out, so a day whose blocks are all archived
+//! has no entry.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sum_tree::{Bias, Dimensions, SumTree};
+
+use crate::timeline::{Chars, DateRange, EntryCount, LatestDate, TaggedBlock, TimelineSummary};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayEntry {
+    /// Indexes of the day's blocks, in document order.
+    pub blocks: Vec<u32>,
+    /// Character offset of the start of the day's first block.
+    pub start: usize,
+    /// Character offset of the end of the day's last block. Blocks from
+    /// other days can sit in between.
+    pub end: usize,
+    pub words: usize,
+}
+
+/// What the index keeps for a day. Positions are left to the tree, so an
+/// edit only changes the days it touches.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct DayStats {
+    words: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayIndex {
+    days: BTreeMap<NaiveDate, DayStats>,
+    /// Totals of the tree the index was built for, to notice when it no
+    /// longer matches.
+    blocks: usize,
+    chars: usize,
+    /// Of `chars`, those in archived blocks, since archiving a block
+    /// changes neither the totals nor the fingerprint.
+    #[serde(default)]
+    archived_chars: usize,
+    /// [`TimelineSummary::fingerprint`] of that tree. Indexes saved before
+    /// it existed read as 0 and are rebuilt.
+    #[serde(default)]
+    fingerprint: u64,
+}
+
+/// How an edit moved what follows it: blocks from `block` on and text from
+/// `offset` on moved by `blocks` and `chars`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shift {
+    pub block: usize,
+    pub offset: usize,
+    pub blocks: isize,
+    pub chars: isize,
+}
+
+impl Shift {
+    /// The shift from replacing the text of `before` at `offset`, where
+    /// `after` is the result.
+    pub fn between(
+        before: &SumTree<TaggedBlock>,
+        after: &SumTree<TaggedBlock>,
+        offset: usize,
+    ) -> Self {
+        let mut cursor = before.cursor::<Dimensions<Chars, EntryCount>>(());
+        cursor.seek(&Chars(offset), Bias::Right);
+        let (before, after) = (before.summary(), after.summary());
+        Self {
+            block: cursor.start().1 .0,
+            offset,
+            blocks: signed(after.entry_count) - signed(before.entry_count),
+            chars: signed(after.total_chars) - signed(before.total_chars),
+        }
+    }
+}
+
+impl DayIndex {
+    pub fn build(tree: &SumTree<TaggedBlock>) -> Self {
+        let mut days: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
+        for block in tree.iter().filter(|block| !block.archived) {
+            days.entry(block.date).or_default().words += crate::timeline::count_words(&block.text);
+        }
+        let summary = tree.summary();
+        Self {
+            days,
+            blocks: summary.entry_count,
+            chars: summary.total_chars,
+            archived_chars: summary.archived_chars,
+            fingerprint: summary.fingerprint,
+        }
+    }
+
+    /// Whether the index was kept up to date with a tree summarised by
+    /// `summary`.
+    pub fn is_current(&self, summary: &TimelineSummary) -> bool {
+        self.blocks == summary.entry_count
+            && self.chars == summary.total_chars
+            && self.archived_chars == summary.archived_chars
+            && self.fingerprint == summary.fingerprint
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.days.contains_key(&date)
+    }
+
+    /// Days within `range`, in date order, with where their blocks sit in
+    /// `tree`.
+    pub fn range<'a>(
+        &'a self,
+        tree: &'a SumTree<TaggedBlock>,
+        range: &DateRange,
+    ) -> impl Iterator<Item = (NaiveDate, DayEntry)> + 'a {
+        let to = match (range.from, range.to) {
+            // An inverted range holds nothing, but `BTreeMap::range` panics on one.
+            (Some(from), Some(to)) if from > to => Bound::Excluded(from),
+            (_, to) => to.map_or(Bound::Unbounded, Bound::Included),
+        };
+        let from = range.from.map_or(Bound::Unbounded, Bound::Included);
+        self.days
+            .range((from, to))
+            .filter_map(|(date, _)| Some((*date, day_entry(tree, *date)?)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.days.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    /// Brings the index up to date with `tree` after edits that changed the
+    /// blocks of the `touched` days. Other days are left alone.
+    pub fn update(&mut self, tree: &SumTree<TaggedBlock>, touched: &BTreeSet<NaiveDate>) {
+        for date in touched {
+            match day_entry(tree, *date) {
+                Some(day) => self.days.insert(*date, DayStats { words: day.words }),
+                None => self.days.remove(date),
+            };
+        }
+        let summary = tree.summary();
+        self.blocks = summary.entry_count;
+        self.chars = summary.total_chars;
+        self.archived_chars = summary.archived_chars;
+        self.fingerprint = summary.fingerprint;
+    }
+}
+
+/// Looks up `date`'s unarchived blocks, skipping subtrees whose dates
+/// don't span it.
+pub fn day_entry(tree: &SumTree<TaggedBlock>, date: NaiveDate) -> Option<DayEntry> {
+    let mut day: Option<DayEntry> = None;
+    let mut cursor = tree.cursor::<Dimensions<LatestDate, EntryCount, Chars>>(());
+    cursor.seek(&LatestDate(Some(date)), Bias::Left);
+    while let Some(block) = cursor.item() {
+        if block.date == date && !block.archived {
+            let start = cursor.start().2 .0;
+            let end = start + block.text.chars().count();
+            let entry = day.get_or_insert_with(|| DayEntry {
+                start,
+                ..DayEntry::default()
+            });
+            entry
+                .blocks
+                .push(u32::try_from(cursor.start().1 .0).unwrap_or(u32::MAX));
+            entry.end = end;
+            entry.words += crate::timeline::count_words(&block.text);
+        }
+        cursor.search_forward(|summary: &TimelineSummary| {
+            summary.min_date <= Some(date) && Some(date) <= summary.max_date
+        });
+    }
+    day
+}
+
+fn signed(count: usize) -> isize {
+    isize::try_from(count).unwrap_or(isize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(day: u32, text: &str) -> TaggedBlock {
+        TaggedBlock::new(
+            NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            text,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn days_record_their_blocks_span_and_words() {
+        let tree = SumTree::from_iter(
+            [
+                block(1, "one two\n"),
+                block(2, "three\n"),
+                block(1, "four\n"),
+            ],
+            (),
+        );
+        let index = DayIndex::build(&tree);
+        let first = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(
+            day_entry(&tree, first),
+            Some(DayEntry {
+                blocks: vec![0, 2],
+                start: 0,
+                end: 19,
+                words: 3,
+            })
+        );
+        assert_eq!(index.days[&first].words, 3);
+        assert!(index.is_current(tree.summary()));
+
+        let second = first.succ_opt();
+        let days: Vec<_> = index
+            .range(&tree, &DateRange::new(second, None))
+            .map(|(date, _)| date)
+            .collect();
+        assert_eq!(days, [second.unwrap()]);
+        assert_eq!(
+            index
+                .range(&tree, &DateRange::new(second, Some(first)))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn archived_blocks_are_left_out() {
+        let archived = TaggedBlock {
+            archived: true,
+            ..block(2, "three\n")
+        };
+        let tree = SumTree::from_iter([block(1, "one two\n"), archived, block(3, "four\n")], ());
+        let index = DayIndex::build(&tree);
+        let second = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        assert!(!index.contains(second));
+        assert_eq!(day_entry(&tree, second), None);
+        assert_eq!(index.len(), 2);
+
+        let unarchived = SumTree::from_iter(
+            [
+                block(1, "one two\n"),
+                block(2, "three\n"),
+                block(3, "four\n"),
+            ],
+            (),
+        );
+        assert!(!index.is_current(unarchived.summary()));
+    }
+
+    #[test]
+    fn rewritten_text_of_the_same_length_is_noticed() {
+        let tree = SumTree::from_iter([block(1, "one two\n")], ());
+        let index = DayIndex::build(&tree);
+        let rewritten = SumTree::from_iter([block(1, "two one\n")], ());
+        assert_eq!(tree.summary().total_chars, rewritten.summary().total_chars);
+        assert!(!index.is_current(rewritten.summary()));
+        let redated = SumTree::from_iter([block(2, "one two\n")], ());
+        assert!(!index.is_current(redated.summary()));
+    }
+}
@@ -0,0 +1,171 @@
+//! Quick-entry parsing of quantified-self lines such as `sleep: 7.5h` or
+//! `ran 5km` into numeric per-day properties.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Metric values recorded for one day, keyed by metric name.
+pub type DayProperties = BTreeMap<String, f64>;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetricPattern {
+    pub name: String,
+    /// Regex matched against each line; the first capture group holds the
+    /// numeric value.
+    pub pattern: String,
+    #[serde(default)]
+    pub aggregate: MetricAggregate,
+}
+
+/// How several matches for the same metric on one day combine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricAggregate {
+    #[default]
+    Last,
+    Sum,
+}
+
+#[derive(Debug, Error)]
+pub enum MetricPatternError {
+    #[error("metric name cannot be empty")]
+    EmptyName,
+    #[error("invalid pattern for metric '{name}': {source}")]
+    InvalidPattern {
+        name: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error("pattern for metric '{name}' needs a capture group for the value")]
+    MissingCapture { name: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricParser {
+    patterns: Vec<MetricPattern>,
+    compiled: Vec<Regex>,
+}
+
+impl MetricParser {
+    pub fn new(patterns: Vec<MetricPattern>) -> Result<Self, MetricPatternError> {
+        let mut compiled = Vec::with_capacity(patterns.len());
+        for pattern in &patterns {
+            if pattern.name.trim().is_empty() {
+                return Err(MetricPatternError::EmptyName);
+            }
+            let regex = Regex::new(&pattern.pattern).map_err(|source| {
+                MetricPatternError::InvalidPattern {
+                    name: pattern.name.clone(),
+                    source,
+                }
+            })?;
+            if regex.captures_len() < 2 {
+                return Err(MetricPatternError::MissingCapture {
+                    name: pattern.name.clone(),
+                });
+            }
+            compiled.push(regex);
+        }
+
+        Ok(Self { patterns, compiled })
+    }
+
+    pub fn patterns(&self) -> &[MetricPattern] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Folds every metric found in `text`, line by line, into `properties`.
+    pub fn parse_into(&self, text: &str, properties: &mut DayProperties) {
+        for line in text.lines() {
+            for (pattern, regex) in self.patterns.iter().zip(&self.compiled) {
+                let values = regex
+                    .captures_iter(line)
+                    .filter_map(|captures| captures.get(1)?.as_str().parse::<f64>().ok());
+
+                for value in values {
+                    let entry = properties.entry(pattern.name.clone()).or_insert(0.0);
+                    match pattern.aggregate {
+                        MetricAggregate::Last => *entry = value,
+                        MetricAggregate::Sum => *entry += value,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for MetricParser {
+    fn default() -> Self {
+        Self::new(default_patterns()).expect("default metric patterns are valid")
+    }
+}
+
+pub fn default_patterns() -> Vec<MetricPattern> {
+    let pattern = |name: &str, pattern: &str, aggregate| MetricPattern {
+        name: name.to_string(),
+        pattern: pattern.to_string(),
+        aggregate,
+    };
+    vec![
+        pattern(
+            "sleep_hours",
+            r"(?i)^\s*sleep:\s*(\d+(?:\.\d+)?)\s*h",
+            MetricAggregate::Last,
+        ),
+        pattern(
+            "weight_kg",
+            r"(?i)^\s*weight:\s*(\d+(?:\.\d+)?)\s*kg\b",
+            MetricAggregate::Last,
+        ),
+        pattern(
+            "distance_km",
+            r"(?i)\bran\s+(\d+(?:\.\d+)?)\s*km\b",
+            MetricAggregate::Sum,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_patterns_parse_quick_entries() {
+        let mut properties = DayProperties::new();
+        MetricParser::default().parse_into(
+            "Sleep: 7.5h\nweight: 80kg\nran 5km before work, then ran 2.5 km home\nsleepy day",
+            &mut properties,
+        );
+
+        assert_eq!(properties.get("sleep_hours"), Some(&7.5));
+        assert_eq!(properties.get("weight_kg"), Some(&80.0));
+        assert_eq!(properties.get("distance_km"), Some(&7.5));
+    }
+
+    #[test]
+    fn sum_aggregates_across_lines() {
+        let mut properties = DayProperties::new();
+        MetricParser::default().parse_into("ran 5km\nran 3km", &mut properties);
+        assert_eq!(properties.get("distance_km"), Some(&8.0));
+    }
+
+    #[test]
+    fn patterns_need_a_value_capture() {
+        let patterns = vec![MetricPattern {
+            name: "mood".to_string(),
+            pattern: r"mood: \d+".to_string(),
+            aggregate: MetricAggregate::Last,
+        }];
+        assert!(matches!(
+            MetricParser::new(patterns),
+            Err(MetricPatternError::MissingCapture { .. })
+        ));
+    }
+}
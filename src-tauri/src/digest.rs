@@ -0,0 +1,607 @@
+//! Weekly review of the timeline, rendered as an HTML email body and saved
+//! as an `.eml` draft that any mail client can open and send, or sent every
+//! Monday morning as configured in a [`DigestSchedule`]. Each send is a
+//! repeating [`crate::jobs::JobKind::WeeklyDigest`] in the job queue.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::export_schedule::ExportRun;
+use crate::important_dates::DateOccurrence;
+use crate::jobs::{Job, JobKind};
+use crate::network::{self, NetworkError};
+use crate::timeline::{count_words, ArchivedContent, DateRange, SensitiveContent, Timeline};
+
+/// Blocks tagged with this root tag (or a child) are listed as highlights.
+pub const HIGHLIGHT_TAG: &str = "highlight";
+
+/// Where the [`DigestSchedule`] is saved, beside the timeline file rather
+/// than in it, so SMTP credentials never travel in bundles or exports. The
+/// password is kept as given, since sending needs it back; on unix the file
+/// is readable by its owner only, elsewhere it takes the folder's access.
+pub const DIGEST_SCHEDULE_FILE: &str = "digest.json";
+
+/// Scheduled digests go out on Mondays at this local hour.
+pub const SEND_HOUR: u32 = 8;
+pub const WEEK_HOURS: u32 = 24 * 7;
+
+const TOP_TAG_COUNT: usize = 5;
+/// The submission port, upgraded with STARTTLS.
+const DEFAULT_SMTP_PORT: u16 = 587;
+const SMTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum DigestError {
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error("invalid email address {0:?}")]
+    Address(String),
+    #[error(transparent)]
+    Message(#[from] lettre::error::Error),
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+/// Who gets the weekly digest and how it is delivered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestSchedule {
+    pub to: String,
+    #[serde(flatten)]
+    pub delivery: DigestDelivery,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum DigestDelivery {
+    /// Sent through an SMTP server.
+    Smtp(SmtpSettings),
+    /// Saved as `digest-<monday>.eml` drafts in `dir`, to send by hand.
+    Draft { dir: PathBuf },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// Defaults to 587.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    pub from: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+/// From `get_digest_schedule_status`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DigestScheduleStatus {
+    /// The schedule with its SMTP password left out.
+    pub schedule: Option<DigestSchedule>,
+    pub last_run: Option<ExportRun>,
+    /// When the next digest, or retry, is due.
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WeeklyDigest {
+    /// Monday of the week.
+    pub start: NaiveDate,
+    /// Sunday of the week.
+    pub end: NaiveDate,
+    pub entries: usize,
+    pub words: usize,
+    pub active_days: usize,
+    /// Most-used tags by block count, ties broken by name.
+    pub top_tags: Vec<TagCount>,
+    pub highlights: Vec<Highlight>,
+    /// Birthdays, anniversaries and holidays during the week.
+    pub important_dates: Vec<DateOccurrence>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub blocks: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Highlight {
+    pub date: NaiveDate,
+    /// First non-empty line of the block.
+    pub text: String,
+}
+
+/// Reviews the Monday-to-Sunday week containing `day`. Sensitive blocks
+/// are never included, since the digest leaves the app, and neither are
+/// archived ones.
+pub fn weekly(timeline: &Timeline, day: NaiveDate) -> WeeklyDigest {
+    let start = day - Duration::days(i64::from(day.weekday().num_days_from_monday()));
+    let end = start + Duration::days(6);
+    let blocks = timeline.blocks_in_range(
+        &DateRange::new(Some(start), Some(end)),
+        SensitiveContent::Masked,
+        ArchivedContent::Excluded,
+    );
+    let registry = timeline.tag_registry();
+    let highlight_root = registry.find_id(None, HIGHLIGHT_TAG);
+
+    let mut tag_counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut days = BTreeSet::new();
+    let mut words = 0;
+    let mut highlights = Vec::new();
+    for block in &blocks {
+        days.insert(block.date);
+        words += count_words(&block.text);
+        for &tag in &block.tags {
+            if let Some(name) = registry.full_name(tag) {
+                *tag_counts.entry(name).or_default() += 1;
+            }
+        }
+
+        let highlighted = highlight_root.is_some_and(|root| {
+            block
+                .tags
+                .iter()
+                .any(|&tag| registry.has_ancestor(tag, root))
+        });
+        let first_line = block
+            .text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty());
+        if let (true, Some(line)) = (highlighted, first_line) {
+            highlights.push(Highlight {
+                date: block.date,
+                text: line.to_string(),
+            });
+        }
+    }
+
+    let mut top_tags: Vec<TagCount> = tag_counts
+        .into_iter()
+        .map(|(tag, blocks)| TagCount { tag, blocks })
+        .collect();
+    top_tags.sort_by(|a, b| b.blocks.cmp(&a.blocks).then_with(|| a.tag.cmp(&b.tag)));
+    top_tags.truncate(TOP_TAG_COUNT);
+
+    WeeklyDigest {
+        start,
+        end,
+        entries: blocks.len(),
+        words,
+        active_days: days.len(),
+        top_tags,
+        highlights,
+        important_dates: timeline.important_dates().between(start, end),
+    }
+}
+
+impl WeeklyDigest {
+    pub fn subject(&self) -> String {
+        format!("Sightline weekly digest: {} to {}", self.start, self.end)
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n<html><body>\n");
+        html.push_str(&format!("<h1>{}</h1>\n", escape(&self.subject())));
+        html.push_str(&format!(
+            "<p>{} entries, {} words, {} of 7 days written.</p>\n",
+            self.entries, self.words, self.active_days
+        ));
+
+        if !self.top_tags.is_empty() {
+            html.push_str("<h2>Top tags</h2>\n<ul>\n");
+            for tag in &self.top_tags {
+                html.push_str(&format!(
+                    "<li>#{} ({})</li>\n",
+                    escape(&tag.tag),
+                    tag.blocks
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !self.important_dates.is_empty() {
+            html.push_str("<h2>This week</h2>\n<ul>\n");
+            for occurrence in &self.important_dates {
+                html.push_str(&format!(
+                    "<li><strong>{}</strong> {}</li>\n",
+                    occurrence.date.format("%a %b %-d"),
+                    escape(&occurrence.label())
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !self.highlights.is_empty() {
+            html.push_str("<h2>Highlights</h2>\n<ul>\n");
+            for highlight in &self.highlights {
+                html.push_str(&format!(
+                    "<li><strong>{}</strong> {}</li>\n",
+                    highlight.date.format("%a %b %-d"),
+                    escape(&highlight.text)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    /// An RFC 5322 message with the HTML body. `X-Unsent` makes mail
+    /// clients open it as a draft ready to send.
+    pub fn to_eml(&self, to: Option<&str>, now: DateTime<FixedOffset>) -> String {
+        let mut message = String::new();
+        if let Some(to) = to {
+            message.push_str(&format!("To: {}\r\n", to.replace(['\r', '\n'], "")));
+        }
+        message.push_str(&format!("Subject: {}\r\n", self.subject()));
+        message.push_str(&format!("Date: {}\r\n", now.to_rfc2822()));
+        message.push_str("X-Unsent: 1\r\n");
+        message.push_str("MIME-Version: 1.0\r\n");
+        message.push_str("Content-Type: text/html; charset=utf-8\r\n");
+        message.push_str("Content-Transfer-Encoding: 8bit\r\n\r\n");
+        message.push_str(&self.to_html().replace('\n', "\r\n"));
+        message
+    }
+
+    pub fn write_eml(
+        &self,
+        path: &Path,
+        to: Option<&str>,
+        now: DateTime<FixedOffset>,
+    ) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(path, self.to_eml(to, now))
+    }
+
+    /// The digest as an HTML email from `from` to `to`.
+    pub fn to_message(
+        &self,
+        from: &str,
+        to: &str,
+        now: DateTime<FixedOffset>,
+    ) -> Result<Message, DigestError> {
+        let mailbox = |address: &str| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|_| DigestError::Address(address.to_string()))
+        };
+        Ok(Message::builder()
+            .from(mailbox(from)?)
+            .to(mailbox(to)?)
+            .subject(self.subject())
+            .date(now.into())
+            .header(ContentType::TEXT_HTML)
+            .body(self.to_html())?)
+    }
+}
+
+impl DigestSchedule {
+    /// The schedule saved at `path`, if any. An unreadable file is logged
+    /// and ignored.
+    pub fn load(path: &Path) -> Option<Self> {
+        match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|err| {
+                    tracing::warn!(%err, path = %path.display(), "ignoring unreadable digest schedule");
+                })
+                .ok(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::warn!(%err, path = %path.display(), "failed to read digest schedule");
+                None
+            }
+        }
+    }
+
+    /// Saves `schedule` at `path`, or removes the file when there is none.
+    pub fn store(schedule: Option<&Self>, path: &Path) -> Result<(), DigestError> {
+        let Some(schedule) = schedule else {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            };
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        create_private(path)?.write_all(&serde_json::to_vec_pretty(schedule)?)?;
+        Ok(())
+    }
+
+    /// A schedule replacing `previous` keeps its SMTP password when it
+    /// names none for the same server and user, since the frontend never
+    /// sees the saved one.
+    pub fn keeping_password(mut self, previous: Option<&Self>) -> Self {
+        if let (DigestDelivery::Smtp(smtp), Some(DigestDelivery::Smtp(old))) = (
+            &mut self.delivery,
+            previous.map(|previous| &previous.delivery),
+        ) {
+            if smtp.password.is_none() && smtp.host == old.host && smtp.username == old.username {
+                smtp.password = old.password.clone();
+            }
+        }
+        self
+    }
+
+    /// The schedule without its SMTP password.
+    pub fn redacted(&self) -> Self {
+        let mut schedule = self.clone();
+        if let DigestDelivery::Smtp(smtp) = &mut schedule.delivery {
+            smtp.password = None;
+        }
+        schedule
+    }
+
+    /// Sends `digest`, or saves it as a draft, as the schedule says.
+    pub fn deliver(
+        &self,
+        digest: &WeeklyDigest,
+        now: DateTime<FixedOffset>,
+    ) -> Result<(), DigestError> {
+        match &self.delivery {
+            DigestDelivery::Draft { dir } => {
+                let path = dir.join(format!("digest-{}.eml", digest.start));
+                digest.write_eml(&path, Some(&self.to), now)?;
+            }
+            DigestDelivery::Smtp(smtp) => {
+                smtp.send(&digest.to_message(&smtp.from, &self.to, now)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SmtpSettings {
+    /// Sends `message` over STARTTLS, logging in when a username is set.
+    pub fn send(&self, message: &Message) -> Result<(), DigestError> {
+        network::ensure_enabled()?;
+        let mut transport = SmtpTransport::starttls_relay(&self.host)?
+            .port(self.port.unwrap_or(DEFAULT_SMTP_PORT))
+            .timeout(Some(SMTP_TIMEOUT));
+        if let Some(username) = &self.username {
+            transport = transport.credentials(Credentials::new(
+                username.clone(),
+                self.password.clone().unwrap_or_default(),
+            ));
+        }
+        transport.build().send(message)?;
+        Ok(())
+    }
+}
+
+impl DigestScheduleStatus {
+    /// The status of `schedule`, whose sends are `job`.
+    pub fn new(schedule: Option<&DigestSchedule>, job: Option<&Job>) -> Self {
+        let job = job.filter(|job| job.kind == JobKind::WeeklyDigest);
+        Self {
+            last_run: job.and_then(ExportRun::last),
+            next_run: job
+                .filter(|_| schedule.is_some())
+                .map(|job| job.next_attempt_at),
+            schedule: schedule.map(DigestSchedule::redacted),
+        }
+    }
+}
+
+/// Creates or truncates `path` for writing, readable and writable by its
+/// owner only on unix.
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    // The mode only applies to new files; one saved before keeps its own.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+/// The first Monday at [`SEND_HOUR`] in `now`'s time zone after `now`.
+pub fn next_send_time<Tz: TimeZone>(now: &DateTime<Tz>) -> DateTime<Utc> {
+    let today = now.date_naive();
+    let mut monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+    loop {
+        let at = monday
+            .and_hms_opt(SEND_HOUR, 0, 0)
+            .and_then(|at| now.timezone().from_local_datetime(&at).earliest());
+        match at {
+            Some(at) if at > *now => return at.with_timezone(&Utc),
+            _ => monday += Duration::weeks(1),
+        }
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline() -> Timeline {
+        let snapshot = serde_json::json!({
+            "version": 1,
+            "blocks": [
+                {"date": "2025-03-02", "text": "last week\n", "tags": [1]},
+                {"date": "2025-03-03", "text": "\nShipped <v1>\nmore\n", "tags": [1, 2]},
+                {"date": "2025-03-04", "text": "Dropped <v0>\n", "tags": [1, 2], "archived": true},
+                {"date": "2025-03-05", "text": "standup\n", "tags": [1]},
+                {"date": "2025-03-06", "text": "private\n", "tags": [2, 3]},
+                {"date": "2025-03-10", "text": "next week\n", "tags": []}
+            ],
+            "tag_registry": [
+                {"id": 1, "name": "work", "parent_id": null},
+                {"id": 2, "name": HIGHLIGHT_TAG, "parent_id": null},
+                {"id": 3, "name": "sensitive", "parent_id": null}
+            ],
+            "important_dates": {
+                "dates": [
+                    {"name": "Mom's birthday", "kind": "birthday", "month": 3, "day": 4, "year": 1965}
+                ]
+            }
+        });
+        Timeline::from_snapshot_json(snapshot.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn weekly_summarizes_monday_to_sunday() {
+        let digest = weekly(&timeline(), NaiveDate::from_ymd_opt(2025, 3, 7).unwrap());
+        assert_eq!(digest.start, NaiveDate::from_ymd_opt(2025, 3, 3).unwrap());
+        assert_eq!(digest.end, NaiveDate::from_ymd_opt(2025, 3, 9).unwrap());
+        assert_eq!(
+            (digest.entries, digest.words, digest.active_days),
+            (2, 4, 2)
+        );
+        assert_eq!(
+            digest.top_tags,
+            vec![
+                TagCount {
+                    tag: "work".to_string(),
+                    blocks: 2,
+                },
+                TagCount {
+                    tag: "highlight".to_string(),
+                    blocks: 1,
+                },
+            ]
+        );
+        assert_eq!(digest.highlights.len(), 1);
+        assert_eq!(digest.highlights[0].text, "Shipped <v1>");
+        assert!(!digest.to_html().contains("Dropped"));
+    }
+
+    #[test]
+    fn digests_go_out_on_monday_mornings() {
+        let at = |rfc3339: &str| DateTime::parse_from_rfc3339(rfc3339).unwrap();
+        assert_eq!(
+            next_send_time(&at("2025-03-05T12:00:00+01:00")),
+            at("2025-03-10T07:00:00Z")
+        );
+        assert_eq!(
+            next_send_time(&at("2025-03-10T07:59:00+01:00")),
+            at("2025-03-10T07:00:00Z")
+        );
+        assert_eq!(
+            next_send_time(&at("2025-03-10T08:00:00+01:00")),
+            at("2025-03-17T07:00:00Z")
+        );
+    }
+
+    #[test]
+    fn schedules_deliver_drafts_and_keep_passwords_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let digest = weekly(&timeline(), NaiveDate::from_ymd_opt(2025, 3, 3).unwrap());
+        let now = DateTime::parse_from_rfc3339("2025-03-10T08:00:00+01:00").unwrap();
+        let draft = DigestSchedule {
+            to: "me@example.com".to_string(),
+            delivery: DigestDelivery::Draft {
+                dir: dir.path().join("drafts"),
+            },
+        };
+        draft.deliver(&digest, now).unwrap();
+        let eml = fs::read_to_string(dir.path().join("drafts/digest-2025-03-03.eml")).unwrap();
+        assert!(eml.starts_with("To: me@example.com\r\n"));
+
+        let smtp = DigestSchedule {
+            to: "me@example.com".to_string(),
+            delivery: DigestDelivery::Smtp(SmtpSettings {
+                host: "smtp.example.com".to_string(),
+                port: None,
+                from: "Sightline <sightline@example.com>".to_string(),
+                username: Some("me".to_string()),
+                password: Some("hunter2".to_string()),
+            }),
+        };
+        let path = dir.path().join(DIGEST_SCHEDULE_FILE);
+        DigestSchedule::store(Some(&smtp), &path).unwrap();
+        assert_eq!(DigestSchedule::load(&path), Some(smtp.clone()));
+        assert_eq!(smtp.redacted().keeping_password(Some(&smtp)), smtp);
+        DigestSchedule::store(None, &path).unwrap();
+        assert_eq!(DigestSchedule::load(&path), None);
+
+        let message = digest
+            .to_message("Sightline <sightline@example.com>", "me@example.com", now)
+            .unwrap();
+        let message = String::from_utf8(message.formatted()).unwrap();
+        assert!(message.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(message.contains("Date: Mon, 10 Mar 2025 07:00:00 +0000"));
+        assert!(matches!(
+            digest.to_message("not an address", "me@example.com", now),
+            Err(DigestError::Address(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_schedules_are_private_to_their_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DIGEST_SCHEDULE_FILE);
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let schedule = DigestSchedule {
+            to: "me@example.com".to_string(),
+            delivery: DigestDelivery::Smtp(SmtpSettings {
+                host: "smtp.example.com".to_string(),
+                port: None,
+                from: "sightline@example.com".to_string(),
+                username: Some("me".to_string()),
+                password: Some("hunter2".to_string()),
+            }),
+        };
+        DigestSchedule::store(Some(&schedule), &path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0, "mode {mode:o}");
+        assert_eq!(DigestSchedule::load(&path), Some(schedule));
+    }
+
+    #[test]
+    fn eml_wraps_escaped_html_body() {
+        let digest = weekly(&timeline(), NaiveDate::from_ymd_opt(2025, 3, 3).unwrap());
+        let now = DateTime::parse_from_rfc3339("2025-03-10T08:00:00+01:00").unwrap();
+        let eml = digest.to_eml(Some("me@example.com"), now);
+
+        assert!(eml.starts_with(
+            "To: me@example.com\r\nSubject: Sightline weekly digest: 2025-03-03 to 2025-03-09\r\n"
+        ));
+        assert!(eml.contains("Date: Mon, 10 Mar 2025 08:00:00 +0100\r\n"));
+        assert!(eml.contains("\r\n\r\n<!DOCTYPE html>"));
+        assert!(eml.contains("<li><strong>Mon Mar 3</strong> Shipped &lt;v1&gt;</li>"));
+        assert!(eml.contains(
+            "<h2>This week</h2>\r\n<ul>\r\n<li><strong>Tue Mar 4</strong> Mom's birthday (60)</li>"
+        ));
+        assert!(!eml.contains("private"));
+    }
+}
@@ -0,0 +1,305 @@
+//! Short-lived locks on document ranges. A writer that changes the
+//! document over several calls, like the chat assistant applying tool
+//! calls, holds a lock so typing can't land in the middle of its work.
+//! Edits that touch a range locked by someone else are refused with a
+//! retriable error until the lock is released or expires. Offsets are
+//! characters into the timeline, archived blocks included, like the
+//! [`TextOperation`]s applied to it; commands convert them from and to
+//! offsets into the rendered document. Locks move with edits made around
+//! them.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::TextOperation;
+
+/// Locks not used for this long are dropped, so a writer that stops
+/// responding doesn't block the range forever. Editing with a lock's
+/// token renews it.
+pub const LOCK_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EditLock {
+    pub token: String,
+    pub owner: String,
+    pub start_position: usize,
+    pub end_position: usize,
+    pub expires_in_ms: u64,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LockError {
+    #[error("{start_position}..{end_position} is locked by {owner}")]
+    Locked {
+        owner: String,
+        start_position: usize,
+        end_position: usize,
+        /// When the lock expires if its owner doesn't use it again.
+        retry_after_ms: u64,
+    },
+    #[error("unknown or expired lock '{0}'")]
+    UnknownLock(String),
+}
+
+#[derive(Clone, Debug)]
+struct HeldLock {
+    owner: String,
+    start: usize,
+    end: usize,
+    expires: Instant,
+}
+
+impl HeldLock {
+    fn conflict(&self, now: Instant) -> LockError {
+        LockError::Locked {
+            owner: self.owner.clone(),
+            start_position: self.start,
+            end_position: self.end,
+            retry_after_ms: millis(self.expires.saturating_duration_since(now)),
+        }
+    }
+
+    /// Whether `op` changes text inside the lock. Inserting at either edge
+    /// of a non-empty lock is allowed; an empty lock holds its position.
+    fn blocks(&self, op: &TextOperation) -> bool {
+        match *op {
+            TextOperation::Insert { position, .. } => {
+                (self.start < position && position < self.end)
+                    || (self.start == self.end && position == self.start)
+            }
+            TextOperation::Delete {
+                start_position,
+                end_position,
+            } => {
+                (start_position < self.end && self.start < end_position)
+                    || (self.start == self.end
+                        && start_position < self.start
+                        && self.start < end_position)
+            }
+        }
+    }
+
+    /// Moves the lock to where its text is after `op`. The owner's inserts
+    /// at or inside the lock extend it.
+    fn shift(&mut self, op: &TextOperation, owned: bool) {
+        match op {
+            TextOperation::Insert { position, text } => {
+                let len = text.chars().count();
+                if owned && self.start <= *position && *position <= self.end {
+                    self.end += len;
+                } else if *position <= self.start {
+                    self.start += len;
+                    self.end += len;
+                }
+            }
+            TextOperation::Delete {
+                start_position,
+                end_position,
+            } => {
+                let map = |offset: usize| {
+                    if offset <= *start_position {
+                        offset
+                    } else if offset >= *end_position {
+                        offset - (end_position - start_position)
+                    } else {
+                        *start_position
+                    }
+                };
+                self.start = map(self.start);
+                self.end = map(self.end);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EditLocks {
+    next_id: AtomicU64,
+    held: Mutex<HashMap<String, HeldLock>>,
+}
+
+impl EditLocks {
+    /// Locks `start..end` for `owner`, unless another live lock overlaps
+    /// it.
+    pub fn acquire(
+        &self,
+        owner: &str,
+        start: usize,
+        end: usize,
+        now: Instant,
+    ) -> Result<EditLock, LockError> {
+        let (start, end) = (start.min(end), start.max(end));
+        let mut held = self.held(now);
+        let overlapping = held.values().find(|lock| {
+            (start < lock.end && lock.start < end)
+                || ((start == end || lock.start == lock.end)
+                    && lock.start <= end
+                    && start <= lock.end)
+        });
+        if let Some(lock) = overlapping {
+            return Err(lock.conflict(now));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = format!("lock-{id}");
+        let lock = HeldLock {
+            owner: owner.to_string(),
+            start,
+            end,
+            expires: now + LOCK_TTL,
+        };
+        held.insert(token.clone(), lock);
+        Ok(EditLock {
+            token,
+            owner: owner.to_string(),
+            start_position: start,
+            end_position: end,
+            expires_in_ms: millis(LOCK_TTL),
+        })
+    }
+
+    /// Returns whether the lock was still held.
+    pub fn release(&self, token: &str, now: Instant) -> bool {
+        self.held(now).remove(token).is_some()
+    }
+
+    /// Refuses `ops` if any of them changes text under a lock other than
+    /// `token`'s. Each op is checked against the locks as earlier ops in
+    /// the batch move them.
+    pub fn check(
+        &self,
+        ops: &[TextOperation],
+        token: Option<&str>,
+        now: Instant,
+    ) -> Result<(), LockError> {
+        let mut held = self.held(now).clone();
+        if let Some(token) = token.filter(|token| !held.contains_key(*token)) {
+            return Err(LockError::UnknownLock(token.to_string()));
+        }
+        for op in ops {
+            let blocking = held
+                .iter()
+                .find(|(held_token, lock)| Some(held_token.as_str()) != token && lock.blocks(op));
+            if let Some((_, lock)) = blocking {
+                return Err(lock.conflict(now));
+            }
+            for (held_token, lock) in held.iter_mut() {
+                lock.shift(op, Some(held_token.as_str()) == token);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves locks past `ops` once they've been applied, renewing
+    /// `token`'s lock.
+    pub fn apply(&self, ops: &[TextOperation], token: Option<&str>, now: Instant) {
+        let mut held = self.held(now);
+        for op in ops {
+            for (held_token, lock) in held.iter_mut() {
+                lock.shift(op, Some(held_token.as_str()) == token);
+            }
+        }
+        if let Some(lock) = token.and_then(|token| held.get_mut(token)) {
+            lock.expires = now + LOCK_TTL;
+        }
+    }
+
+    /// The locks still live at `now`.
+    fn held(&self, now: Instant) -> MutexGuard<'_, HashMap<String, HeldLock>> {
+        let mut held = self.held.lock().expect("edit locks poisoned");
+        held.retain(|_, lock| lock.expires > now);
+        held
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(position: usize, text: &str) -> TextOperation {
+        TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        }
+    }
+
+    fn delete(start_position: usize, end_position: usize) -> TextOperation {
+        TextOperation::Delete {
+            start_position,
+            end_position,
+        }
+    }
+
+    #[test]
+    fn locked_ranges_refuse_other_writers_until_released() {
+        let locks = EditLocks::default();
+        let now = Instant::now();
+        let lock = locks.acquire("assistant", 10, 20, now).unwrap();
+        assert!(matches!(
+            locks.acquire("someone", 15, 25, now),
+            Err(LockError::Locked { .. })
+        ));
+
+        // Typing inside the range is refused; at its edges it's fine.
+        let refused = locks.check(&[insert(15, "x")], None, now).unwrap_err();
+        assert_eq!(
+            refused,
+            LockError::Locked {
+                owner: "assistant".to_string(),
+                start_position: 10,
+                end_position: 20,
+                retry_after_ms: 30_000,
+            }
+        );
+        assert!(locks.check(&[delete(5, 11)], None, now).is_err());
+        assert!(locks
+            .check(&[insert(10, "x"), insert(25, "y")], None, now)
+            .is_ok());
+        assert!(locks
+            .check(&[insert(15, "x")], Some(&lock.token), now)
+            .is_ok());
+        assert_eq!(
+            locks.check(&[], Some("lock-99"), now),
+            Err(LockError::UnknownLock("lock-99".to_string()))
+        );
+
+        // Text typed before the lock moves it; the owner's text grows it.
+        locks.apply(&[insert(0, "abc")], None, now);
+        locks.apply(&[insert(23, "four")], Some(&lock.token), now);
+        assert!(locks.check(&[insert(13, "x")], None, now).is_ok());
+        assert!(locks.check(&[insert(14, "x")], None, now).is_err());
+        assert!(locks.check(&[insert(26, "x")], None, now).is_err());
+        assert!(locks.check(&[insert(27, "x")], None, now).is_ok());
+
+        assert!(locks.release(&lock.token, now));
+        assert!(!locks.release(&lock.token, now));
+        assert!(locks.check(&[insert(15, "x")], None, now).is_ok());
+    }
+
+    #[test]
+    fn empty_locks_hold_their_position_and_locks_expire() {
+        let locks = EditLocks::default();
+        let now = Instant::now();
+        let lock = locks.acquire("assistant", 40, 40, now).unwrap();
+        assert!(locks.check(&[insert(40, "x")], None, now).is_err());
+        assert!(locks.check(&[delete(30, 50)], None, now).is_err());
+        assert!(locks.check(&[insert(39, "x")], None, now).is_ok());
+
+        locks.apply(&[insert(40, "Summary")], Some(&lock.token), now);
+        assert!(locks.check(&[insert(44, "x")], None, now).is_err());
+        assert!(locks.check(&[insert(47, "x")], None, now).is_ok());
+
+        let later = now + LOCK_TTL;
+        assert!(locks.check(&[insert(44, "x")], None, later).is_ok());
+        assert!(!locks.release(&lock.token, later));
+    }
+}
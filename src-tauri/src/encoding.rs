@@ -0,0 +1,133 @@
+//! Reading notes that older tools saved as UTF-16 or Windows-1252 instead
+//! of UTF-8, shared by the importer and the in-app vault import.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use encoding_rs::{UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Encoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// Also covers latin-1, which it extends.
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+}
+
+pub fn read_text(path: &Path) -> io::Result<Decoded> {
+    Ok(decode(&fs::read(path)?))
+}
+
+/// Decodes by byte-order mark, then by the NUL pattern of BOM-less UTF-16,
+/// then as UTF-8, falling back to Windows-1252, which maps every byte.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (detected, bom_length) = encoding_rs::Encoding::for_bom(bytes)
+        .unwrap_or_else(|| (sniff_utf16(bytes).unwrap_or(UTF_8), 0));
+    let bytes = &bytes[bom_length..];
+    if detected == UTF_8 {
+        if let Some(text) = UTF_8.decode_without_bom_handling_and_without_replacement(bytes) {
+            return Decoded {
+                text: text.into_owned(),
+                encoding: Encoding::Utf8,
+            };
+        }
+    }
+    let (detected, encoding) = if detected == UTF_16LE {
+        (UTF_16LE, Encoding::Utf16Le)
+    } else if detected == UTF_16BE {
+        (UTF_16BE, Encoding::Utf16Be)
+    } else {
+        (WINDOWS_1252, Encoding::Windows1252)
+    };
+    let (text, _) = detected.decode_without_bom_handling(bytes);
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+    }
+}
+
+/// Mostly-ASCII UTF-16 has a NUL in every other byte.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let (mut even, mut odd) = (0, 0);
+    for pair in bytes.chunks_exact(2) {
+        even += usize::from(pair[0] == 0);
+        odd += usize::from(pair[1] == 0);
+    }
+    if odd * 2 > pairs && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 2 > pairs && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_detects_boms_utf16_and_windows_1252() {
+        let decoded = |bytes: &[u8]| {
+            let Decoded { text, encoding } = decode(bytes);
+            (text, encoding)
+        };
+
+        assert_eq!(
+            decoded("café".as_bytes()),
+            ("café".to_string(), Encoding::Utf8)
+        );
+        assert_eq!(
+            decoded(b"\xef\xbb\xbfplain"),
+            ("plain".to_string(), Encoding::Utf8)
+        );
+        assert_eq!(
+            decoded(b"caf\xe9 \x93quoted\x94 \x80"),
+            (
+                "café \u{201c}quoted\u{201d} €".to_string(),
+                Encoding::Windows1252
+            )
+        );
+
+        let mut with_bom = b"\xff\xfe".to_vec();
+        with_bom.extend(utf16("Día 1", false));
+        assert_eq!(decoded(&with_bom), ("Día 1".to_string(), Encoding::Utf16Le));
+        assert_eq!(
+            decoded(&utf16("Notes\n", true)),
+            ("Notes\n".to_string(), Encoding::Utf16Be)
+        );
+        assert_eq!(
+            decoded(&utf16("Notes\n", false)),
+            ("Notes\n".to_string(), Encoding::Utf16Le)
+        );
+    }
+}
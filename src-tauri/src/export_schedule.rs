@@ -0,0 +1,136 @@
+//! Markdown exports taken on a schedule, such as "export to ~/Backups
+//! weekly". The schedule is a timeline setting; each run is a repeating
+//! [`JobKind::ScheduledExport`] in the job queue, which retries failed
+//! runs and remembers how the last one went.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::{Job, JobError, JobKind};
+use crate::markdown::TagRendering;
+
+/// Emitted with an [`ExportRun`] after each scheduled export.
+pub const EXPORT_FINISHED_EVENT: &str = "scheduled-export-finished";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPeriod {
+    Daily,
+    Weekly,
+}
+
+impl ExportPeriod {
+    pub fn hours(self) -> u32 {
+        match self {
+            Self::Daily => 24,
+            Self::Weekly => 24 * 7,
+        }
+    }
+}
+
+/// Where and how often the timeline is exported as markdown. Sensitive
+/// blocks are always masked, since nobody is there to unlock them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportSchedule {
+    pub dir: PathBuf,
+    pub every: ExportPeriod,
+    #[serde(default)]
+    pub tag_rendering: TagRendering,
+}
+
+/// How a scheduled export went.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExportRun {
+    pub at: DateTime<Utc>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExportRun {
+    pub fn new(at: DateTime<Utc>, result: &Result<(), JobError>) -> Self {
+        Self {
+            at,
+            ok: result.is_ok(),
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+
+    /// How `job` last ran, if it has.
+    pub fn last(job: &Job) -> Option<Self> {
+        Some(Self {
+            at: job.last_run_at?,
+            ok: job.last_error.is_none(),
+            error: job.last_error.clone(),
+        })
+    }
+}
+
+/// From `get_export_schedule_status`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ExportScheduleStatus {
+    pub schedule: Option<ExportSchedule>,
+    pub last_run: Option<ExportRun>,
+    /// When the next export, or retry, is due.
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+impl ExportScheduleStatus {
+    /// The status of `schedule`, whose runs are `job`.
+    pub fn new(schedule: Option<ExportSchedule>, job: Option<&Job>) -> Self {
+        let job = job.filter(|job| job.kind == JobKind::ScheduledExport);
+        Self {
+            last_run: job.and_then(ExportRun::last),
+            next_run: job
+                .filter(|_| schedule.is_some())
+                .map(|job| job.next_attempt_at),
+            schedule,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobQueue;
+
+    #[test]
+    fn status_reports_the_last_run_and_the_next() {
+        let at = |hour: i64| DateTime::from_timestamp(1_700_000_000 + hour * 3600, 0).unwrap();
+        let schedule = ExportSchedule {
+            dir: PathBuf::from("/backups"),
+            every: ExportPeriod::Weekly,
+            tag_rendering: TagRendering::default(),
+        };
+        let queue = JobQueue::load(None);
+        let job = queue
+            .schedule(
+                JobKind::ScheduledExport,
+                at(0),
+                at(0),
+                schedule.every.hours(),
+            )
+            .unwrap();
+        let status = ExportScheduleStatus::new(Some(schedule.clone()), Some(&job));
+        assert_eq!(status.last_run, None);
+        assert_eq!(status.next_run, Some(at(0)));
+
+        queue
+            .run_due(at(1), |_| Err(JobError::Failed("disk full".into())))
+            .unwrap();
+        let job = queue.find(&JobKind::ScheduledExport).unwrap();
+        let status = ExportScheduleStatus::new(Some(schedule), Some(&job));
+        assert_eq!(
+            status.last_run,
+            Some(ExportRun {
+                at: at(1),
+                ok: false,
+                error: Some("disk full".to_string()),
+            })
+        );
+        assert_eq!(status.next_run, Some(job.next_attempt_at));
+        assert_eq!(ExportScheduleStatus::new(None, Some(&job)).next_run, None);
+    }
+}
@@ -0,0 +1,189 @@
+//! Flashcards written in tagged blocks as `front :: back` lines, exported as
+//! a TSV file Anki can import directly.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::timeline::{ArchivedContent, SensitiveContent, TagRegistry, Timeline};
+
+/// Separates a card's front from its back within one line.
+pub const CARD_DELIMITER: &str = "::";
+
+/// File headers Anki (2.1.55+) reads to configure the import.
+const ANKI_HEADER: &str = "#separator:tab\n#html:true\n#tags column:3\n";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Card {
+    pub front: String,
+    pub back: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FlashcardExport {
+    pub cards: usize,
+    /// Blocks carrying the tag that held no `front :: back` lines.
+    pub blocks_without_cards: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum FlashcardError {
+    #[error("no tag named '{0}'")]
+    UnknownTag(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Cards in `text`, one per line containing [`CARD_DELIMITER`] with text on
+/// both sides. List markers before the front are dropped.
+pub fn cards(text: &str) -> Vec<Card> {
+    text.lines()
+        .filter_map(|line| {
+            let (front, back) = line.split_once(CARD_DELIMITER)?;
+            let front = strip_list_marker(front.trim());
+            let back = back.trim();
+            (!front.is_empty() && !back.is_empty()).then(|| Card {
+                front: front.to_string(),
+                back: back.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    for marker in ["- [ ] ", "- [x] ", "- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return rest.trim_start();
+        }
+    }
+    match line.split_once(". ") {
+        Some((number, rest)) if number.chars().all(|ch| ch.is_ascii_digit()) => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// Writes every card in blocks tagged `tag` (or a descendant) to `path`.
+/// Each card carries its block's tags, with `a:b` written as Anki's `a::b`.
+pub fn export(
+    timeline: &Timeline,
+    tag: &str,
+    path: &Path,
+    sensitive: SensitiveContent,
+) -> Result<FlashcardExport, FlashcardError> {
+    let registry = timeline.tag_registry();
+    let tag_id = registry
+        .find_colon_path(tag)
+        .ok_or_else(|| FlashcardError::UnknownTag(tag.to_string()))?;
+
+    let mut summary = FlashcardExport::default();
+    let mut tsv = String::from(ANKI_HEADER);
+    for block in timeline.blocks_tagged(tag_id, sensitive, ArchivedContent::Excluded) {
+        let block_cards = cards(&block.text);
+        if block_cards.is_empty() {
+            summary.blocks_without_cards += 1;
+            continue;
+        }
+
+        let tags = anki_tags(registry, &block.tags);
+        for card in block_cards {
+            tsv.push_str(&format!(
+                "{}\t{}\t{tags}\n",
+                escape_field(&card.front),
+                escape_field(&card.back)
+            ));
+            summary.cards += 1;
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, tsv)?;
+    Ok(summary)
+}
+
+fn anki_tags(registry: &TagRegistry, tag_ids: &[u32]) -> String {
+    tag_ids
+        .iter()
+        .filter_map(|&id| registry.full_name(id))
+        .map(|name| name.replace(':', "::").replace(char::is_whitespace, "_"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Fields are imported as HTML, so markup characters are escaped and tabs,
+/// which would split the field, become spaces.
+fn escape_field(field: &str) -> String {
+    field
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::TextOperation;
+    use tempfile::tempdir;
+
+    #[test]
+    fn cards_split_lines_on_the_delimiter() {
+        let text = "Spanish vocab\n- perro :: dog\n2. gato::cat\nno card here\n :: missing front\n";
+        assert_eq!(
+            cards(text),
+            vec![
+                Card {
+                    front: "perro".to_string(),
+                    back: "dog".to_string(),
+                },
+                Card {
+                    front: "gato".to_string(),
+                    back: "cat".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn export_writes_anki_tsv_for_tagged_blocks() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "a<b :: true\nplain note\n".to_string(),
+                }],
+            )
+            .unwrap();
+        timeline
+            .assign_block_tags(0, &["#flashcard:math".to_string()])
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cards.tsv");
+        let summary = export(&timeline, "#flashcard", &path, SensitiveContent::Masked).unwrap();
+        assert_eq!(
+            summary,
+            FlashcardExport {
+                cards: 1,
+                blocks_without_cards: 0,
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{ANKI_HEADER}a&lt;b\ttrue\tflashcard::math\n")
+        );
+
+        assert!(matches!(
+            export(&timeline, "missing", &path, SensitiveContent::Masked),
+            Err(FlashcardError::UnknownTag(_))
+        ));
+    }
+}
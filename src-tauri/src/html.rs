@@ -0,0 +1,54 @@
+//! Reading HTML parsed with `scraper`, shared by link titles and the
+//! importer's HTML notes. The parser decodes entities, so text and
+//! attribute values come back as plain text.
+
+use scraper::{Html, Selector};
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).unwrap_or_else(|err| panic!("invalid selector {selector:?}: {err}"))
+}
+
+/// `text` with runs of whitespace, non-breaking spaces included, collapsed
+/// to single spaces and trimmed.
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The text of the first element matching `css`, whitespace collapsed, or
+/// `None` when there is no such element or it has no text.
+pub fn first_text(document: &Html, css: &str) -> Option<String> {
+    let element = document.select(&selector(css)).next()?;
+    Some(collapse_whitespace(&element.text().collect::<String>())).filter(|text| !text.is_empty())
+}
+
+/// Attribute `name` of the first element matching `css`, whitespace
+/// collapsed, or `None` when it is missing or blank.
+pub fn first_attr(document: &Html, css: &str, name: &str) -> Option<String> {
+    let element = document.select(&selector(css)).next()?;
+    Some(collapse_whitespace(element.value().attr(name)?)).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_and_attributes_are_decoded_and_collapsed() {
+        let document = Html::parse_document(
+            r#"<title>
+                Trip &amp;&nbsp;plans </title>
+            <meta property="og:title" content=" Rust &quot;book&quot; ">
+            <p> </p>"#,
+        );
+        assert_eq!(
+            first_text(&document, "title").as_deref(),
+            Some("Trip & plans")
+        );
+        assert_eq!(
+            first_attr(&document, r#"meta[property="og:title"]"#, "content").as_deref(),
+            Some("Rust \"book\"")
+        );
+        assert_eq!(first_text(&document, "p"), None);
+        assert_eq!(first_attr(&document, "p", "class"), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! iCalendar export: an all-day event for each day with entries and a to-do
+//! for each task with a due date, so the journal can be laid over a
+//! calendar app.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::tasks::TaskFilter;
+use crate::timeline::{ArchivedContent, DateRange, SensitiveContent, TaggedBlock, Timeline};
+
+const PRODUCT_ID: &str = "-//Sightline//Journal Export//EN";
+
+/// Longest content line, in bytes, before it is folded (RFC 5545 §3.1).
+const LINE_LIMIT: usize = 75;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IcalExport {
+    /// Days written as events.
+    pub events: usize,
+    /// Due-dated tasks written as to-dos.
+    pub todos: usize,
+}
+
+/// Writes [`render`]'s calendar to `path`, replacing it.
+pub fn export(
+    timeline: &Timeline,
+    path: &Path,
+    sensitive: SensitiveContent,
+    stamp: DateTime<Utc>,
+) -> io::Result<IcalExport> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let (calendar, summary) = render(timeline, sensitive, stamp);
+    fs::write(path, calendar)?;
+    Ok(summary)
+}
+
+/// The timeline as an iCalendar document. Each day with unarchived blocks
+/// is an all-day `VEVENT` titled with its first line and holding the day's
+/// text; each task with an `@due` date is a `VTODO` due that day. Masked
+/// sensitive blocks and their tasks are left out. `stamp` is written as
+/// every component's `DTSTAMP`.
+pub fn render(
+    timeline: &Timeline,
+    sensitive: SensitiveContent,
+    stamp: DateTime<Utc>,
+) -> (String, IcalExport) {
+    let stamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut summary = IcalExport::default();
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, &format!("PRODID:{PRODUCT_ID}"));
+    line(&mut out, "CALSCALE:GREGORIAN");
+
+    let mut days: BTreeMap<NaiveDate, Vec<&TaggedBlock>> = BTreeMap::new();
+    for block in
+        timeline.blocks_in_range(&DateRange::default(), sensitive, ArchivedContent::Excluded)
+    {
+        if !block.text.trim().is_empty() {
+            days.entry(block.date).or_default().push(block);
+        }
+    }
+    for (date, blocks) in &days {
+        let text = blocks
+            .iter()
+            .map(|block| block.text.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let title = text.lines().map(str::trim).find(|line| !line.is_empty());
+        line(&mut out, "BEGIN:VEVENT");
+        line(
+            &mut out,
+            &format!("UID:{}@sightline", date.format("%Y%m%d")),
+        );
+        line(&mut out, &format!("DTSTAMP:{stamp}"));
+        line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", ical_date(*date)),
+        );
+        if let Some(next) = date.succ_opt() {
+            line(&mut out, &format!("DTEND;VALUE=DATE:{}", ical_date(next)));
+        }
+        line(
+            &mut out,
+            &format!("SUMMARY:{}", escape(title.unwrap_or_default())),
+        );
+        line(&mut out, &format!("DESCRIPTION:{}", escape(&text)));
+        line(&mut out, "END:VEVENT");
+        summary.events += 1;
+    }
+
+    // Ids are derived from the task so re-exports update the same to-dos;
+    // repeats of one task on one day are numbered.
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for task in timeline.tasks(&TaskFilter::default(), sensitive) {
+        let Some(due) = task.due else {
+            continue;
+        };
+        let key = format!(
+            "{}-{:08x}",
+            task.date.format("%Y%m%d"),
+            crc32fast::hash(task.text.as_bytes())
+        );
+        let repeat = seen.entry(key.clone()).or_default();
+        let uid = match *repeat {
+            0 => format!("task-{key}@sightline"),
+            n => format!("task-{key}-{n}@sightline"),
+        };
+        *repeat += 1;
+        let title = task.text.replace(&format!("@due({due})"), "");
+        line(&mut out, "BEGIN:VTODO");
+        line(&mut out, &format!("UID:{uid}"));
+        line(&mut out, &format!("DTSTAMP:{stamp}"));
+        line(&mut out, &format!("SUMMARY:{}", escape(title.trim())));
+        line(&mut out, &format!("DUE;VALUE=DATE:{}", ical_date(due)));
+        let status = if task.done {
+            "COMPLETED"
+        } else {
+            "NEEDS-ACTION"
+        };
+        line(&mut out, &format!("STATUS:{status}"));
+        line(&mut out, "END:VTODO");
+        summary.todos += 1;
+    }
+
+    line(&mut out, "END:VCALENDAR");
+    (out, summary)
+}
+
+fn ical_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Escapes a TEXT value (RFC 5545 §3.3.11).
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Appends `content` as a CRLF-terminated line, folded so no line is longer
+/// than [`LINE_LIMIT`] bytes and no character is split.
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for ch in content.chars() {
+        if width + ch.len_utf8() > LINE_LIMIT {
+            out.push_str("\r\n ");
+            // The leading space counts towards the folded line.
+            width = 1;
+        }
+        out.push(ch);
+        width += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped_and_long_lines_folded() {
+        assert_eq!(escape("a,b;c\\d\r\ne"), "a\\,b\\;c\\\\d\\ne");
+
+        let mut out = String::new();
+        line(&mut out, &format!("SUMMARY:{}", "é".repeat(50)));
+        let lines: Vec<&str> = out.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= LINE_LIMIT));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(
+            out.replace("\r\n ", "").trim_end(),
+            format!("SUMMARY:{}", "é".repeat(50))
+        );
+    }
+
+    #[test]
+    fn days_become_events_and_due_tasks_todos() {
+        let snapshot = serde_json::json!({
+            "version": 1,
+            "blocks": [
+                {"date": "2024-05-01", "text": "Planning, day one\n- [ ] rent @due(2024-06-01)\n", "tags": []},
+                {"date": "2024-05-01", "text": "- [x] book train @due(2024-05-03)\n", "tags": []},
+                {"date": "2024-05-02", "text": "diary\n- [ ] secret @due(2024-05-09)\n", "tags": [1]},
+                {"date": "2024-05-03", "text": "- [ ] no date\n", "tags": []},
+                {"date": "2024-05-04", "text": "old\n", "tags": [], "archived": true}
+            ],
+            "tag_registry": [{"id": 1, "name": "sensitive", "parent_id": null}]
+        });
+        let timeline = Timeline::from_snapshot_json(snapshot.to_string().as_bytes()).unwrap();
+        let stamp = DateTime::parse_from_rfc3339("2024-06-01T08:30:00Z")
+            .unwrap()
+            .to_utc();
+
+        let (calendar, summary) = render(&timeline, SensitiveContent::Masked, stamp);
+        assert_eq!(
+            summary,
+            IcalExport {
+                events: 2,
+                todos: 2
+            }
+        );
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains(
+            "BEGIN:VEVENT\r\nUID:20240501@sightline\r\nDTSTAMP:20240601T083000Z\r\n\
+             DTSTART;VALUE=DATE:20240501\r\nDTEND;VALUE=DATE:20240502\r\n\
+             SUMMARY:Planning\\, day one\r\n"
+        ));
+        assert!(calendar.contains("SUMMARY:rent\r\nDUE;VALUE=DATE:20240601\r\nSTATUS:NEEDS-ACTION"));
+        assert!(
+            calendar.contains("SUMMARY:book train\r\nDUE;VALUE=DATE:20240503\r\nSTATUS:COMPLETED")
+        );
+        assert!(!calendar.contains("diary") && !calendar.contains("secret"));
+        assert!(!calendar.contains("UID:20240504@sightline"));
+
+        let (_, summary) = render(&timeline, SensitiveContent::Included, stamp);
+        assert_eq!(
+            summary,
+            IcalExport {
+                events: 3,
+                todos: 3
+            }
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Dates that come round every year: birthdays, anniversaries and public
+//! holidays. They are kept with the timeline settings and shown in daily
+//! notes and weekly reviews.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateKind {
+    Birthday,
+    Anniversary,
+    Holiday,
+    Other,
+}
+
+/// A user's own yearly date. A 29 February date falls on 28 February in
+/// other years.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportantDate {
+    /// How it reads in notes, e.g. "Mom's birthday".
+    pub name: String,
+    pub kind: DateKind,
+    pub month: u32,
+    pub day: u32,
+    /// The year it first happened, to count birthdays and anniversaries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportantDates {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dates: Vec<ImportantDate>,
+    /// Locale whose public holidays are included, e.g. `en-US` or `GB`;
+    /// see [`HOLIDAY_REGIONS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holidays: Option<String>,
+}
+
+/// One date landing on a particular day.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DateOccurrence {
+    pub date: NaiveDate,
+    pub name: String,
+    pub kind: DateKind,
+    /// Years since it first happened, when that's known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub years: Option<i32>,
+}
+
+impl DateOccurrence {
+    /// The name with the count of years, e.g. "Mom's birthday (60)".
+    pub fn label(&self) -> String {
+        match self.years {
+            Some(years) => format!("{} ({years})", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ImportantDateError {
+    #[error("important dates need a name")]
+    EmptyName,
+    #[error("{month}/{day} is not a date")]
+    InvalidDate { month: u32, day: u32 },
+    #[error("no public holidays are known for '{0}'")]
+    UnknownLocale(String),
+}
+
+impl ImportantDates {
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty() && self.holidays.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), ImportantDateError> {
+        for date in &self.dates {
+            if date.name.trim().is_empty() {
+                return Err(ImportantDateError::EmptyName);
+            }
+            // 2000 was a leap year, so 29 February passes.
+            if NaiveDate::from_ymd_opt(2000, date.month, date.day).is_none() {
+                return Err(ImportantDateError::InvalidDate {
+                    month: date.month,
+                    day: date.day,
+                });
+            }
+        }
+        if let Some(locale) = &self.holidays {
+            holidays_for(locale)
+                .ok_or_else(|| ImportantDateError::UnknownLocale(locale.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Every date falling between `from` and `to` inclusive, in date
+    /// order. Holidays come before the user's own dates on the same day.
+    pub fn between(&self, from: NaiveDate, to: NaiveDate) -> Vec<DateOccurrence> {
+        let holidays = self
+            .holidays
+            .as_deref()
+            .and_then(holidays_for)
+            .unwrap_or(&[]);
+        let mut found = Vec::new();
+        for year in from.year()..=to.year() {
+            for (name, rule) in holidays {
+                if let Some(date) = rule.in_year(year) {
+                    found.push(DateOccurrence {
+                        date,
+                        name: name.to_string(),
+                        kind: DateKind::Holiday,
+                        years: None,
+                    });
+                }
+            }
+            for important in &self.dates {
+                let leap_day = important.month == 2 && important.day == 29;
+                let Some(date) = NaiveDate::from_ymd_opt(year, important.month, important.day)
+                    .or_else(|| {
+                        leap_day
+                            .then(|| NaiveDate::from_ymd_opt(year, 2, 28))
+                            .flatten()
+                    })
+                else {
+                    continue;
+                };
+                let years = important.year.map(|first| year - first);
+                if years.is_some_and(|years| years < 0) {
+                    continue;
+                }
+                found.push(DateOccurrence {
+                    date,
+                    name: important.name.clone(),
+                    kind: important.kind,
+                    years,
+                });
+            }
+        }
+        found.retain(|occurrence| from <= occurrence.date && occurrence.date <= to);
+        found.sort_by_key(|occurrence| occurrence.date);
+        found
+    }
+}
+
+/// Regions with a built-in list of public holidays.
+pub const HOLIDAY_REGIONS: &[&str] = &["CA", "DE", "GB", "US"];
+
+/// How a holiday's date is found in a given year.
+#[derive(Clone, Copy, Debug)]
+enum HolidayRule {
+    Fixed {
+        month: u32,
+        day: u32,
+    },
+    /// The `n`th `weekday` of the month; a negative `n` counts from the
+    /// end.
+    Nth {
+        month: u32,
+        weekday: Weekday,
+        n: i8,
+    },
+    /// The last `weekday` on or before the date.
+    OnOrBefore {
+        month: u32,
+        day: u32,
+        weekday: Weekday,
+    },
+    /// Days after Easter Sunday.
+    Easter(i64),
+}
+
+impl HolidayRule {
+    fn in_year(self, year: i32) -> Option<NaiveDate> {
+        match self {
+            Self::Fixed { month, day } => NaiveDate::from_ymd_opt(year, month, day),
+            Self::Nth { month, weekday, n } if n > 0 => {
+                NaiveDate::from_weekday_of_month_opt(year, month, weekday, n.unsigned_abs())
+            }
+            Self::Nth { month, weekday, n } => {
+                let (next_year, next_month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                let last = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt()?;
+                let back = (7 + last.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                Some(last - Duration::days(i64::from(back) + 7 * (i64::from(-n) - 1)))
+            }
+            Self::OnOrBefore {
+                month,
+                day,
+                weekday,
+            } => {
+                let date = NaiveDate::from_ymd_opt(year, month, day)?;
+                let back = (7 + date.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                Some(date - Duration::days(i64::from(back)))
+            }
+            Self::Easter(offset) => Some(easter(year)? + Duration::days(offset)),
+        }
+    }
+}
+
+/// Easter Sunday in the Gregorian calendar (the anonymous Gregorian
+/// algorithm).
+fn easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+const fn fixed(month: u32, day: u32) -> HolidayRule {
+    HolidayRule::Fixed { month, day }
+}
+
+const fn nth(month: u32, weekday: Weekday, n: i8) -> HolidayRule {
+    HolidayRule::Nth { month, weekday, n }
+}
+
+const US: &[(&str, HolidayRule)] = &[
+    ("New Year's Day", fixed(1, 1)),
+    ("Martin Luther King Jr. Day", nth(1, Weekday::Mon, 3)),
+    ("Presidents' Day", nth(2, Weekday::Mon, 3)),
+    ("Memorial Day", nth(5, Weekday::Mon, -1)),
+    ("Juneteenth", fixed(6, 19)),
+    ("Independence Day", fixed(7, 4)),
+    ("Labor Day", nth(9, Weekday::Mon, 1)),
+    ("Columbus Day", nth(10, Weekday::Mon, 2)),
+    ("Veterans Day", fixed(11, 11)),
+    ("Thanksgiving", nth(11, Weekday::Thu, 4)),
+    ("Christmas Day", fixed(12, 25)),
+];
+
+/// England and Wales. Holidays falling on a weekend are listed on the day
+/// itself rather than the substitute weekday.
+const GB: &[(&str, HolidayRule)] = &[
+    ("New Year's Day", fixed(1, 1)),
+    ("Good Friday", HolidayRule::Easter(-2)),
+    ("Easter Monday", HolidayRule::Easter(1)),
+    ("Early May bank holiday", nth(5, Weekday::Mon, 1)),
+    ("Spring bank holiday", nth(5, Weekday::Mon, -1)),
+    ("Summer bank holiday", nth(8, Weekday::Mon, -1)),
+    ("Christmas Day", fixed(12, 25)),
+    ("Boxing Day", fixed(12, 26)),
+];
+
+const CA: &[(&str, HolidayRule)] = &[
+    ("New Year's Day", fixed(1, 1)),
+    ("Good Friday", HolidayRule::Easter(-2)),
+    (
+        "Victoria Day",
+        HolidayRule::OnOrBefore {
+            month: 5,
+            day: 24,
+            weekday: Weekday::Mon,
+        },
+    ),
+    ("Canada Day", fixed(7, 1)),
+    ("Labour Day", nth(9, Weekday::Mon, 1)),
+    ("Thanksgiving", nth(10, Weekday::Mon, 2)),
+    ("Remembrance Day", fixed(11, 11)),
+    ("Christmas Day", fixed(12, 25)),
+    ("Boxing Day", fixed(12, 26)),
+];
+
+/// Nationwide holidays only; state holidays are left out.
+const DE: &[(&str, HolidayRule)] = &[
+    ("New Year's Day", fixed(1, 1)),
+    ("Good Friday", HolidayRule::Easter(-2)),
+    ("Easter Monday", HolidayRule::Easter(1)),
+    ("Labour Day", fixed(5, 1)),
+    ("Ascension Day", HolidayRule::Easter(39)),
+    ("Whit Monday", HolidayRule::Easter(50)),
+    ("German Unity Day", fixed(10, 3)),
+    ("Christmas Day", fixed(12, 25)),
+    ("Second Day of Christmas", fixed(12, 26)),
+];
+
+/// The holidays for a locale's region: `en-US`, `en_GB` and `de` all
+/// work.
+fn holidays_for(locale: &str) -> Option<&'static [(&'static str, HolidayRule)]> {
+    let region = locale.rsplit(['-', '_']).next()?.to_ascii_uppercase();
+    match region.as_str() {
+        "US" => Some(US),
+        "GB" | "UK" => Some(GB),
+        "CA" => Some(CA),
+        "DE" => Some(DE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn holidays_follow_their_rules() {
+        let year = |locale: &str, y: i32| {
+            ImportantDates {
+                dates: Vec::new(),
+                holidays: Some(locale.to_string()),
+            }
+            .between(date(y, 1, 1), date(y, 12, 31))
+        };
+        let on = |found: &[DateOccurrence], name: &str| {
+            found
+                .iter()
+                .find(|occurrence| occurrence.name == name)
+                .map(|occurrence| occurrence.date)
+        };
+
+        let us = year("en-US", 2024);
+        assert_eq!(on(&us, "Memorial Day"), Some(date(2024, 5, 27)));
+        assert_eq!(on(&us, "Thanksgiving"), Some(date(2024, 11, 28)));
+        let gb = year("en_GB", 2025);
+        assert_eq!(on(&gb, "Good Friday"), Some(date(2025, 4, 18)));
+        assert_eq!(on(&gb, "Summer bank holiday"), Some(date(2025, 8, 25)));
+        let ca = year("CA", 2024);
+        assert_eq!(on(&ca, "Victoria Day"), Some(date(2024, 5, 20)));
+        let de = year("de-DE", 2024);
+        assert_eq!(on(&de, "Whit Monday"), Some(date(2024, 5, 20)));
+        assert!(de.windows(2).all(|pair| pair[0].date <= pair[1].date));
+    }
+
+    #[test]
+    fn own_dates_recur_yearly_and_count_years() {
+        let dates = ImportantDates {
+            dates: vec![
+                ImportantDate {
+                    name: "Mom's birthday".to_string(),
+                    kind: DateKind::Birthday,
+                    month: 3,
+                    day: 4,
+                    year: Some(1964),
+                },
+                ImportantDate {
+                    name: "Leap day party".to_string(),
+                    kind: DateKind::Other,
+                    month: 2,
+                    day: 29,
+                    year: None,
+                },
+            ],
+            holidays: None,
+        };
+        assert_eq!(dates.validate(), Ok(()));
+
+        let found = dates.between(date(2023, 2, 1), date(2024, 3, 31));
+        let labels: Vec<(NaiveDate, String)> = found
+            .iter()
+            .map(|occurrence| (occurrence.date, occurrence.label()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                (date(2023, 2, 28), "Leap day party".to_string()),
+                (date(2023, 3, 4), "Mom's birthday (59)".to_string()),
+                (date(2024, 2, 29), "Leap day party".to_string()),
+                (date(2024, 3, 4), "Mom's birthday (60)".to_string()),
+            ]
+        );
+        // Nothing is counted before the year it first happened.
+        let before = dates.between(date(1963, 1, 1), date(1963, 12, 31));
+        assert_eq!(before.len(), 1);
+        assert_eq!(before[0].name, "Leap day party");
+    }
+
+    #[test]
+    fn invalid_settings_are_refused() {
+        let mut dates = ImportantDates {
+            dates: vec![ImportantDate {
+                name: "Someday".to_string(),
+                kind: DateKind::Other,
+                month: 2,
+                day: 30,
+                year: None,
+            }],
+            holidays: None,
+        };
+        assert_eq!(
+            dates.validate(),
+            Err(ImportantDateError::InvalidDate { month: 2, day: 30 })
+        );
+        dates.dates.clear();
+        dates.holidays = Some("Atlantis".to_string());
+        assert_eq!(
+            dates.validate(),
+            Err(ImportantDateError::UnknownLocale("Atlantis".to_string()))
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Background work, retried with backoff: network work queued while
+//! offline, and jobs that repeat on a schedule. The queue is saved beside
+//! the timeline so jobs survive a restart, and a monitor runs due jobs,
+//! holding network jobs back until the network is reachable.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use thiserror::Error;
+
+use crate::export_schedule::{ExportRun, EXPORT_FINISHED_EVENT};
+use crate::{network, AppState};
+
+/// File name of the queue, next to the timeline file.
+pub const JOBS_FILE: &str = "jobs.json";
+
+/// Emitted with a [`ConnectivityChange`] when the network goes away or
+/// comes back.
+pub const CONNECTIVITY_EVENT: &str = "connectivity-changed";
+
+/// How often the monitor probes the network and runs due jobs, while any
+/// are queued.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// An address that answers on any working connection. It is an IP literal
+/// so probing doesn't depend on DNS.
+const PROBE_ADDR: ([u8; 4], u16) = ([1, 1, 1, 1], 443);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Delay before the first retry; it doubles after every failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Failed attempts, not counting offline ones, before a job is dropped.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Commit and push the now page once the network is back.
+    PublishNowPage,
+    /// Look up the title of a link pasted while offline, so pasting it
+    /// again gives a titled link.
+    LinkTitle { url: String },
+    /// Export the timeline as configured in its export schedule; see
+    /// [`crate::export_schedule`].
+    ScheduledExport,
+    /// Send the weekly digest as configured in its schedule; see
+    /// [`crate::digest`].
+    WeeklyDigest,
+}
+
+impl JobKind {
+    /// Whether the job waits for connectivity while offline. The weekly
+    /// digest may only be saving a draft, so it runs regardless and
+    /// reports [`JobError::Offline`] itself when it can't send.
+    pub fn needs_network(&self) -> bool {
+        !matches!(self, Self::ScheduledExport | Self::WeeklyDigest)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    #[serde(flatten)]
+    pub kind: JobKind,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Hours between runs of a job that repeats. It stays queued after
+    /// succeeding, or after failing [`MAX_ATTEMPTS`] times in a row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_hours: Option<u32>,
+    /// When the job last ran, whether or not it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+/// Why a job didn't finish.
+#[derive(Debug, Error)]
+pub enum JobError {
+    /// The network is unreachable; the job waits for connectivity without
+    /// using up an attempt.
+    #[error("offline")]
+    Offline,
+    /// Worth retrying after a backoff.
+    #[error("{0}")]
+    Failed(String),
+    /// Retrying won't help; the job is dropped.
+    #[error("{0}")]
+    Fatal(String),
+}
+
+#[derive(Debug, Error)]
+pub enum JobQueueError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RunSummary {
+    pub completed: usize,
+    pub retrying: usize,
+    pub dropped: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ConnectivityChange {
+    pub online: bool,
+    pub pending: usize,
+}
+
+pub struct JobQueue {
+    /// `None` keeps the queue in memory only.
+    path: Option<PathBuf>,
+    jobs: Mutex<Vec<Job>>,
+    online: AtomicBool,
+}
+
+impl JobQueue {
+    /// Loads the queue saved at `path`. A missing file is an empty queue;
+    /// an unreadable one is logged and replaced.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let jobs = match path.as_deref().map(fs::read) {
+            Some(Ok(contents)) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+                tracing::warn!(%err, "discarding unreadable job queue");
+                Vec::new()
+            }),
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+                tracing::warn!(%err, "failed to read job queue");
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            path,
+            jobs: Mutex::new(jobs),
+            online: AtomicBool::new(true),
+        }
+    }
+
+    /// Adds a job that is due immediately. A job identical to one already
+    /// pending is not queued twice.
+    pub fn enqueue(&self, kind: JobKind, now: DateTime<Utc>) -> Result<Job, JobQueueError> {
+        let mut jobs = self.jobs();
+        if let Some(existing) = jobs.iter().find(|job| job.kind == kind) {
+            return Ok(existing.clone());
+        }
+        let job = new_job(&jobs, kind, now, now, None);
+        jobs.push(job.clone());
+        self.save(&jobs)?;
+        Ok(job)
+    }
+
+    /// Queues a job first due at `first_run` and then every `repeat_hours`,
+    /// replacing any pending job of the same kind.
+    pub fn schedule(
+        &self,
+        kind: JobKind,
+        now: DateTime<Utc>,
+        first_run: DateTime<Utc>,
+        repeat_hours: u32,
+    ) -> Result<Job, JobQueueError> {
+        let mut jobs = self.jobs();
+        let previous = jobs.iter().position(|job| job.kind == kind);
+        let mut job = new_job(&jobs, kind, now, first_run, Some(repeat_hours));
+        if let Some(previous) = previous {
+            job.last_run_at = jobs[previous].last_run_at;
+            job.last_error = jobs[previous].last_error.clone();
+            jobs.remove(previous);
+        }
+        jobs.push(job.clone());
+        self.save(&jobs)?;
+        Ok(job)
+    }
+
+    /// Removes the pending job of `kind`, returning whether there was one.
+    pub fn cancel(&self, kind: &JobKind) -> Result<bool, JobQueueError> {
+        let mut jobs = self.jobs();
+        let before = jobs.len();
+        jobs.retain(|job| job.kind != *kind);
+        if jobs.len() == before {
+            return Ok(false);
+        }
+        self.save(&jobs)?;
+        Ok(true)
+    }
+
+    /// The pending job of `kind`, if any.
+    pub fn find(&self, kind: &JobKind) -> Option<Job> {
+        self.jobs().iter().find(|job| job.kind == *kind).cloned()
+    }
+
+    /// Queued jobs, oldest first.
+    pub fn pending(&self) -> Vec<Job> {
+        self.jobs().clone()
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.jobs().is_empty()
+    }
+
+    /// Whether any queued job waits for the network.
+    pub fn has_pending_network(&self) -> bool {
+        self.jobs().iter().any(|job| job.kind.needs_network())
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    /// Records the network state and returns whether it changed.
+    pub fn set_online(&self, online: bool) -> bool {
+        self.online.swap(online, Ordering::SeqCst) != online
+    }
+
+    /// Runs every job due at `now` through `run`; while offline, network
+    /// jobs wait. Failed jobs are rescheduled with exponential backoff and
+    /// dropped after [`MAX_ATTEMPTS`], except that repeating jobs move on
+    /// to their next run instead of being dropped. A job that reports
+    /// [`JobError::Offline`] marks the queue offline.
+    pub fn run_due(
+        &self,
+        now: DateTime<Utc>,
+        mut run: impl FnMut(&Job) -> Result<(), JobError>,
+    ) -> Result<RunSummary, JobQueueError> {
+        let mut summary = RunSummary::default();
+        // Jobs run without the lock held, so they may enqueue follow-ups.
+        let due: Vec<Job> = self
+            .jobs()
+            .iter()
+            .filter(|job| job.next_attempt_at <= now)
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            return Ok(summary);
+        }
+        let mut finished = Vec::new();
+        let mut ran = Vec::new();
+        for job in due {
+            if job.kind.needs_network() && !self.is_online() {
+                continue;
+            }
+            match run(&job) {
+                Ok(()) => {
+                    summary.completed += 1;
+                    ran.push((job.id, None));
+                }
+                Err(JobError::Offline) => {
+                    self.set_online(false);
+                }
+                Err(JobError::Fatal(message)) => {
+                    tracing::warn!(id = job.id, %message, "dropping job");
+                    summary.dropped += 1;
+                    finished.push(job.id);
+                }
+                Err(JobError::Failed(message))
+                    if job.attempts + 1 >= MAX_ATTEMPTS && job.repeat_hours.is_none() =>
+                {
+                    tracing::warn!(id = job.id, %message, "dropping job after repeated failures");
+                    summary.dropped += 1;
+                    finished.push(job.id);
+                }
+                Err(JobError::Failed(message)) => {
+                    summary.retrying += 1;
+                    ran.push((job.id, Some(message)));
+                }
+            }
+        }
+
+        let mut jobs = self.jobs();
+        for (id, error) in ran {
+            let Some(job) = jobs.iter_mut().find(|job| job.id == id) else {
+                continue;
+            };
+            job.last_run_at = Some(now);
+            let retry = error.is_some() && job.attempts + 1 < MAX_ATTEMPTS;
+            job.last_error = error;
+            if retry {
+                job.attempts += 1;
+                job.next_attempt_at = now + backoff(job.attempts);
+            } else if let Some(hours) = job.repeat_hours {
+                job.attempts = 0;
+                job.next_attempt_at = now + chrono::Duration::hours(i64::from(hours));
+            } else {
+                finished.push(id);
+            }
+        }
+        jobs.retain(|job| !finished.contains(&job.id));
+        self.save(&jobs)?;
+        Ok(summary)
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, Vec<Job>> {
+        self.jobs.lock().expect("job queue lock poisoned")
+    }
+
+    fn save(&self, jobs: &[Job]) -> Result<(), JobQueueError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(jobs)?)?;
+        Ok(())
+    }
+}
+
+fn new_job(
+    jobs: &[Job],
+    kind: JobKind,
+    now: DateTime<Utc>,
+    first_run: DateTime<Utc>,
+    repeat_hours: Option<u32>,
+) -> Job {
+    Job {
+        id: jobs.iter().map(|job| job.id + 1).max().unwrap_or(1),
+        kind,
+        created_at: now,
+        next_attempt_at: first_run,
+        attempts: 0,
+        last_error: None,
+        repeat_hours,
+        last_run_at: None,
+    }
+}
+
+/// Wait before retrying a job that has failed `attempts` times.
+pub fn backoff(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    BASE_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+/// Whether the network is reachable, by opening a TCP connection. Always
+/// false in offline mode, so queued jobs wait until it is turned off.
+pub fn probe() -> bool {
+    let (ip, port) = PROBE_ADDR;
+    network::connect(SocketAddr::from((ip, port)), PROBE_TIMEOUT).is_ok()
+}
+
+/// Every [`CHECK_INTERVAL`] while jobs are queued, runs the due ones.
+/// While network jobs are queued it first probes the network and emits
+/// [`CONNECTIVITY_EVENT`] when it goes away or comes back; nothing is
+/// probed otherwise, or while offline mode is on. Scheduled exports emit
+/// [`EXPORT_FINISHED_EVENT`] after each run.
+pub fn spawn_monitor<R: Runtime>(app: AppHandle<R>) {
+    std::thread::spawn(move || loop {
+        let state = app.state::<AppState>();
+        if state.jobs.has_pending() {
+            if !network::offline_mode() && state.jobs.has_pending_network() {
+                let online = probe();
+                if state.jobs.set_online(online) {
+                    let change = ConnectivityChange {
+                        online,
+                        pending: state.jobs.pending().len(),
+                    };
+                    if let Err(err) = app.emit(CONNECTIVITY_EVENT, change) {
+                        tracing::error!(?err, "failed to emit connectivity-changed event");
+                    }
+                }
+            }
+            let now = state.clock().now();
+            let result = state.jobs.run_due(now, |job| {
+                let result = state.run_job(job);
+                if job.kind == JobKind::ScheduledExport {
+                    let run = ExportRun::new(now, &result);
+                    if let Err(err) = app.emit(EXPORT_FINISHED_EVENT, run) {
+                        tracing::error!(?err, "failed to emit scheduled-export-finished event");
+                    }
+                }
+                result
+            });
+            if let Err(err) = result {
+                tracing::warn!(%err, "failed to save job queue");
+            }
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn jobs_persist_and_dedupe() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(JOBS_FILE);
+        let queue = JobQueue::load(Some(path.clone()));
+        assert!(!queue.has_pending());
+        let first = queue.enqueue(JobKind::PublishNowPage, at(0)).unwrap();
+        let again = queue.enqueue(JobKind::PublishNowPage, at(5)).unwrap();
+        assert_eq!(first, again);
+        let url = JobKind::LinkTitle {
+            url: "https://example.com".to_string(),
+        };
+        assert_eq!(queue.enqueue(url.clone(), at(5)).unwrap().id, 2);
+
+        let reloaded = JobQueue::load(Some(path));
+        let kinds: Vec<JobKind> = reloaded.pending().into_iter().map(|job| job.kind).collect();
+        assert_eq!(kinds, vec![JobKind::PublishNowPage, url]);
+    }
+
+    #[test]
+    fn run_due_retries_with_backoff_and_waits_while_offline() {
+        let queue = JobQueue::load(None);
+        queue.enqueue(JobKind::PublishNowPage, at(0)).unwrap();
+
+        let summary = queue
+            .run_due(at(0), |_| Err(JobError::Failed("push rejected".into())))
+            .unwrap();
+        assert_eq!(summary.retrying, 1);
+        let job = &queue.pending()[0];
+        assert_eq!((job.attempts, job.next_attempt_at), (1, at(30)));
+        assert_eq!(job.last_error.as_deref(), Some("push rejected"));
+
+        let mut calls = 0;
+        queue
+            .run_due(at(10), |_| {
+                calls += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(calls, 0, "not due yet");
+
+        queue.run_due(at(30), |_| Err(JobError::Offline)).unwrap();
+        assert!(!queue.is_online());
+        assert_eq!(queue.pending()[0].attempts, 1);
+        assert_eq!(queue.run_due(at(40), |_| Ok(())).unwrap().completed, 0);
+
+        assert!(queue.set_online(true));
+        assert!(!queue.set_online(true));
+        assert_eq!(queue.run_due(at(40), |_| Ok(())).unwrap().completed, 1);
+        assert!(!queue.has_pending());
+
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(30), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn repeating_jobs_run_while_offline_and_stay_queued() {
+        let queue = JobQueue::load(None);
+        let export = JobKind::ScheduledExport;
+        queue.enqueue(JobKind::PublishNowPage, at(0)).unwrap();
+        queue.schedule(export.clone(), at(0), at(0), 24).unwrap();
+        queue.set_online(false);
+
+        let mut ran = Vec::new();
+        let summary = queue
+            .run_due(at(0), |job| {
+                ran.push(job.kind.clone());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(ran, [export.clone()]);
+        assert_eq!(summary.completed, 1);
+        let job = queue.find(&export).unwrap();
+        assert_eq!(job.next_attempt_at, at(24 * 3600));
+        assert_eq!(job.last_run_at, Some(at(0)));
+
+        // Failures back off, then wait for the next run instead of dropping
+        // the job.
+        let mut now = job.next_attempt_at;
+        for _ in 0..MAX_ATTEMPTS {
+            now = queue.find(&export).unwrap().next_attempt_at;
+            queue
+                .run_due(now, |_| Err(JobError::Failed("disk full".into())))
+                .unwrap();
+        }
+        let job = queue.find(&export).unwrap();
+        assert_eq!(job.attempts, 0);
+        assert_eq!(job.last_error.as_deref(), Some("disk full"));
+        assert_eq!(job.next_attempt_at, now + chrono::Duration::hours(24));
+
+        let job = queue.schedule(export.clone(), now, now, 24 * 7).unwrap();
+        assert_eq!(job.last_run_at, Some(now));
+        assert_eq!(queue.pending().len(), 2);
+        assert!(queue.cancel(&export).unwrap());
+        assert!(!queue.cancel(&export).unwrap());
+        assert!(queue.has_pending_network());
+    }
+}
@@ -0,0 +1,211 @@
+//! Each block's language, and the word stems text search matches in it,
+//! so "running" finds "runs" in English and "Häuser" finds "Haus" in
+//! German. Like [`crate::terms`], analyses are cached by the hash a
+//! block's summary holds, so a block is only analyzed again after an edit.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_segmentation::UnicodeSegmentation;
+use whatlang::Lang;
+
+/// Text with fewer letters than this is too short to tell its language.
+const MIN_DETECT_LETTERS: usize = 20;
+
+/// The language of `text`, when it's long enough to tell reliably.
+pub fn detect(text: &str) -> Option<Lang> {
+    if text.chars().filter(|ch| ch.is_alphabetic()).count() < MIN_DETECT_LETTERS {
+        return None;
+    }
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang())
+}
+
+fn stemmer(language: Lang) -> Option<Stemmer> {
+    let algorithm = match language {
+        Lang::Ara => Algorithm::Arabic,
+        Lang::Dan => Algorithm::Danish,
+        Lang::Nld => Algorithm::Dutch,
+        Lang::Eng => Algorithm::English,
+        Lang::Fin => Algorithm::Finnish,
+        Lang::Fra => Algorithm::French,
+        Lang::Deu => Algorithm::German,
+        Lang::Ell => Algorithm::Greek,
+        Lang::Hun => Algorithm::Hungarian,
+        Lang::Ita => Algorithm::Italian,
+        Lang::Nob => Algorithm::Norwegian,
+        Lang::Por => Algorithm::Portuguese,
+        Lang::Ron => Algorithm::Romanian,
+        Lang::Rus => Algorithm::Russian,
+        Lang::Spa => Algorithm::Spanish,
+        Lang::Swe => Algorithm::Swedish,
+        Lang::Tam => Algorithm::Tamil,
+        Lang::Tur => Algorithm::Turkish,
+        _ => return None,
+    };
+    Some(Stemmer::create(algorithm))
+}
+
+/// The words of `text` with their stems in `language`. Words are split on
+/// Unicode word boundaries, which also splits Chinese and Japanese text
+/// into single characters; languages without a stemmer, or text of no
+/// known language, keep the lowercased word as its stem.
+pub fn stemmed_words(text: &str, language: Option<Lang>) -> Vec<(&str, String)> {
+    let stemmer = language.and_then(stemmer);
+    text.unicode_words()
+        .map(|word| {
+            let lower = word.to_lowercase();
+            let stem = match &stemmer {
+                Some(stemmer) => match stemmer.stem(&lower) {
+                    Cow::Borrowed(stem) => stem.to_string(),
+                    Cow::Owned(stem) => stem,
+                },
+                None => lower,
+            };
+            (word, stem)
+        })
+        .collect()
+}
+
+/// The distinct stems of `query` in `language`, for
+/// [`BlockAnalysis::find`].
+pub fn query_stems(query: &str, language: Option<Lang>) -> Vec<String> {
+    let mut stems: Vec<String> = stemmed_words(query, language)
+        .into_iter()
+        .map(|(_, stem)| stem)
+        .collect();
+    stems.sort_unstable();
+    stems.dedup();
+    stems
+}
+
+#[derive(Debug)]
+pub struct BlockAnalysis {
+    pub language: Option<Lang>,
+    stems: HashSet<String>,
+}
+
+impl BlockAnalysis {
+    pub fn new(text: &str) -> Self {
+        let language = detect(text);
+        Self {
+            language,
+            stems: stemmed_words(text, language)
+                .into_iter()
+                .map(|(_, stem)| stem)
+                .collect(),
+        }
+    }
+
+    /// Where `text`, the analyzed block's, has every one of `stems`: the
+    /// first line with one of them, the first such word on it, and how
+    /// many words in the block have one.
+    pub fn find<'a>(&self, text: &'a str, stems: &[String]) -> Option<(&'a str, &'a str, usize)> {
+        if stems.is_empty() || !stems.iter().all(|stem| self.stems.contains(stem)) {
+            return None;
+        }
+        let mut first = None;
+        let mut occurrences = 0;
+        for line in text.lines() {
+            for (word, stem) in stemmed_words(line, self.language) {
+                if stems.contains(&stem) {
+                    first.get_or_insert((line, word));
+                    occurrences += 1;
+                }
+            }
+        }
+        let (line, word) = first?;
+        Some((line, word, occurrences))
+    }
+}
+
+/// Analyses of recently seen blocks, keyed by
+/// [`crate::timeline::TimelineSummary::fingerprint`].
+#[derive(Debug, Default)]
+pub struct AnalysisCache {
+    by_block: Mutex<HashMap<u64, Arc<BlockAnalysis>>>,
+}
+
+impl AnalysisCache {
+    /// The analysis of the block keyed `key` with `text`, from the cache
+    /// when it has been seen before.
+    pub fn get(&self, key: u64, text: &str) -> Arc<BlockAnalysis> {
+        let mut by_block = self.by_block.lock().expect("analysis cache lock poisoned");
+        by_block
+            .entry(key)
+            .or_insert_with(|| Arc::new(BlockAnalysis::new(text)))
+            .clone()
+    }
+
+    /// Analyses of each of `blocks`, given as key and text, in order.
+    /// Afterwards the cache holds only these blocks, dropping those since
+    /// edited or deleted.
+    pub fn all<'a>(
+        &self,
+        blocks: impl IntoIterator<Item = (u64, &'a str)>,
+    ) -> Vec<Arc<BlockAnalysis>> {
+        let mut by_block = self.by_block.lock().expect("analysis cache lock poisoned");
+        let mut kept = HashMap::with_capacity(by_block.len());
+        let analyses = blocks
+            .into_iter()
+            .map(|(key, text)| {
+                let analysis = by_block
+                    .remove(&key)
+                    .or_else(|| kept.get(&key).cloned())
+                    .unwrap_or_else(|| Arc::new(BlockAnalysis::new(text)));
+                kept.insert(key, analysis.clone());
+                analysis
+            })
+            .collect();
+        *by_block = kept;
+        analyses
+    }
+}
+
+/// Clones start empty, like [`crate::terms::TermCache`].
+impl Clone for AnalysisCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_stemmed_in_their_own_language() {
+        let english =
+            "We went running before work, and the dog runs ahead of us along the river path.";
+        let analysis = BlockAnalysis::new(english);
+        assert_eq!(analysis.language, Some(Lang::Eng));
+        let stems = query_stems("Running", analysis.language);
+        assert_eq!(
+            analysis.find(english, &stems),
+            Some((english, "running", 2))
+        );
+        assert_eq!(
+            analysis.find(english, &query_stems("running cats", analysis.language)),
+            None
+        );
+
+        let german = "Die Häuser am Fluss sind alt, aber jedes Haus hat einen schönen Garten.";
+        let analysis = BlockAnalysis::new(german);
+        assert_eq!(analysis.language, Some(Lang::Deu));
+        let stems = query_stems("haus", analysis.language);
+        assert_eq!(analysis.find(german, &stems), Some((german, "Häuser", 2)));
+    }
+
+    #[test]
+    fn short_text_has_no_language() {
+        assert_eq!(detect("ran late"), None);
+        let analysis = BlockAnalysis::new("ran late");
+        assert_eq!(
+            analysis.find("ran late", &query_stems("RAN", None)),
+            Some(("ran late", "ran", 1))
+        );
+    }
+}
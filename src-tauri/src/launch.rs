@@ -0,0 +1,164 @@
+//! Launch options read from the command line and environment at startup.
+
+use std::env;
+use std::path::PathBuf;
+
+const SAFE_MODE_FLAG: &str = "--safe-mode";
+const SAFE_MODE_ENV: &str = "SIGHTLINE_SAFE_MODE";
+const PROFILE_FLAG: &str = "--profile";
+const PROFILE_ENV: &str = "SIGHTLINE_PROFILE";
+const DATA_DIR_FLAG: &str = "--data-dir";
+const TIMELINE_PATH_FLAG: &str = "--timeline-path";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LaunchOptions {
+    /// Start with only the core timeline: no chat backend, integrations, or
+    /// background listeners.
+    pub safe_mode: bool,
+    /// Named profile whose data lives in its own config directory, separate
+    /// from the default journal.
+    pub profile: Option<String>,
+    /// Overrides where workspaces are kept; see [`crate::paths`].
+    pub data_dir: Option<PathBuf>,
+    /// Overrides the timeline file itself.
+    pub timeline_path: Option<PathBuf>,
+}
+
+impl LaunchOptions {
+    pub fn from_env() -> Self {
+        Self::parse(env::args().skip(1), |key| env::var(key).ok())
+    }
+
+    fn parse<I, S, F>(args: I, var: F) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut safe_mode = false;
+        let mut profile = None;
+        let mut data_dir = None;
+        let mut timeline_path = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            if arg == SAFE_MODE_FLAG {
+                safe_mode = true;
+            } else if arg == PROFILE_FLAG {
+                profile = args.next().map(|value| value.as_ref().to_string());
+            } else if let Some(value) = arg.strip_prefix("--profile=") {
+                profile = Some(value.to_string());
+            } else if arg == DATA_DIR_FLAG {
+                data_dir = args.next().map(|value| PathBuf::from(value.as_ref()));
+            } else if let Some(value) = arg.strip_prefix("--data-dir=") {
+                data_dir = Some(PathBuf::from(value));
+            } else if arg == TIMELINE_PATH_FLAG {
+                timeline_path = args.next().map(|value| PathBuf::from(value.as_ref()));
+            } else if let Some(value) = arg.strip_prefix("--timeline-path=") {
+                timeline_path = Some(PathBuf::from(value));
+            }
+        }
+
+        safe_mode |= var(SAFE_MODE_ENV)
+            .as_deref()
+            .map(is_truthy)
+            .unwrap_or(false);
+        let profile = profile.or_else(|| var(PROFILE_ENV)).and_then(|name| {
+            if is_valid_profile_name(&name) {
+                Some(name)
+            } else {
+                tracing::warn!(profile = %name, "ignoring invalid profile name");
+                None
+            }
+        });
+
+        Self {
+            safe_mode,
+            profile,
+            data_dir,
+            timeline_path,
+        }
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Profile names become directory names, so only allow characters that are
+/// safe in a single path component on every platform.
+fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn safe_mode_defaults_to_off() {
+        let options = LaunchOptions::parse(Vec::<String>::new(), no_env);
+        assert!(!options.safe_mode);
+        assert_eq!(options.profile, None);
+    }
+
+    #[test]
+    fn safe_mode_enabled_by_flag() {
+        let options = LaunchOptions::parse(["--safe-mode"], no_env);
+        assert!(options.safe_mode);
+    }
+
+    #[test]
+    fn safe_mode_enabled_by_truthy_env() {
+        let env_with = |value: &'static str| {
+            move |key: &str| (key == SAFE_MODE_ENV).then(|| value.to_string())
+        };
+        assert!(LaunchOptions::parse(Vec::<String>::new(), env_with("1")).safe_mode);
+        assert!(LaunchOptions::parse(Vec::<String>::new(), env_with("TRUE")).safe_mode);
+        assert!(!LaunchOptions::parse(Vec::<String>::new(), env_with("0")).safe_mode);
+    }
+
+    #[test]
+    fn profile_read_from_flag_before_env() {
+        let env = |key: &str| (key == PROFILE_ENV).then(|| "from-env".to_string());
+
+        let options = LaunchOptions::parse(["--profile", "dev"], env);
+        assert_eq!(options.profile.as_deref(), Some("dev"));
+
+        let options = LaunchOptions::parse(["--profile=qa_1"], no_env);
+        assert_eq!(options.profile.as_deref(), Some("qa_1"));
+
+        let options = LaunchOptions::parse(Vec::<String>::new(), env);
+        assert_eq!(options.profile.as_deref(), Some("from-env"));
+    }
+
+    #[test]
+    fn path_flags_take_a_value_either_way() {
+        let options = LaunchOptions::parse(
+            ["--data-dir", "/data", "--timeline-path=/elsewhere/t.json"],
+            no_env,
+        );
+        assert_eq!(options.data_dir, Some(PathBuf::from("/data")));
+        assert_eq!(
+            options.timeline_path,
+            Some(PathBuf::from("/elsewhere/t.json"))
+        );
+    }
+
+    #[test]
+    fn profile_rejects_path_like_names() {
+        let options = LaunchOptions::parse(["--profile", "../escape"], no_env);
+        assert_eq!(options.profile, None);
+    }
+}
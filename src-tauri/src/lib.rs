@@ -1,25 +1,403 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
 pub mod api;
+pub mod attachments;
+pub mod bundle;
 pub mod chat;
+pub mod clock;
+pub mod day_index;
+pub mod day_metrics;
+pub mod digest;
+pub mod edit_locks;
+pub mod encoding;
+pub mod export_schedule;
+pub mod flashcards;
+pub mod html;
+pub mod ical;
+pub mod important_dates;
+pub mod jobs;
+pub mod language;
+pub mod launch;
+pub mod link_titles;
+pub mod logging;
+pub mod markdown;
+pub mod network;
+pub mod now_page;
+pub mod obsidian;
+pub mod pagination;
+pub mod passphrase;
+pub mod paths;
+pub mod perf;
+pub mod persistence;
+pub mod rollover;
+pub mod search;
+pub mod snippets;
+pub mod sqlite_snapshot;
+pub mod streaks;
+pub mod streams;
 mod tag_palette;
+pub mod tag_query;
+pub mod tasks;
+pub mod terms;
+pub mod thumbnails;
 pub mod timeline;
+pub mod vault;
+pub mod wal;
+pub mod workspace;
+pub mod writing_stats;
+pub mod year_review;
 
 pub struct AppState {
     timeline: Mutex<timeline::Timeline>,
+    paths: Option<paths::AppPaths>,
+    profile: Option<String>,
+    sensitive_unlocked: AtomicBool,
+    passphrase: passphrase::Passphrase,
+    link_titles: link_titles::LinkTitles,
+    /// The now page as last written, so saves only touch the file when the
+    /// `#now` blocks change.
+    now_page: Mutex<Option<String>>,
+    /// Network work waiting for connectivity or a retry.
+    jobs: jobs::JobQueue,
+    digest_schedule: Mutex<Option<digest::DigestSchedule>>,
+    search_history: search::SearchHistory,
+    persistence: Mutex<persistence::PersistencePolicy>,
+    perf: perf::PerfMetrics,
+    streams: streams::Streams,
+    edit_locks: edit_locks::EditLocks,
+    clock: clock::SharedClock,
+    /// The day the session is on, to notice when it changes.
+    day: rollover::DayWatch,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let timeline = timeline::Timeline::load().unwrap_or_default();
+        Self::with_options(&launch::LaunchOptions::default())
+    }
+
+    pub fn with_options(options: &launch::LaunchOptions) -> Self {
+        Self::with_clock(options, clock::SharedClock::default())
+    }
+
+    /// State whose date-dependent behaviour reads `clock` instead of the
+    /// system time.
+    pub fn with_clock(options: &launch::LaunchOptions, clock: clock::SharedClock) -> Self {
+        let paths = paths::AppPaths::resolve(options).ok();
+        let mut timeline = paths
+            .as_ref()
+            .and_then(|paths| timeline::Timeline::load_from_path(&paths.timeline).ok())
+            .unwrap_or_default();
+        timeline.set_clock(clock.clone());
+        network::set_offline_mode(timeline.offline_mode());
+        let search_history =
+            search::SearchHistory::load(paths.as_ref().map(|paths| paths.search_history.clone()));
+        let legacy_history = timeline.take_legacy_search_history();
+        if !legacy_history.is_empty() && !search_history.is_saved() {
+            if let Err(err) = search_history.replace(legacy_history) {
+                tracing::warn!(%err, "failed to move search history out of the timeline");
+            }
+        }
+        let jobs = jobs::JobQueue::load(paths.as_ref().map(|paths| paths.jobs.clone()));
+        if let Err(err) = sync_export_job(&jobs, timeline.export_schedule(), clock.now()) {
+            tracing::warn!(%err, "failed to schedule exports");
+        }
+        let digest_schedule = paths
+            .as_ref()
+            .and_then(|paths| digest::DigestSchedule::load(&paths.digest_schedule));
+        if let Err(err) = sync_digest_job(&jobs, digest_schedule.as_ref(), clock.local_now()) {
+            tracing::warn!(%err, "failed to schedule the weekly digest");
+        }
         Self {
             timeline: Mutex::new(timeline),
+            profile: options.profile.clone(),
+            sensitive_unlocked: AtomicBool::new(false),
+            passphrase: passphrase::Passphrase::load(
+                paths.as_ref().map(|paths| paths.passphrase.clone()),
+            ),
+            link_titles: link_titles::LinkTitles::default(),
+            now_page: Mutex::new(None),
+            jobs,
+            digest_schedule: Mutex::new(digest_schedule),
+            search_history,
+            persistence: Mutex::new(
+                paths
+                    .as_ref()
+                    .map(|paths| persistence::PersistencePolicy::load(&paths.persistence))
+                    .unwrap_or_default(),
+            ),
+            paths,
+            perf: perf::PerfMetrics::default(),
+            streams: streams::Streams::default(),
+            edit_locks: edit_locks::EditLocks::default(),
+            day: rollover::DayWatch::new(clock.today()),
+            clock,
         }
     }
 
+    pub fn clock(&self) -> &clock::SharedClock {
+        &self.clock
+    }
+
     pub fn get_timeline(&self) -> std::sync::MutexGuard<'_, timeline::Timeline> {
         self.timeline.lock().expect("timeline lock poisoned")
     }
+
+    pub fn set_sensitive_unlocked(&self, unlocked: bool) {
+        self.sensitive_unlocked.store(unlocked, Ordering::SeqCst);
+    }
+
+    /// Sensitive text is only included when the caller asks for it and the
+    /// session has been unlocked; otherwise it is masked.
+    pub fn sensitive_content(&self, include_sensitive: Option<bool>) -> timeline::SensitiveContent {
+        if include_sensitive.unwrap_or(false) && self.sensitive_unlocked.load(Ordering::SeqCst) {
+            timeline::SensitiveContent::Included
+        } else {
+            timeline::SensitiveContent::Masked
+        }
+    }
+
+    pub fn persistence_policy(&self) -> persistence::PersistencePolicy {
+        self.persistence
+            .lock()
+            .expect("persistence policy lock poisoned")
+            .clone()
+    }
+
+    /// Saves `policy` for this workspace and applies it from the next save.
+    pub fn set_persistence_policy(
+        &self,
+        policy: persistence::PersistencePolicy,
+    ) -> Result<(), timeline::TimelinePersistenceError> {
+        policy.save(&self.paths()?.persistence)?;
+        *self
+            .persistence
+            .lock()
+            .expect("persistence policy lock poisoned") = policy;
+        Ok(())
+    }
+
+    /// Writes the snapshot and takes a backup if the persistence policy
+    /// says one is due. Ephemeral workspaces write nothing.
+    pub fn save_timeline(
+        &self,
+        timeline: &timeline::Timeline,
+    ) -> Result<(), timeline::TimelinePersistenceError> {
+        let policy = self.persistence_policy();
+        if policy.ephemeral {
+            return Ok(());
+        }
+        let paths = self.paths()?;
+        timeline.save_to_path(&paths.timeline)?;
+        if let Err(err) = policy.back_up(&paths.timeline, &paths.backups, self.clock.now()) {
+            tracing::warn!(%err, dir = %paths.backups.display(), "failed to back up timeline");
+        }
+        self.refresh_now_page(timeline);
+        Ok(())
+    }
+
+    /// Saves `timeline` and copies the snapshot into the backups directory
+    /// whatever the backup interval, before an import replaces it.
+    pub fn back_up_timeline(
+        &self,
+        timeline: &timeline::Timeline,
+    ) -> Result<Option<PathBuf>, timeline::TimelinePersistenceError> {
+        let policy = self.persistence_policy();
+        if policy.ephemeral {
+            return Ok(None);
+        }
+        self.save_timeline(timeline)?;
+        let paths = self.paths()?;
+        Ok(policy.back_up_now(&paths.timeline, &paths.backups, self.clock.now())?)
+    }
+
+    /// Appends an applied edit to the write-ahead log rather than rewriting
+    /// the snapshot, compacting the log into a snapshot once it passes the
+    /// persistence policy's size or a backup is due. Without the log every
+    /// edit saves the snapshot.
+    pub fn log_edit(
+        &self,
+        timeline: &timeline::Timeline,
+        entry: &wal::LogEntry,
+    ) -> Result<(), timeline::TimelinePersistenceError> {
+        let policy = self.persistence_policy();
+        if entry.ops.is_empty() || policy.ephemeral {
+            return Ok(());
+        }
+        let paths = self.paths()?;
+        let path = &paths.timeline;
+        if !policy.write_ahead_log
+            || !path.exists()
+            || wal::append(&wal::log_path(path), entry)? >= policy.compact_bytes
+        {
+            return self.save_timeline(timeline);
+        }
+        // Backups copy the snapshot, which the log has moved past.
+        match policy.backup_due(path, &paths.backups, self.clock.now()) {
+            Ok(true) => return self.save_timeline(timeline),
+            Ok(false) => {}
+            Err(err) => {
+                tracing::warn!(%err, dir = %paths.backups.display(), "failed to check backups")
+            }
+        }
+        self.refresh_now_page(timeline);
+        Ok(())
+    }
+
+    /// Rewrites the configured now page when its rendering has changed.
+    /// Failures are logged rather than failing the save; pushing to git is
+    /// left to an explicit publish.
+    fn refresh_now_page(&self, timeline: &timeline::Timeline) {
+        let Some(config) = timeline.now_page() else {
+            return;
+        };
+        let (page, _) = now_page::render(timeline, config.format);
+        let mut last = self.now_page.lock().expect("now page lock poisoned");
+        if last.as_deref() == Some(page.as_str()) {
+            return;
+        }
+        match now_page::write_page(&config.path, &page) {
+            Ok(_) => *last = Some(page),
+            Err(err) => {
+                tracing::warn!(%err, path = %config.path.display(), "failed to write now page")
+            }
+        }
+    }
+
+    /// Whether network features may run now: offline mode is off and the
+    /// last connectivity probe succeeded.
+    pub fn network_available(&self) -> bool {
+        !network::offline_mode() && self.jobs.is_online()
+    }
+
+    /// Handles the date changing since the last check by creating the
+    /// daily note if one is configured. Jobs the new day made due are left
+    /// to the job monitor. Returns `None` while the day is unchanged.
+    pub fn roll_over(&self) -> Option<rollover::DayChange> {
+        let today = self.clock.today();
+        let previous = self.day.observe(today)?;
+        let mut timeline = self.get_timeline();
+        let note = timeline.daily_note_op();
+        let now = std::time::Instant::now();
+        let created = match self.edit_locks.check(note.as_slice(), None, now) {
+            Ok(()) => timeline.create_daily_note(),
+            // Left for `start_day` once the writer is done.
+            Err(err) => {
+                tracing::warn!(%err, "daily note would land inside a locked range");
+                Ok(None)
+            }
+        };
+        let daily_note = match created {
+            Ok(Some(position)) => {
+                self.edit_locks.apply(note.as_slice(), None, now);
+                if let Err(err) = self.save_timeline(&timeline) {
+                    tracing::warn!(%err, "failed to save daily note");
+                }
+                Some(position)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                tracing::warn!(%err, "failed to create daily note");
+                None
+            }
+        };
+        Some(rollover::DayChange {
+            previous,
+            today,
+            daily_note,
+        })
+    }
+
+    /// Runs one queued job for [`jobs::JobQueue::run_due`].
+    pub fn run_job(&self, job: &jobs::Job) -> Result<(), jobs::JobError> {
+        match &job.kind {
+            jobs::JobKind::PublishNowPage => {
+                let timeline = self.get_timeline().clone();
+                let config = timeline.now_page().cloned().ok_or_else(|| {
+                    jobs::JobError::Fatal("no now page is configured".to_string())
+                })?;
+                match now_page::publish(&timeline, &config) {
+                    Ok(_) => Ok(()),
+                    Err(now_page::NowPageError::Network(
+                        network::NetworkError::NetworkDisabled,
+                    )) => Err(jobs::JobError::Offline),
+                    Err(err) => Err(jobs::JobError::Failed(err.to_string())),
+                }
+            }
+            // Warms the cache. A page without a title is an answer too;
+            // failed fetches aren't cached, so they are retried.
+            jobs::JobKind::LinkTitle { url } => match self.link_titles.lookup(url) {
+                Ok(_) => Ok(()),
+                Err(network::NetworkError::NetworkDisabled) => Err(jobs::JobError::Offline),
+                // The server answered; asking again won't change it.
+                Err(network::NetworkError::Http(err)) if err.is_status() => {
+                    Err(jobs::JobError::Fatal(err.to_string()))
+                }
+                Err(err) => Err(jobs::JobError::Failed(err.to_string())),
+            },
+            jobs::JobKind::ScheduledExport => {
+                let timeline = self.get_timeline().clone();
+                let schedule = timeline.export_schedule().ok_or_else(|| {
+                    jobs::JobError::Fatal("no export schedule is configured".to_string())
+                })?;
+                timeline
+                    .export_markdown(
+                        &schedule.dir,
+                        schedule.tag_rendering,
+                        timeline::SensitiveContent::Masked,
+                    )
+                    .map(|_| ())
+                    .map_err(|err| jobs::JobError::Failed(err.to_string()))
+            }
+            // Reviews the week before the one the send falls in, so a
+            // digest sent late still covers last week.
+            jobs::JobKind::WeeklyDigest => {
+                let schedule = self.digest_schedule().ok_or_else(|| {
+                    jobs::JobError::Fatal("no digest schedule is configured".to_string())
+                })?;
+                let now = self.clock.local_now();
+                let last_week = now.date_naive() - chrono::Duration::weeks(1);
+                let weekly = digest::weekly(&self.get_timeline(), last_week);
+                match schedule.deliver(&weekly, now.fixed_offset()) {
+                    Ok(()) => Ok(()),
+                    Err(digest::DigestError::Network(network::NetworkError::NetworkDisabled)) => {
+                        Err(jobs::JobError::Offline)
+                    }
+                    Err(err) => Err(jobs::JobError::Failed(err.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Who gets the weekly digest and how, if anyone.
+    pub fn digest_schedule(&self) -> Option<digest::DigestSchedule> {
+        self.digest_schedule
+            .lock()
+            .expect("digest schedule lock poisoned")
+            .clone()
+    }
+
+    /// Where this session's files live, if they could be resolved.
+    pub fn paths(&self) -> Result<&paths::AppPaths, timeline::TimelinePersistenceError> {
+        self.paths
+            .as_ref()
+            .ok_or(timeline::TimelinePersistenceError::MissingConfigDir)
+    }
+
+    pub fn assets_dir(&self) -> Result<PathBuf, timeline::TimelinePersistenceError> {
+        Ok(self.paths()?.assets.clone())
+    }
+
+    pub fn thumbnail_cache(
+        &self,
+    ) -> Result<thumbnails::ThumbnailCache, timeline::TimelinePersistenceError> {
+        let paths = self.paths()?;
+        Ok(thumbnails::ThumbnailCache::new(
+            paths.assets.clone(),
+            paths.thumbnails.clone(),
+        ))
+    }
 }
 
 impl Default for AppState {
@@ -28,16 +406,71 @@ impl Default for AppState {
     }
 }
 
+/// Queues the runs of `schedule`, or cancels them when there is none. A
+/// queued run at the same interval is kept, so a restart doesn't move it.
+fn sync_export_job(
+    jobs: &jobs::JobQueue,
+    schedule: Option<&export_schedule::ExportSchedule>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), jobs::JobQueueError> {
+    let kind = jobs::JobKind::ScheduledExport;
+    let Some(schedule) = schedule else {
+        jobs.cancel(&kind)?;
+        return Ok(());
+    };
+    let hours = schedule.every.hours();
+    if jobs
+        .find(&kind)
+        .is_none_or(|job| job.repeat_hours != Some(hours))
+    {
+        jobs.schedule(kind, now, now, hours)?;
+    }
+    Ok(())
+}
+
+/// Queues the weekly digest for next Monday morning, or cancels it when
+/// there is no schedule. A queued digest is kept, so a restart doesn't
+/// move it.
+fn sync_digest_job(
+    jobs: &jobs::JobQueue,
+    schedule: Option<&digest::DigestSchedule>,
+    now: chrono::DateTime<chrono::Local>,
+) -> Result<(), jobs::JobQueueError> {
+    let kind = jobs::JobKind::WeeklyDigest;
+    if schedule.is_none() {
+        jobs.cancel(&kind)?;
+    } else if jobs.find(&kind).is_none() {
+        jobs.schedule(
+            kind,
+            now.to_utc(),
+            digest::next_send_time(&now),
+            digest::WEEK_HOURS,
+        )?;
+    }
+    Ok(())
+}
+
 pub mod commands {
     use super::*;
     use chrono::NaiveDate;
     use serde::Serialize;
-    use tauri::State;
+    use std::collections::BTreeMap;
+    use tauri::{Emitter, State};
 
     #[tauri::command]
     pub fn entry_count(state: State<AppState>) -> Result<usize, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.entry_count())
+        state.perf.measure("entry_count", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.entry_count())
+        })
+    }
+
+    #[tauri::command]
+    pub fn word_count(state: State<AppState>) -> Result<usize, String> {
+        state.perf.measure("word_count", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.word_count())
+        })
     }
 
     #[tauri::command]
@@ -45,29 +478,173 @@ pub mod commands {
         state: State<AppState>,
         payload: api::EditPayload,
     ) -> Result<api::EditResponse, String> {
-        let mut timeline = state.get_timeline();
-        let api::EditPayload { base_version, ops } = payload;
+        state.perf.measure("handle_edit", || {
+            let mut timeline = state.get_timeline();
+            let api::EditPayload {
+                base_version,
+                ops,
+                literal,
+                lock_token,
+                offsets,
+            } = payload;
+            // Offsets only make sense against the version they were made
+            // on; a stale edit is left for apply_edit to report as a conflict.
+            let ops = if base_version == timeline.version() {
+                timeline
+                    .ops_in_chars(&ops, offsets)
+                    .map_err(|err| err.to_string())?
+            } else {
+                ops
+            };
 
-        match timeline.apply_ops(base_version, &ops) {
-            Ok(new_version) => {
-                if let Err(err) = timeline.save() {
-                    tracing::warn!(?err, "failed to save timeline after edit");
+            let now = std::time::Instant::now();
+            match state.edit_locks.check(&ops, lock_token.as_deref(), now) {
+                Ok(()) => {}
+                // Locks are held in timeline offsets; clients see the document.
+                Err(edit_locks::LockError::Locked {
+                    owner,
+                    start_position,
+                    end_position,
+                    retry_after_ms,
+                }) => {
+                    return Ok(api::EditResponse::Locked {
+                        server_version: timeline.version(),
+                        owner,
+                        start_position: timeline.document_offset(start_position),
+                        end_position: timeline.document_offset(end_position),
+                        retry_after_ms,
+                    })
                 }
-                Ok(api::EditResponse::Ok { new_version })
+                Err(err) => return Err(err.to_string()),
             }
-            Err(timeline::ApplyOpsError::VersionMismatch { expected, .. }) => {
-                Ok(api::EditResponse::Conflict {
-                    server_version: expected,
-                })
+
+            match timeline.apply_edit(base_version, &ops, literal) {
+                Ok(applied) => {
+                    state
+                        .edit_locks
+                        .apply(&applied.log.ops, lock_token.as_deref(), now);
+                    if let Err(err) = state.log_edit(&timeline, &applied.log) {
+                        tracing::warn!(?err, "failed to log edit");
+                    }
+                    Ok(api::EditResponse::Ok {
+                        new_version: applied.version,
+                        rewritten: applied.rewritten,
+                    })
+                }
+                Err(timeline::ApplyOpsError::VersionMismatch { expected, .. }) => {
+                    Ok(api::EditResponse::Conflict {
+                        server_version: expected,
+                    })
+                }
+                Err(timeline::ApplyOpsError::Frozen { start, end, .. }) => {
+                    Ok(api::EditResponse::Frozen {
+                        server_version: timeline.version(),
+                        start_position: timeline.document_offset(start),
+                        end_position: timeline.document_offset(end),
+                    })
+                }
+                Err(err) => Err(err.to_string()),
             }
-            Err(err) => Err(err.to_string()),
+        })
+    }
+
+    /// Locks `start_position..end_position` for `owner` so other writers
+    /// can't edit inside it until it's released or expires. Pass the token
+    /// with edits to write inside the range.
+    #[tauri::command]
+    pub fn acquire_edit_lock(
+        state: State<AppState>,
+        owner: String,
+        start_position: usize,
+        end_position: usize,
+    ) -> Result<edit_locks::EditLock, String> {
+        state.perf.measure("acquire_edit_lock", || {
+            let timeline = state.get_timeline();
+            let invalid = || format!("invalid range: {start_position}..{end_position}");
+            let start = timeline
+                .timeline_offset(start_position)
+                .ok_or_else(invalid)?;
+            let end = timeline.timeline_offset(end_position).ok_or_else(invalid)?;
+            let mut lock = state
+                .edit_locks
+                .acquire(&owner, start, end, std::time::Instant::now())
+                .map_err(|err| lock_error_in_document(&timeline, err).to_string())?;
+            lock.start_position = timeline.document_offset(lock.start_position);
+            lock.end_position = timeline.document_offset(lock.end_position);
+            Ok(lock)
+        })
+    }
+
+    /// `err` with its offsets turned into offsets into the rendered document.
+    fn lock_error_in_document(
+        timeline: &timeline::Timeline,
+        err: edit_locks::LockError,
+    ) -> edit_locks::LockError {
+        match err {
+            edit_locks::LockError::Locked {
+                owner,
+                start_position,
+                end_position,
+                retry_after_ms,
+            } => edit_locks::LockError::Locked {
+                owner,
+                start_position: timeline.document_offset(start_position),
+                end_position: timeline.document_offset(end_position),
+                retry_after_ms,
+            },
+            err => err,
         }
     }
 
+    /// Refuses a change made by a command other than `handle_edit` when
+    /// its text `ops`, in timeline offsets, touch a range another writer
+    /// has locked. Such commands take no lock token.
+    fn check_edit_locks(
+        state: &AppState,
+        timeline: &timeline::Timeline,
+        ops: &[api::TextOperation],
+    ) -> Result<(), String> {
+        state
+            .edit_locks
+            .check(ops, None, std::time::Instant::now())
+            .map_err(|err| lock_error_in_document(timeline, err).to_string())
+    }
+
+    /// Returns whether the lock was still held.
+    #[tauri::command]
+    pub fn release_edit_lock(state: State<AppState>, token: String) -> Result<bool, String> {
+        state.perf.measure("release_edit_lock", || {
+            Ok(state.edit_locks.release(&token, std::time::Instant::now()))
+        })
+    }
+
+    /// The document as the editor shows it. With `include_archived` the
+    /// archived blocks are put back in, for reading: edit offsets don't
+    /// count them.
+    #[tauri::command]
+    pub fn get_full_document(
+        state: State<AppState>,
+        include_sensitive: Option<bool>,
+        include_archived: Option<bool>,
+    ) -> Result<streams::Chunked<String>, String> {
+        state.perf.measure("get_full_document", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            let archived = include_archived.unwrap_or(false).into();
+            let document = state.get_timeline().render(sensitive, archived);
+            state
+                .streams
+                .respond(document)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// The next chunk of a result that a command returned as a stream token.
     #[tauri::command]
-    pub fn get_full_document(state: State<AppState>) -> Result<String, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.content())
+    pub fn read_stream(
+        state: State<AppState>,
+        token: String,
+    ) -> Result<streams::StreamChunk, String> {
+        state.streams.read(&token).map_err(|err| err.to_string())
     }
 
     #[derive(Debug, Serialize)]
@@ -77,33 +654,303 @@ pub mod commands {
     }
 
     #[tauri::command]
-    pub fn get_document_snapshot(state: State<AppState>) -> Result<DocumentSnapshot, String> {
-        let timeline = state.get_timeline();
-        Ok(DocumentSnapshot {
-            content: timeline.content(),
-            version: timeline.version(),
+    pub fn get_document_snapshot(
+        state: State<AppState>,
+        include_sensitive: Option<bool>,
+    ) -> Result<DocumentSnapshot, String> {
+        state.perf.measure("get_document_snapshot", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            let timeline = state.get_timeline();
+            Ok(DocumentSnapshot {
+                content: timeline.render(sensitive, timeline::ArchivedContent::Excluded),
+                version: timeline.version(),
+            })
+        })
+    }
+
+    /// Reverts the most recent edit or tag assignment and returns the
+    /// resulting version. With nothing to undo the document is unchanged.
+    /// The document itself is read afterwards, in chunks if need be: a
+    /// whole document could be too large to return once the undo is made.
+    #[tauri::command]
+    pub fn undo(state: State<AppState>) -> Result<u64, String> {
+        state.perf.measure("undo", || {
+            step_history(
+                &state,
+                timeline::Timeline::undo_ops,
+                timeline::Timeline::undo,
+            )
+        })
+    }
+
+    /// Reapplies the most recently undone change and returns the resulting
+    /// version, as [`undo`] does.
+    #[tauri::command]
+    pub fn redo(state: State<AppState>) -> Result<u64, String> {
+        state.perf.measure("redo", || {
+            step_history(
+                &state,
+                timeline::Timeline::redo_ops,
+                timeline::Timeline::redo,
+            )
+        })
+    }
+
+    fn step_history(
+        state: &AppState,
+        step_ops: fn(&timeline::Timeline) -> Vec<api::TextOperation>,
+        step: fn(&mut timeline::Timeline) -> Result<bool, timeline::ApplyOpsError>,
+    ) -> Result<u64, String> {
+        let mut timeline = state.get_timeline();
+        let ops = step_ops(&timeline);
+        check_edit_locks(state, &timeline, &ops)?;
+        if step(&mut timeline).map_err(|err| err.to_string())? {
+            state
+                .edit_locks
+                .apply(&ops, None, std::time::Instant::now());
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after undo or redo");
+            }
+        }
+        Ok(timeline.version())
+    }
+
+    fn parse_date(date: &str) -> Result<NaiveDate, String> {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|err| format!("invalid date format: {err}"))
+    }
+
+    fn parse_date_range(
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<timeline::DateRange, String> {
+        let from = from.as_deref().map(parse_date).transpose()?;
+        let to = to.as_deref().map(parse_date).transpose()?;
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err(format!("invalid date range: {from} is after {to}"));
+            }
+        }
+        Ok(timeline::DateRange::new(from, to))
+    }
+
+    #[tauri::command]
+    pub fn get_log_for_date(
+        state: State<AppState>,
+        date: String,
+        include_sensitive: Option<bool>,
+        include_archived: Option<bool>,
+    ) -> Result<String, String> {
+        state.perf.measure("get_log_for_date", || {
+            let parsed = parse_date(&date)?;
+            let sensitive = state.sensitive_content(include_sensitive);
+            let archived = include_archived.unwrap_or(false).into();
+
+            let timeline = state.get_timeline();
+            Ok(timeline
+                .render_date(parsed, sensitive, archived)
+                .unwrap_or_default())
+        })
+    }
+
+    /// Whether a passphrase for unlocking sensitive blocks has been set.
+    #[tauri::command]
+    pub fn has_sensitive_passphrase(state: State<AppState>) -> Result<bool, String> {
+        state
+            .perf
+            .measure("has_sensitive_passphrase", || Ok(state.passphrase.is_set()))
+    }
+
+    /// Sets the passphrase that unlocks sensitive blocks. Changing it
+    /// needs the `current` one.
+    #[tauri::command]
+    pub fn set_sensitive_passphrase(
+        state: State<AppState>,
+        current: Option<String>,
+        passphrase: String,
+    ) -> Result<(), String> {
+        state.perf.measure("set_sensitive_passphrase", || {
+            state
+                .passphrase
+                .set(current.as_deref(), &passphrase)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Unlocks sensitive blocks for the session, given the passphrase.
+    #[tauri::command]
+    pub fn unlock_sensitive(state: State<AppState>, passphrase: String) -> Result<(), String> {
+        state.perf.measure("unlock_sensitive", || {
+            state
+                .passphrase
+                .verify(&passphrase)
+                .map_err(|err| err.to_string())?;
+            state.set_sensitive_unlocked(true);
+            Ok(())
+        })
+    }
+
+    #[tauri::command]
+    pub fn lock_sensitive(state: State<AppState>) -> Result<(), String> {
+        state.perf.measure("lock_sensitive", || {
+            state.set_sensitive_unlocked(false);
+            Ok(())
+        })
+    }
+
+    /// Blocks matching a regular expression, for structured queries like
+    /// `\bTODO\b.*urgent`, optionally limited to blocks dated `from`..=`to`.
+    /// Sensitive blocks are never searched.
+    #[tauri::command]
+    pub fn search_regex(
+        state: State<AppState>,
+        pattern: String,
+        from: Option<String>,
+        to: Option<String>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        include_archived: Option<bool>,
+    ) -> Result<pagination::Page<timeline::TextMatch>, String> {
+        state.perf.measure("search_regex", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            let search = timeline
+                .search_regex(&pattern, &range, include_archived.unwrap_or(false).into())
+                .map_err(|err| err.to_string())?;
+            let mut page = pagination::PageRequest::new(offset, limit)
+                .page(search.matches, timeline.version());
+            page.truncated = search.truncated;
+            Ok(page)
+        })
+    }
+
+    /// Text search across every workspace, of blocks dated from `from` to
+    /// `to` (both optional and inclusive). Matches keep search order unless
+    /// `sort` is given, and come back as one group unless `group_by` is.
+    /// Pages count matches, which are grouped within each page, so a group
+    /// can carry on into the next page; the page's version is the active
+    /// timeline's.
+    #[tauri::command]
+    #[allow(clippy::too_many_arguments)]
+    pub fn search_all_workspaces(
+        state: State<AppState>,
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        sort: Option<workspace::SearchSort>,
+        group_by: Option<workspace::SearchGroupBy>,
+        include_archived: Option<bool>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<streams::Chunked<pagination::Page<workspace::SearchGroup>>, String> {
+        state.perf.measure("search_all_workspaces", || {
+            let range = parse_date_range(from, to)?;
+            // Without a data directory only the active timeline is searchable.
+            let workspaces = match state.paths() {
+                Ok(paths) => workspace::discover(&paths.data_dir).map_err(|err| err.to_string())?,
+                Err(_) => Vec::new(),
+            };
+
+            let active = state.get_timeline();
+            let version = active.version();
+            let mut matches = workspace::search_all(
+                &workspaces,
+                &active,
+                state.profile.as_deref(),
+                state.paths.as_ref().map(|paths| paths.timeline.as_path()),
+                &query,
+                &range,
+                include_archived.unwrap_or(false).into(),
+            );
+            drop(active);
+            if let Err(err) = state.search_history.record(&query, state.clock.now()) {
+                tracing::warn!(?err, "failed to save search history");
+            }
+
+            if let Some(sort) = sort {
+                workspace::sort_matches(&mut matches, sort);
+            }
+            let groups = pagination::PageRequest::new(offset, limit)
+                .page(matches, version)
+                .map(|matches| workspace::group_matches(matches, group_by.unwrap_or_default()));
+            state.streams.respond(groups).map_err(|err| err.to_string())
         })
     }
 
+    /// Recent search queries, newest first, for the search box's dropdown.
+    #[tauri::command]
+    pub fn get_search_history(state: State<AppState>) -> Result<Vec<search::RecentSearch>, String> {
+        state
+            .perf
+            .measure("get_search_history", || Ok(state.search_history.recent()))
+    }
+
     #[tauri::command]
-    pub fn get_log_for_date(state: State<AppState>, date: String) -> Result<String, String> {
-        let parsed = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
-            .map_err(|err| format!("invalid date format: {err}"))?;
+    pub fn clear_search_history(state: State<AppState>) -> Result<(), String> {
+        state.perf.measure("clear_search_history", || {
+            state.search_history.clear().map_err(|err| err.to_string())
+        })
+    }
 
-        let timeline = state.get_timeline();
-        Ok(timeline.log_for_date(parsed).unwrap_or_default())
+    #[tauri::command]
+    pub fn search_prefix(
+        state: State<AppState>,
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        include_archived: Option<bool>,
+    ) -> Result<pagination::Page<u32>, String> {
+        state.perf.measure("search_prefix", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            let ids =
+                timeline.search_prefix(&query, &range, include_archived.unwrap_or(false).into());
+            Ok(pagination::PageRequest::new(offset, limit).page(ids, timeline.version()))
+        })
     }
 
     #[tauri::command]
-    pub fn search_prefix(state: State<AppState>, query: String) -> Result<Vec<u32>, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.search_prefix(&query))
+    pub fn search_infix(
+        state: State<AppState>,
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        include_archived: Option<bool>,
+    ) -> Result<pagination::Page<u32>, String> {
+        state.perf.measure("search_infix", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            let ids =
+                timeline.search_infix(&query, &range, include_archived.unwrap_or(false).into());
+            Ok(pagination::PageRequest::new(offset, limit).page(ids, timeline.version()))
+        })
     }
 
+    /// Block ids matching a boolean tag query, e.g.
+    /// `#project:sightline AND #type:journal NOT #archived`, optionally
+    /// limited to blocks dated `from`..=`to`.
     #[tauri::command]
-    pub fn search_infix(state: State<AppState>, query: String) -> Result<Vec<u32>, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.search_infix(&query))
+    pub fn search_tags_query(
+        state: State<AppState>,
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        include_archived: Option<bool>,
+    ) -> Result<pagination::Page<u32>, String> {
+        state.perf.measure("search_tags_query", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            let ids = timeline
+                .search_tags_query(&query, &range, include_archived.unwrap_or(false).into())
+                .map_err(|err| err.to_string())?;
+            Ok(pagination::PageRequest::new(offset, limit).page(ids, timeline.version()))
+        })
     }
 
     #[tauri::command]
@@ -111,24 +958,101 @@ pub mod commands {
         state: State<AppState>,
         query: String,
     ) -> Result<Vec<timeline::TagSuggestion>, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.autocomplete_tags(&query))
+        state.perf.measure("autocomplete_tag", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.autocomplete_tags(&query))
+        })
+    }
+
+    /// Tags that blocks with similar words carry and block `index` doesn't,
+    /// best first; `limit` defaults to 5.
+    #[tauri::command]
+    pub fn suggest_block_tags(
+        state: State<AppState>,
+        index: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<timeline::TagSuggestion>, String> {
+        state.perf.measure("suggest_block_tags", || {
+            let timeline = state.get_timeline();
+            timeline
+                .suggest_block_tags(index, limit.unwrap_or(SUGGESTION_LIMIT))
+                .ok_or_else(|| format!("block index {index} out of range"))
+        })
+    }
+
+    /// Blocks sharing the most words with block `index`, best first;
+    /// `limit` defaults to 5.
+    #[tauri::command]
+    pub fn related_blocks(
+        state: State<AppState>,
+        index: usize,
+        limit: Option<usize>,
+        include_sensitive: Option<bool>,
+    ) -> Result<Vec<timeline::RelatedBlock>, String> {
+        state.perf.measure("related_blocks", || {
+            let timeline = state.get_timeline();
+            timeline
+                .related_blocks(
+                    index,
+                    limit.unwrap_or(SUGGESTION_LIMIT),
+                    state.sensitive_content(include_sensitive),
+                )
+                .ok_or_else(|| format!("block index {index} out of range"))
+        })
     }
 
+    const SUGGESTION_LIMIT: usize = 5;
+
     #[tauri::command]
     pub fn intern_tag(
         state: State<AppState>,
         tag: String,
     ) -> Result<timeline::TagDescriptor, String> {
-        let mut timeline = state.get_timeline();
-        let descriptor = timeline.intern_tag(&tag).map_err(|err| err.to_string())?;
+        state.perf.measure("intern_tag", || {
+            let mut timeline = state.get_timeline();
+            let descriptor = timeline.intern_tag(&tag).map_err(|err| err.to_string())?;
 
-        if let Err(err) = timeline.save() {
-            tracing::warn!(?err, "failed to save timeline after interning tag");
-            return Err(err.to_string());
-        }
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after interning tag");
+                return Err(err.to_string());
+            }
+
+            Ok(descriptor)
+        })
+    }
+
+    /// Interns every tag with a single save, returning results in input
+    /// order. A tag that fails doesn't stop the rest.
+    #[tauri::command]
+    pub fn intern_tags(
+        state: State<AppState>,
+        tags: Vec<String>,
+    ) -> Result<Vec<api::InternTagResult>, String> {
+        state.perf.measure("intern_tags", || {
+            let mut timeline = state.get_timeline();
+            let results: Vec<api::InternTagResult> = tags
+                .into_iter()
+                .map(|tag| match timeline.intern_tag(&tag) {
+                    Ok(descriptor) => api::InternTagResult::Ok { tag: descriptor },
+                    Err(err) => api::InternTagResult::Error {
+                        tag,
+                        message: err.to_string(),
+                    },
+                })
+                .collect();
+
+            let interned = results
+                .iter()
+                .any(|result| matches!(result, api::InternTagResult::Ok { .. }));
+            if interned {
+                if let Err(err) = state.save_timeline(&timeline) {
+                    tracing::warn!(?err, "failed to save timeline after interning tags");
+                    return Err(err.to_string());
+                }
+            }
 
-        Ok(descriptor)
+            Ok(results)
+        })
     }
 
     #[tauri::command]
@@ -137,54 +1061,1562 @@ pub mod commands {
         block_index: u32,
         tags: Vec<String>,
     ) -> Result<Vec<timeline::TagDescriptor>, String> {
-        let mut timeline = state.get_timeline();
-        let descriptors = timeline
-            .assign_block_tags(block_index as usize, &tags)
-            .map_err(|err| err.to_string())?;
+        state.perf.measure("assign_block_tags", || {
+            let mut timeline = state.get_timeline();
+            let descriptors = timeline
+                .assign_block_tags(block_index as usize, &tags)
+                .map_err(|err| err.to_string())?;
 
-        if let Err(err) = timeline.save() {
-            tracing::warn!(?err, "failed to save timeline after assigning block tags");
-            return Err(err.to_string());
-        }
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after assigning block tags");
+                return Err(err.to_string());
+            }
 
-        Ok(descriptors)
+            Ok(descriptors)
+        })
     }
 
+    /// Archives block `block_index`, hiding it from the document, day logs
+    /// and search, or restores it when `archived` is false. Returns the new
+    /// document version.
     #[tauri::command]
-    pub fn list_tags(state: State<AppState>) -> Result<Vec<timeline::TagDescriptor>, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.list_tags())
+    pub fn archive_block(
+        state: State<AppState>,
+        block_index: u32,
+        archived: Option<bool>,
+    ) -> Result<u64, String> {
+        state.perf.measure("archive_block", || {
+            let mut timeline = state.get_timeline();
+            // The block's text leaves or rejoins the document, so any part
+            // of it under another writer's lock is off limits.
+            if let Some(range) = timeline.block_range(block_index as usize) {
+                let hidden = api::TextOperation::Delete {
+                    start_position: range.start,
+                    end_position: range.end,
+                };
+                check_edit_locks(&state, &timeline, &[hidden])?;
+            }
+            let version = timeline
+                .archive_block(block_index as usize, archived.unwrap_or(true))
+                .map_err(|err| err.to_string())?;
+
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after archiving a block");
+                return Err(err.to_string());
+            }
+
+            Ok(version)
+        })
     }
 
+    /// Checkbox tasks (`- [ ] ...`) in document order, narrowed by
+    /// `filter`.
     #[tauri::command]
-    pub fn list_blocks(state: State<AppState>) -> Result<Vec<timeline::BlockMetadata>, String> {
-        let timeline = state.get_timeline();
-        Ok(timeline.list_blocks())
+    pub fn list_tasks(
+        state: State<AppState>,
+        filter: Option<tasks::TaskFilter>,
+        include_sensitive: Option<bool>,
+    ) -> Result<Vec<tasks::Task>, String> {
+        state.perf.measure("list_tasks", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            Ok(state
+                .get_timeline()
+                .tasks(&filter.unwrap_or_default(), sensitive))
+        })
     }
-}
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
-            chat::register(app.handle().clone());
+    /// Open tasks due before `date` (`YYYY-MM-DD`), soonest first.
+    #[tauri::command]
+    pub fn tasks_due_before(
+        state: State<AppState>,
+        date: String,
+        include_sensitive: Option<bool>,
+    ) -> Result<Vec<tasks::Task>, String> {
+        state.perf.measure("tasks_due_before", || {
+            let date = parse_date(&date)?;
+            let sensitive = state.sensitive_content(include_sensitive);
+            Ok(state.get_timeline().tasks_due_before(date, sensitive))
+        })
+    }
+
+    /// Open tasks whose due date has passed, soonest first.
+    #[tauri::command]
+    pub fn overdue_tasks(
+        state: State<AppState>,
+        include_sensitive: Option<bool>,
+    ) -> Result<Vec<tasks::Task>, String> {
+        state.perf.measure("overdue_tasks", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            Ok(state.get_timeline().overdue_tasks(sensitive))
+        })
+    }
+
+    /// Ticks or clears task `task_index` of block `block_id`.
+    #[tauri::command]
+    pub fn toggle_task(
+        state: State<AppState>,
+        block_id: u32,
+        task_index: u32,
+    ) -> Result<tasks::Task, String> {
+        state.perf.measure("toggle_task", || {
+            let mut timeline = state.get_timeline();
+            if let Some(mark) = timeline.task_mark(block_id as usize, task_index as usize) {
+                let rewrite = api::TextOperation::Delete {
+                    start_position: mark,
+                    end_position: mark + 1,
+                };
+                check_edit_locks(&state, &timeline, &[rewrite])?;
+            }
+            let task = timeline
+                .toggle_task(block_id as usize, task_index as usize)
+                .map_err(|err| err.to_string())?;
+
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after toggling a task");
+                return Err(err.to_string());
+            }
+
+            Ok(task)
+        })
+    }
+
+    /// Splits block `block_id` at the character `offset` within it. The
+    /// second half takes `date` and `tags` when they are given.
+    #[tauri::command]
+    pub fn split_block(
+        state: State<AppState>,
+        block_id: u32,
+        offset: u32,
+        date: Option<String>,
+        tags: Option<Vec<String>>,
+    ) -> Result<Vec<timeline::TagDescriptor>, String> {
+        state.perf.measure("split_block", || {
+            let date = date.as_deref().map(parse_date).transpose()?;
+            let mut timeline = state.get_timeline();
+            if let Some(range) = timeline.block_range(block_id as usize) {
+                let split = api::TextOperation::Insert {
+                    position: range.start + offset as usize,
+                    text: String::new(),
+                };
+                check_edit_locks(&state, &timeline, &[split])?;
+            }
+            let descriptors = timeline
+                .split_block(block_id as usize, offset as usize, date, tags.as_deref())
+                .map_err(|err| err.to_string())?;
+
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after splitting a block");
+                return Err(err.to_string());
+            }
+
+            Ok(descriptors)
+        })
+    }
+
+    /// Merges fragmented blocks; returns how many were merged away.
+    #[tauri::command]
+    pub fn compact_timeline(state: State<AppState>) -> Result<usize, String> {
+        state.perf.measure("compact_timeline", || {
+            let mut timeline = state.get_timeline();
+            let removed = timeline.compact();
+            if removed > 0 {
+                state
+                    .save_timeline(&timeline)
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Deletes a tag, and its children when `cascade` is set, removing it
+    /// from every block. Reports how many tags and blocks were affected.
+    #[tauri::command]
+    pub fn delete_tag<R: tauri::Runtime>(
+        app: tauri::AppHandle<R>,
+        state: State<AppState>,
+        id: u32,
+        cascade: Option<bool>,
+    ) -> Result<timeline::TagDeletion, String> {
+        state.perf.measure("delete_tag", || {
+            let mut timeline = state.get_timeline();
+            let deletion = timeline
+                .delete_tag(id, cascade.unwrap_or(false))
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+
+            if let Err(err) = app.emit(TAG_REGISTRY_EVENT, timeline.list_tags()) {
+                tracing::warn!(%err, "failed to emit tag registry change");
+            }
+            Ok(deletion)
+        })
+    }
+
+    #[tauri::command]
+    pub fn get_persistence_policy(
+        state: State<AppState>,
+    ) -> Result<persistence::PersistencePolicy, String> {
+        state
+            .perf
+            .measure("get_persistence_policy", || Ok(state.persistence_policy()))
+    }
+
+    /// Sets how durably this workspace is kept. Leaving ephemeral mode
+    /// saves the timeline straight away.
+    #[tauri::command]
+    pub fn set_persistence_policy(
+        state: State<AppState>,
+        policy: persistence::PersistencePolicy,
+    ) -> Result<(), String> {
+        state.perf.measure("set_persistence_policy", || {
+            let was_ephemeral = state.persistence_policy().ephemeral;
+            state
+                .set_persistence_policy(policy)
+                .map_err(|err| err.to_string())?;
+            if was_ephemeral {
+                let timeline = state.get_timeline();
+                state
+                    .save_timeline(&timeline)
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Emitted with the full tag list when tags are moved or renumbered.
+    pub const TAG_REGISTRY_EVENT: &str = "tag-registry-changed";
+
+    /// Moves a tag under `new_parent`, or to the root when it's `None`, and
+    /// returns the updated tag list.
+    #[tauri::command]
+    pub fn reparent_tag<R: tauri::Runtime>(
+        app: tauri::AppHandle<R>,
+        state: State<AppState>,
+        id: u32,
+        new_parent: Option<u32>,
+    ) -> Result<Vec<timeline::TagDescriptor>, String> {
+        state.perf.measure("reparent_tag", || {
+            let mut timeline = state.get_timeline();
+            timeline
+                .reparent_tag(id, new_parent)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+
+            let tags = timeline.list_tags();
+            if let Err(err) = app.emit(TAG_REGISTRY_EVENT, &tags) {
+                tracing::warn!(%err, "failed to emit tag registry change");
+            }
+            Ok(tags)
+        })
+    }
+
+    /// Renames a tag in place, keeping its id so tagged blocks are
+    /// untouched, and returns the updated tag list.
+    #[tauri::command]
+    pub fn rename_tag<R: tauri::Runtime>(
+        app: tauri::AppHandle<R>,
+        state: State<AppState>,
+        id: u32,
+        new_name: String,
+    ) -> Result<Vec<timeline::TagDescriptor>, String> {
+        state.perf.measure("rename_tag", || {
+            let mut timeline = state.get_timeline();
+            timeline
+                .rename_tag(id, &new_name)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+
+            let tags = timeline.list_tags();
+            if let Err(err) = app.emit(TAG_REGISTRY_EVENT, &tags) {
+                tracing::warn!(%err, "failed to emit tag registry change");
+            }
+            Ok(tags)
+        })
+    }
+
+    /// How a legacy flat tag registry was rebuilt as a hierarchy on load,
+    /// for the user to review; `None` when there is nothing to migrate.
+    #[tauri::command]
+    pub fn preview_registry_migration(
+        state: State<AppState>,
+    ) -> Result<Option<Vec<timeline::TagMigration>>, String> {
+        state.perf.measure("preview_registry_migration", || {
+            Ok(state.get_timeline().registry_migration_preview())
+        })
+    }
+
+    /// Applies the user's corrections (colon paths by legacy tag id) to the
+    /// previewed migration and saves the registry in the current format.
+    #[tauri::command]
+    pub fn commit_registry_migration<R: tauri::Runtime>(
+        app: tauri::AppHandle<R>,
+        state: State<AppState>,
+        paths: std::collections::HashMap<u32, String>,
+    ) -> Result<Vec<timeline::TagMigration>, String> {
+        state.perf.measure("commit_registry_migration", || {
+            let mut timeline = state.get_timeline();
+            let migrations = timeline
+                .commit_registry_migration(&paths)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+
+            if let Err(err) = app.emit(TAG_REGISTRY_EVENT, timeline.list_tags()) {
+                tracing::warn!(%err, "failed to emit tag registry change");
+            }
+            Ok(migrations)
+        })
+    }
+
+    #[tauri::command]
+    pub fn list_tags(state: State<AppState>) -> Result<Vec<timeline::TagDescriptor>, String> {
+        state.perf.measure("list_tags", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.list_tags())
+        })
+    }
+
+    #[tauri::command]
+    pub fn get_tag_tree(state: State<AppState>) -> Result<Vec<timeline::TagNode>, String> {
+        state.perf.measure("get_tag_tree", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.tag_tree())
+        })
+    }
+
+    /// The line and column of character `offset`.
+    #[tauri::command]
+    pub fn offset_to_point(
+        state: State<AppState>,
+        offset: usize,
+    ) -> Result<timeline::Point, String> {
+        state.perf.measure("offset_to_point", || {
+            let timeline = state.get_timeline();
+            timeline
+                .offset_to_point(offset)
+                .ok_or_else(|| format!("offset {offset} is past the end of the document"))
+        })
+    }
+
+    /// The character offset of a line and column.
+    #[tauri::command]
+    pub fn point_to_offset(
+        state: State<AppState>,
+        point: timeline::Point,
+    ) -> Result<usize, String> {
+        state.perf.measure("point_to_offset", || {
+            let timeline = state.get_timeline();
+            timeline
+                .point_to_offset(point)
+                .ok_or_else(|| format!("line {} has no column {}", point.row, point.column))
+        })
+    }
+
+    #[tauri::command]
+    pub fn list_frozen_ranges(state: State<AppState>) -> Result<Vec<timeline::DateRange>, String> {
+        state.perf.measure("list_frozen_ranges", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.frozen_ranges().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn freeze_range(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<Vec<timeline::DateRange>, String> {
+        state.perf.measure("freeze_range", || {
+            let range = parse_date_range(from, to)?;
+            if range.from.is_none() && range.to.is_none() {
+                return Err("freeze range needs a start or end date".to_string());
+            }
+
+            let mut timeline = state.get_timeline();
+            timeline.freeze_range(range);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.frozen_ranges().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn unfreeze_range(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<Vec<timeline::DateRange>, String> {
+        state.perf.measure("unfreeze_range", || {
+            let range = parse_date_range(from, to)?;
+
+            let mut timeline = state.get_timeline();
+            if timeline.unfreeze_range(&range) {
+                state
+                    .save_timeline(&timeline)
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(timeline.frozen_ranges().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn get_day_properties(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+        include_sensitive: Option<bool>,
+    ) -> Result<BTreeMap<NaiveDate, day_metrics::DayProperties>, String> {
+        state.perf.measure("get_day_properties", || {
+            let range = parse_date_range(from, to)?;
+            let sensitive = state.sensitive_content(include_sensitive);
+            let timeline = state.get_timeline();
+            Ok(timeline.day_properties(&range, sensitive))
+        })
+    }
+
+    /// Each day between `from` and `to` that has blocks, with their
+    /// indexes, the span of text they cover and their word count.
+    #[tauri::command]
+    pub fn get_day_index(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<BTreeMap<NaiveDate, day_index::DayEntry>, String> {
+        state.perf.measure("get_day_index", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            Ok(timeline.days(&range))
+        })
+    }
+
+    /// Words, characters and blocks written between `from` and `to`, per
+    /// day (the default), week or month.
+    #[tauri::command]
+    pub fn writing_stats(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+        period: Option<writing_stats::StatsPeriod>,
+    ) -> Result<Vec<writing_stats::PeriodStats>, String> {
+        state.perf.measure("writing_stats", || {
+            let range = parse_date_range(from, to)?;
+            let timeline = state.get_timeline();
+            Ok(timeline.writing_stats(&range, period.unwrap_or_default()))
+        })
+    }
+
+    /// Characters and blocks per day of `year`, for the calendar heatmap.
+    #[tauri::command]
+    pub fn daily_activity(
+        state: State<AppState>,
+        year: i32,
+    ) -> Result<writing_stats::DailyActivity, String> {
+        state.perf.measure("daily_activity", || {
+            Ok(state.get_timeline().daily_activity(year))
+        })
+    }
+
+    /// The current and longest runs of days with a journal entry.
+    #[tauri::command]
+    pub fn get_streaks(state: State<AppState>) -> Result<streaks::Streaks, String> {
+        state
+            .perf
+            .measure("get_streaks", || Ok(state.get_timeline().streaks()))
+    }
+
+    /// Rebuilds the day index from scratch, returning how many days it
+    /// holds.
+    #[tauri::command]
+    pub fn rebuild_day_index(state: State<AppState>) -> Result<usize, String> {
+        state.perf.measure("rebuild_day_index", || {
+            let mut timeline = state.get_timeline();
+            let days = timeline.rebuild_day_index();
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(days)
+        })
+    }
+
+    #[tauri::command]
+    pub fn reparse_metrics(
+        state: State<AppState>,
+        from: Option<String>,
+        to: Option<String>,
+        include_sensitive: Option<bool>,
+    ) -> Result<BTreeMap<NaiveDate, day_metrics::DayProperties>, String> {
+        state.perf.measure("reparse_metrics", || {
+            let range = parse_date_range(from, to)?;
+            let sensitive = state.sensitive_content(include_sensitive);
+            let mut timeline = state.get_timeline();
+            let properties = timeline.reparse_metrics(&range, sensitive);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(properties)
+        })
+    }
+
+    #[tauri::command]
+    pub fn list_metric_patterns(
+        state: State<AppState>,
+    ) -> Result<Vec<day_metrics::MetricPattern>, String> {
+        state.perf.measure("list_metric_patterns", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.metric_patterns().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn set_metric_patterns(
+        state: State<AppState>,
+        patterns: Vec<day_metrics::MetricPattern>,
+    ) -> Result<Vec<day_metrics::MetricPattern>, String> {
+        state.perf.measure("set_metric_patterns", || {
+            let mut timeline = state.get_timeline();
+            timeline
+                .set_metric_patterns(patterns)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.metric_patterns().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn list_snippets(state: State<AppState>) -> Result<Vec<snippets::Snippet>, String> {
+        state.perf.measure("list_snippets", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.snippets().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn save_snippet(
+        state: State<AppState>,
+        trigger: String,
+        expansion: String,
+    ) -> Result<Vec<snippets::Snippet>, String> {
+        state.perf.measure("save_snippet", || {
+            let mut timeline = state.get_timeline();
+            timeline
+                .save_snippet(&trigger, &expansion)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.snippets().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn delete_snippet(
+        state: State<AppState>,
+        trigger: String,
+    ) -> Result<Vec<snippets::Snippet>, String> {
+        state.perf.measure("delete_snippet", || {
+            let mut timeline = state.get_timeline();
+            if timeline.delete_snippet(&trigger) {
+                state
+                    .save_timeline(&timeline)
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(timeline.snippets().to_vec())
+        })
+    }
+
+    #[tauri::command]
+    pub fn set_snippet_expansion(state: State<AppState>, enabled: bool) -> Result<bool, String> {
+        state.perf.measure("set_snippet_expansion", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_snippet_expansion(enabled);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.snippet_expansion())
+        })
+    }
+
+    #[tauri::command]
+    pub fn set_fetch_link_titles(state: State<AppState>, enabled: bool) -> Result<bool, String> {
+        state.perf.measure("set_fetch_link_titles", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_fetch_link_titles(enabled);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.fetch_link_titles())
+        })
+    }
+
+    /// Turns offline mode on or off. While it is on, nothing in the app
+    /// touches the network and network work is queued instead.
+    #[tauri::command]
+    pub fn set_offline_mode(state: State<AppState>, enabled: bool) -> Result<bool, String> {
+        state.perf.measure("set_offline_mode", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_offline_mode(enabled);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            network::set_offline_mode(enabled);
+            Ok(timeline.offline_mode())
+        })
+    }
+
+    /// Sets where the `#now` page is written, or stops writing it. The page
+    /// is written immediately and then kept up to date on every save.
+    #[tauri::command]
+    pub fn set_now_page(
+        state: State<AppState>,
+        config: Option<now_page::NowPageConfig>,
+    ) -> Result<Option<now_page::NowPageConfig>, String> {
+        state.perf.measure("set_now_page", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_now_page(config);
+            *state.now_page.lock().expect("now page lock poisoned") = None;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.now_page().cloned())
+        })
+    }
+
+    /// Sets the text each new day starts with, or stops creating daily
+    /// notes. `{date}` in the template becomes the day's date and
+    /// `{weekday}` its name.
+    #[tauri::command]
+    pub fn set_daily_note(
+        state: State<AppState>,
+        template: Option<String>,
+    ) -> Result<Option<String>, String> {
+        state.perf.measure("set_daily_note", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_daily_note(template);
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.daily_note().map(str::to_string))
+        })
+    }
+
+    /// Sets where and how often the timeline is exported as markdown, or
+    /// stops scheduled exports. A new schedule exports straight away.
+    #[tauri::command]
+    pub fn set_export_schedule(
+        state: State<AppState>,
+        schedule: Option<export_schedule::ExportSchedule>,
+    ) -> Result<export_schedule::ExportScheduleStatus, String> {
+        state.perf.measure("set_export_schedule", || {
+            let mut timeline = state.get_timeline();
+            timeline.set_export_schedule(schedule.clone());
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            let kind = jobs::JobKind::ScheduledExport;
+            let now = state.clock.now();
+            let job = match &schedule {
+                Some(schedule) => Some(
+                    state
+                        .jobs
+                        .schedule(kind, now, now, schedule.every.hours())
+                        .map_err(|err| err.to_string())?,
+                ),
+                None => {
+                    state.jobs.cancel(&kind).map_err(|err| err.to_string())?;
+                    None
+                }
+            };
+            Ok(export_schedule::ExportScheduleStatus::new(
+                schedule,
+                job.as_ref(),
+            ))
+        })
+    }
+
+    /// The export schedule, how its last run went and when the next is due.
+    #[tauri::command]
+    pub fn get_export_schedule_status(
+        state: State<AppState>,
+    ) -> Result<export_schedule::ExportScheduleStatus, String> {
+        state.perf.measure("get_export_schedule_status", || {
+            let schedule = state.get_timeline().export_schedule().cloned();
+            let job = state.jobs.find(&jobs::JobKind::ScheduledExport);
+            Ok(export_schedule::ExportScheduleStatus::new(
+                schedule,
+                job.as_ref(),
+            ))
+        })
+    }
+
+    /// Inserts today's daily note if nothing is written today yet, e.g.
+    /// when a session starts. Returns where it was inserted.
+    #[tauri::command]
+    pub fn start_day(state: State<AppState>) -> Result<Option<usize>, String> {
+        state.perf.measure("start_day", || {
+            let mut timeline = state.get_timeline();
+            let note = timeline.daily_note_op();
+            check_edit_locks(&state, &timeline, note.as_slice())?;
+            let position = timeline
+                .create_daily_note()
+                .map_err(|err| err.to_string())?;
+            if position.is_some() {
+                state
+                    .edit_locks
+                    .apply(note.as_slice(), None, std::time::Instant::now());
+                if let Err(err) = state.save_timeline(&timeline) {
+                    tracing::warn!(?err, "failed to save timeline after starting the day");
+                    return Err(err.to_string());
+                }
+            }
+            Ok(position)
+        })
+    }
+
+    /// Sets the birthdays, anniversaries and holiday locale to remember.
+    #[tauri::command]
+    pub fn set_important_dates(
+        state: State<AppState>,
+        dates: important_dates::ImportantDates,
+    ) -> Result<important_dates::ImportantDates, String> {
+        state.perf.measure("set_important_dates", || {
+            let mut timeline = state.get_timeline();
+            timeline
+                .set_important_dates(dates)
+                .map_err(|err| err.to_string())?;
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(timeline.important_dates().clone())
+        })
+    }
+
+    /// Important dates falling between `from` and `to` inclusive.
+    #[tauri::command]
+    pub fn get_important_dates(
+        state: State<AppState>,
+        from: String,
+        to: String,
+    ) -> Result<Vec<important_dates::DateOccurrence>, String> {
+        state.perf.measure("get_important_dates", || {
+            let (from, to) = (parse_date(&from)?, parse_date(&to)?);
+            if from > to {
+                return Err(format!("invalid date range: {from} is after {to}"));
+            }
+            let timeline = state.get_timeline();
+            Ok(timeline.important_dates().between(from, to))
+        })
+    }
+
+    /// Checks for a new day now rather than waiting for the watcher, e.g.
+    /// when the window regains focus after sleep. Emits
+    /// [`rollover::DAY_CHANGED_EVENT`] if the day changed.
+    #[tauri::command]
+    pub fn check_day_rollover<R: tauri::Runtime>(
+        app: tauri::AppHandle<R>,
+        state: State<AppState>,
+    ) -> Result<Option<rollover::DayChange>, String> {
+        state.perf.measure("check_day_rollover", || {
+            let change = state.roll_over();
+            if let Some(change) = &change {
+                if let Err(err) = app.emit(rollover::DAY_CHANGED_EVENT, change) {
+                    tracing::error!(?err, "failed to emit day-changed event");
+                }
+            }
+            Ok(change)
+        })
+    }
+
+    /// Writes the now page and, for git targets, commits and pushes it.
+    /// While offline, or in offline mode, the push is queued instead.
+    #[tauri::command(async)]
+    pub fn publish_now_page(state: State<'_, AppState>) -> Result<now_page::NowPageStatus, String> {
+        state.perf.measure("publish_now_page", || {
+            let timeline = state.get_timeline().clone();
+            let config = timeline
+                .now_page()
+                .cloned()
+                .ok_or_else(|| "no now page is configured".to_string())?;
+            if config.git && !state.network_available() {
+                state
+                    .jobs
+                    .enqueue(jobs::JobKind::PublishNowPage, state.clock.now())
+                    .map_err(|err| err.to_string())?;
+                return now_page::write(&timeline, &config).map_err(|err| err.to_string());
+            }
+            now_page::publish(&timeline, &config).map_err(|err| err.to_string())
+        })
+    }
+
+    /// Returns the text to insert for a paste: a bare URL becomes
+    /// `[Title](url)` when link titles are enabled and the page answers in
+    /// time; anything else is returned unchanged. While offline, or in
+    /// offline mode, the URL is pasted as-is and its title fetched later.
+    #[tauri::command(async)]
+    pub fn smart_paste(state: State<'_, AppState>, text: String) -> Result<String, String> {
+        state.perf.measure("smart_paste", || {
+            if !state.get_timeline().fetch_link_titles() {
+                return Ok(text);
+            }
+            if !state.network_available() {
+                if let Some(url) = link_titles::bare_url(&text) {
+                    let job = jobs::JobKind::LinkTitle {
+                        url: url.to_string(),
+                    };
+                    state
+                        .jobs
+                        .enqueue(job, state.clock.now())
+                        .map_err(|err| err.to_string())?;
+                }
+                return Ok(text);
+            }
+            Ok(state.link_titles.smart_paste(&text))
+        })
+    }
+
+    /// Latency and response-size histograms for every command called since
+    /// launch.
+    #[tauri::command]
+    pub fn get_perf_metrics(state: State<AppState>) -> Result<Vec<perf::CommandMetrics>, String> {
+        Ok(state.perf.snapshot())
+    }
+
+    /// Network jobs waiting for connectivity or a retry, oldest first.
+    #[tauri::command]
+    pub fn get_pending_jobs(state: State<AppState>) -> Result<Vec<jobs::Job>, String> {
+        Ok(state.jobs.pending())
+    }
+
+    #[tauri::command]
+    pub fn expand_preview(state: State<AppState>, text: String) -> Result<String, String> {
+        state.perf.measure("expand_preview", || {
+            let timeline = state.get_timeline();
+            Ok(timeline.expand_preview(&text))
+        })
+    }
+
+    #[tauri::command]
+    pub fn gc_attachments(
+        state: State<AppState>,
+        dry_run: bool,
+    ) -> Result<attachments::AttachmentReport, String> {
+        state.perf.measure("gc_attachments", || {
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+            let timeline = state.get_timeline();
+            attachments::collect_garbage(
+                // Archived blocks can be brought back, so their attachments stay.
+                &timeline.render(
+                    timeline::SensitiveContent::Included,
+                    timeline::ArchivedContent::Included,
+                ),
+                &assets_dir,
+                dry_run,
+            )
+            .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Returns the path of a cached thumbnail for the attachment, generating
+    /// it on first use.
+    #[tauri::command]
+    pub fn get_thumbnail(
+        state: State<AppState>,
+        attachment_id: String,
+        size: thumbnails::ThumbnailSize,
+    ) -> Result<PathBuf, String> {
+        state.perf.measure("get_thumbnail", || {
+            let cache = state.thumbnail_cache().map_err(|err| err.to_string())?;
+            cache
+                .get(&attachment_id, size)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Whether an insert at `target` made against `base_version` could
+    /// apply, checked before copying anything into the attachment store.
+    /// Frozen dates are only caught by the insert itself.
+    fn check_drop_target(
+        timeline: &timeline::Timeline,
+        base_version: u64,
+        target: api::DropTarget,
+    ) -> Result<(), timeline::ApplyOpsError> {
+        if base_version != timeline.version() {
+            return Err(timeline::ApplyOpsError::VersionMismatch {
+                expected: timeline.version(),
+                actual: base_version,
+            });
+        }
+        match target {
+            api::DropTarget::Offset(position) => timeline
+                .timeline_offset(position)
+                .map(|_| ())
+                .ok_or(timeline::ApplyOpsError::InvalidPosition { position }),
+            api::DropTarget::Date(_) => Ok(()),
+        }
+    }
+
+    /// Inserts `text` literally at `target` against `base_version`, unless
+    /// it would land inside a range another writer has locked, and moves
+    /// the locks past it. Returns the offset into the rendered document it
+    /// starts at.
+    fn insert_at(
+        state: &AppState,
+        timeline: &mut timeline::Timeline,
+        base_version: u64,
+        target: api::DropTarget,
+        text: String,
+    ) -> Result<usize, String> {
+        check_drop_target(timeline, base_version, target).map_err(|err| err.to_string())?;
+        let ops = match target {
+            api::DropTarget::Offset(position) => timeline
+                .ops_in_chars(
+                    &[api::TextOperation::Insert {
+                        position,
+                        text: text.clone(),
+                    }],
+                    api::OffsetUnit::Chars,
+                )
+                .map_err(|err| err.to_string())?,
+            api::DropTarget::Date(date) => vec![timeline.date_insert_op(date, &text)],
+        };
+        check_edit_locks(state, timeline, &ops)?;
+        let position = match target {
+            api::DropTarget::Offset(position) => timeline
+                .apply_edit(base_version, &ops, true)
+                .map(|_| position),
+            api::DropTarget::Date(date) => timeline.insert_on_date(base_version, date, &text),
+        }
+        .map_err(|err| err.to_string())?;
+        state
+            .edit_locks
+            .apply(&ops, None, std::time::Instant::now());
+        Ok(position)
+    }
+
+    /// Removes attachments stored for an insert that then failed.
+    fn discard_attachments(assets_dir: &std::path::Path, ids: &[String]) {
+        for id in ids {
+            if let Err(err) = std::fs::remove_file(assets_dir.join(id)) {
+                tracing::warn!(%err, id, "failed to remove unused attachment");
+            }
+        }
+    }
+
+    /// Stores a clipboard image, sent as base64 `data`, as an attachment
+    /// and links it at `target`, usually the caret offset. Like
+    /// [`ingest_dropped_files`], the target is checked against
+    /// `base_version` first and the image removed if the insert fails.
+    /// Returns the attachment id.
+    #[tauri::command]
+    pub fn paste_image(
+        state: State<AppState>,
+        base_version: u64,
+        data: String,
+        mime: String,
+        target: api::DropTarget,
+    ) -> Result<String, String> {
+        state.perf.measure("paste_image", || {
+            use base64::Engine as _;
+
+            let extension = attachments::image_extension(&mime)
+                .ok_or_else(|| format!("unsupported image type '{mime}'"))?;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|err| format!("pasted image is not valid base64: {err}"))?;
+            check_drop_target(&state.get_timeline(), base_version, target)
+                .map_err(|err| err.to_string())?;
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+            let name = format!(
+                "pasted-{}.{extension}",
+                state.clock.local_now().format("%Y%m%d-%H%M%S")
+            );
+            let id = attachments::store_bytes(&assets_dir, &name, &bytes)
+                .map_err(|err| format!("failed to store pasted image: {err}"))?;
+
+            let mut timeline = state.get_timeline();
+            let link = attachments::link(&id, &id);
+            if let Err(err) = insert_at(&state, &mut timeline, base_version, target, link) {
+                discard_attachments(&assets_dir, std::slice::from_ref(&id));
+                return Err(err);
+            }
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(id)
+        })
+    }
+
+    /// Copies files dropped onto the editor into the attachment store and
+    /// links them at `target`: a new block on a date, or the caret offset.
+    /// The target is checked against `base_version` before anything is
+    /// copied, and the copies are removed again if the insert fails.
+    #[tauri::command]
+    pub fn ingest_dropped_files(
+        state: State<AppState>,
+        base_version: u64,
+        paths: Vec<PathBuf>,
+        target: api::DropTarget,
+    ) -> Result<api::IngestResponse, String> {
+        state.perf.measure("ingest_dropped_files", || {
+            if paths.is_empty() {
+                return Err("no files were dropped".to_string());
+            }
+            check_drop_target(&state.get_timeline(), base_version, target)
+                .map_err(|err| err.to_string())?;
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+
+            let mut stored: Vec<api::StoredAttachment> = Vec::new();
+            let mut links = String::new();
+            for path in &paths {
+                let id = match attachments::store_file(&assets_dir, path) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        let ids: Vec<_> = stored.into_iter().map(|stored| stored.id).collect();
+                        discard_attachments(&assets_dir, &ids);
+                        return Err(format!("failed to store '{}': {err}", path.display()));
+                    }
+                };
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                links.push_str(&attachments::link(&file_name, &id));
+                links.push('\n');
+                stored.push(api::StoredAttachment {
+                    size: std::fs::metadata(assets_dir.join(&id))
+                        .map(|metadata| metadata.len())
+                        .unwrap_or_default(),
+                    id,
+                    file_name,
+                });
+            }
+
+            let mut timeline = state.get_timeline();
+            let position = match insert_at(&state, &mut timeline, base_version, target, links) {
+                Ok(position) => position,
+                Err(err) => {
+                    let ids: Vec<_> = stored.into_iter().map(|stored| stored.id).collect();
+                    discard_attachments(&assets_dir, &ids);
+                    return Err(err);
+                }
+            };
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+
+            // The block the links start in: a new one for a date target, or
+            // the block around the caret for an offset.
+            let block = timeline
+                .list_blocks()
+                .into_iter()
+                .filter(|block| !block.archived)
+                .take_while(|block| block.start_offset as usize <= position)
+                .last()
+                .ok_or("inserted block not found")?;
+            Ok(api::IngestResponse {
+                new_version: timeline.version(),
+                block,
+                attachments: stored,
+            })
+        })
+    }
+
+    /// Copies `path` into the attachment store and links it on a new line
+    /// at the end of block `block_id`.
+    #[tauri::command]
+    pub fn attach_file(
+        state: State<AppState>,
+        block_id: u32,
+        path: PathBuf,
+    ) -> Result<api::IngestResponse, String> {
+        state.perf.measure("attach_file", || {
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+            let id = attachments::store_file(&assets_dir, &path)
+                .map_err(|err| format!("failed to store '{}': {err}", path.display()))?;
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let link = attachments::link(&file_name, &id);
+            let mut timeline = state.get_timeline();
+            let append = timeline.append_op(block_id as usize, &link);
+            let appended = check_edit_locks(&state, &timeline, append.as_slice()).and_then(|()| {
+                timeline
+                    .append_to_block(block_id as usize, &link)
+                    .map_err(|err| err.to_string())
+            });
+            let new_version = match appended {
+                Ok(version) => version,
+                Err(err) => {
+                    discard_attachments(&assets_dir, std::slice::from_ref(&id));
+                    return Err(err);
+                }
+            };
+            state
+                .edit_locks
+                .apply(append.as_slice(), None, std::time::Instant::now());
+
+            if let Err(err) = state.save_timeline(&timeline) {
+                tracing::warn!(?err, "failed to save timeline after attaching a file");
+                return Err(err.to_string());
+            }
+
+            let block = timeline
+                .list_blocks()
+                .into_iter()
+                .nth(block_id as usize)
+                .ok_or("attached block not found")?;
+            Ok(api::IngestResponse {
+                new_version,
+                block,
+                attachments: vec![api::StoredAttachment {
+                    size: std::fs::metadata(assets_dir.join(&id))
+                        .map(|metadata| metadata.len())
+                        .unwrap_or_default(),
+                    id,
+                    file_name,
+                }],
+            })
+        })
+    }
+
+    /// Writes the `front :: back` lines of blocks tagged `tag` as an Anki
+    /// TSV. Sensitive blocks are included only while unlocked.
+    #[tauri::command]
+    pub fn export_flashcards(
+        state: State<AppState>,
+        tag: String,
+        path: String,
+    ) -> Result<flashcards::FlashcardExport, String> {
+        state.perf.measure("export_flashcards", || {
+            let sensitive = state.sensitive_content(Some(true));
+            let timeline = state.get_timeline();
+            flashcards::export(&timeline, &tag, std::path::Path::new(&path), sensitive)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Saves the weekly review for the week containing `week_of` (default:
+    /// this week) as an `.eml` draft addressed to `to`.
+    #[tauri::command]
+    pub fn write_weekly_digest(
+        state: State<AppState>,
+        path: String,
+        week_of: Option<String>,
+        to: Option<String>,
+    ) -> Result<digest::WeeklyDigest, String> {
+        state.perf.measure("write_weekly_digest", || {
+            let now = state.clock.local_now();
+            let day = match week_of {
+                Some(date) => parse_date(&date)?,
+                None => now.date_naive(),
+            };
+            let timeline = state.get_timeline();
+            let weekly = digest::weekly(&timeline, day);
+            weekly
+                .write_eml(
+                    std::path::Path::new(&path),
+                    to.as_deref(),
+                    now.fixed_offset(),
+                )
+                .map_err(|err| err.to_string())?;
+            Ok(weekly)
+        })
+    }
+
+    /// Sets who gets the weekly digest and how, or stops sending it. A
+    /// schedule without an SMTP password keeps the saved one.
+    #[tauri::command]
+    pub fn set_digest_schedule(
+        state: State<AppState>,
+        schedule: Option<digest::DigestSchedule>,
+    ) -> Result<digest::DigestScheduleStatus, String> {
+        state.perf.measure("set_digest_schedule", || {
+            let mut current = state
+                .digest_schedule
+                .lock()
+                .expect("digest schedule lock poisoned");
+            let schedule = schedule
+                .clone()
+                .map(|schedule| schedule.keeping_password(current.as_ref()));
+            let path = &state
+                .paths()
+                .map_err(|err| err.to_string())?
+                .digest_schedule;
+            digest::DigestSchedule::store(schedule.as_ref(), path)
+                .map_err(|err| err.to_string())?;
+            sync_digest_job(&state.jobs, schedule.as_ref(), state.clock.local_now())
+                .map_err(|err| err.to_string())?;
+            let job = state.jobs.find(&jobs::JobKind::WeeklyDigest);
+            let status = digest::DigestScheduleStatus::new(schedule.as_ref(), job.as_ref());
+            *current = schedule;
+            Ok(status)
+        })
+    }
+
+    /// The digest schedule, how its last send went and when the next is
+    /// due. The SMTP password is never returned.
+    #[tauri::command]
+    pub fn get_digest_schedule_status(
+        state: State<AppState>,
+    ) -> Result<digest::DigestScheduleStatus, String> {
+        state.perf.measure("get_digest_schedule_status", || {
+            let job = state.jobs.find(&jobs::JobKind::WeeklyDigest);
+            Ok(digest::DigestScheduleStatus::new(
+                state.digest_schedule().as_ref(),
+                job.as_ref(),
+            ))
+        })
+    }
+
+    /// Reviews `year`, opening with `narrative` if given, and saves it as an
+    /// HTML document at `path` if given.
+    #[tauri::command]
+    pub fn generate_year_review(
+        state: State<AppState>,
+        year: i32,
+        path: Option<String>,
+        narrative: Option<String>,
+    ) -> Result<year_review::YearReview, String> {
+        state.perf.measure("generate_year_review", || {
+            let timeline = state.get_timeline();
+            let review = year_review::yearly(&timeline, year, narrative);
+            if let Some(path) = path {
+                review
+                    .write_html(std::path::Path::new(&path))
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(review)
+        })
+    }
+
+    #[tauri::command]
+    pub fn export_bundle(
+        state: State<AppState>,
+        path: String,
+    ) -> Result<bundle::BundleManifest, String> {
+        state.perf.measure("export_bundle", || {
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+            let timeline = state.get_timeline();
+            bundle::export(&timeline, &assets_dir, std::path::Path::new(&path))
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Writes the timeline as markdown under `dir`: day files in
+    /// `journal/` and project files in `projects/`, with tags rendered as
+    /// frontmatter unless `tag_rendering` says otherwise.
+    #[tauri::command]
+    pub fn export_markdown(
+        state: State<AppState>,
+        dir: String,
+        tag_rendering: Option<markdown::TagRendering>,
+        include_sensitive: Option<bool>,
+    ) -> Result<markdown::MarkdownExport, String> {
+        state.perf.measure("export_markdown", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            let timeline = state.get_timeline();
+            timeline
+                .export_markdown(
+                    std::path::Path::new(&dir),
+                    tag_rendering.unwrap_or_default(),
+                    sensitive,
+                )
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Writes the timeline to `path` as JSON Lines: a header line with the
+    /// tag registry, then one block per line. Returns the number of blocks
+    /// written.
+    #[tauri::command]
+    pub fn export_jsonl(
+        state: State<AppState>,
+        path: String,
+        include_sensitive: Option<bool>,
+    ) -> Result<usize, String> {
+        state.perf.measure("export_jsonl", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            let timeline = state.get_timeline();
+            timeline
+                .export_jsonl(std::path::Path::new(&path), sensitive)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Writes the timeline to `path` as an `.ics` calendar: an all-day
+    /// event per day with entries and a to-do per due-dated task.
+    #[tauri::command]
+    pub fn export_ical(
+        state: State<AppState>,
+        path: String,
+        include_sensitive: Option<bool>,
+    ) -> Result<ical::IcalExport, String> {
+        state.perf.measure("export_ical", || {
+            let sensitive = state.sensitive_content(include_sensitive);
+            let timeline = state.get_timeline();
+            timeline
+                .export_ical(std::path::Path::new(&path), sensitive)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Replaces the active timeline with the bundle's and unpacks its
+    /// attachments. The frontend should refetch the document afterwards.
+    #[tauri::command]
+    pub fn import_bundle(
+        state: State<AppState>,
+        path: String,
+    ) -> Result<bundle::BundleManifest, String> {
+        state.perf.measure("import_bundle", || {
+            let assets_dir = state.assets_dir().map_err(|err| err.to_string())?;
+            let bundle =
+                bundle::read(std::path::Path::new(&path)).map_err(|err| err.to_string())?;
+
+            // Nothing is replaced until the current timeline is backed up.
+            let mut timeline = state.get_timeline();
+            state
+                .back_up_timeline(&timeline)
+                .map_err(|err| format!("failed to back up the timeline before import: {err}"))?;
+            bundle
+                .write_attachments(&assets_dir)
+                .map_err(|err| err.to_string())?;
+            let mut imported = bundle.timeline;
+            imported.advance_version_past(timeline.version());
+            imported.set_clock(state.clock.clone());
+            *timeline = imported;
+            network::set_offline_mode(timeline.offline_mode());
+            if let Err(err) =
+                sync_export_job(&state.jobs, timeline.export_schedule(), state.clock.now())
+            {
+                tracing::warn!(%err, "failed to schedule exports");
+            }
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(bundle.manifest)
+        })
+    }
+
+    #[tauri::command]
+    pub fn preview_import(
+        state: State<AppState>,
+        source: String,
+    ) -> Result<vault::ImportPreview, String> {
+        state.perf.measure("preview_import", || {
+            vault::preview_import(std::path::Path::new(&source)).map_err(|err| err.to_string())
+        })
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct VaultImportSummary {
+        #[serde(flatten)]
+        pub merge: timeline::MergeSummary,
+        pub undated: Vec<PathBuf>,
+    }
+
+    /// Reads a vault's journal and project notes straight into the live
+    /// timeline. The vault is read before the timeline is locked, so edits
+    /// carry on meanwhile; notes imported before are not added again. The
+    /// frontend should refetch the document afterwards.
+    #[tauri::command]
+    pub fn import_vault(
+        state: State<AppState>,
+        source: String,
+        strategy: Option<timeline::MergeStrategy>,
+    ) -> Result<VaultImportSummary, String> {
+        state.perf.measure("import_vault", || {
+            let mut registry = timeline::TagRegistry::new();
+            let mut notes = vault::read_vault(std::path::Path::new(&source), &mut registry)
+                .map_err(|err| format!("failed to read vault '{source}': {err}"))?;
+
+            let mut timeline = state.get_timeline();
+            // The vault's tags join the registry only once the merge is
+            // allowed, so a locked import leaves no tags behind.
+            let mut tags = timeline.tag_registry().clone();
+            let ids = tags.merge(&registry);
+            for block in &mut notes.blocks {
+                for tag in &mut block.tags {
+                    *tag = ids[tag];
+                }
+                block.tags.sort_unstable();
+            }
+            let plan = timeline.plan_merge(notes.blocks, strategy.unwrap_or_default());
+            check_edit_locks(&state, &timeline, &plan.ops)?;
+            let ops = plan.ops.clone();
+            *timeline.tag_registry_mut() = tags;
+            let merge = timeline.apply_merge(plan);
+            state
+                .edit_locks
+                .apply(&ops, None, std::time::Instant::now());
+            state
+                .save_timeline(&timeline)
+                .map_err(|err| err.to_string())?;
+            Ok(VaultImportSummary {
+                merge,
+                undated: notes.undated,
+            })
+        })
+    }
+
+    /// Blocks' offsets, dates and tags, with the first `preview_chars`
+    /// characters of their text when asked for. Returns `limit` blocks
+    /// from `offset`, or all of them without a limit.
+    #[tauri::command]
+    pub fn list_blocks(
+        state: State<AppState>,
+        preview_chars: Option<usize>,
+        include_sensitive: Option<bool>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<streams::Chunked<pagination::Page<timeline::BlockMetadata>>, String> {
+        state.perf.measure("list_blocks", || {
+            let timeline = state.get_timeline();
+            let blocks = timeline.list_blocks_page(
+                &pagination::PageRequest::new(offset, limit),
+                preview_chars.unwrap_or(0),
+                state.sensitive_content(include_sensitive),
+            );
+            state.streams.respond(blocks).map_err(|err| err.to_string())
+        })
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let options = launch::LaunchOptions::from_env();
+    let log_dir = paths::AppPaths::resolve(&options)
+        .ok()
+        .map(|paths| paths.logs);
+    let _log_guard = logging::init(log_dir.as_deref());
+    if options.safe_mode {
+        tracing::warn!("starting in safe mode; chat and integrations are disabled");
+    }
+    let state = AppState::with_options(&options);
+    let thumbnail_cache = state.thumbnail_cache().ok();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .setup(move |app| {
+            if !options.safe_mode {
+                chat::register(app.handle().clone());
+                jobs::spawn_monitor(app.handle().clone());
+                rollover::spawn_watcher(app.handle().clone());
+                if let Some(cache) = thumbnail_cache {
+                    std::thread::spawn(move || {
+                        if let Err(err) = cache.warm() {
+                            tracing::warn!(%err, "failed to pre-generate thumbnails");
+                        }
+                    });
+                }
+            }
             Ok(())
         })
-        .manage(AppState::new())
+        .manage(state)
         .invoke_handler(tauri::generate_handler![
             commands::entry_count,
+            commands::word_count,
             commands::handle_edit,
+            commands::acquire_edit_lock,
+            commands::release_edit_lock,
             commands::get_full_document,
+            commands::read_stream,
             commands::get_document_snapshot,
+            commands::undo,
+            commands::redo,
             commands::get_log_for_date,
             commands::search_prefix,
             commands::search_infix,
+            commands::search_regex,
+            commands::search_tags_query,
             commands::autocomplete_tag,
+            commands::suggest_block_tags,
+            commands::related_blocks,
             commands::intern_tag,
+            commands::intern_tags,
             commands::assign_block_tags,
+            commands::archive_block,
+            commands::list_tasks,
+            commands::toggle_task,
+            commands::tasks_due_before,
+            commands::overdue_tasks,
+            commands::split_block,
+            commands::compact_timeline,
+            commands::reparent_tag,
+            commands::rename_tag,
+            commands::delete_tag,
+            commands::get_persistence_policy,
+            commands::set_persistence_policy,
+            commands::preview_registry_migration,
+            commands::commit_registry_migration,
             commands::list_tags,
-            commands::list_blocks
+            commands::get_tag_tree,
+            commands::offset_to_point,
+            commands::point_to_offset,
+            commands::list_blocks,
+            commands::preview_import,
+            commands::import_vault,
+            commands::list_frozen_ranges,
+            commands::freeze_range,
+            commands::unfreeze_range,
+            commands::has_sensitive_passphrase,
+            commands::set_sensitive_passphrase,
+            commands::unlock_sensitive,
+            commands::lock_sensitive,
+            commands::list_snippets,
+            commands::save_snippet,
+            commands::delete_snippet,
+            commands::set_snippet_expansion,
+            commands::set_fetch_link_titles,
+            commands::set_offline_mode,
+            commands::smart_paste,
+            commands::set_now_page,
+            commands::set_daily_note,
+            commands::set_export_schedule,
+            commands::get_export_schedule_status,
+            commands::start_day,
+            commands::set_important_dates,
+            commands::get_important_dates,
+            commands::check_day_rollover,
+            commands::publish_now_page,
+            commands::get_pending_jobs,
+            commands::get_perf_metrics,
+            commands::expand_preview,
+            commands::get_day_properties,
+            commands::get_day_index,
+            commands::writing_stats,
+            commands::get_streaks,
+            commands::daily_activity,
+            commands::rebuild_day_index,
+            commands::reparse_metrics,
+            commands::list_metric_patterns,
+            commands::set_metric_patterns,
+            commands::search_all_workspaces,
+            commands::get_search_history,
+            commands::clear_search_history,
+            commands::export_flashcards,
+            commands::write_weekly_digest,
+            commands::set_digest_schedule,
+            commands::get_digest_schedule_status,
+            commands::generate_year_review,
+            commands::export_bundle,
+            commands::export_markdown,
+            commands::export_jsonl,
+            commands::export_ical,
+            commands::import_bundle,
+            commands::gc_attachments,
+            commands::get_thumbnail,
+            commands::ingest_dropped_files,
+            commands::attach_file,
+            commands::paste_image
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,183 @@
+//! Page titles for pasted URLs, so a bare link can be inserted as
+//! `[Title](url)`.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use scraper::Html;
+
+use crate::html;
+use crate::network::{self, NetworkError};
+
+/// How long a title fetch may take before the URL is pasted as-is.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Only the start of a page is read; `<title>` belongs in `<head>`.
+const MAX_PAGE_BYTES: u64 = 256 * 1024;
+
+/// Cached lookups before the cache is cleared.
+const CACHE_CAPACITY: usize = 512;
+
+type Fetcher = Box<dyn Fn(&str) -> Result<Option<String>, NetworkError> + Send + Sync>;
+
+pub struct LinkTitles {
+    fetch: Fetcher,
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl LinkTitles {
+    pub fn new(timeout: Duration) -> Self {
+        Self::with_fetcher(move |url| fetch_title(url, timeout))
+    }
+
+    pub fn with_fetcher(
+        fetch: impl Fn(&str) -> Result<Option<String>, NetworkError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            fetch: Box::new(fetch),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The page title for `url`, or `None` when the page has none or
+    /// can't be fetched.
+    pub fn title(&self, url: &str) -> Option<String> {
+        self.lookup(url).ok().flatten()
+    }
+
+    /// The page title for `url`, fetched once and then served from the
+    /// cache. Pages read without a title are cached too; failed fetches are
+    /// not, so a later lookup tries again.
+    pub fn lookup(&self, url: &str) -> Result<Option<String>, NetworkError> {
+        if let Some(cached) = self.cache().get(url) {
+            return Ok(cached.clone());
+        }
+
+        let title = (self.fetch)(url)?;
+        let mut cache = self.cache();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(url.to_string(), title.clone());
+        Ok(title)
+    }
+
+    /// Rewrites a bare URL as a markdown link titled after its page. Any
+    /// other text, or a URL whose title can't be read, comes back unchanged.
+    pub fn smart_paste(&self, text: &str) -> String {
+        let Some(url) = bare_url(text) else {
+            return text.to_string();
+        };
+        match self.title(url) {
+            Some(title) => format!("[{}]({url})", escape_link_text(&title)),
+            None => text.to_string(),
+        }
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, Option<String>>> {
+        self.cache.lock().expect("link title cache poisoned")
+    }
+}
+
+impl Default for LinkTitles {
+    fn default() -> Self {
+        Self::new(FETCH_TIMEOUT)
+    }
+}
+
+/// `text` trimmed, if it is a single `http(s)://` URL and nothing else.
+pub fn bare_url(text: &str) -> Option<&str> {
+    let url = text.trim();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    (!rest.is_empty() && !url.contains(char::is_whitespace)).then_some(url)
+}
+
+fn fetch_title(url: &str, timeout: Duration) -> Result<Option<String>, NetworkError> {
+    let client = network::http_client(timeout)?;
+    let response = client.get(url).send()?.error_for_status()?;
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.contains("html"));
+    if !is_html {
+        return Ok(None);
+    }
+
+    let mut page = Vec::new();
+    response.take(MAX_PAGE_BYTES).read_to_end(&mut page)?;
+    Ok(title_from_html(&String::from_utf8_lossy(&page)))
+}
+
+/// The page's `og:title`, falling back to `<title>`, with whitespace
+/// collapsed.
+pub fn title_from_html(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    html::first_attr(&document, r#"meta[property="og:title"]"#, "content")
+        .or_else(|| html::first_text(&document, "title"))
+}
+
+fn escape_link_text(title: &str) -> String {
+    title.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn title_from_html_prefers_og_title_and_decodes() {
+        let page = r#"<html><head><TITLE>
+            Fallback &amp; more</TITLE>
+            <meta property="og:title" content="Rust &quot;book&quot;"></head></html>"#;
+        assert_eq!(title_from_html(page).as_deref(), Some("Rust \"book\""));
+        assert_eq!(
+            title_from_html("<title> Fallback &amp; more </title>").as_deref(),
+            Some("Fallback & more")
+        );
+        assert_eq!(title_from_html("<title>  </title>"), None);
+    }
+
+    #[test]
+    fn smart_paste_links_bare_urls_and_caches_lookups() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let titles = LinkTitles::with_fetcher(move |url| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            if url.contains("down.test") {
+                return Err(NetworkError::NetworkDisabled);
+            }
+            Ok(url.ends_with("/docs").then(|| "The [Docs]".to_string()))
+        });
+
+        assert_eq!(
+            titles.smart_paste(" https://example.com/docs\n"),
+            "[The \\[Docs\\]](https://example.com/docs)"
+        );
+        titles.smart_paste("https://example.com/docs");
+        assert_eq!(
+            titles.smart_paste("http://broken.test"),
+            "http://broken.test"
+        );
+        titles.smart_paste("http://broken.test");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        assert_eq!(
+            titles.smart_paste("see https://a.test"),
+            "see https://a.test"
+        );
+        assert_eq!(titles.smart_paste("ftp://a.test"), "ftp://a.test");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Failed fetches aren't cached, so the next paste tries again.
+        assert_eq!(titles.smart_paste("https://down.test"), "https://down.test");
+        assert!(titles.lookup("https://down.test").is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
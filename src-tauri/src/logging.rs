@@ -0,0 +1,84 @@
+//! Where the app's tracing output goes: stderr, and a log file per day in
+//! [`crate::paths::AppPaths::logs`], of which the last [`KEEP_LOG_FILES`]
+//! are kept.
+
+use std::path::Path;
+
+use tracing::level_filters::LevelFilter;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{InitError, RollingFileAppender, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub const KEEP_LOG_FILES: usize = 7;
+
+const LOG_FILE_PREFIX: &str = "sightline";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Daily log files in `dir`, named like `sightline.2025-03-01.log`.
+fn log_files(dir: &Path) -> Result<RollingFileAppender, InitError> {
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(KEEP_LOG_FILES)
+        .build(dir)
+}
+
+/// Installs the global subscriber, logging to files in `dir` when given and
+/// it can be created. Log lines are written by a background thread until
+/// the returned guard is dropped, so the caller keeps it for the life of
+/// the app.
+pub fn init(dir: Option<&Path>) -> Option<WorkerGuard> {
+    let files = dir.map(|dir| (dir, log_files(dir)));
+    let (file_layer, guard, failed) = match files {
+        Some((_, Ok(appender))) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard), None)
+        }
+        Some((dir, Err(err))) => (None, None, Some((dir, err))),
+        None => (None, None, None),
+    };
+    let installed = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
+        .try_init();
+    if installed.is_err() {
+        return None;
+    }
+    if let Some((dir, err)) = failed {
+        tracing::warn!(%err, dir = %dir.display(), "failed to open log files; logging to stderr only");
+    }
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn log_lines_land_in_a_dated_file() {
+        let dir = tempdir().unwrap();
+        let logs = dir.path().join("logs");
+        let mut files = log_files(&logs).unwrap();
+        files.write_all(b"started\n").unwrap();
+        files.flush().unwrap();
+
+        let names: Vec<String> = fs::read_dir(&logs)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 1);
+        assert!(names[0].starts_with("sightline.") && names[0].ends_with(".log"));
+        assert_eq!(
+            fs::read_to_string(logs.join(&names[0])).unwrap(),
+            "started\n"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Markdown export: one `journal/YYYY-MM-DD.md` file per day, the layout the
+//! vault importer reads, with block tags written so that importing the
+//! files again brings them back. Exporting into an Obsidian vault names and
+//! places the files the way its daily-notes settings say instead.
+//!
+//! Unless tags are omitted, each block is followed by a comment recording
+//! its tags, e.g. `<!-- tags: work -->`, placed before the block's final
+//! line break. [`parse_export`] splits a file back into its blocks at these
+//! markers, so blocks return with their own text and tags.
+//!
+//! Blocks tagged under `#project` go to `projects/<tag path>/YYYY-MM-DD.md`
+//! instead. The importer dates them by name and, as with day files, takes
+//! their tags from the markers rather than from the folder.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::attachments;
+use crate::obsidian::DailyNotesSettings;
+use crate::timeline::{
+    ArchivedContent, DateRange, SensitiveContent, TagRegistry, TaggedBlock, Timeline,
+};
+
+/// Directory under the export root that day files are written to.
+pub const JOURNAL_DIR: &str = "journal";
+
+/// Directory under the export root that project files are written to.
+pub const PROJECTS_DIR: &str = "projects";
+
+/// Tag whose descendants get project files, as the importer names it by
+/// default.
+pub const PROJECT_TAG: &str = "project";
+
+/// How block tags appear in exported files. Both forms that write tags
+/// also write each block's marker comment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagRendering {
+    /// A `tags:` list in YAML frontmatter with every tag in the file, for
+    /// tools that read tags per note.
+    #[default]
+    Frontmatter,
+    /// `#tags` at the end of each tagged block, before its marker.
+    Inline,
+    /// Neither tags nor markers; the files read back as plain notes.
+    Omit,
+}
+
+/// One block read back from an exported file by [`parse_export`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedBlock {
+    pub text: String,
+    /// Full tag names, e.g. `project:home`.
+    pub tags: Vec<String>,
+    /// Bytes of the file the block and its marker take up.
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MarkdownExport {
+    /// Day and project files written.
+    pub files: usize,
+    /// Of `files`, those under `projects/`.
+    pub projects: usize,
+    pub blocks: usize,
+}
+
+/// Writes each day's blocks to `dir/journal/<date>.md`, or the daily note
+/// path from `dir`'s Obsidian settings, and project blocks to their tag's
+/// file under `dir/projects/`, replacing files of the same name. Masked
+/// sensitive blocks are left out rather than written as bullets, and
+/// archived blocks are left out too, since the files carry no flag to
+/// import them back archived.
+pub fn export(
+    timeline: &Timeline,
+    dir: &Path,
+    rendering: TagRendering,
+    sensitive: SensitiveContent,
+) -> io::Result<MarkdownExport> {
+    let registry = timeline.tag_registry();
+    let project_root = registry.find_colon_path(PROJECT_TAG);
+    let mut days: BTreeMap<NaiveDate, Vec<&TaggedBlock>> = BTreeMap::new();
+    let mut projects: BTreeMap<(String, NaiveDate), Vec<&TaggedBlock>> = BTreeMap::new();
+    for block in
+        timeline.blocks_in_range(&DateRange::default(), sensitive, ArchivedContent::Excluded)
+    {
+        match project_root.and_then(|root| project_folder(registry, root, block)) {
+            Some(folder) => projects
+                .entry((folder, block.date))
+                .or_default()
+                .push(block),
+            None => days.entry(block.date).or_default().push(block),
+        }
+    }
+
+    let daily_notes = DailyNotesSettings::load(dir)?;
+    let mut summary = MarkdownExport::default();
+    for (date, blocks) in &days {
+        let relative = daily_notes
+            .as_ref()
+            .and_then(|settings| settings.note_path(*date))
+            .unwrap_or_else(|| Path::new(JOURNAL_DIR).join(format!("{date}.md")));
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = render_day(blocks, registry, rendering);
+        fs::write(path, text)?;
+        summary.files += 1;
+        summary.blocks += blocks.len();
+    }
+
+    for ((folder, date), blocks) in &projects {
+        let path = dir.join(folder).join(format!("{date}.md"));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, render_day(blocks, registry, rendering))?;
+        summary.files += 1;
+        summary.projects += 1;
+        summary.blocks += blocks.len();
+    }
+    Ok(summary)
+}
+
+/// The folder, relative to the export root, for `block`'s most specific
+/// tag under `root`. Blocks tagged only `root` itself, or with tag names
+/// that can't be folder names, have none.
+fn project_folder(registry: &TagRegistry, root: u32, block: &TaggedBlock) -> Option<String> {
+    let root_name = registry.full_name(root)?;
+    // The deepest tag wins; `rev` gives ties to the first.
+    let name = block
+        .tags
+        .iter()
+        .rev()
+        .filter(|&&tag| tag != root && registry.has_ancestor(tag, root))
+        .filter_map(|&tag| registry.full_name(tag))
+        .max_by_key(|name| name.matches(':').count())?;
+    let folders = name
+        .strip_prefix(&root_name)?
+        .strip_prefix(':')?
+        .replace(':', "/");
+    attachments::is_valid_id(&folders).then(|| format!("{PROJECTS_DIR}/{folders}"))
+}
+
+/// One day's blocks as markdown. A marker follows each block whose tags
+/// differ from the next one's, so runs of blocks with the same tags read
+/// back as one block.
+pub fn render_day(
+    blocks: &[&TaggedBlock],
+    registry: &TagRegistry,
+    rendering: TagRendering,
+) -> String {
+    let mut text = String::new();
+    if rendering == TagRendering::Frontmatter {
+        text.push_str(&frontmatter(tag_names(blocks, registry)));
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        let tags = tag_names(&[*block], registry);
+        let joins_next = blocks
+            .get(index + 1)
+            .is_some_and(|next| tag_names(&[*next], registry) == tags);
+        if rendering == TagRendering::Omit || joins_next {
+            text.push_str(&block.text);
+            continue;
+        }
+        let (body, line_break) = match block.text.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (block.text.as_str(), ""),
+        };
+        text.push_str(body);
+        text.push_str(&marker(&tags, rendering));
+        text.push_str(line_break);
+    }
+    text
+}
+
+/// Full names of the tags on `blocks`, sorted.
+fn tag_names(blocks: &[&TaggedBlock], registry: &TagRegistry) -> BTreeSet<String> {
+    blocks
+        .iter()
+        .flat_map(|block| &block.tags)
+        .filter_map(|&tag| registry.full_name(tag))
+        .collect()
+}
+
+/// The comment recording a block's tags, after the tags themselves when
+/// they are rendered inline.
+fn marker(tags: &BTreeSet<String>, rendering: TagRendering) -> String {
+    let list: Vec<&str> = tags.iter().map(String::as_str).collect();
+    let list = list.join(", ");
+    if rendering == TagRendering::Inline && !tags.is_empty() {
+        format!(" {} <!-- #tags: {list} -->", inline_tags(tags.iter()))
+    } else if tags.is_empty() {
+        "<!-- tags: -->".to_string()
+    } else {
+        format!("<!-- tags: {list} -->")
+    }
+}
+
+fn inline_tags<'a>(tags: impl Iterator<Item = &'a String>) -> String {
+    let tags: Vec<String> = tags.map(|name| format!("#{name}")).collect();
+    tags.join(" ")
+}
+
+/// Splits a file [`export`] wrote with tags back into its blocks, each
+/// with the tags its marker records. Files without markers, including
+/// exports with tags omitted, are `None`. Text after the last marker, e.g.
+/// added by hand, is an untagged block of its own.
+pub fn parse_export(text: &str) -> Option<Vec<ExportedBlock>> {
+    let mut blocks = Vec::new();
+    let mut start = frontmatter_len(text);
+    let mut search = start;
+    while let Some(found) = text[search..].find("<!-- ") {
+        let open = search + found;
+        let after = &text[open + "<!-- ".len()..];
+        let (inline, list) = if let Some(list) = after.strip_prefix("tags:") {
+            (false, list)
+        } else if let Some(list) = after.strip_prefix("#tags:") {
+            (true, list)
+        } else {
+            search = open + "<!-- ".len();
+            continue;
+        };
+        let Some(close) = list.find("-->") else {
+            break;
+        };
+        let tags: Vec<String> = list[..close]
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let mut body = &text[start..open];
+        if inline {
+            let shown = format!(" {} ", inline_tags(tags.iter()));
+            body = body.strip_suffix(shown.as_str()).unwrap_or(body);
+        }
+        let mut end = text.len() - list.len() + close + "-->".len();
+        let mut block_text = body.to_string();
+        if text[end..].starts_with('\n') {
+            block_text.push('\n');
+            end += 1;
+        }
+        blocks.push(ExportedBlock {
+            text: block_text,
+            tags,
+            span: start..end,
+        });
+        start = end;
+        search = end;
+    }
+
+    if blocks.is_empty() {
+        return None;
+    }
+    if start < text.len() {
+        blocks.push(ExportedBlock {
+            text: text[start..].to_string(),
+            tags: Vec::new(),
+            span: start..text.len(),
+        });
+    }
+    Some(blocks)
+}
+
+/// Length of the frontmatter [`frontmatter`] writes, if `text` starts with
+/// exactly that.
+fn frontmatter_len(text: &str) -> usize {
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return 0;
+    };
+    let Some((line, rest)) = rest.split_once('\n') else {
+        return 0;
+    };
+    match rest.strip_prefix("---\n") {
+        Some(body) if line.starts_with("tags: [") && line.ends_with(']') => text.len() - body.len(),
+        _ => 0,
+    }
+}
+
+fn frontmatter(tags: BTreeSet<String>) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let tags: Vec<String> = tags.into_iter().collect();
+    format!("---\ntags: [{}]\n---\n", tags.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn tags_render_as_frontmatter_inline_or_not_at_all() {
+        let mut registry = TagRegistry::new();
+        let home = registry.intern_colon_path("project:home").unwrap();
+        let work = registry.intern_colon_path("work").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let block = |text: &str, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        let blocks = [
+            block("Fixed the ", vec![home]),
+            block("fence\n", vec![home]),
+            block("Standup\n", vec![work]),
+            block("Lunch\n", Vec::new()),
+        ];
+        let blocks: Vec<&TaggedBlock> = blocks.iter().collect();
+
+        let frontmatter = render_day(&blocks, &registry, TagRendering::Frontmatter);
+        assert_eq!(
+            frontmatter,
+            "---\ntags: [project:home, work]\n---\n\
+             Fixed the fence<!-- tags: project:home -->\n\
+             Standup<!-- tags: work -->\n\
+             Lunch<!-- tags: -->\n"
+        );
+        let inline = render_day(&blocks, &registry, TagRendering::Inline);
+        assert_eq!(
+            inline,
+            "Fixed the fence #project:home <!-- #tags: project:home -->\n\
+             Standup #work <!-- #tags: work -->\n\
+             Lunch<!-- tags: -->\n"
+        );
+        let omitted = render_day(&blocks, &registry, TagRendering::Omit);
+        assert_eq!(omitted, "Fixed the fence\nStandup\nLunch\n");
+        assert_eq!(parse_export(&omitted), None);
+
+        for text in [frontmatter, inline] {
+            let parsed: Vec<(String, Vec<String>)> = parse_export(&text)
+                .unwrap()
+                .into_iter()
+                .map(|block| (block.text, block.tags))
+                .collect();
+            assert_eq!(
+                parsed,
+                [
+                    (
+                        "Fixed the fence\n".to_string(),
+                        vec!["project:home".to_string()]
+                    ),
+                    ("Standup\n".to_string(), vec!["work".to_string()]),
+                    ("Lunch\n".to_string(), Vec::new()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn hand_edits_around_markers_are_kept() {
+        let text = "Standup #work <!-- #tags: work -->\nLater\n<!-- note -->\n";
+        let blocks = parse_export(text).unwrap();
+        assert_eq!(blocks[0].text, "Standup\n");
+        assert_eq!(blocks[0].span, 0..35);
+        assert_eq!(blocks[1].text, "Later\n<!-- note -->\n");
+        assert!(blocks[1].tags.is_empty());
+    }
+
+    #[test]
+    fn export_writes_a_file_per_day() {
+        let mut timeline = Timeline::default();
+        for (day, text) in [(1, "First\n"), (2, "Second\n"), (1, "More\n")] {
+            let date = NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+            timeline
+                .insert_on_date(timeline.version(), date, text)
+                .expect("insert");
+        }
+
+        let dir = tempdir().unwrap();
+        let summary = export(
+            &timeline,
+            dir.path(),
+            TagRendering::Omit,
+            SensitiveContent::Masked,
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            MarkdownExport {
+                files: 2,
+                projects: 0,
+                blocks: 3
+            }
+        );
+        let journal = dir.path().join(JOURNAL_DIR);
+        assert_eq!(
+            fs::read_to_string(journal.join("2024-05-01.md")).unwrap(),
+            "First\nMore\n"
+        );
+        assert_eq!(
+            fs::read_to_string(journal.join("2024-05-02.md")).unwrap(),
+            "Second\n"
+        );
+
+        let vault = tempdir().unwrap();
+        fs::create_dir_all(vault.path().join(".obsidian")).unwrap();
+        fs::write(
+            vault.path().join(".obsidian/daily-notes.json"),
+            r#"{"format": "YYYY/DD.MM.YYYY", "folder": "Daily"}"#,
+        )
+        .unwrap();
+        export(
+            &timeline,
+            vault.path(),
+            TagRendering::Omit,
+            SensitiveContent::Masked,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(vault.path().join("Daily/2024/02.05.2024.md")).unwrap(),
+            "Second\n"
+        );
+        assert!(!vault.path().join(JOURNAL_DIR).exists());
+    }
+
+    #[test]
+    fn project_blocks_get_a_file_per_tag_and_day() {
+        let mut timeline = Timeline::default();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        for (index, (text, tags)) in [
+            ("Standup\n", vec!["work"]),
+            (
+                "Fixed the fence\n",
+                vec!["project:home:garden", "project:home", "chores"],
+            ),
+            ("Bought paint\n", vec!["project:home"]),
+            ("Ideas\n", vec!["project"]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            timeline
+                .insert_on_date(timeline.version(), date, text)
+                .expect("insert");
+            let tags: Vec<String> = tags.into_iter().map(str::to_string).collect();
+            timeline.assign_block_tags(index, &tags).expect("tag");
+        }
+
+        let dir = tempdir().unwrap();
+        let summary = timeline
+            .export_markdown(
+                dir.path(),
+                TagRendering::Frontmatter,
+                SensitiveContent::Masked,
+            )
+            .unwrap();
+        assert_eq!(
+            summary,
+            MarkdownExport {
+                files: 3,
+                projects: 2,
+                blocks: 4
+            }
+        );
+        let read = |path: &str| fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(
+            read("journal/2024-05-01.md"),
+            "---\ntags: [project, work]\n---\n\
+             Standup<!-- tags: work -->\nIdeas<!-- tags: project -->\n"
+        );
+        assert_eq!(
+            read("projects/home/garden/2024-05-01.md"),
+            "---\ntags: [chores, project:home, project:home:garden]\n---\n\
+             Fixed the fence<!-- tags: chores, project:home, project:home:garden -->\n"
+        );
+        assert_eq!(
+            read("projects/home/2024-05-01.md"),
+            "---\ntags: [project:home]\n---\nBought paint<!-- tags: project:home -->\n"
+        );
+    }
+}
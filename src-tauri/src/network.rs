@@ -0,0 +1,79 @@
+//! The one door to the network. Integrations build HTTP clients and open
+//! connections through here, so offline mode is enforced in one place
+//! rather than by each feature's own toggle.
+
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use thiserror::Error;
+
+static OFFLINE_MODE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    /// Offline mode is on; nothing was sent.
+    #[error("network access is disabled by offline mode")]
+    NetworkDisabled,
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Whether offline mode forbids all network access.
+pub fn offline_mode() -> bool {
+    OFFLINE_MODE.load(Ordering::SeqCst)
+}
+
+/// Turns offline mode on or off for the whole process.
+pub fn set_offline_mode(enabled: bool) {
+    OFFLINE_MODE.store(enabled, Ordering::SeqCst);
+}
+
+/// Fails with [`NetworkError::NetworkDisabled`] in offline mode. Code that
+/// reaches the network without an HTTP client, such as `git push`, checks
+/// this first.
+pub fn ensure_enabled() -> Result<(), NetworkError> {
+    if offline_mode() {
+        return Err(NetworkError::NetworkDisabled);
+    }
+    Ok(())
+}
+
+/// An HTTP client with Sightline's user agent and the given timeout.
+pub fn http_client(timeout: Duration) -> Result<reqwest::blocking::Client, NetworkError> {
+    ensure_enabled()?;
+    Ok(reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .user_agent(concat!("Sightline/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+/// Opens a TCP connection to `addr`.
+pub fn connect(addr: SocketAddr, timeout: Duration) -> Result<TcpStream, NetworkError> {
+    ensure_enabled()?;
+    Ok(TcpStream::connect_timeout(&addr, timeout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_mode_refuses_clients_and_connections() {
+        set_offline_mode(true);
+        let addr = SocketAddr::from(([127, 0, 0, 1], 9));
+        assert!(matches!(
+            http_client(Duration::from_secs(1)),
+            Err(NetworkError::NetworkDisabled)
+        ));
+        assert!(matches!(
+            connect(addr, Duration::from_secs(1)),
+            Err(NetworkError::NetworkDisabled)
+        ));
+
+        set_offline_mode(false);
+        assert!(http_client(Duration::from_secs(1)).is_ok());
+    }
+}
@@ -0,0 +1,244 @@
+//! A public "now page" built from blocks tagged `#now`, written to a local
+//! file and optionally committed and pushed when that file is in a git
+//! repository.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::network::{self, NetworkError};
+use crate::timeline::{ArchivedContent, SensitiveContent, Timeline};
+
+/// Blocks tagged with this root tag (or a child) make up the page.
+pub const NOW_TAG: &str = "now";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NowPageFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NowPageConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: NowPageFormat,
+    /// Commit and push the page on [`publish`]; `path` must be inside a git
+    /// work tree with an upstream.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub git: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NowPageStatus {
+    pub path: PathBuf,
+    pub blocks: usize,
+    /// Whether the file's contents differed from the new render.
+    pub changed: bool,
+    pub committed: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum NowPageError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error("git {command} failed: {message}")]
+    Git {
+        command: &'static str,
+        message: String,
+    },
+}
+
+/// Renders the `#now` blocks, newest first. Sensitive blocks are never
+/// included, since the page is public, and neither are archived ones.
+pub fn render(timeline: &Timeline, format: NowPageFormat) -> (String, usize) {
+    let mut blocks = timeline
+        .tag_registry()
+        .find_id(None, NOW_TAG)
+        .map(|root| {
+            timeline.blocks_tagged(root, SensitiveContent::Masked, ArchivedContent::Excluded)
+        })
+        .unwrap_or_default();
+    blocks.retain(|block| !block.text.trim().is_empty());
+    blocks.sort_by(|a, b| b.date.cmp(&a.date));
+    let updated = blocks.first().map(|block| block.date.to_string());
+
+    let page = match format {
+        NowPageFormat::Markdown => {
+            let mut page = String::from("# Now\n\n");
+            if let Some(updated) = &updated {
+                page.push_str(&format!("_Updated {updated}_\n\n"));
+            }
+            let sections: Vec<&str> = blocks.iter().map(|block| block.text.trim()).collect();
+            page.push_str(&sections.join("\n\n"));
+            page.push('\n');
+            page
+        }
+        NowPageFormat::Html => {
+            let mut page = String::from(
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Now</title></head>\n<body>\n<h1>Now</h1>\n",
+            );
+            if let Some(updated) = &updated {
+                page.push_str(&format!("<p><em>Updated {updated}</em></p>\n"));
+            }
+            for block in &blocks {
+                let lines: Vec<String> = block.text.trim().lines().map(escape).collect();
+                page.push_str(&format!(
+                    "<section><p>{}</p></section>\n",
+                    lines.join("<br>\n")
+                ));
+            }
+            page.push_str("</body>\n</html>\n");
+            page
+        }
+    };
+    (page, blocks.len())
+}
+
+/// Writes the page to `config.path` if its contents changed.
+pub fn write(timeline: &Timeline, config: &NowPageConfig) -> Result<NowPageStatus, NowPageError> {
+    let (page, blocks) = render(timeline, config.format);
+    let changed = write_page(&config.path, &page)?;
+    Ok(NowPageStatus {
+        path: config.path.clone(),
+        blocks,
+        changed,
+        committed: false,
+    })
+}
+
+/// Writes an already rendered page, leaving an identical file untouched.
+/// Returns whether the file was written.
+pub fn write_page(path: &Path, page: &str) -> io::Result<bool> {
+    if fs::read_to_string(path).ok().as_deref() == Some(page) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, page)?;
+    Ok(true)
+}
+
+/// Writes the page and, for git targets, commits and pushes it when the
+/// file has uncommitted changes. In offline mode a git target is written
+/// but not committed, so a later publish still pushes it.
+pub fn publish(timeline: &Timeline, config: &NowPageConfig) -> Result<NowPageStatus, NowPageError> {
+    let mut status = write(timeline, config)?;
+    if config.git {
+        network::ensure_enabled()?;
+    }
+    if config.git && has_uncommitted_changes(&config.path)? {
+        git(&config.path, "add", &["add", "--"])?;
+        git(
+            &config.path,
+            "commit",
+            &["commit", "-m", "Update now page", "--"],
+        )?;
+        git(&config.path, "push", &["push"])?;
+        status.committed = true;
+    }
+    Ok(status)
+}
+
+fn has_uncommitted_changes(path: &Path) -> Result<bool, NowPageError> {
+    let output = git(path, "status", &["status", "--porcelain", "--"])?;
+    Ok(!output.trim().is_empty())
+}
+
+/// Runs git from the page's directory. Pathspec-taking commands end their
+/// `args` with `--` and get the page's file name appended.
+fn git(path: &Path, command: &'static str, args: &[&str]) -> Result<String, NowPageError> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut git = Command::new("git");
+    git.arg("-C").arg(dir).args(args);
+    if args.last() == Some(&"--") {
+        git.arg(path.file_name().unwrap_or_default());
+    }
+
+    let output = git.output()?;
+    if !output.status.success() {
+        return Err(NowPageError::Git {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn timeline() -> Timeline {
+        let snapshot = serde_json::json!({
+            "version": 1,
+            "blocks": [
+                {"date": "2025-03-01", "text": "Reading Dune\n", "tags": [1]},
+                {"date": "2025-03-02", "text": "Not on the page\n", "tags": []},
+                {"date": "2025-03-04", "text": "Learning <Rust> & Go\n", "tags": [2]},
+                {"date": "2025-03-05", "text": "Secret plans\n", "tags": [1, 3]},
+                {"date": "2025-03-06", "text": "Old plans\n", "tags": [1], "archived": true}
+            ],
+            "tag_registry": [
+                {"id": 1, "name": NOW_TAG, "parent_id": null},
+                {"id": 2, "name": "work", "parent_id": 1},
+                {"id": 3, "name": "sensitive", "parent_id": null}
+            ]
+        });
+        Timeline::from_snapshot_json(snapshot.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn render_lists_now_blocks_newest_first() {
+        let (markdown, blocks) = render(&timeline(), NowPageFormat::Markdown);
+        assert_eq!(blocks, 2);
+        assert_eq!(
+            markdown,
+            "# Now\n\n_Updated 2025-03-04_\n\nLearning <Rust> & Go\n\nReading Dune\n"
+        );
+
+        let (html, _) = render(&timeline(), NowPageFormat::Html);
+        assert!(html.contains("<section><p>Learning &lt;Rust&gt; &amp; Go</p></section>"));
+        assert!(!html.contains("Secret"));
+        assert!(!html.contains("Old plans"));
+    }
+
+    #[test]
+    fn write_only_touches_the_file_when_the_page_changes() {
+        let dir = tempdir().unwrap();
+        let config = NowPageConfig {
+            path: dir.path().join("site/now.md"),
+            format: NowPageFormat::Markdown,
+            git: false,
+        };
+
+        let status = write(&timeline(), &config).unwrap();
+        assert!(status.changed);
+        assert!(fs::read_to_string(&config.path)
+            .unwrap()
+            .contains("Reading Dune"));
+        assert!(!write(&timeline(), &config).unwrap().changed);
+        assert!(!publish(&timeline(), &config).unwrap().committed);
+    }
+}
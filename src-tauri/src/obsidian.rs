@@ -0,0 +1,192 @@
+//! Readers for Obsidian vault configuration that affects where daily notes
+//! live and how they are named, shared by the importer, the in-app import
+//! and markdown export.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+const DAILY_NOTES_CONFIG: &str = ".obsidian/daily-notes.json";
+
+/// Obsidian's daily-notes plugin uses this format when none is configured.
+const DEFAULT_DAILY_NOTE_FORMAT: &str = "YYYY-MM-DD";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DailyNotesSettings {
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Vault-relative folder new daily notes go in; the vault root when
+    /// empty.
+    #[serde(default)]
+    pub folder: Option<String>,
+}
+
+impl DailyNotesSettings {
+    /// Reads `.obsidian/daily-notes.json` from `vault`, returning `None` when
+    /// the vault has no daily-notes configuration.
+    pub fn load(vault: &Path) -> io::Result<Option<Self>> {
+        let path = vault.join(DAILY_NOTES_CONFIG);
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map(Some).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("failed to parse '{}': {err}", path.display()),
+                )
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The configured folder without surrounding slashes, or `None` for
+    /// the vault root.
+    pub fn folder(&self) -> Option<&str> {
+        self.folder
+            .as_deref()
+            .map(|folder| folder.trim().trim_matches('/'))
+            .filter(|folder| !folder.is_empty())
+    }
+
+    /// Vault-relative path of `date`'s note, or `None` when the format uses
+    /// tokens chrono can't write.
+    pub fn note_path(&self, date: NaiveDate) -> Option<PathBuf> {
+        let format = moment_to_chrono(self.format())?;
+        let name = format!("{}.md", date.format(&format));
+        Some(match self.folder() {
+            Some(folder) => Path::new(folder).join(name),
+            None => PathBuf::from(name),
+        })
+    }
+
+    /// The configured moment.js format, or Obsidian's default.
+    pub fn format(&self) -> &str {
+        self.format
+            .as_deref()
+            .map(str::trim)
+            .filter(|format| !format.is_empty())
+            .unwrap_or(DEFAULT_DAILY_NOTE_FORMAT)
+    }
+
+    /// A chrono format string matching daily-note file stems. Formats may
+    /// contain `/` to nest notes in folders; only the final segment names the
+    /// file. Returns `None` when the format uses tokens chrono can't parse.
+    pub fn file_name_format(&self) -> Option<String> {
+        let format = self.format();
+        let file_segment = format.rsplit('/').next().unwrap_or(format);
+        moment_to_chrono(file_segment)
+    }
+}
+
+/// Converts a moment.js date format (as used by Obsidian) into a chrono
+/// format string. Only date and weekday tokens are supported.
+pub fn moment_to_chrono(format: &str) -> Option<String> {
+    let mut output = String::with_capacity(format.len() * 2);
+    let mut chars = format.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '[' {
+            for literal in chars.by_ref() {
+                if literal == ']' {
+                    break;
+                }
+                push_literal(&mut output, literal);
+            }
+            continue;
+        }
+
+        if !ch.is_ascii_alphabetic() {
+            push_literal(&mut output, ch);
+            continue;
+        }
+
+        let mut run = 1;
+        while chars.peek() == Some(&ch) {
+            chars.next();
+            run += 1;
+        }
+
+        let token = match (ch, run) {
+            ('Y', 4) => "%Y",
+            ('Y', 2) => "%y",
+            ('M', 4) => "%B",
+            ('M', 3) => "%b",
+            ('M', 1 | 2) => "%m",
+            ('D', 1 | 2) if chars.peek() != Some(&'o') => "%d",
+            ('d', 4) => "%A",
+            ('d', 3) => "%a",
+            _ => return None,
+        };
+        output.push_str(token);
+    }
+
+    Some(output)
+}
+
+fn push_literal(output: &mut String, ch: char) {
+    if ch == '%' {
+        output.push_str("%%");
+    } else {
+        output.push(ch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moment_to_chrono_converts_common_formats() {
+        assert_eq!(moment_to_chrono("YYYY-MM-DD").as_deref(), Some("%Y-%m-%d"));
+        assert_eq!(
+            moment_to_chrono("dddd, MMMM D, YYYY").as_deref(),
+            Some("%A, %B %d, %Y")
+        );
+        assert_eq!(
+            moment_to_chrono("[Journal] DD.MM.YY").as_deref(),
+            Some("Journal %d.%m.%y")
+        );
+    }
+
+    #[test]
+    fn moment_to_chrono_rejects_unsupported_tokens() {
+        assert_eq!(moment_to_chrono("gggg-[W]ww"), None);
+        assert_eq!(moment_to_chrono("MMMM Do, YYYY"), None);
+    }
+
+    #[test]
+    fn file_name_format_uses_last_path_segment() {
+        let settings = DailyNotesSettings {
+            format: Some("YYYY/MM/YYYY-MM-DD".to_string()),
+            folder: None,
+        };
+        assert_eq!(settings.file_name_format().as_deref(), Some("%Y-%m-%d"));
+        assert_eq!(
+            DailyNotesSettings::default().file_name_format().as_deref(),
+            Some("%Y-%m-%d")
+        );
+    }
+
+    #[test]
+    fn note_paths_follow_folder_and_format() {
+        let date = NaiveDate::from_ymd_opt(2025, 4, 3).unwrap();
+        let settings: DailyNotesSettings =
+            serde_json::from_str(r#"{"format": "YYYY/MM/DD.MM.YYYY", "folder": "/Daily/"}"#)
+                .unwrap();
+        assert_eq!(settings.folder(), Some("Daily"));
+        assert_eq!(
+            settings.note_path(date),
+            Some(PathBuf::from("Daily/2025/04/03.04.2025.md"))
+        );
+
+        let root: DailyNotesSettings = serde_json::from_str(r#"{"folder": ""}"#).unwrap();
+        assert_eq!(root.folder(), None);
+        assert_eq!(root.note_path(date), Some(PathBuf::from("2025-04-03.md")));
+
+        let weekly: DailyNotesSettings =
+            serde_json::from_str(r#"{"format": "gggg-[W]ww"}"#).unwrap();
+        assert_eq!(weekly.note_path(date), None);
+    }
+}
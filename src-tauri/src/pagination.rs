@@ -0,0 +1,118 @@
+//! Offset pagination for commands whose results grow with the timeline,
+//! so the UI can load them a page at a time. Block indexes shift as the
+//! timeline is edited, so each page carries the version it was read at;
+//! a page from a different version means starting over.
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PageRequest {
+    pub offset: usize,
+    /// `None` returns everything from `offset` on.
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Results across all pages.
+    pub total: usize,
+    /// Offset of the next page, or `None` on the last one.
+    pub next_offset: Option<usize>,
+    /// Timeline version the results were read at.
+    pub version: u64,
+    /// The search stopped early, so `total` only counts what it found.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl PageRequest {
+    /// A limit of zero is treated as one, so paging always moves forward.
+    pub fn new(offset: Option<usize>, limit: Option<usize>) -> Self {
+        Self {
+            offset: offset.unwrap_or(0),
+            limit: limit.map(|limit| limit.max(1)),
+        }
+    }
+
+    /// Positions of this page's items among `total`.
+    pub fn bounds(&self, total: usize) -> Range<usize> {
+        let start = self.offset.min(total);
+        let end = self
+            .limit
+            .map_or(total, |limit| start.saturating_add(limit).min(total));
+        start..end
+    }
+
+    /// This page of `items`.
+    pub fn page<T>(&self, mut items: Vec<T>, version: u64) -> Page<T> {
+        let total = items.len();
+        let bounds = self.bounds(total);
+        items.truncate(bounds.end);
+        items.drain(..bounds.start);
+        Page::new(items, bounds.end, total, version)
+    }
+}
+
+impl<T> Page<T> {
+    /// A page of `items` ending at position `end` of `total`.
+    pub fn new(items: Vec<T>, end: usize, total: usize, version: u64) -> Self {
+        Self {
+            items,
+            total,
+            next_offset: (end < total).then_some(end),
+            version,
+            truncated: false,
+        }
+    }
+
+    /// The same page with its items rearranged by `f`, such as grouped.
+    /// `total` and `next_offset` still count the original items.
+    pub fn map<U>(self, f: impl FnOnce(Vec<T>) -> Vec<U>) -> Page<U> {
+        Page {
+            items: f(self.items),
+            total: self.total,
+            next_offset: self.next_offset,
+            version: self.version,
+            truncated: self.truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_walk_through_items() {
+        let items: Vec<u32> = (0..5).collect();
+        let first = PageRequest::new(None, Some(2)).page(items.clone(), 7);
+        assert_eq!(
+            first,
+            Page {
+                items: vec![0, 1],
+                total: 5,
+                next_offset: Some(2),
+                version: 7,
+                truncated: false,
+            }
+        );
+
+        let last = PageRequest::new(Some(4), Some(2)).page(items.clone(), 7);
+        assert_eq!((last.items, last.next_offset), (vec![4], None));
+
+        let past_the_end = PageRequest::new(Some(9), Some(2)).page(items.clone(), 7);
+        assert!(past_the_end.items.is_empty());
+        assert_eq!(past_the_end.next_offset, None);
+
+        let all = PageRequest::default().page(items.clone(), 7);
+        assert_eq!(all.items, items);
+        assert_eq!(PageRequest::new(None, Some(0)).bounds(5), 0..1);
+
+        let grouped = first.map(|items| vec![items]);
+        assert_eq!(grouped.items, vec![vec![0, 1]]);
+        assert_eq!((grouped.total, grouped.next_offset), (5, Some(2)));
+    }
+}
@@ -0,0 +1,144 @@
+//! The passphrase that unlocks sensitive blocks for a session. Only an
+//! Argon2 hash of it is kept, in its own file beside the timeline, so
+//! bundles and exports never carry it.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Where [`Passphrase`] is saved, beside the timeline file.
+pub const PASSPHRASE_FILE: &str = "passphrase.json";
+
+#[derive(Debug, Error)]
+pub enum PassphraseError {
+    #[error("no passphrase is set for sensitive blocks")]
+    NotSet,
+    #[error("wrong passphrase")]
+    Wrong,
+    #[error("passphrase must not be empty")]
+    Empty,
+    #[error("failed to hash passphrase: {0}")]
+    Hash(argon2::password_hash::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredPassphrase {
+    /// PHC string, salt and parameters included.
+    hash: String,
+}
+
+pub struct Passphrase {
+    /// `None` keeps the hash in memory only.
+    path: Option<PathBuf>,
+    hash: Mutex<Option<String>>,
+}
+
+impl Passphrase {
+    /// Loads the hash saved at `path`. A missing file means no passphrase
+    /// is set; an unreadable one is logged and treated the same, so
+    /// sensitive blocks stay locked until a new one is set.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let hash = match path.as_deref().map(fs::read) {
+            Some(Ok(contents)) => serde_json::from_slice::<StoredPassphrase>(&contents)
+                .map(|stored| stored.hash)
+                .map_err(|err| tracing::warn!(%err, "ignoring unreadable passphrase file"))
+                .ok(),
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+                tracing::warn!(%err, "failed to read passphrase file");
+                None
+            }
+            _ => None,
+        };
+        Self {
+            path,
+            hash: Mutex::new(hash),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Checks `passphrase` against the saved hash.
+    pub fn verify(&self, passphrase: &str) -> Result<(), PassphraseError> {
+        let hash = self.lock();
+        let hash = hash.as_deref().ok_or(PassphraseError::NotSet)?;
+        let parsed = PasswordHash::new(hash).map_err(PassphraseError::Hash)?;
+        Argon2::default()
+            .verify_password(passphrase.as_bytes(), &parsed)
+            .map_err(|_| PassphraseError::Wrong)
+    }
+
+    /// Sets the passphrase to `new`. Once one is set, changing it needs
+    /// the `current` one.
+    pub fn set(&self, current: Option<&str>, new: &str) -> Result<(), PassphraseError> {
+        if new.is_empty() {
+            return Err(PassphraseError::Empty);
+        }
+        if self.is_set() {
+            self.verify(current.unwrap_or_default())?;
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(new.as_bytes(), &salt)
+            .map_err(PassphraseError::Hash)?
+            .to_string();
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let stored = StoredPassphrase { hash: hash.clone() };
+            fs::write(path, serde_json::to_vec_pretty(&stored)?)?;
+        }
+        *self.lock() = Some(hash);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<String>> {
+        self.hash.lock().expect("passphrase lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn passphrases_are_checked_against_the_saved_hash() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(PASSPHRASE_FILE);
+        let passphrase = Passphrase::load(Some(path.clone()));
+        assert!(matches!(
+            passphrase.verify("open sesame"),
+            Err(PassphraseError::NotSet)
+        ));
+
+        passphrase.set(None, "open sesame").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("open sesame"));
+
+        let loaded = Passphrase::load(Some(path));
+        assert!(loaded.verify("open sesame").is_ok());
+        assert!(matches!(
+            loaded.verify("guess"),
+            Err(PassphraseError::Wrong)
+        ));
+        assert!(matches!(
+            loaded.set(Some("guess"), "new"),
+            Err(PassphraseError::Wrong)
+        ));
+        loaded.set(Some("open sesame"), "new").unwrap();
+        assert!(loaded.verify("new").is_ok());
+    }
+}
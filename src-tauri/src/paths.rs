@@ -0,0 +1,294 @@
+//! Where sightline keeps its files. Every location is resolved here, each
+//! taking the first of, in order:
+//!
+//! 1. a command-line flag (`--data-dir`, `--timeline-path`),
+//! 2. an environment variable (`SIGHTLINE_DATA_DIR`,
+//!    `SIGHTLINE_TIMELINE_PATH`, `SIGHTLINE_LOG_DIR`,
+//!    `SIGHTLINE_BACKUP_DIR`),
+//! 3. the path settings in `sightline/paths.json` under the platform
+//!    config directory,
+//! 4. the default under that `sightline` directory.
+//!
+//! The data directory holds the default workspace and `profiles/<name>`
+//! for named ones. A timeline path override names the default workspace's
+//! timeline in the same way: a profile's sits in `profiles/<name>` beside
+//! it, under the same file name. Attachments, thumbnails, the job queue,
+//! the search history, the persistence policy, the sensitive-block
+//! passphrase and the digest schedule always sit beside the timeline file,
+//! since blocks link to attachments relative to it.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::attachments::ASSETS_DIR;
+use crate::digest::DIGEST_SCHEDULE_FILE;
+use crate::jobs::JOBS_FILE;
+use crate::launch::LaunchOptions;
+use crate::passphrase::PASSPHRASE_FILE;
+use crate::persistence::PERSISTENCE_FILE;
+use crate::search::SEARCH_HISTORY_FILE;
+use crate::thumbnails::THUMBNAILS_DIR;
+use crate::timeline::TimelinePersistenceError;
+
+pub const DATA_DIR_ENV: &str = "SIGHTLINE_DATA_DIR";
+pub const TIMELINE_PATH_ENV: &str = "SIGHTLINE_TIMELINE_PATH";
+pub const LOG_DIR_ENV: &str = "SIGHTLINE_LOG_DIR";
+pub const BACKUP_DIR_ENV: &str = "SIGHTLINE_BACKUP_DIR";
+
+/// Path settings, read from the default data directory.
+pub const SETTINGS_FILE: &str = "paths.json";
+pub const TIMELINE_FILE: &str = "timeline.json";
+const PROFILES_DIR: &str = "profiles";
+const LOGS_DIR: &str = "logs";
+const BACKUPS_DIR: &str = "backups";
+
+/// Locations the user has chosen in settings.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AppPaths {
+    /// Holds the default workspace and every profile. When no config
+    /// directory is available it falls back to the timeline's directory.
+    pub data_dir: PathBuf,
+    pub timeline: PathBuf,
+    pub assets: PathBuf,
+    pub thumbnails: PathBuf,
+    pub jobs: PathBuf,
+    pub search_history: PathBuf,
+    /// The workspace's [`crate::persistence::PersistencePolicy`].
+    pub persistence: PathBuf,
+    /// The hash of the passphrase that unlocks sensitive blocks.
+    pub passphrase: PathBuf,
+    /// The workspace's [`crate::digest::DigestSchedule`].
+    pub digest_schedule: PathBuf,
+    pub logs: PathBuf,
+    pub backups: PathBuf,
+}
+
+impl AppPaths {
+    /// Resolves every path for `options` from the process environment and
+    /// the platform config directory.
+    pub fn resolve(options: &LaunchOptions) -> Result<Self, TimelinePersistenceError> {
+        Self::resolve_with(options, |key| env::var(key).ok(), dirs::config_dir())
+    }
+
+    fn resolve_with(
+        options: &LaunchOptions,
+        var: impl Fn(&str) -> Option<String>,
+        config_dir: Option<PathBuf>,
+    ) -> Result<Self, TimelinePersistenceError> {
+        let default_root = config_dir.map(|dir| dir.join("sightline"));
+        let settings = default_root
+            .as_deref()
+            .map(load_settings)
+            .unwrap_or_default();
+        let from_env = |key: &str| {
+            var(key)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let data_dir = options
+            .data_dir
+            .clone()
+            .or_else(|| from_env(DATA_DIR_ENV))
+            .or(settings.data_dir)
+            .or(default_root);
+        let workspace_dir = data_dir
+            .as_deref()
+            .map(|root| workspace_dir(root, options.profile.as_deref()));
+        let overridden = options
+            .timeline_path
+            .clone()
+            .or_else(|| from_env(TIMELINE_PATH_ENV));
+        let override_dir = overridden
+            .as_deref()
+            .map(|path| path.parent().unwrap_or(Path::new("")).to_path_buf());
+        let timeline = match &overridden {
+            Some(path) => in_profile(path, options.profile.as_deref()),
+            None => workspace_dir
+                .as_ref()
+                .map(|dir| dir.join(TIMELINE_FILE))
+                .ok_or(TimelinePersistenceError::MissingConfigDir)?,
+        };
+
+        let beside = timeline.parent().unwrap_or(Path::new("")).to_path_buf();
+        let workspace_dir = workspace_dir.unwrap_or_else(|| beside.clone());
+        Ok(Self {
+            data_dir: data_dir.or(override_dir).unwrap_or_else(|| beside.clone()),
+            assets: beside.join(ASSETS_DIR),
+            thumbnails: beside.join(THUMBNAILS_DIR),
+            jobs: beside.join(JOBS_FILE),
+            search_history: beside.join(SEARCH_HISTORY_FILE),
+            persistence: beside.join(PERSISTENCE_FILE),
+            passphrase: beside.join(PASSPHRASE_FILE),
+            digest_schedule: beside.join(DIGEST_SCHEDULE_FILE),
+            logs: from_env(LOG_DIR_ENV)
+                .or(settings.log_dir)
+                .unwrap_or_else(|| workspace_dir.join(LOGS_DIR)),
+            backups: from_env(BACKUP_DIR_ENV)
+                .or(settings.backup_dir)
+                .unwrap_or_else(|| workspace_dir.join(BACKUPS_DIR)),
+            timeline,
+        })
+    }
+}
+
+/// Directory of `profile`'s workspace under the data directory.
+pub fn workspace_dir(data_dir: &Path, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => data_dir.join(PROFILES_DIR).join(name),
+        None => data_dir.to_path_buf(),
+    }
+}
+
+/// `profile`'s timeline when `timeline` is the default workspace's.
+fn in_profile(timeline: &Path, profile: Option<&str>) -> PathBuf {
+    match (timeline.parent(), timeline.file_name()) {
+        (Some(dir), Some(name)) => workspace_dir(dir, profile).join(name),
+        _ => timeline.to_path_buf(),
+    }
+}
+
+/// The settings in `dir`. A missing file means none; an unreadable one is
+/// logged and ignored.
+fn load_settings(dir: &Path) -> PathSettings {
+    let path = dir.join(SETTINGS_FILE);
+    match fs::read(&path) {
+        Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+            tracing::warn!(%err, path = %path.display(), "ignoring unreadable path settings");
+            PathSettings::default()
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => PathSettings::default(),
+        Err(err) => {
+            tracing::warn!(%err, path = %path.display(), "failed to read path settings");
+            PathSettings::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn defaults_live_under_the_config_dir() {
+        let config = PathBuf::from("/config");
+        let paths = AppPaths::resolve_with(&LaunchOptions::default(), no_env, Some(config.clone()))
+            .unwrap();
+        let root = config.join("sightline");
+        assert_eq!(paths.data_dir, root);
+        assert_eq!(paths.timeline, root.join("timeline.json"));
+        assert_eq!(paths.assets, root.join("assets"));
+        assert_eq!(paths.jobs, root.join("jobs.json"));
+        assert_eq!(paths.logs, root.join("logs"));
+
+        let profile = LaunchOptions {
+            profile: Some("dev".to_string()),
+            ..LaunchOptions::default()
+        };
+        let paths = AppPaths::resolve_with(&profile, no_env, Some(config)).unwrap();
+        assert_eq!(paths.data_dir, root);
+        assert_eq!(paths.timeline, root.join("profiles/dev/timeline.json"));
+        assert_eq!(paths.backups, root.join("profiles/dev/backups"));
+
+        assert!(matches!(
+            AppPaths::resolve_with(&LaunchOptions::default(), no_env, None),
+            Err(TimelinePersistenceError::MissingConfigDir)
+        ));
+    }
+
+    #[test]
+    fn flags_beat_env_beat_settings() {
+        let config = tempdir().unwrap();
+        let root = config.path().join("sightline");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join(SETTINGS_FILE),
+            r#"{"data_dir": "/settings", "log_dir": "/settings-logs"}"#,
+        )
+        .unwrap();
+        let env = |key: &str| match key {
+            DATA_DIR_ENV => Some("/env".to_string()),
+            BACKUP_DIR_ENV => Some("/env-backups".to_string()),
+            _ => None,
+        };
+        let config_dir = || Some(config.path().to_path_buf());
+
+        let from_settings =
+            AppPaths::resolve_with(&LaunchOptions::default(), no_env, config_dir()).unwrap();
+        assert_eq!(from_settings.timeline, Path::new("/settings/timeline.json"));
+        assert_eq!(from_settings.logs, Path::new("/settings-logs"));
+
+        let from_env =
+            AppPaths::resolve_with(&LaunchOptions::default(), env, config_dir()).unwrap();
+        assert_eq!(from_env.data_dir, Path::new("/env"));
+        assert_eq!(from_env.backups, Path::new("/env-backups"));
+        assert_eq!(from_env.logs, Path::new("/settings-logs"));
+
+        let flags = LaunchOptions {
+            data_dir: Some(PathBuf::from("/flag")),
+            timeline_path: Some(PathBuf::from("/elsewhere/journal.json")),
+            ..LaunchOptions::default()
+        };
+        let from_flags = AppPaths::resolve_with(&flags, env, config_dir()).unwrap();
+        assert_eq!(from_flags.data_dir, Path::new("/flag"));
+        assert_eq!(from_flags.timeline, Path::new("/elsewhere/journal.json"));
+        assert_eq!(from_flags.assets, Path::new("/elsewhere/assets"));
+    }
+
+    #[test]
+    fn a_timeline_path_works_without_a_config_dir() {
+        let env =
+            |key: &str| (key == TIMELINE_PATH_ENV).then(|| "/tmp/t/timeline.json".to_string());
+        let paths = AppPaths::resolve_with(&LaunchOptions::default(), env, None).unwrap();
+        assert_eq!(paths.data_dir, Path::new("/tmp/t"));
+        assert_eq!(paths.thumbnails, Path::new("/tmp/t/thumbnails"));
+        assert_eq!(paths.logs, Path::new("/tmp/t/logs"));
+    }
+
+    #[test]
+    fn a_timeline_path_is_resolved_within_the_profile() {
+        let env = |key: &str| (key == TIMELINE_PATH_ENV).then(|| "/tmp/t/journal.json".to_string());
+        let profile = LaunchOptions {
+            profile: Some("dev".to_string()),
+            ..LaunchOptions::default()
+        };
+        let paths = AppPaths::resolve_with(&profile, env, None).unwrap();
+        assert_eq!(paths.data_dir, Path::new("/tmp/t"));
+        assert_eq!(
+            paths.timeline,
+            Path::new("/tmp/t/profiles/dev/journal.json")
+        );
+        assert_eq!(paths.assets, Path::new("/tmp/t/profiles/dev/assets"));
+        assert_eq!(paths.logs, Path::new("/tmp/t/profiles/dev/logs"));
+
+        let flagged = LaunchOptions {
+            timeline_path: Some(PathBuf::from("/elsewhere/journal.json")),
+            ..profile
+        };
+        let paths = AppPaths::resolve_with(&flagged, env, Some(PathBuf::from("/config"))).unwrap();
+        assert_eq!(
+            paths.timeline,
+            Path::new("/elsewhere/profiles/dev/journal.json")
+        );
+        assert_eq!(paths.data_dir, Path::new("/config/sightline"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Per-command latency and response-size histograms, so slow commands and
+//! oversized IPC payloads show up in the field rather than only in
+//! benchmarks.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::streams::MAX_RESPONSE_BYTES;
+
+/// Commands slower than this are logged.
+pub const SLOW_COMMAND: Duration = Duration::from_millis(250);
+
+/// Responses larger than this, serialized, are logged.
+pub const LARGE_RESPONSE: u64 = 1024 * 1024;
+
+const LATENCY_BOUNDS_MS: [u64; 8] = [1, 5, 10, 50, 100, 250, 1_000, 5_000];
+const SIZE_BOUNDS: [u64; 6] = [1 << 10, 10 << 10, 100 << 10, 1 << 20, 10 << 20, 100 << 20];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Bucket {
+    /// Inclusive upper bound; `None` for values above the last bound.
+    pub le: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    pub buckets: Vec<Bucket>,
+    pub count: u64,
+    pub sum: u64,
+    pub max: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[u64]) -> Self {
+        let buckets = bounds
+            .iter()
+            .map(|&bound| Some(bound))
+            .chain([None])
+            .map(|le| Bucket { le, count: 0 })
+            .collect();
+        Self {
+            buckets,
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+
+    fn record(&mut self, value: u64) {
+        if let Some(bucket) = self
+            .buckets
+            .iter_mut()
+            .find(|bucket| bucket.le.is_none_or(|le| value <= le))
+        {
+            bucket.count += 1;
+        }
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub errors: u64,
+    pub latency_ms: Histogram,
+    /// Size of the JSON response, or of the error message.
+    pub response_bytes: Histogram,
+}
+
+#[derive(Default)]
+pub struct PerfMetrics {
+    commands: Mutex<BTreeMap<&'static str, CommandMetrics>>,
+}
+
+impl PerfMetrics {
+    /// Runs a command body and records how long it took and how large its
+    /// response is. Responses over [`MAX_RESPONSE_BYTES`] are refused with
+    /// an error, so no command can hand the frontend an unbounded payload.
+    /// The check comes after the body has run, so commands that change
+    /// state return small results, e.g. a version, rather than documents.
+    /// Sizing counts the response's JSON without allocating; a
+    /// [`crate::streams::Chunked`] response is already JSON, so counting it
+    /// just walks the text [`crate::streams::Streams::respond`] produced.
+    pub fn measure<T: Serialize>(
+        &self,
+        command: &'static str,
+        run: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.measure_within(command, MAX_RESPONSE_BYTES as u64, run)
+    }
+
+    fn measure_within<T: Serialize>(
+        &self,
+        command: &'static str,
+        limit: u64,
+        run: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let started = Instant::now();
+        let result = match run() {
+            Ok(value) => match response_size(&value) {
+                bytes if bytes > limit => Err(format!(
+                    "result is {bytes} bytes, over the {limit} byte limit"
+                )),
+                bytes => Ok((value, bytes)),
+            },
+            Err(message) => Err(message),
+        };
+        let elapsed = started.elapsed();
+        let bytes = match &result {
+            Ok((_, bytes)) => *bytes,
+            Err(message) => message.len() as u64,
+        };
+        self.record(command, elapsed, bytes, result.is_err());
+        result.map(|(value, _)| value)
+    }
+
+    /// Records one call, warning when it crosses [`SLOW_COMMAND`] or
+    /// [`LARGE_RESPONSE`].
+    pub fn record(&self, command: &'static str, elapsed: Duration, bytes: u64, failed: bool) {
+        if elapsed > SLOW_COMMAND {
+            tracing::warn!(
+                command,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "slow command"
+            );
+        }
+        if bytes > LARGE_RESPONSE {
+            tracing::warn!(command, bytes, "large command response");
+        }
+
+        let mut commands = self.commands();
+        let metrics = commands.entry(command).or_insert_with(|| CommandMetrics {
+            command: command.to_string(),
+            errors: 0,
+            latency_ms: Histogram::new(&LATENCY_BOUNDS_MS),
+            response_bytes: Histogram::new(&SIZE_BOUNDS),
+        });
+        metrics.errors += u64::from(failed);
+        metrics
+            .latency_ms
+            .record(elapsed.as_millis().try_into().unwrap_or(u64::MAX));
+        metrics.response_bytes.record(bytes);
+    }
+
+    /// Metrics for every command called so far, sorted by name.
+    pub fn snapshot(&self) -> Vec<CommandMetrics> {
+        self.commands().values().cloned().collect()
+    }
+
+    fn commands(&self) -> MutexGuard<'_, BTreeMap<&'static str, CommandMetrics>> {
+        self.commands.lock().expect("perf metrics lock poisoned")
+    }
+}
+
+/// Length of `value` as JSON, the way it crosses the IPC bridge.
+fn response_size<T: Serialize>(value: &T) -> u64 {
+    struct Counter(u64);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_records_latency_size_and_errors() {
+        let perf = PerfMetrics::default();
+        let value = perf.measure("list_blocks", || Ok(vec!["a", "b"]));
+        assert_eq!(value, Ok(vec!["a", "b"]));
+        let failed: Result<(), String> = perf.measure("list_blocks", || Err("nope".into()));
+        assert!(failed.is_err());
+        perf.record("search_prefix", Duration::from_millis(300), 2 << 20, false);
+
+        let metrics = perf.snapshot();
+        assert_eq!(metrics.len(), 2);
+        let list_blocks = &metrics[0];
+        assert_eq!(list_blocks.command, "list_blocks");
+        assert_eq!(list_blocks.errors, 1);
+        assert_eq!(list_blocks.latency_ms.count, 2);
+        assert_eq!(list_blocks.response_bytes.sum, 13);
+        assert_eq!(list_blocks.response_bytes.buckets[0].count, 2);
+
+        let search = &metrics[1];
+        assert_eq!(search.latency_ms.max, 300);
+        let slow = &search.latency_ms.buckets;
+        assert_eq!((slow[6].le, slow[6].count), (Some(1_000), 1));
+        let large = &search.response_bytes.buckets;
+        assert_eq!((large[4].le, large[4].count), (Some(10 << 20), 1));
+    }
+
+    #[test]
+    fn oversized_responses_are_refused() {
+        let perf = PerfMetrics::default();
+        let small = perf.measure_within("get_block", 8, || Ok("ok"));
+        assert_eq!(small, Ok("ok"));
+        let large = perf.measure_within("get_block", 8, || Ok("far too long"));
+        assert_eq!(
+            large,
+            Err("result is 14 bytes, over the 8 byte limit".to_string())
+        );
+
+        let metrics = perf.snapshot();
+        assert_eq!(metrics[0].errors, 1);
+        assert_eq!(metrics[0].response_bytes.max, 41);
+    }
+}
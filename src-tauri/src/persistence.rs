@@ -0,0 +1,266 @@
+//! How durably a workspace keeps its timeline, set per workspace in
+//! `persistence.json` beside the timeline file. A work journal can log
+//! every edit and take frequent backups; a scratch workspace can live in
+//! memory only.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::wal;
+
+pub const PERSISTENCE_FILE: &str = "persistence.json";
+
+const BACKUP_STAMP: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistencePolicy {
+    /// Keep the timeline in memory only: nothing is saved, logged or
+    /// backed up, and edits are lost when the app quits.
+    pub ephemeral: bool,
+    /// Append edits to the write-ahead log; without it every edit
+    /// rewrites the snapshot.
+    pub write_ahead_log: bool,
+    /// Log size at which the log is compacted into a snapshot.
+    pub compact_bytes: u64,
+    /// Hours between snapshot backups. A logged edit saves the snapshot
+    /// early once a backup is due, so the backup includes it. No backups
+    /// when unset.
+    pub backup_every_hours: Option<u32>,
+    /// Backups kept; the oldest beyond this are deleted.
+    pub keep_backups: usize,
+}
+
+impl Default for PersistencePolicy {
+    fn default() -> Self {
+        Self {
+            ephemeral: false,
+            write_ahead_log: true,
+            compact_bytes: wal::COMPACT_BYTES,
+            backup_every_hours: None,
+            keep_backups: 10,
+        }
+    }
+}
+
+impl PersistencePolicy {
+    /// The policy saved at `path`, or the default when there is none. An
+    /// unreadable file is logged and ignored.
+    pub fn load(path: &Path) -> Self {
+        match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+                tracing::warn!(%err, path = %path.display(), "ignoring unreadable persistence policy");
+                Self::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                tracing::warn!(%err, path = %path.display(), "failed to read persistence policy");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Whether the policy takes backups and the newest backup of `snapshot`
+    /// in `dir` is at least the interval old.
+    pub fn backup_due(&self, snapshot: &Path, dir: &Path, now: DateTime<Utc>) -> io::Result<bool> {
+        if self.backup_every_hours.is_none() {
+            return Ok(false);
+        }
+        let (stem, extension) = backup_name_parts(snapshot);
+        Ok(self.due_after(&list_backups(dir, &stem, &extension)?, now))
+    }
+
+    fn due_after(&self, backups: &[(DateTime<Utc>, PathBuf)], now: DateTime<Utc>) -> bool {
+        self.backup_every_hours.is_some_and(|hours| {
+            backups
+                .last()
+                .is_none_or(|(taken, _)| now - *taken >= Duration::hours(i64::from(hours)))
+        })
+    }
+
+    /// Copies `snapshot` into `dir` if [`Self::backup_due`], then deletes
+    /// backups beyond [`Self::keep_backups`]. Returns the new backup's path.
+    pub fn back_up(
+        &self,
+        snapshot: &Path,
+        dir: &Path,
+        now: DateTime<Utc>,
+    ) -> io::Result<Option<PathBuf>> {
+        if self.backup_every_hours.is_none() {
+            return Ok(None);
+        }
+        self.copy_backup(snapshot, dir, now, true)
+    }
+
+    /// Copies `snapshot` into `dir` whether or not a backup is due, before
+    /// something replaces it wholesale, then deletes backups beyond
+    /// [`Self::keep_backups`]. Returns the new backup's path.
+    pub fn back_up_now(
+        &self,
+        snapshot: &Path,
+        dir: &Path,
+        now: DateTime<Utc>,
+    ) -> io::Result<Option<PathBuf>> {
+        self.copy_backup(snapshot, dir, now, false)
+    }
+
+    fn copy_backup(
+        &self,
+        snapshot: &Path,
+        dir: &Path,
+        now: DateTime<Utc>,
+        only_if_due: bool,
+    ) -> io::Result<Option<PathBuf>> {
+        if !snapshot.exists() {
+            return Ok(None);
+        }
+        let (stem, extension) = backup_name_parts(snapshot);
+        let mut backups = list_backups(dir, &stem, &extension)?;
+        if only_if_due && !self.due_after(&backups, now) {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{stem}-{}{extension}", now.format(BACKUP_STAMP)));
+        fs::copy(snapshot, &path)?;
+        backups.push((now, path.clone()));
+        let excess = backups.len().saturating_sub(self.keep_backups.max(1));
+        for (_, old) in backups.drain(..excess) {
+            fs::remove_file(old)?;
+        }
+        Ok(Some(path))
+    }
+}
+
+/// `timeline.json` backs up as `timeline-<stamp>.json`.
+fn backup_name_parts(snapshot: &Path) -> (String, String) {
+    let stem = snapshot
+        .file_stem()
+        .map_or_else(|| "timeline".into(), |stem| stem.to_string_lossy());
+    let extension = snapshot
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (stem.into_owned(), extension)
+}
+
+/// Backups of a snapshot in `dir`, oldest first.
+fn list_backups(
+    dir: &Path,
+    stem: &str,
+    extension: &str,
+) -> io::Result<Vec<(DateTime<Utc>, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let stamp = name
+            .strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(extension));
+        if let Some(taken) =
+            stamp.and_then(|stamp| NaiveDateTime::parse_from_str(stamp, BACKUP_STAMP).ok())
+        {
+            backups.push((taken.and_utc(), path));
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn policies_load_with_defaults_for_missing_fields() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(PERSISTENCE_FILE);
+        assert_eq!(PersistencePolicy::load(&path), PersistencePolicy::default());
+
+        fs::write(&path, r#"{"ephemeral": true}"#).unwrap();
+        let scratch = PersistencePolicy::load(&path);
+        assert!(scratch.ephemeral && scratch.write_ahead_log);
+
+        let journal = PersistencePolicy {
+            backup_every_hours: Some(6),
+            ..PersistencePolicy::default()
+        };
+        journal.save(&path).unwrap();
+        assert_eq!(PersistencePolicy::load(&path), journal);
+    }
+
+    #[test]
+    fn backups_follow_the_interval_and_are_pruned() {
+        let dir = tempdir().unwrap();
+        let snapshot = dir.path().join("timeline.json");
+        let backups = dir.path().join("backups");
+        let policy = PersistencePolicy {
+            backup_every_hours: Some(1),
+            keep_backups: 2,
+            ..PersistencePolicy::default()
+        };
+        let at = |hour, minute| Utc.with_ymd_and_hms(2025, 3, 1, hour, minute, 0).unwrap();
+
+        assert_eq!(policy.back_up(&snapshot, &backups, at(9, 0)).unwrap(), None);
+        fs::write(&snapshot, "{}").unwrap();
+        let first = policy.back_up(&snapshot, &backups, at(9, 0)).unwrap();
+        assert_eq!(first, Some(backups.join("timeline-20250301T090000Z.json")));
+        assert!(!policy.backup_due(&snapshot, &backups, at(9, 30)).unwrap());
+        assert_eq!(
+            policy.back_up(&snapshot, &backups, at(9, 30)).unwrap(),
+            None
+        );
+        assert!(policy.backup_due(&snapshot, &backups, at(10, 0)).unwrap());
+        policy
+            .back_up(&snapshot, &backups, at(10, 0))
+            .unwrap()
+            .unwrap();
+        policy
+            .back_up(&snapshot, &backups, at(11, 0))
+            .unwrap()
+            .unwrap();
+
+        let kept: Vec<PathBuf> = list_backups(&backups, "timeline", ".json")
+            .unwrap()
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                backups.join("timeline-20250301T100000Z.json"),
+                backups.join("timeline-20250301T110000Z.json"),
+            ]
+        );
+
+        let off = PersistencePolicy::default();
+        assert!(!off.backup_due(&snapshot, &backups, at(23, 0)).unwrap());
+        assert_eq!(off.back_up(&snapshot, &backups, at(23, 0)).unwrap(), None);
+        // Backups before a replacement are taken regardless.
+        assert_eq!(
+            off.back_up_now(&snapshot, &backups, at(23, 0)).unwrap(),
+            Some(backups.join("timeline-20250301T230000Z.json"))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Noticing when the date changes while the app is open. Typed text is
+//! dated as it arrives, but anything that was worked out for "today" when
+//! the session started (the daily note, the UI's idea of the current day)
+//! has to be told. A watcher checks the clock every [`CHECK_INTERVAL`] and
+//! emits [`DAY_CHANGED_EVENT`] when the day turns. Jobs the new day makes
+//! due are left to the job monitor, the only thing that runs them, so no
+//! job runs twice at midnight.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::AppState;
+
+/// Emitted with a [`DayChange`] when the date changes.
+pub const DAY_CHANGED_EVENT: &str = "day-changed";
+
+/// How often the watcher reads the clock.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DayChange {
+    pub previous: NaiveDate,
+    pub today: NaiveDate,
+    /// Where the new day's note was inserted, if one was created.
+    pub daily_note: Option<usize>,
+}
+
+/// The day the session last saw.
+#[derive(Debug)]
+pub struct DayWatch {
+    day: Mutex<NaiveDate>,
+}
+
+impl DayWatch {
+    pub fn new(today: NaiveDate) -> Self {
+        Self {
+            day: Mutex::new(today),
+        }
+    }
+
+    pub fn day(&self) -> NaiveDate {
+        *self.day.lock().expect("day watch lock poisoned")
+    }
+
+    /// Records `today` and returns the day it replaces, if the date has
+    /// changed. Moving the clock back counts as a change too.
+    pub fn observe(&self, today: NaiveDate) -> Option<NaiveDate> {
+        let mut day = self.day.lock().expect("day watch lock poisoned");
+        if *day == today {
+            return None;
+        }
+        Some(std::mem::replace(&mut *day, today))
+    }
+}
+
+/// Checks for a new day every [`CHECK_INTERVAL`] and emits
+/// [`DAY_CHANGED_EVENT`] after handling it.
+pub fn spawn_watcher<R: Runtime>(app: AppHandle<R>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        let state = app.state::<AppState>();
+        if let Some(change) = state.roll_over() {
+            if let Err(err) = app.emit(DAY_CHANGED_EVENT, change) {
+                tracing::error!(?err, "failed to emit day-changed event");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observing_reports_each_change_once() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 2, d).unwrap();
+        let watch = DayWatch::new(day(28));
+        assert_eq!(watch.observe(day(28)), None);
+        assert_eq!(watch.observe(day(29)), Some(day(28)));
+        assert_eq!(watch.observe(day(29)), None);
+        assert_eq!(watch.day(), day(29));
+        assert_eq!(watch.observe(day(27)), Some(day(29)));
+    }
+}
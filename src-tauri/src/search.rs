@@ -0,0 +1,449 @@
+//! Snippets for text search results: the matching line, cut down to some
+//! context around the first match, with every match located in it so the
+//! results list can highlight them without fetching the block. Also the
+//! recently-used query history behind the search box, and the compiled
+//! pattern cache behind regex search.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Characters kept on each side of the first match when a line is cut.
+pub const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Recent queries kept.
+pub const SEARCH_HISTORY_LIMIT: usize = 50;
+
+/// Where [`SearchHistory`] is saved, beside the timeline file.
+pub const SEARCH_HISTORY_FILE: &str = "search_history.json";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentSearch {
+    pub query: String,
+    pub searched_at: DateTime<Utc>,
+}
+
+/// Puts `query` at the front of `history`, newest first, replacing an
+/// earlier use of the same query regardless of case. Blank queries aren't
+/// remembered; returns whether the history changed.
+pub fn remember(history: &mut Vec<RecentSearch>, query: &str, now: DateTime<Utc>) -> bool {
+    let query = query.trim();
+    if query.is_empty() {
+        return false;
+    }
+
+    let key = query.to_lowercase();
+    history.retain(|recent| recent.query.to_lowercase() != key);
+    history.insert(
+        0,
+        RecentSearch {
+            query: query.to_string(),
+            searched_at: now,
+        },
+    );
+    history.truncate(SEARCH_HISTORY_LIMIT);
+    true
+}
+
+/// The search box's recent queries. They live in their own file rather
+/// than the timeline snapshot, so a search never rewrites the timeline,
+/// and bundles and exports leave them out.
+pub struct SearchHistory {
+    /// `None` keeps the history in memory only.
+    path: Option<PathBuf>,
+    recent: Mutex<Vec<RecentSearch>>,
+}
+
+impl SearchHistory {
+    /// Loads the history saved at `path`. A missing file is an empty
+    /// history; an unreadable one is logged and replaced.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let recent = match path.as_deref().map(fs::read) {
+            Some(Ok(contents)) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+                tracing::warn!(%err, "discarding unreadable search history");
+                Vec::new()
+            }),
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+                tracing::warn!(%err, "failed to read search history");
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            path,
+            recent: Mutex::new(recent),
+        }
+    }
+
+    /// Whether a history file was found or has been written.
+    pub fn is_saved(&self) -> bool {
+        self.path.as_deref().is_some_and(|path| path.exists())
+    }
+
+    /// Recent queries, newest first.
+    pub fn recent(&self) -> Vec<RecentSearch> {
+        self.lock().clone()
+    }
+
+    /// Remembers `query` (see [`remember`]) and saves the history if it
+    /// changed.
+    pub fn record(&self, query: &str, now: DateTime<Utc>) -> io::Result<()> {
+        let mut recent = self.lock();
+        if remember(&mut recent, query, now) {
+            self.save(&recent)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the history, e.g. with one carried over from an older
+    /// timeline snapshot.
+    pub fn replace(&self, history: Vec<RecentSearch>) -> io::Result<()> {
+        let mut recent = self.lock();
+        *recent = history;
+        recent.truncate(SEARCH_HISTORY_LIMIT);
+        self.save(&recent)
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        self.replace(Vec::new())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<RecentSearch>> {
+        self.recent.lock().expect("search history lock poisoned")
+    }
+
+    fn save(&self, recent: &[RecentSearch]) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(recent)?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SearchSnippet {
+    pub text: String,
+    /// Matches within `text`, in order.
+    pub matches: Vec<MatchRange>,
+    /// Whether the line continues before or after `text`.
+    pub truncated_start: bool,
+    pub truncated_end: bool,
+}
+
+/// A match's position in a snippet, counted in characters (Unicode scalar
+/// values), end exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The snippet for a case-insensitive `query` in `line`: the trimmed line,
+/// or `context` characters either side of the first match when the line
+/// is longer than that. `None` if the query doesn't occur.
+pub fn snippet(line: &str, query: &str, context: usize) -> Option<SearchSnippet> {
+    let chars: Vec<char> = line.trim().chars().collect();
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return None;
+    }
+
+    let found = find_all(&chars, &needle);
+    let first = found.first()?;
+    let from = first.start.saturating_sub(context);
+    let to = (first.end + context).min(chars.len());
+    Some(SearchSnippet {
+        text: chars[from..to].iter().collect(),
+        matches: found
+            .iter()
+            .filter(|range| range.end <= to)
+            .map(|range| MatchRange {
+                start: range.start - from,
+                end: range.end - from,
+            })
+            .collect(),
+        truncated_start: from > 0,
+        truncated_end: to < chars.len(),
+    })
+}
+
+/// The snippet for the first non-empty match of `regex` in `text`: the
+/// line it starts on, cut like [`snippet`]. A match running past the end
+/// of the line is highlighted up to the line's end.
+pub fn regex_snippet(text: &str, regex: &Regex, context: usize) -> Option<SearchSnippet> {
+    let first = regex.find_iter(text).find(|found| !found.is_empty())?;
+    let line_start = text[..first.start()]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line_end = text[first.start()..]
+        .find('\n')
+        .map_or(text.len(), |newline| first.start() + newline);
+    let raw_line = &text[line_start..line_end];
+    let line = raw_line.trim();
+    let trimmed = raw_line.len() - raw_line.trim_start().len();
+
+    let chars_before = |byte: usize| line[..byte].chars().count();
+    let mut found: Vec<MatchRange> = regex
+        .find_iter(line)
+        .filter(|found| !found.is_empty())
+        .map(|found| MatchRange {
+            start: chars_before(found.start()),
+            end: chars_before(found.end()),
+        })
+        .collect();
+    if found.is_empty() {
+        let start = (first.start() - line_start)
+            .saturating_sub(trimmed)
+            .min(line.len());
+        found.push(MatchRange {
+            start: chars_before(start),
+            end: line.chars().count(),
+        });
+    }
+
+    let len = line.chars().count();
+    let from = found[0].start.saturating_sub(context);
+    let to = (found[0].end + context).min(len);
+    Some(SearchSnippet {
+        text: line.chars().skip(from).take(to - from).collect(),
+        matches: found
+            .iter()
+            .filter(|range| range.end <= to)
+            .map(|range| MatchRange {
+                start: range.start - from,
+                end: range.end - from,
+            })
+            .collect(),
+        truncated_start: from > 0,
+        truncated_end: to < len,
+    })
+}
+
+/// How many times `query` occurs in `text`, case-insensitively.
+pub fn count_matches(text: &str, query: &str) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return 0;
+    }
+    find_all(&chars, &needle).len()
+}
+
+/// Non-overlapping case-insensitive matches of `needle` (already
+/// lowercase) in `haystack`.
+fn find_all(haystack: &[char], needle: &[char]) -> Vec<MatchRange> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start < haystack.len() {
+        match match_len(&haystack[start..], needle) {
+            Some(len) => {
+                found.push(MatchRange {
+                    start,
+                    end: start + len,
+                });
+                start += len;
+            }
+            None => start += 1,
+        }
+    }
+    found
+}
+
+/// How many characters at the start of `haystack` lowercase to `needle`.
+/// Lowercasing can change a character's length, so the original length is
+/// counted separately.
+fn match_len(haystack: &[char], needle: &[char]) -> Option<usize> {
+    let mut lowered = Vec::with_capacity(needle.len());
+    for (index, ch) in haystack.iter().enumerate() {
+        lowered.extend(ch.to_lowercase());
+        if lowered.len() >= needle.len() {
+            return (lowered == needle).then_some(index + 1);
+        }
+    }
+    None
+}
+
+/// Compiled patterns kept for repeated searches.
+pub const REGEX_CACHE_SIZE: usize = 16;
+
+/// Largest compiled program a search pattern may build, so a pathological
+/// pattern fails to compile rather than using unbounded memory.
+pub const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Error)]
+pub enum RegexSearchError {
+    #[error("search pattern is empty")]
+    Empty,
+    #[error("invalid search pattern: {0}")]
+    Invalid(#[from] regex::Error),
+}
+
+/// The most recently used search patterns, compiled.
+#[derive(Debug, Default)]
+pub struct RegexCache {
+    compiled: Mutex<VecDeque<Regex>>,
+}
+
+impl RegexCache {
+    /// `pattern` compiled, from the cache when it was used recently.
+    pub fn get(&self, pattern: &str) -> Result<Regex, RegexSearchError> {
+        if pattern.is_empty() {
+            return Err(RegexSearchError::Empty);
+        }
+        let mut compiled = self.compiled.lock().expect("regex cache lock poisoned");
+        if let Some(index) = compiled.iter().position(|regex| regex.as_str() == pattern) {
+            let regex = compiled.remove(index).expect("index is in range");
+            compiled.push_front(regex.clone());
+            return Ok(regex);
+        }
+
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_SIZE_LIMIT)
+            .build()?;
+        compiled.push_front(regex.clone());
+        compiled.truncate(REGEX_CACHE_SIZE);
+        Ok(regex)
+    }
+
+    pub fn len(&self) -> usize {
+        self.compiled
+            .lock()
+            .expect("regex cache lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Clones start empty; compiled patterns are cheap to rebuild and not
+/// worth sharing between timeline copies.
+impl Clone for RegexCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn range(start: usize, end: usize) -> MatchRange {
+        MatchRange { start, end }
+    }
+
+    #[test]
+    fn snippets_locate_every_match_around_the_first() {
+        let short = snippet("  Plan the garden, then plant it  ", "PLAN", 40).unwrap();
+        assert_eq!(short.text, "Plan the garden, then plant it");
+        assert_eq!(short.matches, vec![range(0, 4), range(22, 26)]);
+        assert!(!short.truncated_start && !short.truncated_end);
+
+        let long = format!("{} needle {}", "a".repeat(50), "b".repeat(50));
+        let cut = snippet(&long, "needle", 3).unwrap();
+        assert_eq!(cut.text, "aa needle bb");
+        assert_eq!(cut.matches, vec![range(3, 9)]);
+        assert!(cut.truncated_start && cut.truncated_end);
+
+        let accented = snippet("Café ÉCOLE", "école", 5).unwrap();
+        assert_eq!(accented.matches, vec![range(5, 10)]);
+        assert_eq!(snippet("nothing here", "garden", 5), None);
+        assert_eq!(count_matches("Plan\nplanned PLANS", "plan"), 3);
+    }
+
+    #[test]
+    fn regex_snippets_use_the_line_of_the_first_match() {
+        let regex = Regex::new(r"\bTODO\b.*urgent").unwrap();
+        let text = "Monday\n  TODO: call the plumber, urgent  \nTODO later\n";
+        let found = regex_snippet(text, &regex, 40).unwrap();
+        assert_eq!(found.text, "TODO: call the plumber, urgent");
+        assert_eq!(found.matches, vec![range(0, 30)]);
+        assert!(!found.truncated_start && !found.truncated_end);
+
+        let spanning = Regex::new(r"(?s)plumber.*later").unwrap();
+        let found = regex_snippet(text, &spanning, 40).unwrap();
+        assert_eq!(found.matches, vec![range(15, 30)]);
+        assert_eq!(regex_snippet(text, &Regex::new("garden").unwrap(), 5), None);
+    }
+
+    #[test]
+    fn compiled_patterns_are_cached_and_bounded() {
+        let cache = RegexCache::default();
+        assert!(matches!(cache.get(""), Err(RegexSearchError::Empty)));
+        assert!(matches!(cache.get("("), Err(RegexSearchError::Invalid(_))));
+        assert!(matches!(
+            cache.get(r"\w{1000}{1000}"),
+            Err(RegexSearchError::Invalid(regex::Error::CompiledTooBig(_)))
+        ));
+        assert!(cache.is_empty());
+
+        for n in 0..=REGEX_CACHE_SIZE {
+            cache.get(&format!("pattern {n}")).unwrap();
+        }
+        cache.get("pattern 1").unwrap();
+        assert_eq!(cache.len(), REGEX_CACHE_SIZE);
+        assert_eq!(cache.compiled.lock().unwrap()[0].as_str(), "pattern 1");
+    }
+
+    #[test]
+    fn history_is_deduped_newest_first_and_capped() {
+        let at = |minute| Utc.with_ymd_and_hms(2024, 5, 1, 9, minute, 0).unwrap();
+        let mut history = Vec::new();
+        assert!(!remember(&mut history, "   ", at(0)));
+        for (minute, query) in ["garden", "plan", " Garden "].into_iter().enumerate() {
+            assert!(remember(&mut history, query, at(minute as u32)));
+        }
+        assert_eq!(
+            history,
+            vec![
+                RecentSearch {
+                    query: "Garden".to_string(),
+                    searched_at: at(2),
+                },
+                RecentSearch {
+                    query: "plan".to_string(),
+                    searched_at: at(1),
+                },
+            ]
+        );
+
+        for n in 0..SEARCH_HISTORY_LIMIT {
+            remember(&mut history, &format!("query {n}"), at(3));
+        }
+        assert_eq!(history.len(), SEARCH_HISTORY_LIMIT);
+        assert_eq!(
+            history[0].query,
+            format!("query {}", SEARCH_HISTORY_LIMIT - 1)
+        );
+    }
+
+    #[test]
+    fn search_history_is_saved_to_its_own_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SEARCH_HISTORY_FILE);
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+
+        let history = SearchHistory::load(Some(path.clone()));
+        assert!(!history.is_saved());
+        history.record("garden", now).unwrap();
+        assert!(history.is_saved());
+
+        let reloaded = SearchHistory::load(Some(path));
+        assert_eq!(reloaded.recent()[0].query, "garden");
+        reloaded.clear().unwrap();
+        assert!(reloaded.recent().is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! User-defined text snippets. Typing a trigger such as `:mtg` followed by
+//! whitespace replaces the trigger with its expansion.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snippet {
+    pub trigger: String,
+    pub expansion: String,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SnippetError {
+    #[error("snippet trigger cannot be empty")]
+    EmptyTrigger,
+    #[error("snippet trigger cannot contain whitespace")]
+    WhitespaceInTrigger,
+}
+
+impl Snippet {
+    pub fn new(trigger: &str, expansion: &str) -> Result<Self, SnippetError> {
+        let trigger = trigger.trim();
+        if trigger.is_empty() {
+            return Err(SnippetError::EmptyTrigger);
+        }
+        if trigger.chars().any(char::is_whitespace) {
+            return Err(SnippetError::WhitespaceInTrigger);
+        }
+
+        Ok(Self {
+            trigger: trigger.to_string(),
+            expansion: expansion.to_string(),
+        })
+    }
+
+    pub fn trigger_chars(&self) -> usize {
+        self.trigger.chars().count()
+    }
+}
+
+/// Finds the snippet whose trigger ends `before`. The trigger must start a
+/// word: either whitespace precedes it, or nothing does and `at_start` says
+/// `before` reaches the start of the document.
+pub fn match_trigger<'a>(
+    snippets: &'a [Snippet],
+    before: &str,
+    at_start: bool,
+) -> Option<&'a Snippet> {
+    snippets
+        .iter()
+        .filter(|snippet| before.ends_with(&snippet.trigger))
+        .filter(|snippet| {
+            before[..before.len() - snippet.trigger.len()]
+                .chars()
+                .next_back()
+                .map_or(at_start, char::is_whitespace)
+        })
+        .max_by_key(|snippet| snippet.trigger.len())
+}
+
+/// Expands every whitespace-delimited trigger in `text`.
+pub fn expand_text(snippets: &[Snippet], text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for piece in text.split_inclusive(char::is_whitespace) {
+        let token = piece.trim_end_matches(|ch: char| ch.is_whitespace());
+        match snippets.iter().find(|snippet| snippet.trigger == token) {
+            Some(snippet) => {
+                output.push_str(&snippet.expansion);
+                output.push_str(&piece[token.len()..]);
+            }
+            None => output.push_str(piece),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippets() -> Vec<Snippet> {
+        vec![
+            Snippet::new(":mtg", "Meeting #work\n- attendees:\n").unwrap(),
+            Snippet::new(":m", "maybe").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn snippet_new_validates_trigger() {
+        assert_eq!(Snippet::new("  ", "x"), Err(SnippetError::EmptyTrigger));
+        assert_eq!(
+            Snippet::new(":a b", "x"),
+            Err(SnippetError::WhitespaceInTrigger)
+        );
+    }
+
+    #[test]
+    fn match_trigger_requires_word_boundary() {
+        let snippets = snippets();
+        assert_eq!(
+            match_trigger(&snippets, "notes :mtg", false).map(|s| s.trigger.as_str()),
+            Some(":mtg")
+        );
+        assert_eq!(
+            match_trigger(&snippets, ":mtg", true).map(|s| s.trigger.as_str()),
+            Some(":mtg")
+        );
+        assert!(match_trigger(&snippets, ":mtg", false).is_none());
+        assert!(match_trigger(&snippets, "foo:mtg", true).is_none());
+    }
+
+    #[test]
+    fn expand_text_replaces_whole_tokens_only() {
+        let snippets = snippets();
+        assert_eq!(
+            expand_text(&snippets, "a :m b:m :mtg"),
+            "a maybe b:m Meeting #work\n- attendees:\n"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Timeline snapshots as SQLite databases, for imports other tools can
+//! query. The header a JSONL snapshot puts on its first line (version, tag
+//! registry, settings) is the single row of `snapshot`, and each block is
+//! a row of `blocks` in document order, with its date and text in columns
+//! of their own beside the full block as JSON.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::timeline::{TaggedBlock, TimelinePersistenceError};
+
+/// The first bytes of every SQLite database file.
+pub const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+const SCHEMA: &str = "
+    CREATE TABLE snapshot (header TEXT NOT NULL);
+    CREATE TABLE blocks (
+        position INTEGER PRIMARY KEY,
+        date TEXT NOT NULL,
+        text TEXT NOT NULL,
+        block TEXT NOT NULL
+    );
+    CREATE INDEX blocks_by_date ON blocks (date);
+";
+
+/// Writes `header` and `blocks` to a new database at `path`, replacing any
+/// file there. Blocks are inserted one at a time in a single transaction.
+/// Returns the number of blocks written.
+pub fn write<'a>(
+    path: &Path,
+    header: &impl Serialize,
+    blocks: impl Iterator<Item = &'a TaggedBlock>,
+) -> Result<usize, TimelinePersistenceError> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "INSERT INTO snapshot (header) VALUES (?1)",
+        params![serde_json::to_string(header)?],
+    )?;
+    let mut written = 0;
+    {
+        let mut insert = transaction
+            .prepare("INSERT INTO blocks (position, date, text, block) VALUES (?1, ?2, ?3, ?4)")?;
+        for block in blocks {
+            insert.execute(params![
+                written,
+                block.date.to_string(),
+                block.text,
+                serde_json::to_string(block)?,
+            ])?;
+            written += 1;
+        }
+    }
+    transaction.commit()?;
+    Ok(written)
+}
+
+/// The header and blocks of the database at `path`, reading blocks a row
+/// at a time.
+pub fn read(path: &Path) -> Result<(String, Vec<TaggedBlock>), TimelinePersistenceError> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let header: String =
+        connection.query_row("SELECT header FROM snapshot", [], |row| row.get(0))?;
+    let mut select = connection.prepare("SELECT block FROM blocks ORDER BY position")?;
+    let mut rows = select.query([])?;
+    let mut blocks = Vec::new();
+    while let Some(row) = rows.next()? {
+        let block: String = row.get(0)?;
+        blocks.push(serde_json::from_str(&block)?);
+    }
+    Ok((header, blocks))
+}
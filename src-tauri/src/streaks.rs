@@ -0,0 +1,88 @@
+//! Runs of consecutive days with something written, for the year review
+//! and the journaling streak shown on launch.
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Blocks with this tag or one under it are journal entries.
+pub const JOURNAL_TAG: &str = "type:journal";
+
+/// Consecutive days with at least one entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Streak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Streaks {
+    pub current: Option<Streak>,
+    pub longest: Option<Streak>,
+}
+
+/// The runs of consecutive `dates`, which must be ascending.
+fn runs(dates: impl IntoIterator<Item = NaiveDate>) -> impl Iterator<Item = Streak> {
+    let mut dates = dates.into_iter().peekable();
+    std::iter::from_fn(move || {
+        let start = dates.next()?;
+        let mut streak = Streak {
+            start,
+            end: start,
+            days: 1,
+        };
+        while let Some(date) = dates.next_if(|date| streak.end.succ_opt() == Some(*date)) {
+            streak.end = date;
+            streak.days += 1;
+        }
+        Some(streak)
+    })
+}
+
+/// The longest run of consecutive `dates`, which must be ascending. Ties
+/// go to the earliest run.
+pub fn longest(dates: impl IntoIterator<Item = NaiveDate>) -> Option<Streak> {
+    runs(dates).reduce(|best, streak| {
+        if streak.days > best.days {
+            streak
+        } else {
+            best
+        }
+    })
+}
+
+/// The run of `dates` (ascending) that reaches `today`, or yesterday: a
+/// streak isn't broken until a whole day passes without an entry.
+pub fn current(dates: impl IntoIterator<Item = NaiveDate>, today: NaiveDate) -> Option<Streak> {
+    let yesterday = today.pred_opt()?;
+    runs(dates.into_iter().take_while(|date| *date <= today))
+        .last()
+        .filter(|streak| streak.end == today || streak.end == yesterday)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaks_follow_consecutive_days() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let streak = |start, end, days| Streak {
+            start: day(start),
+            end: day(end),
+            days,
+        };
+        let dates = [day(1), day(2), day(3), day(6), day(9), day(10)];
+
+        assert_eq!(longest(dates), Some(streak(1, 3, 3)));
+        assert_eq!(longest([day(1), day(3)]), Some(streak(1, 1, 1)));
+        assert_eq!(longest([]), None);
+
+        assert_eq!(current(dates, day(10)), Some(streak(9, 10, 2)));
+        assert_eq!(current(dates, day(11)), Some(streak(9, 10, 2)));
+        assert_eq!(current(dates, day(12)), None);
+        assert_eq!(current(dates, day(7)), Some(streak(6, 6, 1)));
+        assert_eq!(current(dates, day(5)), None);
+        assert_eq!(current(dates, day(2)), Some(streak(1, 2, 2)));
+    }
+}
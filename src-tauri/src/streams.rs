@@ -0,0 +1,244 @@
+//! Chunked responses for commands that can return very large payloads.
+//! Small results are returned as usual; larger ones are kept here as JSON
+//! and the command returns a [`StreamToken`] that the frontend drains with
+//! `read_stream`, one chunk per call, so no single IPC message is huge.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Serializer};
+use serde_json::value::RawValue;
+use thiserror::Error;
+
+/// Results whose JSON is at most this long are returned inline.
+pub const INLINE_LIMIT: usize = 256 * 1024;
+
+/// Largest result any command may return, streamed or not. Anything
+/// bigger is refused rather than held in memory for the frontend; see
+/// [`crate::perf::PerfMetrics::measure`].
+pub const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Bytes of JSON handed out per `read_stream` call.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Streams not read for this long are dropped, so an abandoned stream
+/// doesn't hold its payload forever.
+const STREAM_TTL: Duration = Duration::from_secs(60);
+
+/// A command result: the value itself, or a token for reading it in
+/// chunks. Inline values serialize exactly as the bare value would.
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Chunked<T> {
+    Inline(Json<T>),
+    Stream(StreamToken),
+}
+
+/// The JSON text of a `T`. [`Streams::respond`] serializes a value once
+/// to size it; the text is passed through as is when the response is
+/// serialized again, for the IPC bridge or to measure it.
+pub struct Json<T> {
+    raw: Box<RawValue>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Json<T> {
+    pub fn get(&self) -> &str {
+        self.raw.get()
+    }
+}
+
+impl<T> Clone for Json<T> {
+    fn clone(&self) -> Self {
+        Self {
+            raw: self.raw.clone(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Json<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Json").field(&self.get()).finish()
+    }
+}
+
+impl<T> Serialize for Json<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StreamToken {
+    pub stream_token: String,
+    /// Length of the full JSON text.
+    pub bytes: usize,
+    /// Expected `read_stream` calls; chunks end on character boundaries,
+    /// so occasionally one more is needed. Read until `done`.
+    pub chunks: usize,
+}
+
+/// The next piece of a stream's JSON text. Concatenating every chunk up to
+/// and including the one with `done` gives the full result.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StreamChunk {
+    pub data: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("unknown or expired stream '{0}'")]
+    UnknownStream(String),
+    #[error("result is {bytes} bytes, over the {limit} byte limit")]
+    TooLarge { bytes: usize, limit: usize },
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+struct PendingStream {
+    json: String,
+    offset: usize,
+    touched: Instant,
+}
+
+#[derive(Default)]
+pub struct Streams {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<String, PendingStream>>,
+}
+
+impl Streams {
+    /// Returns `value` inline when its JSON fits in [`INLINE_LIMIT`], and
+    /// otherwise stores the JSON and returns a token for it. Values over
+    /// [`MAX_RESPONSE_BYTES`] are refused here as well, since the token
+    /// that stands in for a streamed value is small enough to pass the
+    /// check in [`crate::perf::PerfMetrics::measure`].
+    pub fn respond<T: Serialize>(&self, value: T) -> Result<Chunked<T>, StreamError> {
+        self.respond_within(value, MAX_RESPONSE_BYTES)
+    }
+
+    fn respond_within<T: Serialize>(
+        &self,
+        value: T,
+        limit: usize,
+    ) -> Result<Chunked<T>, StreamError> {
+        let json = serde_json::to_string(&value)?;
+        if json.len() > limit {
+            return Err(StreamError::TooLarge {
+                bytes: json.len(),
+                limit,
+            });
+        }
+        if json.len() <= INLINE_LIMIT {
+            return Ok(Chunked::Inline(Json {
+                raw: RawValue::from_string(json)?,
+                value: PhantomData,
+            }));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = StreamToken {
+            stream_token: format!("stream-{id}"),
+            bytes: json.len(),
+            chunks: json.len().div_ceil(CHUNK_SIZE),
+        };
+        let mut pending = self.pending();
+        pending.retain(|_, stream| stream.touched.elapsed() < STREAM_TTL);
+        pending.insert(
+            token.stream_token.clone(),
+            PendingStream {
+                json,
+                offset: 0,
+                touched: Instant::now(),
+            },
+        );
+        Ok(Chunked::Stream(token))
+    }
+
+    /// Hands out the next chunk of a stream, dropping the stream once its
+    /// last chunk has been read.
+    pub fn read(&self, token: &str) -> Result<StreamChunk, StreamError> {
+        let mut pending = self.pending();
+        let stream = pending
+            .get_mut(token)
+            .ok_or_else(|| StreamError::UnknownStream(token.to_string()))?;
+
+        let mut end = (stream.offset + CHUNK_SIZE).min(stream.json.len());
+        while !stream.json.is_char_boundary(end) {
+            end -= 1;
+        }
+        let data = stream.json[stream.offset..end].to_string();
+        stream.offset = end;
+        stream.touched = Instant::now();
+
+        let done = end == stream.json.len();
+        if done {
+            pending.remove(token);
+        }
+        Ok(StreamChunk { data, done })
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<String, PendingStream>> {
+        self.pending.lock().expect("streams lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_results_are_streamed_in_chunks() {
+        let streams = Streams::default();
+        let Chunked::Inline(small) = streams.respond("small").unwrap() else {
+            panic!("expected an inline result");
+        };
+        assert_eq!(small.get(), r#""small""#);
+        assert_eq!(
+            serde_json::to_string(&Chunked::Inline(small)).unwrap(),
+            r#""small""#
+        );
+
+        // Multi-byte characters straddle the chunk boundary.
+        let text = "é".repeat(INLINE_LIMIT);
+        let Chunked::Stream(token) = streams.respond(text.clone()).unwrap() else {
+            panic!("expected a stream");
+        };
+        assert_eq!(token.bytes, text.len() + 2);
+        assert_eq!(token.chunks, 3);
+
+        let mut json = String::new();
+        loop {
+            let chunk = streams.read(&token.stream_token).unwrap();
+            assert!(chunk.data.len() <= CHUNK_SIZE);
+            json.push_str(&chunk.data);
+            if chunk.done {
+                break;
+            }
+        }
+        assert_eq!(serde_json::from_str::<String>(&json).unwrap(), text);
+        assert!(matches!(
+            streams.read(&token.stream_token),
+            Err(StreamError::UnknownStream(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_results_are_refused() {
+        let streams = Streams::default();
+        assert!(matches!(
+            streams.respond_within("x".repeat(100), 64),
+            Err(StreamError::TooLarge {
+                bytes: 102,
+                limit: 64
+            })
+        ));
+        assert!(streams.pending().is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+//! Boolean queries over block tags, such as
+//! `#project:sightline AND #type:journal NOT #archived`. A tag matches
+//! blocks tagged with it or any of its descendants. `AND` binds tighter
+//! than `OR`, terms side by side are ANDed, and parentheses group.
+//! Keywords are case-insensitive; tags must start with `#`.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::timeline::TagFilter;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagQuery {
+    /// A tag path without its `#`, e.g. `project:sightline`.
+    Tag(String),
+    Not(Box<TagQuery>),
+    And(Vec<TagQuery>),
+    Or(Vec<TagQuery>),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TagQueryError {
+    #[error("tag query is empty")]
+    Empty,
+    #[error("tag query ends unexpectedly")]
+    UnexpectedEnd,
+    #[error("unexpected '{0}' in tag query")]
+    Unexpected(String),
+    #[error("'{0}' is not a tag; tags start with '#'")]
+    NotATag(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Tag(String),
+}
+
+impl TagQuery {
+    pub fn parse(input: &str) -> Result<Self, TagQueryError> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(TagQueryError::Empty);
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(query),
+            Some(token) => Err(TagQueryError::Unexpected(describe(token))),
+        }
+    }
+
+    /// Resolves each tag to the ids it matches, using `tag_ids` (which
+    /// should include descendants). Unknown tags match nothing.
+    pub fn compile(&self, tag_ids: &impl Fn(&str) -> HashSet<u32>) -> CompiledTagQuery {
+        match self {
+            Self::Tag(path) => CompiledTagQuery::Tags(tag_ids(path)),
+            Self::Not(inner) => CompiledTagQuery::Not(Box::new(inner.compile(tag_ids))),
+            Self::And(terms) => {
+                CompiledTagQuery::And(terms.iter().map(|term| term.compile(tag_ids)).collect())
+            }
+            Self::Or(terms) => {
+                CompiledTagQuery::Or(terms.iter().map(|term| term.compile(tag_ids)).collect())
+            }
+        }
+    }
+}
+
+/// A [`TagQuery`] with its tags resolved to ids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompiledTagQuery {
+    Tags(HashSet<u32>),
+    Not(Box<CompiledTagQuery>),
+    And(Vec<CompiledTagQuery>),
+    Or(Vec<CompiledTagQuery>),
+}
+
+impl CompiledTagQuery {
+    /// Whether a block tagged `tags` matches.
+    pub fn matches(&self, tags: &[u32]) -> bool {
+        match self {
+            Self::Tags(ids) => tags.iter().any(|tag| ids.contains(tag)),
+            Self::Not(inner) => !inner.matches(tags),
+            Self::And(terms) => terms.iter().all(|term| term.matches(tags)),
+            Self::Or(terms) => terms.iter().any(|term| term.matches(tags)),
+        }
+    }
+
+    /// Whether any block under a subtree with this tag filter could match.
+    /// A filter can only rule tags out, so `NOT` never prunes.
+    pub fn might_match(&self, filter: &TagFilter) -> bool {
+        match self {
+            Self::Tags(ids) => ids.iter().any(|tag| filter.check(tag)),
+            Self::Not(_) => true,
+            Self::And(terms) => terms.iter().all(|term| term.might_match(filter)),
+            Self::Or(terms) => terms.iter().any(|term| term.might_match(filter)),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, TagQueryError> {
+    let spaced = input.replace('(', " ( ").replace(')', " ) ");
+    spaced
+        .split_whitespace()
+        .map(|word| match word {
+            "(" => Ok(Token::Open),
+            ")" => Ok(Token::Close),
+            _ if word.eq_ignore_ascii_case("and") => Ok(Token::And),
+            _ if word.eq_ignore_ascii_case("or") => Ok(Token::Or),
+            _ if word.eq_ignore_ascii_case("not") => Ok(Token::Not),
+            _ => match word.strip_prefix('#') {
+                Some(path) if !path.is_empty() => Ok(Token::Tag(path.to_string())),
+                _ => Err(TagQueryError::NotATag(word.to_string())),
+            },
+        })
+        .collect()
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+        Token::And => "AND".to_string(),
+        Token::Or => "OR".to_string(),
+        Token::Not => "NOT".to_string(),
+        Token::Tag(path) => format!("#{path}"),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn or(&mut self) -> Result<TagQuery, TagQueryError> {
+        let mut terms = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            terms.push(self.and()?);
+        }
+        Ok(collapse(terms, TagQuery::Or))
+    }
+
+    fn and(&mut self) -> Result<TagQuery, TagQueryError> {
+        let mut terms = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next += 1;
+                    terms.push(self.unary()?);
+                }
+                Some(Token::Not | Token::Open | Token::Tag(_)) => terms.push(self.unary()?),
+                _ => break,
+            }
+        }
+        Ok(collapse(terms, TagQuery::And))
+    }
+
+    fn unary(&mut self) -> Result<TagQuery, TagQueryError> {
+        let token = self.peek().cloned().ok_or(TagQueryError::UnexpectedEnd)?;
+        self.next += 1;
+        match token {
+            Token::Not => Ok(TagQuery::Not(Box::new(self.unary()?))),
+            Token::Tag(path) => Ok(TagQuery::Tag(path)),
+            Token::Open => {
+                let inner = self.or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.next += 1;
+                        Ok(inner)
+                    }
+                    Some(token) => Err(TagQueryError::Unexpected(describe(token))),
+                    None => Err(TagQueryError::UnexpectedEnd),
+                }
+            }
+            token => Err(TagQueryError::Unexpected(describe(&token))),
+        }
+    }
+}
+
+fn collapse(mut terms: Vec<TagQuery>, join: fn(Vec<TagQuery>) -> TagQuery) -> TagQuery {
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        join(terms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(path: &str) -> TagQuery {
+        TagQuery::Tag(path.to_string())
+    }
+
+    #[test]
+    fn queries_parse_with_and_binding_tighter_than_or() {
+        assert_eq!(
+            TagQuery::parse("#project:sightline AND #type:journal NOT #archived"),
+            Ok(TagQuery::And(vec![
+                tag("project:sightline"),
+                tag("type:journal"),
+                TagQuery::Not(Box::new(tag("archived"))),
+            ]))
+        );
+        assert_eq!(
+            TagQuery::parse("#a or #b and not (#c OR #d)"),
+            Ok(TagQuery::Or(vec![
+                tag("a"),
+                TagQuery::And(vec![
+                    tag("b"),
+                    TagQuery::Not(Box::new(TagQuery::Or(vec![tag("c"), tag("d")]))),
+                ]),
+            ]))
+        );
+
+        assert_eq!(TagQuery::parse("  "), Err(TagQueryError::Empty));
+        assert_eq!(TagQuery::parse("#a AND"), Err(TagQueryError::UnexpectedEnd));
+        assert_eq!(TagQuery::parse("(#a"), Err(TagQueryError::UnexpectedEnd));
+        assert_eq!(
+            TagQuery::parse("#a )"),
+            Err(TagQueryError::Unexpected(")".to_string()))
+        );
+        assert_eq!(
+            TagQuery::parse("#a OR OR #b"),
+            Err(TagQueryError::Unexpected("OR".to_string()))
+        );
+        assert_eq!(
+            TagQuery::parse("work"),
+            Err(TagQueryError::NotATag("work".to_string()))
+        );
+    }
+
+    #[test]
+    fn compiled_queries_match_tags_and_prune_by_filter() {
+        let ids = |path: &str| -> HashSet<u32> {
+            match path {
+                "project" => HashSet::from([1, 2]),
+                "archived" => HashSet::from([3]),
+                _ => HashSet::new(),
+            }
+        };
+        let query = TagQuery::parse("#project NOT #archived")
+            .unwrap()
+            .compile(&ids);
+        assert!(query.matches(&[2]));
+        assert!(!query.matches(&[2, 3]));
+        assert!(!query.matches(&[]));
+
+        let mut filter = TagFilter::default();
+        filter.insert(3);
+        assert!(!query.might_match(&filter));
+        filter.insert(1);
+        assert!(query.might_match(&filter));
+
+        let unknown = TagQuery::parse("#missing").unwrap().compile(&ids);
+        assert!(!unknown.matches(&[1, 2, 3]));
+    }
+}
@@ -0,0 +1,276 @@
+//! Markdown checkboxes (`- [ ] call Sam`, `- [x] book flights`) in the
+//! timeline, indexed alongside the tree like the day index. Tasks are found
+//! line by line, so a task typed over several edits, and so spread over
+//! several blocks, is still one task; it belongs to the block its line
+//! starts in. Text edits rescan only the lines they touched; anything that
+//! rebuilds the tree wholesale rebuilds the index with it.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sum_tree::{Bias, SumTree};
+
+use crate::day_index::Shift;
+use crate::timeline::{self, Chars, DateRange, Point, TaggedBlock, TimelineSummary};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Task {
+    /// The block the task's line starts in.
+    pub block_id: u32,
+    /// Which of that block's tasks this is, from zero.
+    pub task_index: u32,
+    pub date: NaiveDate,
+    pub done: bool,
+    /// The line after the checkbox, trimmed.
+    pub text: String,
+    /// From an `@due(YYYY-MM-DD)` on the line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    /// Character offset of the start of the line in the rendered document.
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TaskFilter {
+    /// Only ticked (`true`) or open (`false`) tasks; both when unset.
+    pub done: Option<bool>,
+    /// Only tasks in blocks dated within the range.
+    #[serde(flatten)]
+    pub range: DateRange,
+    pub include_archived: bool,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, done: bool, block: &TaggedBlock) -> bool {
+        self.done.is_none_or(|wanted| wanted == done)
+            && self.range.contains(block.date)
+            && (self.include_archived || !block.archived)
+    }
+}
+
+/// A checkbox line, keyed in the index by the offset its line starts at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskLine {
+    /// Characters from the start of the line to the mark between the
+    /// brackets.
+    pub column: usize,
+    pub done: bool,
+    pub text: String,
+    pub due: Option<NaiveDate>,
+}
+
+impl TaskLine {
+    /// The checkbox on `line`, if it is a list item that starts with one.
+    fn parse(line: &str) -> Option<Self> {
+        let indent = line.chars().take_while(|ch| ch.is_whitespace()).count();
+        let rest = line
+            .trim_start()
+            .strip_prefix(['-', '*', '+'])?
+            .strip_prefix(' ')?
+            .strip_prefix('[')?;
+        let mut chars = rest.chars();
+        let done = match chars.next()? {
+            ' ' => false,
+            'x' | 'X' => true,
+            _ => return None,
+        };
+        let text = chars.as_str().strip_prefix(']')?;
+        if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+            return None;
+        }
+        Some(Self {
+            column: indent + 3,
+            done,
+            text: text.trim().to_string(),
+            due: due_date(text),
+        })
+    }
+}
+
+const DUE_MARKER: &str = "@due(";
+
+/// The date of the first well-formed `@due(YYYY-MM-DD)` in `text`.
+fn due_date(text: &str) -> Option<NaiveDate> {
+    text.match_indices(DUE_MARKER).find_map(|(at, _)| {
+        let rest = &text[at + DUE_MARKER.len()..];
+        let (date, _) = rest.split_once(')')?;
+        NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()
+    })
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaskIndex {
+    tasks: BTreeMap<usize, TaskLine>,
+    /// Totals of the tree the index was built for, to notice when it no
+    /// longer matches.
+    blocks: usize,
+    chars: usize,
+}
+
+impl TaskIndex {
+    pub fn build(tree: &SumTree<TaggedBlock>) -> Self {
+        let mut index = Self::default();
+        scan(tree, 0, tree.summary().total_chars, &mut index.tasks);
+        index.blocks = tree.summary().entry_count;
+        index.chars = tree.summary().total_chars;
+        index
+    }
+
+    /// Whether the index was kept up to date with a tree summarised by
+    /// `summary`.
+    pub fn is_current(&self, summary: &TimelineSummary) -> bool {
+        self.blocks == summary.entry_count && self.chars == summary.total_chars
+    }
+
+    /// Checkbox lines by the offset their line starts at, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &TaskLine)> {
+        self.tasks.iter().map(|(line, task)| (*line, task))
+    }
+
+    /// Checkbox lines starting within `offsets`, in order.
+    pub fn range(&self, offsets: Range<usize>) -> impl Iterator<Item = (usize, &TaskLine)> {
+        self.tasks.range(offsets).map(|(line, task)| (*line, task))
+    }
+
+    /// The checkbox line starting at `line`.
+    pub fn get_mut(&mut self, line: usize) -> Option<&mut TaskLine> {
+        self.tasks.get_mut(&line)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Brings the index up to date with `tree` after edits that moved text
+    /// by `shifts`, in the order they were made. Lines the edits wrote to
+    /// are scanned again; other tasks just move.
+    pub fn update(&mut self, tree: &SumTree<TaggedBlock>, shifts: &[Shift]) {
+        // What each edit wrote, in the coordinates after it.
+        let mut written: Vec<(usize, usize)> = Vec::new();
+        for shift in shifts {
+            let start = shift.offset;
+            let removed = start + shift.chars.min(0).unsigned_abs();
+            let moved = |at: usize| {
+                if at >= removed {
+                    at.saturating_add_signed(shift.chars)
+                } else {
+                    at.min(start)
+                }
+            };
+            // Tasks before the edit stay put, those in removed text go, and
+            // only those after it are moved.
+            let mut after = self.tasks.split_off(&start).split_off(&removed);
+            if shift.chars == 0 {
+                self.tasks.append(&mut after);
+            } else {
+                self.tasks.extend(
+                    after
+                        .into_iter()
+                        .map(|(line, task)| (line.saturating_add_signed(shift.chars), task)),
+                );
+            }
+            for range in &mut written {
+                *range = (moved(range.0), moved(range.1));
+            }
+            written.push((start, start + shift.chars.max(0).unsigned_abs()));
+        }
+
+        let total = tree.summary().total_chars;
+        for (from, to) in written {
+            let to = to.min(total);
+            let from = line_start(tree, from.min(to));
+            let stale: Vec<usize> = self.tasks.range(from..=to).map(|(line, _)| *line).collect();
+            for line in stale {
+                self.tasks.remove(&line);
+            }
+            scan(tree, from, to, &mut self.tasks);
+        }
+        self.blocks = tree.summary().entry_count;
+        self.chars = total;
+    }
+}
+
+/// The offset of the start of the line holding `offset`.
+fn line_start(tree: &SumTree<TaggedBlock>, offset: usize) -> usize {
+    timeline::point_at(tree, offset)
+        .and_then(|point| timeline::offset_at(tree, Point::new(point.row, 0)))
+        .unwrap_or(0)
+}
+
+/// Adds the checkbox lines starting from `from`, itself a line start, up
+/// to and including the line holding `to`.
+fn scan(
+    tree: &SumTree<TaggedBlock>,
+    from: usize,
+    to: usize,
+    tasks: &mut BTreeMap<usize, TaskLine>,
+) {
+    let mut cursor = tree.cursor::<Chars>(());
+    cursor.seek(&Chars(from), Bias::Right);
+    let mut offset = cursor.start().0;
+    let mut line = String::new();
+    let mut line_offset = from;
+    while let Some(block) = cursor.item() {
+        for ch in block.text.chars() {
+            if offset >= from {
+                if ch == '\n' {
+                    if let Some(task) = TaskLine::parse(&line) {
+                        tasks.insert(line_offset, task);
+                    }
+                    line.clear();
+                    line_offset = offset + 1;
+                    if line_offset > to {
+                        return;
+                    }
+                } else {
+                    line.push(ch);
+                }
+            }
+            offset += 1;
+        }
+        cursor.next();
+    }
+    if let Some(task) = TaskLine::parse(&line) {
+        tasks.insert(line_offset, task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkbox_lines_parse() {
+        let task = |line| TaskLine::parse(line).map(|task| (task.column, task.done, task.text));
+        assert_eq!(task("- [ ] call Sam"), Some((3, false, "call Sam".into())));
+        assert_eq!(task("  * [X] done "), Some((5, true, "done".into())));
+        assert_eq!(task("+ [x]"), Some((3, true, String::new())));
+        assert_eq!(task("- [x]nope"), None);
+        assert_eq!(task("- [?] maybe"), None);
+        assert_eq!(task("[ ] bare"), None);
+        assert_eq!(task("-[ ] tight"), None);
+    }
+
+    #[test]
+    fn due_dates_parse() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(due_date("rent @due(2024-06-01)"), date(2024, 6, 1));
+        assert_eq!(
+            due_date("@due(soon) then @due( 2024-02-29 )"),
+            date(2024, 2, 29)
+        );
+        assert_eq!(due_date("@due(2023-02-29)"), None);
+        assert_eq!(due_date("@due(2024-06-01"), None);
+        assert_eq!(
+            TaskLine::parse("- [ ] rent @due(2024-06-01)").and_then(|task| task.due),
+            date(2024, 6, 1)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Each block's most frequent words, for tag suggestions and related
+//! entries. Terms are cached by the hash a block's summary holds, which is
+//! worked out when the block is written, so a block is only tokenized
+//! again after an edit changes it and reads never hash its text.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Terms kept per block.
+pub const TOP_TERMS: usize = 8;
+
+/// Words shorter than this (in characters) aren't terms.
+const MIN_TERM_CHARS: usize = 3;
+
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "back", "been", "but", "can",
+    "could", "did", "does", "for", "from", "get", "got", "had", "has", "have", "her", "him", "his",
+    "how", "into", "its", "just", "more", "not", "now", "off", "one", "only", "our", "out", "over",
+    "she", "should", "some", "than", "that", "the", "their", "them", "then", "there", "these",
+    "they", "this", "too", "very", "was", "way", "were", "what", "when", "which", "who", "will",
+    "with", "would", "you", "your",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Term {
+    /// Lowercased.
+    pub word: String,
+    pub count: u32,
+}
+
+/// The [`TOP_TERMS`] most frequent words of `text`, most frequent first,
+/// leaving out short words, numbers and common English words.
+pub fn top_terms(text: &str) -> Vec<Term> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for word in text.unicode_words() {
+        if word.chars().count() < MIN_TERM_CHARS || word.chars().all(|ch| ch.is_numeric()) {
+            continue;
+        }
+        let word = word.to_lowercase();
+        if STOP_WORDS.binary_search(&word.as_str()).is_ok() {
+            continue;
+        }
+        *counts.entry(word).or_default() += 1;
+    }
+    let mut terms: Vec<Term> = counts
+        .into_iter()
+        .map(|(word, count)| Term { word, count })
+        .collect();
+    terms.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+    terms.truncate(TOP_TERMS);
+    terms
+}
+
+/// How much two blocks' terms overlap: for each shared word, the smaller
+/// of its two counts.
+pub fn similarity(a: &[Term], b: &[Term]) -> u32 {
+    a.iter()
+        .filter_map(|term| {
+            b.iter()
+                .find(|other| other.word == term.word)
+                .map(|other| term.count.min(other.count))
+        })
+        .sum()
+}
+
+/// Terms of recently seen blocks, keyed by
+/// [`crate::timeline::TimelineSummary::fingerprint`].
+#[derive(Debug, Default)]
+pub struct TermCache {
+    by_block: Mutex<HashMap<u64, Arc<[Term]>>>,
+}
+
+impl TermCache {
+    /// Terms of the block keyed `key` with `text`, from the cache when it
+    /// has been seen before.
+    pub fn get(&self, key: u64, text: &str) -> Arc<[Term]> {
+        let mut by_block = self.by_block.lock().expect("term cache lock poisoned");
+        by_block
+            .entry(key)
+            .or_insert_with(|| top_terms(text).into())
+            .clone()
+    }
+
+    /// Terms of each of `blocks`, given as key and text, in order.
+    /// Afterwards the cache holds only these blocks, dropping those since
+    /// edited or deleted.
+    pub fn all<'a>(&self, blocks: impl IntoIterator<Item = (u64, &'a str)>) -> Vec<Arc<[Term]>> {
+        let mut by_block = self.by_block.lock().expect("term cache lock poisoned");
+        let mut kept = HashMap::with_capacity(by_block.len());
+        let terms = blocks
+            .into_iter()
+            .map(|(key, text)| {
+                let terms = by_block
+                    .remove(&key)
+                    .or_else(|| kept.get(&key).cloned())
+                    .unwrap_or_else(|| top_terms(text).into());
+                kept.insert(key, terms.clone());
+                terms
+            })
+            .collect();
+        *by_block = kept;
+        terms
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_block
+            .lock()
+            .expect("term cache lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Clones start empty, like [`crate::search::RegexCache`].
+impl Clone for TermCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_words_are_sorted() {
+        assert!(STOP_WORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn top_terms_skip_short_and_common_words() {
+        let terms = top_terms("The garden, the GARDEN and a fence. 2024 garden fence shed");
+        let words: Vec<(&str, u32)> = terms
+            .iter()
+            .map(|term| (term.word.as_str(), term.count))
+            .collect();
+        assert_eq!(words, [("garden", 3), ("fence", 2), ("shed", 1)]);
+        assert_eq!(
+            similarity(&terms, &top_terms("fence fence fence garden")),
+            3
+        );
+
+        let many: String = (0..20).map(|n| format!("word{n} ")).collect();
+        assert_eq!(top_terms(&many).len(), TOP_TERMS);
+    }
+
+    #[test]
+    fn cache_keeps_only_current_texts() {
+        let cache = TermCache::default();
+        cache.get(1, "old garden");
+        let terms = cache.all([(2, "new fence"), (2, "new fence"), (3, "shed")]);
+        assert_eq!(terms.len(), 3);
+        assert!(Arc::ptr_eq(&terms[0], &terms[1]));
+        assert_eq!(cache.len(), 2);
+        // Cached terms are found by key alone.
+        assert_eq!(cache.get(3, "")[0].word, "shed");
+    }
+}
@@ -0,0 +1,286 @@
+//! Downscaled copies of image attachments, cached in `thumbnails/` beside the
+//! timeline so scrolling never has to decode full-size images.
+//!
+//! PNG, JPEG and WebP attachments get thumbnails in their own format; other
+//! formats report [`ThumbnailError::UnsupportedFormat`] and the UI shows the
+//! original. Decoding is capped by [`decode_limits`], so a hostile or
+//! corrupt image fails instead of exhausting memory.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, RgbaImage};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::attachments;
+
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// Larger images are refused rather than decoded.
+const MAX_SOURCE_EDGE: u32 = 16_384;
+/// Memory one decode may allocate.
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl ThumbnailSize {
+    pub const ALL: [ThumbnailSize; 3] = [Self::Small, Self::Medium, Self::Large];
+
+    /// Longest edge in pixels. Smaller images are never upscaled.
+    pub fn max_edge(self) -> u32 {
+        match self {
+            Self::Small => 128,
+            Self::Medium => 256,
+            Self::Large => 512,
+        }
+    }
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error("invalid attachment id '{0}'")]
+    InvalidAttachment(String),
+    #[error("cannot make thumbnails for '{0}'; only PNG, JPEG and WebP images are supported")]
+    UnsupportedFormat(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ThumbnailCache {
+    assets_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new(assets_dir: PathBuf, cache_dir: PathBuf) -> Self {
+        Self {
+            assets_dir,
+            cache_dir,
+        }
+    }
+
+    /// Path of the cached thumbnail for `attachment`, an id relative to the
+    /// assets directory such as `2025/photo.png`. The thumbnail is generated
+    /// first if it is missing or older than the attachment.
+    pub fn get(&self, attachment: &str, size: ThumbnailSize) -> Result<PathBuf, ThumbnailError> {
+        if !attachments::is_valid_id(attachment) {
+            return Err(ThumbnailError::InvalidAttachment(attachment.to_string()));
+        }
+        let Some(format) = thumbnail_format(attachment) else {
+            return Err(ThumbnailError::UnsupportedFormat(attachment.to_string()));
+        };
+
+        let source = self.assets_dir.join(attachment);
+        let target = self.cache_dir.join(size.dir_name()).join(attachment);
+        let source_modified = fs::metadata(&source)?.modified()?;
+        let fresh = fs::metadata(&target)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified >= source_modified);
+        if !fresh {
+            write_thumbnail(&source, &target, format, size.max_edge())?;
+        }
+        Ok(target)
+    }
+
+    /// Brings thumbnails for every image attachment up to date and returns how
+    /// many are ready. Meant to run on a background thread at startup;
+    /// failures are logged and skipped.
+    pub fn warm(&self) -> io::Result<usize> {
+        let mut ready = 0;
+        for attachment in attachments::list(&self.assets_dir)? {
+            if thumbnail_format(&attachment).is_none() {
+                continue;
+            }
+            for size in ThumbnailSize::ALL {
+                match self.get(&attachment, size) {
+                    Ok(_) => ready += 1,
+                    Err(err) => {
+                        tracing::warn!(%attachment, ?size, %err, "thumbnail generation failed");
+                    }
+                }
+            }
+        }
+        Ok(ready)
+    }
+}
+
+/// The format thumbnails of `attachment` are written in, going by its
+/// extension, if it is one thumbnails are made for.
+fn thumbnail_format(attachment: &str) -> Option<ImageFormat> {
+    ImageFormat::from_path(attachment).ok().filter(|format| {
+        matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+        )
+    })
+}
+
+fn decode_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_EDGE);
+    limits.max_image_height = Some(MAX_SOURCE_EDGE);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    limits
+}
+
+fn write_thumbnail(
+    source: &Path,
+    target: &Path,
+    format: ImageFormat,
+    max_edge: u32,
+) -> Result<(), ThumbnailError> {
+    // The content decides how to decode, in case the extension is wrong.
+    let mut reader = ImageReader::open(source)?.with_guessed_format()?;
+    reader.limits(decode_limits());
+    let rgba = reader.decode()?.into_rgba8();
+
+    let (pixels, width, height) = downscale(rgba.as_raw(), rgba.width(), rgba.height(), max_edge);
+    let thumbnail =
+        RgbaImage::from_raw(width, height, pixels).expect("downscale returns a full buffer");
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let thumbnail = if format == ImageFormat::Jpeg {
+        // JPEG has no alpha channel.
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(thumbnail).into_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(thumbnail)
+    };
+    thumbnail.save_with_format(target, format)?;
+    Ok(())
+}
+
+/// Box-filters RGBA pixels so the longest edge is at most `max_edge`.
+fn downscale(rgba: &[u8], width: u32, height: u32, max_edge: u32) -> (Vec<u8>, u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_edge {
+        return (rgba.to_vec(), width, height);
+    }
+
+    let scale = |edge: u32| ((u64::from(edge) * u64::from(max_edge)) / u64::from(longest)).max(1);
+    let (out_width, out_height) = (scale(width) as u32, scale(height) as u32);
+    let (width, height) = (width as usize, height as usize);
+
+    let mut pixels = Vec::with_capacity(out_width as usize * out_height as usize * 4);
+    for oy in 0..out_height as usize {
+        let y0 = oy * height / out_height as usize;
+        let y1 = ((oy + 1) * height / out_height as usize).max(y0 + 1);
+        for ox in 0..out_width as usize {
+            let x0 = ox * width / out_width as usize;
+            let x1 = ((ox + 1) * width / out_width as usize).max(x0 + 1);
+
+            let mut sum = [0u64; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let at = (y * width + x) * 4;
+                    for (channel, total) in sum.iter_mut().enumerate() {
+                        *total += u64::from(rgba[at + channel]);
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            pixels.extend(sum.iter().map(|total| (total / count) as u8));
+        }
+    }
+    (pixels, out_width, out_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_image(path: &Path, width: u32, height: u32) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image::RgbImage::from_pixel(width, height, image::Rgb([200, 120, 40]))
+            .save(path)
+            .unwrap();
+    }
+
+    fn dimensions(path: &Path) -> (u32, u32) {
+        image::image_dimensions(path).unwrap()
+    }
+
+    #[test]
+    fn get_caches_scaled_thumbnails() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join("assets");
+        write_image(&assets.join("shots/wide.png"), 1000, 500);
+        write_image(&assets.join("tiny.png"), 40, 20);
+        let cache = ThumbnailCache::new(assets, dir.path().join(THUMBNAILS_DIR));
+
+        let small = cache.get("shots/wide.png", ThumbnailSize::Small).unwrap();
+        assert_eq!(small, dir.path().join("thumbnails/small/shots/wide.png"));
+        assert_eq!(dimensions(&small), (128, 64));
+
+        let tiny = cache.get("tiny.png", ThumbnailSize::Large).unwrap();
+        assert_eq!(dimensions(&tiny), (40, 20));
+
+        assert_eq!(cache.warm().unwrap(), 6);
+    }
+
+    #[test]
+    fn jpeg_thumbnails_stay_jpeg() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join("assets");
+        write_image(&assets.join("photo.jpg"), 600, 900);
+        let cache = ThumbnailCache::new(assets, dir.path().join(THUMBNAILS_DIR));
+
+        let medium = cache.get("photo.jpg", ThumbnailSize::Medium).unwrap();
+        assert_eq!(dimensions(&medium), (170, 256));
+        assert_eq!(
+            image::ImageReader::open(&medium)
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .format(),
+            Some(ImageFormat::Jpeg)
+        );
+    }
+
+    #[test]
+    fn oversized_images_are_refused() {
+        let dir = tempdir().unwrap();
+        let assets = dir.path().join("assets");
+        write_image(&assets.join("strip.png"), MAX_SOURCE_EDGE + 1, 1);
+        let cache = ThumbnailCache::new(assets, dir.path().join(THUMBNAILS_DIR));
+        assert!(matches!(
+            cache.get("strip.png", ThumbnailSize::Small),
+            Err(ThumbnailError::Image(image::ImageError::Limits(_)))
+        ));
+    }
+
+    #[test]
+    fn get_rejects_unsupported_and_escaping_ids() {
+        let dir = tempdir().unwrap();
+        let cache = ThumbnailCache::new(dir.path().join("assets"), dir.path().join("thumbs"));
+        assert!(matches!(
+            cache.get("notes.pdf", ThumbnailSize::Small),
+            Err(ThumbnailError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            cache.get("../timeline.png", ThumbnailSize::Small),
+            Err(ThumbnailError::InvalidAttachment(_))
+        ));
+    }
+}
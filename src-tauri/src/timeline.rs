@@ -1,19 +1,53 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
-use std::{cmp, env};
-
-use crate::{api::TextOperation, tag_palette};
+use std::sync::Arc;
+
+use crate::clock::SharedClock;
+use crate::day_index::{DayEntry, DayIndex, Shift};
+use crate::day_metrics::{DayProperties, MetricParser, MetricPattern, MetricPatternError};
+use crate::export_schedule::ExportSchedule;
+use crate::ical::{self, IcalExport};
+use crate::important_dates::{DateOccurrence, ImportantDateError, ImportantDates};
+use crate::language::{self, AnalysisCache};
+use crate::launch::LaunchOptions;
+use crate::markdown::{self, MarkdownExport, TagRendering};
+use crate::now_page::NowPageConfig;
+use crate::pagination::{Page, PageRequest};
+use crate::paths::AppPaths;
+use crate::search::{
+    count_matches, regex_snippet, snippet, RecentSearch, RegexCache, RegexSearchError,
+    SearchSnippet, SNIPPET_CONTEXT_CHARS,
+};
+use crate::snippets::{self, Snippet, SnippetError};
+use crate::sqlite_snapshot::{self, SQLITE_MAGIC};
+use crate::streaks::{self, Streaks};
+use crate::tag_query::{TagQuery, TagQueryError};
+use crate::tasks::{Task, TaskFilter, TaskIndex, TaskLine};
+use crate::terms::{self, Term, TermCache};
+use crate::wal::{self, LogEntry};
+use crate::writing_stats::{DailyActivity, PeriodStats, StatsPeriod};
+use crate::{
+    api::{OffsetUnit, TextOperation},
+    attachments, tag_palette,
+};
 use bloomfilter::Bloom;
-use chrono::NaiveDate;
-use dirs::config_dir;
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use sum_tree::{Bias, Dimension, Item, SumTree, Summary};
+use smallvec::SmallVec;
+use sum_tree::{Bias, Dimension, Dimensions, Item, SumTree, Summary};
+use unicode_segmentation::UnicodeSegmentation;
 
 const TAG_FILTER_CAPACITY: usize = 256;
 const TAG_FILTER_FALSE_POSITIVE_RATE: f64 = 0.01;
 const TAG_FILTER_SEED: [u8; 32] = [0; 32];
+/// Tag sets up to this size are kept exactly; larger ones as a bloom filter.
+const EXACT_TAG_LIMIT: usize = 8;
 
 fn new_tag_filter() -> Bloom<u32> {
     Bloom::new_for_fp_rate_with_seed(
@@ -24,7 +58,81 @@ fn new_tag_filter() -> Bloom<u32> {
     .expect("failed to create tag bloom filter")
 }
 
-fn union_tag_filters(target: &mut Bloom<u32>, source: &Bloom<u32>) {
+/// The tags used in a subtree, for skipping subtrees during tag search.
+/// Most blocks have a handful of tags, kept exactly as a sorted list; past
+/// [`EXACT_TAG_LIMIT`] tags the set becomes a bloom filter, which may
+/// report a tag that isn't there but never misses one that is.
+#[derive(Clone, Debug)]
+pub enum TagFilter {
+    Exact(SmallVec<[u32; EXACT_TAG_LIMIT]>),
+    Bloom(Box<Bloom<u32>>),
+}
+
+impl Default for TagFilter {
+    fn default() -> Self {
+        Self::Exact(SmallVec::new())
+    }
+}
+
+impl TagFilter {
+    /// Whether `tag` may be in the set; always exact below the limit.
+    pub fn check(&self, tag: &u32) -> bool {
+        match self {
+            Self::Exact(tags) => tags.binary_search(tag).is_ok(),
+            Self::Bloom(filter) => filter.check(tag),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Exact(tags) => tags.is_empty(),
+            Self::Bloom(filter) => filter.is_empty(),
+        }
+    }
+
+    pub fn insert(&mut self, tag: u32) {
+        match self {
+            Self::Exact(tags) => {
+                let Err(at) = tags.binary_search(&tag) else {
+                    return;
+                };
+                if tags.len() < EXACT_TAG_LIMIT {
+                    tags.insert(at, tag);
+                    return;
+                }
+                let mut filter = new_tag_filter();
+                for existing in tags.iter() {
+                    filter.set(existing);
+                }
+                filter.set(&tag);
+                *self = Self::Bloom(Box::new(filter));
+            }
+            Self::Bloom(filter) => filter.set(&tag),
+        }
+    }
+}
+
+fn union_tag_filters(target: &mut TagFilter, source: &TagFilter) {
+    match source {
+        TagFilter::Exact(tags) => {
+            for &tag in tags {
+                target.insert(tag);
+            }
+        }
+        TagFilter::Bloom(source) => match target {
+            TagFilter::Bloom(filter) => union_blooms(filter, source),
+            TagFilter::Exact(tags) => {
+                let mut filter = source.clone();
+                for tag in tags.iter() {
+                    filter.set(tag);
+                }
+                *target = TagFilter::Bloom(filter);
+            }
+        },
+    }
+}
+
+fn union_blooms(target: &mut Bloom<u32>, source: &Bloom<u32>) {
     if source.is_empty() {
         return;
     }
@@ -71,6 +179,15 @@ pub struct TagSuggestion {
     pub color: Option<String>,
 }
 
+/// A block whose words overlap another's, from [`Timeline::related_blocks`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RelatedBlock {
+    pub index: u32,
+    pub date: NaiveDate,
+    /// Shared term occurrences; see [`crate::terms::similarity`].
+    pub score: u32,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TagDescriptor {
     pub id: u32,
@@ -78,6 +195,22 @@ pub struct TagDescriptor {
     pub color: String,
 }
 
+/// A tag and everything below it, from [`Timeline::tag_tree`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagNode {
+    pub id: u32,
+    /// The tag's own segment, without its parents.
+    pub name: String,
+    /// As in [`TagDescriptor::name`].
+    pub full_name: String,
+    pub color: String,
+    /// Blocks tagged with this tag itself.
+    pub block_count: u32,
+    /// Blocks tagged with this tag or any below it, each counted once.
+    pub total_block_count: u32,
+    pub children: Vec<TagNode>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockMetadata {
     pub index: u32,
@@ -86,13 +219,144 @@ pub struct BlockMetadata {
     pub date: String,
     #[serde(default)]
     pub tags: Vec<u32>,
+    /// The start of the block's text, when a preview was asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    /// Whether `preview` stops short of the block's text.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_truncated: bool,
+    /// See [`TaggedBlock::created_at`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Archived blocks take no room in the rendered document, so their
+    /// start and end offsets are the same.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// The soonest `@due(YYYY-MM-DD)` among the open tasks whose lines
+    /// start in the block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    /// Ids of the attachments the block links to, in order of first link.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// The ISO 639-3 code of the block's language, such as `eng`, when
+    /// the block is long enough to tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// Longest preview a metadata listing returns per block, whatever the
+/// caller asks for.
+pub const MAX_PREVIEW_CHARS: usize = 1_000;
+
+/// A block whose text contains a search query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TextMatch {
+    pub block_index: u32,
+    pub date: NaiveDate,
+    /// The trimmed line containing the first match.
+    pub excerpt: String,
+    /// That line with the matches located in it.
+    pub snippet: SearchSnippet,
+    /// Matches anywhere in the block, for ranking by relevance.
+    pub occurrences: usize,
+    /// Full names of the block's tags, e.g. `#project:home`.
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RegexSearch {
+    pub matches: Vec<TextMatch>,
+    /// Whether the search stopped at [`REGEX_SCAN_BUDGET`] before reaching
+    /// the end of the timeline.
+    pub truncated: bool,
+}
+
+/// Bytes of text a regex search reads before giving up, so a slow pattern
+/// over a large journal returns partial results instead of stalling.
+pub const REGEX_SCAN_BUDGET: usize = 32 << 20;
+
+/// Inclusive range of dates; a missing bound leaves that side open.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateRange {
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn new(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        Self { from, to }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+
+    /// Whether any date in `min..=max` falls in the range. Missing bounds
+    /// mean nothing is dated, so nothing can overlap.
+    pub fn overlaps(&self, min: Option<NaiveDate>, max: Option<NaiveDate>) -> bool {
+        let (Some(min), Some(max)) = (min, max) else {
+            return false;
+        };
+        self.from.is_none_or(|from| max >= from) && self.to.is_none_or(|to| min <= to)
+    }
+}
+
+/// Blocks tagged with this root tag (or any of its children) are masked
+/// when content is assembled with [`SensitiveContent::Masked`].
+pub const SENSITIVE_TAG: &str = "sensitive";
+
+/// Masking replaces each character with this one, keeping newlines, so
+/// character offsets into masked content line up with the real document.
+/// Byte and UTF-16 offsets don't, so [`Timeline::ops_in_chars`] refuses
+/// them while the document shows masked blocks.
+const SENSITIVE_MASK: char = '\u{2022}';
+
+const SEARCH_EXCERPT_CHARS: usize = 160;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SensitiveContent {
+    #[default]
+    Masked,
+    Included,
+}
+
+/// Whether rendered content and search take in archived blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArchivedContent {
+    #[default]
+    Excluded,
+    Included,
+}
+
+impl ArchivedContent {
+    fn shows(self, block: &TaggedBlock) -> bool {
+        self == Self::Included || !block.archived
+    }
+}
+
+/// `true` includes archived blocks.
+impl From<bool> for ArchivedContent {
+    fn from(include: bool) -> Self {
+        if include {
+            Self::Included
+        } else {
+            Self::Excluded
+        }
+    }
 }
 
+/// Tags keyed by ids derived from their content (see
+/// [`TagRegistry::intern_segment`]), so registries built on different
+/// devices agree on ids and merge without collisions.
 #[derive(Clone, Debug, Default)]
 pub struct TagRegistry {
     tags: HashMap<u32, Tag>,
     index: HashMap<Option<u32>, HashMap<String, u32>>,
-    next_id: u32,
 }
 
 impl TagRegistry {
@@ -123,10 +387,167 @@ impl TagRegistry {
             .copied()
     }
 
+    /// Returns the id of `name` under `parent_id`, adding the tag if it is
+    /// new. A new tag's id is a hash of its parent id and name, so the same
+    /// tag interned independently elsewhere gets the same id.
     pub fn intern_segment(&mut self, parent_id: Option<u32>, name: &str) -> u32 {
         self.intern_segment_with_id(parent_id, name, None)
     }
 
+    /// Interns every tag of `other` and returns its ids mapped to ours.
+    /// Because ids are content-derived the map is the identity, except where
+    /// one side had to step past a hash collision. Tags new to `self` keep
+    /// their color from `other`.
+    pub fn merge(&mut self, other: &TagRegistry) -> HashMap<u32, u32> {
+        let mut ids = HashMap::new();
+        for tag in other.parents_first() {
+            let parent_id = tag.parent_id.and_then(|parent| ids.get(&parent).copied());
+            let is_new = self.find_id(parent_id, &tag.name).is_none();
+            let id = self.intern_segment(parent_id, &tag.name);
+            if is_new && tag.color.is_some() {
+                if let Some(merged) = self.tags.get_mut(&id) {
+                    merged.color = tag.color.clone();
+                }
+            }
+            ids.insert(tag.id, id);
+        }
+        ids
+    }
+
+    /// Gives every tag its content-derived id, keeping names, hierarchy and
+    /// colors, and returns the old ids of tags that moved mapped to their
+    /// new ones. Migrates registries from before ids were content-derived,
+    /// when they were handed out sequentially.
+    pub fn migrate_to_content_ids(&mut self) -> HashMap<u32, u32> {
+        let mut migrated = TagRegistry::new();
+        let ids = migrated.merge(self);
+        for (old, new) in &ids {
+            let color = self.tags[old].color.clone();
+            if let Some(tag) = migrated.tags.get_mut(new) {
+                tag.color = color;
+            }
+        }
+        *self = migrated;
+        ids.into_iter().filter(|(old, new)| old != new).collect()
+    }
+
+    /// Moves `id` and its descendants under `new_parent`, or to the root.
+    /// Ids derive from the parent, so moved tags get new ids, returned as
+    /// old id to new. A moved tag whose name is already taken at its new
+    /// place merges into the existing tag, which keeps its color.
+    pub fn reparent(
+        &mut self,
+        id: u32,
+        new_parent: Option<u32>,
+    ) -> Result<HashMap<u32, u32>, ReparentTagError> {
+        let tag = self.tags.get(&id).ok_or(ReparentTagError::UnknownTag(id))?;
+        if let Some(parent) = new_parent {
+            if !self.tags.contains_key(&parent) {
+                return Err(ReparentTagError::UnknownTag(parent));
+            }
+            if self.has_ancestor(parent, id) {
+                return Err(ReparentTagError::Cycle { id, parent });
+            }
+        }
+        if tag.parent_id == new_parent {
+            return Ok(HashMap::new());
+        }
+
+        let moved: Vec<Tag> = self
+            .parents_first()
+            .into_iter()
+            .filter(|tag| self.has_ancestor(tag.id, id))
+            .collect();
+        for tag in &moved {
+            self.tags.remove(&tag.id);
+        }
+        self.rebuild_indexes();
+
+        let mut ids = HashMap::new();
+        for tag in moved {
+            let parent_id = if tag.id == id {
+                new_parent
+            } else {
+                tag.parent_id.and_then(|parent| ids.get(&parent).copied())
+            };
+            let is_new = self.find_id(parent_id, &tag.name).is_none();
+            let new_id = self.intern_segment(parent_id, &tag.name);
+            if is_new {
+                if let Some(reparented) = self.tags.get_mut(&new_id) {
+                    reparented.color = tag.color;
+                }
+            }
+            ids.insert(tag.id, new_id);
+        }
+        Ok(ids)
+    }
+
+    /// Renames `id` in place, keeping its id, parent, children and color,
+    /// so blocks tagged with it need no retagging. A leading `#` is
+    /// ignored; the name must be a single segment not already used by a
+    /// sibling. The kept id is no longer derived from the new name, which
+    /// only matters if the old name is interned again: it then steps past
+    /// the renamed tag like any other collision.
+    pub fn rename(&mut self, id: u32, new_name: &str) -> Result<(), RenameTagError> {
+        let tag = self.tags.get(&id).ok_or(RenameTagError::UnknownTag(id))?;
+        let name = new_name.trim().trim_start_matches('#').trim();
+        if name.is_empty() || name.contains(':') {
+            return Err(RenameTagError::InvalidName);
+        }
+        let parent_id = tag.parent_id;
+        match self.find_id(parent_id, name) {
+            Some(existing) if existing == id => return Ok(()),
+            Some(_) => return Err(RenameTagError::NameTaken(name.to_string())),
+            None => {}
+        }
+
+        let old_name = std::mem::replace(
+            &mut self.tags.get_mut(&id).expect("tag exists").name,
+            name.to_string(),
+        );
+        let siblings = self.index.entry(parent_id).or_default();
+        siblings.remove(&old_name);
+        siblings.insert(name.to_string(), id);
+        Ok(())
+    }
+
+    /// Removes `id`, and with `cascade` every tag below it, returning the
+    /// removed ids. Without `cascade` a tag with children can't be removed.
+    pub fn remove(&mut self, id: u32, cascade: bool) -> Result<HashSet<u32>, DeleteTagError> {
+        if !self.tags.contains_key(&id) {
+            return Err(DeleteTagError::UnknownTag(id));
+        }
+        let removed = self.descendant_ids(id);
+        if !cascade && removed.len() > 1 {
+            return Err(DeleteTagError::HasChildren(id));
+        }
+        for tag in &removed {
+            self.tags.remove(tag);
+        }
+        self.rebuild_indexes();
+        Ok(removed)
+    }
+
+    /// Removes the `candidates` that aren't in `keep` and have no children
+    /// left, repeating as removals leave parents childless.
+    fn prune(&mut self, candidates: &BTreeSet<u32>, keep: &HashSet<u32>) {
+        loop {
+            let removable: Vec<u32> = candidates
+                .iter()
+                .copied()
+                .filter(|id| self.tags.contains_key(id) && !keep.contains(id))
+                .filter(|id| self.index.get(&Some(*id)).is_none_or(HashMap::is_empty))
+                .collect();
+            if removable.is_empty() {
+                return;
+            }
+            for id in removable {
+                self.tags.remove(&id);
+            }
+            self.rebuild_indexes();
+        }
+    }
+
     pub fn intern_path<'a, I>(&mut self, segments: I) -> Option<u32>
     where
         I: IntoIterator<Item = &'a str>,
@@ -159,6 +580,20 @@ impl TagRegistry {
         }))
     }
 
+    /// Looks up an existing `a:b` tag without interning it. A leading `#`
+    /// is ignored.
+    pub fn find_colon_path(&self, path: &str) -> Option<u32> {
+        path.trim()
+            .trim_start_matches('#')
+            .split(':')
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty())
+            .try_fold(None, |parent, segment| {
+                self.find_id(parent, segment).map(Some)
+            })
+            .flatten()
+    }
+
     pub fn full_name(&self, id: u32) -> Option<String> {
         let mut segments = Vec::new();
         let mut current_id = Some(id);
@@ -183,6 +618,62 @@ impl TagRegistry {
         Some(segments.join(":"))
     }
 
+    /// Whether `id` is `ancestor` or one of its descendants.
+    pub fn has_ancestor(&self, id: u32, ancestor: u32) -> bool {
+        let mut current_id = Some(id);
+        let mut guard = 0usize;
+
+        while let Some(tag_id) = current_id {
+            if tag_id == ancestor {
+                return true;
+            }
+
+            guard += 1;
+            if guard > self.tags.len() {
+                return false;
+            }
+
+            current_id = self.tags.get(&tag_id).and_then(|tag| tag.parent_id);
+        }
+
+        false
+    }
+
+    /// `id` and every tag below it.
+    pub fn descendant_ids(&self, id: u32) -> HashSet<u32> {
+        let mut ids: HashSet<u32> = self.descendants(id).into_iter().collect();
+        if self.tags.contains_key(&id) {
+            ids.insert(id);
+        }
+        ids
+    }
+
+    /// Every tag below `id`, depth first with siblings by name, so each
+    /// tag comes before its children. Empty for an unknown tag.
+    pub fn descendants(&self, id: u32) -> Vec<u32> {
+        let mut found = Vec::new();
+        let mut stack: Vec<u32> = self.child_ids(Some(id)).into_iter().rev().collect();
+        while let Some(tag) = stack.pop() {
+            if found.len() >= self.tags.len() {
+                break;
+            }
+            found.push(tag);
+            stack.extend(self.child_ids(Some(tag)).into_iter().rev());
+        }
+        found
+    }
+
+    /// Tags directly under `parent_id`, or the roots, by name.
+    fn child_ids(&self, parent_id: Option<u32>) -> Vec<u32> {
+        let mut children: Vec<(&String, u32)> = self
+            .index
+            .get(&parent_id)
+            .map(|by_name| by_name.iter().map(|(name, &id)| (name, id)).collect())
+            .unwrap_or_default();
+        children.sort();
+        children.into_iter().map(|(_, id)| id).collect()
+    }
+
     pub fn tag_ids_with_prefix(&self, query: &str) -> Vec<u32> {
         self.filter_tag_ids(query, |name, normalized| name.starts_with(normalized))
     }
@@ -262,7 +753,7 @@ impl TagRegistry {
             }
         }
 
-        let id = desired_id.unwrap_or_else(|| self.next_available_id());
+        let id = desired_id.unwrap_or_else(|| self.next_available_id(parent_id, name));
 
         if self.tags.contains_key(&id) {
             panic!("tag id {id} already exists");
@@ -280,27 +771,40 @@ impl TagRegistry {
             .entry(parent_id)
             .or_default()
             .insert(name_string, id);
-        self.bump_next_id(id);
         id
     }
 
-    fn next_available_id(&mut self) -> u32 {
-        let mut id = self.next_id;
-        while self.tags.contains_key(&id) {
-            id = id.wrapping_add(1);
-            if id == self.next_id {
-                panic!("tag registry exhausted");
-            }
-        }
-        self.next_id = id.wrapping_add(1);
-        id
+    /// The first of the tag's candidate ids that is free. Candidates past
+    /// the first are only needed on a 32-bit hash collision.
+    fn next_available_id(&self, parent_id: Option<u32>, name: &str) -> u32 {
+        (0..=u32::MAX)
+            .map(|attempt| content_id(parent_id, name, attempt))
+            .find(|id| !self.tags.contains_key(id))
+            .expect("tag registry exhausted")
     }
 
-    fn bump_next_id(&mut self, id: u32) {
-        let next = id.wrapping_add(1);
-        if self.next_id <= id {
-            self.next_id = next;
-        }
+    /// Tags ordered so every parent comes before its children, then by
+    /// name, so replaying them is deterministic. Tags whose ancestry is
+    /// broken come last, as roots.
+    fn parents_first(&self) -> Vec<Tag> {
+        let depth = |tag: &Tag| {
+            self.full_name(tag.id)
+                .map_or(usize::MAX, |name| name.matches(':').count())
+        };
+        let mut tags: Vec<(usize, Option<String>, Tag)> = self
+            .tags
+            .values()
+            .map(|tag| (depth(tag), self.full_name(tag.id), tag.clone()))
+            .collect();
+        tags.sort_by(|a, b| (a.0, &a.1, a.2.id).cmp(&(b.0, &b.1, b.2.id)));
+        tags.into_iter()
+            .map(|(depth, _, mut tag)| {
+                if depth == usize::MAX {
+                    tag.parent_id = None;
+                }
+                tag
+            })
+            .collect()
     }
 
     fn from_map(id_to_tag: HashMap<u32, String>) -> Self {
@@ -340,7 +844,6 @@ impl TagRegistry {
         let mut registry = Self {
             tags: tags.into_iter().map(|tag| (tag.id, tag)).collect(),
             index: HashMap::new(),
-            next_id: 0,
         };
         registry.ensure_tag_colors();
         registry.rebuild_indexes();
@@ -362,14 +865,6 @@ impl TagRegistry {
                 .insert(tag.name.clone(), tag.id);
         }
 
-        self.next_id = self
-            .tags
-            .keys()
-            .copied()
-            .max()
-            .map(|max| max.wrapping_add(1))
-            .unwrap_or(0);
-
         self.ensure_tag_colors();
     }
 
@@ -382,12 +877,140 @@ impl TagRegistry {
     }
 }
 
+/// A tag's own color, or its palette color when it has none.
+fn display_color(tag: &Tag) -> String {
+    tag.color
+        .clone()
+        .unwrap_or_else(|| tag_palette::color_for(tag.id).to_string())
+}
+
+/// The node for `id`, with block counts looked up in `direct` and
+/// `total` (see [`TagNode`]).
+fn tag_node(
+    registry: &TagRegistry,
+    id: u32,
+    direct: &HashMap<u32, u32>,
+    total: &HashMap<u32, u32>,
+) -> Option<TagNode> {
+    let tag = registry.get_tag(id)?;
+    Some(TagNode {
+        id,
+        name: tag.name.clone(),
+        full_name: format!("#{}", registry.full_name(id)?),
+        color: display_color(tag),
+        block_count: direct.get(&id).copied().unwrap_or_default(),
+        total_block_count: total.get(&id).copied().unwrap_or_default(),
+        children: registry
+            .child_ids(Some(id))
+            .into_iter()
+            .filter_map(|child| tag_node(registry, child, direct, total))
+            .collect(),
+    })
+}
+
+/// FNV-1a over the parent id, name and attempt number. Unlike the std
+/// hashers its output is fixed, which ids persisted and synced across
+/// devices and releases depend on.
+fn content_id(parent_id: Option<u32>, name: &str, attempt: u32) -> u32 {
+    let parent = match parent_id {
+        Some(id) => [&[1][..], &id.to_le_bytes()].concat(),
+        None => vec![0],
+    };
+    parent
+        .iter()
+        .chain(name.as_bytes())
+        .chain(&attempt.to_le_bytes())
+        .fold(0x811c_9dc5, |hash: u32, &byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TaggedBlock {
     pub date: NaiveDate,
     pub text: String,
     #[serde(default)]
     pub tags: Vec<u32>,
+    /// Targets of `[[wikilinks]]` found in the original note, when the
+    /// importer was asked to keep them as links rather than tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    /// The file an imported block was read from, so the app can open the
+    /// original and a re-import can match blocks back to files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<BlockSource>,
+    /// When the block's text was typed. Unknown for blocks from before
+    /// timestamps were kept, and for imported blocks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the block's text or tags last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Archived blocks stay in the timeline but are left out of the
+    /// rendered document, day logs and search unless asked for.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+}
+
+impl TaggedBlock {
+    /// An unarchived block with no links, source or timestamps, as typed
+    /// or imported text starts out.
+    pub fn new(date: NaiveDate, text: impl Into<String>, tags: Vec<u32>) -> Self {
+        Self {
+            date,
+            text: text.into(),
+            tags,
+            links: Vec::new(),
+            source: None,
+            created_at: None,
+            updated_at: None,
+            archived: false,
+        }
+    }
+
+    /// A block with this one's date, tags and history but `text`, for the
+    /// pieces left when a block is split.
+    fn with_text(&self, text: String) -> Self {
+        Self {
+            date: self.date,
+            text,
+            tags: self.tags.clone(),
+            links: self.links.clone(),
+            source: self.source.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            archived: self.archived,
+        }
+    }
+
+    /// [`Self::with_text`], marked as changed at `now`.
+    fn edited(&self, text: String, now: DateTime<Utc>) -> Self {
+        Self {
+            updated_at: Some(now),
+            ..self.with_text(text)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockSource {
+    /// Relative to the directory (or file) that was imported.
+    pub path: PathBuf,
+    /// Byte range of the block in the file's text, once decoded to UTF-8 and
+    /// without any byte-order mark. Edits to the block don't update it.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl BlockSource {
+    /// The whole of a file whose text is `text`.
+    pub fn file(path: impl Into<PathBuf>, text: &str) -> Self {
+        Self {
+            path: path.into(),
+            start: 0,
+            end: text.len(),
+        }
+    }
 }
 
 impl TaggedBlock {
@@ -398,21 +1021,72 @@ impl TaggedBlock {
     fn byte_count(&self) -> usize {
         self.text.len()
     }
+
+    fn word_count(&self) -> usize {
+        count_words(&self.text)
+    }
+}
+
+/// Counts words using Unicode word boundaries (UAX #29), so text without
+/// spaces such as CJK counts each ideograph as a word. Words joined by a
+/// single hyphen (`well-known`) count once.
+pub fn count_words(text: &str) -> usize {
+    let mut count = 0;
+    let mut after_word = false;
+    let mut after_hyphen = false;
+
+    for segment in text.split_word_bounds() {
+        if segment.chars().any(char::is_alphanumeric) {
+            if !after_hyphen {
+                count += 1;
+            }
+            after_word = true;
+            after_hyphen = false;
+        } else if after_word && !after_hyphen && matches!(segment, "-" | "\u{2010}") {
+            after_hyphen = true;
+        } else {
+            after_word = false;
+            after_hyphen = false;
+        }
+    }
+
+    count
 }
 
 impl Item for TaggedBlock {
     type Summary = TimelineSummary;
 
     fn summary(&self, (): ()) -> Self::Summary {
-        let mut tags_filter = new_tag_filter();
-        for tag_id in &self.tags {
-            tags_filter.set(tag_id);
+        let mut tags_filter = TagFilter::default();
+        for &tag_id in &self.tags {
+            tags_filter.insert(tag_id);
         }
 
+        let total_bytes = self.byte_count();
+        let total_chars = self.char_count();
+        let total_utf16 = self.text.encode_utf16().count();
+        let archived = |units| if self.archived { units } else { 0 };
+        let lines = Point::of(&self.text);
         TimelineSummary {
-            total_bytes: self.byte_count(),
-            total_chars: self.char_count(),
+            total_bytes,
+            total_chars,
+            total_utf16,
+            archived_bytes: archived(total_bytes),
+            archived_chars: archived(total_chars),
+            archived_utf16: archived(total_utf16),
+            lines,
+            visible_lines: if self.archived {
+                Point::default()
+            } else {
+                lines
+            },
+            total_words: self.word_count(),
             entry_count: 1,
+            fingerprint: {
+                let mut hasher = DefaultHasher::new();
+                (self.date, &self.text).hash(&mut hasher);
+                hasher.finish()
+            },
             min_date: Some(self.date),
             max_date: Some(self.date),
             tags_filter,
@@ -420,27 +1094,32 @@ impl Item for TaggedBlock {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct TimelineSummary {
     pub total_bytes: usize,
     pub total_chars: usize,
+    pub total_utf16: usize,
+    /// The part of each total above in archived blocks, which the rendered
+    /// document leaves out.
+    pub archived_bytes: usize,
+    pub archived_chars: usize,
+    pub archived_utf16: usize,
+    /// Newlines, and the characters after the last one.
+    pub lines: Point,
+    /// `lines` counted over unarchived blocks only.
+    pub visible_lines: Point,
+    /// Sum of per-block word counts. A word split across two blocks by an
+    /// edit counts once in each.
+    pub total_words: usize,
     pub entry_count: usize,
+    /// Wrapping sum of a hash of each block's date and text. A block's own
+    /// is worked out once, when it is written, and keys caches of results
+    /// per block; an index kept beside the tree compares the total to tell
+    /// the tree was changed behind its back.
+    pub fingerprint: u64,
     pub min_date: Option<NaiveDate>,
     pub max_date: Option<NaiveDate>,
-    pub tags_filter: Bloom<u32>,
-}
-
-impl Default for TimelineSummary {
-    fn default() -> Self {
-        Self {
-            total_bytes: 0,
-            total_chars: 0,
-            entry_count: 0,
-            min_date: None,
-            max_date: None,
-            tags_filter: new_tag_filter(),
-        }
-    }
+    pub tags_filter: TagFilter,
 }
 
 impl Summary for TimelineSummary {
@@ -453,7 +1132,15 @@ impl Summary for TimelineSummary {
     fn add_summary(&mut self, summary: &Self, (): ()) {
         self.total_bytes += summary.total_bytes;
         self.total_chars += summary.total_chars;
+        self.total_utf16 += summary.total_utf16;
+        self.archived_bytes += summary.archived_bytes;
+        self.archived_chars += summary.archived_chars;
+        self.archived_utf16 += summary.archived_utf16;
+        self.lines += summary.lines;
+        self.visible_lines += summary.visible_lines;
+        self.total_words += summary.total_words;
         self.entry_count += summary.entry_count;
+        self.fingerprint = self.fingerprint.wrapping_add(summary.fingerprint);
         self.min_date = match (self.min_date, summary.min_date) {
             (Some(current), Some(other)) => Some(cmp::min(current, other)),
             (None, other) => other,
@@ -473,6 +1160,7 @@ pub trait EditableTimeline {
         &mut self,
         ops: &[TextOperation],
         date_for_inserts: NaiveDate,
+        now: DateTime<Utc>,
     ) -> Result<(), ApplyOpsError>;
 }
 
@@ -489,44 +1177,466 @@ impl<'a> Dimension<'a, TimelineSummary> for Chars {
     }
 }
 
-impl EditableTimeline for SumTree<TaggedBlock> {
-    fn apply_ops(
-        &mut self,
-        ops: &[TextOperation],
-        date_for_inserts: NaiveDate,
-    ) -> Result<(), ApplyOpsError> {
-        for op in ops {
-            match op {
-                TextOperation::Insert { position, text } => {
-                    apply_insert(self, *position, text, date_for_inserts)?;
-                }
-                TextOperation::Delete {
-                    start_position,
-                    end_position,
-                } => {
-                    apply_delete(self, *start_position, *end_position)?;
-                }
-            }
-        }
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Bytes(pub usize);
 
-        Ok(())
+impl<'a> Dimension<'a, TimelineSummary> for Bytes {
+    fn zero(_: ()) -> Self {
+        Self(0)
     }
-}
 
-fn apply_insert(
-    tree: &mut SumTree<TaggedBlock>,
-    position: usize,
-    text: &str,
-    date: NaiveDate,
-) -> Result<(), ApplyOpsError> {
-    if text.is_empty() {
-        return Ok(());
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 += summary.total_bytes;
     }
+}
 
-    let total_chars = tree.summary().total_chars;
-    if position > total_chars {
-        return Err(ApplyOpsError::InvalidPosition { position });
-    }
+/// UTF-16 code units, the offsets JavaScript strings use.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Utf16(pub usize);
+
+impl<'a> Dimension<'a, TimelineSummary> for Utf16 {
+    fn zero(_: ()) -> Self {
+        Self(0)
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 += summary.total_utf16;
+    }
+}
+
+/// A dimension counting text in some unit, for converting offsets in
+/// that unit to characters.
+trait TextUnits<'a>: Dimension<'a, TimelineSummary> + Ord {
+    fn new(units: usize) -> Self;
+    fn units(&self) -> usize;
+    fn width(ch: char) -> usize;
+    /// Units of `summary` outside archived blocks.
+    fn visible(summary: &TimelineSummary) -> usize;
+}
+
+impl TextUnits<'_> for Chars {
+    fn new(units: usize) -> Self {
+        Self(units)
+    }
+
+    fn units(&self) -> usize {
+        self.0
+    }
+
+    fn width(_: char) -> usize {
+        1
+    }
+
+    fn visible(summary: &TimelineSummary) -> usize {
+        summary.total_chars - summary.archived_chars
+    }
+}
+
+impl TextUnits<'_> for Bytes {
+    fn new(units: usize) -> Self {
+        Self(units)
+    }
+
+    fn units(&self) -> usize {
+        self.0
+    }
+
+    fn width(ch: char) -> usize {
+        ch.len_utf8()
+    }
+
+    fn visible(summary: &TimelineSummary) -> usize {
+        summary.total_bytes - summary.archived_bytes
+    }
+}
+
+impl TextUnits<'_> for Utf16 {
+    fn new(units: usize) -> Self {
+        Self(units)
+    }
+
+    fn units(&self) -> usize {
+        self.0
+    }
+
+    fn width(ch: char) -> usize {
+        ch.len_utf16()
+    }
+
+    fn visible(summary: &TimelineSummary) -> usize {
+        summary.total_utf16 - summary.archived_utf16
+    }
+}
+
+/// `D` counted over unarchived blocks only: offsets into the rendered
+/// document.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+struct Visible<D>(D);
+
+impl<'a, D: TextUnits<'a>> Dimension<'a, TimelineSummary> for Visible<D> {
+    fn zero(_: ()) -> Self {
+        Self(D::new(0))
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 = D::new(self.0.units() + D::visible(summary));
+    }
+}
+
+/// Lines and columns of the rendered document.
+impl<'a> Dimension<'a, TimelineSummary> for Visible<Point> {
+    fn zero(_: ()) -> Self {
+        Self(Point::default())
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 += summary.visible_lines;
+    }
+}
+
+/// `offset` into the rendered document, counted in `unit`s, as a
+/// character offset into the timeline. Offsets at the edge of archived
+/// blocks land after them. `None` past the end of the document or partway
+/// through a character.
+fn char_offset(tree: &SumTree<TaggedBlock>, offset: usize, unit: OffsetUnit) -> Option<usize> {
+    match unit {
+        OffsetUnit::Chars if tree.summary().archived_chars == 0 => Some(offset),
+        OffsetUnit::Chars => char_offset_from::<Chars>(tree, offset),
+        OffsetUnit::Bytes => char_offset_from::<Bytes>(tree, offset),
+        OffsetUnit::Utf16 => char_offset_from::<Utf16>(tree, offset),
+    }
+}
+
+fn char_offset_from<'a, D: TextUnits<'a>>(
+    tree: &'a SumTree<TaggedBlock>,
+    offset: usize,
+) -> Option<usize> {
+    // Archived blocks have no visible width, so seeking right passes any
+    // that end at `offset`.
+    let mut cursor = tree.cursor::<Dimensions<Visible<D>, Chars>>(());
+    cursor.seek(&Visible(D::new(offset)), Bias::Right);
+    let Dimensions(Visible(start), Chars(mut chars), ()) = cursor.start().clone();
+    let Some(block) = cursor.item() else {
+        return (start.units() == offset).then_some(chars);
+    };
+    let mut units = start.units();
+    for ch in block.text.chars() {
+        if units >= offset {
+            break;
+        }
+        units += D::width(ch);
+        chars += 1;
+    }
+    (units == offset).then_some(chars)
+}
+
+/// Character `offset` into the timeline as an offset into the rendered
+/// document, where archived blocks take no room.
+fn visible_offset(tree: &SumTree<TaggedBlock>, offset: usize) -> usize {
+    let mut cursor = tree.cursor::<Dimensions<Chars, Visible<Chars>>>(());
+    cursor.seek(&Chars(offset), Bias::Right);
+    let Dimensions(Chars(start), Visible(Chars(shown)), ()) = *cursor.start();
+    match cursor.item() {
+        Some(block) if !block.archived => shown + offset - start,
+        _ => shown,
+    }
+}
+
+/// The parts of `start..end` outside archived blocks, in order.
+fn unarchived_ranges(tree: &SumTree<TaggedBlock>, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut cursor = tree.cursor::<Chars>(());
+    cursor.seek(&Chars(start), Bias::Right);
+    while let Some(block) = cursor.item() {
+        let block_start = cursor.start().0;
+        if block_start >= end {
+            break;
+        }
+        if !block.archived {
+            let range = (
+                block_start.max(start),
+                (block_start + block.char_count()).min(end),
+            );
+            match ranges.last_mut() {
+                Some(last) if last.1 == range.0 => last.1 = range.1,
+                _ => ranges.push(range),
+            }
+        }
+        cursor.next();
+    }
+    ranges
+}
+
+/// A line and column in the document, both from zero. Columns count
+/// characters.
+#[derive(
+    Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct Point {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Point {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+
+    /// Where `text` ends, read from its start.
+    pub fn of(text: &str) -> Self {
+        let mut point = Self::default();
+        for ch in text.chars() {
+            point.advance(ch);
+        }
+        point
+    }
+
+    fn advance(&mut self, ch: char) {
+        if ch == '\n' {
+            self.row += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+/// Moves past text that ends at `other`, read from its own start.
+impl std::ops::AddAssign for Point {
+    fn add_assign(&mut self, other: Self) {
+        if other.row == 0 {
+            self.column += other.column;
+        } else {
+            self.row += other.row;
+            self.column = other.column;
+        }
+    }
+}
+
+impl<'a> Dimension<'a, TimelineSummary> for Point {
+    fn zero(_: ()) -> Self {
+        Self::default()
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        *self += summary.lines;
+    }
+}
+
+/// Blocks counted so far, for seeking to a block by index.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct EntryCount(pub usize);
+
+impl<'a> Dimension<'a, TimelineSummary> for EntryCount {
+    fn zero(_: ()) -> Self {
+        Self(0)
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 += summary.entry_count;
+    }
+}
+
+/// The latest date seen so far. Blocks aren't stored in date order (edits
+/// are dated when they're made, wherever they land), but this only grows,
+/// so seeking to a date stops at the first block dated on or after it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct LatestDate(pub Option<NaiveDate>);
+
+impl<'a> Dimension<'a, TimelineSummary> for LatestDate {
+    fn zero(_: ()) -> Self {
+        Self(None)
+    }
+
+    fn add_summary(&mut self, summary: &'a TimelineSummary, _: ()) {
+        self.0 = cmp::max(self.0, summary.max_date);
+    }
+}
+
+impl EditableTimeline for SumTree<TaggedBlock> {
+    fn apply_ops(
+        &mut self,
+        ops: &[TextOperation],
+        date_for_inserts: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Result<(), ApplyOpsError> {
+        for op in ops {
+            match op {
+                TextOperation::Insert { position, text } => {
+                    apply_insert(self, *position, text, date_for_inserts, now)?;
+                }
+                TextOperation::Delete {
+                    start_position,
+                    end_position,
+                } => {
+                    apply_delete(self, *start_position, *end_position, now)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn apply_insert(
+    tree: &mut SumTree<TaggedBlock>,
+    position: usize,
+    text: &str,
+    date: NaiveDate,
+    now: DateTime<Utc>,
+) -> Result<(), ApplyOpsError> {
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    let block = TaggedBlock {
+        created_at: Some(now),
+        updated_at: Some(now),
+        ..TaggedBlock::new(date, text, Vec::new())
+    };
+    insert_blocks(tree, position, [block])
+}
+
+/// See [`Timeline::offset_to_point`].
+pub(crate) fn point_at(tree: &SumTree<TaggedBlock>, offset: usize) -> Option<Point> {
+    let mut cursor = tree.cursor::<Dimensions<Chars, Point>>(());
+    cursor.seek(&Chars(offset), Bias::Right);
+    let Dimensions(Chars(start), mut point, ()) = *cursor.start();
+    let Some(block) = cursor.item() else {
+        return (start == offset).then_some(point);
+    };
+    for ch in block.text.chars().take(offset - start) {
+        point.advance(ch);
+    }
+    Some(point)
+}
+
+/// See [`Timeline::point_to_offset`].
+pub(crate) fn offset_at(tree: &SumTree<TaggedBlock>, point: Point) -> Option<usize> {
+    let mut cursor = tree.cursor::<Dimensions<Point, Chars>>(());
+    cursor.seek(&point, Bias::Right);
+    let Dimensions(mut at, Chars(mut offset), ()) = *cursor.start();
+    let Some(block) = cursor.item() else {
+        return (at == point).then_some(offset);
+    };
+    for ch in block.text.chars() {
+        if at >= point {
+            break;
+        }
+        at.advance(ch);
+        offset += 1;
+    }
+    (at == point).then_some(offset)
+}
+
+/// See [`Timeline::offset_to_point`]. Archived blocks have no width, so
+/// seeking right passes them, as in [`char_offset_from`].
+fn document_point_at(tree: &SumTree<TaggedBlock>, offset: usize) -> Option<Point> {
+    let mut cursor = tree.cursor::<Dimensions<Visible<Chars>, Visible<Point>>>(());
+    cursor.seek(&Visible(Chars(offset)), Bias::Right);
+    let Dimensions(Visible(Chars(start)), Visible(mut point), ()) = *cursor.start();
+    let Some(block) = cursor.item() else {
+        return (start == offset).then_some(point);
+    };
+    for ch in block.text.chars().take(offset - start) {
+        point.advance(ch);
+    }
+    Some(point)
+}
+
+/// See [`Timeline::point_to_offset`].
+fn document_offset_at(tree: &SumTree<TaggedBlock>, point: Point) -> Option<usize> {
+    let mut cursor = tree.cursor::<Dimensions<Visible<Point>, Visible<Chars>>>(());
+    cursor.seek(&Visible(point), Bias::Right);
+    let Dimensions(Visible(mut at), Visible(Chars(mut offset)), ()) = *cursor.start();
+    let Some(block) = cursor.item() else {
+        return (at == point).then_some(offset);
+    };
+    for ch in block.text.chars() {
+        if at >= point {
+            break;
+        }
+        at.advance(ch);
+        offset += 1;
+    }
+    (at == point).then_some(offset)
+}
+
+/// Block `index` and the character offset it starts at.
+fn block_at(tree: &SumTree<TaggedBlock>, index: usize) -> Option<(usize, &TaggedBlock)> {
+    let mut cursor = tree.cursor::<EntryCount>(());
+    let start: Chars = cursor.summary(&EntryCount(index), Bias::Right);
+    cursor.item().map(|block| (start.0, block))
+}
+
+/// Task `task_index` among the checkbox lines of the block spanning
+/// `start..end`, with the offset of its line.
+fn task_in_block(
+    index: &TaskIndex,
+    start: usize,
+    end: usize,
+    task_index: usize,
+) -> Option<(usize, &TaskLine)> {
+    index
+        .iter()
+        .skip_while(|(line, _)| *line < start)
+        .take_while(|(line, _)| *line < end)
+        .nth(task_index)
+}
+
+/// `text` as whole lines to append to a block whose text is `block_text`.
+fn appended_lines(block_text: &str, text: &str) -> String {
+    let mut appended = String::new();
+    if !block_text.is_empty() && !block_text.ends_with('\n') {
+        appended.push('\n');
+    }
+    appended.push_str(text);
+    if !appended.ends_with('\n') {
+        appended.push('\n');
+    }
+    appended
+}
+
+/// The text changes a recorded step makes, leaving out tag changes.
+fn step_text_ops(step: Option<&Vec<UndoOp>>) -> Vec<TextOperation> {
+    step.into_iter()
+        .flatten()
+        .filter_map(|op| match op {
+            UndoOp::Text(op) => Some(op.clone()),
+            UndoOp::Restore { position, blocks } => Some(TextOperation::Insert {
+                position: *position,
+                text: blocks.iter().map(|block| block.text.as_str()).collect(),
+            }),
+            UndoOp::Tags { .. } => None,
+        })
+        .collect()
+}
+
+/// Replaces block `index` with `blocks`. `index` must be in range.
+fn replace_block(
+    tree: &mut SumTree<TaggedBlock>,
+    index: usize,
+    blocks: impl IntoIterator<Item = TaggedBlock>,
+) {
+    let mut cursor = tree.cursor::<EntryCount>(());
+    let mut replaced = cursor.slice(&EntryCount(index), Bias::Right);
+    cursor.next();
+    replaced.extend(blocks, ());
+    replaced.append(cursor.suffix(), ());
+    drop(cursor);
+    *tree = replaced;
+}
+
+/// Inserts `blocks` at `position`, splitting the block it falls inside.
+fn insert_blocks(
+    tree: &mut SumTree<TaggedBlock>,
+    position: usize,
+    blocks: impl IntoIterator<Item = TaggedBlock>,
+) -> Result<(), ApplyOpsError> {
+    let total_chars = tree.summary().total_chars;
+    if position > total_chars {
+        return Err(ApplyOpsError::InvalidPosition { position });
+    }
 
     let mut cursor = tree.cursor::<Chars>(());
     let mut left_tree = cursor.slice(&Chars(position), Bias::Left);
@@ -546,49 +1656,21 @@ fn apply_insert(
             .ok_or(ApplyOpsError::InvalidPosition { position })?;
 
         if !left_fragment.is_empty() {
-            left_tree.push(
-                TaggedBlock {
-                    date: current.date,
-                    text: left_fragment,
-                    tags: current.tags.clone(),
-                },
-                (),
-            );
+            left_tree.push(current.with_text(left_fragment), ());
         }
 
-        left_tree.push(
-            TaggedBlock {
-                date,
-                text: text.to_string(),
-                tags: Vec::new(),
-            },
-            (),
-        );
+        left_tree.extend(blocks, ());
 
         let mut right_tree = SumTree::new(());
         if !right_fragment.is_empty() {
-            right_tree.push(
-                TaggedBlock {
-                    date: current.date,
-                    text: right_fragment,
-                    tags: current.tags.clone(),
-                },
-                (),
-            );
+            right_tree.push(current.with_text(right_fragment), ());
         }
 
         cursor.next();
         right_tree.append(cursor.suffix(), ());
         left_tree.append(right_tree, ());
     } else {
-        left_tree.push(
-            TaggedBlock {
-                date,
-                text: text.to_string(),
-                tags: Vec::new(),
-            },
-            (),
-        );
+        left_tree.extend(blocks, ());
         left_tree.append(cursor.suffix(), ());
     }
 
@@ -602,6 +1684,7 @@ fn apply_delete(
     tree: &mut SumTree<TaggedBlock>,
     start: usize,
     end: usize,
+    now: DateTime<Utc>,
 ) -> Result<(), ApplyOpsError> {
     if start == end {
         return Ok(());
@@ -634,14 +1717,7 @@ fn apply_delete(
             .ok_or(ApplyOpsError::InvalidRange { start, end })?;
 
         if !left_fragment.is_empty() {
-            left_tree.push(
-                TaggedBlock {
-                    date: current.date,
-                    text: left_fragment,
-                    tags: current.tags.clone(),
-                },
-                (),
-            );
+            left_tree.push(current.edited(left_fragment, now), ());
         }
 
         prefix_cursor.next();
@@ -666,14 +1742,7 @@ fn apply_delete(
             .ok_or(ApplyOpsError::InvalidRange { start, end })?;
 
         if !tail.is_empty() {
-            right_tree.push(
-                TaggedBlock {
-                    date: item.date,
-                    text: tail,
-                    tags: item.tags.clone(),
-                },
-                (),
-            );
+            right_tree.push(item.edited(tail, now), ());
         }
 
         suffix_cursor.next();
@@ -699,6 +1768,14 @@ pub enum ApplyOpsError {
     InvalidPosition { position: usize },
     #[error("invalid range: {start}..{end}")]
     InvalidRange { start: usize, end: usize },
+    #[error("edit touches frozen entry dated {date} at {start}..{end}")]
+    Frozen {
+        start: usize,
+        end: usize,
+        date: NaiveDate,
+    },
+    #[error("{unit:?} offsets don't line up with masked sensitive blocks; use character offsets")]
+    MaskedOffsets { unit: OffsetUnit },
 }
 
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
@@ -711,6 +1788,40 @@ pub enum InternTagError {
     MissingName(u32),
 }
 
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ReparentTagError {
+    #[error("unknown tag id {0}")]
+    UnknownTag(u32),
+    #[error("tag {parent} is tag {id} or one of its descendants")]
+    Cycle { id: u32, parent: u32 },
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum RenameTagError {
+    #[error("unknown tag id {0}")]
+    UnknownTag(u32),
+    #[error("tag name must be a single non-empty segment")]
+    InvalidName,
+    #[error("a sibling tag is already named {0}")]
+    NameTaken(String),
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DeleteTagError {
+    #[error("unknown tag id {0}")]
+    UnknownTag(u32),
+    #[error("tag {0} has children; delete them too or move them first")]
+    HasChildren(u32),
+}
+
+/// What [`Timeline::delete_tag`] removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TagDeletion {
+    pub tags: usize,
+    /// Blocks that lost at least one tag.
+    pub blocks: usize,
+}
+
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
 pub enum AssignBlockTagsError {
     #[error("block index {index} out of range")]
@@ -719,6 +1830,53 @@ pub enum AssignBlockTagsError {
     Intern(#[from] InternTagError),
 }
 
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ArchiveBlockError {
+    #[error("block index {index} out of range")]
+    InvalidBlock { index: usize },
+    #[error("date {date} is frozen")]
+    Frozen { date: NaiveDate },
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum AppendToBlockError {
+    #[error("block index {index} out of range")]
+    InvalidBlock { index: usize },
+    #[error("date {date} is frozen")]
+    Frozen { date: NaiveDate },
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ToggleTaskError {
+    #[error("block {block} has no task {task}")]
+    InvalidTask { block: usize, task: usize },
+    #[error("date {date} is frozen")]
+    Frozen { date: NaiveDate },
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SplitBlockError {
+    /// Archived blocks aren't in the document, so can't be split either.
+    #[error("block index {index} out of range or archived")]
+    InvalidBlock { index: usize },
+    #[error("offset {offset} is not inside block {index}")]
+    InvalidOffset { index: usize, offset: usize },
+    #[error("date {date} is frozen")]
+    Frozen { date: NaiveDate },
+    #[error(transparent)]
+    Intern(#[from] InternTagError),
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum RegistryMigrationError {
+    #[error("there is no legacy tag registry to migrate")]
+    NotLegacy,
+    #[error("tag {0} is not in the legacy registry")]
+    UnknownTag(u32),
+    #[error("tag {0} needs a non-empty path")]
+    EmptyPath(u32),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TimelinePersistenceError {
     #[error("config directory unavailable")]
@@ -727,22 +1885,250 @@ pub enum TimelinePersistenceError {
     Io(#[from] io::Error),
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    CborEncode(#[from] ciborium::ser::Error<io::Error>),
+    #[error(transparent)]
+    CborDecode(#[from] ciborium::de::Error<io::Error>),
+    #[error(transparent)]
+    MetricPatterns(#[from] MetricPatternError),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-enum TagRegistrySnapshot {
-    Hierarchical(Vec<Tag>),
+/// A flat `id -> "a:b"` registry from before tags were hierarchical. Loading
+/// one rebuilds the hierarchy from the names straight away, but the names
+/// are kept, along with the tag each became, until the user confirms or
+/// corrects the result (see [`Timeline::commit_registry_migration`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct LegacyTagRegistry {
+    names: BTreeMap<u32, String>,
+    /// Legacy id to the id of the tag its name was rebuilt as.
+    ids: BTreeMap<u32, u32>,
+}
+
+impl LegacyTagRegistry {
+    fn rebuilt(names: BTreeMap<u32, String>, registry: &TagRegistry) -> Self {
+        let ids = names
+            .iter()
+            .filter_map(|(&id, name)| registry.find_colon_path(name).map(|tag| (id, tag)))
+            .collect();
+        Self { names, ids }
+    }
+
+    fn migrations(&self, registry: &TagRegistry) -> Vec<TagMigration> {
+        self.ids
+            .iter()
+            .filter_map(|(&legacy_id, &tag_id)| {
+                Some(TagMigration {
+                    legacy_id,
+                    legacy_name: self.names.get(&legacy_id)?.clone(),
+                    path: registry.full_name(tag_id)?,
+                    tag_id,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Where a legacy tag ends up in the hierarchy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagMigration {
+    pub legacy_id: u32,
+    pub legacy_name: String,
+    /// Colon path of the tag it maps to, without the leading `#`.
+    pub path: String,
+    pub tag_id: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum TagRegistrySnapshot {
+    Hierarchical(Vec<Tag>),
     Flat(HashMap<String, String>),
 }
 
+/// Encodings a snapshot can be written in; [`Timeline::load_from_path`]
+/// reads all of them, and [`Timeline::from_snapshot_bytes`] all but
+/// SQLite.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotEncoding {
+    /// Pretty-printed JSON, as the app saves.
+    #[default]
+    Json,
+    /// CBOR, prefixed with the self-describe tag so loaders can detect it.
+    Cbor,
+    /// A header line followed by one block per line, so large snapshots can
+    /// be written and read without holding one huge JSON document.
+    Jsonl,
+    /// An SQLite database other tools can query; see
+    /// [`crate::sqlite_snapshot`]. Only written and read as a file.
+    Sqlite,
+}
+
+/// `format` value of the header line in a JSONL snapshot.
+pub const JSONL_SNAPSHOT_FORMAT: &str = "sightline-jsonl";
+
+/// CBOR tag 55799 ("self-described CBOR").
+const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+    version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<Vec<&'a TaggedBlock>>,
+    #[serde(skip_serializing_if = "<[Tag]>::is_empty")]
+    tag_registry: &'a [Tag],
+    content_tag_ids: bool,
+}
+
+#[derive(Deserialize)]
+struct JsonlHeader {
+    format: String,
+}
+
+/// Writes a snapshot of `blocks` and `tags` to `path` for
+/// [`Timeline::load_from_path`], replacing any file there; see
+/// [`write_snapshot`].
+pub fn write_snapshot_file<'a>(
+    path: &Path,
+    encoding: SnapshotEncoding,
+    version: u64,
+    blocks: impl IntoIterator<Item = &'a TaggedBlock>,
+    tags: &[Tag],
+    mut keep: impl FnMut(&TaggedBlock) -> bool,
+) -> Result<usize, TimelinePersistenceError> {
+    if encoding != SnapshotEncoding::Sqlite {
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        return write_snapshot(file, encoding, version, blocks, tags, keep);
+    }
+    let header = SnapshotRef {
+        format: None,
+        version,
+        blocks: None,
+        tag_registry: tags,
+        content_tag_ids: true,
+    };
+    let blocks = blocks.into_iter().filter(|block| keep(block));
+    sqlite_snapshot::write(path, &header, blocks)
+}
+
+/// Writes a snapshot of `blocks` and `tags` that the timeline loader reads.
+/// `tags` must come from a [`TagRegistry`], so their ids are content-derived.
+/// Blocks for which `keep` is false are left out. Returns the number of
+/// blocks written. SQLite snapshots are written with
+/// [`write_snapshot_file`] instead.
+pub fn write_snapshot<'a, W: Write>(
+    mut writer: W,
+    encoding: SnapshotEncoding,
+    version: u64,
+    blocks: impl IntoIterator<Item = &'a TaggedBlock>,
+    tags: &[Tag],
+    mut keep: impl FnMut(&TaggedBlock) -> bool,
+) -> Result<usize, TimelinePersistenceError> {
+    let blocks = blocks.into_iter().filter(|block| keep(block));
+    let mut snapshot = SnapshotRef {
+        format: None,
+        version,
+        blocks: None,
+        tag_registry: tags,
+        content_tag_ids: true,
+    };
+    match encoding {
+        SnapshotEncoding::Jsonl => {
+            snapshot.format = Some(JSONL_SNAPSHOT_FORMAT);
+            return write_jsonl_lines(writer, &snapshot, blocks);
+        }
+        SnapshotEncoding::Sqlite => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SQLite snapshots can only be written to a file",
+            )
+            .into());
+        }
+        SnapshotEncoding::Json | SnapshotEncoding::Cbor => {}
+    }
+
+    let blocks: Vec<_> = blocks.collect();
+    let written = blocks.len();
+    snapshot.blocks = Some(blocks);
+    if encoding == SnapshotEncoding::Cbor {
+        writer.write_all(&CBOR_MAGIC)?;
+        ciborium::into_writer(&snapshot, &mut writer)?;
+    } else {
+        serde_json::to_writer_pretty(&mut writer, &snapshot)?;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Writes `header` on the first line and each of `blocks` on a line of its
+/// own, so the blocks are never serialized as one document.
+fn write_jsonl_lines<'a, W: Write>(
+    mut writer: W,
+    header: &impl Serialize,
+    blocks: impl Iterator<Item = &'a TaggedBlock>,
+) -> Result<usize, TimelinePersistenceError> {
+    serde_json::to_writer(&mut writer, header)?;
+    writer.write_all(b"\n")?;
+    let mut written = 0;
+    for block in blocks {
+        serde_json::to_writer(&mut writer, block)?;
+        writer.write_all(b"\n")?;
+        written += 1;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct TimelineSnapshot {
+    /// [`JSONL_SNAPSHOT_FORMAT`] on the header line of a JSONL snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
     version: u64,
-    #[serde(alias = "entries")]
+    /// Empty on the header line of a JSONL snapshot.
+    #[serde(alias = "entries", default)]
     blocks: Vec<TaggedBlock>,
     #[serde(default)]
     tag_registry: Option<TagRegistrySnapshot>,
+    /// Whether tag ids are content-derived. Older snapshots numbered tags
+    /// sequentially and are migrated on load.
+    #[serde(default)]
+    content_tag_ids: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    frozen_ranges: Vec<DateRange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    snippets: Vec<Snippet>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    snippet_expansion: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    fetch_link_titles: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    offline_mode: bool,
+    /// `None` means the built-in patterns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metric_patterns: Option<Vec<MetricPattern>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    now_page: Option<NowPageConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    daily_note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    export_schedule: Option<ExportSchedule>,
+    #[serde(default, skip_serializing_if = "ImportantDates::is_empty")]
+    important_dates: ImportantDates,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    day_properties: BTreeMap<NaiveDate, DayProperties>,
+    /// Read from snapshots written before the search history moved to its
+    /// own file; never written.
+    #[serde(default, skip_serializing)]
+    search_history: Vec<RecentSearch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    legacy_tag_registry: Option<LegacyTagRegistry>,
+    /// Rebuilt on load when missing or out of step with `blocks`.
+    #[serde(default, skip_serializing_if = "DayIndex::is_empty")]
+    day_index: DayIndex,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -750,6 +2136,139 @@ pub struct Timeline {
     tree: SumTree<TaggedBlock>,
     version: u64,
     tag_registry: TagRegistry,
+    frozen_ranges: Vec<DateRange>,
+    snippets: Vec<Snippet>,
+    snippet_expansion: bool,
+    fetch_link_titles: bool,
+    offline_mode: bool,
+    metric_parser: MetricParser,
+    custom_metric_patterns: bool,
+    now_page: Option<NowPageConfig>,
+    daily_note: Option<String>,
+    export_schedule: Option<ExportSchedule>,
+    important_dates: ImportantDates,
+    day_properties: BTreeMap<NaiveDate, DayProperties>,
+    /// Search history found in an older snapshot, until the app moves it
+    /// into its own file.
+    legacy_search_history: Vec<RecentSearch>,
+    legacy_tags: Option<LegacyTagRegistry>,
+    day_index: DayIndex,
+    task_index: TaskIndex,
+    history: UndoHistory,
+    clock: SharedClock,
+    regex_cache: RegexCache,
+    term_cache: TermCache,
+    analysis_cache: AnalysisCache,
+}
+
+/// Undo steps kept; older ones are dropped.
+const UNDO_LIMIT: usize = 100;
+
+/// One change in an undo step, recorded as the inverse of what an edit did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum UndoOp {
+    Text(TextOperation),
+    /// Puts deleted text back as the blocks it came from, keeping the
+    /// dates and tags a plain insert would lose.
+    Restore {
+        position: usize,
+        blocks: Vec<TaggedBlock>,
+    },
+    /// Sets the tags of the blocks covering `start..end`. Blocks are
+    /// addressed by offset because undoing a delete can split them, which
+    /// shifts block indexes.
+    Tags {
+        start: usize,
+        end: usize,
+        tags: Vec<u32>,
+    },
+}
+
+/// Steps are stored in the order they should be applied.
+#[derive(Clone, Debug, Default)]
+struct UndoHistory {
+    undo: Vec<Vec<UndoOp>>,
+    redo: Vec<Vec<UndoOp>>,
+}
+
+impl UndoHistory {
+    /// Records the inverse of a new edit, which invalidates anything that
+    /// was undone before it.
+    fn record(&mut self, mut inverse: Vec<UndoOp>) {
+        if inverse.is_empty() {
+            return;
+        }
+        inverse.reverse();
+        self.undo.push(inverse);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Rewrites the tag ids held by recorded steps.
+    fn remap_tags(&mut self, mut remap: impl FnMut(&mut Vec<u32>)) {
+        for op in self.undo.iter_mut().chain(&mut self.redo).flatten() {
+            match op {
+                UndoOp::Text(_) => {}
+                UndoOp::Restore { blocks, .. } => {
+                    blocks.iter_mut().for_each(|block| remap(&mut block.tags))
+                }
+                UndoOp::Tags { tags, .. } => remap(tags),
+            }
+        }
+    }
+}
+
+/// How [`Timeline::merge_blocks`] treats an incoming block whose text
+/// matches a block already in the timeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Drop the incoming block.
+    Skip,
+    /// Drop the incoming block but add its tags to the existing one.
+    MergeTags,
+    /// Keep both copies.
+    #[default]
+    KeepBoth,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MergeSummary {
+    pub version: u64,
+    pub added: usize,
+    /// Incoming blocks matching existing text, handled per the strategy.
+    pub duplicates: usize,
+    /// Incoming blocks read from the same place in the same file as an
+    /// existing block, i.e. imported before. They are never added again;
+    /// with [`MergeStrategy::MergeTags`] their tags still are.
+    pub reimported: usize,
+    /// Incoming blocks dropped because their date is frozen.
+    pub frozen: usize,
+}
+
+/// A merge worked out by [`Timeline::plan_merge`], for
+/// [`Timeline::apply_merge`] to make.
+#[derive(Clone, Debug)]
+pub struct MergePlan {
+    blocks: Vec<TaggedBlock>,
+    touched: BTreeSet<NaiveDate>,
+    summary: MergeSummary,
+    /// The added blocks as inserts in timeline offsets, in order.
+    pub ops: Vec<TextOperation>,
+}
+
+/// Result of [`Timeline::apply_edit`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedEdit {
+    pub version: u64,
+    /// The backend changed the document beyond the submitted ops, e.g. by
+    /// expanding a snippet, so the client's optimistic copy is stale.
+    pub rewritten: bool,
+    /// The edit as it was applied, for the write-ahead log. Empty ops when
+    /// nothing changed.
+    pub log: LogEntry,
 }
 
 impl Timeline {
@@ -757,6 +2276,21 @@ impl Timeline {
         self.version
     }
 
+    /// The clock edits are dated by.
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Moves the version past `version` so clients still editing against a
+    /// replaced timeline get a conflict and refetch.
+    pub fn advance_version_past(&mut self, version: u64) {
+        self.version = self.version.max(version + 1);
+    }
+
     pub fn summary(&self) -> &TimelineSummary {
         self.tree.summary()
     }
@@ -765,6 +2299,10 @@ impl Timeline {
         self.summary().entry_count
     }
 
+    pub fn word_count(&self) -> usize {
+        self.summary().total_words
+    }
+
     pub fn tag_registry(&self) -> &TagRegistry {
         &self.tag_registry
     }
@@ -773,14 +2311,33 @@ impl Timeline {
         &mut self.tag_registry
     }
 
+    /// The document as the editor shows it, without archived blocks.
     pub fn content(&self) -> String {
-        self.tree
-            .iter()
-            .map(|entry| entry.text.as_str())
-            .collect::<String>()
+        self.render(SensitiveContent::Included, ArchivedContent::Excluded)
+    }
+
+    /// Assembles the full document. Every path that hands timeline text
+    /// outside the backend should go through here or [`Self::render_date`].
+    /// Edit offsets are into the document without archived blocks.
+    pub fn render(&self, sensitive: SensitiveContent, archived: ArchivedContent) -> String {
+        let masked = self.masked_tag_ids(sensitive);
+        let mut content = String::with_capacity(self.summary().total_bytes);
+        for entry in self.tree.iter().filter(|entry| archived.shows(entry)) {
+            push_block_text(&mut content, entry, &masked);
+        }
+        content
     }
 
     pub fn log_for_date(&self, date: NaiveDate) -> Option<String> {
+        self.render_date(date, SensitiveContent::Included, ArchivedContent::Excluded)
+    }
+
+    pub fn render_date(
+        &self,
+        date: NaiveDate,
+        sensitive: SensitiveContent,
+        archived: ArchivedContent,
+    ) -> Option<String> {
         let summary = self.summary();
         let min_date = summary.min_date?;
         let max_date = summary.max_date?;
@@ -789,11 +2346,24 @@ impl Timeline {
             return None;
         }
 
+        if self.day_index.is_current(summary) && !self.day_index.contains(date) {
+            return None;
+        }
+
+        let masked = self.masked_tag_ids(sensitive);
         let mut content = String::new();
-        for entry in self.tree.iter() {
-            if entry.date == date {
-                content.push_str(entry.text.as_str());
+        // Seek past everything before the first block of the day, then skip
+        // subtrees whose dates don't span it, so a day costs O(log n) plus
+        // its own blocks rather than a scan of the whole tree.
+        let mut cursor = self.tree.cursor::<LatestDate>(());
+        cursor.seek(&LatestDate(Some(date)), Bias::Left);
+        while let Some(entry) = cursor.item() {
+            if entry.date == date && archived.shows(entry) {
+                push_block_text(&mut content, entry, &masked);
             }
+            cursor.search_forward(|summary: &TimelineSummary| {
+                summary.min_date <= Some(date) && Some(date) <= summary.max_date
+            });
         }
 
         if content.is_empty() {
@@ -803,20 +2373,504 @@ impl Timeline {
         }
     }
 
-    pub fn search_prefix(&self, query: &str) -> Vec<u32> {
+    fn masked_tag_ids(&self, sensitive: SensitiveContent) -> HashSet<u32> {
+        if sensitive == SensitiveContent::Included {
+            return HashSet::new();
+        }
+
+        let Some(root) = self.tag_registry.find_id(None, SENSITIVE_TAG) else {
+            return HashSet::new();
+        };
+
+        self.tag_registry
+            .iter()
+            .filter(|tag| self.tag_registry.has_ancestor(tag.id, root))
+            .map(|tag| tag.id)
+            .collect()
+    }
+
+    /// Whether the rendered document has a block that masking hides,
+    /// skipping subtrees without a sensitive tag.
+    fn shows_masked_blocks(&self) -> bool {
+        let masked = self.masked_tag_ids(SensitiveContent::Masked);
+        if masked.is_empty() {
+            return false;
+        }
+        let mut cursor = self.tree.filter::<_, ()>((), |summary: &TimelineSummary| {
+            masked.iter().any(|tag| summary.tags_filter.check(tag))
+        });
+        cursor.next();
+        while let Some(block) = cursor.item() {
+            if !block.archived && block.tags.iter().any(|tag| masked.contains(tag)) {
+                return true;
+            }
+            cursor.next();
+        }
+        false
+    }
+
+    /// Blocks dated within `range`, in document order. Sensitive and
+    /// archived blocks are left out unless `sensitive` and `archived`
+    /// include them.
+    pub fn blocks_in_range(
+        &self,
+        range: &DateRange,
+        sensitive: SensitiveContent,
+        archived: ArchivedContent,
+    ) -> Vec<&TaggedBlock> {
+        let masked = self.masked_tag_ids(sensitive);
+        let mut blocks = Vec::new();
+        let mut cursor = self.tree.filter::<_, ()>((), |summary: &TimelineSummary| {
+            range.overlaps(summary.min_date, summary.max_date)
+        });
+        cursor.next();
+        while let Some(block) = cursor.item() {
+            if range.contains(block.date)
+                && archived.shows(block)
+                && !block.tags.iter().any(|tag| masked.contains(tag))
+            {
+                blocks.push(block);
+            }
+            cursor.next();
+        }
+        blocks
+    }
+
+    /// Each day in `range` with blocks: where they are in the rendered
+    /// document and how many words they hold, for calendar views.
+    pub fn days(&self, range: &DateRange) -> BTreeMap<NaiveDate, DayEntry> {
+        let rebuilt;
+        let index = if self.day_index.is_current(self.summary()) {
+            &self.day_index
+        } else {
+            rebuilt = DayIndex::build(&self.tree);
+            &rebuilt
+        };
+        index
+            .range(&self.tree, range)
+            .map(|(date, day)| {
+                let day = DayEntry {
+                    start: visible_offset(&self.tree, day.start),
+                    end: visible_offset(&self.tree, day.end),
+                    ..day
+                };
+                (date, day)
+            })
+            .collect()
+    }
+
+    /// Words, characters and blocks written in `range`, per `period`, for
+    /// periods with any. Archived blocks don't count.
+    pub fn writing_stats(&self, range: &DateRange, period: StatsPeriod) -> Vec<PeriodStats> {
+        let mut days: BTreeMap<NaiveDate, PeriodStats> = BTreeMap::new();
+        let mut cursor = self.tree.filter::<_, ()>((), |summary: &TimelineSummary| {
+            range.overlaps(summary.min_date, summary.max_date)
+        });
+        cursor.next();
+        while let (Some(block), Some(summary)) = (cursor.item(), cursor.item_summary()) {
+            if range.contains(block.date) && !block.archived {
+                let day = days.entry(block.date).or_default();
+                day.words += summary.total_words;
+                day.chars += summary.total_chars;
+                day.blocks += 1;
+            }
+            cursor.next();
+        }
+
+        let mut periods: BTreeMap<NaiveDate, PeriodStats> = BTreeMap::new();
+        for (date, day) in days {
+            let start = period.start_of(date);
+            let stats = periods.entry(start).or_insert_with(|| PeriodStats {
+                start,
+                ..PeriodStats::default()
+            });
+            stats.words += day.words;
+            stats.chars += day.chars;
+            stats.blocks += day.blocks;
+            stats.active_days += 1;
+        }
+        periods.into_values().collect()
+    }
+
+    /// Characters and blocks written on each day of `year`.
+    pub fn daily_activity(&self, year: i32) -> DailyActivity {
+        let range = DateRange::new(
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year, 12, 31),
+        );
+        DailyActivity::new(year, &self.writing_stats(&range, StatsPeriod::Day))
+    }
+
+    /// Days with a non-empty, unarchived journal entry, in order. Untagged
+    /// blocks, daily notes among them, are journal entries too, as are
+    /// blocks tagged [`streaks::JOURNAL_TAG`] or under it.
+    fn journal_days(&self) -> BTreeSet<NaiveDate> {
+        let journal = self
+            .tag_registry
+            .find_colon_path(streaks::JOURNAL_TAG)
+            .map(|root| self.tag_registry.descendant_ids(root))
+            .unwrap_or_default();
+        self.tree
+            .iter()
+            .filter(|block| {
+                !block.archived
+                    && !block.text.trim().is_empty()
+                    && (block.tags.is_empty() || block.tags.iter().any(|tag| journal.contains(tag)))
+            })
+            .map(|block| block.date)
+            .collect()
+    }
+
+    /// The run of journal days reaching today (or yesterday, if nothing is
+    /// written yet today) and the longest run, ties going to the earliest.
+    pub fn streaks(&self) -> Streaks {
+        let days = self.journal_days();
+        Streaks {
+            current: streaks::current(days.iter().copied(), self.clock.today()),
+            longest: streaks::longest(days),
+        }
+    }
+
+    /// Rebuilds the day index from the blocks, in case it has drifted.
+    /// Returns how many days it holds.
+    pub fn rebuild_day_index(&mut self) -> usize {
+        self.day_index = DayIndex::build(&self.tree);
+        self.day_index.len()
+    }
+
+    /// Blocks tagged `tag_id` or one of its descendants, in document order.
+    /// Sensitive and archived blocks are left out unless `sensitive` and
+    /// `archived` include them.
+    pub fn blocks_tagged(
+        &self,
+        tag_id: u32,
+        sensitive: SensitiveContent,
+        archived: ArchivedContent,
+    ) -> Vec<&TaggedBlock> {
+        let masked = self.masked_tag_ids(sensitive);
+        self.tree
+            .iter()
+            .filter(|block| archived.shows(block))
+            .filter(|block| !block.tags.iter().any(|tag| masked.contains(tag)))
+            .filter(|block| {
+                block
+                    .tags
+                    .iter()
+                    .any(|&tag| self.tag_registry.has_ancestor(tag, tag_id))
+            })
+            .collect()
+    }
+
+    /// Case-insensitive substring search over block text. A block without
+    /// the query as written still matches when it has each of the query's
+    /// words in some form, stemmed in the block's language (see
+    /// [`crate::language`]). Sensitive blocks are never matched, since
+    /// excerpts would reveal their contents. Only blocks dated within
+    /// `range` are searched; subtrees wholly outside it are skipped.
+    pub fn search_text(
+        &self,
+        query: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Vec<TextMatch> {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        let masked = self.masked_tag_ids(SensitiveContent::Masked);
+        let mut blocks = Vec::new();
+        let mut cursor = self
+            .tree
+            .filter::<_, EntryCount>((), |summary: &TimelineSummary| {
+                range.overlaps(summary.min_date, summary.max_date)
+            });
+        cursor.next();
+        while let (Some(block), Some(summary)) = (cursor.item(), cursor.item_summary()) {
+            let index = cursor.start().0;
+            cursor.next();
+            if range.contains(block.date)
+                && archived.shows(block)
+                && !block.tags.iter().any(|tag| masked.contains(tag))
+            {
+                blocks.push((index, summary.fingerprint, block));
+            }
+        }
+        let analyses = self.analysis_cache.all(
+            blocks
+                .iter()
+                .map(|(_, key, block)| (*key, block.text.as_str())),
+        );
+        // Blocks without the query as written may still have its words in
+        // another form, stemmed in the block's language.
+        let mut query_stems = HashMap::new();
+        let mut matches = Vec::new();
+        for ((index, _, block), analysis) in blocks.into_iter().zip(analyses) {
+            let found = block
+                .text
+                .lines()
+                .find_map(|line| Some((line, snippet(line, &needle, SNIPPET_CONTEXT_CHARS)?)));
+            let (line, snippet, occurrences) = match found {
+                Some((line, snippet)) => (line, snippet, count_matches(&block.text, &needle)),
+                None => {
+                    let stems = query_stems
+                        .entry(analysis.language)
+                        .or_insert_with(|| language::query_stems(&needle, analysis.language));
+                    let Some((line, word, occurrences)) = analysis.find(&block.text, stems) else {
+                        continue;
+                    };
+                    let Some(snippet) = snippet(line, word, SNIPPET_CONTEXT_CHARS) else {
+                        continue;
+                    };
+                    (line, snippet, occurrences)
+                }
+            };
+            let Ok(block_index) = u32::try_from(index) else {
+                break;
+            };
+            matches.push(TextMatch {
+                block_index,
+                date: block.date,
+                excerpt: line.trim().chars().take(SEARCH_EXCERPT_CHARS).collect(),
+                snippet,
+                occurrences,
+                tags: self.hashtag_names(block),
+            });
+        }
+        matches
+    }
+
+    /// Blocks with text matching the regular expression `pattern`, like
+    /// [`Timeline::search_text`] but case-sensitive unless the pattern
+    /// says otherwise (`(?i)`). Patterns match within a block, so `.` stops
+    /// at line ends unless `(?s)` is set. Only blocks dated within `range`
+    /// are scanned or count toward the budget.
+    pub fn search_regex(
+        &self,
+        pattern: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Result<RegexSearch, RegexSearchError> {
+        self.search_regex_within(pattern, range, archived, REGEX_SCAN_BUDGET)
+    }
+
+    fn search_regex_within(
+        &self,
+        pattern: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+        budget: usize,
+    ) -> Result<RegexSearch, RegexSearchError> {
+        let regex = self.regex_cache.get(pattern)?;
+        let masked = self.masked_tag_ids(SensitiveContent::Masked);
+        let mut scanned = 0;
+        let mut found = RegexSearch {
+            matches: Vec::new(),
+            truncated: false,
+        };
+        let mut cursor = self
+            .tree
+            .filter::<_, EntryCount>((), |summary: &TimelineSummary| {
+                range.overlaps(summary.min_date, summary.max_date)
+            });
+        cursor.next();
+        while let Some(block) = cursor.item() {
+            let index = cursor.start().0;
+            cursor.next();
+            if !range.contains(block.date)
+                || !archived.shows(block)
+                || block.tags.iter().any(|tag| masked.contains(tag))
+            {
+                continue;
+            }
+            scanned += block.text.len();
+            if scanned > budget {
+                found.truncated = true;
+                break;
+            }
+            let Some(snippet) = regex_snippet(&block.text, &regex, SNIPPET_CONTEXT_CHARS) else {
+                continue;
+            };
+            let Ok(block_index) = u32::try_from(index) else {
+                break;
+            };
+            found.matches.push(TextMatch {
+                block_index,
+                date: block.date,
+                excerpt: snippet_line(&block.text, &regex)
+                    .trim()
+                    .chars()
+                    .take(SEARCH_EXCERPT_CHARS)
+                    .collect(),
+                snippet,
+                occurrences: regex
+                    .find_iter(&block.text)
+                    .filter(|m| !m.is_empty())
+                    .count(),
+                tags: self.hashtag_names(block),
+            });
+        }
+        Ok(found)
+    }
+
+    /// The block's tags as `#full:name`s.
+    fn hashtag_names(&self, block: &TaggedBlock) -> Vec<String> {
+        block
+            .tags
+            .iter()
+            .filter_map(|&tag| self.tag_registry.full_name(tag))
+            .map(|name| format!("#{name}"))
+            .collect()
+    }
+
+    /// Blocks dated within `range` with a tag starting with `query`.
+    pub fn search_prefix(
+        &self,
+        query: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Vec<u32> {
         let tag_ids = self.tag_registry.tag_ids_with_prefix(query);
-        self.block_ids_with_tags(&tag_ids)
+        self.block_ids_with_tags(&tag_ids, range, archived)
     }
 
-    pub fn search_infix(&self, query: &str) -> Vec<u32> {
+    /// Blocks dated within `range` with a tag containing `query`.
+    pub fn search_infix(
+        &self,
+        query: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Vec<u32> {
         let tag_ids = self.tag_registry.tag_ids_with_infix(query);
-        self.block_ids_with_tags(&tag_ids)
+        self.block_ids_with_tags(&tag_ids, range, archived)
+    }
+
+    /// Blocks dated within `range` matching a boolean tag query such as
+    /// `#project AND #type:journal NOT #archived`; see [`crate::tag_query`].
+    pub fn search_tags_query(
+        &self,
+        query: &str,
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Result<Vec<u32>, TagQueryError> {
+        let registry = &self.tag_registry;
+        let query = TagQuery::parse(query)?.compile(&|path: &str| {
+            registry
+                .find_colon_path(path)
+                .map(|id| registry.descendant_ids(id))
+                .unwrap_or_default()
+        });
+        Ok(self.block_ids_matching(
+            range,
+            archived,
+            |summary| query.might_match(&summary.tags_filter),
+            |block| query.matches(&block.tags),
+        ))
     }
 
     pub fn autocomplete_tags(&self, query: &str) -> Vec<TagSuggestion> {
         self.tag_registry.autocomplete(query)
     }
 
+    /// The most frequent words of block `index`, or `None` past the end.
+    pub fn block_terms(&self, index: usize) -> Option<Arc<[Term]>> {
+        let mut cursor = self.tree.cursor::<EntryCount>(());
+        cursor.seek(&EntryCount(index), Bias::Right);
+        let (block, summary) = (cursor.item()?, cursor.item_summary()?);
+        Some(self.term_cache.get(summary.fingerprint, &block.text))
+    }
+
+    /// Every block's terms, in document order.
+    fn all_block_terms(&self) -> Vec<Arc<[Term]>> {
+        let mut blocks = Vec::with_capacity(self.tree.summary().entry_count);
+        let mut cursor = self.tree.cursor::<()>(());
+        cursor.next();
+        while let (Some(block), Some(summary)) = (cursor.item(), cursor.item_summary()) {
+            blocks.push((summary.fingerprint, block.text.as_str()));
+            cursor.next();
+        }
+        self.term_cache.all(blocks)
+    }
+
+    /// Tags block `index` doesn't have yet, best first: each scores the
+    /// term overlap of the blocks carrying it with this one. Masked
+    /// sensitive blocks don't count. `None` past the end.
+    pub fn suggest_block_tags(&self, index: usize, limit: usize) -> Option<Vec<TagSuggestion>> {
+        let own = block_at(&self.tree, index)?.1.tags.clone();
+        let masked = self.masked_tag_ids(SensitiveContent::Masked);
+        let terms = self.all_block_terms();
+        let mut scores: HashMap<u32, u32> = HashMap::new();
+        for (other, block) in self.tree.iter().enumerate() {
+            if other == index
+                || block.tags.is_empty()
+                || block.tags.iter().any(|tag| masked.contains(tag))
+            {
+                continue;
+            }
+            let score = terms::similarity(&terms[index], &terms[other]);
+            if score == 0 {
+                continue;
+            }
+            for tag in block.tags.iter().filter(|tag| !own.contains(tag)) {
+                *scores.entry(*tag).or_default() += score;
+            }
+        }
+
+        let mut ranked: Vec<(u32, String, Option<String>)> = scores
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let color = self.tag_registry.get_tag(id)?.color.clone();
+                Some((score, self.tag_registry.full_name(id)?, color))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        ranked.truncate(limit);
+        Some(
+            ranked
+                .into_iter()
+                .map(|(_, name, color)| TagSuggestion {
+                    name: format!("#{name}"),
+                    color,
+                })
+                .collect(),
+        )
+    }
+
+    /// Blocks sharing the most words with block `index`, best first.
+    /// Masked sensitive blocks and archived blocks are neither compared nor
+    /// returned. `None` past the end.
+    pub fn related_blocks(
+        &self,
+        index: usize,
+        limit: usize,
+        sensitive: SensitiveContent,
+    ) -> Option<Vec<RelatedBlock>> {
+        block_at(&self.tree, index)?;
+        let masked = self.masked_tag_ids(sensitive);
+        let terms = self.all_block_terms();
+        let mut related: Vec<RelatedBlock> = self
+            .tree
+            .iter()
+            .enumerate()
+            .filter(|(other, block)| {
+                *other != index
+                    && !block.archived
+                    && !block.tags.iter().any(|tag| masked.contains(tag))
+            })
+            .filter_map(|(other, block)| {
+                let score = terms::similarity(&terms[index], &terms[other]);
+                (score > 0).then(|| RelatedBlock {
+                    index: u32::try_from(other).unwrap_or(u32::MAX),
+                    date: block.date,
+                    score,
+                })
+            })
+            .collect();
+        related.sort_by(|a, b| b.score.cmp(&a.score).then(a.index.cmp(&b.index)));
+        related.truncate(limit);
+        Some(related)
+    }
+
     pub fn intern_tag(&mut self, raw: &str) -> Result<TagDescriptor, InternTagError> {
         let trimmed = raw.trim();
         if trimmed.is_empty() {
@@ -861,10 +2915,9 @@ impl Timeline {
         block_index: usize,
         tags: &[String],
     ) -> Result<Vec<TagDescriptor>, AssignBlockTagsError> {
-        let mut blocks: Vec<TaggedBlock> = self.tree.iter().cloned().collect();
-        let block = blocks
-            .get_mut(block_index)
+        let (start, block) = block_at(&self.tree, block_index)
             .ok_or(AssignBlockTagsError::InvalidBlock { index: block_index })?;
+        let mut block = block.clone();
 
         let mut descriptors = Vec::new();
         let mut tag_ids = Vec::new();
@@ -875,860 +2928,5211 @@ impl Timeline {
             descriptors.push(descriptor);
         }
 
-        block.tags = tag_ids;
+        let previous = std::mem::replace(&mut block.tags, tag_ids);
+        block.updated_at = Some(self.clock.now());
+        let end = start + block.char_count();
+        if start < end {
+            self.history.record(vec![UndoOp::Tags {
+                start,
+                end,
+                tags: previous,
+            }]);
+        }
 
-        self.tree = SumTree::from_iter(blocks, ());
+        replace_block(&mut self.tree, block_index, [block]);
 
         Ok(descriptors)
     }
 
-    pub fn list_tags(&self) -> Vec<TagDescriptor> {
-        let mut descriptors = Vec::new();
-        for tag in self.tag_registry.iter() {
-            if let Some(name) = self.tag_registry.full_name(tag.id) {
-                let color = tag
-                    .color
-                    .clone()
-                    .unwrap_or_else(|| tag_palette::color_for(tag.id).to_string());
-                descriptors.push(TagDescriptor {
-                    id: tag.id,
-                    name: format!("#{name}"),
-                    color,
-                });
-            }
+    /// Archives block `block_index`, or brings it back when `archived` is
+    /// false. Either way the rendered document changes, so the version
+    /// moves on and editors holding the old one get a conflict and refetch.
+    /// Returns the new version.
+    pub fn archive_block(
+        &mut self,
+        block_index: usize,
+        archived: bool,
+    ) -> Result<u64, ArchiveBlockError> {
+        let (_, block) = block_at(&self.tree, block_index)
+            .ok_or(ArchiveBlockError::InvalidBlock { index: block_index })?;
+        if block.archived == archived {
+            return Ok(self.version);
         }
-        descriptors.sort_by(|a, b| a.name.cmp(&b.name));
-        descriptors
+        if self.is_frozen(block.date) {
+            return Err(ArchiveBlockError::Frozen { date: block.date });
+        }
+
+        let block = TaggedBlock {
+            archived,
+            updated_at: Some(self.clock.now()),
+            ..block.clone()
+        };
+        let date = block.date;
+        replace_block(&mut self.tree, block_index, [block]);
+        self.day_index.update(&self.tree, &[date].into());
+        self.version += 1;
+        Ok(self.version)
     }
 
-    pub fn list_blocks(&self) -> Vec<BlockMetadata> {
-        let mut metadata = Vec::new();
-        let mut offset: u32 = 0;
-        for (index, block) in self.tree.iter().enumerate() {
-            let char_count = u32::try_from(block.char_count()).unwrap_or(u32::MAX);
-            let start = offset;
-            let end = offset.saturating_add(char_count);
-            metadata.push(BlockMetadata {
-                index: u32::try_from(index).unwrap_or(u32::MAX),
-                start_offset: start,
-                end_offset: end,
-                date: block.date.to_string(),
-                tags: block.tags.clone(),
-            });
-            offset = end;
-        }
-        metadata
+    /// The insert [`Self::append_to_block`] makes, in timeline offsets.
+    pub fn append_op(&self, block_index: usize, text: &str) -> Option<TextOperation> {
+        let (start, block) = block_at(&self.tree, block_index)?;
+        Some(TextOperation::Insert {
+            position: start + block.char_count(),
+            text: appended_lines(&block.text, text),
+        })
     }
 
-    pub fn apply_ops(
+    /// Adds `text` as new lines at the end of block `block_index`, keeping
+    /// the block's date and tags. Returns the new version.
+    pub fn append_to_block(
         &mut self,
-        base_version: u64,
-        ops: &[TextOperation],
-    ) -> Result<u64, ApplyOpsError> {
-        if base_version != self.version {
-            return Err(ApplyOpsError::VersionMismatch {
-                expected: self.version,
-                actual: base_version,
-            });
-        }
-
-        if ops.is_empty() {
-            return Ok(self.version);
+        block_index: usize,
+        text: &str,
+    ) -> Result<u64, AppendToBlockError> {
+        let (start, block) = block_at(&self.tree, block_index)
+            .ok_or(AppendToBlockError::InvalidBlock { index: block_index })?;
+        if self.is_frozen(block.date) {
+            return Err(AppendToBlockError::Frozen { date: block.date });
         }
 
-        let today = chrono::Utc::now().date_naive();
-        self.tree.apply_ops(ops, today)?;
+        let appended = appended_lines(&block.text, text);
+        let end = start + block.char_count();
+        self.history
+            .record(vec![UndoOp::Text(TextOperation::Delete {
+                start_position: end,
+                end_position: end + appended.chars().count(),
+            })]);
+        let touched = BTreeSet::from([block.date]);
+        let block = block.edited(format!("{}{appended}", block.text), self.clock.now());
+        let before = self.tree.clone();
+        replace_block(&mut self.tree, block_index, [block]);
+        let shift = Shift::between(&before, &self.tree, end);
         self.version += 1;
+        self.day_index.update(&self.tree, &touched);
+        self.task_index.update(&self.tree, &[shift]);
+        self.reparse_dates(&touched);
         Ok(self.version)
     }
 
-    fn block_ids_with_tags(&self, tag_ids: &[u32]) -> Vec<u32> {
-        if tag_ids.is_empty() {
-            return Vec::new();
+    /// Checkbox tasks passing `filter`, in document order. Tasks in
+    /// sensitive blocks are left out unless `sensitive` includes them.
+    pub fn tasks(&self, filter: &TaskFilter, sensitive: SensitiveContent) -> Vec<Task> {
+        let rebuilt;
+        let index = if self.task_index.is_current(self.summary()) {
+            &self.task_index
+        } else {
+            rebuilt = TaskIndex::build(&self.tree);
+            &rebuilt
+        };
+        let masked = self.masked_tag_ids(sensitive);
+        let mut cursor = self
+            .tree
+            .cursor::<Dimensions<Chars, EntryCount, Visible<Chars>>>(());
+        let mut tasks = Vec::new();
+        let mut previous_block = None;
+        let mut task_index = 0;
+        for (line, task) in index.iter() {
+            cursor.seek_forward(&Chars(line), Bias::Right);
+            let Some(block) = cursor.item() else {
+                break;
+            };
+            let Dimensions(Chars(start), EntryCount(block_index), Visible(Chars(shown))) =
+                *cursor.start();
+            if previous_block == Some(block_index) {
+                task_index += 1;
+            } else {
+                task_index = 0;
+                previous_block = Some(block_index);
+            }
+            if !filter.matches(task.done, block)
+                || block.tags.iter().any(|tag| masked.contains(tag))
+            {
+                continue;
+            }
+            tasks.push(Task {
+                block_id: u32::try_from(block_index).unwrap_or(u32::MAX),
+                task_index,
+                date: block.date,
+                done: task.done,
+                text: task.text.clone(),
+                due: task.due,
+                offset: shown + if block.archived { 0 } else { line - start },
+            });
         }
+        tasks
+    }
 
-        let matching: HashSet<u32> = tag_ids.iter().copied().collect();
+    /// Open tasks due before `date`, soonest first. Archived blocks and,
+    /// unless `sensitive` includes them, sensitive ones are left out.
+    pub fn tasks_due_before(&self, date: NaiveDate, sensitive: SensitiveContent) -> Vec<Task> {
+        let open = TaskFilter {
+            done: Some(false),
+            ..TaskFilter::default()
+        };
+        let mut due: Vec<Task> = self
+            .tasks(&open, sensitive)
+            .into_iter()
+            .filter(|task| task.due.is_some_and(|due| due < date))
+            .collect();
+        // Stable, so tasks due the same day stay in document order.
+        due.sort_by_key(|task| task.due);
+        due
+    }
 
-        self.tree
-            .iter()
+    /// Open tasks whose due date has passed.
+    pub fn overdue_tasks(&self, sensitive: SensitiveContent) -> Vec<Task> {
+        self.tasks_due_before(self.clock.today(), sensitive)
+    }
+
+    /// Character offset into the timeline of the mark between the brackets
+    /// of task `task_index` of block `block_index`, which
+    /// [`Self::toggle_task`] rewrites.
+    pub fn task_mark(&self, block_index: usize, task_index: usize) -> Option<usize> {
+        let rebuilt;
+        let index = if self.task_index.is_current(self.summary()) {
+            &self.task_index
+        } else {
+            rebuilt = TaskIndex::build(&self.tree);
+            &rebuilt
+        };
+        let (start, block) = block_at(&self.tree, block_index)?;
+        let (line, task) = task_in_block(index, start, start + block.char_count(), task_index)?;
+        Some(line + task.column)
+    }
+
+    /// Character range of block `block_index` in the timeline.
+    pub fn block_range(&self, block_index: usize) -> Option<std::ops::Range<usize>> {
+        block_at(&self.tree, block_index).map(|(start, block)| start..start + block.char_count())
+    }
+
+    /// Ticks task `task_index` of block `block_index`, or clears it if it
+    /// was ticked, by rewriting the mark between its brackets. Returns the
+    /// task as it now is.
+    pub fn toggle_task(
+        &mut self,
+        block_index: usize,
+        task_index: usize,
+    ) -> Result<Task, ToggleTaskError> {
+        let invalid = || ToggleTaskError::InvalidTask {
+            block: block_index,
+            task: task_index,
+        };
+        if !self.task_index.is_current(self.summary()) {
+            self.task_index = TaskIndex::build(&self.tree);
+        }
+        let (start, block) = block_at(&self.tree, block_index).ok_or_else(invalid)?;
+        let end = start + block.char_count();
+        let (line, task) =
+            task_in_block(&self.task_index, start, end, task_index).ok_or_else(invalid)?;
+        let done = !task.done;
+        let mark = line + task.column;
+        let toggled = Task {
+            block_id: u32::try_from(block_index).unwrap_or(u32::MAX),
+            task_index: u32::try_from(task_index).unwrap_or(u32::MAX),
+            date: block.date,
+            done,
+            text: task.text.clone(),
+            due: task.due,
+            offset: visible_offset(&self.tree, line),
+        };
+
+        // The mark may sit in a later block than the line's start.
+        let mut cursor = self.tree.cursor::<Dimensions<Chars, EntryCount>>(());
+        cursor.seek(&Chars(mark), Bias::Right);
+        let Dimensions(Chars(mark_block_start), EntryCount(mark_block), ()) = *cursor.start();
+        let block = cursor
+            .item()
+            .expect("indexed task marks are in the document");
+        drop(cursor);
+        if self.is_frozen(block.date) {
+            return Err(ToggleTaskError::Frozen { date: block.date });
+        }
+        let column = mark - mark_block_start;
+        let text = block
+            .text
+            .chars()
             .enumerate()
-            .filter_map(|(index, block)| {
-                if block.tags.iter().any(|tag| matching.contains(tag)) {
-                    u32::try_from(index).ok()
-                } else {
-                    None
-                }
+            .map(|(at, ch)| match (at == column, done) {
+                (true, true) => 'x',
+                (true, false) => ' ',
+                (false, _) => ch,
             })
-            .collect()
+            .collect();
+        let touched = BTreeSet::from([block.date]);
+        let block = block.edited(text, self.clock.now());
+        // Undone like an edit that replaced the mark.
+        let removed = TextOperation::Delete {
+            start_position: mark,
+            end_position: mark + 1,
+        };
+        self.history.record(vec![
+            inverse_of(&self.tree, &removed),
+            UndoOp::Text(removed),
+        ]);
+        replace_block(&mut self.tree, mark_block, [block]);
+        self.version += 1;
+        self.day_index.update(&self.tree, &touched);
+        self.reparse_dates(&touched);
+        if let Some(task) = self.task_index.get_mut(line) {
+            task.done = done;
+        }
+        Ok(toggled)
     }
 
-    pub fn save(&self) -> Result<(), TimelinePersistenceError> {
-        let path = get_storage_path()?;
-        self.save_to_path(path)
-    }
+    /// Splits a block in two at the character `offset` within it. The second
+    /// half stays where it is in the document but can be given its own
+    /// `date` and, when `tags` is given, its own tags; otherwise it keeps
+    /// the original's. Returns the descriptors of the new tags.
+    pub fn split_block(
+        &mut self,
+        block_index: usize,
+        offset: usize,
+        date: Option<NaiveDate>,
+        tags: Option<&[String]>,
+    ) -> Result<Vec<TagDescriptor>, SplitBlockError> {
+        let (block_start, block) = block_at(&self.tree, block_index)
+            .filter(|(_, block)| !block.archived)
+            .ok_or(SplitBlockError::InvalidBlock { index: block_index })?;
+        let block = block.clone();
+        let invalid_offset = SplitBlockError::InvalidOffset {
+            index: block_index,
+            offset,
+        };
+        if offset == 0 || offset >= block.char_count() {
+            return Err(invalid_offset);
+        }
+        let (left, right) = split_at_char(&block.text, offset).ok_or(invalid_offset)?;
+        let new_date = date.unwrap_or(block.date);
+        if new_date != block.date {
+            for date in [block.date, new_date] {
+                if self.is_frozen(date) {
+                    return Err(SplitBlockError::Frozen { date });
+                }
+            }
+        }
 
-    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), TimelinePersistenceError> {
-        let path = path.as_ref();
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        let mut descriptors = Vec::new();
+        for tag in tags.unwrap_or_default() {
+            descriptors.push(self.intern_tag(tag)?);
         }
 
-        let exported_tags = self.tag_registry.export();
-        let snapshot = TimelineSnapshot {
-            version: self.version,
-            blocks: self.tree.items(()),
-            tag_registry: if exported_tags.is_empty() {
-                None
-            } else {
-                Some(TagRegistrySnapshot::Hierarchical(exported_tags))
+        let original = TaggedBlock {
+            text: right,
+            ..block.clone()
+        };
+        let mut second = TaggedBlock {
+            date: new_date,
+            tags: match tags {
+                Some(_) => descriptors.iter().map(|descriptor| descriptor.id).collect(),
+                None => original.tags.clone(),
             },
+            ..original.clone()
         };
+        if second != original {
+            let start = block_start + offset;
+            let end = start + second.char_count();
+            self.history.record(vec![
+                UndoOp::Restore {
+                    position: start,
+                    blocks: vec![original.clone()],
+                },
+                UndoOp::Text(TextOperation::Delete {
+                    start_position: start,
+                    end_position: end,
+                }),
+            ]);
+            second.updated_at = Some(self.clock.now());
+        }
 
-        let data = serde_json::to_vec_pretty(&snapshot)?;
-        fs::write(path, data)?;
-        Ok(())
+        let first = TaggedBlock {
+            text: left,
+            ..block
+        };
+        let before = self.tree.clone();
+        replace_block(&mut self.tree, block_index, [first, second]);
+        let shift = Shift::between(&before, &self.tree, block_start);
+        // Block indexes after the split move, so clients must refetch.
+        self.version += 1;
+        self.day_index
+            .update(&self.tree, &BTreeSet::from([original.date, new_date]));
+        self.task_index.update(&self.tree, &[shift]);
+        if new_date != original.date && !self.metric_parser.is_empty() {
+            self.reparse_dates(&BTreeSet::from([original.date, new_date]));
+        }
+        Ok(descriptors)
     }
 
-    pub fn load() -> Result<Self, TimelinePersistenceError> {
-        let path = get_storage_path()?;
-        Self::load_from_path(path)
+    /// Moves a tag in the hierarchy (see [`TagRegistry::reparent`]) and
+    /// retags blocks with the moved tags' new ids. Returns the tag's new id.
+    pub fn reparent_tag(
+        &mut self,
+        id: u32,
+        new_parent: Option<u32>,
+    ) -> Result<u32, ReparentTagError> {
+        let ids = self.tag_registry.reparent(id, new_parent)?;
+        if ids.is_empty() {
+            return Ok(id);
+        }
+
+        self.retag(&ids);
+        Ok(ids[&id])
     }
 
-    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self, TimelinePersistenceError> {
-        let path = path.as_ref();
-        match fs::read_to_string(path) {
-            Ok(contents) => {
-                let snapshot: TimelineSnapshot = serde_json::from_str(&contents)?;
-                let tree = SumTree::from_iter(snapshot.blocks, ());
-                let tag_registry = match snapshot.tag_registry {
-                    Some(TagRegistrySnapshot::Hierarchical(tags)) => TagRegistry::from_tags(tags),
-                    Some(TagRegistrySnapshot::Flat(map)) => {
-                        let parsed: HashMap<u32, String> = map
-                            .into_iter()
-                            .filter_map(|(id, tag)| id.parse::<u32>().ok().map(|id| (id, tag)))
-                            .collect();
-                        TagRegistry::from_map(parsed)
-                    }
-                    None => TagRegistry::new(),
-                };
-                Ok(Self {
-                    tree,
-                    version: snapshot.version,
-                    tag_registry,
-                })
-            }
-            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
-            Err(err) => Err(err.into()),
+    /// Renames a tag (see [`TagRegistry::rename`]). Blocks keep their tag
+    /// ids, so nothing is retagged.
+    pub fn rename_tag(&mut self, id: u32, new_name: &str) -> Result<(), RenameTagError> {
+        self.tag_registry.rename(id, new_name)
+    }
+
+    /// Deletes a tag (see [`TagRegistry::remove`]) and strips the removed
+    /// ids from every block and from the undo history, so undoing an edit
+    /// can't bring a deleted tag back.
+    pub fn delete_tag(&mut self, id: u32, cascade: bool) -> Result<TagDeletion, DeleteTagError> {
+        let removed = self.tag_registry.remove(id, cascade)?;
+        let mut blocks = 0;
+        let retagged: Vec<TaggedBlock> = self
+            .tree
+            .iter()
+            .cloned()
+            .map(|mut block| {
+                let before = block.tags.len();
+                block.tags.retain(|tag| !removed.contains(tag));
+                if block.tags.len() != before {
+                    blocks += 1;
+                }
+                block
+            })
+            .collect();
+        if blocks > 0 {
+            self.tree = SumTree::from_iter(retagged, ());
         }
+        self.history
+            .remap_tags(|tags| tags.retain(|tag| !removed.contains(tag)));
+        Ok(TagDeletion {
+            tags: removed.len(),
+            blocks,
+        })
     }
-}
 
-pub fn get_storage_path() -> Result<PathBuf, TimelinePersistenceError> {
-    if let Ok(custom) = env::var("SIGHTLINE_TIMELINE_PATH") {
-        return Ok(PathBuf::from(custom));
+    /// Rewrites block tags, and those in the undo history, from old ids to
+    /// new ones.
+    fn retag(&mut self, ids: &HashMap<u32, u32>) {
+        let remap = |tags: &mut Vec<u32>| {
+            for tag in tags.iter_mut() {
+                *tag = ids.get(tag).copied().unwrap_or(*tag);
+            }
+            tags.sort_unstable();
+            tags.dedup();
+        };
+        let blocks = self.tree.iter().cloned().map(|mut block| {
+            remap(&mut block.tags);
+            block
+        });
+        self.tree = SumTree::from_iter(blocks.collect::<Vec<_>>(), ());
+        self.history.remap_tags(remap);
     }
-    let base = config_dir().ok_or(TimelinePersistenceError::MissingConfigDir)?;
-    Ok(base.join("sightline").join("timeline.json"))
-}
 
-fn split_at_char(input: &str, char_index: usize) -> Option<(String, String)> {
-    if char_index == 0 {
-        return Some((String::new(), input.to_string()));
+    /// How the legacy flat registry this timeline was loaded from was
+    /// rebuilt as a hierarchy, or `None` once that has been confirmed (or
+    /// if the timeline never had one).
+    pub fn registry_migration_preview(&self) -> Option<Vec<TagMigration>> {
+        self.legacy_tags
+            .as_ref()
+            .map(|legacy| legacy.migrations(&self.tag_registry))
     }
 
-    let mut count = 0;
-    for (byte, _) in input.char_indices() {
-        if count == char_index {
-            let (left, right) = input.split_at(byte);
-            return Some((left.to_string(), right.to_string()));
+    /// Confirms the legacy registry's migration, first moving the legacy
+    /// tags in `paths` (by legacy id) to the given colon paths. Blocks are
+    /// retagged, and tags the rebuild created that nothing uses any more
+    /// are removed. Returns where every legacy tag ended up.
+    pub fn commit_registry_migration(
+        &mut self,
+        paths: &HashMap<u32, String>,
+    ) -> Result<Vec<TagMigration>, RegistryMigrationError> {
+        let legacy = self
+            .legacy_tags
+            .as_ref()
+            .ok_or(RegistryMigrationError::NotLegacy)?;
+        for (&id, path) in paths {
+            if !legacy.ids.contains_key(&id) {
+                return Err(RegistryMigrationError::UnknownTag(id));
+            }
+            if path
+                .trim()
+                .trim_start_matches('#')
+                .split(':')
+                .all(|segment| segment.trim().is_empty())
+            {
+                return Err(RegistryMigrationError::EmptyPath(id));
+            }
         }
-        count += 1;
-    }
 
-    if count == char_index {
-        Some((input.to_string(), String::new()))
-    } else {
-        None
+        let mut legacy = self.legacy_tags.take().unwrap_or_default();
+        let mut rebuilt = BTreeSet::new();
+        for &tag in legacy.ids.values() {
+            let mut ancestor = Some(tag);
+            while let Some(id) = ancestor.filter(|id| rebuilt.insert(*id)) {
+                ancestor = self.tag_registry.get_tag(id).and_then(|tag| tag.parent_id);
+            }
+        }
+
+        let mut ids = HashMap::new();
+        let mut keep = HashSet::new();
+        for (legacy_id, tag) in legacy.ids.iter_mut() {
+            if let Some(path) = paths.get(legacy_id) {
+                let target = self
+                    .tag_registry
+                    .intern_colon_path(path.trim().trim_start_matches('#'))
+                    .unwrap_or(*tag);
+                if target != *tag {
+                    ids.entry(*tag).or_insert(target);
+                    *tag = target;
+                }
+            }
+            keep.insert(*tag);
+        }
+
+        if !ids.is_empty() {
+            self.retag(&ids);
+        }
+        keep.extend(
+            self.tree
+                .iter()
+                .flat_map(|block| block.tags.iter().copied()),
+        );
+        self.tag_registry.prune(&rebuilt, &keep);
+        Ok(legacy.migrations(&self.tag_registry))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::from_str;
-    use std::env;
-    use tempfile::tempdir;
+    pub fn list_tags(&self) -> Vec<TagDescriptor> {
+        let mut descriptors = Vec::new();
+        for tag in self.tag_registry.iter() {
+            if let Some(name) = self.tag_registry.full_name(tag.id) {
+                descriptors.push(TagDescriptor {
+                    id: tag.id,
+                    name: format!("#{name}"),
+                    color: display_color(tag),
+                });
+            }
+        }
+        descriptors.sort_by(|a, b| a.name.cmp(&b.name));
+        descriptors
+    }
 
-    fn sample_insert(text: &str) -> TextOperation {
-        TextOperation::Insert {
-            position: 0,
-            text: text.to_string(),
+    /// The tag hierarchy, roots and siblings by name, with how many blocks
+    /// use each tag.
+    pub fn tag_tree(&self) -> Vec<TagNode> {
+        let registry = &self.tag_registry;
+        let mut direct: HashMap<u32, u32> = HashMap::new();
+        let mut total: HashMap<u32, u32> = HashMap::new();
+        for block in self.tree.iter() {
+            let mut counted = HashSet::new();
+            for &tag in &block.tags {
+                *direct.entry(tag).or_default() += 1;
+                let mut current = Some(tag);
+                // Stop at the first tag already counted: its ancestors were too.
+                while let Some(id) = current.filter(|&id| counted.insert(id)) {
+                    current = registry.get_tag(id).and_then(|tag| tag.parent_id);
+                }
+            }
+            for id in counted {
+                *total.entry(id).or_default() += 1;
+            }
         }
+
+        registry
+            .child_ids(None)
+            .into_iter()
+            .filter_map(|id| tag_node(registry, id, &direct, &total))
+            .collect()
     }
 
-    #[test]
-    fn tag_registry_assigns_unique_ids() {
-        let mut registry = TagRegistry::new();
-        let alpha = registry.intern_segment(None, "alpha");
-        let beta = registry.intern_segment(None, "beta");
+    pub fn list_blocks(&self) -> Vec<BlockMetadata> {
+        self.list_blocks_with_previews(0, SensitiveContent::Masked)
+    }
 
-        assert_ne!(alpha, beta);
-        assert_eq!(
-            registry.get_tag(alpha).map(|tag| tag.name.as_str()),
-            Some("alpha")
-        );
-        assert_eq!(
-            registry.get_tag(beta).map(|tag| tag.name.as_str()),
-            Some("beta")
-        );
+    /// Like [`Self::list_blocks`], with the first `preview_chars`
+    /// characters of each block (at most [`MAX_PREVIEW_CHARS`]). Sensitive
+    /// blocks are masked unless `sensitive` includes them.
+    pub fn list_blocks_with_previews(
+        &self,
+        preview_chars: usize,
+        sensitive: SensitiveContent,
+    ) -> Vec<BlockMetadata> {
+        self.list_blocks_page(&PageRequest::default(), preview_chars, sensitive)
+            .items
     }
 
-    #[test]
-    fn tag_registry_reuses_existing_id_for_same_tag() {
-        let mut registry = TagRegistry::new();
-        let first = registry
-            .intern_path(["project", "sightline"])
-            .expect("path id");
-        let second = registry
-            .intern_path(["project", "sightline"])
-            .expect("path id");
+    /// One page of [`Self::list_blocks_with_previews`], found without
+    /// visiting the blocks before it.
+    pub fn list_blocks_page(
+        &self,
+        page: &PageRequest,
+        preview_chars: usize,
+        sensitive: SensitiveContent,
+    ) -> Page<BlockMetadata> {
+        let preview_chars = preview_chars.min(MAX_PREVIEW_CHARS);
+        let masked = if preview_chars > 0 {
+            self.masked_tag_ids(sensitive)
+        } else {
+            HashSet::new()
+        };
+        let rebuilt;
+        let task_index = if self.task_index.is_current(self.summary()) {
+            &self.task_index
+        } else {
+            rebuilt = TaskIndex::build(&self.tree);
+            &rebuilt
+        };
+        let total = self.entry_count();
+        let bounds = page.bounds(total);
+        // Offsets are into the rendered document, which skips archived blocks.
+        let mut cursor = self
+            .tree
+            .cursor::<Dimensions<EntryCount, Visible<Chars>, Chars>>(());
+        cursor.seek(&EntryCount(bounds.start), Bias::Right);
+        let mut metadata = Vec::with_capacity(bounds.len());
+        let mut offset = u32::try_from(cursor.start().1 .0 .0).unwrap_or(u32::MAX);
+        for index in bounds.clone() {
+            let Some(block) = cursor.item() else {
+                break;
+            };
+            let Chars(block_start) = cursor.start().2;
+            let language = cursor.item_summary().and_then(|summary| {
+                self.analysis_cache
+                    .get(summary.fingerprint, &block.text)
+                    .language
+            });
+            cursor.next();
+            let char_count = if block.archived {
+                0
+            } else {
+                u32::try_from(block.char_count()).unwrap_or(u32::MAX)
+            };
+            let start = offset;
+            let end = offset.saturating_add(char_count);
+            let (preview, is_truncated) = if preview_chars > 0 {
+                let hidden = block.tags.iter().any(|tag| masked.contains(tag));
+                let preview: String = block
+                    .text
+                    .chars()
+                    .take(preview_chars)
+                    .map(|ch| {
+                        if hidden && ch != '\n' {
+                            SENSITIVE_MASK
+                        } else {
+                            ch
+                        }
+                    })
+                    .collect();
+                (Some(preview), block.char_count() > preview_chars)
+            } else {
+                (None, false)
+            };
+            metadata.push(BlockMetadata {
+                index: u32::try_from(index).unwrap_or(u32::MAX),
+                start_offset: start,
+                end_offset: end,
+                date: block.date.to_string(),
+                tags: block.tags.clone(),
+                preview,
+                is_truncated,
+                created_at: block.created_at,
+                updated_at: block.updated_at,
+                archived: block.archived,
+                due: task_index
+                    .range(block_start..block_start + block.char_count())
+                    .filter(|(_, task)| !task.done)
+                    .filter_map(|(_, task)| task.due)
+                    .min(),
+                attachments: attachments::references(&block.text).fold(
+                    Vec::new(),
+                    |mut ids, id| {
+                        if !ids.iter().any(|seen| seen == id) {
+                            ids.push(id.to_string());
+                        }
+                        ids
+                    },
+                ),
+                language: language.map(|language| language.code().to_string()),
+            });
+            offset = end;
+        }
+        Page::new(metadata, bounds.end, total, self.version)
+    }
 
-        assert_eq!(first, second);
-        let tag = registry.get_tag(first).expect("tag exists");
-        assert_eq!(tag.name, "sightline");
-        let parent = tag.parent_id.and_then(|id| registry.get_tag(id));
-        assert_eq!(parent.map(|tag| tag.name.as_str()), Some("project"));
+    pub fn apply_ops(
+        &mut self,
+        base_version: u64,
+        ops: &[TextOperation],
+    ) -> Result<u64, ApplyOpsError> {
+        self.apply_edit(base_version, ops, true)
+            .map(|applied| applied.version)
     }
 
-    #[test]
-    fn tag_registry_full_name_reconstructs_hierarchy() {
-        let mut registry = TagRegistry::new();
-        let id = registry
-            .intern_path(["project", "sightline", "importer"])
-            .expect("path id");
+    /// Applies `ops` like [`Self::apply_ops`], expanding snippets after each
+    /// insert unless `literal` is set or expansion is turned off.
+    pub fn apply_edit(
+        &mut self,
+        base_version: u64,
+        ops: &[TextOperation],
+        literal: bool,
+    ) -> Result<AppliedEdit, ApplyOpsError> {
+        let today = self.clock.today();
+        let now = self.clock.now();
+        self.apply_edit_on(base_version, ops, literal, today, now)
+    }
 
-        assert_eq!(
-            registry.full_name(id).as_deref(),
-            Some("project:sightline:importer")
-        );
+    /// The line and column of character `offset` into the rendered
+    /// document, or `None` past the end.
+    pub fn offset_to_point(&self, offset: usize) -> Option<Point> {
+        document_point_at(&self.tree, offset)
+    }
 
-        let child = registry.get_tag(id).unwrap();
-        let parent = registry.get_tag(child.parent_id.unwrap()).unwrap();
-        assert_eq!(parent.name, "sightline");
-        let grandparent = registry.get_tag(parent.parent_id.unwrap()).unwrap();
-        assert_eq!(grandparent.name, "project");
+    /// The character offset into the rendered document of `point`, or
+    /// `None` if the document has no such line or the line is shorter than
+    /// `point.column`.
+    pub fn point_to_offset(&self, point: Point) -> Option<usize> {
+        document_offset_at(&self.tree, point)
     }
 
-    #[test]
-    fn tag_registry_intern_colon_path_trims_segments() {
-        let mut registry = TagRegistry::new();
-        let id = registry
-            .intern_colon_path(" project : sightline : importer ")
-            .expect("path id");
+    /// Character `offset` into the rendered document as a character offset
+    /// into the timeline, which counts archived blocks too. Offsets at the
+    /// edge of archived blocks land after them; `None` past the end.
+    pub fn timeline_offset(&self, offset: usize) -> Option<usize> {
+        char_offset(&self.tree, offset, OffsetUnit::Chars)
+    }
 
-        assert_eq!(
-            registry.full_name(id).as_deref(),
-            Some("project:sightline:importer")
-        );
+    /// Character `offset` into the timeline as an offset into the rendered
+    /// document. Offsets inside archived blocks land where they would be.
+    pub fn document_offset(&self, offset: usize) -> usize {
+        visible_offset(&self.tree, offset)
     }
 
-    #[test]
-    fn filter_cursor_prunes_blocks_without_matching_tag() {
-        let tag_id = 42;
-        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
-        let blocks = vec![
-            TaggedBlock {
-                date,
-                text: "First".to_string(),
-                tags: Vec::new(),
-            },
-            TaggedBlock {
-                date,
-                text: "Tagged".to_string(),
-                tags: vec![tag_id],
+    /// `ops` with their offsets into the rendered document, counted in
+    /// `unit`s, turned into character offsets into the timeline. Each op's
+    /// offsets are read against the document as the ops before it leave
+    /// it, as [`Self::apply_edit`] applies them. A delete spanning archived
+    /// blocks becomes one delete per stretch of text between them, so the
+    /// archived blocks survive it. Masking keeps only character widths (see
+    /// [`SENSITIVE_MASK`]), so other units are refused while the document
+    /// shows a sensitive block.
+    pub fn ops_in_chars(
+        &self,
+        ops: &[TextOperation],
+        unit: OffsetUnit,
+    ) -> Result<Vec<TextOperation>, ApplyOpsError> {
+        if unit.is_chars() && self.summary().archived_chars == 0 {
+            return Ok(ops.to_vec());
+        }
+        if !unit.is_chars() && self.shows_masked_blocks() {
+            return Err(ApplyOpsError::MaskedOffsets { unit });
+        }
+
+        let today = self.clock.today();
+        let now = self.clock.now();
+        let mut tree = self.tree.clone();
+        let mut converted = Vec::with_capacity(ops.len());
+        for (index, op) in ops.iter().enumerate() {
+            let in_chars = match op {
+                TextOperation::Insert { position, text } => vec![TextOperation::Insert {
+                    position: char_offset(&tree, *position, unit).ok_or(
+                        ApplyOpsError::InvalidPosition {
+                            position: *position,
+                        },
+                    )?,
+                    text: text.clone(),
+                }],
+                TextOperation::Delete {
+                    start_position,
+                    end_position,
+                } => {
+                    let invalid = || ApplyOpsError::InvalidRange {
+                        start: *start_position,
+                        end: *end_position,
+                    };
+                    let start = char_offset(&tree, *start_position, unit).ok_or_else(invalid)?;
+                    let end = char_offset(&tree, *end_position, unit).ok_or_else(invalid)?;
+                    if start >= end || tree.summary().archived_chars == 0 {
+                        vec![TextOperation::Delete {
+                            start_position: start,
+                            end_position: end,
+                        }]
+                    } else {
+                        // Last first, so each leaves the ones before it in place.
+                        unarchived_ranges(&tree, start, end)
+                            .into_iter()
+                            .rev()
+                            .map(|(start, end)| TextOperation::Delete {
+                                start_position: start,
+                                end_position: end,
+                            })
+                            .collect()
+                    }
+                }
+            };
+            if index + 1 < ops.len() {
+                tree.apply_ops(&in_chars, today, now)?;
+            }
+            converted.extend(in_chars);
+        }
+        Ok(converted)
+    }
+
+    /// [`Self::apply_edit`] with inserted text dated `today` and changed
+    /// blocks stamped `now`.
+    fn apply_edit_on(
+        &mut self,
+        base_version: u64,
+        ops: &[TextOperation],
+        literal: bool,
+        today: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Result<AppliedEdit, ApplyOpsError> {
+        if base_version != self.version {
+            return Err(ApplyOpsError::VersionMismatch {
+                expected: self.version,
+                actual: base_version,
+            });
+        }
+
+        if ops.is_empty() {
+            return Ok(AppliedEdit {
+                version: self.version,
+                rewritten: false,
+                log: LogEntry {
+                    version: self.version,
+                    date: today,
+                    at: Some(now),
+                    ops: Vec::new(),
+                },
+            });
+        }
+
+        let expand = !literal && self.snippet_expansion && !self.snippets.is_empty();
+        let mut tree = self.tree.clone();
+        let mut rewritten = false;
+        let mut touched = BTreeSet::new();
+        let mut shifts = Vec::new();
+        let mut inverse = Vec::new();
+        let mut applied = Vec::with_capacity(ops.len());
+        for op in ops {
+            inverse.push(inverse_of(&tree, op));
+            self.apply_checked(&mut tree, op, today, now, &mut touched, &mut shifts)?;
+            applied.push(op.clone());
+
+            if !expand {
+                continue;
+            }
+            for expansion in self.snippet_ops(&tree, op) {
+                inverse.push(inverse_of(&tree, &expansion));
+                self.apply_checked(&mut tree, &expansion, today, now, &mut touched, &mut shifts)?;
+                applied.push(expansion);
+                rewritten = true;
+            }
+        }
+        self.tree = tree;
+        self.version += 1;
+        self.history.record(inverse);
+        self.day_index.update(&self.tree, &touched);
+        self.task_index.update(&self.tree, &shifts);
+        self.reparse_dates(&touched);
+        Ok(AppliedEdit {
+            version: self.version,
+            rewritten,
+            log: LogEntry {
+                version: self.version,
+                date: today,
+                at: Some(now),
+                ops: applied,
             },
-            TaggedBlock {
+        })
+    }
+
+    /// Applies logged edits the timeline doesn't include yet. Replay stops
+    /// at the first edit that doesn't follow on from the current version
+    /// or no longer applies. Returns how many edits were replayed.
+    pub fn replay_log(&mut self, entries: &[LogEntry]) -> usize {
+        let mut replayed = 0;
+        for entry in entries {
+            if entry.version <= self.version {
+                continue;
+            }
+            if entry.version != self.version + 1 {
+                tracing::warn!(
+                    version = self.version,
+                    next = entry.version,
+                    "gap in the edit log; stopping replay"
+                );
+                break;
+            }
+            // Logs written before edits were timed stamp replayed blocks now.
+            let at = entry.at.unwrap_or_else(|| self.clock.now());
+            if let Err(err) = self.apply_edit_on(self.version, &entry.ops, true, entry.date, at) {
+                tracing::warn!(%err, version = entry.version, "logged edit no longer applies");
+                break;
+            }
+            replayed += 1;
+        }
+        replayed
+    }
+
+    /// The insert [`Self::insert_on_date`] makes, in timeline offsets.
+    pub fn date_insert_op(&self, date: NaiveDate, text: &str) -> TextOperation {
+        let (position, text) = self.date_insert_point(date, text);
+        TextOperation::Insert { position, text }
+    }
+
+    /// Where a block dated `date` goes, and `text` with a line break before
+    /// it if the preceding text doesn't end in one.
+    fn date_insert_point(&self, date: NaiveDate, text: &str) -> (usize, String) {
+        // Seeking right past `date` stops at the first block dated after
+        // it, or at the end.
+        let mut cursor = self.tree.cursor::<Dimensions<LatestDate, Chars>>(());
+        cursor.seek(&LatestDate(Some(date)), Bias::Right);
+        let position = cursor.start().1 .0;
+        // Empty blocks hold no character to look at.
+        while cursor
+            .prev_item()
+            .is_some_and(|block| block.text.is_empty())
+        {
+            cursor.prev();
+        }
+        let text = match cursor.prev_item() {
+            Some(block) if !block.text.ends_with('\n') => format!("\n{text}"),
+            _ => text.to_string(),
+        };
+        (position, text)
+    }
+
+    /// Inserts `text` as a block dated `date`, before the first block dated
+    /// after it, starting a new line if needed. Returns the offset into
+    /// the rendered document that the new block starts at.
+    pub fn insert_on_date(
+        &mut self,
+        base_version: u64,
+        date: NaiveDate,
+        text: &str,
+    ) -> Result<usize, ApplyOpsError> {
+        if base_version != self.version {
+            return Err(ApplyOpsError::VersionMismatch {
+                expected: self.version,
+                actual: base_version,
+            });
+        }
+
+        let (position, text) = self.date_insert_point(date, text);
+        if self.is_frozen(date) {
+            return Err(ApplyOpsError::Frozen {
+                start: position,
+                end: position,
                 date,
-                text: "Third".to_string(),
-                tags: Vec::new(),
+            });
+        }
+
+        let op = TextOperation::Insert { position, text };
+        let mut tree = self.tree.clone();
+        let mut touched = BTreeSet::new();
+        let mut shifts = Vec::new();
+        let inverse = inverse_of(&tree, &op);
+        let now = self.clock.now();
+        self.apply_checked(&mut tree, &op, date, now, &mut touched, &mut shifts)?;
+        self.tree = tree;
+        self.version += 1;
+        self.history.record(vec![inverse]);
+        self.day_index.update(&self.tree, &touched);
+        self.task_index.update(&self.tree, &shifts);
+        self.reparse_dates(&touched);
+        Ok(visible_offset(&self.tree, position))
+    }
+
+    /// Adds `blocks` (with tag ids from this timeline's registry), each
+    /// placed after the existing blocks dated on or before it. Blocks on
+    /// frozen dates are dropped, and so are blocks with the same
+    /// [`BlockSource`] as one already here, so importing a vault twice
+    /// doesn't duplicate it whatever the strategy. Merging can't be undone,
+    /// so it clears the undo history.
+    pub fn merge_blocks(
+        &mut self,
+        blocks: Vec<TaggedBlock>,
+        strategy: MergeStrategy,
+    ) -> MergeSummary {
+        let plan = self.plan_merge(blocks, strategy);
+        self.apply_merge(plan)
+    }
+
+    /// Works out what [`Self::merge_blocks`] would do without changing
+    /// anything, so its inserts can be checked first.
+    pub fn plan_merge(&self, mut blocks: Vec<TaggedBlock>, strategy: MergeStrategy) -> MergePlan {
+        let mut summary = MergeSummary::default();
+        let mut existing: Vec<TaggedBlock> = self.tree.iter().cloned().collect();
+        let mut by_text: HashMap<String, usize> = existing
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.text.trim().to_string(), index))
+            .collect();
+        // Edits leave an imported block in fragments that share its source.
+        let mut by_source: HashMap<BlockSource, Vec<usize>> = HashMap::new();
+        for (index, block) in existing.iter().enumerate() {
+            if let Some(source) = &block.source {
+                by_source.entry(source.clone()).or_default().push(index);
+            }
+        }
+
+        blocks.sort_by_key(|block| block.date);
+        let mut incoming = Vec::new();
+        for block in blocks {
+            if self.is_frozen(block.date) {
+                summary.frozen += 1;
+                continue;
+            }
+            let fragments = block
+                .source
+                .as_ref()
+                .and_then(|source| by_source.get(source));
+            if let Some(fragments) = fragments {
+                summary.reimported += 1;
+                if strategy == MergeStrategy::MergeTags {
+                    for &index in fragments {
+                        let target = &mut existing[index];
+                        target.tags.extend(block.tags.iter().copied());
+                        target.tags.sort_unstable();
+                        target.tags.dedup();
+                    }
+                }
+                continue;
+            }
+            if let Some(&index) = by_text.get(block.text.trim()) {
+                summary.duplicates += 1;
+                match strategy {
+                    MergeStrategy::Skip => continue,
+                    MergeStrategy::MergeTags => {
+                        let target = if index < existing.len() {
+                            &mut existing[index]
+                        } else {
+                            &mut incoming[index - existing.len()]
+                        };
+                        target.tags.extend(block.tags);
+                        target.tags.sort_unstable();
+                        target.tags.dedup();
+                        continue;
+                    }
+                    MergeStrategy::KeepBoth => {}
+                }
+            }
+            by_text.insert(
+                block.text.trim().to_string(),
+                existing.len() + incoming.len(),
+            );
+            incoming.push(block);
+        }
+
+        summary.added = incoming.len();
+        let touched: BTreeSet<NaiveDate> = incoming.iter().map(|block| block.date).collect();
+        let mut merged = Vec::with_capacity(existing.len() + incoming.len());
+        let mut ops = Vec::with_capacity(incoming.len());
+        let mut offset = 0;
+        let mut open_line = false;
+        let mut add = |mut block: TaggedBlock, added: bool| {
+            if added {
+                // As in `date_insert_point`, added notes start and end on
+                // lines of their own rather than running into their
+                // neighbours.
+                if open_line {
+                    block.text.insert(0, '\n');
+                }
+                if !block.text.ends_with('\n') {
+                    block.text.push('\n');
+                }
+                ops.push(TextOperation::Insert {
+                    position: offset,
+                    text: block.text.clone(),
+                });
+            }
+            if !block.text.is_empty() {
+                open_line = !block.text.ends_with('\n');
+            }
+            offset += block.char_count();
+            merged.push(block);
+        };
+        let mut incoming = incoming.into_iter().peekable();
+        for block in existing {
+            while let Some(next) = incoming.next_if(|next| next.date < block.date) {
+                add(next, true);
+            }
+            add(block, false);
+        }
+        for block in incoming {
+            add(block, true);
+        }
+
+        MergePlan {
+            blocks: merged,
+            touched,
+            summary,
+            ops,
+        }
+    }
+
+    /// Makes a merge from [`Self::plan_merge`]. The timeline must not have
+    /// changed since.
+    pub fn apply_merge(&mut self, plan: MergePlan) -> MergeSummary {
+        let MergePlan {
+            blocks,
+            touched,
+            mut summary,
+            ..
+        } = plan;
+        self.tree = SumTree::from_iter(blocks, ());
+        self.day_index = DayIndex::build(&self.tree);
+        self.task_index = TaskIndex::build(&self.tree);
+        self.version += 1;
+        self.history = UndoHistory::default();
+        self.reparse_dates(&touched);
+        summary.version = self.version;
+        summary
+    }
+
+    /// Merges adjacent blocks with the same date, tags, links and source,
+    /// such as the fragments character-level edits leave behind, and drops
+    /// empty blocks. The text and its offsets don't change, but block
+    /// indexes do, so the version moves on when any block was removed and
+    /// clients holding block ids refetch. Returns how many blocks were
+    /// removed.
+    pub fn compact(&mut self) -> usize {
+        let before = self.entry_count();
+        let mut blocks: Vec<TaggedBlock> = Vec::with_capacity(before);
+        for block in self.tree.iter() {
+            if block.text.is_empty() {
+                continue;
+            }
+            match blocks.last_mut() {
+                Some(last) if same_entry(last, block) => {
+                    last.text.push_str(&block.text);
+                    // The merged block was written when its first part was
+                    // and changed when its latest part was.
+                    last.created_at = match (last.created_at, block.created_at) {
+                        (Some(last), Some(next)) => Some(last.min(next)),
+                        (last, next) => last.or(next),
+                    };
+                    last.updated_at = last.updated_at.max(block.updated_at);
+                }
+                _ => blocks.push(block.clone()),
+            }
+        }
+
+        let removed = before - blocks.len();
+        if removed > 0 {
+            self.tree = SumTree::from_iter(blocks, ());
+            self.day_index = DayIndex::build(&self.tree);
+            self.task_index = TaskIndex::build(&self.tree);
+            self.version += 1;
+        }
+        removed
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// The text changes [`Self::undo`] would make, in timeline offsets, so
+    /// they can be checked against edit locks first. Tag changes are left
+    /// out.
+    pub fn undo_ops(&self) -> Vec<TextOperation> {
+        step_text_ops(self.history.undo.last())
+    }
+
+    /// Like [`Self::undo_ops`], for [`Self::redo`].
+    pub fn redo_ops(&self) -> Vec<TextOperation> {
+        step_text_ops(self.history.redo.last())
+    }
+
+    /// Reverts the most recent edit or tag assignment. Returns `false` when
+    /// there is nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, ApplyOpsError> {
+        let Some(step) = self.history.undo.pop() else {
+            return Ok(false);
+        };
+        match self.apply_undo_step(&step) {
+            Ok(inverse) => {
+                self.history.redo.push(inverse);
+                Ok(true)
+            }
+            Err(err) => {
+                self.history.undo.push(step);
+                Err(err)
+            }
+        }
+    }
+
+    /// Reapplies the most recently undone step. Returns `false` when there
+    /// is nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, ApplyOpsError> {
+        let Some(step) = self.history.redo.pop() else {
+            return Ok(false);
+        };
+        match self.apply_undo_step(&step) {
+            Ok(inverse) => {
+                self.history.undo.push(inverse);
+                Ok(true)
+            }
+            Err(err) => {
+                self.history.redo.push(step);
+                Err(err)
+            }
+        }
+    }
+
+    /// Applies a recorded step as a new version, returning the step that
+    /// reverses it.
+    fn apply_undo_step(&mut self, step: &[UndoOp]) -> Result<Vec<UndoOp>, ApplyOpsError> {
+        let today = self.clock.today();
+        let now = self.clock.now();
+        let mut tree = self.tree.clone();
+        let mut touched = BTreeSet::new();
+        let mut shifts = Vec::new();
+        let mut inverse = Vec::with_capacity(step.len());
+        for op in step {
+            let before = tree.clone();
+            let reverse = match op {
+                UndoOp::Text(op) => {
+                    let reverse = inverse_of(&tree, op);
+                    self.apply_checked(&mut tree, op, today, now, &mut touched, &mut shifts)?;
+                    reverse
+                }
+                UndoOp::Restore { position, blocks } => {
+                    let text: String = blocks.iter().map(|block| block.text.as_str()).collect();
+                    let end = position + text.chars().count();
+                    let insert = TextOperation::Insert {
+                        position: *position,
+                        text,
+                    };
+                    self.check_frozen(&tree, &insert)?;
+                    insert_blocks(&mut tree, *position, blocks.iter().cloned())?;
+                    touched.extend(dates_in_range(&tree, *position, end));
+                    shifts.push(Shift::between(&before, &tree, *position));
+                    UndoOp::Text(TextOperation::Delete {
+                        start_position: *position,
+                        end_position: end,
+                    })
+                }
+                UndoOp::Tags { start, end, tags } => {
+                    let tags = set_tags_in_range(&mut tree, *start, *end, tags, now)?;
+                    touched.extend(dates_in_range(&tree, *start, *end));
+                    shifts.push(Shift::between(&before, &tree, *start));
+                    UndoOp::Tags {
+                        start: *start,
+                        end: *end,
+                        tags,
+                    }
+                }
+            };
+            inverse.push(reverse);
+        }
+        inverse.reverse();
+        self.tree = tree;
+        self.version += 1;
+        self.day_index.update(&self.tree, &touched);
+        self.task_index.update(&self.tree, &shifts);
+        self.reparse_dates(&touched);
+        Ok(inverse)
+    }
+
+    /// Applies one op to `tree` after the frozen-range check, recording the
+    /// dates of blocks it touched, so their metrics and index entries can be
+    /// refreshed, and how it moved the text after it.
+    fn apply_checked(
+        &self,
+        tree: &mut SumTree<TaggedBlock>,
+        op: &TextOperation,
+        today: NaiveDate,
+        now: DateTime<Utc>,
+        touched: &mut BTreeSet<NaiveDate>,
+        shifts: &mut Vec<Shift>,
+    ) -> Result<(), ApplyOpsError> {
+        self.check_frozen(tree, op)?;
+        let (start, end) = match op {
+            TextOperation::Insert { position, .. } => (*position, *position),
+            TextOperation::Delete {
+                start_position,
+                end_position,
+            } => (*start_position, *end_position),
+        };
+        touched.extend(dates_in_range(tree, start, end));
+
+        let before = tree.clone();
+        tree.apply_ops(std::slice::from_ref(op), today, now)?;
+        shifts.push(Shift::between(&before, tree, start));
+
+        let end = match op {
+            TextOperation::Insert { position, text } => position + text.chars().count(),
+            TextOperation::Delete { start_position, .. } => *start_position,
+        };
+        touched.extend(dates_in_range(tree, start, end));
+        Ok(())
+    }
+
+    pub fn metric_patterns(&self) -> &[MetricPattern] {
+        self.metric_parser.patterns()
+    }
+
+    /// Replaces the quick-entry metric patterns and reparses every day.
+    pub fn set_metric_patterns(
+        &mut self,
+        patterns: Vec<MetricPattern>,
+    ) -> Result<(), MetricPatternError> {
+        self.metric_parser = MetricParser::new(patterns)?;
+        self.custom_metric_patterns = true;
+        self.reparse_dates(&self.metric_dates(&DateRange::default()));
+        Ok(())
+    }
+
+    /// Metrics for each day in `range`. Unless `sensitive` includes them,
+    /// days with sensitive blocks are parsed again without those blocks,
+    /// so their metrics don't give away what the blocks say.
+    pub fn day_properties(
+        &self,
+        range: &DateRange,
+        sensitive: SensitiveContent,
+    ) -> BTreeMap<NaiveDate, DayProperties> {
+        let masked = self.masked_tag_ids(sensitive);
+        let masked_days: BTreeSet<NaiveDate> = if masked.is_empty() {
+            BTreeSet::new()
+        } else {
+            self.blocks_in_range(range, SensitiveContent::Included, ArchivedContent::Included)
+                .into_iter()
+                .filter(|block| block.tags.iter().any(|tag| masked.contains(tag)))
+                .map(|block| block.date)
+                .collect()
+        };
+        self.day_properties
+            .iter()
+            .filter(|(date, _)| range.contains(**date))
+            .filter_map(|(date, properties)| {
+                if !masked_days.contains(date) {
+                    return Some((*date, properties.clone()));
+                }
+                let properties = self.parse_day(*date, &masked);
+                (!properties.is_empty()).then_some((*date, properties))
+            })
+            .collect()
+    }
+
+    /// Recomputes metrics for every day in `range` from block text and
+    /// returns the result, masked as [`Self::day_properties`] is.
+    pub fn reparse_metrics(
+        &mut self,
+        range: &DateRange,
+        sensitive: SensitiveContent,
+    ) -> BTreeMap<NaiveDate, DayProperties> {
+        self.reparse_dates(&self.metric_dates(range));
+        self.day_properties(range, sensitive)
+    }
+
+    /// Days in `range` with metrics or blocks.
+    fn metric_dates(&self, range: &DateRange) -> BTreeSet<NaiveDate> {
+        let mut dates: BTreeSet<NaiveDate> = self
+            .day_properties
+            .keys()
+            .copied()
+            .filter(|date| range.contains(*date))
+            .collect();
+        dates.extend(
+            self.tree
+                .iter()
+                .map(|block| block.date)
+                .filter(|date| range.contains(*date)),
+        );
+        dates
+    }
+
+    /// Reparses the metrics of `dates`. Each day's blocks are found by
+    /// seeking on date summaries, so the cost is those blocks rather than
+    /// the tree. A whole day is reparsed because `Last` and `Sum` metrics
+    /// fold over all of its lines.
+    fn reparse_dates(&mut self, dates: &BTreeSet<NaiveDate>) {
+        if dates.is_empty() || self.metric_parser.is_empty() {
+            return;
+        }
+
+        for date in dates {
+            let properties = self.parse_day(*date, &HashSet::new());
+            if properties.is_empty() {
+                self.day_properties.remove(date);
+            } else {
+                self.day_properties.insert(*date, properties);
+            }
+        }
+    }
+
+    /// Metrics parsed from `date`'s blocks, leaving out those tagged with
+    /// any of `skipped`.
+    fn parse_day(&self, date: NaiveDate, skipped: &HashSet<u32>) -> DayProperties {
+        // Edits split blocks mid-line, so a day's text is joined before
+        // parsing rather than parsed block by block.
+        let mut text = String::new();
+        let mut cursor = self.tree.cursor::<LatestDate>(());
+        cursor.seek(&LatestDate(Some(date)), Bias::Left);
+        while let Some(block) = cursor.item() {
+            if block.date == date && !block.tags.iter().any(|tag| skipped.contains(tag)) {
+                text.push_str(&block.text);
+            }
+            cursor.search_forward(|summary: &TimelineSummary| {
+                summary.min_date <= Some(date) && Some(date) <= summary.max_date
+            });
+        }
+
+        let mut properties = DayProperties::new();
+        self.metric_parser.parse_into(&text, &mut properties);
+        properties
+    }
+
+    pub fn snippets(&self) -> &[Snippet] {
+        &self.snippets
+    }
+
+    /// Adds a snippet, replacing any existing snippet with the same trigger.
+    pub fn save_snippet(&mut self, trigger: &str, expansion: &str) -> Result<(), SnippetError> {
+        let snippet = Snippet::new(trigger, expansion)?;
+        match self
+            .snippets
+            .iter_mut()
+            .find(|existing| existing.trigger == snippet.trigger)
+        {
+            Some(existing) => *existing = snippet,
+            None => self.snippets.push(snippet),
+        }
+        Ok(())
+    }
+
+    pub fn delete_snippet(&mut self, trigger: &str) -> bool {
+        let before = self.snippets.len();
+        self.snippets
+            .retain(|snippet| snippet.trigger != trigger.trim());
+        self.snippets.len() != before
+    }
+
+    pub fn snippet_expansion(&self) -> bool {
+        self.snippet_expansion
+    }
+
+    pub fn set_snippet_expansion(&mut self, enabled: bool) {
+        self.snippet_expansion = enabled;
+    }
+
+    /// Whether pasting a bare URL may fetch the page title over the network.
+    pub fn fetch_link_titles(&self) -> bool {
+        self.fetch_link_titles
+    }
+
+    pub fn set_fetch_link_titles(&mut self, enabled: bool) {
+        self.fetch_link_titles = enabled;
+    }
+
+    /// Whether every feature is kept off the network; see
+    /// [`crate::network`].
+    pub fn offline_mode(&self) -> bool {
+        self.offline_mode
+    }
+
+    pub fn set_offline_mode(&mut self, enabled: bool) {
+        self.offline_mode = enabled;
+    }
+
+    /// Takes the search history an older snapshot held, newest first;
+    /// see [`crate::search::SearchHistory`].
+    pub fn take_legacy_search_history(&mut self) -> Vec<RecentSearch> {
+        std::mem::take(&mut self.legacy_search_history)
+    }
+
+    /// Where the public `#now` page is written, if anywhere.
+    pub fn now_page(&self) -> Option<&NowPageConfig> {
+        self.now_page.as_ref()
+    }
+
+    pub fn set_now_page(&mut self, config: Option<NowPageConfig>) {
+        self.now_page = config;
+    }
+
+    /// Text a new day starts with, if anything. `{date}` is replaced by
+    /// the day's date, `{weekday}` by its name, and `{important_dates}` by
+    /// what falls on it; lines holding `{important_dates}` are dropped on
+    /// days with none. Variables may also be written `{{date}}`.
+    pub fn daily_note(&self) -> Option<&str> {
+        self.daily_note.as_deref()
+    }
+
+    pub fn set_daily_note(&mut self, template: Option<String>) {
+        self.daily_note = template.filter(|template| !template.trim().is_empty());
+    }
+
+    /// Inserts today's note from the daily note template, unless there is
+    /// no template or something is already written today. Returns where
+    /// the note was inserted.
+    pub fn create_daily_note(&mut self) -> Result<Option<usize>, ApplyOpsError> {
+        let Some(text) = self.daily_note_text() else {
+            return Ok(None);
+        };
+        self.insert_on_date(self.version, self.clock.today(), &text)
+            .map(Some)
+    }
+
+    /// The insert [`Self::create_daily_note`] makes, in timeline offsets,
+    /// if it makes one.
+    pub fn daily_note_op(&self) -> Option<TextOperation> {
+        let text = self.daily_note_text()?;
+        Some(self.date_insert_op(self.clock.today(), &text))
+    }
+
+    /// Today's note, unless there is no template or something is already
+    /// written today.
+    fn daily_note_text(&self) -> Option<String> {
+        let today = self.clock.today();
+        let template = self.daily_note.as_ref()?;
+        if self.log_for_date(today).is_some() {
+            return None;
+        }
+        let dates = self.important_dates.between(today, today);
+        let mut text = fill_daily_note(template, today, &dates);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        Some(text)
+    }
+
+    /// Where and how often the timeline is exported as markdown, if it is.
+    pub fn export_schedule(&self) -> Option<&ExportSchedule> {
+        self.export_schedule.as_ref()
+    }
+
+    pub fn set_export_schedule(&mut self, schedule: Option<ExportSchedule>) {
+        self.export_schedule = schedule;
+    }
+
+    /// Birthdays, anniversaries and holidays to remember.
+    pub fn important_dates(&self) -> &ImportantDates {
+        &self.important_dates
+    }
+
+    pub fn set_important_dates(&mut self, dates: ImportantDates) -> Result<(), ImportantDateError> {
+        dates.validate()?;
+        self.important_dates = dates;
+        Ok(())
+    }
+
+    pub fn expand_preview(&self, text: &str) -> String {
+        snippets::expand_text(&self.snippets, text)
+    }
+
+    /// When `op` (already applied to `tree`) inserted text ending in
+    /// whitespace right after a snippet trigger, returns the ops replacing
+    /// the trigger with its expansion.
+    fn snippet_ops(&self, tree: &SumTree<TaggedBlock>, op: &TextOperation) -> Vec<TextOperation> {
+        let TextOperation::Insert { position, text } = op else {
+            return Vec::new();
+        };
+        if !text.ends_with(char::is_whitespace) {
+            return Vec::new();
+        }
+
+        let delimiter = position + text.chars().count() - 1;
+        let limit = self
+            .snippets
+            .iter()
+            .map(Snippet::trigger_chars)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let before = text_before(tree, delimiter, limit);
+        let at_start = before.chars().count() < limit;
+
+        let Some(snippet) = snippets::match_trigger(&self.snippets, &before, at_start) else {
+            return Vec::new();
+        };
+
+        let trigger_start = delimiter - snippet.trigger_chars();
+        vec![
+            TextOperation::Delete {
+                start_position: trigger_start,
+                end_position: delimiter,
+            },
+            TextOperation::Insert {
+                position: trigger_start,
+                text: snippet.expansion.clone(),
+            },
+        ]
+    }
+
+    pub fn frozen_ranges(&self) -> &[DateRange] {
+        &self.frozen_ranges
+    }
+
+    /// Names of the settings changed from a new timeline's, for tools that
+    /// copy blocks between timelines but not settings.
+    pub fn customized_settings(&self) -> Vec<&'static str> {
+        [
+            ("frozen_ranges", !self.frozen_ranges.is_empty()),
+            ("snippets", !self.snippets.is_empty()),
+            ("snippet_expansion", self.snippet_expansion),
+            ("fetch_link_titles", self.fetch_link_titles),
+            ("offline_mode", self.offline_mode),
+            ("metric_patterns", self.custom_metric_patterns),
+            ("now_page", self.now_page.is_some()),
+            ("daily_note", self.daily_note.is_some()),
+            ("export_schedule", self.export_schedule.is_some()),
+            ("important_dates", !self.important_dates.is_empty()),
+        ]
+        .into_iter()
+        .filter_map(|(name, customized)| customized.then_some(name))
+        .collect()
+    }
+
+    /// Rejects future edits to blocks dated within `range`. Tag assignments
+    /// are still allowed on frozen blocks.
+    pub fn freeze_range(&mut self, range: DateRange) {
+        if !self.frozen_ranges.contains(&range) {
+            self.frozen_ranges.push(range);
+        }
+    }
+
+    pub fn unfreeze_range(&mut self, range: &DateRange) -> bool {
+        let before = self.frozen_ranges.len();
+        self.frozen_ranges.retain(|frozen| frozen != range);
+        self.frozen_ranges.len() != before
+    }
+
+    fn is_frozen(&self, date: NaiveDate) -> bool {
+        self.frozen_ranges.iter().any(|range| range.contains(date))
+    }
+
+    /// Deletes may not overlap a frozen block, and inserts may not land
+    /// strictly inside one; inserting at a block boundary is allowed.
+    fn check_frozen(
+        &self,
+        tree: &SumTree<TaggedBlock>,
+        op: &TextOperation,
+    ) -> Result<(), ApplyOpsError> {
+        if self.frozen_ranges.is_empty() {
+            return Ok(());
+        }
+
+        let (from, to) = match op {
+            TextOperation::Insert { position, .. } => (*position, *position),
+            TextOperation::Delete {
+                start_position,
+                end_position,
+            } => (*start_position, *end_position),
+        };
+        // Seeking right past `from` stops at the first block ending after
+        // it; the blocks from there that start before `to` are touched.
+        let mut cursor = tree.cursor::<Chars>(());
+        cursor.seek(&Chars(from), Bias::Right);
+        while let Some(block) = cursor.item() {
+            let start = cursor.start().0;
+            if start >= to {
+                break;
+            }
+            if self.is_frozen(block.date) {
+                return Err(ApplyOpsError::Frozen {
+                    start,
+                    end: start + block.char_count(),
+                    date: block.date,
+                });
+            }
+            cursor.next();
+        }
+
+        Ok(())
+    }
+
+    fn block_ids_with_tags(
+        &self,
+        tag_ids: &[u32],
+        range: &DateRange,
+        archived: ArchivedContent,
+    ) -> Vec<u32> {
+        if tag_ids.is_empty() {
+            return Vec::new();
+        }
+
+        let matching: HashSet<u32> = tag_ids.iter().copied().collect();
+        self.block_ids_matching(
+            range,
+            archived,
+            |summary| tag_ids.iter().any(|tag| summary.tags_filter.check(tag)),
+            |block| block.tags.iter().any(|tag| matching.contains(tag)),
+        )
+    }
+
+    /// Indexes of blocks dated within `range`, shown by `archived` and
+    /// passing `matches`, skipping subtrees outside the range or for which
+    /// `might_match` is false.
+    fn block_ids_matching(
+        &self,
+        range: &DateRange,
+        archived: ArchivedContent,
+        might_match: impl Fn(&TimelineSummary) -> bool,
+        matches: impl Fn(&TaggedBlock) -> bool,
+    ) -> Vec<u32> {
+        // The cursor's position is the index of the block it stops on.
+        let mut cursor = self
+            .tree
+            .filter::<_, EntryCount>((), |summary: &TimelineSummary| {
+                range.overlaps(summary.min_date, summary.max_date) && might_match(summary)
+            });
+        let mut ids = Vec::new();
+        cursor.next();
+        while let Some(block) = cursor.item() {
+            if range.contains(block.date) && archived.shows(block) && matches(block) {
+                if let Ok(index) = u32::try_from(cursor.start().0) {
+                    ids.push(index);
+                }
+            }
+            cursor.next();
+        }
+        ids
+    }
+
+    pub fn save(&self) -> Result<(), TimelinePersistenceError> {
+        let path = get_storage_path()?;
+        self.save_to_path(path)
+    }
+
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), TimelinePersistenceError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, self.to_snapshot_json()?)?;
+        wal::clear(&wal::log_path(path))?;
+        Ok(())
+    }
+
+    /// Writes the timeline to `dir` as a markdown vault the importer can
+    /// read back; see [`markdown::export`].
+    pub fn export_markdown(
+        &self,
+        dir: &Path,
+        rendering: TagRendering,
+        sensitive: SensitiveContent,
+    ) -> io::Result<MarkdownExport> {
+        markdown::export(self, dir, rendering, sensitive)
+    }
+
+    /// Writes the timeline as a JSONL snapshot: a header line with the tag
+    /// registry and settings, then one block per line. Blocks are written
+    /// straight from the tree, so the document is never serialized whole.
+    /// Sensitive blocks are left out unless `sensitive` includes them; the
+    /// result loads with [`Timeline::from_snapshot_bytes`]. Returns the
+    /// number of blocks written.
+    pub fn write_jsonl<W: Write>(
+        &self,
+        writer: W,
+        sensitive: SensitiveContent,
+    ) -> Result<usize, TimelinePersistenceError> {
+        let mut header = self.snapshot(Vec::new());
+        header.format = Some(JSONL_SNAPSHOT_FORMAT.to_string());
+        // Out of step once blocks are left out; loading rebuilds it.
+        header.day_index = DayIndex::default();
+
+        let masked = self.masked_tag_ids(sensitive);
+        let blocks = self
+            .tree
+            .iter()
+            .filter(|block| !block.tags.iter().any(|tag| masked.contains(tag)));
+        write_jsonl_lines(writer, &header, blocks)
+    }
+
+    /// [`Timeline::write_jsonl`] into the file at `path`, replacing it. The
+    /// export is written beside `path` and renamed over it, so a failed
+    /// export leaves the previous file in place.
+    pub fn export_jsonl(
+        &self,
+        path: &Path,
+        sensitive: SensitiveContent,
+    ) -> Result<usize, TimelinePersistenceError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let written =
+            match self.write_jsonl(io::BufWriter::new(fs::File::create(&temp)?), sensitive) {
+                Ok(written) => written,
+                Err(err) => {
+                    let _ = fs::remove_file(&temp);
+                    return Err(err);
+                }
+            };
+        fs::rename(&temp, path)?;
+        Ok(written)
+    }
+
+    /// Writes the timeline to `path` as an iCalendar file of day events and
+    /// due-dated to-dos; see [`ical::render`].
+    pub fn export_ical(&self, path: &Path, sensitive: SensitiveContent) -> io::Result<IcalExport> {
+        ical::export(self, path, sensitive, self.clock.now())
+    }
+
+    /// The JSON written by [`Timeline::save_to_path`].
+    pub fn to_snapshot_json(&self) -> Result<Vec<u8>, TimelinePersistenceError> {
+        Ok(serde_json::to_vec_pretty(
+            &self.snapshot(self.tree.items(())),
+        )?)
+    }
+
+    fn snapshot(&self, blocks: Vec<TaggedBlock>) -> TimelineSnapshot {
+        let exported_tags = self.tag_registry.export();
+        TimelineSnapshot {
+            format: None,
+            version: self.version,
+            blocks,
+            tag_registry: if exported_tags.is_empty() {
+                None
+            } else {
+                Some(TagRegistrySnapshot::Hierarchical(exported_tags))
+            },
+            content_tag_ids: true,
+            frozen_ranges: self.frozen_ranges.clone(),
+            snippets: self.snippets.clone(),
+            snippet_expansion: self.snippet_expansion,
+            fetch_link_titles: self.fetch_link_titles,
+            offline_mode: self.offline_mode,
+            metric_patterns: self
+                .custom_metric_patterns
+                .then(|| self.metric_parser.patterns().to_vec()),
+            now_page: self.now_page.clone(),
+            daily_note: self.daily_note.clone(),
+            export_schedule: self.export_schedule.clone(),
+            important_dates: self.important_dates.clone(),
+            day_properties: self.day_properties.clone(),
+            search_history: Vec::new(),
+            legacy_tag_registry: self.legacy_tags.clone(),
+            day_index: self.day_index.clone(),
+        }
+    }
+
+    pub fn load() -> Result<Self, TimelinePersistenceError> {
+        let path = get_storage_path()?;
+        Self::load_from_path(path)
+    }
+
+    /// Loads the snapshot at `path` and replays edits logged since it was
+    /// written. CBOR, JSONL and SQLite snapshots are decoded as the file is
+    /// read rather than read into memory first.
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self, TimelinePersistenceError> {
+        let path = path.as_ref();
+        let mut file = match fs::File::open(path) {
+            Ok(file) => io::BufReader::new(file),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let mut timeline = Self::default();
+                timeline.replay_log(&wal::read(&wal::log_path(path))?);
+                return Ok(timeline);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut timeline = if file.fill_buf()?.starts_with(SQLITE_MAGIC) {
+            let (header, blocks) = sqlite_snapshot::read(path)?;
+            let mut snapshot: TimelineSnapshot = serde_json::from_str(&header)?;
+            snapshot.blocks = blocks;
+            Self::from_snapshot(snapshot)?
+        } else {
+            Self::from_snapshot_reader(file)?
+        };
+        timeline.replay_log(&wal::read(&wal::log_path(path))?);
+        Ok(timeline)
+    }
+
+    /// Loads a snapshot in any [`SnapshotEncoding`] but SQLite, detected
+    /// from its first bytes.
+    pub fn from_snapshot_bytes(contents: &[u8]) -> Result<Self, TimelinePersistenceError> {
+        Self::from_snapshot_reader(contents)
+    }
+
+    fn from_snapshot_reader(mut reader: impl BufRead) -> Result<Self, TimelinePersistenceError> {
+        let start = reader.fill_buf()?;
+        if start.starts_with(SQLITE_MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SQLite snapshots can only be loaded from a file",
+            )
+            .into());
+        }
+        if start.starts_with(&CBOR_MAGIC) {
+            reader.consume(CBOR_MAGIC.len());
+            return Self::from_snapshot(ciborium::from_reader(reader)?);
+        }
+
+        let mut first_line = Vec::new();
+        reader.read_until(b'\n', &mut first_line)?;
+        let is_jsonl = serde_json::from_slice::<JsonlHeader>(&first_line)
+            .is_ok_and(|header| header.format == JSONL_SNAPSHOT_FORMAT);
+        if !is_jsonl {
+            // One JSON document, so there is nothing to gain from decoding
+            // it as it's read, and parsing a slice is much faster.
+            let mut contents = first_line;
+            reader.read_to_end(&mut contents)?;
+            return Self::from_snapshot_json(&contents);
+        }
+
+        let mut snapshot: TimelineSnapshot = serde_json::from_slice(&first_line)?;
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                snapshot.blocks.push(serde_json::from_str(&line)?);
+            }
+        }
+        Self::from_snapshot(snapshot)
+    }
+
+    pub fn from_snapshot_json(contents: &[u8]) -> Result<Self, TimelinePersistenceError> {
+        Self::from_snapshot(serde_json::from_slice(contents)?)
+    }
+
+    fn from_snapshot(mut snapshot: TimelineSnapshot) -> Result<Self, TimelinePersistenceError> {
+        let mut legacy_names = BTreeMap::new();
+        let mut tag_registry = match snapshot.tag_registry {
+            Some(TagRegistrySnapshot::Hierarchical(tags)) => TagRegistry::from_tags(tags),
+            Some(TagRegistrySnapshot::Flat(map)) => {
+                let parsed: HashMap<u32, String> = map
+                    .into_iter()
+                    .filter_map(|(id, tag)| id.parse::<u32>().ok().map(|id| (id, tag)))
+                    .collect();
+                legacy_names.extend(parsed.clone());
+                TagRegistry::from_map(parsed)
+            }
+            None => TagRegistry::new(),
+        };
+        if !snapshot.content_tag_ids {
+            let moved = tag_registry.migrate_to_content_ids();
+            if !moved.is_empty() {
+                for block in &mut snapshot.blocks {
+                    for tag in &mut block.tags {
+                        *tag = moved.get(tag).copied().unwrap_or(*tag);
+                    }
+                    block.tags.sort_unstable();
+                    block.tags.dedup();
+                }
+            }
+        }
+        let legacy_tags = if legacy_names.is_empty() {
+            snapshot.legacy_tag_registry
+        } else {
+            Some(LegacyTagRegistry::rebuilt(legacy_names, &tag_registry))
+        };
+        let tree = SumTree::from_iter(snapshot.blocks, ());
+        let day_index = if snapshot.day_index.is_current(tree.summary()) {
+            snapshot.day_index
+        } else {
+            DayIndex::build(&tree)
+        };
+        let task_index = TaskIndex::build(&tree);
+        let custom_metric_patterns = snapshot.metric_patterns.is_some();
+        let metric_parser = match snapshot.metric_patterns {
+            Some(patterns) => MetricParser::new(patterns)?,
+            None => MetricParser::default(),
+        };
+        Ok(Self {
+            tree,
+            version: snapshot.version,
+            tag_registry,
+            frozen_ranges: snapshot.frozen_ranges,
+            snippets: snapshot.snippets,
+            snippet_expansion: snapshot.snippet_expansion,
+            fetch_link_titles: snapshot.fetch_link_titles,
+            offline_mode: snapshot.offline_mode,
+            metric_parser,
+            custom_metric_patterns,
+            now_page: snapshot.now_page,
+            daily_note: snapshot.daily_note,
+            export_schedule: snapshot.export_schedule,
+            important_dates: snapshot.important_dates,
+            day_properties: snapshot.day_properties,
+            legacy_search_history: snapshot.search_history,
+            legacy_tags,
+            day_index,
+            task_index,
+            history: UndoHistory::default(),
+            clock: SharedClock::default(),
+            regex_cache: RegexCache::default(),
+            term_cache: TermCache::default(),
+            analysis_cache: AnalysisCache::default(),
+        })
+    }
+}
+
+/// The default profile's timeline file; see [`AppPaths`] for how it is
+/// chosen.
+pub fn get_storage_path() -> Result<PathBuf, TimelinePersistenceError> {
+    Ok(AppPaths::resolve(&LaunchOptions::default())?.timeline)
+}
+
+/// Dates of blocks overlapping `start..=end`, including blocks that merely
+/// touch either end.
+fn dates_in_range(tree: &SumTree<TaggedBlock>, start: usize, end: usize) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    let mut cursor = tree.cursor::<Chars>(());
+    // A left bias stops at the first block ending at or after `start`.
+    cursor.seek(&Chars(start), Bias::Left);
+    while let Some(block) = cursor.item() {
+        if cursor.start().0 > end {
+            break;
+        }
+        dates.push(block.date);
+        cursor.next();
+    }
+    dates
+}
+
+/// Whether two blocks differ only in text, so adjacent ones can merge.
+fn same_entry(a: &TaggedBlock, b: &TaggedBlock) -> bool {
+    let tag_set = |block: &TaggedBlock| block.tags.iter().copied().collect::<BTreeSet<u32>>();
+    a.date == b.date
+        && a.links == b.links
+        && a.source == b.source
+        && a.archived == b.archived
+        && tag_set(a) == tag_set(b)
+}
+
+/// The op that reverses `op` when applied right after it to `tree`, where
+/// `tree` is the document before `op`.
+fn inverse_of(tree: &SumTree<TaggedBlock>, op: &TextOperation) -> UndoOp {
+    match op {
+        TextOperation::Insert { position, text } => UndoOp::Text(TextOperation::Delete {
+            start_position: *position,
+            end_position: position + text.chars().count(),
+        }),
+        TextOperation::Delete {
+            start_position,
+            end_position,
+        } => UndoOp::Restore {
+            position: *start_position,
+            blocks: blocks_between(tree, *start_position, *end_position),
+        },
+    }
+}
+
+/// The parts of blocks lying in `start..end`, with their metadata.
+fn blocks_between(tree: &SumTree<TaggedBlock>, start: usize, end: usize) -> Vec<TaggedBlock> {
+    let mut blocks = Vec::new();
+    let mut cursor = tree.cursor::<Chars>(());
+    cursor.seek(&Chars(start), Bias::Right);
+    while let Some(block) = cursor.item() {
+        let offset = cursor.start().0;
+        if offset >= end {
+            break;
+        }
+        let skip = start.saturating_sub(offset);
+        let take = end.min(offset + block.char_count()) - offset - skip;
+        blocks.push(TaggedBlock {
+            text: block.text.chars().skip(skip).take(take).collect(),
+            ..block.clone()
+        });
+        cursor.next();
+    }
+    blocks
+}
+
+/// Sets the tags of the text in `start..end`, splitting blocks that extend
+/// past it (e.g. after [`Timeline::compact`]). Returns the tags the first
+/// of them had.
+fn set_tags_in_range(
+    tree: &mut SumTree<TaggedBlock>,
+    start: usize,
+    end: usize,
+    tags: &[u32],
+    now: DateTime<Utc>,
+) -> Result<Vec<u32>, ApplyOpsError> {
+    let mut cursor = tree.cursor::<Chars>(());
+    let mut blocks = cursor.slice(&Chars(start), Bias::Right);
+    let mut previous = None;
+    while let Some(block) = cursor.item() {
+        let block_start = cursor.start().0;
+        if end <= block_start {
+            break;
+        }
+        let offset = block_start + block.char_count();
+        cursor.next();
+
+        let chars: Vec<char> = block.text.chars().collect();
+        let from = start.saturating_sub(block_start);
+        let to = end.min(offset) - block_start;
+        let part = |range: std::ops::Range<usize>, tags: &[u32]| TaggedBlock {
+            text: chars[range].iter().collect(),
+            tags: tags.to_vec(),
+            ..block.clone()
+        };
+        if from > 0 {
+            blocks.push(part(0..from, &block.tags), ());
+        }
+        blocks.push(
+            TaggedBlock {
+                updated_at: Some(now),
+                ..part(from..to, tags)
+            },
+            (),
+        );
+        if to < chars.len() {
+            blocks.push(part(to..chars.len(), &block.tags), ());
+        }
+        previous.get_or_insert_with(|| block.tags.clone());
+    }
+    let previous = previous.ok_or(ApplyOpsError::InvalidRange { start, end })?;
+    blocks.append(cursor.suffix(), ());
+    drop(cursor);
+    *tree = blocks;
+    Ok(previous)
+}
+
+/// The line of `text` where the first non-empty match of `regex` starts.
+fn snippet_line<'a>(text: &'a str, regex: &Regex) -> &'a str {
+    let Some(first) = regex.find_iter(text).find(|found| !found.is_empty()) else {
+        return "";
+    };
+    let start = text[..first.start()]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    text[start..].lines().next().unwrap_or_default()
+}
+
+fn fill_daily_note(template: &str, date: NaiveDate, dates: &[DateOccurrence]) -> String {
+    let labels: Vec<String> = dates.iter().map(DateOccurrence::label).collect();
+    let labels = labels.join(", ");
+    let values = [
+        ("date", date.to_string()),
+        ("weekday", date.format("%A").to_string()),
+        ("important_dates", labels),
+    ];
+    let mut text = String::new();
+    for line in template.split_inclusive('\n') {
+        if line.contains("{important_dates}") && values[2].1.is_empty() {
+            continue;
+        }
+        let mut line = line.to_string();
+        for (name, value) in &values {
+            line = line
+                .replace(&format!("{{{{{name}}}}}"), value)
+                .replace(&format!("{{{name}}}"), value);
+        }
+        text.push_str(&line);
+    }
+    text
+}
+
+/// Up to `limit` characters of document text immediately before `position`.
+fn text_before(tree: &SumTree<TaggedBlock>, position: usize, limit: usize) -> String {
+    let start = position.saturating_sub(limit);
+    let mut text = String::new();
+    let mut offset = 0;
+    for block in tree.iter() {
+        let end = offset + block.char_count();
+        if end > start {
+            let skip = start.saturating_sub(offset);
+            let take = position.min(end) - offset - skip;
+            text.extend(block.text.chars().skip(skip).take(take));
+        }
+        if end >= position {
+            break;
+        }
+        offset = end;
+    }
+    text
+}
+
+fn push_block_text(content: &mut String, block: &TaggedBlock, masked: &HashSet<u32>) {
+    if block.tags.iter().any(|tag| masked.contains(tag)) {
+        content.extend(block.text.chars().map(|ch| match ch {
+            '\n' => '\n',
+            _ => SENSITIVE_MASK,
+        }));
+    } else {
+        content.push_str(&block.text);
+    }
+}
+
+fn split_at_char(input: &str, char_index: usize) -> Option<(String, String)> {
+    if char_index == 0 {
+        return Some((String::new(), input.to_string()));
+    }
+
+    let mut count = 0;
+    for (byte, _) in input.char_indices() {
+        if count == char_index {
+            let (left, right) = input.split_at(byte);
+            return Some((left.to_string(), right.to_string()));
+        }
+        count += 1;
+    }
+
+    if count == char_index {
+        Some((input.to_string(), String::new()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+    use std::env;
+    use tempfile::tempdir;
+
+    fn sample_insert(text: &str) -> TextOperation {
+        TextOperation::Insert {
+            position: 0,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn tag_registry_assigns_unique_ids() {
+        let mut registry = TagRegistry::new();
+        let alpha = registry.intern_segment(None, "alpha");
+        let beta = registry.intern_segment(None, "beta");
+
+        assert_ne!(alpha, beta);
+        assert_eq!(
+            registry.get_tag(alpha).map(|tag| tag.name.as_str()),
+            Some("alpha")
+        );
+        assert_eq!(
+            registry.get_tag(beta).map(|tag| tag.name.as_str()),
+            Some("beta")
+        );
+    }
+
+    #[test]
+    fn tag_registry_reuses_existing_id_for_same_tag() {
+        let mut registry = TagRegistry::new();
+        let first = registry
+            .intern_path(["project", "sightline"])
+            .expect("path id");
+        let second = registry
+            .intern_path(["project", "sightline"])
+            .expect("path id");
+
+        assert_eq!(first, second);
+        let tag = registry.get_tag(first).expect("tag exists");
+        assert_eq!(tag.name, "sightline");
+        let parent = tag.parent_id.and_then(|id| registry.get_tag(id));
+        assert_eq!(parent.map(|tag| tag.name.as_str()), Some("project"));
+    }
+
+    #[test]
+    fn tag_registry_full_name_reconstructs_hierarchy() {
+        let mut registry = TagRegistry::new();
+        let id = registry
+            .intern_path(["project", "sightline", "importer"])
+            .expect("path id");
+
+        assert_eq!(
+            registry.full_name(id).as_deref(),
+            Some("project:sightline:importer")
+        );
+
+        let child = registry.get_tag(id).unwrap();
+        let parent = registry.get_tag(child.parent_id.unwrap()).unwrap();
+        assert_eq!(parent.name, "sightline");
+        let grandparent = registry.get_tag(parent.parent_id.unwrap()).unwrap();
+        assert_eq!(grandparent.name, "project");
+    }
+
+    #[test]
+    fn tag_registry_intern_colon_path_trims_segments() {
+        let mut registry = TagRegistry::new();
+        let id = registry
+            .intern_colon_path(" project : sightline : importer ")
+            .expect("path id");
+
+        assert_eq!(
+            registry.full_name(id).as_deref(),
+            Some("project:sightline:importer")
+        );
+    }
+
+    #[test]
+    fn filter_cursor_prunes_blocks_without_matching_tag() {
+        let tag_id = 42;
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let blocks = vec![
+            TaggedBlock::new(date, "First", Vec::new()),
+            TaggedBlock::new(date, "Tagged", vec![tag_id]),
+            TaggedBlock::new(date, "Third", Vec::new()),
+        ];
+
+        let tree = SumTree::from_iter(blocks, ());
+        let mut cursor = tree.filter::<_, ()>((), |summary: &TimelineSummary| {
+            summary.tags_filter.check(&tag_id)
+        });
+
+        cursor.next();
+        let item = cursor.item().expect("cursor should point at tagged block");
+        assert_eq!(item.text, "Tagged");
+
+        cursor.next();
+        assert!(cursor.item().is_none());
+    }
+
+    #[test]
+    fn count_words_handles_cjk_and_hyphenation() {
+        assert_eq!(count_words("A well-known plan, again."), 4);
+        assert_eq!(count_words("今日は晴れ"), 5);
+        assert_eq!(count_words("Trip to 東京 -- great"), 5);
+        assert_eq!(count_words("state-of-the-art"), 1);
+        assert_eq!(count_words("   \n"), 0);
+    }
+
+    #[test]
+    fn byte_and_utf16_offsets_convert_to_chars() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let block = |text: &str| TaggedBlock::new(date, text, Vec::new());
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter([block("a🌱b\n"), block("é🚀\n")], ()),
+            ..Timeline::default()
+        };
+        let tree = &timeline.tree;
+        assert_eq!(timeline.summary().total_utf16, 9);
+
+        assert_eq!(char_offset(tree, 3, OffsetUnit::Utf16), Some(2));
+        assert_eq!(char_offset(tree, 2, OffsetUnit::Utf16), None);
+        assert_eq!(char_offset(tree, 9, OffsetUnit::Utf16), Some(7));
+        assert_eq!(char_offset(tree, 10, OffsetUnit::Utf16), None);
+        assert_eq!(char_offset(tree, 7, OffsetUnit::Bytes), Some(4));
+        assert_eq!(char_offset(tree, 8, OffsetUnit::Bytes), None);
+        assert_eq!(char_offset(tree, 9, OffsetUnit::Bytes), Some(5));
+        assert_eq!(char_offset(tree, 42, OffsetUnit::Chars), Some(42));
+
+        // The delete is read against the document after the insert.
+        let ops = [
+            TextOperation::Insert {
+                position: 3,
+                text: "🍀".to_string(),
+            },
+            TextOperation::Delete {
+                start_position: 3,
+                end_position: 5,
+            },
+        ];
+        let converted = timeline.ops_in_chars(&ops, OffsetUnit::Utf16).unwrap();
+        assert_eq!(
+            converted,
+            [
+                TextOperation::Insert {
+                    position: 2,
+                    text: "🍀".to_string(),
+                },
+                TextOperation::Delete {
+                    start_position: 2,
+                    end_position: 3,
+                },
+            ]
+        );
+        let version = timeline.version();
+        timeline.apply_ops(version, &converted).unwrap();
+        assert_eq!(timeline.content(), "a🌱b\né🚀\n");
+
+        assert_eq!(
+            timeline.ops_in_chars(&[sample_insert("x")][..], OffsetUnit::Bytes),
+            Ok(vec![sample_insert("x")])
+        );
+        let split = TextOperation::Insert {
+            position: 2,
+            text: "x".to_string(),
+        };
+        assert_eq!(
+            timeline.ops_in_chars(&[split], OffsetUnit::Utf16),
+            Err(ApplyOpsError::InvalidPosition { position: 2 })
+        );
+    }
+
+    #[test]
+    fn points_and_offsets_convert_both_ways() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let block = |text: &str| TaggedBlock::new(date, text, Vec::new());
+        let timeline = Timeline {
+            tree: SumTree::from_iter([block("ab\ncd"), block("é\n"), block("\nxyz")], ()),
+            ..Timeline::default()
+        };
+        // ab
+        // cdé
+        //
+        // xyz
+        assert_eq!(timeline.summary().lines, Point::new(3, 3));
+
+        let cases = [
+            (0, Point::new(0, 0)),
+            (2, Point::new(0, 2)),
+            (3, Point::new(1, 0)),
+            (5, Point::new(1, 2)),
+            (6, Point::new(1, 3)),
+            (7, Point::new(2, 0)),
+            (8, Point::new(3, 0)),
+            (11, Point::new(3, 3)),
+        ];
+        for (offset, point) in cases {
+            assert_eq!(timeline.offset_to_point(offset), Some(point), "{offset}");
+            assert_eq!(timeline.point_to_offset(point), Some(offset), "{point:?}");
+        }
+
+        assert_eq!(timeline.offset_to_point(12), None);
+        assert_eq!(timeline.point_to_offset(Point::new(0, 3)), None);
+        assert_eq!(timeline.point_to_offset(Point::new(2, 1)), None);
+        assert_eq!(timeline.point_to_offset(Point::new(4, 0)), None);
+        assert_eq!(
+            Timeline::default().point_to_offset(Point::default()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn chars_dimension_accumulates_character_counts() {
+        let mut dimension = Chars::zero(());
+
+        let summary_a = TimelineSummary {
+            total_chars: 3,
+            ..TimelineSummary::default()
+        };
+        dimension.add_summary(&summary_a, ());
+
+        let summary_b = TimelineSummary {
+            total_chars: 5,
+            ..TimelineSummary::default()
+        };
+        dimension.add_summary(&summary_b, ());
+
+        assert_eq!(dimension.0, 8);
+    }
+
+    #[test]
+    fn blocks_are_found_and_replaced_by_index() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let mut timeline = Timeline::default();
+        let blocks = (0..200).map(|i| TaggedBlock::new(date, format!("{i:03}\n"), Vec::new()));
+        timeline.tree = SumTree::from_iter(blocks.collect::<Vec<_>>(), ());
+
+        let (start, block) = block_at(&timeline.tree, 150).expect("block 150");
+        assert_eq!((start, block.text.as_str()), (600, "150\n"));
+        assert!(block_at(&timeline.tree, 200).is_none());
+
+        for index in [0, 150, 199] {
+            timeline
+                .assign_block_tags(index, &["marked".to_string()])
+                .expect("tag block");
+        }
+        let marked = timeline.tag_registry().find_colon_path("marked").unwrap();
+        assert_eq!(
+            timeline.block_ids_with_tags(
+                &[marked],
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            vec![0, 150, 199]
+        );
+        assert_eq!(timeline.entry_count(), 200);
+        assert_eq!(timeline.content().lines().nth(150), Some("150"));
+    }
+
+    #[test]
+    fn render_date_finds_blocks_out_of_date_order() {
+        let day = |n| NaiveDate::from_ymd_opt(2024, 5, n).unwrap();
+        let block = |date, text: &str| TaggedBlock::new(date, text, Vec::new());
+        // Enough blocks for several tree levels, with an edit made on the
+        // 9th landing among the 3rd's entries.
+        let mut blocks = Vec::new();
+        for n in 1..=8 {
+            for i in 0..40 {
+                blocks.push(block(day(n), &format!("{n}.{i}\n")));
+            }
+        }
+        blocks.insert(90, block(day(9), "late "));
+        blocks.push(block(day(9), "ninth\n"));
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            ..Timeline::default()
+        };
+
+        let mut cursor = timeline.tree.cursor::<LatestDate>(());
+        cursor.seek(&LatestDate(Some(day(2))), Bias::Left);
+        assert_eq!(
+            cursor.item().map(|block| block.text.as_str()),
+            Some("2.0\n")
+        );
+
+        let first: String = (0..40).map(|i| format!("1.{i}\n")).collect();
+        assert_eq!(timeline.log_for_date(day(1)), Some(first));
+        assert_eq!(
+            timeline.log_for_date(day(9)).as_deref(),
+            Some("late ninth\n")
+        );
+        let third = timeline.log_for_date(day(3)).unwrap();
+        assert_eq!(third.lines().count(), 40);
+        assert!(!third.contains("late"));
+        assert_eq!(timeline.log_for_date(day(10)), None);
+    }
+
+    #[test]
+    fn editable_timeline_insert_inserts_text_at_position() {
+        let base_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let entries = vec![TaggedBlock::new(base_date, "abcd", Vec::new())];
+
+        let mut tree = SumTree::from_iter(entries, ());
+        tree.apply_ops(
+            &[TextOperation::Insert {
+                position: 2,
+                text: "XY".to_string(),
+            }],
+            base_date,
+            Utc::now(),
+        )
+        .expect("insert");
+
+        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(content, "abXYcd");
+    }
+
+    #[test]
+    fn editable_timeline_delete_within_entry_removes_characters() {
+        let base_date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let entries = vec![TaggedBlock::new(base_date, "abcdef", Vec::new())];
+
+        let mut tree = SumTree::from_iter(entries, ());
+        tree.apply_ops(
+            &[TextOperation::Delete {
+                start_position: 2,
+                end_position: 4,
+            }],
+            base_date,
+            Utc::now(),
+        )
+        .expect("delete");
+
+        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(content, "abef");
+    }
+
+    #[test]
+    fn editable_timeline_delete_spanning_entries_trims_correctly() {
+        let date_a = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let date_b = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+
+        let entries = vec![
+            TaggedBlock::new(date_a, "12345", Vec::new()),
+            TaggedBlock::new(date_b, "ABCDE", Vec::new()),
+        ];
+
+        let mut tree = SumTree::from_iter(entries, ());
+        tree.apply_ops(
+            &[TextOperation::Delete {
+                start_position: 3,
+                end_position: 7,
+            }],
+            date_a,
+            Utc::now(),
+        )
+        .expect("delete across entries");
+
+        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(content, "123CDE");
+    }
+
+    #[test]
+    fn summary_aggregates_counts_and_dates() {
+        let date_a = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let date_b = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+
+        let tag_id = 7;
+        let entry_a = TaggedBlock::new(date_a, "Hello", vec![tag_id]);
+        let entry_b = TaggedBlock::new(date_b, "世界", Vec::new());
+
+        let mut summary = entry_a.summary(());
+        let other_summary = entry_b.summary(());
+        sum_tree::Summary::add_summary(&mut summary, &other_summary, ());
+
+        assert_eq!(summary.entry_count, 2);
+        assert_eq!(summary.total_chars, 7);
+        assert_eq!(summary.total_bytes, 5 + "世界".len());
+        assert_eq!(summary.total_words, 3);
+        assert_eq!(summary.min_date, Some(date_a));
+        assert_eq!(summary.max_date, Some(date_b));
+        assert!(summary.tags_filter.check(&tag_id));
+    }
+
+    #[test]
+    fn tag_filters_stay_exact_until_the_limit() {
+        let mut filter = TagFilter::default();
+        for tag in (0..EXACT_TAG_LIMIT as u32).rev() {
+            filter.insert(tag * 10);
+        }
+        filter.insert(0);
+        assert!(matches!(&filter, TagFilter::Exact(tags) if tags.len() == EXACT_TAG_LIMIT));
+        assert!(filter.check(&70) && !filter.check(&5));
+
+        let mut many = filter.clone();
+        many.insert(1_000);
+        assert!(matches!(many, TagFilter::Bloom(_)));
+        assert!((0..EXACT_TAG_LIMIT as u32).all(|tag| many.check(&(tag * 10))));
+        assert!(many.check(&1_000));
+
+        // An exact set takes on a bloom filter's tags and becomes one.
+        let mut small = TagFilter::default();
+        small.insert(4_242);
+        union_tag_filters(&mut small, &many);
+        assert!(matches!(small, TagFilter::Bloom(_)));
+        assert!(small.check(&4_242) && small.check(&1_000));
+
+        let mut empty = TagFilter::default();
+        union_tag_filters(&mut empty, &TagFilter::default());
+        assert!(empty.is_empty());
+        union_tag_filters(&mut empty, &filter);
+        assert!(matches!(empty, TagFilter::Exact(_)) && empty.check(&30));
+    }
+
+    #[test]
+    fn apply_insert_updates_content_and_version() {
+        let mut timeline = Timeline::default();
+
+        let new_version = timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "Happy New Year!".to_string(),
+                }],
+            )
+            .expect("insert succeeds");
+
+        assert_eq!(new_version, 1);
+        assert_eq!(timeline.version(), 1);
+        assert_eq!(timeline.content(), "Happy New Year!");
+        assert_eq!(timeline.entry_count(), 1);
+        assert_eq!(timeline.summary().min_date, timeline.summary().max_date);
+        assert!(timeline.summary().min_date.is_some());
+    }
+
+    #[test]
+    fn apply_delete_removes_text() {
+        let mut timeline = Timeline::default();
+
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "abcdef".to_string(),
+                }],
+            )
+            .expect("initial insert succeeds");
+
+        let new_version = timeline
+            .apply_ops(
+                1,
+                &[TextOperation::Delete {
+                    start_position: 2,
+                    end_position: 4,
+                }],
+            )
+            .expect("delete succeeds");
+
+        assert_eq!(new_version, 2);
+        assert_eq!(timeline.version(), 2);
+        assert_eq!(timeline.content(), "abef");
+        assert_eq!(timeline.summary().total_chars, 4);
+    }
+
+    #[test]
+    fn undo_restores_deleted_blocks_and_tags() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let block = |d, text: &str| TaggedBlock::new(day(d), text, Vec::new());
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter([block(1, "First day\n"), block(2, "Second day\n")], ()),
+            ..Timeline::default()
+        };
+        assert!(!timeline.can_undo());
+        assert_eq!(timeline.undo(), Ok(false));
+
+        let tags = timeline
+            .assign_block_tags(1, &["#work".to_string()])
+            .expect("tags assigned");
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Delete {
+                    start_position: 6,
+                    end_position: 17,
+                }],
+            )
+            .expect("delete succeeds");
+        assert_eq!(timeline.content(), "First day\n");
+
+        assert_eq!(timeline.undo(), Ok(true));
+        assert_eq!(timeline.version(), 2);
+        assert_eq!(timeline.content(), "First day\nSecond day\n");
+        let dated = |timeline: &Timeline| -> Vec<(NaiveDate, Vec<u32>)> {
+            timeline
+                .blocks_in_range(
+                    &DateRange::default(),
+                    SensitiveContent::Included,
+                    ArchivedContent::Excluded,
+                )
+                .into_iter()
+                .filter(|block| block.text.contains("Second"))
+                .map(|block| (block.date, block.tags.clone()))
+                .collect()
+        };
+        assert_eq!(dated(&timeline), vec![(day(2), vec![tags[0].id])]);
+
+        assert_eq!(timeline.undo(), Ok(true));
+        assert_eq!(dated(&timeline), vec![(day(2), Vec::new())]);
+        assert!(!timeline.can_undo());
+
+        assert_eq!(timeline.redo(), Ok(true));
+        assert_eq!(dated(&timeline), vec![(day(2), vec![tags[0].id])]);
+        assert_eq!(timeline.redo(), Ok(true));
+        assert_eq!(timeline.content(), "First day\n");
+        assert_eq!(timeline.redo(), Ok(false));
+
+        timeline.undo().expect("undo succeeds");
+        timeline
+            .apply_ops(
+                timeline.version(),
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "New ".to_string(),
+                }],
+            )
+            .expect("insert succeeds");
+        assert!(!timeline.can_redo());
+        assert_eq!(timeline.undo(), Ok(true));
+        assert_eq!(timeline.content(), "First day\nSecond day\n");
+    }
+
+    #[test]
+    fn similar_blocks_suggest_tags_and_relate() {
+        let mut timeline = Timeline::default();
+        let mut tag = |name: &str| vec![timeline.intern_tag(name).unwrap().id];
+        let (garden, admin, sensitive) = (tag("#garden"), tag("#admin"), tag("#sensitive"));
+        let block = |text: &str, tags: Vec<u32>| {
+            TaggedBlock::new(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(), text, tags)
+        };
+        timeline.tree = SumTree::from_iter(
+            [
+                block("Pruned the roses in the garden\n", garden),
+                block("Garden roses need water\n", Vec::new()),
+                block("Tax forms due\n", admin),
+                block("Secret garden roses\n", sensitive),
+            ],
+            (),
+        );
+
+        // The sensitive block shares words with both, but is masked.
+        let suggestions = timeline.suggest_block_tags(1, 5).unwrap();
+        let names: Vec<&str> = suggestions.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, ["#garden"]);
+        assert_eq!(timeline.suggest_block_tags(0, 5), Some(Vec::new()));
+        assert_eq!(timeline.suggest_block_tags(9, 5), None);
+
+        let related = timeline
+            .related_blocks(1, 5, SensitiveContent::Masked)
+            .unwrap();
+        let indexes: Vec<u32> = related.iter().map(|block| block.index).collect();
+        assert_eq!(indexes, [0]);
+        assert_eq!(related[0].score, 2);
+        let with_sensitive = timeline
+            .related_blocks(1, 5, SensitiveContent::Included)
+            .unwrap();
+        assert_eq!(with_sensitive.len(), 2);
+
+        // Edited blocks get fresh terms.
+        assert_eq!(timeline.block_terms(2).unwrap()[0].word, "due");
+        let offset = timeline.content().find("Tax").unwrap();
+        timeline
+            .apply_ops(
+                timeline.version(),
+                &[TextOperation::Insert {
+                    position: offset,
+                    text: "Garden ".to_string(),
+                }],
+            )
+            .expect("edit succeeds");
+        let words: Vec<String> = timeline
+            .block_terms(2)
+            .unwrap()
+            .iter()
+            .map(|term| term.word.clone())
+            .collect();
+        assert!(words.contains(&"garden".to_string()));
+    }
+
+    #[test]
+    fn delete_tag_strips_ids_from_blocks_and_history() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(0, &[sample_insert("Plan\n")])
+            .expect("insert succeeds");
+        timeline
+            .assign_block_tags(0, &["#work:meetings".to_string(), "#home".to_string()])
+            .expect("tags assigned");
+        let registry = timeline.tag_registry();
+        let work = registry.find_colon_path("work").unwrap();
+        let home = registry.find_colon_path("home").unwrap();
+
+        assert_eq!(
+            timeline.delete_tag(work, false),
+            Err(DeleteTagError::HasChildren(work))
+        );
+        assert_eq!(
+            timeline.delete_tag(404, true),
+            Err(DeleteTagError::UnknownTag(404))
+        );
+
+        assert_eq!(
+            timeline.delete_tag(work, true),
+            Ok(TagDeletion { tags: 2, blocks: 1 })
+        );
+        assert_eq!(timeline.tag_registry().find_colon_path("work"), None);
+        assert_eq!(timeline.list_blocks()[0].tags, vec![home]);
+
+        // Undoing the tag assignment brings back only the surviving tag.
+        timeline.undo().expect("undo succeeds");
+        timeline.redo().expect("redo succeeds");
+        assert_eq!(timeline.list_blocks()[0].tags, vec![home]);
+
+        assert_eq!(
+            timeline.delete_tag(home, false),
+            Ok(TagDeletion { tags: 1, blocks: 1 })
+        );
+        assert!(timeline.list_blocks()[0].tags.is_empty());
+    }
+
+    #[test]
+    fn tag_tree_nests_tags_with_usage_counts() {
+        let mut timeline = Timeline::default();
+        let notes = timeline.intern_tag("#work:notes").unwrap().id;
+        let meetings = timeline.intern_tag("#work:meetings").unwrap().id;
+        let home = timeline.intern_tag("#home").unwrap().id;
+        let work = timeline.tag_registry().find_colon_path("work").unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let block = |text: &str, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        timeline.tree = SumTree::from_iter(
+            [
+                block("Standup\n", vec![meetings, work]),
+                block("Minutes\n", vec![notes]),
+                block("Chores\n", vec![home]),
+            ],
+            (),
+        );
+
+        let registry = timeline.tag_registry();
+        assert_eq!(registry.descendants(work), [meetings, notes]);
+        assert!(registry.descendants(home).is_empty());
+        assert!(registry.descendants(404).is_empty());
+        assert_eq!(
+            registry.descendant_ids(work),
+            HashSet::from([work, meetings, notes])
+        );
+
+        let tree = timeline.tag_tree();
+        let summary: Vec<(&str, u32, u32, usize)> = tree
+            .iter()
+            .map(|node| {
+                (
+                    node.full_name.as_str(),
+                    node.block_count,
+                    node.total_block_count,
+                    node.children.len(),
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("#home", 1, 1, 0), ("#work", 1, 2, 2)]);
+        let children: Vec<(&str, u32)> = tree[1]
+            .children
+            .iter()
+            .map(|node| (node.name.as_str(), node.total_block_count))
+            .collect();
+        assert_eq!(children, [("meetings", 1), ("notes", 1)]);
+        assert_eq!(tree[0].color, timeline.list_tags()[0].color);
+    }
+
+    #[test]
+    fn rename_tag_keeps_ids_and_checks_siblings() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(0, &[sample_insert("Plan\n")])
+            .expect("insert succeeds");
+        timeline
+            .assign_block_tags(0, &["#proj:ideas".to_string(), "#home".to_string()])
+            .expect("tags assigned");
+        let registry = timeline.tag_registry();
+        let proj = registry.find_colon_path("proj").unwrap();
+        let ideas = registry.find_colon_path("proj:ideas").unwrap();
+        let tags = timeline.list_blocks()[0].tags.clone();
+
+        assert_eq!(
+            timeline.rename_tag(proj, "#home"),
+            Err(RenameTagError::NameTaken("home".to_string()))
+        );
+        assert_eq!(
+            timeline.rename_tag(proj, "a:b"),
+            Err(RenameTagError::InvalidName)
+        );
+        assert_eq!(
+            timeline.rename_tag(404, "x"),
+            Err(RenameTagError::UnknownTag(404))
+        );
+
+        timeline.rename_tag(proj, "#project").unwrap();
+        let registry = timeline.tag_registry();
+        assert_eq!(registry.find_colon_path("project"), Some(proj));
+        assert_eq!(registry.find_colon_path("project:ideas"), Some(ideas));
+        assert_eq!(registry.find_colon_path("proj"), None);
+        assert_eq!(timeline.list_blocks()[0].tags, tags);
+
+        // The old name is free again, and gets a different id.
+        let reused = timeline.intern_tag("#proj").unwrap().id;
+        assert_ne!(reused, proj);
+    }
+
+    #[test]
+    fn reparent_tag_moves_subtrees_and_retags_blocks() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(0, &[sample_insert("Plan\n")])
+            .expect("insert succeeds");
+        timeline
+            .assign_block_tags(0, &["#sightline:ideas".to_string(), "#home".to_string()])
+            .expect("tags assigned");
+        let registry = timeline.tag_registry();
+        let sightline = registry.find_colon_path("sightline").unwrap();
+        let ideas = registry.find_colon_path("sightline:ideas").unwrap();
+        let home = registry.find_colon_path("home").unwrap();
+        let existing = timeline.intern_tag("#project:home").unwrap().id;
+        let project = timeline.tag_registry().find_colon_path("project").unwrap();
+
+        assert_eq!(
+            timeline.reparent_tag(sightline, Some(ideas)),
+            Err(ReparentTagError::Cycle {
+                id: sightline,
+                parent: ideas
+            })
+        );
+        assert_eq!(
+            timeline.reparent_tag(404, None),
+            Err(ReparentTagError::UnknownTag(404))
+        );
+
+        let moved = timeline.reparent_tag(sightline, Some(project)).unwrap();
+        let registry = timeline.tag_registry();
+        assert_eq!(registry.find_colon_path("project:sightline"), Some(moved));
+        let moved_ideas = registry.find_colon_path("project:sightline:ideas").unwrap();
+        assert_eq!(registry.find_colon_path("sightline"), None);
+        assert_eq!(registry.get_tag(ideas), None);
+
+        // Moving onto a taken name merges into the existing tag.
+        assert_eq!(timeline.reparent_tag(home, Some(project)), Ok(existing));
+        assert_eq!(timeline.tag_registry().find_colon_path("home"), None);
+        let mut expected = vec![moved_ideas, existing];
+        expected.sort_unstable();
+        assert_eq!(timeline.list_blocks()[0].tags, expected);
+
+        // Undo history follows the new ids.
+        timeline.undo().expect("undo succeeds");
+        assert!(timeline.list_blocks()[0].tags.is_empty());
+        timeline.redo().expect("redo succeeds");
+        assert_eq!(timeline.list_blocks()[0].tags, expected);
+    }
+
+    #[test]
+    fn compact_merges_fragments_without_changing_text() {
+        let mut timeline = Timeline::default();
+        for (position, text) in [(0, "Hello"), (5, " world"), (5, ","), (12, "!")] {
+            timeline
+                .apply_ops(
+                    timeline.version(),
+                    &[TextOperation::Insert {
+                        position,
+                        text: text.to_string(),
+                    }],
+                )
+                .expect("insert succeeds");
+        }
+        let tags = timeline
+            .assign_block_tags(0, &["#greeting".to_string()])
+            .expect("tags assigned");
+        timeline
+            .assign_block_tags(1, &["#greeting".to_string()])
+            .expect("tags assigned");
+        assert_eq!(timeline.entry_count(), 4);
+        let version = timeline.version();
+
+        assert_eq!(timeline.compact(), 2);
+        assert_eq!(timeline.entry_count(), 2);
+        assert_eq!(timeline.content(), "Hello, world!");
+        assert_eq!(timeline.version(), version + 1);
+        assert_eq!(timeline.word_count(), 2);
+        assert_eq!(timeline.compact(), 0);
+        assert_eq!(timeline.version(), version + 1);
+
+        // "Hello" and "," merged; undoing the second assignment splits them.
+        let tagged = |timeline: &Timeline| -> Vec<String> {
+            timeline
+                .blocks_tagged(
+                    tags[0].id,
+                    SensitiveContent::Included,
+                    ArchivedContent::Excluded,
+                )
+                .into_iter()
+                .map(|block| block.text.clone())
+                .collect()
+        };
+        assert_eq!(tagged(&timeline), vec!["Hello,"]);
+        timeline.undo().expect("undo succeeds");
+        assert_eq!(tagged(&timeline), vec!["Hello"]);
+        assert_eq!(timeline.content(), "Hello, world!");
+    }
+
+    #[test]
+    fn compact_keeps_the_earliest_creation_and_latest_update() {
+        let day = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let at = |hour| chrono::TimeZone::with_ymd_and_hms(&Utc, 2025, 3, 1, hour, 0, 0).unwrap();
+        let part = |text: &str, created_at, updated_at| TaggedBlock {
+            created_at,
+            updated_at,
+            ..TaggedBlock::new(day, text, Vec::new())
+        };
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(
+                [
+                    part("Hello", Some(at(9)), Some(at(11))),
+                    part(", ", None, None),
+                    part("world", Some(at(8)), Some(at(10))),
+                ],
+                (),
+            ),
+            ..Timeline::default()
+        };
+
+        assert_eq!(timeline.compact(), 2);
+        let block = timeline.tree.first().unwrap();
+        assert_eq!(block.created_at, Some(at(8)));
+        assert_eq!(block.updated_at, Some(at(11)));
+    }
+
+    #[test]
+    fn apply_delete_spanning_entries_truncates_correctly() {
+        let mut timeline = Timeline::default();
+
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "12345".to_string(),
+                }],
+            )
+            .expect("first insert succeeds");
+
+        let position = timeline.summary().total_chars;
+        timeline
+            .apply_ops(
+                1,
+                &[TextOperation::Insert {
+                    position,
+                    text: "ABCDE".to_string(),
+                }],
+            )
+            .expect("second insert succeeds");
+
+        let new_version = timeline
+            .apply_ops(
+                2,
+                &[TextOperation::Delete {
+                    start_position: 3,
+                    end_position: 7,
+                }],
+            )
+            .expect("delete succeeds");
+
+        assert_eq!(new_version, 3);
+        assert_eq!(timeline.version(), 3);
+        assert_eq!(timeline.content(), "123CDE");
+        assert_eq!(timeline.summary().total_chars, 6);
+    }
+
+    #[test]
+    fn apply_delete_out_of_bounds_returns_error() {
+        let mut timeline = Timeline::default();
+
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "short".to_string(),
+                }],
+            )
+            .expect("insert succeeds");
+
+        let result = timeline.apply_ops(
+            1,
+            &[TextOperation::Delete {
+                start_position: 0,
+                end_position: 10,
+            }],
+        );
+
+        assert_eq!(
+            result.expect_err("delete should fail"),
+            ApplyOpsError::InvalidRange { start: 0, end: 10 }
+        );
+    }
+
+    #[test]
+    fn save_to_path_writes_snapshot() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(0, &[sample_insert("Snapshot test")])
+            .expect("apply insert");
+
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        timeline.save_to_path(&path).expect("save timeline");
+
+        let contents = std::fs::read_to_string(&path).expect("read snapshot");
+        let snapshot: TimelineSnapshot = from_str(&contents).expect("parse snapshot");
+
+        assert_eq!(snapshot.version, timeline.version());
+        assert_eq!(snapshot.blocks.len(), 1);
+        assert_eq!(snapshot.blocks[0].text, "Snapshot test");
+        assert!(snapshot.tag_registry.is_none());
+    }
+
+    #[test]
+    fn save_to_path_includes_tag_hierarchy() {
+        let mut timeline = Timeline::default();
+        let project_id = timeline.tag_registry_mut().intern_segment(None, "project");
+        let _child_id = timeline
+            .tag_registry_mut()
+            .intern_segment(Some(project_id), "sightline");
+
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        timeline.save_to_path(&path).expect("save timeline");
+
+        let contents = std::fs::read_to_string(&path).expect("read snapshot");
+        let snapshot: TimelineSnapshot = from_str(&contents).expect("parse snapshot");
+
+        let tags = match snapshot.tag_registry {
+            Some(TagRegistrySnapshot::Hierarchical(tags)) => tags,
+            other => panic!("unexpected tag registry format: {other:?}"),
+        };
+
+        assert_eq!(tags.len(), 2);
+        let project = tags
+            .iter()
+            .find(|tag| tag.name == "project")
+            .expect("project tag present");
+        assert!(project.parent_id.is_none());
+
+        let sightline = tags
+            .iter()
+            .find(|tag| tag.name == "sightline")
+            .expect("sightline tag present");
+        assert_eq!(sightline.parent_id, Some(project.id));
+    }
+
+    #[test]
+    fn load_from_path_restores_state() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(0, &[sample_insert("Restored state")])
+            .expect("apply insert");
+
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        timeline.save_to_path(&path).expect("save timeline");
+
+        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+
+        assert_eq!(loaded.version(), timeline.version());
+        assert_eq!(loaded.content(), timeline.content());
+        assert_eq!(loaded.entry_count(), timeline.entry_count());
+    }
+
+    #[test]
+    fn load_replays_edits_logged_after_the_snapshot() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        let log = wal::log_path(&path);
+
+        let mut timeline = Timeline::default();
+        let first = timeline
+            .apply_edit(0, &[sample_insert("Saved. ")], true)
+            .expect("first edit");
+        wal::append(&log, &first.log).expect("log first edit");
+        timeline.save_to_path(&path).expect("save timeline");
+        assert!(!log.exists());
+
+        for text in ["Logged ", "twice"] {
+            let position = timeline.summary().total_chars;
+            let applied = timeline
+                .apply_edit(
+                    timeline.version(),
+                    &[TextOperation::Insert {
+                        position,
+                        text: text.to_string(),
+                    }],
+                    true,
+                )
+                .expect("logged edit");
+            wal::append(&log, &applied.log).expect("log edit");
+        }
+
+        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+        assert_eq!(loaded.version(), 3);
+        assert_eq!(loaded.content(), "Saved. Logged twice");
+
+        // Entries the snapshot already includes are skipped.
+        let mut replayed = loaded.clone();
+        assert_eq!(replayed.replay_log(&[first.log]), 0);
+        assert_eq!(replayed.content(), loaded.content());
+    }
+
+    #[test]
+    fn load_missing_file_returns_default() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+
+        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+
+        assert_eq!(loaded.version(), 0);
+        assert_eq!(loaded.entry_count(), 0);
+    }
+
+    #[test]
+    fn load_legacy_flat_tag_registry() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        let legacy_snapshot = serde_json::json!({
+            "version": 1,
+            "blocks": [],
+            "tag_registry": {
+                "5": "project:sightline"
+            }
+        });
+        std::fs::write(&path, legacy_snapshot.to_string()).expect("write legacy snapshot");
+
+        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+        assert_eq!(loaded.version(), 1);
+        assert_eq!(loaded.entry_count(), 0);
+        let id = loaded
+            .tag_registry()
+            .find_colon_path("project:sightline")
+            .expect("migrated tag");
+        assert_eq!(
+            loaded.tag_registry().full_name(id).as_deref(),
+            Some("project:sightline")
+        );
+    }
+
+    #[test]
+    fn legacy_registry_migration_can_be_previewed_and_corrected() {
+        let legacy_snapshot = serde_json::json!({
+            "version": 2,
+            "blocks": [
+                {"date": "2024-08-01", "text": "Plan\n", "tags": [5]},
+                {"date": "2024-08-02", "text": "Run\n", "tags": [7]}
+            ],
+            "tag_registry": {
+                "5": "sightline",
+                "7": "health:run"
+            }
+        });
+        let timeline =
+            Timeline::from_snapshot_json(legacy_snapshot.to_string().as_bytes()).expect("load");
+        let preview = timeline.registry_migration_preview().expect("legacy");
+        let paths: Vec<(u32, &str)> = preview
+            .iter()
+            .map(|migration| (migration.legacy_id, migration.path.as_str()))
+            .collect();
+        assert_eq!(paths, vec![(5, "sightline"), (7, "health:run")]);
+
+        // The pending migration survives a save.
+        let mut timeline =
+            Timeline::from_snapshot_json(&timeline.to_snapshot_json().expect("save"))
+                .expect("reload");
+        assert_eq!(timeline.registry_migration_preview(), Some(preview));
+
+        assert_eq!(
+            timeline.commit_registry_migration(&HashMap::from([(9, "x".to_string())])),
+            Err(RegistryMigrationError::UnknownTag(9))
+        );
+        assert_eq!(
+            timeline.commit_registry_migration(&HashMap::from([(5, " : ".to_string())])),
+            Err(RegistryMigrationError::EmptyPath(5))
+        );
+        let committed = timeline
+            .commit_registry_migration(&HashMap::from([(5, "#project:sightline".to_string())]))
+            .expect("commit");
+        assert_eq!(committed[0].path, "project:sightline");
+        assert_eq!(timeline.registry_migration_preview(), None);
+
+        let registry = timeline.tag_registry();
+        let sightline = registry.find_colon_path("project:sightline").unwrap();
+        assert_eq!(registry.find_colon_path("sightline"), None);
+        assert!(registry.find_colon_path("health:run").is_some());
+        assert_eq!(timeline.list_blocks()[0].tags, vec![sightline]);
+        assert_eq!(
+            timeline.commit_registry_migration(&HashMap::new()),
+            Err(RegistryMigrationError::NotLegacy)
+        );
+    }
+
+    #[test]
+    fn sequential_tag_ids_are_migrated_on_load() {
+        let legacy_snapshot = serde_json::json!({
+            "version": 3,
+            "blocks": [
+                {"date": "2024-08-01", "text": "Plan\n", "tags": [2, 0]}
+            ],
+            "tag_registry": [
+                {"id": 0, "name": "project", "parent_id": null, "color": "red"},
+                {"id": 1, "name": "sightline", "parent_id": 0},
+                {"id": 2, "name": "work", "parent_id": null}
+            ]
+        });
+        let loaded =
+            Timeline::from_snapshot_json(legacy_snapshot.to_string().as_bytes()).expect("load");
+
+        let mut fresh = TagRegistry::new();
+        let project = fresh.intern_segment(None, "project");
+        let sightline = fresh.intern_segment(Some(project), "sightline");
+        let work = fresh.intern_segment(None, "work");
+        let registry = loaded.tag_registry();
+        assert_eq!(
+            registry.find_colon_path("project:sightline"),
+            Some(sightline)
+        );
+        assert_eq!(
+            registry.get_tag(project).unwrap().color.as_deref(),
+            Some("red")
+        );
+        let mut expected = vec![project, work];
+        expected.sort_unstable();
+        assert_eq!(loaded.list_blocks()[0].tags, expected);
+
+        let saved = loaded.to_snapshot_json().expect("save");
+        let reloaded = Timeline::from_snapshot_json(&saved).expect("reload");
+        assert_eq!(reloaded.list_blocks()[0].tags, expected);
+        assert_eq!(
+            reloaded.tag_registry().find_colon_path("project:sightline"),
+            Some(sightline)
+        );
+    }
+
+    #[test]
+    fn tag_ids_agree_across_registries_and_merge() {
+        let mut laptop = TagRegistry::new();
+        let mut phone = TagRegistry::new();
+        let laptop_work = laptop.intern_colon_path("work:q3").unwrap();
+        phone.intern_colon_path("home").unwrap();
+        let phone_work = phone.intern_colon_path("work:q3").unwrap();
+        assert_eq!(laptop_work, phone_work);
+
+        let ids = laptop.merge(&phone);
+        assert!(ids.iter().all(|(theirs, ours)| theirs == ours));
+        assert_eq!(laptop.len(), 3);
+
+        // A tag squatting on another's id, as after a hash collision, is
+        // stepped past and remapped on merge.
+        let home = laptop.find_colon_path("home").unwrap();
+        let mut colliding = TagRegistry::from_tags(vec![Tag {
+            id: home,
+            name: "garden".to_string(),
+            parent_id: None,
+            color: None,
+        }]);
+        let colliding_home = colliding.intern_segment(None, "home");
+        assert_ne!(colliding_home, home);
+        let ids = laptop.merge(&colliding);
+        assert_eq!(ids[&colliding_home], home);
+        assert_ne!(ids[&home], home);
+        assert_eq!(laptop.full_name(ids[&home]).as_deref(), Some("garden"));
+    }
+
+    #[test]
+    fn tag_search_skips_subtrees_without_the_tag() {
+        let start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let mut registry = TagRegistry::new();
+        let rare = registry.intern_segment(None, "rare");
+        let topics: Vec<u32> = (0..50)
+            .map(|topic| registry.intern_segment(None, &format!("topic{topic}")))
+            .collect();
+        let blocks: Vec<TaggedBlock> = (0..10_000)
+            .map(|index: usize| {
+                TaggedBlock::new(
+                    start + chrono::Days::new(index as u64 / 4),
+                    format!("entry {index}\n"),
+                    if index % 1_000 == 500 {
+                        vec![rare]
+                    } else {
+                        vec![topics[index % topics.len()]]
+                    },
+                )
+            })
+            .collect();
+        let expected: Vec<u32> = (0..10).map(|n| n * 1_000 + 500).collect();
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+
+        let checked = std::cell::Cell::new(0);
+        let found = timeline.block_ids_matching(
+            &DateRange::default(),
+            ArchivedContent::Excluded,
+            |summary| summary.tags_filter.check(&rare),
+            |block| {
+                checked.set(checked.get() + 1);
+                block.tags.contains(&rare)
             },
+        );
+        assert_eq!(found, expected);
+        assert!(checked.get() < 1_000, "checked {} blocks", checked.get());
+        assert_eq!(
+            timeline.block_ids_with_tags(&[rare], &DateRange::default(), ArchivedContent::Excluded),
+            expected
+        );
+    }
+
+    #[test]
+    fn search_prefix_returns_matching_blocks() {
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let mut registry = TagRegistry::new();
+        let project = registry.intern_segment(None, "project");
+        let sightline = registry.intern_segment(Some(project), "sightline");
+        let home = registry.intern_segment(Some(project), "home");
+        let journal = registry
+            .intern_path(["type", "journal"])
+            .expect("journal tag");
+
+        let blocks = vec![
+            TaggedBlock::new(date, "Sightline plan", vec![sightline]),
+            TaggedBlock::new(date, "Home renovation", vec![home]),
+            TaggedBlock::new(date, "Daily reflection", vec![journal]),
+        ];
+
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            version: 0,
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+
+        assert_eq!(
+            timeline.search_prefix("#project", &DateRange::default(), ArchivedContent::Excluded),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn searches_are_limited_to_a_date_range() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut registry = TagRegistry::new();
+        let todo = registry.intern_colon_path("todo").unwrap();
+        let blocks = (0..200u64).map(|i| {
+            TaggedBlock::new(
+                start + chrono::Days::new(i / 2),
+                format!("TODO {i}\n"),
+                vec![todo],
+            )
+        });
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks.collect::<Vec<_>>(), ()),
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+
+        let day = |n| Some(start + chrono::Days::new(n));
+        let range = DateRange::new(day(10), day(11));
+        assert_eq!(
+            timeline.search_prefix("#todo", &range, ArchivedContent::Excluded),
+            vec![20, 21, 22, 23]
+        );
+        assert_eq!(
+            timeline.search_infix("od", &range, ArchivedContent::Excluded),
+            vec![20, 21, 22, 23]
+        );
+        assert_eq!(
+            timeline.search_tags_query(
+                "#todo",
+                &DateRange::new(day(98), None),
+                ArchivedContent::Excluded
+            ),
+            Ok(vec![196, 197, 198, 199])
+        );
+        let found = timeline
+            .search_regex(
+                "TODO",
+                &DateRange::new(None, day(0)),
+                ArchivedContent::Excluded,
+            )
+            .unwrap();
+        let blocks: Vec<_> = found.matches.iter().map(|m| m.block_index).collect();
+        assert_eq!(blocks, vec![0, 1]);
+
+        let before = DateRange::new(None, start.pred_opt());
+        assert!(timeline
+            .search_prefix("#todo", &before, ArchivedContent::Excluded)
+            .is_empty());
+        assert!(!before.overlaps(day(0), day(5)));
+        assert!(range.overlaps(day(0), day(10)));
+        assert!(!range.overlaps(None, None));
+    }
+
+    #[test]
+    fn search_tags_query_combines_tags() {
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let mut registry = TagRegistry::new();
+        let sightline = registry.intern_colon_path("project:sightline").unwrap();
+        let home = registry.intern_colon_path("project:home").unwrap();
+        let journal = registry.intern_colon_path("type:journal").unwrap();
+        let archived = registry.intern_colon_path("archived").unwrap();
+
+        // Enough blocks for whole subtrees to be skipped.
+        let tag_sets = [
+            vec![sightline, journal],
+            vec![sightline, journal, archived],
+            vec![home, journal],
+            vec![sightline],
+            vec![],
+        ];
+        let blocks: Vec<TaggedBlock> = (0..200)
+            .map(|n| {
+                TaggedBlock::new(
+                    date,
+                    format!("block {n}\n"),
+                    if n < 100 {
+                        Vec::new()
+                    } else {
+                        tag_sets[n % 5].clone()
+                    },
+                )
+            })
+            .collect();
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+        let expected = |sets: &[usize]| -> Vec<u32> {
+            (100..200)
+                .filter(|n| sets.contains(&(n % 5)))
+                .map(|n| n as u32)
+                .collect()
+        };
+
+        assert_eq!(
+            timeline.search_tags_query(
+                "#project:sightline AND #type:journal NOT #archived",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Ok(expected(&[0]))
+        );
+        assert_eq!(
+            timeline.search_tags_query(
+                "#project NOT #type",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Ok(expected(&[3]))
+        );
+        assert_eq!(
+            timeline.search_tags_query(
+                "#project:home OR #archived",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Ok(expected(&[1, 2]))
+        );
+        assert_eq!(
+            timeline.search_tags_query(
+                "#nothing",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            timeline
+                .search_tags_query(
+                    "NOT #project",
+                    &DateRange::default(),
+                    ArchivedContent::Excluded
+                )
+                .unwrap()
+                .len(),
+            100 + 20
+        );
+        assert_eq!(
+            timeline.search_tags_query(
+                "#project AND",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Err(TagQueryError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn search_infix_finds_partial_matches() {
+        let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let mut registry = TagRegistry::new();
+        let project = registry.intern_segment(None, "project");
+        let sightline = registry.intern_segment(Some(project), "sightline");
+        let research = registry.intern_segment(Some(project), "research");
+
+        let blocks = vec![
+            TaggedBlock::new(date, "Sightline planning", vec![sightline]),
+            TaggedBlock::new(date, "Research notes", vec![research]),
         ];
 
-        let tree = SumTree::from_iter(blocks, ());
-        let mut cursor = tree.filter::<_, ()>((), |summary: &TimelineSummary| {
-            summary.tags_filter.check(&tag_id)
-        });
+        let timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            version: 0,
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+
+        assert_eq!(
+            timeline.search_infix("sight", &DateRange::default(), ArchivedContent::Excluded),
+            vec![0]
+        );
+        assert_eq!(
+            timeline.search_infix("search", &DateRange::default(), ArchivedContent::Excluded),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn autocomplete_tags_returns_suggestions() {
+        let mut registry = TagRegistry::new();
+        let project = registry.intern_segment(None, "project");
+        let _sightline = registry.intern_segment(Some(project), "sightline");
+        let _strategy = registry.intern_segment(Some(project), "strategy");
+        let _journal = registry
+            .intern_path(["type", "journal"])
+            .expect("journal tag");
+
+        let timeline = Timeline {
+            tree: SumTree::new(()),
+            version: 0,
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+
+        let results = timeline.autocomplete_tags("#pro");
+        let names: Vec<_> = results
+            .iter()
+            .map(|suggestion| suggestion.name.as_str())
+            .collect();
+        assert!(names.contains(&"#project"));
+        assert!(names.contains(&"#project:sightline"));
+        assert!(names.contains(&"#project:strategy"));
+        assert!(results.iter().all(|suggestion| suggestion.color.is_some()));
+
+        let type_results = timeline.autocomplete_tags("#type:j");
+        assert_eq!(
+            type_results
+                .iter()
+                .map(|suggestion| suggestion.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["#type:journal"]
+        );
+        assert!(type_results
+            .iter()
+            .all(|suggestion| suggestion.color.is_some()));
+    }
+
+    #[test]
+    fn intern_tag_creates_and_reuses_entries() {
+        let mut timeline = Timeline::default();
+
+        let first = timeline
+            .intern_tag("#project:new")
+            .expect("create project tag");
+        assert_eq!(first.name, "#project:new");
+        assert_eq!(
+            first.id,
+            Timeline::default().intern_tag("#project:new").unwrap().id
+        );
+        assert!(!first.color.is_empty());
+
+        let reused = timeline
+            .intern_tag("project:new")
+            .expect("reuse existing tag");
+        assert_eq!(reused.id, first.id);
+        assert_eq!(reused.name, first.name);
+
+        let other = timeline
+            .intern_tag("type:journal")
+            .expect("create second tag");
+        assert_ne!(other.id, first.id);
+        assert!(other.name.starts_with("#type"));
+    }
 
-        cursor.next();
-        let item = cursor.item().expect("cursor should point at tagged block");
-        assert_eq!(item.text, "Tagged");
+    #[test]
+    fn intern_tag_rejects_invalid_input() {
+        let mut timeline = Timeline::default();
+        assert_eq!(timeline.intern_tag("   "), Err(InternTagError::Empty));
+        assert_eq!(timeline.intern_tag("#"), Err(InternTagError::Invalid));
+    }
 
-        cursor.next();
-        assert!(cursor.item().is_none());
+    #[test]
+    fn assign_block_tags_updates_block() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "entry one\n".to_string(),
+                }],
+            )
+            .expect("insert first entry");
+        let position = timeline.summary().total_chars;
+        timeline
+            .apply_ops(
+                1,
+                &[TextOperation::Insert {
+                    position,
+                    text: "entry two".to_string(),
+                }],
+            )
+            .expect("insert second entry");
+
+        let descriptors = timeline
+            .assign_block_tags(
+                1,
+                &["#project:alpha".to_string(), "type:journal".to_string()],
+            )
+            .expect("assign tags");
+
+        assert_eq!(descriptors.len(), 2);
+        let block = timeline.tree.iter().nth(1).expect("second block");
+        assert_eq!(block.tags.len(), 2);
     }
 
     #[test]
-    fn chars_dimension_accumulates_character_counts() {
-        let mut dimension = Chars::zero(());
+    fn split_block_redates_and_retags_the_second_half() {
+        let mut timeline = Timeline::default();
+        let may_1 = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let may_2 = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        timeline
+            .insert_on_date(0, may_1, "Morning run. Evening call\n")
+            .expect("insert");
+        timeline
+            .assign_block_tags(0, &["health".to_string()])
+            .expect("tag block");
 
-        let summary_a = TimelineSummary {
-            total_chars: 3,
-            ..TimelineSummary::default()
-        };
-        dimension.add_summary(&summary_a, ());
+        assert_eq!(
+            timeline.split_block(0, 0, None, None),
+            Err(SplitBlockError::InvalidOffset {
+                index: 0,
+                offset: 0
+            })
+        );
+        let version = timeline.version();
+        let descriptors = timeline
+            .split_block(0, 13, Some(may_2), Some(&["work".to_string()]))
+            .expect("split");
+        assert_eq!(descriptors.len(), 1);
+        assert_eq!(timeline.version(), version + 1);
 
-        let summary_b = TimelineSummary {
-            total_chars: 5,
-            ..TimelineSummary::default()
-        };
-        dimension.add_summary(&summary_b, ());
+        let blocks: Vec<TaggedBlock> = timeline.tree.iter().cloned().collect();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].text, "Morning run. ");
+        assert_eq!(blocks[0].date, may_1);
+        assert_eq!(blocks[1].text, "Evening call\n");
+        assert_eq!(blocks[1].date, may_2);
+        assert_eq!(blocks[1].tags, vec![descriptors[0].id]);
+        assert_eq!(timeline.content(), "Morning run. Evening call\n");
+
+        assert!(timeline.undo().expect("undo"));
+        assert!(timeline.tree.iter().all(|block| block.date == may_1));
+        assert!(timeline.redo().expect("redo"));
+        assert_eq!(
+            timeline
+                .blocks_in_range(
+                    &DateRange::new(Some(may_2), None),
+                    SensitiveContent::Included,
+                    ArchivedContent::Excluded,
+                )
+                .len(),
+            1
+        );
 
-        assert_eq!(dimension.0, 8);
+        timeline.freeze_range(DateRange::new(Some(may_2), Some(may_2)));
+        assert_eq!(
+            timeline.split_block(0, 8, Some(may_2), None),
+            Err(SplitBlockError::Frozen { date: may_2 })
+        );
     }
 
     #[test]
-    fn editable_timeline_insert_inserts_text_at_position() {
-        let base_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
-        let entries = vec![TaggedBlock {
-            date: base_date,
-            text: "abcd".to_string(),
-            tags: Vec::new(),
-        }];
+    fn assign_block_tags_rejects_invalid_index() {
+        let mut timeline = Timeline::default();
+        let error = timeline.assign_block_tags(0, &[]).unwrap_err();
+        assert_eq!(error, AssignBlockTagsError::InvalidBlock { index: 0 });
+    }
 
-        let mut tree = SumTree::from_iter(entries, ());
-        tree.apply_ops(
-            &[TextOperation::Insert {
-                position: 2,
-                text: "XY".to_string(),
-            }],
-            base_date,
-        )
-        .expect("insert");
+    #[test]
+    fn list_blocks_returns_offsets() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "alpha\n".to_string(),
+                }],
+            )
+            .expect("insert first block");
+        let position = timeline.summary().total_chars;
+        timeline
+            .apply_ops(
+                1,
+                &[TextOperation::Insert {
+                    position,
+                    text: "beta".to_string(),
+                }],
+            )
+            .expect("insert second block");
 
-        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
-        assert_eq!(content, "abXYcd");
+        let blocks = timeline.list_blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].start_offset, 0);
+        assert_eq!(blocks[0].end_offset, 6);
+        assert_eq!(blocks[1].start_offset, blocks[0].end_offset);
+        assert_eq!(blocks[1].end_offset, blocks[1].start_offset + 4);
     }
 
     #[test]
-    fn editable_timeline_delete_within_entry_removes_characters() {
-        let base_date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
-        let entries = vec![TaggedBlock {
-            date: base_date,
-            text: "abcdef".to_string(),
-            tags: Vec::new(),
-        }];
+    fn block_previews_are_capped_and_masked() {
+        let mut timeline = Timeline::default();
+        let sensitive = timeline
+            .tag_registry_mut()
+            .intern_segment(None, "sensitive");
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let block = |text: String, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        timeline.tree = SumTree::from_iter(
+            [
+                block("short\n".to_string(), Vec::new()),
+                block("secret\nplan\n".to_string(), vec![sensitive]),
+                block("é".repeat(MAX_PREVIEW_CHARS + 5), Vec::new()),
+            ],
+            (),
+        );
 
-        let mut tree = SumTree::from_iter(entries, ());
-        tree.apply_ops(
-            &[TextOperation::Delete {
-                start_position: 2,
-                end_position: 4,
-            }],
-            base_date,
-        )
-        .expect("delete");
+        let previews = timeline.list_blocks_with_previews(8, SensitiveContent::Masked);
+        assert_eq!(previews[0].preview.as_deref(), Some("short\n"));
+        assert!(!previews[0].is_truncated);
+        assert_eq!(previews[1].preview.as_deref(), Some("••••••\n•"));
+        assert!(previews[1].is_truncated);
 
-        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
-        assert_eq!(content, "abef");
+        let included = timeline.list_blocks_with_previews(8, SensitiveContent::Included);
+        assert_eq!(included[1].preview.as_deref(), Some("secret\np"));
+
+        let capped = timeline.list_blocks_with_previews(usize::MAX, SensitiveContent::Masked);
+        assert_eq!(
+            capped[2].preview.as_ref().map(|text| text.chars().count()),
+            Some(MAX_PREVIEW_CHARS)
+        );
+        assert!(capped[2].is_truncated);
+        assert_eq!(timeline.list_blocks()[2].preview, None);
+
+        let page = timeline.list_blocks_page(
+            &PageRequest::new(Some(1), Some(1)),
+            8,
+            SensitiveContent::Masked,
+        );
+        assert_eq!((page.total, page.next_offset), (3, Some(2)));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0], previews[1]);
     }
 
     #[test]
-    fn editable_timeline_delete_spanning_entries_trims_correctly() {
-        let date_a = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
-        let date_b = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+    fn insert_on_date_places_block_chronologically() {
+        let day = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        let blocks = [(day(1), "first"), (day(3), "third\n")]
+            .into_iter()
+            .map(|(date, text)| TaggedBlock::new(date, text, Vec::new()));
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            ..Timeline::default()
+        };
 
-        let entries = vec![
-            TaggedBlock {
-                date: date_a,
-                text: "12345".to_string(),
-                tags: Vec::new(),
-            },
-            TaggedBlock {
-                date: date_b,
-                text: "ABCDE".to_string(),
-                tags: Vec::new(),
-            },
-        ];
+        let position = timeline.insert_on_date(0, day(2), "second\n").unwrap();
+        assert_eq!(position, 5);
+        assert_eq!(timeline.content(), "first\nsecond\nthird\n");
+        assert_eq!(timeline.list_blocks()[1].date, "2025-03-02");
 
-        let mut tree = SumTree::from_iter(entries, ());
-        tree.apply_ops(
-            &[TextOperation::Delete {
-                start_position: 3,
-                end_position: 7,
-            }],
-            date_a,
-        )
-        .expect("delete across entries");
+        let position = timeline.insert_on_date(1, day(3), "more\n").unwrap();
+        assert_eq!(position, 19);
+        assert_eq!(timeline.content(), "first\nsecond\nthird\nmore\n");
+        assert_eq!(timeline.version(), 2);
 
-        let content: String = tree.iter().map(|entry| entry.text.as_str()).collect();
-        assert_eq!(content, "123CDE");
+        let february = NaiveDate::from_ymd_opt(2025, 2, 28).unwrap();
+        assert_eq!(
+            timeline.date_insert_op(february, "early\n"),
+            TextOperation::Insert {
+                position: 0,
+                text: "early\n".to_string(),
+            }
+        );
+
+        timeline.freeze_range(DateRange::new(None, Some(day(1))));
+        assert!(matches!(
+            timeline.insert_on_date(2, day(1), "late\n"),
+            Err(ApplyOpsError::Frozen { .. })
+        ));
     }
 
     #[test]
-    fn summary_aggregates_counts_and_dates() {
-        let date_a = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
-        let date_b = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+    fn merge_blocks_interleaves_by_date_and_applies_strategy() {
+        let day = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        let block = |date, text: &str, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        let existing = vec![
+            block(day(1), "one\n", vec![1]),
+            block(day(3), "three\n", vec![]),
+        ];
+        let incoming = || {
+            vec![
+                block(day(4), "four\n", vec![]),
+                block(day(2), "two\n", vec![]),
+                block(day(5), "one\n", vec![2]),
+            ]
+        };
+        let timeline_with = || Timeline {
+            tree: SumTree::from_iter(existing.clone(), ()),
+            ..Timeline::default()
+        };
 
-        let tag_id = 7;
-        let entry_a = TaggedBlock {
-            date: date_a,
-            text: "Hello".to_string(),
-            tags: vec![tag_id],
+        let mut timeline = timeline_with();
+        let summary = timeline.merge_blocks(incoming(), MergeStrategy::MergeTags);
+        assert_eq!(
+            summary,
+            MergeSummary {
+                version: 1,
+                added: 2,
+                duplicates: 1,
+                reimported: 0,
+                frozen: 0,
+            }
+        );
+        assert_eq!(timeline.content(), "one\ntwo\nthree\nfour\n");
+        assert_eq!(timeline.list_blocks()[0].tags, vec![1, 2]);
+
+        let mut timeline = timeline_with();
+        timeline.merge_blocks(incoming(), MergeStrategy::KeepBoth);
+        assert_eq!(timeline.content(), "one\ntwo\nthree\nfour\none\n");
+
+        let mut timeline = timeline_with();
+        timeline.freeze_range(DateRange::new(None, Some(day(2))));
+        let summary = timeline.merge_blocks(incoming(), MergeStrategy::Skip);
+        assert_eq!((summary.added, summary.frozen), (1, 1));
+        assert_eq!(timeline.content(), "one\nthree\nfour\n");
+    }
+
+    #[test]
+    fn lock_checks_see_the_text_other_changes_would_make() {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: "Intro\n- [ ] call\n".to_string(),
+                }],
+            )
+            .unwrap();
+
+        assert_eq!(timeline.task_mark(0, 0), Some(9));
+        assert_eq!(timeline.task_mark(0, 1), None);
+        assert_eq!(timeline.block_range(0), Some(0..17));
+        assert_eq!(
+            timeline.append_op(0, "more"),
+            Some(TextOperation::Insert {
+                position: 17,
+                text: "more\n".to_string(),
+            })
+        );
+        let tomorrow = timeline.clock.today().succ_opt().unwrap();
+        let next = TextOperation::Insert {
+            position: 17,
+            text: "next\n".to_string(),
         };
-        let entry_b = TaggedBlock {
-            date: date_b,
-            text: "世界".to_string(),
-            tags: Vec::new(),
+        assert_eq!(timeline.date_insert_op(tomorrow, "next\n"), next);
+        let first = timeline.tree.iter().next().unwrap();
+        let block = TaggedBlock {
+            date: tomorrow,
+            ..first.with_text("next\n".to_string())
         };
+        let plan = timeline.plan_merge(vec![block], MergeStrategy::KeepBoth);
+        assert_eq!(plan.ops, [next]);
+        assert_eq!(
+            timeline.undo_ops(),
+            vec![TextOperation::Delete {
+                start_position: 0,
+                end_position: 17,
+            }]
+        );
+        assert!(timeline.redo_ops().is_empty());
+        timeline.undo().unwrap();
+        assert!(timeline.undo_ops().is_empty());
+        assert_eq!(
+            timeline.redo_ops(),
+            vec![TextOperation::Insert {
+                position: 0,
+                text: "Intro\n- [ ] call\n".to_string(),
+            }]
+        );
+    }
 
-        let mut summary = entry_a.summary(());
-        let other_summary = entry_b.summary(());
-        sum_tree::Summary::add_summary(&mut summary, &other_summary, ());
+    #[test]
+    fn merge_blocks_puts_unterminated_notes_on_lines_of_their_own() {
+        let block = |d, text: &str| {
+            TaggedBlock::new(
+                NaiveDate::from_ymd_opt(2025, 3, d).unwrap(),
+                text,
+                Vec::new(),
+            )
+        };
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter([block(1, "one\n"), block(3, "three")], ()),
+            ..Timeline::default()
+        };
 
-        assert_eq!(summary.entry_count, 2);
-        assert_eq!(summary.total_chars, 7);
-        assert_eq!(summary.total_bytes, 5 + "世界".len());
-        assert_eq!(summary.min_date, Some(date_a));
-        assert_eq!(summary.max_date, Some(date_b));
-        assert!(summary.tags_filter.check(&tag_id));
+        let plan = timeline.plan_merge(
+            vec![block(2, "two"), block(4, "four")],
+            MergeStrategy::KeepBoth,
+        );
+        assert_eq!(
+            plan.ops,
+            [
+                TextOperation::Insert {
+                    position: 4,
+                    text: "two\n".to_string(),
+                },
+                TextOperation::Insert {
+                    position: 13,
+                    text: "\nfour\n".to_string(),
+                },
+            ]
+        );
+        timeline.apply_merge(plan);
+        assert_eq!(timeline.content(), "one\ntwo\nthree\nfour\n");
     }
 
     #[test]
-    fn apply_insert_updates_content_and_version() {
+    fn merge_blocks_skips_blocks_imported_before_whatever_the_strategy() {
+        let day = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let imported = |text: &str, tags: Vec<u32>| TaggedBlock {
+            source: Some(BlockSource::file("journal/2025-03-01.md", "note\n")),
+            ..TaggedBlock::new(day, text, tags)
+        };
+
         let mut timeline = Timeline::default();
+        timeline.merge_blocks(vec![imported("note\n", vec![1])], MergeStrategy::KeepBoth);
+        let version = timeline.version();
+        timeline
+            .apply_ops(
+                version,
+                &[TextOperation::Insert {
+                    position: 4,
+                    text: ", edited".to_string(),
+                }],
+            )
+            .unwrap();
+
+        let summary =
+            timeline.merge_blocks(vec![imported("note\n", vec![1])], MergeStrategy::KeepBoth);
+        assert_eq!((summary.added, summary.reimported), (0, 1));
+        assert_eq!(timeline.content(), "note, edited\n");
+
+        timeline.merge_blocks(vec![imported("note\n", vec![2])], MergeStrategy::MergeTags);
+        assert_eq!(timeline.content(), "note, edited\n");
+        assert_eq!(timeline.list_blocks()[0].tags, vec![1, 2]);
+    }
+
+    #[test]
+    fn frozen_range_rejects_edits_but_allows_tagging() {
+        let old_date = NaiveDate::from_ymd_opt(2023, 12, 31).unwrap();
+        let blocks = vec![TaggedBlock::new(old_date, "Historic entry", Vec::new())];
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(blocks, ()),
+            ..Timeline::default()
+        };
+        timeline.freeze_range(DateRange::new(None, NaiveDate::from_ymd_opt(2023, 12, 31)));
+
+        let delete = timeline.apply_ops(
+            0,
+            &[TextOperation::Delete {
+                start_position: 0,
+                end_position: 4,
+            }],
+        );
+        assert_eq!(
+            delete.expect_err("delete should be rejected"),
+            ApplyOpsError::Frozen {
+                start: 0,
+                end: 14,
+                date: old_date,
+            }
+        );
+
+        let insert_inside = timeline.apply_ops(
+            0,
+            &[TextOperation::Insert {
+                position: 3,
+                text: "x".to_string(),
+            }],
+        );
+        assert!(matches!(insert_inside, Err(ApplyOpsError::Frozen { .. })));
+        assert_eq!(timeline.content(), "Historic entry");
+        assert_eq!(timeline.version(), 0);
 
-        let new_version = timeline
+        timeline
             .apply_ops(
                 0,
                 &[TextOperation::Insert {
-                    position: 0,
-                    text: "Happy New Year!".to_string(),
+                    position: 14,
+                    text: " and today".to_string(),
                 }],
             )
-            .expect("insert succeeds");
-
-        assert_eq!(new_version, 1);
-        assert_eq!(timeline.version(), 1);
-        assert_eq!(timeline.content(), "Happy New Year!");
-        assert_eq!(timeline.entry_count(), 1);
-        assert_eq!(timeline.summary().min_date, timeline.summary().max_date);
-        assert!(timeline.summary().min_date.is_some());
+            .expect("append after frozen block");
+        timeline
+            .assign_block_tags(0, &["#archive".to_string()])
+            .expect("tagging frozen block");
+        assert_eq!(timeline.content(), "Historic entry and today");
     }
 
     #[test]
-    fn apply_delete_removes_text() {
+    fn apply_edit_expands_snippets_when_enabled() {
         let mut timeline = Timeline::default();
-
         timeline
-            .apply_ops(
-                0,
-                &[TextOperation::Insert {
-                    position: 0,
-                    text: "abcdef".to_string(),
-                }],
-            )
-            .expect("initial insert succeeds");
+            .save_snippet(":mtg", "Meeting notes:")
+            .expect("save snippet");
 
-        let new_version = timeline
-            .apply_ops(
-                1,
-                &[TextOperation::Delete {
-                    start_position: 2,
-                    end_position: 4,
-                }],
-            )
-            .expect("delete succeeds");
+        let typed = |position: usize, text: &str| TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        };
 
-        assert_eq!(new_version, 2);
-        assert_eq!(timeline.version(), 2);
-        assert_eq!(timeline.content(), "abef");
-        assert_eq!(timeline.summary().total_chars, 4);
+        let applied = timeline
+            .apply_edit(0, &[typed(0, "Hi :mtg ")], false)
+            .expect("apply while disabled");
+        assert!(!applied.rewritten);
+        assert_eq!(timeline.content(), "Hi :mtg ");
+
+        timeline.set_snippet_expansion(true);
+        let applied = timeline
+            .apply_edit(1, &[typed(8, ":mtg"), typed(12, "\n")], false)
+            .expect("apply with expansion");
+        assert!(applied.rewritten);
+        assert_eq!(timeline.content(), "Hi :mtg Meeting notes:\n");
+
+        let applied = timeline
+            .apply_edit(2, &[typed(23, ":mtg ")], true)
+            .expect("apply literally");
+        assert!(!applied.rewritten);
+        assert_eq!(timeline.content(), "Hi :mtg Meeting notes:\n:mtg ");
     }
 
     #[test]
-    fn apply_delete_spanning_entries_truncates_correctly() {
+    fn blocks_record_when_they_were_written_and_changed() {
+        use crate::clock::{Clock, ManualClock};
+
+        let start = DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z")
+            .unwrap()
+            .to_utc();
+        let clock = std::sync::Arc::new(ManualClock::new(start));
         let mut timeline = Timeline::default();
+        timeline.set_clock(clock.clone().into());
+        type Stamp = Option<DateTime<Utc>>;
+        let stamps = |timeline: &Timeline| -> Vec<(String, Stamp, Stamp)> {
+            timeline
+                .tree
+                .iter()
+                .map(|block| (block.text.clone(), block.created_at, block.updated_at))
+                .collect()
+        };
 
         timeline
-            .apply_ops(
-                0,
-                &[TextOperation::Insert {
-                    position: 0,
-                    text: "12345".to_string(),
-                }],
-            )
-            .expect("first insert succeeds");
-
-        let position = timeline.summary().total_chars;
+            .apply_ops(0, &[sample_insert("Morning\n")])
+            .unwrap();
+        let typed = clock.now();
+        clock.advance(chrono::Duration::hours(1));
+        // Typing into the block splits it; the halves keep their stamps.
         timeline
             .apply_ops(
                 1,
                 &[TextOperation::Insert {
-                    position,
-                    text: "ABCDE".to_string(),
+                    position: 4,
+                    text: "!".to_string(),
                 }],
             )
-            .expect("second insert succeeds");
+            .unwrap();
+        let later = clock.now();
+        assert_eq!(
+            stamps(&timeline),
+            [
+                ("Morn".to_string(), Some(typed), Some(typed)),
+                ("!".to_string(), Some(later), Some(later)),
+                ("ing\n".to_string(), Some(typed), Some(typed)),
+            ]
+        );
 
-        let new_version = timeline
+        clock.advance(chrono::Duration::hours(1));
+        let deleted = clock.now();
+        timeline
             .apply_ops(
                 2,
                 &[TextOperation::Delete {
-                    start_position: 3,
-                    end_position: 7,
+                    start_position: 5,
+                    end_position: 6,
                 }],
             )
-            .expect("delete succeeds");
+            .unwrap();
+        assert_eq!(
+            stamps(&timeline)[2],
+            ("ng\n".to_string(), Some(typed), Some(deleted))
+        );
 
-        assert_eq!(new_version, 3);
-        assert_eq!(timeline.version(), 3);
-        assert_eq!(timeline.content(), "123CDE");
-        assert_eq!(timeline.summary().total_chars, 6);
+        clock.advance(chrono::Duration::hours(1));
+        timeline
+            .assign_block_tags(0, &["#home".to_string()])
+            .unwrap();
+        let metadata = &timeline.list_blocks()[0];
+        assert_eq!(metadata.created_at, Some(typed));
+        assert_eq!(metadata.updated_at, Some(clock.now()));
+
+        // Timestamps survive a snapshot, and older snapshots load without.
+        let json = timeline.to_snapshot_json().unwrap();
+        let restored = Timeline::from_snapshot_bytes(&json).unwrap();
+        assert_eq!(stamps(&restored), stamps(&timeline));
+        let old: TaggedBlock =
+            serde_json::from_str(r#"{"date": "2024-05-01", "text": "Old"}"#).unwrap();
+        assert_eq!((old.created_at, old.updated_at), (None, None));
+
+        // Replaying the write-ahead log keeps the time each edit was made.
+        let applied = timeline
+            .apply_edit(timeline.version(), &[sample_insert("Logged\n")], true)
+            .unwrap();
+        clock.advance(chrono::Duration::days(1));
+        let mut replica = Timeline::default();
+        replica.set_clock(clock.clone().into());
+        replica.version = applied.log.version - 1;
+        replica.replay_log(&[applied.log.clone()]);
+        assert_eq!(replica.tree.first().unwrap().created_at, applied.log.at);
     }
 
     #[test]
-    fn apply_delete_out_of_bounds_returns_error() {
+    fn task_index_follows_edits_and_toggles() {
         let mut timeline = Timeline::default();
+        let insert = |timeline: &mut Timeline, position, text: &str| {
+            let version = timeline.version();
+            let op = TextOperation::Insert {
+                position,
+                text: text.to_string(),
+            };
+            timeline.apply_ops(version, &[op]).unwrap();
+        };
+        let open = |timeline: &Timeline| -> Vec<(u32, u32, bool, String)> {
+            timeline
+                .tasks(&TaskFilter::default(), SensitiveContent::Included)
+                .into_iter()
+                .map(|task| (task.block_id, task.task_index, task.done, task.text))
+                .collect()
+        };
+
+        insert(&mut timeline, 0, "- [ ] milk\n- [x] eggs\nnotes\n");
+        assert_eq!(
+            open(&timeline),
+            [
+                (0, 0, false, "milk".to_string()),
+                (0, 1, true, "eggs".to_string()),
+            ]
+        );
 
+        // A task typed in pieces is still one task, in its first block.
+        insert(&mut timeline, 28, "- [ ");
+        insert(&mut timeline, 32, "] bread\n");
+        assert_eq!(open(&timeline)[2], (1, 0, false, "bread".to_string()));
+        // Editing a task's text or breaking its checkbox rescans its line.
+        insert(&mut timeline, 10, " and oats");
+        assert_eq!(open(&timeline)[0].3, "milk and oats");
+        let version = timeline.version();
         timeline
             .apply_ops(
-                0,
-                &[TextOperation::Insert {
-                    position: 0,
-                    text: "short".to_string(),
+                version,
+                &[TextOperation::Delete {
+                    start_position: 0,
+                    end_position: 1,
                 }],
             )
-            .expect("insert succeeds");
+            .unwrap();
+        assert_eq!(open(&timeline).len(), 2);
+        assert_eq!(timeline.task_index, TaskIndex::build(&timeline.tree));
+
+        let eggs = &open(&timeline)[0];
+        let eggs = timeline
+            .toggle_task(eggs.0 as usize, eggs.1 as usize)
+            .unwrap();
+        assert_eq!((eggs.done, eggs.text.as_str()), (false, "eggs"));
+        assert!(timeline.content().contains("- [ ] eggs"));
+        let bread = &open(&timeline)[1];
+        let bread = timeline
+            .toggle_task(bread.0 as usize, bread.1 as usize)
+            .unwrap();
+        assert!(bread.done);
+        assert!(timeline.content().contains("- [x] bread"));
+        assert_eq!(timeline.task_index, TaskIndex::build(&timeline.tree));
+        assert_eq!(
+            timeline.toggle_task(0, 5),
+            Err(ToggleTaskError::InvalidTask { block: 0, task: 5 })
+        );
+        let done = TaskFilter {
+            done: Some(true),
+            ..TaskFilter::default()
+        };
+        assert_eq!(timeline.tasks(&done, SensitiveContent::Included).len(), 1);
+    }
 
-        let result = timeline.apply_ops(
-            1,
-            &[TextOperation::Delete {
-                start_position: 0,
-                end_position: 10,
-            }],
+    #[test]
+    fn toggling_a_task_can_be_undone() {
+        let day = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter([TaggedBlock::new(day, "- [ ] milk\n", vec![3])], ()),
+            ..Timeline::default()
+        };
+
+        assert!(timeline.toggle_task(0, 0).unwrap().done);
+        assert_eq!(timeline.content(), "- [x] milk\n");
+        assert!(timeline.undo().unwrap());
+        assert_eq!(timeline.content(), "- [ ] milk\n");
+        assert!(timeline.list_blocks().iter().all(|block| block.tags == [3]));
+        let open = timeline.tasks(&TaskFilter::default(), SensitiveContent::Included);
+        assert!(!open[0].done);
+        assert!(timeline.redo().unwrap());
+        assert_eq!(timeline.content(), "- [x] milk\n");
+    }
+
+    #[test]
+    fn due_tasks_come_soonest_first() {
+        let mut timeline = Timeline::default();
+        let now = DateTime::parse_from_rfc3339("2024-06-10T12:00:00Z").unwrap();
+        timeline.set_clock(crate::clock::SharedClock::new(
+            crate::clock::ManualClock::new(now.to_utc()),
+        ));
+        let text = "- [ ] rent @due(2024-06-01)\n\
+                    - [x] taxes @due(2024-04-15)\n\
+                    - [ ] dentist @due(2024-07-02)\n\
+                    - [ ] call Sam\n\
+                    - [ ] milk @due(2024-05-20)\n\
+                    ship it @due(2024-05-01)\n";
+        timeline.apply_ops(0, &[sample_insert(text)]).unwrap();
+        let texts =
+            |tasks: Vec<Task>| -> Vec<String> { tasks.into_iter().map(|task| task.text).collect() };
+
+        assert_eq!(
+            texts(timeline.overdue_tasks(SensitiveContent::Included)),
+            ["milk @due(2024-05-20)", "rent @due(2024-06-01)"]
+        );
+        let july = NaiveDate::from_ymd_opt(2024, 7, 3).unwrap();
+        assert_eq!(
+            timeline
+                .tasks_due_before(july, SensitiveContent::Included)
+                .len(),
+            3
         );
+        // The block's due date is its soonest open task's, not the first
+        // @due in its text.
+        let blocks = timeline.list_blocks();
+        assert_eq!(blocks[0].due, NaiveDate::from_ymd_opt(2024, 5, 20));
 
+        timeline.toggle_task(0, 4).unwrap();
+        assert!(timeline.content().contains("- [x] milk"));
         assert_eq!(
-            result.expect_err("delete should fail"),
-            ApplyOpsError::InvalidRange { start: 0, end: 10 }
+            timeline.list_blocks()[0].due,
+            NaiveDate::from_ymd_opt(2024, 6, 1)
         );
     }
 
     #[test]
-    fn save_to_path_writes_snapshot() {
-        let mut timeline = Timeline::default();
-        timeline
-            .apply_ops(0, &[sample_insert("Snapshot test")])
-            .expect("apply insert");
-
-        let dir = tempdir().expect("tempdir");
-        let path = dir.path().join("timeline.json");
-        timeline.save_to_path(&path).expect("save timeline");
+    fn writing_stats_add_up_per_period() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let block = |d, text: &str, archived| TaggedBlock {
+            archived,
+            ..TaggedBlock::new(day(d), text, Vec::new())
+        };
+        // May 12th is a Sunday; the 13th starts a new week.
+        let timeline = Timeline {
+            tree: SumTree::from_iter(
+                [
+                    block(12, "one two\n", false),
+                    block(13, "three\n", false),
+                    block(12, "four\n", false),
+                    block(14, "hidden words\n", true),
+                    block(14, "five six seven\n", false),
+                ],
+                (),
+            ),
+            ..Timeline::default()
+        };
+        let stats = |range: &DateRange, period| -> Vec<(NaiveDate, usize, usize, usize, usize)> {
+            timeline
+                .writing_stats(range, period)
+                .into_iter()
+                .map(|stats| {
+                    (
+                        stats.start,
+                        stats.words,
+                        stats.chars,
+                        stats.blocks,
+                        stats.active_days,
+                    )
+                })
+                .collect()
+        };
 
-        let contents = std::fs::read_to_string(&path).expect("read snapshot");
-        let snapshot: TimelineSnapshot = from_str(&contents).expect("parse snapshot");
+        assert_eq!(
+            stats(&DateRange::default(), StatsPeriod::Day),
+            [
+                (day(12), 3, 13, 2, 1),
+                (day(13), 1, 6, 1, 1),
+                (day(14), 3, 15, 1, 1),
+            ]
+        );
+        assert_eq!(
+            stats(&DateRange::default(), StatsPeriod::Week),
+            [(day(6), 3, 13, 2, 1), (day(13), 4, 21, 2, 2)]
+        );
+        assert_eq!(
+            stats(&DateRange::new(Some(day(13)), None), StatsPeriod::Month),
+            [(day(1), 4, 21, 2, 2)]
+        );
+    }
 
-        assert_eq!(snapshot.version, timeline.version());
-        assert_eq!(snapshot.blocks.len(), 1);
-        assert_eq!(snapshot.blocks[0].text, "Snapshot test");
-        assert!(snapshot.tag_registry.is_none());
+    #[test]
+    fn streaks_count_days_with_journal_entries() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let mut registry = TagRegistry::new();
+        let journal = registry.intern_colon_path("type:journal").unwrap();
+        let morning = registry.intern_colon_path("type:journal:morning").unwrap();
+        let work = registry.intern_colon_path("work").unwrap();
+        let block = |d, text: &str, tags: Vec<u32>, archived| TaggedBlock {
+            archived,
+            ..TaggedBlock::new(day(d), text, tags)
+        };
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(
+                [
+                    block(1, "one\n", vec![journal], false),
+                    block(2, "two\n", vec![morning], false),
+                    block(3, "three\n", vec![journal], false),
+                    // Untagged blocks, like daily notes, count.
+                    block(4, "# 2024-05-04\n", Vec::new(), false),
+                    block(5, "five\n", vec![journal], false),
+                    // Empty, archived and otherwise tagged entries don't.
+                    block(6, " \n", vec![journal], false),
+                    block(6, "six\n", vec![journal], true),
+                    block(7, "seven\n", vec![work], false),
+                    block(8, "eight\n", vec![journal, work], false),
+                    block(9, "nine\n", Vec::new(), false),
+                ],
+                (),
+            ),
+            tag_registry: registry,
+            ..Timeline::default()
+        };
+        let now = DateTime::parse_from_rfc3339("2024-05-09T12:00:00Z").unwrap();
+        timeline.set_clock(crate::clock::SharedClock::new(
+            crate::clock::ManualClock::new(now.to_utc()),
+        ));
+
+        let streak = |start, end, days| crate::streaks::Streak {
+            start: day(start),
+            end: day(end),
+            days,
+        };
+        assert_eq!(
+            timeline.streaks(),
+            Streaks {
+                current: Some(streak(8, 9, 2)),
+                longest: Some(streak(1, 5, 5)),
+            }
+        );
+        assert_eq!(Timeline::default().streaks(), Streaks::default());
     }
 
     #[test]
-    fn save_to_path_includes_tag_hierarchy() {
-        let mut timeline = Timeline::default();
-        let project_id = timeline.tag_registry_mut().intern_segment(None, "project");
-        let _child_id = timeline
-            .tag_registry_mut()
-            .intern_segment(Some(project_id), "sightline");
+    fn appended_links_stay_in_their_block() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let block = |date, text: &str| TaggedBlock::new(date, text, Vec::new());
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter([block(day(1), "Trip"), block(day(2), "Home\n")], ()),
+            ..Timeline::default()
+        };
+        timeline.day_index = DayIndex::build(&timeline.tree);
 
-        let dir = tempdir().expect("tempdir");
-        let path = dir.path().join("timeline.json");
-        timeline.save_to_path(&path).expect("save timeline");
+        let link = attachments::link("map.png", "map.png");
+        assert_eq!(timeline.append_to_block(0, &link), Ok(1));
+        assert_eq!(
+            timeline.content(),
+            "Trip\n![map.png](<assets/map.png>)\nHome\n"
+        );
+        let blocks = timeline.list_blocks();
+        assert_eq!(blocks[0].attachments, ["map.png"]);
+        assert_eq!(blocks[0].date, "2024-05-01");
+        assert!(blocks[1].attachments.is_empty());
+        assert_eq!(timeline.day_index, DayIndex::build(&timeline.tree));
+        assert_eq!(
+            timeline.append_to_block(2, &link),
+            Err(AppendToBlockError::InvalidBlock { index: 2 })
+        );
 
-        let contents = std::fs::read_to_string(&path).expect("read snapshot");
-        let snapshot: TimelineSnapshot = from_str(&contents).expect("parse snapshot");
+        assert_eq!(timeline.undo(), Ok(true));
+        assert_eq!(timeline.content(), "TripHome\n");
+    }
 
-        let tags = match snapshot.tag_registry {
-            Some(TagRegistrySnapshot::Hierarchical(tags)) => tags,
-            other => panic!("unexpected tag registry format: {other:?}"),
+    #[test]
+    fn archived_blocks_leave_the_document_but_keep_their_place() {
+        let day = |n| NaiveDate::from_ymd_opt(2024, 5, n).unwrap();
+        let block = |n, text: &str| TaggedBlock::new(day(n), text, Vec::new());
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(
+                [block(1, "one\n"), block(2, "two\n"), block(3, "three\n")],
+                (),
+            ),
+            ..Timeline::default()
+        };
+        timeline.day_index = DayIndex::build(&timeline.tree);
+        let everything = |timeline: &Timeline| {
+            timeline.render(SensitiveContent::Included, ArchivedContent::Included)
         };
 
-        assert_eq!(tags.len(), 2);
-        let project = tags
+        assert_eq!(timeline.archive_block(1, true), Ok(1));
+        assert_eq!(timeline.archive_block(1, true), Ok(1));
+        assert_eq!(
+            timeline.archive_block(9, true),
+            Err(ArchiveBlockError::InvalidBlock { index: 9 })
+        );
+        assert_eq!(timeline.content(), "one\nthree\n");
+        assert_eq!(everything(&timeline), "one\ntwo\nthree\n");
+        assert_eq!(timeline.log_for_date(day(2)), None);
+        assert_eq!(timeline.day_index, DayIndex::build(&timeline.tree));
+        assert!(!timeline.days(&DateRange::default()).contains_key(&day(2)));
+        assert!(timeline
+            .search_text("two", &DateRange::default(), ArchivedContent::Excluded)
+            .is_empty());
+        assert_eq!(
+            timeline
+                .search_text("two", &DateRange::default(), ArchivedContent::Included)
+                .len(),
+            1
+        );
+        let offsets: Vec<(u32, u32, bool)> = timeline
+            .list_blocks()
             .iter()
-            .find(|tag| tag.name == "project")
-            .expect("project tag present");
-        assert!(project.parent_id.is_none());
+            .map(|block| (block.start_offset, block.end_offset, block.archived))
+            .collect();
+        assert_eq!(offsets, [(0, 4, false), (4, 4, true), (4, 10, false)]);
 
-        let sightline = tags
+        // Edits address the document without archived blocks, and deleting
+        // across one leaves it alone.
+        let ops = timeline
+            .ops_in_chars(
+                &[TextOperation::Delete {
+                    start_position: 2,
+                    end_position: 6,
+                }],
+                OffsetUnit::Chars,
+            )
+            .unwrap();
+        timeline.apply_ops(timeline.version(), &ops).unwrap();
+        assert_eq!(timeline.content(), "onree\n");
+        assert_eq!(everything(&timeline), "ontwo\nree\n");
+        let ops = timeline
+            .ops_in_chars(
+                &[TextOperation::Insert {
+                    position: 2,
+                    text: "!".to_string(),
+                }],
+                OffsetUnit::Utf16,
+            )
+            .unwrap();
+        timeline.apply_ops(timeline.version(), &ops).unwrap();
+        assert_eq!(everything(&timeline), "ontwo\n!ree\n");
+
+        let archived = timeline
+            .list_blocks()
             .iter()
-            .find(|tag| tag.name == "sightline")
-            .expect("sightline tag present");
-        assert_eq!(sightline.parent_id, Some(project.id));
+            .position(|block| block.archived)
+            .unwrap();
+        timeline.archive_block(archived, false).unwrap();
+        assert_eq!(timeline.content(), "ontwo\n!ree\n");
     }
 
     #[test]
-    fn load_from_path_restores_state() {
+    fn archived_blocks_take_no_room_in_document_coordinates() {
+        let day = |n| NaiveDate::from_ymd_opt(2024, 5, n).unwrap();
+        let block = |n, text: &str, archived| TaggedBlock {
+            archived,
+            ..TaggedBlock::new(day(n), text, Vec::new())
+        };
+        let mut timeline = Timeline {
+            tree: SumTree::from_iter(
+                [
+                    block(1, "one\n", false),
+                    block(2, "two\nlines\n", true),
+                    block(3, "three\n", false),
+                ],
+                (),
+            ),
+            ..Timeline::default()
+        };
+        assert_eq!(timeline.content(), "one\nthree\n");
+
+        assert_eq!(timeline.offset_to_point(4), Some(Point::new(1, 0)));
+        assert_eq!(timeline.offset_to_point(10), Some(Point::new(2, 0)));
+        assert_eq!(timeline.offset_to_point(11), None);
+        assert_eq!(timeline.point_to_offset(Point::new(1, 2)), Some(6));
+        assert_eq!(timeline.point_to_offset(Point::new(3, 0)), None);
+
+        assert_eq!(timeline.timeline_offset(4), Some(14));
+        assert_eq!(timeline.timeline_offset(11), None);
+        assert_eq!(timeline.document_offset(6), 4);
+        assert_eq!(timeline.document_offset(16), 6);
+
+        let days = timeline.days(&DateRange::default());
+        assert_eq!((days[&day(2)].start, days[&day(2)].end), (4, 4));
+        assert_eq!((days[&day(3)].start, days[&day(3)].end), (4, 10));
+
+        assert_eq!(
+            timeline.split_block(1, 2, None, None),
+            Err(SplitBlockError::InvalidBlock { index: 1 })
+        );
+        assert_eq!(
+            timeline.insert_on_date(timeline.version(), day(3), "four\n"),
+            Ok(10)
+        );
+        assert_eq!(timeline.content(), "one\nthree\nfour\n");
+    }
+
+    #[test]
+    fn edits_are_dated_by_the_timeline_clock() {
+        use crate::clock::ManualClock;
+
+        let late = DateTime::parse_from_rfc3339("2024-05-01T23:59:00Z").unwrap();
+        let clock = std::sync::Arc::new(ManualClock::new(late.to_utc()));
         let mut timeline = Timeline::default();
+        timeline.set_clock(clock.clone().into());
+
         timeline
-            .apply_ops(0, &[sample_insert("Restored state")])
+            .apply_ops(0, &[sample_insert("Before midnight\n")])
+            .expect("apply insert");
+        clock.advance(chrono::Duration::minutes(2));
+        timeline
+            .apply_ops(1, &[sample_insert("After\n")])
             .expect("apply insert");
 
-        let dir = tempdir().expect("tempdir");
-        let path = dir.path().join("timeline.json");
-        timeline.save_to_path(&path).expect("save timeline");
+        let may_1 = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(
+            timeline.log_for_date(may_1).as_deref(),
+            Some("Before midnight\n")
+        );
+        assert_eq!(
+            timeline.log_for_date(may_1.succ_opt().unwrap()).as_deref(),
+            Some("After\n")
+        );
+    }
 
-        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+    #[test]
+    fn day_index_keeps_up_with_edits() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let insert = |position, text: &str| TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        };
+        let mut timeline = Timeline::default();
+        let check = |timeline: &Timeline| {
+            assert_eq!(timeline.day_index, DayIndex::build(&timeline.tree));
+        };
 
-        assert_eq!(loaded.version(), timeline.version());
-        assert_eq!(loaded.content(), timeline.content());
-        assert_eq!(loaded.entry_count(), timeline.entry_count());
+        for (d, text) in [(1, "First day\n"), (2, "Second day\n"), (3, "Third\n")] {
+            let end = timeline.summary().total_chars;
+            timeline
+                .apply_edit_on(
+                    timeline.version,
+                    &[insert(end, text)],
+                    true,
+                    day(d),
+                    Utc::now(),
+                )
+                .unwrap();
+            check(&timeline);
+        }
+        // Into the middle of day 1, then across days 1 and 2.
+        timeline
+            .apply_edit_on(
+                timeline.version,
+                &[insert(5, " busy")],
+                true,
+                day(4),
+                Utc::now(),
+            )
+            .unwrap();
+        check(&timeline);
+        let delete = TextOperation::Delete {
+            start_position: 8,
+            end_position: 20,
+        };
+        timeline
+            .apply_edit_on(timeline.version, &[delete], true, day(4), Utc::now())
+            .unwrap();
+        check(&timeline);
+
+        timeline
+            .insert_on_date(timeline.version, day(2), "Late note")
+            .unwrap();
+        check(&timeline);
+        timeline.split_block(0, 2, Some(day(5)), None).unwrap();
+        check(&timeline);
+        timeline
+            .assign_block_tags(1, &["work".to_string()])
+            .unwrap();
+        check(&timeline);
+        while timeline.undo().unwrap() {
+            check(&timeline);
+        }
+        while timeline.redo().unwrap() {
+            check(&timeline);
+        }
+        timeline.compact();
+        check(&timeline);
+
+        let json = timeline.to_snapshot_json().unwrap();
+        let loaded = Timeline::from_snapshot_bytes(&json).unwrap();
+        assert_eq!(loaded.day_index, timeline.day_index);
+
+        let days = timeline.days(&DateRange::new(Some(day(2)), Some(day(3))));
+        assert_eq!(days.keys().copied().collect::<Vec<_>>(), [day(2), day(3)]);
+        assert_eq!(timeline.log_for_date(day(3)).as_deref(), Some("Third\n"));
+    }
+
+    #[test]
+    fn daily_notes_are_created_once_per_day() {
+        use crate::clock::ManualClock;
+
+        let morning = DateTime::parse_from_rfc3339("2024-05-02T08:00:00Z").unwrap();
+        let clock = std::sync::Arc::new(ManualClock::new(morning.to_utc()));
+        let mut timeline = Timeline::default();
+        timeline.set_clock(clock.clone().into());
+        assert_eq!(timeline.create_daily_note(), Ok(None));
+
+        timeline.set_daily_note(Some("# {date}".to_string()));
+        timeline
+            .insert_on_date(0, NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), "Old\n")
+            .expect("insert");
+        assert_eq!(timeline.create_daily_note(), Ok(Some(4)));
+        assert_eq!(timeline.create_daily_note(), Ok(None));
+        assert_eq!(timeline.content(), "Old\n# 2024-05-02\n");
+
+        let dates = serde_json::json!({
+            "dates": [{"name": "Dad's birthday", "kind": "birthday", "month": 5, "day": 3}]
+        });
+        timeline
+            .set_important_dates(serde_json::from_value(dates).unwrap())
+            .expect("valid dates");
+        timeline.set_daily_note(Some("# {date}\nRemember: {important_dates}".to_string()));
+        clock.advance(chrono::Duration::days(1));
+        assert_eq!(timeline.create_daily_note(), Ok(Some(17)));
+        assert_eq!(
+            timeline.log_for_date(NaiveDate::from_ymd_opt(2024, 5, 3).unwrap()),
+            Some("# 2024-05-03\nRemember: Dad's birthday\n".to_string())
+        );
+        clock.advance(chrono::Duration::days(1));
+        timeline.create_daily_note().expect("create note");
+        assert_eq!(
+            timeline.log_for_date(NaiveDate::from_ymd_opt(2024, 5, 4).unwrap()),
+            Some("# 2024-05-04\n".to_string())
+        );
+
+        timeline.set_daily_note(Some(
+            "# {{weekday}} {{date}}\n{{important_dates}}".to_string(),
+        ));
+        clock.advance(chrono::Duration::days(1));
+        timeline.create_daily_note().expect("create note");
+        assert_eq!(
+            timeline.log_for_date(NaiveDate::from_ymd_opt(2024, 5, 5).unwrap()),
+            Some("# Sunday 2024-05-05\n".to_string())
+        );
     }
 
     #[test]
-    fn load_missing_file_returns_default() {
-        let dir = tempdir().expect("tempdir");
-        let path = dir.path().join("timeline.json");
+    fn edits_update_day_properties_for_touched_days() {
+        let mut timeline = Timeline::default();
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap();
+        timeline.set_clock(crate::clock::SharedClock::new(
+            crate::clock::ManualClock::new(now.to_utc()),
+        ));
+        let today = now.date_naive();
+        timeline
+            .apply_ops(0, &[sample_insert("sleep: 7h\n")])
+            .expect("apply insert");
+        assert_eq!(
+            timeline.day_properties(&DateRange::default(), SensitiveContent::Included)[&today]
+                .get("sleep_hours"),
+            Some(&7.0)
+        );
 
-        let loaded = Timeline::load_from_path(&path).expect("load timeline");
+        timeline
+            .apply_ops(
+                1,
+                &[TextOperation::Delete {
+                    start_position: 7,
+                    end_position: 8,
+                }],
+            )
+            .expect("apply delete");
+        timeline
+            .apply_ops(
+                2,
+                &[TextOperation::Insert {
+                    position: 7,
+                    text: "8.5".to_string(),
+                }],
+            )
+            .expect("apply insert");
+        assert_eq!(
+            timeline.day_properties(&DateRange::default(), SensitiveContent::Included)[&today]
+                .get("sleep_hours"),
+            Some(&8.5)
+        );
 
-        assert_eq!(loaded.version(), 0);
-        assert_eq!(loaded.entry_count(), 0);
+        let length = timeline.content().chars().count();
+        timeline
+            .apply_ops(
+                3,
+                &[TextOperation::Delete {
+                    start_position: 0,
+                    end_position: length,
+                }],
+            )
+            .expect("apply delete");
+        assert!(timeline
+            .day_properties(&DateRange::default(), SensitiveContent::Included)
+            .is_empty());
     }
 
     #[test]
-    fn load_legacy_flat_tag_registry() {
+    fn custom_metric_patterns_persist_and_reparse() {
+        let mut timeline = Timeline::default();
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        timeline.tree = SumTree::from_iter(
+            [TaggedBlock::new(date, "mood 4/5\nsleep: 6h\n", Vec::new())],
+            (),
+        );
+        assert_eq!(
+            timeline.reparse_metrics(
+                &DateRange::new(Some(date), Some(date)),
+                SensitiveContent::Included,
+            )[&date]
+                .get("sleep_hours"),
+            Some(&6.0)
+        );
+
+        timeline
+            .set_metric_patterns(vec![MetricPattern {
+                name: "mood".to_string(),
+                pattern: r"mood (\d)/5".to_string(),
+                aggregate: Default::default(),
+            }])
+            .expect("set patterns");
+        let properties = timeline.day_properties(&DateRange::default(), SensitiveContent::Included);
+        assert_eq!(properties[&date].get("mood"), Some(&4.0));
+        assert_eq!(properties[&date].get("sleep_hours"), None);
+
         let dir = tempdir().expect("tempdir");
         let path = dir.path().join("timeline.json");
-        let legacy_snapshot = serde_json::json!({
-            "version": 1,
-            "blocks": [],
-            "tag_registry": {
-                "5": "project:sightline"
-            }
-        });
-        std::fs::write(&path, legacy_snapshot.to_string()).expect("write legacy snapshot");
-
+        timeline.save_to_path(&path).expect("save timeline");
         let loaded = Timeline::load_from_path(&path).expect("load timeline");
-        assert_eq!(loaded.version(), 1);
-        assert_eq!(loaded.entry_count(), 0);
+        assert_eq!(loaded.metric_patterns(), timeline.metric_patterns());
         assert_eq!(
-            loaded.tag_registry().full_name(5).as_deref(),
-            Some("project:sightline")
+            loaded.day_properties(&DateRange::default(), SensitiveContent::Included),
+            properties
         );
     }
 
     #[test]
-    fn search_prefix_returns_matching_blocks() {
-        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
-        let mut registry = TagRegistry::new();
-        let project = registry.intern_segment(None, "project");
-        let sightline = registry.intern_segment(Some(project), "sightline");
-        let home = registry.intern_segment(Some(project), "home");
-        let journal = registry
-            .intern_path(["type", "journal"])
-            .expect("journal tag");
-
-        let blocks = vec![
-            TaggedBlock {
-                date,
-                text: "Sightline plan".to_string(),
-                tags: vec![sightline],
-            },
-            TaggedBlock {
-                date,
-                text: "Home renovation".to_string(),
-                tags: vec![home],
-            },
-            TaggedBlock {
-                date,
-                text: "Daily reflection".to_string(),
-                tags: vec![journal],
-            },
-        ];
-
-        let timeline = Timeline {
-            tree: SumTree::from_iter(blocks, ()),
-            version: 0,
-            tag_registry: registry,
-        };
+    fn day_properties_leave_out_sensitive_blocks_when_masked() {
+        let mut timeline = Timeline::default();
+        let sensitive = timeline
+            .tag_registry_mut()
+            .intern_segment(None, "sensitive");
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let block = |text: &str, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        timeline.tree = SumTree::from_iter(
+            [
+                block("sleep: 7h\n", Vec::new()),
+                block("weight: 80kg\n", vec![sensitive]),
+            ],
+            (),
+        );
 
-        assert_eq!(timeline.search_prefix("#project"), vec![0, 1]);
+        let range = DateRange::default();
+        let included = timeline.reparse_metrics(&range, SensitiveContent::Included);
+        assert_eq!(included[&date].get("weight_kg"), Some(&80.0));
+        let masked = timeline.day_properties(&range, SensitiveContent::Masked);
+        assert_eq!(masked[&date].get("sleep_hours"), Some(&7.0));
+        assert_eq!(masked[&date].get("weight_kg"), None);
     }
 
     #[test]
-    fn search_infix_finds_partial_matches() {
-        let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
-        let mut registry = TagRegistry::new();
-        let project = registry.intern_segment(None, "project");
-        let sightline = registry.intern_segment(Some(project), "sightline");
-        let research = registry.intern_segment(Some(project), "research");
-
+    fn every_snapshot_encoding_loads() {
         let blocks = vec![
-            TaggedBlock {
-                date,
-                text: "Sightline planning".to_string(),
-                tags: vec![sightline],
-            },
-            TaggedBlock {
-                date,
-                text: "Research notes".to_string(),
-                tags: vec![research],
-            },
+            TaggedBlock::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                "first\n",
+                vec![1],
+            ),
+            TaggedBlock::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                "second\nline\n",
+                Vec::new(),
+            ),
         ];
+        let tags = vec![Tag {
+            id: 1,
+            name: "work".to_string(),
+            parent_id: None,
+            color: None,
+        }];
 
-        let timeline = Timeline {
-            tree: SumTree::from_iter(blocks, ()),
-            version: 0,
-            tag_registry: registry,
-        };
+        for encoding in [
+            SnapshotEncoding::Json,
+            SnapshotEncoding::Cbor,
+            SnapshotEncoding::Jsonl,
+        ] {
+            let mut bytes = Vec::new();
+            write_snapshot(&mut bytes, encoding, 3, &blocks, &tags, |_| true)
+                .expect("write snapshot");
+            let loaded = Timeline::from_snapshot_bytes(&bytes).expect("load snapshot");
+            assert_eq!(loaded.tree.items(()), blocks, "{encoding:?}");
+            assert_eq!(loaded.version(), 3, "{encoding:?}");
+            assert_eq!(loaded.tag_registry().full_name(1).as_deref(), Some("work"));
+
+            let mut bytes = Vec::new();
+            let written = write_snapshot(&mut bytes, encoding, 3, &blocks, &tags, |block| {
+                block.text.starts_with("second")
+            })
+            .expect("write filtered snapshot");
+            assert_eq!(written, 1, "{encoding:?}");
+            let loaded = Timeline::from_snapshot_bytes(&bytes).expect("load snapshot");
+            assert_eq!(loaded.tree.items(()), blocks[1..], "{encoding:?}");
+        }
 
-        assert_eq!(timeline.search_infix("sight"), vec![0]);
-        assert_eq!(timeline.search_infix("search"), vec![1]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("timeline.db");
+        fs::write(&path, "old").unwrap();
+        let written =
+            write_snapshot_file(&path, SnapshotEncoding::Sqlite, 3, &blocks, &tags, |_| true)
+                .expect("write sqlite snapshot");
+        assert_eq!(written, 2);
+        let loaded = Timeline::load_from_path(&path).expect("load sqlite snapshot");
+        assert_eq!(loaded.tree.items(()), blocks);
+        assert_eq!(loaded.version(), 3);
+        assert_eq!(loaded.tag_registry().full_name(1).as_deref(), Some("work"));
+        assert!(Timeline::from_snapshot_bytes(&fs::read(&path).unwrap()).is_err());
+        assert!(write_snapshot(
+            Vec::new(),
+            SnapshotEncoding::Sqlite,
+            3,
+            &blocks,
+            &tags,
+            |_| true
+        )
+        .is_err());
     }
 
     #[test]
-    fn autocomplete_tags_returns_suggestions() {
-        let mut registry = TagRegistry::new();
-        let project = registry.intern_segment(None, "project");
-        let _sightline = registry.intern_segment(Some(project), "sightline");
-        let _strategy = registry.intern_segment(Some(project), "strategy");
-        let _journal = registry
-            .intern_path(["type", "journal"])
-            .expect("journal tag");
+    fn empty_snapshots_still_list_their_blocks() {
+        let json = Timeline::default()
+            .to_snapshot_json()
+            .expect("snapshot json");
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["blocks"], serde_json::json!([]));
+    }
 
-        let timeline = Timeline {
-            tree: SumTree::new(()),
-            version: 0,
-            tag_registry: registry,
+    #[test]
+    fn jsonl_exports_stream_one_block_per_line() {
+        let mut timeline = Timeline::default();
+        let sensitive = timeline
+            .tag_registry_mut()
+            .intern_segment(None, "sensitive");
+        let work = timeline.tag_registry_mut().intern_segment(None, "work");
+        let block = |text: &str, tags: Vec<u32>| {
+            TaggedBlock::new(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), text, tags)
         };
-
-        let results = timeline.autocomplete_tags("#pro");
-        let names: Vec<_> = results
-            .iter()
-            .map(|suggestion| suggestion.name.as_str())
+        timeline.tree = SumTree::from_iter(
+            [
+                block("standup notes\n", vec![work]),
+                block("diary\n", vec![sensitive]),
+                block("groceries\n", Vec::new()),
+            ],
+            (),
+        );
+        timeline.set_offline_mode(true);
+
+        let mut bytes = Vec::new();
+        let written = timeline
+            .write_jsonl(&mut bytes, SensitiveContent::Masked)
+            .expect("write jsonl");
+        assert_eq!(written, 2);
+        let lines: Vec<serde_json::Value> = bytes
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
             .collect();
-        assert!(names.contains(&"#project"));
-        assert!(names.contains(&"#project:sightline"));
-        assert!(names.contains(&"#project:strategy"));
-        assert!(results.iter().all(|suggestion| suggestion.color.is_some()));
-
-        let type_results = timeline.autocomplete_tags("#type:j");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["format"], JSONL_SNAPSHOT_FORMAT);
+        assert!(lines[0]["tag_registry"].is_array());
+        assert_eq!(lines[1]["text"], "standup notes\n");
+        assert_eq!(lines[2]["text"], "groceries\n");
+
+        let loaded = Timeline::from_snapshot_bytes(&bytes).expect("load jsonl");
+        assert_eq!(loaded.tree.iter().count(), 2);
+        assert!(loaded.offline_mode());
         assert_eq!(
-            type_results
-                .iter()
-                .map(|suggestion| suggestion.name.as_str())
-                .collect::<Vec<_>>(),
-            vec!["#type:journal"]
+            loaded.tag_registry().full_name(work).as_deref(),
+            Some("work")
         );
-        assert!(type_results
-            .iter()
-            .all(|suggestion| suggestion.color.is_some()));
+        let masked = bytes;
+
+        let mut bytes = Vec::new();
+        let written = timeline
+            .write_jsonl(&mut bytes, SensitiveContent::Included)
+            .expect("write jsonl");
+        assert_eq!(written, 3);
+        let loaded = Timeline::from_snapshot_bytes(&bytes).expect("load jsonl");
+        assert_eq!(loaded.tree.items(()), timeline.tree.items(()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.jsonl");
+        fs::write(&path, "old").unwrap();
+        let written = timeline
+            .export_jsonl(&path, SensitiveContent::Masked)
+            .expect("export jsonl");
+        assert_eq!(written, 2);
+        assert_eq!(fs::read(&path).unwrap(), masked);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
     }
 
     #[test]
-    fn intern_tag_creates_and_reuses_entries() {
+    fn search_text_skips_sensitive_blocks() {
         let mut timeline = Timeline::default();
+        let sensitive = timeline
+            .tag_registry_mut()
+            .intern_segment(None, "sensitive");
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        timeline.tree = SumTree::from_iter(
+            [
+                TaggedBlock::new(date, "intro\n  Garden plans for June\n", Vec::new()),
+                TaggedBlock::new(date, "garden secret\n", vec![sensitive]),
+            ],
+            (),
+        );
 
-        let first = timeline
-            .intern_tag("#project:new")
-            .expect("create project tag");
-        assert_eq!(first.name, "#project:new");
-        assert_eq!(first.id, 1);
-        assert!(!first.color.is_empty());
-
-        let reused = timeline
-            .intern_tag("project:new")
-            .expect("reuse existing tag");
-        assert_eq!(reused.id, first.id);
-        assert_eq!(reused.name, first.name);
-
-        let other = timeline
-            .intern_tag("type:journal")
-            .expect("create second tag");
-        assert_ne!(other.id, first.id);
-        assert!(other.name.starts_with("#type"));
+        assert_eq!(
+            timeline.search_text("GARDEN", &DateRange::default(), ArchivedContent::Excluded),
+            vec![TextMatch {
+                block_index: 0,
+                date,
+                excerpt: "Garden plans for June".to_string(),
+                snippet: SearchSnippet {
+                    text: "Garden plans for June".to_string(),
+                    matches: vec![crate::search::MatchRange { start: 0, end: 6 }],
+                    truncated_start: false,
+                    truncated_end: false,
+                },
+                occurrences: 1,
+                tags: Vec::new(),
+            }]
+        );
+        assert!(timeline
+            .search_text("  ", &DateRange::default(), ArchivedContent::Excluded)
+            .is_empty());
     }
 
     #[test]
-    fn intern_tag_rejects_invalid_input() {
+    fn search_text_matches_other_forms_of_a_word() {
         let mut timeline = Timeline::default();
-        assert_eq!(timeline.intern_tag("   "), Err(InternTagError::Empty));
-        assert_eq!(timeline.intern_tag("#"), Err(InternTagError::Invalid));
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let block = |text: &str| TaggedBlock::new(date, text, Vec::new());
+        timeline.tree = SumTree::from_iter(
+            [
+                block("Notes\nThe dog runs ahead of us along the river path every morning.\n"),
+                block("Die Häuser am Fluss sind alt, aber jedes hat einen schönen Garten.\n"),
+                block("ok\n"),
+            ],
+            (),
+        );
+
+        let found =
+            timeline.search_text("running", &DateRange::default(), ArchivedContent::Excluded);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].block_index, 0);
+        assert_eq!(found[0].occurrences, 1);
+        assert_eq!(found[0].snippet.matches.len(), 1);
+        assert!(found[0].snippet.text.starts_with("The dog runs"));
+        // "haus" isn't in "Häuser" as written; German stemming finds it.
+        let found = timeline.search_text("Haus", &DateRange::default(), ArchivedContent::Excluded);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].block_index, 1);
+
+        let languages: Vec<Option<String>> = timeline
+            .list_blocks()
+            .into_iter()
+            .map(|metadata| metadata.language)
+            .collect();
+        assert_eq!(
+            languages,
+            [Some("eng".to_string()), Some("deu".to_string()), None]
+        );
     }
 
     #[test]
-    fn assign_block_tags_updates_block() {
+    fn search_regex_matches_within_blocks_up_to_a_budget() {
         let mut timeline = Timeline::default();
-        timeline
-            .apply_ops(
-                0,
-                &[TextOperation::Insert {
-                    position: 0,
-                    text: "entry one\n".to_string(),
-                }],
-            )
-            .expect("insert first entry");
-        let position = timeline.summary().total_chars;
-        timeline
-            .apply_ops(
-                1,
-                &[TextOperation::Insert {
-                    position,
-                    text: "entry two".to_string(),
-                }],
-            )
-            .expect("insert second entry");
+        let sensitive = timeline
+            .tag_registry_mut()
+            .intern_segment(None, "sensitive");
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let block = |text: &str, tags: Vec<u32>| TaggedBlock::new(date, text, tags);
+        timeline.tree = SumTree::from_iter(
+            [
+                block("TODO: water plants\n", Vec::new()),
+                block(
+                    "notes\nTODO fix roof, urgent! TODO: urgent call\n",
+                    Vec::new(),
+                ),
+                block("TODO urgent secret\n", vec![sensitive]),
+                block("TODOS are urgent too\n", Vec::new()),
+            ],
+            (),
+        );
 
-        let descriptors = timeline
-            .assign_block_tags(
-                1,
-                &["#project:alpha".to_string(), "type:journal".to_string()],
+        let found = timeline
+            .search_regex(
+                r"\bTODO\b.*?urgent",
+                &DateRange::default(),
+                ArchivedContent::Excluded,
             )
-            .expect("assign tags");
-
-        assert_eq!(descriptors.len(), 2);
-        let block = timeline.tree.iter().nth(1).expect("second block");
-        assert_eq!(block.tags.len(), 2);
+            .unwrap();
+        assert!(!found.truncated);
+        assert_eq!(found.matches.len(), 1);
+        let hit = &found.matches[0];
+        assert_eq!(hit.block_index, 1);
+        assert_eq!(hit.excerpt, "TODO fix roof, urgent! TODO: urgent call");
+        assert_eq!(hit.occurrences, 2);
+        assert_eq!(hit.snippet.matches.len(), 2);
+
+        assert!(
+            timeline
+                .search_regex(
+                    "(?i)todos",
+                    &DateRange::default(),
+                    ArchivedContent::Excluded
+                )
+                .unwrap()
+                .matches
+                .len()
+                == 1
+        );
+        assert!(timeline
+            .search_regex("z*", &DateRange::default(), ArchivedContent::Excluded)
+            .unwrap()
+            .matches
+            .is_empty());
+        assert!(matches!(
+            timeline.search_regex(
+                "[unclosed",
+                &DateRange::default(),
+                ArchivedContent::Excluded
+            ),
+            Err(RegexSearchError::Invalid(_))
+        ));
+
+        let partial = timeline
+            .search_regex_within("TODO", &DateRange::default(), ArchivedContent::Excluded, 20)
+            .unwrap();
+        assert!(partial.truncated);
+        assert_eq!(partial.matches.len(), 1);
     }
 
     #[test]
-    fn assign_block_tags_rejects_invalid_index() {
+    fn frozen_ranges_persist_in_snapshot() {
         let mut timeline = Timeline::default();
-        let error = timeline.assign_block_tags(0, &[]).unwrap_err();
-        assert_eq!(error, AssignBlockTagsError::InvalidBlock { index: 0 });
+        let range = DateRange::new(NaiveDate::from_ymd_opt(2020, 1, 1), None);
+        timeline.freeze_range(range);
+        timeline.freeze_range(range);
+
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("timeline.json");
+        timeline.save_to_path(&path).expect("save timeline");
+        let mut loaded = Timeline::load_from_path(&path).expect("load timeline");
+
+        assert_eq!(loaded.frozen_ranges(), &[range]);
+        assert!(loaded.unfreeze_range(&range));
+        assert!(loaded.frozen_ranges().is_empty());
     }
 
     #[test]
-    fn list_blocks_returns_offsets() {
+    fn render_masks_sensitive_blocks_and_descendants() {
         let mut timeline = Timeline::default();
-        timeline
-            .apply_ops(
-                0,
-                &[TextOperation::Insert {
-                    position: 0,
-                    text: "alpha\n".to_string(),
-                }],
-            )
-            .expect("insert first block");
-        let position = timeline.summary().total_chars;
-        timeline
-            .apply_ops(
-                1,
-                &[TextOperation::Insert {
-                    position,
-                    text: "beta".to_string(),
-                }],
-            )
-            .expect("insert second block");
+        let health = timeline
+            .tag_registry_mut()
+            .intern_colon_path("sensitive:health")
+            .expect("intern tag");
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        timeline.tree = SumTree::from_iter(
+            [
+                TaggedBlock::new(date, "Open\n", Vec::new()),
+                TaggedBlock::new(date, "Private\n", vec![health]),
+            ],
+            (),
+        );
 
-        let blocks = timeline.list_blocks();
-        assert_eq!(blocks.len(), 2);
-        assert_eq!(blocks[0].start_offset, 0);
-        assert_eq!(blocks[0].end_offset, 6);
-        assert_eq!(blocks[1].start_offset, blocks[0].end_offset);
-        assert_eq!(blocks[1].end_offset, blocks[1].start_offset + 4);
+        let masked = timeline.render(SensitiveContent::Masked, ArchivedContent::Excluded);
+        assert_eq!(
+            masked,
+            "Open\n\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\n"
+        );
+        assert_eq!(masked.chars().count(), timeline.content().chars().count());
+        assert_eq!(
+            timeline.render_date(date, SensitiveContent::Masked, ArchivedContent::Excluded),
+            Some(masked)
+        );
+        assert_eq!(timeline.content(), "Open\nPrivate\n");
     }
 
     #[test]
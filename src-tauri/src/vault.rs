@@ -0,0 +1,643 @@
+//! Helpers for reading note vaults, shared by the importer and the in-app
+//! import so both map directories to the same tags and dates.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc, Weekday};
+use serde::Serialize;
+
+use crate::encoding;
+use crate::obsidian::DailyNotesSettings;
+use crate::timeline::{BlockSource, TagRegistry, TaggedBlock};
+
+const JOURNAL_DIR: &str = "journal";
+
+/// Proposed tag tree for an import, derived from the vault's `projects/`
+/// directory layout.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportPreview {
+    pub journal_notes: usize,
+    pub project_tags: TagMappingNode,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TagMappingNode {
+    /// Normalized tag segment, e.g. `sightline`.
+    pub name: String,
+    /// Full tag path, e.g. `#project:sightline`.
+    pub tag: String,
+    /// Directories (relative to `projects/`) that map onto this tag. More than
+    /// one entry means several folders will be merged into the same tag.
+    pub source_dirs: Vec<String>,
+    /// Notes directly inside the mapped directories.
+    pub note_count: usize,
+    pub children: Vec<TagMappingNode>,
+}
+
+pub fn preview_import(source: &Path) -> io::Result<ImportPreview> {
+    let (journal_dir, _) = journal_layout(source)?;
+    let journal_notes = count_notes(&source.join(journal_dir))?;
+
+    let mut project_tags = TagMappingNode {
+        name: "project".to_string(),
+        tag: "#project".to_string(),
+        ..TagMappingNode::default()
+    };
+    collect_mappings(&source.join("projects"), "", &mut project_tags)?;
+
+    Ok(ImportPreview {
+        journal_notes,
+        project_tags,
+    })
+}
+
+/// Counts markdown notes in `dir` and its subdirectories, matching the
+/// importer's recursive journal walk.
+fn count_notes(dir: &Path) -> io::Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            count += count_notes(&path)?;
+        } else if is_markdown(&path) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn collect_mappings(dir: &Path, relative: &str, node: &mut TagMappingNode) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_file() {
+            if is_markdown(&path) {
+                node.note_count += 1;
+            }
+            continue;
+        }
+
+        let Some(dir_name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        let child_relative = if relative.is_empty() {
+            dir_name.to_string()
+        } else {
+            format!("{relative}/{dir_name}")
+        };
+
+        // Folders whose names normalize to nothing don't produce a tag; their
+        // notes attach to the nearest tagged ancestor, as in the importer.
+        let Some(segment) = normalize_tag_segment(&dir_name) else {
+            collect_mappings(&path, &child_relative, node)?;
+            continue;
+        };
+
+        let index = match node.children.iter().position(|child| child.name == segment) {
+            Some(index) => index,
+            None => {
+                node.children.push(TagMappingNode {
+                    tag: format!("{}:{segment}", node.tag),
+                    name: segment,
+                    ..TagMappingNode::default()
+                });
+                node.children.len() - 1
+            }
+        };
+
+        let child = &mut node.children[index];
+        child.source_dirs.push(child_relative.clone());
+        collect_mappings(&path, &child_relative, child)?;
+    }
+
+    node.children.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(())
+}
+
+/// Notes read by [`read_vault`], in journal-then-projects order.
+#[derive(Debug, Default)]
+pub struct VaultNotes {
+    pub blocks: Vec<TaggedBlock>,
+    /// Journal notes skipped because no date could be read from their
+    /// frontmatter or path, relative to the vault.
+    pub undated: Vec<PathBuf>,
+}
+
+/// The vault's journal folder and the date formats its note names use:
+/// those from its Obsidian daily-notes settings when it has them, else
+/// `journal` and the built-in formats only.
+pub fn journal_layout(source: &Path) -> io::Result<(PathBuf, Vec<String>)> {
+    let settings = DailyNotesSettings::load(source)?;
+    let dir = settings
+        .as_ref()
+        .and_then(DailyNotesSettings::folder)
+        .unwrap_or(JOURNAL_DIR);
+    let formats = settings
+        .as_ref()
+        .and_then(DailyNotesSettings::file_name_format)
+        .into_iter()
+        .collect();
+    Ok((PathBuf::from(dir), formats))
+}
+
+/// Reads the journal (see [`journal_layout`]) and `projects/` the way the
+/// importer does by default, interning tags into `registry`. Notes are
+/// decoded as in [`encoding::read_text`] and dated by the first of
+/// [`DEFAULT_DATE_KEYS`] in their frontmatter when they have one. Journal
+/// notes otherwise take their file-name or path date and get `#type:journal`
+/// (or `#type:weekly-note` / `#type:monthly-note`); project notes fall back
+/// to a file-name date, then their modification date, and get
+/// `#type:project-note` and a `#project:...` tag per folder. The CLI's
+/// filters, tag rules, git dates, and attachment copying are not applied.
+pub fn read_vault(source: &Path, registry: &mut TagRegistry) -> io::Result<VaultNotes> {
+    let mut notes = VaultNotes::default();
+
+    let (journal, date_formats) = journal_layout(source)?;
+    let journal_dir = source.join(&journal);
+    for path in markdown_files(&journal_dir)? {
+        let relative = path.strip_prefix(&journal_dir).unwrap_or(&path);
+        let stem = relative
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default();
+        let text = encoding::read_text(&path)?.text;
+        let named = parse_journal_period(stem, &date_formats);
+        let period = named.map_or(JournalPeriod::Day, |(_, period)| period);
+        let Some(date) = frontmatter_date(&text, &DEFAULT_DATE_KEYS)
+            .map(|(date, _)| date)
+            .or(named.map(|(date, _)| date))
+            .or_else(|| infer_date_from_path(relative))
+        else {
+            notes.undated.push(journal.join(relative));
+            continue;
+        };
+        let journal_tag = registry
+            .intern_path(["type", period.type_tag()])
+            .expect("non-empty tag path");
+
+        notes.blocks.push(TaggedBlock {
+            source: Some(BlockSource::file(journal.join(relative), &text)),
+            ..TaggedBlock::new(date, text, vec![journal_tag])
+        });
+    }
+
+    let projects_dir = source.join("projects");
+    let project_root_tag = registry.intern_segment(None, "project");
+    let project_note_tag = registry
+        .intern_path(["type", "project-note"])
+        .expect("non-empty tag path");
+    for path in markdown_files(&projects_dir)? {
+        let relative = path.strip_prefix(&projects_dir).unwrap_or(&path);
+        let mut tags = vec![project_root_tag, project_note_tag];
+        let mut parent_tag = project_root_tag;
+        for component in relative.parent().into_iter().flat_map(Path::components) {
+            if let Component::Normal(name) = component {
+                if let Some(segment) = normalize_tag_segment(&name.to_string_lossy()) {
+                    parent_tag = registry.intern_segment(Some(parent_tag), &segment);
+                    tags.push(parent_tag);
+                }
+            }
+        }
+        tags.sort_unstable();
+        tags.dedup();
+
+        let text = encoding::read_text(&path)?.text;
+        let named = relative
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .and_then(|stem| parse_journal_date(stem, &[]));
+        let dated = frontmatter_date(&text, &DEFAULT_DATE_KEYS).map(|(date, _)| date);
+        let date = match dated.or(named) {
+            Some(date) => date,
+            None => DateTime::<Utc>::from(fs::metadata(&path)?.modified()?).date_naive(),
+        };
+        notes.blocks.push(TaggedBlock {
+            source: Some(BlockSource::file(
+                Path::new("projects").join(relative),
+                &text,
+            )),
+            ..TaggedBlock::new(date, text, tags)
+        });
+    }
+
+    Ok(notes)
+}
+
+/// Markdown files under `dir`, sorted. Hidden entries such as `.obsidian`
+/// are skipped.
+fn markdown_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(markdown_files(&path)?);
+        } else if is_markdown(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// How long a journal note covers, read from its file name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalPeriod {
+    Day,
+    /// An ISO week such as `2024-W21`, dated on its Monday.
+    Week,
+    /// A month such as `2024-05`, dated on its first day.
+    Month,
+}
+
+impl JournalPeriod {
+    /// The `type:` tag segment for notes covering this period.
+    pub fn type_tag(self) -> &'static str {
+        match self {
+            Self::Day => "journal",
+            Self::Week => "weekly-note",
+            Self::Month => "monthly-note",
+        }
+    }
+}
+
+/// Parses a journal file stem, trying `preferred_formats` (chrono syntax)
+/// before the built-in formats. Weekly (`2024-W21`) and monthly
+/// (`2024-05`) notes are placed on the first day of their period.
+pub fn parse_journal_date(name: &str, preferred_formats: &[String]) -> Option<NaiveDate> {
+    parse_journal_period(name, preferred_formats).map(|(date, _)| date)
+}
+
+/// Like [`parse_journal_date`], also saying whether the stem names a day,
+/// an ISO week, or a month.
+pub fn parse_journal_period(
+    name: &str,
+    preferred_formats: &[String],
+) -> Option<(NaiveDate, JournalPeriod)> {
+    let trimmed = name.trim();
+    let normalized = trimmed.trim_matches('.');
+    if let Some(period) = parse_week_or_month(normalized) {
+        return Some(period);
+    }
+
+    let candidates = [normalized, &normalized.replace("Sept", "Sep")];
+    let builtin_formats = ["%B %d, %Y", "%b %d, %Y", "%Y-%m-%d"];
+    let formats: Vec<&str> = preferred_formats
+        .iter()
+        .map(String::as_str)
+        .chain(builtin_formats)
+        .collect();
+
+    candidates.iter().find_map(|candidate| {
+        formats.iter().find_map(|format| {
+            NaiveDate::parse_from_str(candidate, format)
+                .ok()
+                .map(|date| (date, JournalPeriod::Day))
+        })
+    })
+}
+
+/// `YYYY-Www` or `YYYY-MM`, which chrono can't parse as dates.
+fn parse_week_or_month(name: &str) -> Option<(NaiveDate, JournalPeriod)> {
+    let (year, rest) = name.split_once('-')?;
+    if year.len() != 4 || !year.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let year = year.parse().ok()?;
+    let two_digits = |digits: &str| {
+        (digits.len() == 2 && digits.bytes().all(|byte| byte.is_ascii_digit()))
+            .then(|| digits.parse::<u32>().ok())
+            .flatten()
+    };
+    match rest.strip_prefix(['W', 'w']) {
+        Some(week) => NaiveDate::from_isoywd_opt(year, two_digits(week)?, Weekday::Mon)
+            .map(|date| (date, JournalPeriod::Week)),
+        None => NaiveDate::from_ymd_opt(year, two_digits(rest)?, 1)
+            .map(|date| (date, JournalPeriod::Month)),
+    }
+}
+
+/// Infers a date for nested journals such as `2024/05/01.md` or
+/// `2024/May/01.md` by joining trailing path components, dropping leading
+/// directories (e.g. `archive/`) until the remainder parses.
+pub fn infer_date_from_path(relative: &Path) -> Option<NaiveDate> {
+    let components: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    (0..components.len().saturating_sub(1)).find_map(|start| {
+        let candidate = components[start..].join("-");
+        ["%Y-%m-%d", "%Y-%B-%d", "%Y-%b-%d"]
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(&candidate, format).ok())
+    })
+}
+
+/// Frontmatter keys tried, in order, when dating vault notes.
+pub const DEFAULT_DATE_KEYS: [&str; 4] = ["date", "created", "day", "journal-date"];
+
+/// Returns the value of `key` in the YAML frontmatter (`---` fenced, at the
+/// very start of `text`), with surrounding quotes removed. Only flat
+/// `key: value` lines are read; keys match case-insensitively.
+pub fn frontmatter_value<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines();
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+
+    lines
+        .take_while(|line| !matches!(line.trim_end(), "---" | "..."))
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim().trim_matches(['"', '\'']))
+        .filter(|value| !value.is_empty())
+}
+
+/// Parses a date written in a note or a table row: the journal's built-in
+/// date formats, or an ISO timestamp cut to its date.
+pub fn parse_date_value(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    parse_journal_date(value, &[]).or_else(|| {
+        value
+            .get(..10)
+            .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+    })
+}
+
+/// The date under the first of `keys` that the note's frontmatter holds
+/// and that parses as a date, with the key it came from.
+pub fn frontmatter_date<'k>(
+    text: &str,
+    keys: &'k [impl AsRef<str>],
+) -> Option<(NaiveDate, &'k str)> {
+    keys.iter().find_map(|key| {
+        let key = key.as_ref();
+        Some((parse_date_value(frontmatter_value(text, key)?)?, key))
+    })
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(|ext| ext.eq_ignore_ascii_case("md"))
+        .unwrap_or(false)
+}
+
+/// Lowercases a directory name and collapses runs of non-alphanumeric
+/// characters into single dashes, e.g. `My Project!` becomes `my-project`.
+pub fn normalize_tag_segment(segment: &str) -> Option<String> {
+    let trimmed = segment.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let mut result = String::with_capacity(trimmed.len());
+    let mut last_dash = false;
+    for ch in trimmed.chars() {
+        let lower = ch.to_ascii_lowercase();
+        if lower.is_ascii_alphanumeric() {
+            result.push(lower);
+            last_dash = false;
+        } else if !last_dash {
+            result.push('-');
+            last_dash = true;
+        }
+    }
+
+    while result.ends_with('-') {
+        result.pop();
+    }
+
+    while result.starts_with('-') {
+        result.remove(0);
+    }
+
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn frontmatter_value_reads_flat_keys() {
+        let text = "---\ntitle: Trip\nCreated: \"2024-03-04T10:15\"\nday:\n---\nday: body\n";
+        assert_eq!(frontmatter_value(text, "created"), Some("2024-03-04T10:15"));
+        assert_eq!(frontmatter_value(text, "title"), Some("Trip"));
+        assert_eq!(frontmatter_value(text, "day"), None);
+        assert_eq!(frontmatter_value("day: 2024-03-04\n", "day"), None);
+    }
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).expect("create parent");
+        fs::write(path, "note").expect("write note");
+    }
+
+    #[test]
+    fn normalize_tag_segment_collapses_punctuation() {
+        assert_eq!(
+            normalize_tag_segment(" My Project! ").as_deref(),
+            Some("my-project")
+        );
+        assert_eq!(normalize_tag_segment("___"), None);
+    }
+
+    #[test]
+    fn weekly_and_monthly_notes_start_on_their_period() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        assert_eq!(
+            parse_journal_period("2024-W21", &[]),
+            Some((date(2024, 5, 20), JournalPeriod::Week))
+        );
+        assert_eq!(
+            parse_journal_period("2021-W01", &[]),
+            Some((date(2021, 1, 4), JournalPeriod::Week))
+        );
+        assert_eq!(
+            parse_journal_period("2024-05", &[]),
+            Some((date(2024, 5, 1), JournalPeriod::Month))
+        );
+        assert_eq!(
+            parse_journal_date("2024-05-07", &[]),
+            Some(date(2024, 5, 7))
+        );
+        assert_eq!(parse_journal_date("2024-W54", &[]), None);
+        assert_eq!(parse_journal_date("2024-13", &[]), None);
+        assert_eq!(parse_journal_date("24-05", &[]), None);
+    }
+
+    #[test]
+    fn infer_date_from_path_rejects_undated_folders() {
+        assert_eq!(infer_date_from_path(Path::new("misc/notes.md")), None);
+        assert_eq!(infer_date_from_path(Path::new("07.md")), None);
+    }
+
+    #[test]
+    fn read_vault_tags_and_dates_notes_like_the_importer() {
+        let dir = tempdir().expect("tempdir");
+        let vault = dir.path();
+        touch(&vault.join("journal/March 3, 2025.md"));
+        touch(&vault.join("journal/2024/May/01.md"));
+        touch(&vault.join("journal/2024-W21.md"));
+        touch(&vault.join("journal/ideas.md"));
+        touch(&vault.join("projects/Work/Q3/plan.md"));
+        touch(&vault.join("projects/.obsidian/workspace.md"));
+
+        let mut registry = TagRegistry::new();
+        let notes = read_vault(vault, &mut registry).expect("read vault");
+
+        assert_eq!(notes.undated, vec![PathBuf::from("journal/ideas.md")]);
+        let dates: Vec<_> = notes.blocks.iter().map(|block| block.date).collect();
+        assert_eq!(
+            dates[..3],
+            [
+                NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 20).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+            ]
+        );
+        assert_eq!(
+            registry.full_name(notes.blocks[1].tags[0]).as_deref(),
+            Some("type:weekly-note")
+        );
+        assert_eq!(notes.blocks.len(), 4);
+        assert_eq!(
+            notes.blocks[3].source,
+            Some(BlockSource::file("projects/Work/Q3/plan.md", "note"))
+        );
+        let mut names: Vec<_> = notes.blocks[3]
+            .tags
+            .iter()
+            .filter_map(|&id| registry.full_name(id))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "project",
+                "project:work",
+                "project:work:q3",
+                "type:project-note"
+            ]
+        );
+    }
+
+    #[test]
+    fn read_vault_reads_frontmatter_dates_and_legacy_encodings() {
+        let dir = tempdir().expect("tempdir");
+        let vault = dir.path();
+        touch(&vault.join("journal/ideas.md"));
+        fs::write(
+            vault.join("journal/ideas.md"),
+            "---\ndate: 2024-02-03\n---\nIdeas\n",
+        )
+        .expect("write note");
+        touch(&vault.join("projects/Home/caf\u{e9}.md"));
+        fs::write(
+            vault.join("projects/Home/caf\u{e9}.md"),
+            b"---\ncreated: 2023-11-05T08:00\n---\nCaf\xe9\n",
+        )
+        .expect("write note");
+
+        let mut registry = TagRegistry::new();
+        let notes = read_vault(vault, &mut registry).expect("read vault");
+
+        assert!(notes.undated.is_empty());
+        let dated: Vec<_> = notes
+            .blocks
+            .iter()
+            .map(|block| (block.date, block.text.as_str()))
+            .collect();
+        assert_eq!(
+            dated,
+            vec![
+                (
+                    NaiveDate::from_ymd_opt(2024, 2, 3).unwrap(),
+                    "---\ndate: 2024-02-03\n---\nIdeas\n"
+                ),
+                (
+                    NaiveDate::from_ymd_opt(2023, 11, 5).unwrap(),
+                    "---\ncreated: 2023-11-05T08:00\n---\nCaf\u{e9}\n"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_vault_follows_obsidian_daily_notes_settings() {
+        let dir = tempdir().expect("tempdir");
+        let vault = dir.path();
+        fs::create_dir_all(vault.join(".obsidian")).expect("create settings dir");
+        fs::write(
+            vault.join(".obsidian/daily-notes.json"),
+            r#"{"format": "DD.MM.YYYY", "folder": "Daily"}"#,
+        )
+        .expect("write settings");
+        touch(&vault.join("Daily/03.04.2025.md"));
+        touch(&vault.join("journal/2024-01-01.md"));
+        fs::create_dir_all(vault.join("projects")).expect("create projects");
+
+        assert_eq!(preview_import(vault).expect("preview").journal_notes, 1);
+        let mut registry = TagRegistry::new();
+        let notes = read_vault(vault, &mut registry).expect("read vault");
+        assert_eq!(notes.blocks.len(), 1);
+        assert_eq!(
+            notes.blocks[0].date,
+            NaiveDate::from_ymd_opt(2025, 4, 3).unwrap()
+        );
+        assert_eq!(
+            notes.blocks[0].source,
+            Some(BlockSource::file("Daily/03.04.2025.md", "note"))
+        );
+    }
+
+    #[test]
+    fn preview_import_maps_directories_to_tags() {
+        let dir = tempdir().expect("tempdir");
+        let vault = dir.path();
+        touch(&vault.join("journal/2024-01-01.md"));
+        touch(&vault.join("journal/2024/01/02.md"));
+        touch(&vault.join("projects/Sightline/Plan.md"));
+        touch(&vault.join("projects/sightline/Ideas.md"));
+        touch(&vault.join("projects/Sightline/Importer/Notes.md"));
+        touch(&vault.join("projects/Home/todo.txt"));
+
+        let preview = preview_import(vault).expect("preview");
+
+        assert_eq!(preview.journal_notes, 2);
+        let tags: Vec<_> = preview
+            .project_tags
+            .children
+            .iter()
+            .map(|node| node.tag.as_str())
+            .collect();
+        assert_eq!(tags, vec!["#project:home", "#project:sightline"]);
+
+        let sightline = &preview.project_tags.children[1];
+        assert_eq!(sightline.source_dirs, vec!["Sightline", "sightline"]);
+        assert_eq!(sightline.note_count, 2);
+        assert_eq!(sightline.children[0].tag, "#project:sightline:importer");
+        assert_eq!(preview.project_tags.children[0].note_count, 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Write-ahead log of edits, kept beside the timeline snapshot. Edits are
+//! appended as they are applied instead of rewriting the whole snapshot;
+//! loading replays whatever the snapshot doesn't include yet, and saving a
+//! snapshot empties the log.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::TextOperation;
+
+/// Once the log is this large it is compacted into a snapshot.
+pub const COMPACT_BYTES: u64 = 1024 * 1024;
+
+/// One applied edit, exactly as it changed the document.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Timeline version after the edit.
+    pub version: u64,
+    /// Date given to inserted text.
+    pub date: NaiveDate,
+    /// When the edit was made. Missing from logs written before edits were
+    /// timed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<DateTime<Utc>>,
+    /// The ops applied, including any snippet expansions, so replaying
+    /// them needs no further rewriting.
+    pub ops: Vec<TextOperation>,
+}
+
+/// The log for the snapshot at `snapshot`: the same path with `.wal`
+/// appended.
+pub fn log_path(snapshot: &Path) -> PathBuf {
+    let mut path = snapshot.as_os_str().to_owned();
+    path.push(".wal");
+    PathBuf::from(path)
+}
+
+/// Appends `entry` to the log and returns the log's new size in bytes. The
+/// entry is on disk when this returns.
+pub fn append(path: &Path, entry: &LogEntry) -> io::Result<u64> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(file.metadata()?.len())
+}
+
+/// Entries in the log, oldest first. A missing log is empty. Reading stops
+/// at the first line that doesn't parse, which is what a crash partway
+/// through an append leaves behind.
+pub fn read(path: &Path) -> io::Result<Vec<LogEntry>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => {
+                tracing::warn!(%err, path = %path.display(), "ignoring truncated log entry");
+                break;
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Empties the log once a snapshot includes everything in it, or when the
+/// snapshot is replaced by one its edits don't apply to.
+pub fn clear(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn entries_round_trip_and_a_torn_tail_is_dropped() {
+        let dir = tempdir().unwrap();
+        let path = log_path(&dir.path().join("timeline.json"));
+        assert!(path.ends_with("timeline.json.wal"));
+        assert_eq!(read(&path).unwrap(), Vec::new());
+
+        let entry = LogEntry {
+            version: 3,
+            date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            at: None,
+            ops: vec![TextOperation::Insert {
+                position: 0,
+                text: "Hello".to_string(),
+            }],
+        };
+        let size = append(&path, &entry).unwrap();
+        assert_eq!(size, fs::metadata(&path).unwrap().len());
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"version\": 4, \"da").unwrap();
+        assert_eq!(read(&path).unwrap(), vec![entry]);
+
+        clear(&path).unwrap();
+        clear(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
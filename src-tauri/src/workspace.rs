@@ -0,0 +1,308 @@
+//! Workspaces are the default timeline plus each named profile. Most
+//! commands only touch the active one; global search fans out to all.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{cmp, fs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::timeline::{ArchivedContent, DateRange, TextMatch, Timeline};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Workspace {
+    /// `None` for the default workspace.
+    pub profile: Option<String>,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WorkspaceMatch {
+    pub workspace: Option<String>,
+    #[serde(flatten)]
+    pub hit: TextMatch,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    DateAsc,
+    DateDesc,
+    /// Most occurrences of the query first, newest first among equals.
+    Relevance,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchGroupBy {
+    Day,
+    /// A match appears under each of its block's tags; untagged matches
+    /// form a group without a key.
+    Tag,
+    #[default]
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SearchGroup {
+    /// The day (`YYYY-MM-DD`) or tag name; `None` for the single group when
+    /// not grouping, and for untagged matches.
+    pub key: Option<String>,
+    pub matches: Vec<WorkspaceMatch>,
+}
+
+/// Lists the default workspace and every profile under `root/profiles`
+/// that has a saved timeline, sorted by profile name.
+pub fn discover(root: &Path) -> io::Result<Vec<Workspace>> {
+    let mut workspaces = vec![Workspace {
+        profile: None,
+        path: root.join("timeline.json"),
+    }];
+
+    let profiles_dir = root.join("profiles");
+    let entries = match fs::read_dir(&profiles_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(workspaces),
+        Err(err) => return Err(err),
+    };
+
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path().join("timeline.json");
+        if path.is_file() {
+            profiles.push(Workspace {
+                profile: Some(entry.file_name().to_string_lossy().into_owned()),
+                path,
+            });
+        }
+    }
+    profiles.sort_by(|a, b| a.profile.cmp(&b.profile));
+    workspaces.extend(profiles);
+
+    Ok(workspaces)
+}
+
+/// Searches the active timeline in memory, then loads each other workspace
+/// from disk on demand. Workspaces that fail to load are skipped.
+pub fn search_all(
+    workspaces: &[Workspace],
+    active: &Timeline,
+    active_profile: Option<&str>,
+    active_path: Option<&Path>,
+    query: &str,
+    range: &DateRange,
+    archived: ArchivedContent,
+) -> Vec<WorkspaceMatch> {
+    let tag = |workspace: Option<&str>, hits: Vec<TextMatch>| {
+        let workspace = workspace.map(str::to_string);
+        hits.into_iter().map(move |hit| WorkspaceMatch {
+            workspace: workspace.clone(),
+            hit,
+        })
+    };
+
+    let mut matches: Vec<WorkspaceMatch> =
+        tag(active_profile, active.search_text(query, range, archived)).collect();
+
+    for workspace in workspaces {
+        if Some(workspace.path.as_path()) == active_path
+            || workspace.profile.as_deref() == active_profile
+        {
+            continue;
+        }
+
+        match Timeline::load_from_path(&workspace.path) {
+            Ok(timeline) => {
+                matches.extend(tag(
+                    workspace.profile.as_deref(),
+                    timeline.search_text(query, range, archived),
+                ));
+            }
+            Err(err) => {
+                tracing::warn!(?err, path = %workspace.path.display(), "skipping workspace in search");
+            }
+        }
+    }
+
+    matches
+}
+
+/// Sorts matches in place; ties keep their search order.
+pub fn sort_matches(matches: &mut [WorkspaceMatch], sort: SearchSort) {
+    match sort {
+        SearchSort::DateAsc => matches.sort_by_key(|m| m.hit.date),
+        SearchSort::DateDesc => matches.sort_by_key(|m| cmp::Reverse(m.hit.date)),
+        SearchSort::Relevance => {
+            matches.sort_by_key(|m| (cmp::Reverse(m.hit.occurrences), cmp::Reverse(m.hit.date)))
+        }
+    }
+}
+
+/// Groups matches in the order each group first appears, so groups follow
+/// the sort of their best match.
+pub fn group_matches(matches: Vec<WorkspaceMatch>, group_by: SearchGroupBy) -> Vec<SearchGroup> {
+    if group_by == SearchGroupBy::None {
+        if matches.is_empty() {
+            return Vec::new();
+        }
+        return vec![SearchGroup { key: None, matches }];
+    }
+
+    let mut groups: Vec<SearchGroup> = Vec::new();
+    let mut positions: HashMap<Option<String>, usize> = HashMap::new();
+    for m in matches {
+        let keys = match group_by {
+            SearchGroupBy::Day => vec![Some(m.hit.date.to_string())],
+            SearchGroupBy::Tag if m.hit.tags.is_empty() => vec![None],
+            SearchGroupBy::Tag => m.hit.tags.iter().cloned().map(Some).collect(),
+            SearchGroupBy::None => unreachable!("handled above"),
+        };
+        for key in keys {
+            let position = *positions.entry(key.clone()).or_insert_with(|| {
+                groups.push(SearchGroup {
+                    key,
+                    matches: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            groups[position].matches.push(m.clone());
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::TextOperation;
+    use chrono::{Datelike, NaiveDate};
+    use tempfile::tempdir;
+
+    fn timeline_with(text: &str) -> Timeline {
+        let mut timeline = Timeline::default();
+        timeline
+            .apply_ops(
+                0,
+                &[TextOperation::Insert {
+                    position: 0,
+                    text: text.to_string(),
+                }],
+            )
+            .expect("apply insert");
+        timeline
+    }
+
+    #[test]
+    fn search_all_reports_workspace_provenance() {
+        let dir = tempdir().expect("tempdir");
+        let root = dir.path();
+        timeline_with("Default copy of the plan\n")
+            .save_to_path(root.join("timeline.json"))
+            .expect("save default");
+        timeline_with("Work plan review\n")
+            .save_to_path(root.join("profiles/work/timeline.json"))
+            .expect("save work");
+        fs::create_dir_all(root.join("profiles/empty")).expect("create empty profile");
+
+        let workspaces = discover(root).expect("discover");
+        assert_eq!(workspaces.len(), 2);
+
+        let active = timeline_with("Unsaved plan in memory\n");
+        let matches = search_all(
+            &workspaces,
+            &active,
+            None,
+            Some(&root.join("timeline.json")),
+            "PLAN",
+            &DateRange::default(),
+            ArchivedContent::Excluded,
+        );
+
+        let found: Vec<(Option<&str>, &str)> = matches
+            .iter()
+            .map(|m| (m.workspace.as_deref(), m.hit.excerpt.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (None, "Unsaved plan in memory"),
+                (Some("work"), "Work plan review"),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_sort_and_group_server_side() {
+        let mut timeline = Timeline::default();
+        let tag = |timeline: &mut Timeline, index: usize, tags: &[&str]| {
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            timeline
+                .assign_block_tags(index, &tags)
+                .expect("assign tags");
+        };
+        for (date, text) in [
+            (1, "plan one\n"),
+            (2, "plan plan two\n"),
+            (3, "plan three\n"),
+        ] {
+            let date = NaiveDate::from_ymd_opt(2024, 5, date).unwrap();
+            timeline
+                .insert_on_date(timeline.version(), date, text)
+                .expect("insert");
+        }
+        tag(&mut timeline, 0, &["#home"]);
+        tag(&mut timeline, 2, &["#home", "#work"]);
+
+        let mut matches = search_all(
+            &[],
+            &timeline,
+            None,
+            None,
+            "plan",
+            &DateRange::default(),
+            ArchivedContent::Excluded,
+        );
+        let days = |matches: &[WorkspaceMatch]| -> Vec<u32> {
+            matches.iter().map(|m| m.hit.date.day()).collect()
+        };
+        let from_the_second = DateRange::new(NaiveDate::from_ymd_opt(2024, 5, 2), None);
+        assert_eq!(
+            days(&search_all(
+                &[],
+                &timeline,
+                None,
+                None,
+                "plan",
+                &from_the_second,
+                ArchivedContent::Excluded,
+            )),
+            vec![2, 3]
+        );
+        sort_matches(&mut matches, SearchSort::DateDesc);
+        assert_eq!(days(&matches), vec![3, 2, 1]);
+        sort_matches(&mut matches, SearchSort::Relevance);
+        assert_eq!(days(&matches), vec![2, 3, 1]);
+
+        let groups = group_matches(matches.clone(), SearchGroupBy::Tag);
+        let summary: Vec<(Option<&str>, Vec<u32>)> = groups
+            .iter()
+            .map(|group| (group.key.as_deref(), days(&group.matches)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, vec![2]),
+                (Some("#home"), vec![3, 1]),
+                (Some("#work"), vec![3]),
+            ]
+        );
+
+        let by_day = group_matches(matches.clone(), SearchGroupBy::Day);
+        assert_eq!(by_day[0].key.as_deref(), Some("2024-05-02"));
+        assert_eq!(by_day.len(), 3);
+        assert_eq!(group_matches(matches, SearchGroupBy::None).len(), 1);
+        assert!(group_matches(Vec::new(), SearchGroupBy::None).is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+//! How much was written per day, week or month. Counts come from the
+//! block summaries the tree already keeps, so nothing is re-counted and
+//! the document never has to leave the backend.
+
+use chrono::{Datelike, Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsPeriod {
+    #[default]
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
+}
+
+impl StatsPeriod {
+    /// The first day of the period holding `date`.
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date
+                .checked_sub_days(Days::new(u64::from(date.weekday().num_days_from_monday())))
+                .unwrap_or(date),
+            Self::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PeriodStats {
+    /// First day of the period.
+    pub start: NaiveDate,
+    pub words: usize,
+    pub chars: usize,
+    pub blocks: usize,
+    /// Days in the period with at least one block.
+    pub active_days: usize,
+}
+
+/// A year's writing day by day, for a calendar heatmap. Entry `n` of each
+/// list is day `n` of the year, counting 1 January as 0.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DailyActivity {
+    pub year: i32,
+    pub chars: Vec<usize>,
+    pub blocks: Vec<usize>,
+}
+
+impl DailyActivity {
+    /// Spreads per-day `stats` over `year`, leaving days without any at
+    /// zero. Stats from other years are ignored.
+    pub fn new(year: i32, stats: &[PeriodStats]) -> Self {
+        let days = NaiveDate::from_ymd_opt(year, 12, 31).map_or(0, |last| last.ordinal() as usize);
+        let mut activity = Self {
+            year,
+            chars: vec![0; days],
+            blocks: vec![0; days],
+        };
+        for day in stats.iter().filter(|day| day.start.year() == year) {
+            let at = day.start.ordinal0() as usize;
+            activity.chars[at] += day.chars;
+            activity.blocks[at] += day.blocks;
+        }
+        activity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_start_on_their_first_day() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        // 2024-05-16 is a Thursday.
+        assert_eq!(StatsPeriod::Day.start_of(date(5, 16)), date(5, 16));
+        assert_eq!(StatsPeriod::Week.start_of(date(5, 16)), date(5, 13));
+        assert_eq!(StatsPeriod::Week.start_of(date(5, 13)), date(5, 13));
+        assert_eq!(StatsPeriod::Week.start_of(date(3, 3)), date(2, 26));
+        assert_eq!(StatsPeriod::Month.start_of(date(5, 16)), date(5, 1));
+    }
+
+    #[test]
+    fn daily_activity_covers_every_day_of_the_year() {
+        let day = |start, chars| PeriodStats {
+            start,
+            chars,
+            blocks: 1,
+            ..PeriodStats::default()
+        };
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let stats = [
+            day(date(2024, 1, 1), 10),
+            day(date(2024, 12, 31), 4),
+            day(date(2025, 1, 1), 7),
+        ];
+
+        let leap = DailyActivity::new(2024, &stats);
+        assert_eq!((leap.chars.len(), leap.blocks.len()), (366, 366));
+        assert_eq!((leap.chars[0], leap.chars[365]), (10, 4));
+        assert_eq!(leap.blocks.iter().sum::<usize>(), 2);
+        assert_eq!(DailyActivity::new(2025, &stats).chars.len(), 365);
+    }
+}
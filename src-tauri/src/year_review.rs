@@ -0,0 +1,363 @@
+//! End-of-year review of the timeline: how much was written each month,
+//! the most-used tags and how they moved through the year, the longest
+//! writing streak and the days that stood out. Rendered as a standalone
+//! HTML document.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+
+use crate::digest::escape;
+use crate::streaks::{self, Streak};
+use crate::timeline::{count_words, ArchivedContent, DateRange, SensitiveContent, Timeline};
+
+const TOP_TAG_COUNT: usize = 10;
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct YearReview {
+    pub year: i32,
+    pub entries: usize,
+    pub words: usize,
+    pub active_days: usize,
+    /// January to December.
+    pub months: Vec<MonthStats>,
+    /// Most-used tags by block count, ties broken by name.
+    pub top_tags: Vec<TagTrend>,
+    pub longest_streak: Option<Streak>,
+    pub most_written: Option<NotableDay>,
+    pub most_tagged: Option<NotableDay>,
+    /// Prose about the year supplied by the caller, shown at the top.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub narrative: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MonthStats {
+    pub entries: usize,
+    pub words: usize,
+    pub active_days: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagTrend {
+    pub tag: String,
+    pub blocks: usize,
+    /// Blocks per month, January to December.
+    pub monthly: Vec<usize>,
+    /// The second half of the year against the first: a quarter more or
+    /// less counts as a change.
+    pub trend: Trend,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct NotableDay {
+    pub date: NaiveDate,
+    /// Words written, or distinct tags used, depending on what it is
+    /// notable for.
+    pub count: usize,
+}
+
+#[derive(Default)]
+struct DayTotals {
+    words: usize,
+    tags: BTreeSet<u32>,
+}
+
+/// Reviews `year`. Sensitive blocks are never included, since the review
+/// is meant to be shared.
+pub fn yearly(timeline: &Timeline, year: i32, narrative: Option<String>) -> YearReview {
+    let range = DateRange::new(
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year, 12, 31),
+    );
+    let blocks =
+        timeline.blocks_in_range(&range, SensitiveContent::Masked, ArchivedContent::Excluded);
+    let registry = timeline.tag_registry();
+
+    let mut months = vec![MonthStats::default(); 12];
+    let mut days: BTreeMap<NaiveDate, DayTotals> = BTreeMap::new();
+    let mut tags: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for block in &blocks {
+        let month = block.date.month0() as usize;
+        let words = count_words(&block.text);
+        months[month].entries += 1;
+        months[month].words += words;
+
+        let day = days.entry(block.date).or_default();
+        day.words += words;
+        day.tags.extend(&block.tags);
+        for &tag in &block.tags {
+            if let Some(name) = registry.full_name(tag) {
+                tags.entry(name).or_insert_with(|| vec![0; 12])[month] += 1;
+            }
+        }
+    }
+    for date in days.keys() {
+        months[date.month0() as usize].active_days += 1;
+    }
+
+    let mut top_tags: Vec<TagTrend> = tags
+        .into_iter()
+        .map(|(tag, monthly)| TagTrend {
+            tag,
+            blocks: monthly.iter().sum(),
+            trend: trend(&monthly),
+            monthly,
+        })
+        .collect();
+    top_tags.sort_by(|a, b| b.blocks.cmp(&a.blocks).then_with(|| a.tag.cmp(&b.tag)));
+    top_tags.truncate(TOP_TAG_COUNT);
+
+    // Ties go to the earliest day.
+    let notable = |count: fn(&DayTotals) -> usize| {
+        days.iter()
+            .map(|(date, totals)| NotableDay {
+                date: *date,
+                count: count(totals),
+            })
+            .filter(|day| day.count > 0)
+            .reduce(|best, day| if day.count > best.count { day } else { best })
+    };
+
+    YearReview {
+        year,
+        entries: blocks.len(),
+        words: months.iter().map(|month| month.words).sum(),
+        active_days: days.len(),
+        months,
+        top_tags,
+        longest_streak: streaks::longest(days.keys().copied()),
+        most_written: notable(|totals| totals.words),
+        most_tagged: notable(|totals| totals.tags.len()),
+        narrative: narrative.filter(|text| !text.trim().is_empty()),
+    }
+}
+
+fn trend(monthly: &[usize]) -> Trend {
+    let (first, second) = monthly.split_at(6);
+    let (first, second) = (first.iter().sum::<usize>(), second.iter().sum::<usize>());
+    if second * 4 > first * 5 {
+        Trend::Rising
+    } else if second * 5 < first * 4 {
+        Trend::Falling
+    } else {
+        Trend::Steady
+    }
+}
+
+impl YearReview {
+    pub fn title(&self) -> String {
+        format!("{} in review", self.year)
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+        html.push_str(&format!(
+            "<title>{}</title></head><body>\n",
+            escape(&self.title())
+        ));
+        html.push_str(&format!("<h1>{}</h1>\n", escape(&self.title())));
+        if let Some(narrative) = &self.narrative {
+            for paragraph in narrative.split("\n\n").map(str::trim) {
+                if !paragraph.is_empty() {
+                    html.push_str(&format!("<p>{}</p>\n", escape(paragraph)));
+                }
+            }
+        }
+        html.push_str(&format!(
+            "<p>{} entries, {} words, {} days written.</p>\n",
+            self.entries, self.words, self.active_days
+        ));
+
+        html.push_str("<h2>By month</h2>\n<table>\n");
+        html.push_str("<tr><th>Month</th><th>Entries</th><th>Words</th><th>Days</th></tr>\n");
+        for (name, month) in MONTH_NAMES.iter().zip(&self.months) {
+            html.push_str(&format!(
+                "<tr><td>{name}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                month.entries, month.words, month.active_days
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.top_tags.is_empty() {
+            html.push_str("<h2>Top tags</h2>\n<ul>\n");
+            for tag in &self.top_tags {
+                let trend = match tag.trend {
+                    Trend::Rising => "rising",
+                    Trend::Steady => "steady",
+                    Trend::Falling => "falling",
+                };
+                html.push_str(&format!(
+                    "<li>#{} ({}, {trend})</li>\n",
+                    escape(&tag.tag),
+                    tag.blocks
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        let mut notable = Vec::new();
+        if let Some(streak) = &self.longest_streak {
+            notable.push(format!(
+                "Longest streak: {} days, {} to {}",
+                streak.days,
+                streak.start.format("%b %-d"),
+                streak.end.format("%b %-d")
+            ));
+        }
+        if let Some(day) = &self.most_written {
+            notable.push(format!(
+                "Most written: {} ({} words)",
+                day.date.format("%a %b %-d"),
+                day.count
+            ));
+        }
+        if let Some(day) = &self.most_tagged {
+            notable.push(format!(
+                "Most tagged: {} ({} tags)",
+                day.date.format("%a %b %-d"),
+                day.count
+            ));
+        }
+        if !notable.is_empty() {
+            html.push_str("<h2>Notable days</h2>\n<ul>\n");
+            for line in notable {
+                html.push_str(&format!("<li>{line}</li>\n"));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    pub fn write_html(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(path, self.to_html())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline() -> Timeline {
+        let snapshot = serde_json::json!({
+            "version": 1,
+            "blocks": [
+                {"date": "2024-12-31", "text": "last year\n", "tags": [1]},
+                {"date": "2025-01-01", "text": "New year plans\n", "tags": [1]},
+                {"date": "2025-01-02", "text": "gym\n", "tags": [2]},
+                {"date": "2025-01-03", "text": "long day at <work> today\n", "tags": [1, 2]},
+                {"date": "2025-07-10", "text": "gym again\n", "tags": [2]},
+                {"date": "2025-08-01", "text": "gym\n", "tags": [2]},
+                {"date": "2025-09-01", "text": "secret diary entry\n", "tags": [3]},
+                {"date": "2025-10-05", "text": "gym\n", "tags": [2]}
+            ],
+            "tag_registry": [
+                {"id": 1, "name": "work", "parent_id": null},
+                {"id": 2, "name": "fitness", "parent_id": null},
+                {"id": 3, "name": "sensitive", "parent_id": null}
+            ]
+        });
+        Timeline::from_snapshot_json(snapshot.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn yearly_summarizes_months_tags_and_days() {
+        let review = yearly(&timeline(), 2025, None);
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        assert_eq!(
+            (review.entries, review.words, review.active_days),
+            (6, 13, 6)
+        );
+        assert_eq!(
+            review.months[0],
+            MonthStats {
+                entries: 3,
+                words: 9,
+                active_days: 3,
+            }
+        );
+        assert_eq!(review.months[8], MonthStats::default());
+
+        let tags: Vec<_> = review
+            .top_tags
+            .iter()
+            .map(|tag| (tag.tag.as_str(), tag.blocks, tag.trend))
+            .collect();
+        assert_eq!(
+            tags,
+            [("fitness", 5, Trend::Rising), ("work", 2, Trend::Falling)]
+        );
+        assert_eq!(review.top_tags[0].monthly[6], 1);
+
+        assert_eq!(
+            review.longest_streak,
+            Some(Streak {
+                start: date(1, 1),
+                end: date(1, 3),
+                days: 3,
+            })
+        );
+        assert_eq!(
+            review.most_written,
+            Some(NotableDay {
+                date: date(1, 3),
+                count: 5,
+            })
+        );
+        assert_eq!(review.most_tagged.map(|day| day.count), Some(2));
+    }
+
+    #[test]
+    fn html_includes_the_narrative_and_escapes_text() {
+        let review = yearly(
+            &timeline(),
+            2025,
+            Some("A year of <lifting>.\n\nAnd work.".to_string()),
+        );
+        let html = review.to_html();
+        assert!(html.contains("<h1>2025 in review</h1>"));
+        assert!(html.contains("<p>A year of &lt;lifting&gt;.</p>\n<p>And work.</p>"));
+        assert!(html.contains("<tr><td>January</td><td>3</td><td>9</td><td>3</td></tr>"));
+        assert!(html.contains("<li>#fitness (5, rising)</li>"));
+        assert!(html.contains("<li>Longest streak: 3 days, Jan 1 to Jan 3</li>"));
+        assert!(!html.contains("secret"));
+
+        let empty = yearly(&timeline(), 2023, None);
+        assert_eq!(empty.longest_streak, None);
+        assert!(!empty.to_html().contains("Notable days"));
+    }
+}
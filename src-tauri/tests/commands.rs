@@ -2,7 +2,7 @@ use serde_json::{json, Value};
 use std::{
     env, fs,
     path::PathBuf,
-    sync::{Mutex, MutexGuard, OnceLock},
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
 };
 use tauri::{
     test::{get_ipc_response, mock_builder, mock_context, noop_assets, INVOKE_KEY},
@@ -10,6 +10,9 @@ use tauri::{
 };
 use tempfile::{tempdir, TempDir};
 
+use chrono::{DateTime, Duration};
+use sightline_lib::clock::ManualClock;
+use sightline_lib::launch::LaunchOptions;
 use sightline_lib::{commands, AppState};
 
 static ENV_MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
@@ -51,22 +54,114 @@ impl Drop for TimelineEnvGuard {
 fn build_test_app() -> (
     tauri::App<tauri::test::MockRuntime>,
     WebviewWindow<tauri::test::MockRuntime>,
+) {
+    build_test_app_with(AppState::new())
+}
+
+fn build_test_app_with(
+    state: AppState,
+) -> (
+    tauri::App<tauri::test::MockRuntime>,
+    WebviewWindow<tauri::test::MockRuntime>,
 ) {
     let app = mock_builder()
-        .manage(AppState::new())
+        .manage(state)
         .invoke_handler(tauri::generate_handler![
             commands::entry_count,
+            commands::word_count,
             commands::handle_edit,
+            commands::acquire_edit_lock,
+            commands::release_edit_lock,
             commands::get_full_document,
+            commands::read_stream,
             commands::get_document_snapshot,
+            commands::undo,
+            commands::redo,
             commands::get_log_for_date,
             commands::search_prefix,
             commands::search_infix,
+            commands::search_regex,
+            commands::search_tags_query,
             commands::autocomplete_tag,
+            commands::suggest_block_tags,
+            commands::related_blocks,
             commands::intern_tag,
+            commands::intern_tags,
             commands::assign_block_tags,
+            commands::archive_block,
+            commands::list_tasks,
+            commands::toggle_task,
+            commands::tasks_due_before,
+            commands::overdue_tasks,
+            commands::split_block,
+            commands::compact_timeline,
+            commands::reparent_tag,
+            commands::rename_tag,
+            commands::delete_tag,
+            commands::get_persistence_policy,
+            commands::set_persistence_policy,
+            commands::preview_registry_migration,
+            commands::commit_registry_migration,
             commands::list_tags,
-            commands::list_blocks
+            commands::get_tag_tree,
+            commands::offset_to_point,
+            commands::point_to_offset,
+            commands::list_blocks,
+            commands::preview_import,
+            commands::import_vault,
+            commands::list_frozen_ranges,
+            commands::freeze_range,
+            commands::unfreeze_range,
+            commands::has_sensitive_passphrase,
+            commands::set_sensitive_passphrase,
+            commands::unlock_sensitive,
+            commands::lock_sensitive,
+            commands::list_snippets,
+            commands::save_snippet,
+            commands::delete_snippet,
+            commands::set_snippet_expansion,
+            commands::set_fetch_link_titles,
+            commands::set_offline_mode,
+            commands::smart_paste,
+            commands::set_now_page,
+            commands::set_daily_note,
+            commands::set_export_schedule,
+            commands::get_export_schedule_status,
+            commands::start_day,
+            commands::set_important_dates,
+            commands::get_important_dates,
+            commands::check_day_rollover,
+            commands::publish_now_page,
+            commands::get_pending_jobs,
+            commands::get_perf_metrics,
+            commands::expand_preview,
+            commands::get_day_properties,
+            commands::get_day_index,
+            commands::writing_stats,
+            commands::get_streaks,
+            commands::daily_activity,
+            commands::rebuild_day_index,
+            commands::reparse_metrics,
+            commands::list_metric_patterns,
+            commands::set_metric_patterns,
+            commands::search_all_workspaces,
+            commands::get_search_history,
+            commands::clear_search_history,
+            commands::export_flashcards,
+            commands::write_weekly_digest,
+            commands::set_digest_schedule,
+            commands::get_digest_schedule_status,
+            commands::generate_year_review,
+            commands::export_bundle,
+            commands::export_markdown,
+            commands::export_jsonl,
+            commands::export_ical,
+            commands::import_bundle,
+            commands::gc_attachments,
+            commands::get_thumbnail,
+            commands::ingest_dropped_files,
+            commands::attach_file,
+            commands::paste_image
         ])
         .build(mock_context(noop_assets()))
         .expect("failed to build app");
@@ -83,6 +178,15 @@ fn invoke_command(
     command: &str,
     payload: Value,
 ) -> Value {
+    try_invoke_command(webview, command, payload).expect("command invocation failed")
+}
+
+/// Like [`invoke_command`], returning the error a command fails with.
+fn try_invoke_command(
+    webview: &WebviewWindow<tauri::test::MockRuntime>,
+    command: &str,
+    payload: Value,
+) -> Result<Value, Value> {
     let response = get_ipc_response(
         webview,
         tauri::webview::InvokeRequest {
@@ -94,12 +198,11 @@ fn invoke_command(
             headers: Default::default(),
             invoke_key: INVOKE_KEY.to_string(),
         },
-    )
-    .expect("command invocation failed");
+    )?;
 
     match response {
         tauri::ipc::InvokeResponseBody::Json(json_string) => {
-            serde_json::from_str(&json_string).expect("deserialize command response")
+            Ok(serde_json::from_str(&json_string).expect("deserialize command response"))
         }
         tauri::ipc::InvokeResponseBody::Raw(bytes) => {
             panic!("unexpected raw response: {bytes:?}")
@@ -128,6 +231,521 @@ fn handle_edit_returns_ok_and_updates_document() {
     assert_eq!(document, Value::String("Hello".into()));
 }
 
+#[test]
+fn handle_edit_accepts_utf16_offsets() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let edit = |version: u64, position: usize, text: &str| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": position, "text": text}],
+            "offsets": "utf16",
+        }})
+    };
+
+    invoke_command(&webview, "handle_edit", edit(0, 0, "🌱 sprout"));
+    // "🌱" is two UTF-16 code units but one character.
+    let response = invoke_command(&webview, "handle_edit", edit(1, 2, "!"));
+    assert_eq!(response, json!({"status": "ok", "new_version": 2}));
+
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, Value::String("🌱! sprout".into()));
+}
+
+#[test]
+fn handle_edit_refuses_utf16_offsets_past_masked_blocks() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-01-01", "text": "🔑 code\n", "tags": [1]},
+            {"date": "2024-01-01", "text": "Plan\n", "tags": []}
+        ],
+        "tag_registry": [
+            {"id": 1, "name": "sensitive", "parent_id": null}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+    let (_app, webview) = build_test_app();
+    let edit = |offsets: &str| {
+        json!({"payload": {
+            "base_version": 1,
+            "ops": [{"type": "insert", "position": 7, "text": "!"}],
+            "offsets": offsets,
+        }})
+    };
+
+    // The masked "🔑" is one UTF-16 unit in the editor but two in the
+    // real text, so offset 7 would land inside the sensitive block.
+    let refused = try_invoke_command(&webview, "handle_edit", edit("utf16")).expect_err("utf16");
+    assert!(
+        refused.as_str().unwrap().contains("use character offsets"),
+        "{refused}"
+    );
+    let response = invoke_command(&webview, "handle_edit", edit("chars"));
+    assert_eq!(response, json!({"status": "ok", "new_version": 2}));
+    invoke_command(
+        &webview,
+        "set_sensitive_passphrase",
+        json!({"passphrase": "hunter2"}),
+    );
+    invoke_command(
+        &webview,
+        "unlock_sensitive",
+        json!({"passphrase": "hunter2"}),
+    );
+    let document = invoke_command(
+        &webview,
+        "get_full_document",
+        json!({"includeSensitive": true}),
+    );
+    assert_eq!(document, Value::String("🔑 code\n!Plan\n".into()));
+}
+
+#[test]
+fn archived_blocks_are_hidden_from_the_document() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let insert = |version: u64, text: &str| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": 0, "text": text}],
+        }})
+    };
+    invoke_command(&webview, "handle_edit", insert(0, "keep\n"));
+    invoke_command(&webview, "handle_edit", insert(1, "drop\n"));
+
+    let version = invoke_command(&webview, "archive_block", json!({"blockIndex": 0}));
+    assert_eq!(version, json!(3));
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, Value::String("keep\n".into()));
+
+    // Offsets skip the archived block.
+    invoke_command(&webview, "handle_edit", insert(3, "x"));
+    let everything = invoke_command(
+        &webview,
+        "get_full_document",
+        json!({"includeArchived": true}),
+    );
+    assert_eq!(everything, Value::String("drop\nxkeep\n".into()));
+}
+
+#[test]
+fn tasks_are_listed_and_toggled() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {
+            "base_version": 0,
+            "ops": [{"type": "insert", "position": 0, "text": "- [ ] call Sam\n- [x] pay rent\n"}],
+        }}),
+    );
+
+    let open = invoke_command(&webview, "list_tasks", json!({"filter": {"done": false}}));
+    assert_eq!(open.as_array().map(Vec::len), Some(1));
+    assert_eq!(open[0]["text"], json!("call Sam"));
+    assert_eq!(open[0]["task_index"], json!(0));
+
+    let task = invoke_command(
+        &webview,
+        "toggle_task",
+        json!({"blockId": 0, "taskIndex": 0}),
+    );
+    assert_eq!(task["done"], json!(true));
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(
+        document,
+        Value::String("- [x] call Sam\n- [x] pay rent\n".into())
+    );
+}
+
+#[test]
+fn due_tasks_are_listed_by_date() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {
+            "base_version": 0,
+            "ops": [{"type": "insert", "position": 0, "text":
+                "- [ ] renew @due(2000-01-02)\n- [ ] file @due(2000-01-01)\n- [ ] plan @due(2999-01-01)\n"}],
+        }}),
+    );
+
+    let due = invoke_command(&webview, "tasks_due_before", json!({"date": "2999-01-01"}));
+    assert_eq!(due[0]["due"], json!("2000-01-01"));
+    assert_eq!(due[1]["due"], json!("2000-01-02"));
+    assert_eq!(due.as_array().map(Vec::len), Some(2));
+    let overdue = invoke_command(&webview, "overdue_tasks", json!({}));
+    assert_eq!(overdue, due);
+}
+
+#[test]
+fn offsets_and_points_round_trip() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {
+            "base_version": 0,
+            "ops": [{"type": "insert", "position": 0, "text": "one\ntwo"}],
+        }}),
+    );
+
+    let point = invoke_command(&webview, "offset_to_point", json!({"offset": 5}));
+    assert_eq!(point, json!({"row": 1, "column": 1}));
+    let offset = invoke_command(&webview, "point_to_offset", json!({"point": point}));
+    assert_eq!(offset, json!(5));
+}
+
+#[test]
+fn edits_inside_another_writers_lock_are_refused() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let edit = |version: u64, position: usize, text: &str, token: Option<&Value>| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": position, "text": text}],
+            "lock_token": token,
+        }})
+    };
+
+    invoke_command(&webview, "handle_edit", edit(0, 0, "Notes: ", None));
+    let lock = invoke_command(
+        &webview,
+        "acquire_edit_lock",
+        json!({"owner": "assistant", "startPosition": 7, "endPosition": 7}),
+    );
+    let token = &lock["token"];
+
+    let refused = invoke_command(&webview, "handle_edit", edit(1, 7, "typed", None));
+    assert_eq!(refused["status"], "locked");
+    assert_eq!(refused["owner"], "assistant");
+    assert_eq!(refused["server_version"], 1);
+
+    let applied = invoke_command(&webview, "handle_edit", edit(1, 7, "summary", Some(token)));
+    assert_eq!(applied["status"], "ok");
+    assert_eq!(
+        invoke_command(&webview, "release_edit_lock", json!({"token": token})),
+        Value::Bool(true)
+    );
+    let typed = invoke_command(&webview, "handle_edit", edit(2, 10, "typed", None));
+    assert_eq!(typed["status"], "ok");
+}
+
+#[test]
+fn other_changes_inside_another_writers_lock_are_refused() {
+    let env_guard = TimelineEnvGuard::new();
+    let now = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z")
+        .unwrap()
+        .to_utc();
+    let clock = Arc::new(ManualClock::new(now));
+    let state = AppState::with_clock(&LaunchOptions::default(), Arc::clone(&clock).into());
+    let (_app, webview) = build_test_app_with(state);
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 0, "ops": [{"type": "insert", "position": 0, "text": "- [ ] call\nmore\n"}]}}),
+    );
+    let lock = invoke_command(
+        &webview,
+        "acquire_edit_lock",
+        json!({"owner": "assistant", "startPosition": 0, "endPosition": 10}),
+    );
+    // Holds the end of the document, where the next day's blocks go.
+    let end_lock = invoke_command(
+        &webview,
+        "acquire_edit_lock",
+        json!({"owner": "assistant", "startPosition": 16, "endPosition": 16}),
+    );
+
+    let dropped = env_guard.path().with_file_name("photo.png");
+    fs::write(&dropped, b"png").expect("write dropped file");
+    let vault = env_guard.path().with_file_name("vault");
+    fs::create_dir_all(vault.join("journal")).expect("create journal");
+    fs::write(vault.join("journal/2024-03-05.md"), "Imported #vault\n").expect("write journal");
+    invoke_command(
+        &webview,
+        "set_daily_note",
+        json!({"template": "## {{date}}"}),
+    );
+    clock.advance(Duration::days(1));
+
+    let refusals = [
+        ("toggle_task", json!({"blockId": 0, "taskIndex": 0})),
+        ("split_block", json!({"blockId": 0, "offset": 4})),
+        ("undo", json!({})),
+        ("archive_block", json!({"blockIndex": 0})),
+        (
+            "paste_image",
+            json!({"baseVersion": 1, "data": "iVBORw==", "mime": "image/png", "target": {"offset": 4}}),
+        ),
+        (
+            "ingest_dropped_files",
+            json!({"baseVersion": 1, "paths": [dropped], "target": {"offset": 4}}),
+        ),
+        (
+            "ingest_dropped_files",
+            json!({"baseVersion": 1, "paths": [dropped], "target": {"date": "2024-03-05"}}),
+        ),
+        ("import_vault", json!({"source": vault.to_string_lossy()})),
+        ("start_day", json!({})),
+    ];
+    for (command, args) in refusals {
+        let refused = try_invoke_command(&webview, command, args).expect_err(command);
+        assert!(
+            refused.as_str().unwrap().contains("locked by assistant"),
+            "{command}: {refused}"
+        );
+    }
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, "- [ ] call\nmore\n");
+    let tags = invoke_command(&webview, "list_tags", json!({}));
+    assert!(
+        !tags.to_string().contains("#vault"),
+        "refused import left its tags: {tags}"
+    );
+
+    for lock in [lock, end_lock] {
+        invoke_command(
+            &webview,
+            "release_edit_lock",
+            json!({"token": lock["token"]}),
+        );
+    }
+    let task = invoke_command(
+        &webview,
+        "toggle_task",
+        json!({"blockId": 0, "taskIndex": 0}),
+    );
+    assert_eq!(task["done"], true);
+
+    // Locks move past text pasted before them.
+    invoke_command(
+        &webview,
+        "acquire_edit_lock",
+        json!({"owner": "assistant", "startPosition": 11, "endPosition": 16}),
+    );
+    invoke_command(
+        &webview,
+        "paste_image",
+        json!({"baseVersion": 2, "data": "iVBORw==", "mime": "image/png", "target": {"offset": 0}}),
+    );
+    let typed = invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 3, "ops": [{"type": "insert", "position": 13, "text": "x"}]}}),
+    );
+    assert_eq!(typed["status"], "ok");
+}
+
+#[test]
+fn locks_and_points_use_document_offsets_past_archived_blocks() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let insert = |version: u64, position: usize, text: &str| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": position, "text": text}],
+        }})
+    };
+    invoke_command(&webview, "handle_edit", insert(0, 0, "keep\n"));
+    invoke_command(&webview, "handle_edit", insert(1, 0, "drop\n"));
+    invoke_command(&webview, "archive_block", json!({"blockIndex": 0}));
+
+    let point = invoke_command(&webview, "offset_to_point", json!({"offset": 2}));
+    assert_eq!(point, json!({"row": 0, "column": 2}));
+    let offset = invoke_command(&webview, "point_to_offset", json!({"point": point}));
+    assert_eq!(offset, json!(2));
+
+    let lock = invoke_command(
+        &webview,
+        "acquire_edit_lock",
+        json!({"owner": "assistant", "startPosition": 2, "endPosition": 4}),
+    );
+    assert_eq!(lock["start_position"], json!(2));
+    assert_eq!(lock["end_position"], json!(4));
+
+    let refused = invoke_command(&webview, "handle_edit", insert(3, 3, "x"));
+    assert_eq!(refused["status"], "locked");
+    assert_eq!(refused["start_position"], json!(2));
+    assert_eq!(refused["end_position"], json!(4));
+    let typed = invoke_command(&webview, "handle_edit", insert(3, 0, "x"));
+    assert_eq!(typed["status"], "ok");
+}
+
+#[test]
+fn typed_text_is_dated_by_the_app_clock() {
+    let _env = TimelineEnvGuard::new();
+    let now = DateTime::parse_from_rfc3339("2024-02-29T23:30:00Z")
+        .unwrap()
+        .to_utc();
+    let clock = Arc::new(ManualClock::new(now));
+    let state = AppState::with_clock(&LaunchOptions::default(), Arc::clone(&clock).into());
+    let (_app, webview) = build_test_app_with(state);
+    let edit = |version: u64, position: usize, text: &str| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": position, "text": text}],
+        }})
+    };
+
+    invoke_command(&webview, "handle_edit", edit(0, 0, "Leap day\n"));
+    clock.advance(Duration::hours(1));
+    invoke_command(&webview, "handle_edit", edit(1, 9, "March\n"));
+
+    let leap_day = invoke_command(&webview, "get_log_for_date", json!({"date": "2024-02-29"}));
+    assert_eq!(leap_day, Value::String("Leap day\n".to_string()));
+    let march = invoke_command(&webview, "get_log_for_date", json!({"date": "2024-03-01"}));
+    assert_eq!(march, Value::String("March\n".to_string()));
+}
+
+#[test]
+fn day_rollover_creates_the_daily_note() {
+    let _env = TimelineEnvGuard::new();
+    let now = DateTime::parse_from_rfc3339("2024-03-01T23:59:00Z")
+        .unwrap()
+        .to_utc();
+    let clock = Arc::new(ManualClock::new(now));
+    let state = AppState::with_clock(&LaunchOptions::default(), Arc::clone(&clock).into());
+    let (_app, webview) = build_test_app_with(state);
+
+    invoke_command(&webview, "set_daily_note", json!({"template": "## {date}"}));
+    assert_eq!(
+        invoke_command(&webview, "check_day_rollover", json!({})),
+        Value::Null
+    );
+
+    clock.advance(Duration::minutes(2));
+    let change = invoke_command(&webview, "check_day_rollover", json!({}));
+    assert_eq!(change["previous"], "2024-03-01");
+    assert_eq!(change["today"], "2024-03-02");
+    assert_eq!(change["daily_note"], 0);
+    assert_eq!(
+        invoke_command(&webview, "get_log_for_date", json!({"date": "2024-03-02"})),
+        Value::String("## 2024-03-02\n".to_string())
+    );
+    assert_eq!(
+        invoke_command(&webview, "check_day_rollover", json!({})),
+        Value::Null
+    );
+}
+
+#[test]
+fn starting_the_day_inserts_the_daily_note_once() {
+    let _env = TimelineEnvGuard::new();
+    let now = DateTime::parse_from_rfc3339("2024-03-04T09:00:00Z")
+        .unwrap()
+        .to_utc();
+    let clock = Arc::new(ManualClock::new(now));
+    let state = AppState::with_clock(&LaunchOptions::default(), clock.into());
+    let (_app, webview) = build_test_app_with(state);
+
+    assert_eq!(
+        invoke_command(&webview, "start_day", json!({})),
+        Value::Null
+    );
+    invoke_command(
+        &webview,
+        "set_daily_note",
+        json!({"template": "## {{weekday}}, {{date}}"}),
+    );
+    assert_eq!(invoke_command(&webview, "start_day", json!({})), json!(0));
+    assert_eq!(
+        invoke_command(&webview, "start_day", json!({})),
+        Value::Null
+    );
+    assert_eq!(
+        invoke_command(&webview, "get_log_for_date", json!({"date": "2024-03-04"})),
+        Value::String("## Monday, 2024-03-04\n".to_string())
+    );
+}
+
+#[test]
+fn important_dates_are_listed_for_a_range() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    let dates = json!({
+        "dates": [{"name": "Mom's birthday", "kind": "birthday", "month": 7, "day": 2, "year": 1960}],
+        "holidays": "en-US",
+    });
+    invoke_command(&webview, "set_important_dates", json!({"dates": dates}));
+    let found = invoke_command(
+        &webview,
+        "get_important_dates",
+        json!({"from": "2024-07-01", "to": "2024-07-07"}),
+    );
+    assert_eq!(
+        found,
+        json!([
+            {"date": "2024-07-02", "name": "Mom's birthday", "kind": "birthday", "years": 64},
+            {"date": "2024-07-04", "name": "Independence Day", "kind": "holiday"},
+        ])
+    );
+}
+
+#[test]
+fn handle_edit_logs_edits_that_survive_a_reload() {
+    let env_guard = TimelineEnvGuard::new();
+    fs::write(env_guard.path(), r#"{"version": 0, "blocks": []}"#).expect("write snapshot");
+    let (_app, webview) = build_test_app();
+
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 0, "ops": [{"type": "insert", "position": 0, "text": "Unsaved"}]}}),
+    );
+
+    let snapshot = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(!snapshot.contains("Unsaved"));
+    assert!(sightline_lib::wal::log_path(env_guard.path()).exists());
+
+    let reloaded = sightline_lib::timeline::Timeline::load_from_path(env_guard.path())
+        .expect("reload timeline");
+    assert_eq!(reloaded.content(), "Unsaved");
+    assert_eq!(reloaded.version(), 1);
+}
+
+#[test]
+fn logged_edits_save_and_back_up_once_a_backup_is_due() {
+    let env_guard = TimelineEnvGuard::new();
+    fs::write(env_guard.path(), r#"{"version": 0, "blocks": []}"#).expect("write snapshot");
+    fs::write(
+        env_guard.path().with_file_name("persistence.json"),
+        r#"{"backup_every_hours": 1}"#,
+    )
+    .expect("write policy");
+    let (_app, webview) = build_test_app();
+    let edit = |base_version: u64, text: &str| {
+        invoke_command(
+            &webview,
+            "handle_edit",
+            json!({"payload": {"base_version": base_version, "ops": [{"type": "insert", "position": 0, "text": text}]}}),
+        )
+    };
+
+    edit(0, "First");
+    let snapshot = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(snapshot.contains("First"));
+    let backups = env_guard.path().with_file_name("backups");
+    assert_eq!(fs::read_dir(&backups).expect("backups").count(), 1);
+
+    // The next backup isn't due for an hour, so this edit is only logged.
+    edit(1, "Second ");
+    let snapshot = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(!snapshot.contains("Second"));
+    assert_eq!(fs::read_dir(&backups).expect("backups").count(), 1);
+}
+
 #[test]
 fn handle_edit_returns_conflict_on_version_mismatch() {
     let _env = TimelineEnvGuard::new();
@@ -162,6 +780,657 @@ fn handle_edit_returns_conflict_on_version_mismatch() {
     );
 }
 
+#[test]
+fn handle_edit_reports_frozen_range() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 2,
+        "blocks": [
+            {"date": "2023-06-01", "text": "Old entry\n", "tags": []}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let (_app, webview) = build_test_app();
+    let ranges = invoke_command(&webview, "freeze_range", json!({"to": "2023-12-31"}));
+    assert_eq!(ranges, json!([{"from": null, "to": "2023-12-31"}]));
+
+    let payload = json!({
+        "payload": {
+            "base_version": 2,
+            "ops": [
+                {"type": "delete", "start_position": 0, "end_position": 3}
+            ]
+        }
+    });
+    let response = invoke_command(&webview, "handle_edit", payload);
+    assert_eq!(
+        response,
+        json!({
+            "status": "frozen",
+            "server_version": 2,
+            "start_position": 0,
+            "end_position": 10
+        })
+    );
+
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, Value::String("Old entry\n".into()));
+}
+
+#[test]
+fn large_documents_are_streamed_in_chunks() {
+    let env_guard = TimelineEnvGuard::new();
+    let text = format!("{}\n", "word ".repeat(100_000));
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [{"date": "2024-01-01", "text": text, "tags": []}]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(&webview, "get_full_document", json!({}));
+    let token = response["stream_token"].as_str().expect("stream token");
+    assert_eq!(response["chunks"], 2);
+
+    let mut json = String::new();
+    loop {
+        let chunk = invoke_command(&webview, "read_stream", json!({"token": token}));
+        json.push_str(chunk["data"].as_str().unwrap());
+        if chunk["done"] == true {
+            break;
+        }
+    }
+    let document: String = serde_json::from_str(&json).expect("parse streamed document");
+    assert_eq!(document, text);
+}
+
+#[test]
+fn sensitive_blocks_are_masked_until_unlocked() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-01-01", "text": "Plan\n", "tags": []},
+            {"date": "2024-01-01", "text": "Secret\n", "tags": [1]}
+        ],
+        "tag_registry": [
+            {"id": 1, "name": "sensitive", "parent_id": null}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let masked = Value::String("Plan\n\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\n".into());
+    let (_app, webview) = build_test_app();
+
+    let document = invoke_command(
+        &webview,
+        "get_full_document",
+        json!({"includeSensitive": true}),
+    );
+    assert_eq!(document, masked);
+
+    let refused = try_invoke_command(
+        &webview,
+        "unlock_sensitive",
+        json!({"passphrase": "open sesame"}),
+    )
+    .unwrap_err();
+    assert_eq!(refused, "no passphrase is set for sensitive blocks");
+    invoke_command(
+        &webview,
+        "set_sensitive_passphrase",
+        json!({"passphrase": "open sesame"}),
+    );
+    assert_eq!(
+        invoke_command(&webview, "has_sensitive_passphrase", json!({})),
+        Value::Bool(true)
+    );
+    let refused = try_invoke_command(&webview, "unlock_sensitive", json!({"passphrase": "guess"}))
+        .unwrap_err();
+    assert_eq!(refused, "wrong passphrase");
+    let document = invoke_command(
+        &webview,
+        "get_full_document",
+        json!({"includeSensitive": true}),
+    );
+    assert_eq!(document, masked);
+
+    invoke_command(
+        &webview,
+        "unlock_sensitive",
+        json!({"passphrase": "open sesame"}),
+    );
+    let document = invoke_command(
+        &webview,
+        "get_full_document",
+        json!({"includeSensitive": true}),
+    );
+    assert_eq!(document, Value::String("Plan\nSecret\n".into()));
+    let log = invoke_command(&webview, "get_log_for_date", json!({"date": "2024-01-01"}));
+    assert_eq!(log, masked);
+
+    invoke_command(&webview, "lock_sensitive", json!({}));
+    let snapshot = invoke_command(
+        &webview,
+        "get_document_snapshot",
+        json!({"includeSensitive": true}),
+    );
+    assert_eq!(snapshot["content"], masked);
+}
+
+#[test]
+fn reparse_metrics_populates_day_properties() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-04-01", "text": "sleep: 7.5h\nran 5km\n", "tags": []},
+            {"date": "2024-04-02", "text": "weight: 80kg\n", "tags": []}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let (_app, webview) = build_test_app();
+    let properties = invoke_command(
+        &webview,
+        "reparse_metrics",
+        json!({"from": "2024-04-01", "to": "2024-04-01"}),
+    );
+    assert_eq!(
+        properties,
+        json!({"2024-04-01": {"distance_km": 5.0, "sleep_hours": 7.5}})
+    );
+
+    let properties = invoke_command(&webview, "get_day_properties", json!({}));
+    assert_eq!(
+        properties,
+        json!({"2024-04-01": {"distance_km": 5.0, "sleep_hours": 7.5}})
+    );
+}
+
+#[test]
+fn export_then_import_bundle_restores_timeline() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 4,
+        "blocks": [{"date": "2024-04-01", "text": "Bundled day\n", "tags": []}],
+        "snippets": [{"trigger": ":mtg", "expansion": "Meeting"}],
+        "offline_mode": true
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+    let assets = env_guard.path().with_file_name("assets");
+    fs::create_dir_all(&assets).expect("create assets");
+    fs::write(assets.join("photo.png"), b"png").expect("write attachment");
+
+    let bundle_path = env_guard.path().with_file_name("export.sightline");
+    let (_app, webview) = build_test_app();
+    let manifest = invoke_command(
+        &webview,
+        "export_bundle",
+        json!({"path": bundle_path.to_string_lossy()}),
+    );
+    assert_eq!(manifest["schema_version"], 1);
+    assert_eq!(manifest["attachments"], 1);
+
+    let replaced = json!({
+        "version": 1,
+        "blocks": [{"date": "2024-03-01", "text": "Replaced day\n", "tags": []}]
+    });
+    fs::write(env_guard.path(), replaced.to_string()).expect("write replaced timeline");
+    fs::remove_dir_all(&assets).expect("remove assets");
+
+    let (_app, webview) = build_test_app();
+    assert!(!sightline_lib::network::offline_mode());
+    invoke_command(
+        &webview,
+        "import_bundle",
+        json!({"path": bundle_path.to_string_lossy()}),
+    );
+    // The imported settings take effect, offline mode included.
+    assert!(sightline_lib::network::offline_mode());
+    invoke_command(&webview, "set_offline_mode", json!({"enabled": false}));
+    let document = invoke_command(&webview, "get_document_snapshot", json!({}));
+    assert_eq!(document["content"], "Bundled day\n");
+    assert_eq!(document["version"], 4);
+    let snippets = invoke_command(&webview, "list_snippets", json!({}));
+    assert_eq!(
+        snippets,
+        json!([{"trigger": ":mtg", "expansion": "Meeting"}])
+    );
+    assert_eq!(
+        fs::read(assets.join("photo.png")).expect("read attachment"),
+        b"png"
+    );
+    // The timeline the import replaced was backed up first.
+    let backups: Vec<_> = fs::read_dir(env_guard.path().with_file_name("backups"))
+        .expect("backups")
+        .map(|entry| entry.expect("backup").path())
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert!(fs::read_to_string(&backups[0])
+        .expect("read backup")
+        .contains("Replaced day"));
+}
+
+#[test]
+fn ingest_dropped_files_links_copies_on_requested_day() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 2,
+        "blocks": [
+            {"date": "2024-04-01", "text": "Monday\n", "tags": []},
+            {"date": "2024-04-03", "text": "Wednesday\n", "tags": []}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+    let dropped = env_guard.path().with_file_name("photo.png");
+    fs::write(&dropped, b"png").expect("write dropped file");
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(
+        &webview,
+        "ingest_dropped_files",
+        json!({"baseVersion": 2, "paths": [dropped, dropped], "target": {"date": "2024-04-02"}}),
+    );
+    assert_eq!(response["new_version"], 3);
+    assert_eq!(response["block"]["date"], "2024-04-02");
+    assert_eq!(response["block"]["start_offset"], 7);
+    assert_eq!(response["attachments"][1]["id"], "photo-1.png");
+    assert_eq!(response["attachments"][1]["size"], 3);
+
+    let document = invoke_command(&webview, "get_document_snapshot", json!({}));
+    assert_eq!(
+        document["content"],
+        "Monday\n![photo.png](<assets/photo.png>)\n![photo.png](<assets/photo-1.png>)\nWednesday\n"
+    );
+    let assets = env_guard.path().with_file_name("assets");
+    assert_eq!(
+        fs::read(assets.join("photo-1.png")).expect("read copy"),
+        b"png"
+    );
+}
+
+#[test]
+fn ingest_dropped_files_links_at_a_caret_offset() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 0, "ops": [{"type": "insert", "position": 0, "text": "See \nhere\n"}]}}),
+    );
+    let dropped = env_guard.path().with_file_name("photo.png");
+    fs::write(&dropped, b"png").expect("write dropped file");
+
+    let response = invoke_command(
+        &webview,
+        "ingest_dropped_files",
+        json!({"baseVersion": 1, "paths": [dropped], "target": {"offset": 4}}),
+    );
+    assert_eq!(response["new_version"], 2);
+    assert_eq!(response["block"]["start_offset"], 4);
+
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, "See ![photo.png](<assets/photo.png>)\n\nhere\n");
+}
+
+#[test]
+fn ingest_dropped_files_leaves_no_copies_when_the_insert_fails() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [{"date": "2023-06-01", "text": "Old entry\n", "tags": []}]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+    let dropped = env_guard.path().with_file_name("photo.png");
+    fs::write(&dropped, b"png").expect("write dropped file");
+
+    let (_app, webview) = build_test_app();
+    let drop_on = |version: u64, target: Value| {
+        try_invoke_command(
+            &webview,
+            "ingest_dropped_files",
+            json!({"baseVersion": version, "paths": [&dropped], "target": target}),
+        )
+    };
+    assert!(drop_on(0, json!({"date": "2023-06-02"})).is_err());
+    assert!(drop_on(1, json!({"offset": 99})).is_err());
+
+    invoke_command(&webview, "freeze_range", json!({"to": "2023-12-31"}));
+    let frozen = drop_on(1, json!({"date": "2023-06-02"})).expect_err("frozen date");
+    assert!(frozen.as_str().unwrap().contains("frozen"), "{frozen}");
+
+    let assets = env_guard.path().with_file_name("assets");
+    let copies = fs::read_dir(&assets).map_or(0, |entries| entries.count());
+    assert_eq!(copies, 0);
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, "Old entry\n");
+}
+
+#[test]
+fn attach_file_links_at_the_end_of_the_block() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-04-01", "text": "Receipts", "tags": []},
+            {"date": "2024-04-03", "text": "Wednesday\n", "tags": []}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+    let file = env_guard.path().with_file_name("receipt.pdf");
+    fs::write(&file, b"pdf").expect("write file");
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(&webview, "attach_file", json!({"blockId": 0, "path": file}));
+    assert_eq!(response["new_version"], 2);
+    assert_eq!(response["block"]["date"], "2024-04-01");
+    assert_eq!(response["block"]["attachments"], json!(["receipt.pdf"]));
+    assert_eq!(response["attachments"][0]["size"], 3);
+
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(
+        document,
+        "Receipts\n[receipt.pdf](<assets/receipt.pdf>)\nWednesday\n"
+    );
+    let report = invoke_command(&webview, "gc_attachments", json!({"dryRun": true}));
+    assert_eq!(report["referenced"], 1);
+}
+
+#[test]
+fn paste_image_stores_bytes_and_links_at_caret() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 0, "ops": [{"type": "insert", "position": 0, "text": "See  here"}]}}),
+    );
+
+    let id = invoke_command(
+        &webview,
+        "paste_image",
+        json!({"baseVersion": 1, "data": "iVBORw==", "mime": "image/png", "target": {"offset": 4}}),
+    );
+    let id = id.as_str().expect("attachment id");
+    assert!(id.starts_with("pasted-") && id.ends_with(".png"));
+
+    let document = invoke_command(&webview, "get_document_snapshot", json!({}));
+    assert_eq!(
+        document["content"],
+        format!("See ![{id}](<assets/{id}>) here")
+    );
+    let stored = env_guard.path().with_file_name("assets").join(id);
+    assert_eq!(
+        fs::read(stored).expect("read pasted image"),
+        [137, 80, 78, 71]
+    );
+}
+
+#[test]
+fn paste_image_rejects_stale_versions_and_bad_data_without_storing() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let paste = |version: u64, data: &str| {
+        try_invoke_command(
+            &webview,
+            "paste_image",
+            json!({"baseVersion": version, "data": data, "mime": "image/png", "target": {"offset": 0}}),
+        )
+    };
+    assert!(paste(0, "not base64!").is_err());
+    assert!(paste(1, "iVBORw==").is_err());
+
+    let assets = env_guard.path().with_file_name("assets");
+    let copies = fs::read_dir(&assets).map_or(0, |entries| entries.count());
+    assert_eq!(copies, 0);
+}
+
+#[test]
+fn pasted_images_land_at_document_offsets_past_archived_blocks() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let insert = |version: u64, text: &str| {
+        json!({"payload": {
+            "base_version": version,
+            "ops": [{"type": "insert", "position": 0, "text": text}],
+        }})
+    };
+    invoke_command(&webview, "handle_edit", insert(0, "keep\n"));
+    invoke_command(&webview, "handle_edit", insert(1, "drop\n"));
+    invoke_command(&webview, "archive_block", json!({"blockIndex": 0}));
+
+    let id = invoke_command(
+        &webview,
+        "paste_image",
+        json!({"baseVersion": 3, "data": "iVBORw==", "mime": "image/png", "target": {"offset": 4}}),
+    );
+    let id = id.as_str().expect("attachment id");
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(
+        document,
+        Value::String(format!("keep![{id}](<assets/{id}>)\n"))
+    );
+}
+
+#[test]
+fn smart_paste_leaves_urls_alone_until_link_titles_are_enabled() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    let pasted = invoke_command(
+        &webview,
+        "smart_paste",
+        json!({"text": "https://example.com/docs"}),
+    );
+    assert_eq!(pasted, "https://example.com/docs");
+
+    let enabled = invoke_command(&webview, "set_fetch_link_titles", json!({"enabled": true}));
+    assert_eq!(enabled, true);
+    let pasted = invoke_command(&webview, "smart_paste", json!({"text": "plain words"}));
+    assert_eq!(pasted, "plain words");
+}
+
+#[test]
+fn offline_mode_queues_link_titles_instead_of_fetching() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    invoke_command(&webview, "set_fetch_link_titles", json!({"enabled": true}));
+    let offline = invoke_command(&webview, "set_offline_mode", json!({"enabled": true}));
+    assert_eq!(offline, true);
+    let pasted = invoke_command(
+        &webview,
+        "smart_paste",
+        json!({"text": "https://example.com/docs"}),
+    );
+    assert_eq!(pasted, "https://example.com/docs");
+
+    let pending = invoke_command(&webview, "get_pending_jobs", json!({}));
+    assert_eq!(pending[0]["kind"], "link_title");
+    assert_eq!(pending[0]["url"], "https://example.com/docs");
+
+    let offline = invoke_command(&webview, "set_offline_mode", json!({"enabled": false}));
+    assert_eq!(offline, false);
+}
+
+#[test]
+fn get_perf_metrics_reports_calls_per_command() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    invoke_command(&webview, "list_blocks", json!({}));
+    invoke_command(&webview, "list_blocks", json!({}));
+    invoke_command(&webview, "entry_count", json!({}));
+
+    let metrics = invoke_command(&webview, "get_perf_metrics", json!({}));
+    let commands: Vec<&str> = metrics
+        .as_array()
+        .expect("metrics array")
+        .iter()
+        .map(|metrics| metrics["command"].as_str().unwrap())
+        .collect();
+    assert_eq!(commands, vec!["entry_count", "list_blocks"]);
+    assert_eq!(metrics[1]["latency_ms"]["count"], 2);
+    assert_eq!(metrics[1]["response_bytes"]["sum"], 4);
+    assert_eq!(metrics[1]["errors"], 0);
+}
+
+#[test]
+fn get_pending_jobs_lists_jobs_saved_beside_the_timeline() {
+    let env_guard = TimelineEnvGuard::new();
+    let jobs = json!([{
+        "id": 1,
+        "kind": "link_title",
+        "url": "https://example.com/docs",
+        "created_at": "2024-04-01T08:00:00Z",
+        "next_attempt_at": "2024-04-01T08:00:30Z",
+        "attempts": 1,
+        "last_error": "timed out"
+    }]);
+    fs::write(
+        env_guard.path().with_file_name("jobs.json"),
+        serde_json::to_string_pretty(&jobs).unwrap(),
+    )
+    .expect("write jobs");
+
+    let (_app, webview) = build_test_app();
+    let pending = invoke_command(&webview, "get_pending_jobs", json!({}));
+    assert_eq!(pending, jobs);
+}
+
+#[test]
+fn now_page_is_written_when_configured_and_published() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-04-01", "text": "Training for a half marathon\n", "tags": [1]},
+            {"date": "2024-04-02", "text": "Private errands\n", "tags": []}
+        ],
+        "tag_registry": [{"id": 1, "name": "now", "parent_id": null}]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let path = env_guard.path().with_file_name("now.md");
+    let (_app, webview) = build_test_app();
+    let config = invoke_command(
+        &webview,
+        "set_now_page",
+        json!({"config": {"path": path.to_string_lossy(), "format": "markdown"}}),
+    );
+    assert_eq!(config["format"], "markdown");
+
+    let page = fs::read_to_string(&path).expect("now page written on configure");
+    assert!(page.contains("Training for a half marathon"));
+    assert!(!page.contains("Private errands"));
+
+    let status = invoke_command(&webview, "publish_now_page", json!({}));
+    assert_eq!(status["blocks"], 1);
+    assert_eq!(status["changed"], false);
+    assert_eq!(status["committed"], false);
+}
+
+#[test]
+fn export_flashcards_writes_cards_from_tagged_blocks() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2024-04-01", "text": "perro :: dog\n", "tags": [1]},
+            {"date": "2024-04-02", "text": "gato :: cat\n", "tags": []}
+        ],
+        "tag_registry": [{"id": 1, "name": "flashcard", "parent_id": null}]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let path = env_guard.path().with_file_name("cards.tsv");
+    let (_app, webview) = build_test_app();
+    let summary = invoke_command(
+        &webview,
+        "export_flashcards",
+        json!({"tag": "#flashcard", "path": path.to_string_lossy()}),
+    );
+    assert_eq!(summary, json!({"cards": 1, "blocks_without_cards": 0}));
+    let tsv = fs::read_to_string(&path).expect("read cards");
+    assert!(tsv.ends_with("perro\tdog\tflashcard\n"));
+}
+
+#[test]
+fn import_vault_merges_notes_into_live_timeline() {
+    let env_guard = TimelineEnvGuard::new();
+    let vault = env_guard.path().with_file_name("vault");
+    fs::create_dir_all(vault.join("journal")).expect("create journal");
+    fs::create_dir_all(vault.join("projects/Home")).expect("create projects");
+    fs::write(vault.join("journal/2024-04-02.md"), "Imported day\n").expect("write journal");
+    fs::write(vault.join("journal/2024-04-03.md"), "Existing\n").expect("write duplicate");
+    fs::write(vault.join("journal/someday.md"), "Undated\n").expect("write undated");
+    fs::write(vault.join("projects/Home/todo.md"), "Fix fence\n").expect("write project");
+
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {"base_version": 0, "ops": [{"type": "insert", "position": 0, "text": "Existing\n"}]}}),
+    );
+
+    let summary = invoke_command(
+        &webview,
+        "import_vault",
+        json!({"source": vault.to_string_lossy(), "strategy": "skip"}),
+    );
+    assert_eq!(summary["added"], 2);
+    assert_eq!(summary["duplicates"], 1);
+    assert_eq!(summary["version"], 2);
+    assert_eq!(summary["undated"], json!(["journal/someday.md"]));
+
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    let document = document.as_str().expect("document text");
+    assert!(document.starts_with("Imported day\n"));
+    assert!(document.contains("Existing\n") && document.contains("Fix fence\n"));
+}
+
 #[test]
 fn get_log_for_date_returns_entries_for_requested_day() {
     let env_guard = TimelineEnvGuard::new();
@@ -219,6 +1488,43 @@ fn get_document_snapshot_returns_content_and_version() {
     );
 }
 
+#[test]
+fn undo_and_redo_return_the_new_version() {
+    let env_guard = TimelineEnvGuard::new();
+    let snapshot = json!({
+        "version": 1,
+        "blocks": [
+            {"date": "2025-01-01", "text": "Keep this paragraph", "tags": []}
+        ]
+    });
+    fs::write(
+        env_guard.path(),
+        serde_json::to_string_pretty(&snapshot).unwrap(),
+    )
+    .expect("write snapshot");
+
+    let (_app, webview) = build_test_app();
+    invoke_command(
+        &webview,
+        "handle_edit",
+        json!({"payload": {
+            "base_version": 1,
+            "ops": [{"type": "delete", "start_position": 0, "end_position": 19}]
+        }}),
+    );
+
+    let undone = invoke_command(&webview, "undo", json!({}));
+    assert_eq!(undone, json!(3));
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, "Keep this paragraph");
+    let redone = invoke_command(&webview, "redo", json!({}));
+    assert_eq!(redone, json!(4));
+    let unchanged = invoke_command(&webview, "redo", json!({}));
+    assert_eq!(unchanged, json!(4));
+    let document = invoke_command(&webview, "get_full_document", json!({}));
+    assert_eq!(document, "");
+}
+
 fn write_search_snapshot(path: &PathBuf) {
     let snapshot = json!({
         "version": 1,
@@ -239,6 +1545,53 @@ fn write_search_snapshot(path: &PathBuf) {
     fs::write(path, serde_json::to_string_pretty(&snapshot).unwrap()).expect("write snapshot");
 }
 
+#[test]
+fn export_jsonl_writes_a_header_and_a_line_per_block() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+    let out = tempdir().expect("out dir");
+    let path = out.path().join("backup/timeline.jsonl");
+
+    let (_app, webview) = build_test_app();
+    let written = invoke_command(
+        &webview,
+        "export_jsonl",
+        json!({"path": path.to_string_lossy()}),
+    );
+    assert_eq!(written, 3);
+
+    let contents = fs::read_to_string(&path).expect("read export");
+    let lines: Vec<Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("json line"))
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0]["format"], "sightline-jsonl");
+    assert_eq!(lines[0]["tag_registry"].as_array().map(Vec::len), Some(5));
+    assert_eq!(lines[3]["text"], "Journal entry");
+}
+
+#[test]
+fn export_ical_writes_an_event_per_day() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+    let out = tempdir().expect("out dir");
+    let path = out.path().join("journal.ics");
+
+    let (_app, webview) = build_test_app();
+    let summary = invoke_command(
+        &webview,
+        "export_ical",
+        json!({"path": path.to_string_lossy()}),
+    );
+    assert_eq!(summary, json!({"events": 3, "todos": 0}));
+
+    let calendar = fs::read_to_string(&path).expect("read export");
+    assert_eq!(calendar.matches("BEGIN:VEVENT\r\n").count(), 3);
+    assert!(calendar.contains("DTSTART;VALUE=DATE:20240102\r\nDTEND;VALUE=DATE:20240103\r\n"));
+    assert!(calendar.contains("SUMMARY:Home improvements\r\n"));
+}
+
 #[test]
 fn search_prefix_command_returns_matching_block_ids() {
     let env_guard = TimelineEnvGuard::new();
@@ -247,7 +1600,103 @@ fn search_prefix_command_returns_matching_block_ids() {
     let (_app, webview) = build_test_app();
     let response = invoke_command(&webview, "search_prefix", json!({"query": "#project"}));
 
-    assert_eq!(response, json!([0, 1]));
+    assert_eq!(response["items"], json!([0, 1]));
+    assert_eq!(response["total"], 2);
+
+    let response = invoke_command(
+        &webview,
+        "search_prefix",
+        json!({"query": "#project", "from": "2024-01-02", "to": "2024-01-03"}),
+    );
+    assert_eq!(response["items"], json!([1]));
+
+    let first = invoke_command(
+        &webview,
+        "search_prefix",
+        json!({"query": "#project", "limit": 1}),
+    );
+    assert_eq!(first["items"], json!([0]));
+    assert_eq!(first["next_offset"], 1);
+    let second = invoke_command(
+        &webview,
+        "search_prefix",
+        json!({"query": "#project", "offset": 1, "limit": 1}),
+    );
+    assert_eq!(second["items"], json!([1]));
+    assert_eq!(second["next_offset"], Value::Null);
+    assert_eq!(second["version"], first["version"]);
+}
+
+#[test]
+fn day_index_commands_list_and_rebuild_days() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let days = invoke_command(
+        &webview,
+        "get_day_index",
+        json!({"from": "2024-01-02", "to": "2024-01-02"}),
+    );
+    assert_eq!(
+        days,
+        json!({"2024-01-02": {"blocks": [1], "start": 18, "end": 35, "words": 2}})
+    );
+
+    let rebuilt = invoke_command(&webview, "rebuild_day_index", json!({}));
+    assert_eq!(rebuilt, json!(3));
+}
+
+#[test]
+fn writing_stats_command_groups_by_period() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let days = invoke_command(&webview, "writing_stats", json!({}));
+    assert_eq!(days.as_array().map(Vec::len), Some(3));
+    let weeks = invoke_command(
+        &webview,
+        "writing_stats",
+        json!({"from": "2024-01-02", "period": "week"}),
+    );
+    assert_eq!(
+        weeks,
+        json!([{"start": "2024-01-01", "words": 4, "chars": 30, "blocks": 2, "active_days": 2}])
+    );
+}
+
+#[test]
+fn daily_activity_command_fills_the_year() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let activity = invoke_command(&webview, "daily_activity", json!({"year": 2024}));
+    assert_eq!(activity["year"], 2024);
+    assert_eq!(activity["chars"].as_array().map(Vec::len), Some(366));
+    let chars = activity["chars"].as_array().expect("chars");
+    assert_eq!(chars[..4], [json!(18), json!(17), json!(13), json!(0)]);
+    assert_eq!(activity["blocks"][3], 0);
+}
+
+#[test]
+fn streaks_command_reports_journal_runs() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+    let now = DateTime::parse_from_rfc3339("2024-01-04T09:00:00Z")
+        .unwrap()
+        .to_utc();
+    let state = AppState::with_clock(
+        &LaunchOptions::default(),
+        Arc::new(ManualClock::new(now)).into(),
+    );
+
+    let (_app, webview) = build_test_app_with(state);
+    let streaks = invoke_command(&webview, "get_streaks", json!({}));
+    let only_the_third = json!({"start": "2024-01-03", "end": "2024-01-03", "days": 1});
+    assert_eq!(streaks["current"], only_the_third);
+    assert_eq!(streaks["longest"], only_the_third);
 }
 
 #[test]
@@ -258,7 +1707,43 @@ fn search_infix_command_returns_partial_matches() {
     let (_app, webview) = build_test_app();
     let response = invoke_command(&webview, "search_infix", json!({"query": "sight"}));
 
-    assert_eq!(response, json!([0]));
+    assert_eq!(response["items"], json!([0]));
+}
+
+#[test]
+fn search_tags_query_command_combines_tags() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(
+        &webview,
+        "search_tags_query",
+        json!({"query": "#project NOT #project:home OR #type:journal"}),
+    );
+
+    assert_eq!(response["items"], json!([0, 2]));
+}
+
+#[test]
+fn search_regex_command_returns_matching_blocks() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(
+        &webview,
+        "search_regex",
+        json!({"pattern": r"^\w+ (plan|entry)"}),
+    );
+    assert!(response.get("truncated").is_none());
+    let blocks: Vec<&Value> = response["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| &hit["block_index"])
+        .collect();
+    assert_eq!(blocks, [&json!(0), &json!(2)]);
 }
 
 #[test]
@@ -322,6 +1807,283 @@ fn intern_tag_command_creates_and_persists() {
         .any(|tag| { tag.get("name").and_then(|name| name.as_str()) == Some("deep") }));
 }
 
+#[test]
+fn searches_are_remembered_until_cleared() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+    let (_app, webview) = build_test_app();
+
+    for query in ["journal", "home", "Journal"] {
+        invoke_command(&webview, "search_all_workspaces", json!({"query": query}));
+    }
+    let history = invoke_command(&webview, "get_search_history", json!({}));
+    let queries: Vec<&str> = history
+        .as_array()
+        .expect("history list")
+        .iter()
+        .map(|recent| recent["query"].as_str().expect("query"))
+        .collect();
+    assert_eq!(queries, vec!["Journal", "home"]);
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(!saved.contains("search_history"));
+    let history_file = env_guard.path().with_file_name("search_history.json");
+    let saved = fs::read_to_string(history_file).expect("read search history");
+    assert!(saved.contains("Journal"));
+
+    invoke_command(&webview, "clear_search_history", json!({}));
+    let history = invoke_command(&webview, "get_search_history", json!({}));
+    assert_eq!(history, json!([]));
+}
+
+#[test]
+fn text_search_pages_matches_within_a_date_range() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+    let (_app, webview) = build_test_app();
+
+    let first = invoke_command(
+        &webview,
+        "search_all_workspaces",
+        json!({"query": "n", "limit": 2}),
+    );
+    assert_eq!(first["total"], 3);
+    assert_eq!(first["next_offset"], 2);
+    let dates = |page: &serde_json::Value| -> Vec<String> {
+        page["items"]
+            .as_array()
+            .expect("groups")
+            .iter()
+            .flat_map(|group| group["matches"].as_array().expect("matches").clone())
+            .map(|hit| hit["date"].as_str().expect("date").to_string())
+            .collect()
+    };
+    assert_eq!(dates(&first), vec!["2024-01-01", "2024-01-02"]);
+    let last = invoke_command(
+        &webview,
+        "search_all_workspaces",
+        json!({"query": "n", "offset": 2, "limit": 2}),
+    );
+    assert_eq!(dates(&last), vec!["2024-01-03"]);
+    assert_eq!(last["next_offset"], serde_json::Value::Null);
+
+    let ranged = invoke_command(
+        &webview,
+        "search_all_workspaces",
+        json!({"query": "n", "from": "2024-01-02", "to": "2024-01-02"}),
+    );
+    assert_eq!(dates(&ranged), vec!["2024-01-02"]);
+}
+
+#[test]
+fn intern_tags_reports_each_tag_in_order() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    let response = invoke_command(
+        &webview,
+        "intern_tags",
+        json!({"tags": ["#project:home", "", "type:journal"]}),
+    );
+    let results = response.as_array().expect("result list");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["status"], "ok");
+    assert_eq!(results[0]["tag"]["name"], "#project:home");
+    assert_eq!(
+        results[1],
+        json!({"status": "error", "tag": "", "message": "tag name cannot be empty"})
+    );
+    assert_eq!(results[2]["tag"]["name"], "#type:journal");
+
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(saved.contains("journal"));
+}
+
+#[test]
+fn reparent_tag_moves_a_root_tag_under_another() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let interned = invoke_command(
+        &webview,
+        "intern_tags",
+        json!({"tags": ["#sightline", "#project"]}),
+    );
+    let id = |index: usize| interned[index]["tag"]["id"].clone();
+
+    let tags = invoke_command(
+        &webview,
+        "reparent_tag",
+        json!({"id": id(0), "newParent": id(1)}),
+    );
+    let names: Vec<&str> = tags
+        .as_array()
+        .expect("tag list")
+        .iter()
+        .map(|tag| tag["name"].as_str().expect("tag name"))
+        .collect();
+    assert_eq!(names, vec!["#project", "#project:sightline"]);
+
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(saved.contains("sightline"));
+}
+
+#[test]
+fn export_schedules_queue_a_repeating_export() {
+    let env_guard = TimelineEnvGuard::new();
+    let out = tempdir().expect("out dir");
+    let (_app, webview) = build_test_app();
+
+    let status = invoke_command(&webview, "get_export_schedule_status", json!({}));
+    assert_eq!(
+        status,
+        json!({"schedule": null, "last_run": null, "next_run": null})
+    );
+
+    let schedule = json!({"dir": out.path().to_string_lossy(), "every": "weekly"});
+    let status = invoke_command(
+        &webview,
+        "set_export_schedule",
+        json!({"schedule": schedule}),
+    );
+    assert_eq!(status["schedule"]["every"], "weekly");
+    assert!(status["next_run"].is_string());
+    let jobs = fs::read_to_string(env_guard.path().with_file_name("jobs.json")).expect("read jobs");
+    assert!(jobs.contains("scheduled_export"));
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(saved.contains("export_schedule"));
+
+    let status = invoke_command(&webview, "set_export_schedule", json!({"schedule": null}));
+    assert_eq!(status["next_run"], Value::Null);
+    let jobs = fs::read_to_string(env_guard.path().with_file_name("jobs.json")).expect("read jobs");
+    assert!(!jobs.contains("scheduled_export"));
+}
+
+#[test]
+fn digest_schedules_keep_the_smtp_password_private() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+
+    let schedule = json!({
+        "to": "me@example.com",
+        "method": "smtp",
+        "host": "smtp.example.com",
+        "from": "sightline@example.com",
+        "username": "me",
+        "password": "hunter2"
+    });
+    let status = invoke_command(
+        &webview,
+        "set_digest_schedule",
+        json!({"schedule": schedule}),
+    );
+    assert_eq!(status["schedule"]["host"], "smtp.example.com");
+    assert_eq!(status["schedule"].get("password"), None);
+    assert!(status["next_run"].is_string());
+    let jobs = fs::read_to_string(env_guard.path().with_file_name("jobs.json")).expect("read jobs");
+    assert!(jobs.contains("weekly_digest"));
+    let saved = fs::read_to_string(env_guard.path().with_file_name("digest.json"))
+        .expect("read digest schedule");
+    assert!(saved.contains("hunter2"));
+    let snapshot = fs::read_to_string(env_guard.path()).unwrap_or_default();
+    assert!(!snapshot.contains("hunter2"));
+
+    let status = invoke_command(&webview, "set_digest_schedule", json!({"schedule": null}));
+    assert_eq!(status["next_run"], Value::Null);
+    assert!(!env_guard.path().with_file_name("digest.json").exists());
+}
+
+#[test]
+fn ephemeral_workspaces_save_nothing_until_made_durable() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let policy = invoke_command(&webview, "get_persistence_policy", json!({}));
+    assert_eq!(policy["ephemeral"], false);
+    assert_eq!(policy["write_ahead_log"], true);
+
+    invoke_command(
+        &webview,
+        "set_persistence_policy",
+        json!({"policy": {"ephemeral": true}}),
+    );
+    invoke_command(&webview, "intern_tag", json!({"tag": "#scratch"}));
+    assert!(!env_guard.path().exists());
+    let saved_policy = env_guard.path().with_file_name("persistence.json");
+    assert!(fs::read_to_string(&saved_policy)
+        .expect("read policy")
+        .contains("\"ephemeral\": true"));
+
+    invoke_command(
+        &webview,
+        "set_persistence_policy",
+        json!({"policy": {"write_ahead_log": false}}),
+    );
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(saved.contains("scratch"));
+}
+
+#[test]
+fn delete_tag_cascades_on_request() {
+    let _env = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let interned = invoke_command(
+        &webview,
+        "intern_tags",
+        json!({"tags": ["#work", "#work:meetings"]}),
+    );
+    let work = interned[0]["tag"]["id"].clone();
+    let meetings = interned[1]["tag"]["id"].clone();
+
+    let deleted = invoke_command(&webview, "delete_tag", json!({"id": meetings}));
+    assert_eq!(deleted, json!({"tags": 1, "blocks": 0}));
+    let deleted = invoke_command(&webview, "delete_tag", json!({"id": work, "cascade": true}));
+    assert_eq!(deleted["tags"], 1);
+}
+
+#[test]
+fn rename_tag_keeps_the_id() {
+    let env_guard = TimelineEnvGuard::new();
+    let (_app, webview) = build_test_app();
+    let interned = invoke_command(&webview, "intern_tags", json!({"tags": ["#proj"]}));
+    let id = interned[0]["tag"]["id"].clone();
+
+    let tags = invoke_command(
+        &webview,
+        "rename_tag",
+        json!({"id": id, "newName": "project"}),
+    );
+    assert_eq!(tags[0]["id"], id);
+    assert_eq!(tags[0]["name"], "#project");
+
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(saved.contains("project"));
+}
+
+#[test]
+fn legacy_registry_migration_is_previewed_then_committed() {
+    let env_guard = TimelineEnvGuard::new();
+    let legacy_snapshot = json!({
+        "version": 1,
+        "blocks": [{"date": "2024-08-01", "text": "Plan\n", "tags": [5]}],
+        "tag_registry": {"5": "sightline"}
+    });
+    fs::write(env_guard.path(), legacy_snapshot.to_string()).expect("write legacy snapshot");
+
+    let (_app, webview) = build_test_app();
+    let preview = invoke_command(&webview, "preview_registry_migration", json!({}));
+    assert_eq!(preview[0]["legacy_id"], 5);
+    assert_eq!(preview[0]["path"], "sightline");
+
+    let committed = invoke_command(
+        &webview,
+        "commit_registry_migration",
+        json!({"paths": {"5": "project:sightline"}}),
+    );
+    assert_eq!(committed[0]["path"], "project:sightline");
+    assert!(invoke_command(&webview, "preview_registry_migration", json!({})).is_null());
+
+    let saved = fs::read_to_string(env_guard.path()).expect("read snapshot");
+    assert!(!saved.contains("legacy_tag_registry"));
+}
+
 #[test]
 fn assign_block_tags_command_updates_block() {
     let env_guard = TimelineEnvGuard::new();
@@ -388,6 +2150,32 @@ fn list_tags_command_returns_descriptors() {
     assert!(descriptors.iter().all(|d| !d.color.is_empty()));
 }
 
+#[test]
+fn get_tag_tree_command_nests_tags() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(&webview, "get_tag_tree", json!({}));
+
+    let roots = response.as_array().expect("root nodes");
+    let names: Vec<&str> = roots
+        .iter()
+        .filter_map(|node| node["full_name"].as_str())
+        .collect();
+    assert_eq!(names, ["#project", "#type"]);
+    assert_eq!(roots[0]["block_count"], 0);
+    assert_eq!(roots[0]["total_block_count"], 2);
+    let children: Vec<&str> = roots[0]["children"]
+        .as_array()
+        .expect("child nodes")
+        .iter()
+        .filter_map(|node| node["name"].as_str())
+        .collect();
+    assert_eq!(children, ["home", "sightline"]);
+    assert!(roots.iter().all(|node| node["color"].as_str().is_some()));
+}
+
 #[test]
 fn list_blocks_command_returns_ranges() {
     let env_guard = TimelineEnvGuard::new();
@@ -408,10 +2196,54 @@ fn list_blocks_command_returns_ranges() {
         tags: Vec<u32>,
     }
 
-    let blocks: Vec<BlockMetadata> = serde_json::from_value(response).expect("block list");
+    assert_eq!(response["total"], 3);
+    let blocks: Vec<BlockMetadata> =
+        serde_json::from_value(response["items"].clone()).expect("block list");
     assert!(!blocks.is_empty());
     assert_eq!(blocks[0].date, "2024-01-01");
     assert!(blocks
         .windows(2)
         .all(|pair| pair[0].end_offset <= pair[1].start_offset));
 }
+
+#[test]
+fn list_blocks_command_returns_truncated_previews() {
+    let env_guard = TimelineEnvGuard::new();
+    write_search_snapshot(env_guard.path());
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(&webview, "list_blocks", json!({"previewChars": 7}));
+    assert_eq!(response["items"][0]["preview"], "Sightli");
+    assert_eq!(response["items"][0]["is_truncated"], true);
+
+    let plain = invoke_command(&webview, "list_blocks", json!({}));
+    assert!(plain["items"][0].get("preview").is_none());
+
+    let page = invoke_command(&webview, "list_blocks", json!({"offset": 2, "limit": 5}));
+    assert_eq!(page["items"][0]["index"], 2);
+    assert_eq!(page["next_offset"], Value::Null);
+}
+
+#[test]
+fn preview_import_command_returns_tag_tree() {
+    let _env = TimelineEnvGuard::new();
+    let vault = tempdir().expect("vault dir");
+    fs::create_dir_all(vault.path().join("journal")).expect("create journal");
+    fs::create_dir_all(vault.path().join("projects/Sightline")).expect("create project");
+    fs::write(vault.path().join("projects/Sightline/Plan.md"), "plan").expect("write note");
+
+    let (_app, webview) = build_test_app();
+    let response = invoke_command(
+        &webview,
+        "preview_import",
+        json!({"source": vault.path().to_string_lossy()}),
+    );
+
+    assert_eq!(response["journal_notes"], 0);
+    assert_eq!(response["project_tags"]["tag"], "#project");
+    assert_eq!(
+        response["project_tags"]["children"][0]["tag"],
+        "#project:sightline"
+    );
+    assert_eq!(response["project_tags"]["children"][0]["note_count"], 1);
+}
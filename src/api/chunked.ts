@@ -0,0 +1,30 @@
+import type { InvokeFn } from "../sync/TimelineSyncController";
+import type { StreamChunk, StreamToken } from "./types";
+
+function isStreamToken(value: unknown): value is StreamToken {
+  return typeof value === "object" && value !== null && "stream_token" in value;
+}
+
+/**
+ * Invokes a command whose result may come back as a stream token when it is
+ * large, reading the stream to the end and parsing it.
+ */
+export async function invokeChunked<T>(
+  invoke: InvokeFn,
+  command: string,
+  args?: Record<string, unknown>,
+): Promise<T> {
+  const response = await invoke<T | StreamToken>(command, args);
+  if (!isStreamToken(response)) {
+    return response;
+  }
+
+  let json = "";
+  for (;;) {
+    const chunk = await invoke<StreamChunk>("read_stream", { token: response.stream_token });
+    json += chunk.data;
+    if (chunk.done) {
+      return JSON.parse(json) as T;
+    }
+  }
+}
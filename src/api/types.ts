@@ -5,13 +5,155 @@ export type TextOperation =
 export interface EditPayload {
   base_version: number;
   ops: TextOperation[];
+  literal?: boolean;
+  lock_token?: string;
+  /** Unit of the ops' offsets; characters (code points) when omitted. */
+  offsets?: OffsetUnit;
+}
+
+export type OffsetUnit = "chars" | "bytes" | "utf16";
+
+/** A line and column in the timeline, from zero; columns count characters. */
+export interface Point {
+  row: number;
+  column: number;
 }
 
 export type EditResponse =
-  | { status: "ok"; new_version: number }
-  | { status: "conflict"; server_version: number };
+  | { status: "ok"; new_version: number; rewritten?: boolean }
+  | { status: "conflict"; server_version: number }
+  | {
+      status: "frozen";
+      server_version: number;
+      start_position: number;
+      end_position: number;
+    }
+  | {
+      status: "locked";
+      server_version: number;
+      owner: string;
+      start_position: number;
+      end_position: number;
+      retry_after_ms: number;
+    };
 
 export interface DocumentSnapshot {
   content: string;
   version: number;
 }
+
+/** Returned instead of a large result; read it with `read_stream`. */
+export interface StreamToken {
+  stream_token: string;
+  bytes: number;
+  chunks: number;
+}
+
+export interface StreamChunk {
+  data: string;
+  done: boolean;
+}
+
+/** Payload of the `day-changed` event and `check_day_rollover`. */
+export interface DayChange {
+  previous: string;
+  today: string;
+  daily_note: number | null;
+}
+
+export type DateKind = "birthday" | "anniversary" | "holiday" | "other";
+
+export interface ImportantDate {
+  name: string;
+  kind: DateKind;
+  month: number;
+  day: number;
+  year?: number;
+}
+
+export interface ImportantDates {
+  dates?: ImportantDate[];
+  /** Locale whose public holidays are included, e.g. `en-US`. */
+  holidays?: string;
+}
+
+export interface DateOccurrence {
+  date: string;
+  name: string;
+  kind: DateKind;
+  years?: number;
+}
+
+/** One page of a paginated command's results. */
+export interface Page<T> {
+  items: T[];
+  total: number;
+  /** Offset of the next page, or null on the last one. */
+  next_offset: number | null;
+  /** Timeline version the page was read at; restart paging if it changes. */
+  version: number;
+  /** Set when the search stopped early. */
+  truncated?: boolean;
+}
+
+/** A day's entry from `get_day_index`, keyed by `YYYY-MM-DD`. */
+export interface DayEntry {
+  /** Block indexes, in document order. */
+  blocks: number[];
+  start: number;
+  end: number;
+  words: number;
+}
+
+/** A `- [ ]` checkbox line, from `list_tasks` and `toggle_task`. */
+export interface Task {
+  block_id: number;
+  /** Which of the block's tasks this is; pass both to `toggle_task`. */
+  task_index: number;
+  date: string;
+  done: boolean;
+  text: string;
+  /** `YYYY-MM-DD`, from an `@due(...)` on the task's line. */
+  due?: string;
+  /** Offset of the task's line in the document. */
+  offset: number;
+}
+
+export interface TaskFilter {
+  done?: boolean;
+  from?: string;
+  to?: string;
+  include_archived?: boolean;
+}
+
+export type StatsPeriod = "day" | "week" | "month";
+
+/** One period's totals, from `writing_stats`. */
+export interface PeriodStats {
+  /** First day of the period; weeks start on Monday. */
+  start: string;
+  words: number;
+  chars: number;
+  blocks: number;
+  active_days: number;
+}
+
+/** Consecutive days with a `#type:journal` entry. */
+export interface Streak {
+  start: string;
+  end: string;
+  days: number;
+}
+
+/** From `get_streaks`; `current` still counts while today is unwritten. */
+export interface Streaks {
+  current: Streak | null;
+  longest: Streak | null;
+}
+
+/** From `daily_activity`: entry `n` of each list is day `n` of the year. */
+export interface DailyActivity {
+  year: number;
+  chars: number[];
+  blocks: number[];
+}
@@ -6,7 +6,8 @@ import ChatPane, { type ChatPaneProps } from "./ChatPane";
 import { Button } from "./ui/button";
 import DateGutterOverlay from "./DateGutterOverlay";
 import CommandPalette from "./CommandPalette";
-import type { DocumentSnapshot, TextOperation } from "../api/types";
+import type { DocumentSnapshot, Page, TextOperation } from "../api/types";
+import { invokeChunked } from "../api/chunked";
 import TimelineSyncController, {
   type InvokeFn,
 } from "../sync/TimelineSyncController";
@@ -36,6 +37,13 @@ interface BackendBlockMetadata {
     end_offset: number;
     date: string;
     tags?: number[];
+    preview?: string;
+    is_truncated?: boolean;
+    created_at?: string;
+    updated_at?: string;
+    archived?: boolean;
+    due?: string;
+    attachments?: string[];
 }
 
 function mapBackendBlock(descriptor: BackendBlockMetadata): BlockMetadata {
@@ -45,6 +53,11 @@ function mapBackendBlock(descriptor: BackendBlockMetadata): BlockMetadata {
     endOffset: descriptor.end_offset,
     date: descriptor.date,
     tags: descriptor.tags ?? [],
+    createdAt: descriptor.created_at,
+    updatedAt: descriptor.updated_at,
+    archived: descriptor.archived,
+    due: descriptor.due,
+    attachments: descriptor.attachments,
   };
 }
 
@@ -149,9 +162,9 @@ function TimelineWorkspaceInner({
   }, [blocks, currentDate]);
 
   const refreshBlocks = useCallback(() => {
-    invokeFn<BackendBlockMetadata[]>("list_blocks")
-      .then((metadata) => {
-        replaceAllBlocks(metadata.map(mapBackendBlock));
+    invokeChunked<Page<BackendBlockMetadata>>(invokeFn, "list_blocks")
+      .then((page) => {
+        replaceAllBlocks(page.items.map(mapBackendBlock));
       })
       .catch((err) => {
         const message = err instanceof Error ? err.message : String(err);
@@ -13,6 +13,15 @@ export interface BlockMetadata {
   endOffset: number;
   date: string;
   tags: number[];
+  /** RFC 3339 timestamps; missing for blocks older than timestamps. */
+  createdAt?: string;
+  updatedAt?: string;
+  /** Archived blocks are hidden, so their start and end offsets match. */
+  archived?: boolean;
+  /** `YYYY-MM-DD`, from the first `@due(...)` in the block. */
+  due?: string;
+  /** Ids of the attachments the block links to. */
+  attachments?: string[];
 }
 
 interface BlockStoreValue {
@@ -13,6 +13,19 @@ export interface TagDescriptor {
   color: string;
 }
 
+/** One tag of the hierarchy returned by `get_tag_tree`. */
+export interface TagNode {
+  id: number;
+  /** The tag's own segment, without its parents. */
+  name: string;
+  full_name: string;
+  color: string;
+  block_count: number;
+  /** Blocks tagged with this tag or any below it. */
+  total_block_count: number;
+  children: TagNode[];
+}
+
 interface TagStoreValue {
   tags: Map<number, TagDescriptor>;
   replaceAll(descriptors: TagDescriptor[]): void;
@@ -1,7 +1,13 @@
+import { invokeChunked } from "../api/chunked";
 import type { EditResponse, TextOperation } from "../api/types";
 
 export type InvokeFn = <T>(command: string, args?: Record<string, unknown>) => Promise<T>;
 
+/** Retries against a lock wait at least this long, so an expiring lock isn't polled hot. */
+const MIN_LOCK_RETRY_MS = 50;
+
+const sleep = (ms: number) => new Promise<void>((resolve) => setTimeout(resolve, ms));
+
 export interface TimelineSyncControllerOptions {
   invoke: InvokeFn;
   initialVersion?: number;
@@ -43,23 +49,38 @@ export class TimelineSyncController {
   }
 
   private async syncOperations(operations: TextOperation[]): Promise<void> {
-    const payload = {
-      base_version: this.version,
-      ops: operations,
-    };
-
-    const response = await this.invoke<EditResponse>("handle_edit", { payload });
+    let response = await this.sendOperations(operations);
+    // Another writer holds the range. The edit stays at the head of the
+    // queue, so later edits keep their order, and is retried once the
+    // lock should have been released or expired.
+    while (response.status === "locked" && response.server_version === this.version) {
+      await sleep(Math.max(response.retry_after_ms, MIN_LOCK_RETRY_MS));
+      response = await this.sendOperations(operations);
+    }
 
     if (response.status === "ok") {
       this.version = response.new_version;
       this.onEditApplied?.(response.new_version);
+      if (response.rewritten) {
+        // The backend expanded a snippet, so the local view is out of date.
+        const document = await invokeChunked<string>(this.invoke, "get_full_document");
+        this.onConflictResolved?.(document, response.new_version);
+      }
       return;
     }
 
-    const document = await this.invoke<string>("get_full_document");
+    const document = await invokeChunked<string>(this.invoke, "get_full_document");
     this.version = response.server_version;
     this.onConflictResolved?.(document, response.server_version);
   }
+
+  private sendOperations(operations: TextOperation[]): Promise<EditResponse> {
+    const payload = {
+      base_version: this.version,
+      ops: operations,
+    };
+    return this.invoke<EditResponse>("handle_edit", { payload });
+  }
 }
 
 export default TimelineSyncController;
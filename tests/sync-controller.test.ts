@@ -80,4 +80,31 @@ describe("TimelineSyncController", () => {
     expect(controller.getVersion()).toBe(5);
     expect(onConflictResolved).toHaveBeenCalledWith("SERVER_DOCUMENT", 5);
   });
+
+  it("refetches the document when the backend rewrites an edit", async () => {
+    const onConflictResolved = vi.fn();
+
+    const invoke = vi.fn().mockImplementation(async (command: string) => {
+      if (command === "handle_edit") {
+        return { status: "ok", new_version: 4, rewritten: true } as const;
+      }
+
+      if (command === "get_full_document") {
+        return "EXPANDED_DOCUMENT";
+      }
+
+      throw new Error(`unexpected command: ${command}`);
+    });
+
+    const controller = new TimelineSyncController({
+      invoke,
+      initialVersion: 3,
+      onConflictResolved,
+    });
+
+    await controller.handleEditorChange(sampleOps);
+
+    expect(controller.getVersion()).toBe(4);
+    expect(onConflictResolved).toHaveBeenCalledWith("EXPANDED_DOCUMENT", 4);
+  });
 });
@@ -28,15 +28,20 @@ function handleCommonCommands(command: string, args?: Record<string, unknown>) {
 
   if (command === "list_blocks") {
     const length = typeof editorState.content === "string" ? editorState.content.length : 0;
-    return [
-      {
-        index: 0,
-        start_offset: 0,
-        end_offset: length,
-        date: "2024-01-01",
-        tags: [] as number[],
-      },
-    ];
+    return {
+      items: [
+        {
+          index: 0,
+          start_offset: 0,
+          end_offset: length,
+          date: "2024-01-01",
+          tags: [] as number[],
+        },
+      ],
+      total: 1,
+      next_offset: null,
+      version: 0,
+    };
   }
 
   if (command === "intern_tag") {
@@ -0,0 +1,358 @@
+//! Synthetic Obsidian-style vaults for exercising the importer against
+//! large and messy input: dated journal notes in every naming scheme the
+//! importer understands (and a few it doesn't), nested project folders,
+//! frontmatter, inline tags, wikilinks, odd file names and non-ASCII text.
+//! Output depends only on the spec, so a seed reproduces a vault exactly.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VaultSpec {
+    /// Markdown notes to write, split between journal and projects.
+    pub files: usize,
+    /// Deepest project folder nesting.
+    pub depth: usize,
+    pub seed: u64,
+}
+
+impl Default for VaultSpec {
+    fn default() -> Self {
+        Self {
+            files: 200,
+            depth: 3,
+            seed: 1,
+        }
+    }
+}
+
+/// What [`generate`] wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VaultStats {
+    /// Journal notes whose date can be read from their name, path or
+    /// frontmatter.
+    pub dated_journal_notes: usize,
+    /// Journal notes with no date anywhere.
+    pub undated_journal_notes: usize,
+    pub project_notes: usize,
+    /// Files that aren't markdown notes, such as attachments.
+    pub other_files: usize,
+}
+
+impl VaultStats {
+    pub fn notes(&self) -> usize {
+        self.dated_journal_notes + self.undated_journal_notes + self.project_notes
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WORDS: &[&str] = &[
+    "garden",
+    "meeting",
+    "plan",
+    "review",
+    "coffee",
+    "release",
+    "draft",
+    "budget",
+    "roadmap",
+    "walk",
+    "call",
+    "notes",
+    "idea",
+    "bug",
+    "café",
+    "naïve",
+    "Zürich",
+    "façade",
+    "東京",
+    "日記",
+    "Ωmega",
+    "straße",
+    "año",
+    "🌱",
+    "🚀",
+    "résumé",
+    "smörgåsbord",
+    "пример",
+];
+
+const FOLDERS: &[&str] = &[
+    "Sightline",
+    "Q3 Planning",
+    "Ünïcödé",
+    "a.b.c",
+    "[Archive]",
+    "emoji 🚀",
+    "Home & Garden",
+    "2024",
+    "Client — Acme",
+    "notes",
+];
+
+const NOTE_NAMES: &[&str] = &[
+    "Plan",
+    "Meeting notes (draft)",
+    "ÄÖÜ",
+    "100% done",
+    "#hashtag",
+    "semi;colon",
+    "trailing.dots..",
+    "Ideas 💡",
+    "README",
+    "東京 trip",
+];
+
+const TAGS: &[&str] = &[
+    "work",
+    "home",
+    "project/sightline",
+    "health",
+    "reading",
+    "idea",
+];
+
+/// Writes a vault for `spec` under `root`, which is created if missing.
+pub fn generate(root: &Path, spec: &VaultSpec) -> io::Result<VaultStats> {
+    let mut rng = Rng::new(spec.seed);
+    let mut stats = VaultStats::default();
+    let journal = root.join("journal");
+    let projects = root.join("projects");
+    fs::create_dir_all(&journal)?;
+    fs::create_dir_all(&projects)?;
+    write(
+        &root.join(".obsidian/daily-notes.json"),
+        r#"{"folder": "journal", "format": "YYYY-MM-DD"}"#,
+    )?;
+    stats.other_files += 1;
+
+    let journal_notes = spec.files.div_ceil(2);
+    let mut day = days_from_civil(2022, 1, 1);
+    for n in 0..journal_notes {
+        day += 1 + rng.below(3) as i64;
+        let (year, month, date) = civil_from_days(day);
+        let body = note_body(&mut rng, n);
+        let (path, dated) = match rng.below(12) {
+            0..=4 => (
+                journal.join(format!("{year:04}-{month:02}-{date:02}.md")),
+                true,
+            ),
+            5 | 6 => (
+                journal.join(format!("{} {date}, {year}.md", MONTHS[month as usize - 1])),
+                true,
+            ),
+            7 | 8 => (
+                journal.join(format!("{year:04}/{month:02}/{date:02}.md")),
+                true,
+            ),
+            9 => (
+                journal.join(format!("{year:04}-W{:02}.md", 1 + rng.below(52))),
+                true,
+            ),
+            10 => {
+                let path = journal.join(format!("Untitled {n}.md"));
+                let text = format!("---\ndate: {year:04}-{month:02}-{date:02}\n---\n{body}");
+                write(&path, &text)?;
+                stats.dated_journal_notes += 1;
+                continue;
+            }
+            _ => (journal.join(format!("Scratch ✎ {n}.md")), false),
+        };
+        if path.exists() {
+            // Weekly names can repeat; keep the first.
+            continue;
+        }
+        write(&path, &body)?;
+        if dated {
+            stats.dated_journal_notes += 1;
+        } else {
+            stats.undated_journal_notes += 1;
+        }
+    }
+
+    for n in 0..spec.files - journal_notes {
+        let mut dir = projects.clone();
+        for _ in 0..rng.below(spec.depth as u64 + 1) {
+            dir.push(FOLDERS[rng.below(FOLDERS.len() as u64) as usize]);
+        }
+        let name = NOTE_NAMES[rng.below(NOTE_NAMES.len() as u64) as usize];
+        let path = dir.join(format!("{name} {n}.md"));
+        let mut text = String::new();
+        if rng.below(2) == 0 {
+            let (year, month, date) = civil_from_days(day - rng.below(400) as i64);
+            let tag = TAGS[rng.below(TAGS.len() as u64) as usize];
+            text.push_str(&format!(
+                "---\ncreated: {year:04}-{month:02}-{date:02}\ntags: [{tag}]\n---\n"
+            ));
+        }
+        text.push_str(&note_body(&mut rng, spec.files + n));
+        write(&path, &text)?;
+        stats.project_notes += 1;
+
+        if rng.below(10) == 0 {
+            write_bytes(&dir.join(format!("attachment {n}.png")), &PNG_HEADER)?;
+            stats.other_files += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+const PNG_HEADER: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// A few paragraphs of mixed-script text with tags, wikilinks and a task.
+/// `id` goes in a line of its own so no two notes are identical.
+fn note_body(rng: &mut Rng, id: usize) -> String {
+    let mut text = format!("Note {id}\n");
+    for _ in 0..1 + rng.below(4) {
+        let words: Vec<&str> = (0..4 + rng.below(20))
+            .map(|_| WORDS[rng.below(WORDS.len() as u64) as usize])
+            .collect();
+        text.push_str(&words.join(" "));
+        match rng.below(5) {
+            0 => text.push_str(&format!(
+                " #{}",
+                TAGS[rng.below(TAGS.len() as u64) as usize]
+            )),
+            1 => text.push_str(&format!(
+                " [[{}]]",
+                NOTE_NAMES[rng.below(NOTE_NAMES.len() as u64) as usize]
+            )),
+            2 => text.push_str("\n- [ ] follow up"),
+            _ => {}
+        }
+        text.push_str("\n\n");
+    }
+    text
+}
+
+fn write(path: &Path, text: &str) -> io::Result<()> {
+    write_bytes(path, text.as_bytes())
+}
+
+fn write_bytes(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bytes)
+}
+
+/// Default output directory, under the workspace's `target/`.
+pub fn default_out_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/fixture-vault")
+}
+
+/// SplitMix64; plenty for picking names, and needs no dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` after 1970-01-01, as year, month and day.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+        assert_eq!(
+            civil_from_days(days_from_civil(2023, 12, 31) + 1),
+            (2024, 1, 1)
+        );
+    }
+
+    #[test]
+    fn vaults_are_reproducible() {
+        let spec = VaultSpec {
+            files: 60,
+            depth: 4,
+            seed: 7,
+        };
+        let dir = std::env::temp_dir().join(format!("fixture-vault-{}", std::process::id()));
+        let first = generate(&dir.join("a"), &spec).unwrap();
+        let second = generate(&dir.join("b"), &spec).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.project_notes, 30);
+        assert!(first.notes() <= spec.files);
+        assert!(first.undated_journal_notes > 0);
+        assert_eq!(files(&dir.join("a")), files(&dir.join("b")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Every file under `root`, relative to it, with its contents.
+    fn files(root: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut found = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let contents = fs::read(&path).unwrap();
+                    found.push((path.strip_prefix(root).unwrap().to_path_buf(), contents));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+}
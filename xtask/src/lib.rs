@@ -0,0 +1,4 @@
+//! Helpers shared by the `xtask` binary and the crates whose tests and
+//! benchmarks use its generated fixtures.
+
+pub mod fixture_vault;
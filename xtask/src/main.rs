@@ -1,8 +1,10 @@
 use std::error::Error;
+use std::path::PathBuf;
 use std::process;
 
-use clap::Command;
+use clap::{Arg, ArgMatches, Command, value_parser};
 use duct::cmd;
+use xtask::fixture_vault::{self, VaultSpec};
 
 type AnyResult<T> = Result<T, Box<dyn Error>>;
 type StepFn = fn() -> AnyResult<()>;
@@ -30,6 +32,37 @@ fn cli() -> Command {
                 .subcommand(Command::new("ts").about("Run TypeScript tests")),
         )
         .subcommand(Command::new("all").about("Run every lint and test"))
+        .subcommand(
+            Command::new("gen-vault")
+                .about("Generate a messy Obsidian-style vault for importer testing")
+                .arg(
+                    Arg::new("files")
+                        .long("files")
+                        .value_parser(value_parser!(usize))
+                        .default_value("200")
+                        .help("Markdown notes to write"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_parser(value_parser!(usize))
+                        .default_value("3")
+                        .help("Deepest project folder nesting"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1")
+                        .help("Seed; the same seed writes the same vault"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Directory to write to [default: target/fixture-vault]"),
+                ),
+        )
 }
 
 fn main() {
@@ -54,6 +87,7 @@ fn run() -> AnyResult<()> {
             _ => unreachable!(),
         },
         Some(("all", _)) => run_all(),
+        Some(("gen-vault", args)) => run_gen_vault(args),
         _ => unreachable!(),
     }
 }
@@ -114,6 +148,32 @@ fn run_all() -> AnyResult<()> {
     }
 }
 
+fn run_gen_vault(args: &ArgMatches) -> AnyResult<()> {
+    let spec = VaultSpec {
+        files: *args.get_one("files").expect("has default"),
+        depth: *args.get_one("depth").expect("has default"),
+        seed: *args.get_one("seed").expect("has default"),
+    };
+    let out = args
+        .get_one::<PathBuf>("out")
+        .cloned()
+        .unwrap_or_else(fixture_vault::default_out_dir);
+    if out.exists() {
+        return Err(format!("{} already exists; remove it first", out.display()).into());
+    }
+    let stats = fixture_vault::generate(&out, &spec)?;
+    println!(
+        "Wrote {} notes ({} dated journal, {} undated journal, {} project) and {} other files to {}",
+        stats.notes(),
+        stats.dated_journal_notes,
+        stats.undated_journal_notes,
+        stats.project_notes,
+        stats.other_files,
+        out.display()
+    );
+    Ok(())
+}
+
 fn run_cmd(program: &str, args: &[&str]) -> AnyResult<()> {
     println!("> {} {}", program, args.join(" "));
     cmd(program, args).run()?;